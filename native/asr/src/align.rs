@@ -0,0 +1,220 @@
+pub struct TimedWord {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+pub struct AlignedWord {
+    pub text: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub matched: bool,
+}
+
+// Shortest span a word is stretched to so a caption highlight is actually visible.
+const MIN_WORD_MS: i64 = 80;
+
+pub trait WordSpan {
+    fn span_mut(&mut self) -> (&mut i64, &mut i64);
+}
+
+impl WordSpan for TimedWord {
+    fn span_mut(&mut self) -> (&mut i64, &mut i64) {
+        (&mut self.start_ms, &mut self.end_ms)
+    }
+}
+
+impl WordSpan for AlignedWord {
+    fn span_mut(&mut self) -> (&mut i64, &mut i64) {
+        (&mut self.start_ms, &mut self.end_ms)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Step {
+    Diagonal,
+    SkipScript,
+    SkipRecognized,
+}
+
+fn comparable(word: &str) -> String {
+    word.chars()
+        .filter(|ch| ch.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn substitution_cost(script: &str, recognized: &str) -> u32 {
+    if script == recognized {
+        0
+    } else if !script.is_empty()
+        && !recognized.is_empty()
+        && (script.starts_with(recognized) || recognized.starts_with(script))
+    {
+        // Whisper often splits or truncates words ("o'clock" vs "o"), so a
+        // prefix match is cheaper than an unrelated substitution.
+        1
+    } else {
+        2
+    }
+}
+
+// DTW/edit-distance alignment of the script against the recognized words.
+// Script words that only line up with a gap get timings interpolated from
+// their neighbours.
+pub fn align_script(script: &str, recognized: &[TimedWord], total_ms: i64) -> Vec<AlignedWord> {
+    let script_words: Vec<&str> = script.split_whitespace().collect();
+    let n = script_words.len();
+    let m = recognized.len();
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let script_keys: Vec<String> = script_words.iter().map(|word| comparable(word)).collect();
+    let recognized_keys: Vec<String> = recognized
+        .iter()
+        .map(|word| comparable(&word.text))
+        .collect();
+
+    // cost[i][j]: cheapest alignment of the first i script words with the first j recognized words.
+    let width = m + 1;
+    let mut cost = vec![0_u32; (n + 1) * width];
+    let mut steps = vec![Step::Diagonal; (n + 1) * width];
+    for i in 1..=n {
+        cost[i * width] = i as u32;
+        steps[i * width] = Step::SkipScript;
+    }
+    for j in 1..=m {
+        cost[j] = j as u32;
+        steps[j] = Step::SkipRecognized;
+    }
+
+    for i in 1..=n {
+        for j in 1..=m {
+            let diagonal = cost[(i - 1) * width + j - 1]
+                + substitution_cost(&script_keys[i - 1], &recognized_keys[j - 1]);
+            let skip_script = cost[(i - 1) * width + j] + 1;
+            let skip_recognized = cost[i * width + j - 1] + 1;
+
+            let (best, step) = if diagonal <= skip_script && diagonal <= skip_recognized {
+                (diagonal, Step::Diagonal)
+            } else if skip_script <= skip_recognized {
+                (skip_script, Step::SkipScript)
+            } else {
+                (skip_recognized, Step::SkipRecognized)
+            };
+            cost[i * width + j] = best;
+            steps[i * width + j] = step;
+        }
+    }
+
+    let mut matches: Vec<Option<usize>> = vec![None; n];
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        match steps[i * width + j] {
+            Step::Diagonal => {
+                if substitution_cost(&script_keys[i - 1], &recognized_keys[j - 1]) < 2 {
+                    matches[i - 1] = Some(j - 1);
+                }
+                i -= 1;
+                j -= 1;
+            }
+            Step::SkipScript => i -= 1,
+            Step::SkipRecognized => j -= 1,
+        }
+    }
+
+    let mut aligned: Vec<AlignedWord> = script_words
+        .iter()
+        .zip(&matches)
+        .map(|(word, matched)| {
+            let (start_ms, end_ms) = matched
+                .map(|index| (recognized[index].start_ms, recognized[index].end_ms))
+                .unwrap_or((-1, -1));
+            AlignedWord {
+                text: (*word).to_string(),
+                start_ms,
+                end_ms,
+                matched: matched.is_some(),
+            }
+        })
+        .collect();
+
+    interpolate_unmatched(&mut aligned, total_ms.max(0));
+    smooth_word_timings(&mut aligned, total_ms);
+    aligned
+}
+
+fn interpolate_unmatched(words: &mut [AlignedWord], total_ms: i64) {
+    let mut index = 0;
+    while index < words.len() {
+        if words[index].matched {
+            index += 1;
+            continue;
+        }
+
+        let run_start = index;
+        while index < words.len() && !words[index].matched {
+            index += 1;
+        }
+
+        let gap_start = if run_start == 0 {
+            0
+        } else {
+            words[run_start - 1].end_ms
+        };
+        let gap_end = if index < words.len() {
+            words[index].start_ms
+        } else {
+            total_ms
+        }
+        .max(gap_start);
+
+        let run_len = (index - run_start) as i64;
+        let slot = (gap_end - gap_start) / run_len;
+        for (offset, word) in words[run_start..index].iter_mut().enumerate() {
+            word.start_ms = gap_start + slot * offset as i64;
+            word.end_ms = word.start_ms + slot;
+        }
+    }
+}
+
+// Whisper's token timestamps overlap, repeat and collapse to zero length,
+// which makes karaoke-style highlighting jump around. This makes starts
+// non-decreasing, trims each word to end where the next begins, then stretches
+// words to MIN_WORD_MS, pushing later words along and pulling back at the end
+// of the audio. Starts move as little as possible since that is what a
+// highlight keys off.
+pub fn smooth_word_timings<W: WordSpan>(words: &mut [W], total_ms: i64) {
+    let total_ms = total_ms.max(0);
+
+    let mut floor = 0;
+    for word in words.iter_mut() {
+        let (start, end) = word.span_mut();
+        *start = (*start).clamp(floor, total_ms.max(floor));
+        *end = (*end).max(*start);
+        floor = *start;
+    }
+
+    for index in 1..words.len() {
+        let next_start = *words[index].span_mut().0;
+        let (_, end) = words[index - 1].span_mut();
+        *end = (*end).min(next_start);
+    }
+
+    let mut cursor = 0;
+    for word in words.iter_mut() {
+        let (start, end) = word.span_mut();
+        *start = (*start).max(cursor);
+        *end = (*end).max(*start + MIN_WORD_MS);
+        cursor = *end;
+    }
+
+    let mut limit = total_ms;
+    for word in words.iter_mut().rev() {
+        let (start, end) = word.span_mut();
+        *end = (*end).min(limit);
+        *start = (*start).min(*end - MIN_WORD_MS).max(0).min(*end);
+        limit = *start;
+    }
+}
@@ -0,0 +1,123 @@
+use crate::align::TimedWord;
+use serde_json::Value;
+
+pub use dingoflow_streaming::{normalize_text, TimedPiece};
+
+pub struct Decoded {
+    pub text: String,
+    pub language: String,
+    pub pieces: Vec<TimedPiece>,
+}
+
+// How a transcribe got its decoder state; see prepare_transcribe.
+pub struct StateReuse {
+    pub reused: bool,
+    // The setup a fresh state would have cost, when reused.
+    pub saved_ms: f64,
+}
+
+// What an engine has to provide; the server loop, streaming state machine and
+// audio decoding are shared by every backend. The methods with defaults are
+// extras some engines can't do; requests that need them fail on the others.
+pub trait AsrBackend {
+    // Base hello/model_info payload; the server adds the protocol fields.
+    fn describe(&self) -> Value;
+
+    fn sample_rate(&self) -> u32;
+
+    fn warmup(&mut self) -> Result<(), String>;
+
+    // Decodes one window with piece-level end times relative to its start.
+    fn decode(&mut self, audio: &[f32]) -> Result<Decoded, String>;
+
+    // Words the active vocabulary context expects; backends that can't be
+    // biased ignore them.
+    fn set_hotwords(&mut self, _hotwords: &[String]) {}
+
+    // Which of `candidates` the audio is in, with each one's probability.
+    fn detect_language(
+        &mut self,
+        _audio: &[f32],
+        _candidates: &[String],
+    ) -> Result<(String, Value), String> {
+        Err("languageCandidates is not supported by this backend".into())
+    }
+
+    // Decodes in `language` until set back to None, instead of the backend's
+    // own pick.
+    fn set_language(&mut self, _language: Option<&str>) {}
+
+    // The latest decode's words with start and end times.
+    fn timed_words(&self) -> Result<Vec<TimedWord>, String> {
+        Err("word timings are not supported by this backend".into())
+    }
+
+    // The latest decode's raw tokens, as the backend reports them.
+    fn tokens(&self) -> Result<Vec<Value>, String> {
+        Err("returnTokens is not supported by this backend".into())
+    }
+
+    // Readies the decoder for a transcribe request. `reuse` overrides the
+    // worker's --reuse-state for this request; backends that keep a decoder
+    // state across requests report whether it was reused.
+    fn prepare_transcribe(&mut self, _reuse: Option<bool>) -> Result<Option<StateReuse>, String> {
+        Ok(None)
+    }
+
+    // Another decoder on the same loaded model, for batches that decode
+    // `share` files side by side; it gets that share of the threads and keeps
+    // the active context's hotwords.
+    fn fork(&self, _share: usize) -> Result<Box<dyn AsrBackend + Send>, String> {
+        Err("transcribe_batch is not supported by this backend".into())
+    }
+}
+
+impl<B: AsrBackend + ?Sized> AsrBackend for Box<B> {
+    fn describe(&self) -> Value {
+        (**self).describe()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        (**self).sample_rate()
+    }
+
+    fn warmup(&mut self) -> Result<(), String> {
+        (**self).warmup()
+    }
+
+    fn decode(&mut self, audio: &[f32]) -> Result<Decoded, String> {
+        (**self).decode(audio)
+    }
+
+    fn set_hotwords(&mut self, hotwords: &[String]) {
+        (**self).set_hotwords(hotwords)
+    }
+
+    fn detect_language(
+        &mut self,
+        audio: &[f32],
+        candidates: &[String],
+    ) -> Result<(String, Value), String> {
+        (**self).detect_language(audio, candidates)
+    }
+
+    fn set_language(&mut self, language: Option<&str>) {
+        (**self).set_language(language)
+    }
+
+    fn timed_words(&self) -> Result<Vec<TimedWord>, String> {
+        (**self).timed_words()
+    }
+
+    fn tokens(&self) -> Result<Vec<Value>, String> {
+        (**self).tokens()
+    }
+
+    fn prepare_transcribe(&mut self, reuse: Option<bool>) -> Result<Option<StateReuse>, String> {
+        (**self).prepare_transcribe(reuse)
+    }
+
+    fn fork(&self, share: usize) -> Result<Box<dyn AsrBackend + Send>, String> {
+        (**self).fork(share)
+    }
+}
@@ -0,0 +1,197 @@
+use crate::backend::AsrBackend;
+use crate::engine::Engine;
+use crate::isolate::IsolatedDecoder;
+use crate::protocol::{wav_to_f32, Request};
+use crate::transcribe::{self, TranscribeOptions};
+use crate::turns::{Priority, Turns};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+
+pub const MAX_BATCH_CONCURRENCY: usize = 16;
+
+// How each file is transcribed.
+struct PerFile {
+    options: TranscribeOptions,
+    // The same options as JSON, for isolated children to parse again.
+    request: Value,
+}
+
+// What decodes a worker's share of the files.
+enum Decoder<'a> {
+    Fork(Box<Engine<Box<dyn AsrBackend + Send>>>),
+    Child(&'a IsolatedDecoder),
+}
+
+impl Decoder<'_> {
+    fn transcribe_file(
+        &mut self,
+        per_file: &PerFile,
+        path: &str,
+        share: usize,
+    ) -> Result<Value, String> {
+        match self {
+            Decoder::Fork(engine) => {
+                let (audio, sample_rate) = wav_to_f32(path)?;
+                transcribe::transcribe(
+                    engine,
+                    &audio,
+                    sample_rate,
+                    &per_file.options,
+                    |engine, audio| engine.transcribe(audio, sample_rate),
+                )
+            }
+            Decoder::Child(decoder) => decoder.transcribe_file(path, share, &per_file.request),
+        }
+    }
+}
+
+// A transcribe_batch request with its decoders: forks of the engine's
+// backend, or (with `isolated`) child processes. It needs nothing of the
+// engine once made, so it can run beside the requests after it.
+pub struct Batch<'a> {
+    request_id: String,
+    paths: Vec<String>,
+    per_file: PerFile,
+    priority: Priority,
+    decoders: Vec<Decoder<'a>>,
+}
+
+impl<'a> Batch<'a> {
+    // Forks up front, so a backend that can't fork fails before any file.
+    pub fn new<B: AsrBackend>(
+        engine: &Engine<B>,
+        req: &Request,
+        concurrency: usize,
+        isolated: Option<&'a IsolatedDecoder>,
+    ) -> Result<Self, String> {
+        let paths = req.files.clone().unwrap_or_default();
+        if paths.is_empty() {
+            return Err("transcribe_batch requires a non-empty files list".into());
+        }
+        let per_file = PerFile {
+            options: TranscribeOptions::from_request(req)?,
+            request: json!({
+                "returnTokens": req.return_tokens,
+                "wordTimestamps": req.word_timestamps,
+                "languageCandidates": req.language_candidates,
+                "redact": req.redact,
+                "trimSilence": req.trim_silence,
+                "maxPauseSeconds": req.max_pause_seconds,
+                "silenceThresholdDb": req.silence_threshold_db,
+            }),
+        };
+        let priority = Priority::for_request(req.priority.as_deref(), "transcribe_batch")?;
+
+        let workers = req
+            .concurrency
+            .unwrap_or(concurrency)
+            .clamp(1, MAX_BATCH_CONCURRENCY)
+            .min(paths.len());
+        let decoders = (0..workers)
+            .map(|_| match isolated {
+                Some(decoder) => Ok(Decoder::Child(decoder)),
+                None => engine.backend.fork(workers).map(|backend| {
+                    Decoder::Fork(Box::new(Engine::new(backend, engine.stream_config())))
+                }),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Self {
+            request_id: req.id.clone().unwrap_or_else(|| "unknown".to_string()),
+            paths,
+            per_file,
+            priority,
+            decoders,
+        })
+    }
+
+    // Files are decoded side by side, and each takes its own turn on the
+    // model. Per-file events carry `requestId` instead of `id` so hosts that
+    // only track request/response pairs skip them and wait for the final
+    // summary.
+    pub fn run(
+        self,
+        turns: &Turns,
+        emit: &mut dyn FnMut(Value) -> Result<(), String>,
+    ) -> Result<Value, String> {
+        let Batch {
+            request_id,
+            paths,
+            per_file,
+            priority,
+            decoders,
+        } = self;
+        let (paths, per_file) = (&paths, &per_file);
+        let started = Instant::now();
+        let workers = decoders.len();
+        let next_index = AtomicUsize::new(0);
+        let (tx, rx) = mpsc::channel::<(usize, Result<Value, String>)>();
+
+        let mut succeeded = 0_usize;
+        let mut failed = 0_usize;
+        let mut write_error: Option<String> = None;
+
+        thread::scope(|scope| {
+            for mut decoder in decoders {
+                let tx = tx.clone();
+                let next_index = &next_index;
+                scope.spawn(move || loop {
+                    let index = next_index.fetch_add(1, Ordering::Relaxed);
+                    if index >= paths.len() {
+                        break;
+                    }
+
+                    let turn = turns.take(priority);
+                    let outcome = decoder.transcribe_file(per_file, &paths[index], workers);
+                    drop(turn);
+                    if tx.send((index, outcome)).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+
+            for (index, outcome) in rx {
+                let mut event = json!({
+                    "event": "batch_item",
+                    "requestId": request_id,
+                    "index": index,
+                    "path": paths[index],
+                });
+                match outcome {
+                    Ok(result) => {
+                        succeeded += 1;
+                        event["ok"] = json!(true);
+                        event["result"] = result;
+                    }
+                    Err(error) => {
+                        failed += 1;
+                        event["ok"] = json!(false);
+                        event["error"] = json!(error);
+                    }
+                }
+
+                if let Err(err) = emit(event) {
+                    // Dropping the receiver makes the remaining workers stop early.
+                    write_error = Some(err);
+                    break;
+                }
+            }
+        });
+
+        if let Some(err) = write_error {
+            return Err(format!("failed to write batch event: {err}"));
+        }
+
+        let duration_seconds = started.elapsed().as_secs_f64();
+        Ok(json!({
+            "files": paths.len(),
+            "succeeded": succeeded,
+            "failed": failed,
+            "durationSeconds": ((duration_seconds * 1000.0).round() / 1000.0)
+        }))
+    }
+}
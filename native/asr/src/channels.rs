@@ -0,0 +1,85 @@
+use crate::backend::{normalize_text, TimedPiece};
+use serde_json::{json, Value};
+
+// Two-channel call recordings put each participant on their own channel, so
+// decoding the channels separately attributes every word exactly, where
+// diarizing the mix has to guess.
+
+pub const SPEAKERS: [&str; 2] = ["left", "right"];
+
+// Pieces only carry end times. A turn starts where its channel's previous
+// piece ended, but no earlier than this before its own first piece ends, so
+// the pause before it isn't counted as speech.
+const MAX_PIECE_SECONDS: f32 = 1.0;
+
+pub struct Turn {
+    pub speaker: &'static str,
+    pub text: String,
+    pub start_seconds: f32,
+    pub end_seconds: f32,
+}
+
+// Interleaves both channels' pieces by time; consecutive pieces from the same
+// channel make one turn.
+pub fn merge(channels: [&[TimedPiece]; 2]) -> Vec<Turn> {
+    let mut pieces: Vec<(usize, f32, &TimedPiece)> = Vec::new();
+    for (channel, channel_pieces) in channels.iter().enumerate() {
+        let mut previous_end = 0.0_f32;
+        for piece in channel_pieces.iter() {
+            let start = previous_end
+                .max(piece.end_seconds - MAX_PIECE_SECONDS)
+                .max(0.0);
+            pieces.push((channel, start, piece));
+            previous_end = piece.end_seconds;
+        }
+    }
+    // Stable, so a tie goes to the left channel.
+    pieces.sort_by(|a, b| a.2.end_seconds.total_cmp(&b.2.end_seconds));
+
+    let mut turns: Vec<(usize, Turn)> = Vec::new();
+    for (channel, start, piece) in pieces {
+        match turns.last_mut() {
+            Some((last, turn)) if *last == channel => {
+                turn.text.push(' ');
+                turn.text.push_str(&piece.text);
+                turn.end_seconds = piece.end_seconds;
+            }
+            _ => turns.push((
+                channel,
+                Turn {
+                    speaker: SPEAKERS[channel],
+                    text: piece.text.clone(),
+                    start_seconds: start,
+                    end_seconds: piece.end_seconds,
+                },
+            )),
+        }
+    }
+    turns
+        .into_iter()
+        .map(|(_, mut turn)| {
+            turn.text = normalize_text(&turn.text);
+            turn
+        })
+        .filter(|turn| !turn.text.is_empty())
+        .collect()
+}
+
+pub fn describe(turns: &[Turn]) -> Value {
+    let segments: Vec<Value> = turns
+        .iter()
+        .map(|turn| {
+            json!({
+                "speaker": turn.speaker,
+                "text": turn.text,
+                "start": round3(turn.start_seconds),
+                "end": round3(turn.end_seconds),
+            })
+        })
+        .collect();
+    Value::Array(segments)
+}
+
+fn round3(value: f32) -> f64 {
+    (value as f64 * 1000.0).round() / 1000.0
+}
@@ -0,0 +1,616 @@
+use crate::backend::AsrBackend;
+use crate::batch::MAX_BATCH_CONCURRENCY;
+use crate::context::ContextStore;
+use crate::crash::CrashLog;
+use crate::engine::Engine;
+use crate::http;
+use crate::isolate::IsolatedDecoder;
+use crate::journal::Journal;
+use crate::limits::{ClientLimits, MAX_CLIENT_IN_FLIGHT};
+use crate::macros::Macros;
+#[cfg(feature = "parakeet")]
+use crate::parakeet::{self, ParakeetBackend};
+use crate::protocol::{describe_model, pcm16_to_f32, serve, ServeOptions};
+use crate::schema::protocol_schema;
+use crate::settings::Settings;
+use crate::soak::{self, SoakOptions};
+use crate::stream::StreamConfig;
+use crate::telemetry::{init_logging, log, LogFormat, LogLevel};
+use crate::trace::FrameTrace;
+use crate::transcribe::{self, TranscribeOptions};
+use crate::transport::ListenEndpoint;
+#[cfg(feature = "whisper")]
+use crate::whisper::{self, WhisperBackend};
+use serde_json::json;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// The one flag set every ASR binary takes: dingoflow-asr picks the engine
+// with --backend, and the host's workers (dingoflow-asr-worker,
+// dingoflow-parakeet-worker) are this with the backend fixed.
+const USAGE: &str = "--model <path> [--threads 4] [--language en] [--languages en,es] [--stream-min-audio-ms 120] [--stream-decode-interval-ms 160] [--stream-max-window-ms 6000] [--stream-left-context-ms 1000] [--stream-stability-hold-ms 220] [--stream-silence-floor-db -60] [--low-confidence-threshold 0.5 [--low-confidence-ms 3000]] [--context-dir <dir>] [--settings-dir <dir>] [--transcript-jsonl <path>] [--macros <macros.json>] [--ffmpeg-input] [--trace-frames <path>] [--concurrency 1] [--isolate-decodes] [--warmup] [--reuse-state] [--listen unix:/path.sock|tcp:127.0.0.1:7070] [--client-max-in-flight 8] [--client-max-audio-bytes-per-sec 1048576] [--log-format text|json] [--log-level info] [--serve | --http-port 8178 | --mic [--device <id, index or name substring>] | --soak <hours> [--soak-wavs <dir>] | --dump-schema]; with none of --serve, --http-port, --mic or --soak, one 16-bit PCM clip is read from stdin and its result printed";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BackendKind {
+    #[cfg(feature = "whisper")]
+    Whisper,
+    #[cfg(feature = "parakeet")]
+    Parakeet,
+}
+
+impl BackendKind {
+    fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            #[cfg(feature = "whisper")]
+            "whisper" => Ok(Self::Whisper),
+            #[cfg(feature = "parakeet")]
+            "parakeet" => Ok(Self::Parakeet),
+            other => Err(format!("Unsupported --backend: {other}")),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "whisper")]
+            Self::Whisper => "whisper",
+            #[cfg(feature = "parakeet")]
+            Self::Parakeet => "parakeet",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Config {
+    program: String,
+    backend: BackendKind,
+    model_path: String,
+    threads: i32,
+    language: String,
+    languages: Vec<String>,
+    serve: bool,
+    healthcheck: bool,
+    warmup: bool,
+    reuse_state: bool,
+    stream: StreamConfig,
+    context_dir: Option<PathBuf>,
+    settings_dir: Option<PathBuf>,
+    transcript_path: Option<PathBuf>,
+    macros_path: Option<PathBuf>,
+    ffmpeg_input: bool,
+    trace_path: Option<PathBuf>,
+    soak: Option<SoakOptions>,
+    concurrency: usize,
+    isolate_decodes: bool,
+    listen: Option<ListenEndpoint>,
+    client_limits: ClientLimits,
+    http_port: Option<u16>,
+    // Only read by the capture code, which the `mic` feature compiles in.
+    #[cfg_attr(not(feature = "mic"), allow(dead_code))]
+    mic: bool,
+    #[cfg_attr(not(feature = "mic"), allow(dead_code))]
+    device: Option<String>,
+    log_format: LogFormat,
+    log_level: LogLevel,
+}
+
+impl Config {
+    fn settings(&self) -> Option<Settings> {
+        let dir = self.settings_dir.clone().or_else(Settings::platform_dir)?;
+        Some(Settings::new(dir, &self.model_path))
+    }
+}
+
+fn parse_ms(value: &str, flag: &str) -> Result<u32, String> {
+    value
+        .parse::<u32>()
+        .map_err(|_| format!("Invalid {flag} value"))
+}
+
+// `fixed` is the backend a worker binary was built for; --backend may only
+// repeat it, as isolated decoders do.
+fn parse_args(program: &str, fixed: Option<&str>, args: &[String]) -> Result<Config, String> {
+    let fixed = fixed.map(BackendKind::parse).transpose()?;
+    let mut backend = fixed;
+    let mut model_path = String::new();
+    let mut threads = 4_i32;
+    let mut language = "en".to_string();
+    let mut languages = Vec::new();
+    let mut serve = false;
+    let mut ffmpeg_input = false;
+    let mut healthcheck = false;
+    let mut warmup = false;
+    let mut reuse_state = false;
+    let mut stream = StreamConfig::default();
+    let mut context_dir = std::env::var_os("DINGOFLOW_CONTEXT_DIR").map(PathBuf::from);
+    let mut settings_dir = std::env::var_os("DINGOFLOW_SETTINGS_DIR").map(PathBuf::from);
+    let mut transcript_path = std::env::var_os("DINGOFLOW_TRANSCRIPT_JSONL").map(PathBuf::from);
+    let mut trace_path = std::env::var_os("DINGOFLOW_TRACE_FRAMES").map(PathBuf::from);
+    let mut macros_path = std::env::var_os("DINGOFLOW_MACROS").map(PathBuf::from);
+    let mut soak_hours = None;
+    let mut soak_wavs = None;
+    let mut concurrency = 1_usize;
+    let mut isolate_decodes = false;
+    let mut listen = None;
+    let mut client_limits = ClientLimits::default();
+    let mut http_port = None;
+    let mut mic = false;
+    let mut device = std::env::var("DINGOFLOW_NATIVE_AUDIO_DEVICE")
+        .ok()
+        .filter(|value| !value.is_empty());
+    let mut log_format = LogFormat::Text;
+    let mut log_level = LogLevel::Info;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--backend" => {
+                let chosen = BackendKind::parse(&value()?)?;
+                if let Some(fixed) = fixed.filter(|fixed| *fixed != chosen) {
+                    return Err(format!("{program} only runs --backend {}", fixed.name()));
+                }
+                backend = Some(chosen);
+                i += 2;
+            }
+            "--model" => {
+                model_path = value()?;
+                i += 2;
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse::<i32>()
+                    .map_err(|_| "Invalid --threads value".to_string())?;
+                i += 2;
+            }
+            "--language" => {
+                language = value()?;
+                i += 2;
+            }
+            "--languages" => {
+                languages = value()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|language| !language.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if languages.len() < 2 {
+                    return Err("--languages needs at least two languages".into());
+                }
+                i += 2;
+            }
+            "--stream-min-audio-ms" => {
+                stream.min_audio_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--stream-decode-interval-ms" => {
+                stream.decode_interval_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--stream-max-window-ms" => {
+                stream.max_window_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--stream-left-context-ms" => {
+                stream.left_context_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--stream-stability-hold-ms" => {
+                stream.stability_hold_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--stream-silence-floor-db" => {
+                stream.silence_floor_db = value()?
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid {flag} value"))?;
+                i += 2;
+            }
+            "--low-confidence-threshold" => {
+                stream.low_confidence_threshold = value()?
+                    .parse::<f32>()
+                    .map_err(|_| format!("Invalid {flag} value"))?;
+                i += 2;
+            }
+            "--low-confidence-ms" => {
+                stream.low_confidence_ms = parse_ms(&value()?, flag)?;
+                i += 2;
+            }
+            "--context-dir" => {
+                context_dir = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--settings-dir" => {
+                settings_dir = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--transcript-jsonl" => {
+                transcript_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--macros" => {
+                macros_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--trace-frames" => {
+                trace_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--soak" => {
+                soak_hours = Some(
+                    value()?
+                        .parse::<f64>()
+                        .map_err(|_| "Invalid --soak value".to_string())?,
+                );
+                i += 2;
+            }
+            "--soak-wavs" => {
+                soak_wavs = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--concurrency" => {
+                concurrency = value()?
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid --concurrency value".to_string())?;
+                i += 2;
+            }
+            "--listen" => {
+                listen = Some(ListenEndpoint::parse(&value()?)?);
+                serve = true;
+                i += 2;
+            }
+            "--client-max-in-flight" => {
+                client_limits.max_in_flight = value()?
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid {flag} value"))?;
+                i += 2;
+            }
+            "--client-max-audio-bytes-per-sec" => {
+                client_limits.max_audio_bytes_per_sec = value()?
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid {flag} value"))?;
+                i += 2;
+            }
+            "--http-port" => {
+                http_port = Some(
+                    value()?
+                        .parse::<u16>()
+                        .ok()
+                        .filter(|port| *port > 0)
+                        .ok_or("Invalid --http-port value")?,
+                );
+                i += 2;
+            }
+            "--device" => {
+                device = Some(value()?);
+                i += 2;
+            }
+            "--log-format" => {
+                log_format = LogFormat::parse(&value()?)?;
+                i += 2;
+            }
+            "--log-level" => {
+                log_level = LogLevel::parse(&value()?)?;
+                i += 2;
+            }
+            "--mic" => {
+                mic = true;
+                i += 1;
+            }
+            "--warmup" => {
+                warmup = true;
+                i += 1;
+            }
+            "--reuse-state" => {
+                reuse_state = true;
+                i += 1;
+            }
+            "--isolate-decodes" => {
+                isolate_decodes = true;
+                i += 1;
+            }
+            "--ffmpeg-input" => {
+                ffmpeg_input = true;
+                i += 1;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                let backend = match fixed {
+                    Some(_) => "",
+                    None => "--backend whisper|parakeet ",
+                };
+                return Err(format!("usage: {program} {backend}{USAGE}"));
+            }
+            other => return Err(format!("Unsupported argument: {other}")),
+        }
+    }
+
+    let backend = backend.ok_or("--backend is required (whisper or parakeet)")?;
+
+    if !healthcheck {
+        if model_path.is_empty() {
+            return Err("--model is required unless --healthcheck is used".into());
+        }
+
+        if !(1..=64).contains(&threads) {
+            return Err("--threads must be between 1 and 64".into());
+        }
+
+        if soak_wavs.is_some() && soak_hours.is_none() {
+            return Err("--soak-wavs needs --soak".into());
+        }
+
+        let modes = [serve, http_port.is_some(), mic, soak_hours.is_some()];
+        if modes.iter().filter(|mode| **mode).count() > 1 {
+            return Err("use only one of --serve/--listen, --http-port, --mic and --soak".into());
+        }
+
+        if mic && !cfg!(feature = "mic") {
+            return Err(format!("--mic needs {program} built with --features mic"));
+        }
+
+        stream.validate()?;
+
+        if !(1..=MAX_BATCH_CONCURRENCY).contains(&concurrency) {
+            return Err(format!(
+                "--concurrency must be between 1 and {MAX_BATCH_CONCURRENCY}"
+            ));
+        }
+
+        if !(1..=MAX_CLIENT_IN_FLIGHT).contains(&client_limits.max_in_flight) {
+            return Err(format!(
+                "--client-max-in-flight must be between 1 and {MAX_CLIENT_IN_FLIGHT}"
+            ));
+        }
+
+        if !languages.is_empty() && backend.name() != "whisper" {
+            return Err("--languages is only supported with --backend whisper".into());
+        }
+
+        if reuse_state && backend.name() != "whisper" {
+            return Err("--reuse-state is only supported with --backend whisper".into());
+        }
+
+        if let Some(dir) = &context_dir {
+            if !dir.is_dir() {
+                return Err(format!("--context-dir not found: {}", dir.display()));
+            }
+        }
+    }
+
+    Ok(Config {
+        program: program.to_string(),
+        backend,
+        model_path,
+        threads,
+        language,
+        languages,
+        serve,
+        healthcheck,
+        warmup,
+        reuse_state,
+        stream,
+        context_dir,
+        settings_dir,
+        transcript_path,
+        macros_path,
+        ffmpeg_input,
+        trace_path,
+        soak: soak_hours.map(|hours| SoakOptions {
+            hours,
+            wav_dir: soak_wavs,
+        }),
+        concurrency,
+        isolate_decodes,
+        listen,
+        client_limits,
+        http_port,
+        mic,
+        device,
+        log_format,
+        log_level,
+    })
+}
+
+fn run(cfg: &Config) -> Result<(), String> {
+    let contexts = ContextStore::new(
+        cfg.context_dir
+            .clone()
+            .or_else(|| cfg.settings().map(|settings| settings.contexts_dir())),
+    );
+    match cfg.backend {
+        #[cfg(feature = "whisper")]
+        BackendKind::Whisper => {
+            whisper::check_model_file(&cfg.model_path)?;
+            let backend = WhisperBackend::load(&cfg.model_path, cfg.threads, &cfg.language)?
+                .with_languages(&cfg.languages)?
+                .with_reuse_state(cfg.reuse_state);
+            run_engine(
+                Engine::new(backend, &cfg.stream).with_contexts(contexts),
+                cfg,
+            )
+        }
+        #[cfg(feature = "parakeet")]
+        BackendKind::Parakeet => {
+            parakeet::check_model_dir(&cfg.model_path)?;
+            let backend = ParakeetBackend::load(&cfg.model_path, cfg.threads)?;
+            run_engine(
+                Engine::new(backend, &cfg.stream).with_contexts(contexts),
+                cfg,
+            )
+        }
+    }
+}
+
+fn run_engine<B: AsrBackend>(mut engine: Engine<B>, cfg: &Config) -> Result<(), String> {
+    if let Some(soak) = &cfg.soak {
+        let summary = soak::run(&mut engine, soak)?;
+        println!("{summary:#}");
+        return Ok(());
+    }
+
+    #[cfg(feature = "mic")]
+    if cfg.mic {
+        // Prints committed text as it settles, like dingoflow-dictate.
+        if crate::mic::run_mic(&mut engine, cfg.device.as_deref(), crate::mic::print_commit)? {
+            println!();
+        }
+        return Ok(());
+    }
+
+    // Forces the backend to allocate its buffers (and on Metal compile
+    // kernels) so the first real request doesn't pay for it. Nothing sends a
+    // warmup request over HTTP, so that mode always does it.
+    if cfg.warmup || cfg.http_port.is_some() {
+        let started = Instant::now();
+        engine.warmup()?;
+        log(
+            LogLevel::Info,
+            "warmup decode finished",
+            json!({ "warmupMs": (started.elapsed().as_secs_f64() * 1000.0).round() }),
+        );
+    }
+
+    if let Some(port) = cfg.http_port {
+        for (set, flag) in [
+            (cfg.transcript_path.is_some(), "--transcript-jsonl"),
+            (cfg.trace_path.is_some(), "--trace-frames"),
+            (cfg.macros_path.is_some(), "--macros"),
+        ] {
+            if set {
+                log(
+                    LogLevel::Warn,
+                    "flag only applies to --serve; ignoring it",
+                    json!({ "flag": flag }),
+                );
+            }
+        }
+        let model_id = Path::new(&cfg.model_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| cfg.backend.name().to_string());
+        return http::serve(port, &model_id, &mut engine);
+    }
+
+    if cfg.serve {
+        let model_info = describe_model(&engine, &cfg.model_path);
+        return serve(&mut engine, model_info, serve_options(cfg)?);
+    }
+
+    run_once(&mut engine)
+}
+
+fn serve_options(cfg: &Config) -> Result<ServeOptions, String> {
+    Ok(ServeOptions {
+        journal: cfg
+            .transcript_path
+            .as_deref()
+            .map(Journal::open)
+            .transpose()?,
+        ffmpeg_input: cfg.ffmpeg_input,
+        trace: cfg
+            .trace_path
+            .as_deref()
+            .map(FrameTrace::open)
+            .transpose()?,
+        macros: cfg.macros_path.as_deref().map(Macros::load).transpose()?,
+        settings: cfg.settings(),
+        crash: Some(CrashLog::install(
+            &cfg.program,
+            json!({ "args": std::env::args().collect::<Vec<_>>() }),
+        )),
+        listen: cfg.listen.clone(),
+        client_limits: cfg.client_limits,
+        batch_concurrency: cfg.concurrency,
+        isolate: cfg
+            .isolate_decodes
+            .then(|| IsolatedDecoder::new(child_args(cfg), cfg.threads))
+            .transpose()?,
+    })
+}
+
+// What loads the same model in an isolated decoder.
+fn child_args(cfg: &Config) -> Vec<String> {
+    let mut args = vec![
+        "--backend".to_string(),
+        cfg.backend.name().to_string(),
+        "--model".to_string(),
+        cfg.model_path.clone(),
+        "--language".to_string(),
+        cfg.language.clone(),
+    ];
+    if !cfg.languages.is_empty() {
+        args.push("--languages".to_string());
+        args.push(cfg.languages.join(","));
+    }
+    if cfg.reuse_state {
+        args.push("--reuse-state".to_string());
+    }
+    args
+}
+
+// Without a mode: PCM16 at the backend's rate on stdin, one JSON result on
+// stdout.
+fn run_once<B: AsrBackend>(engine: &mut Engine<B>) -> Result<(), String> {
+    let mut input = Vec::new();
+    io::stdin()
+        .lock()
+        .read_to_end(&mut input)
+        .map_err(|err| format!("failed to read stdin audio: {err}"))?;
+
+    let rate = engine.backend.sample_rate();
+    let result = transcribe::transcribe(
+        engine,
+        &pcm16_to_f32(&input),
+        rate,
+        &TranscribeOptions::default(),
+        |engine, audio| engine.transcribe(audio, rate),
+    )?;
+    println!(
+        "{}",
+        serde_json::to_string(&result).map_err(|err| format!("json serialize failed: {err}"))?
+    );
+    Ok(())
+}
+
+// `backend` fixes the engine for the host's per-engine workers; dingoflow-asr
+// passes None and takes --backend.
+pub fn main(program: &str, backend: Option<&str>) {
+    // Needs no model, so it's answered before the other flags are checked.
+    if std::env::args().skip(1).any(|arg| arg == "--dump-schema") {
+        println!("{:#}", protocol_schema());
+        return;
+    }
+
+    let args: Vec<String> = std::env::args().collect();
+    let cfg = match parse_args(program, backend, &args) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    init_logging(cfg.log_format, cfg.log_level);
+
+    if cfg.healthcheck {
+        println!("ok");
+        return;
+    }
+
+    if let Err(err) = run(&cfg) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::backend::{Decoded, TimedPiece};
+use crate::stream::confidence::word_key;
+use serde_json::{json, Value};
+
+// Constrained output for fields like phone numbers and IDs, where free-form
+// decoding turns "four two" into "for to". The backends' greedy decoders
+// don't take a vocabulary mask, so the constraint is applied to what they
+// decode, before the streamer sees it: each word becomes the allowed output
+// it most likely was, or is dropped.
+
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    // Digits and the symbols spoken in numbers: "plus one five five five".
+    Digits,
+    // Only these words, as written in the list.
+    Words(Vec<(String, String)>),
+}
+
+// Spoken forms, with the homophones models pick for them.
+const DIGITS: &[(&str, &str)] = &[
+    ("zero", "0"),
+    ("oh", "0"),
+    ("o", "0"),
+    ("one", "1"),
+    ("won", "1"),
+    ("two", "2"),
+    ("to", "2"),
+    ("too", "2"),
+    ("three", "3"),
+    ("tree", "3"),
+    ("four", "4"),
+    ("for", "4"),
+    ("fore", "4"),
+    ("five", "5"),
+    ("six", "6"),
+    ("seven", "7"),
+    ("eight", "8"),
+    ("ate", "8"),
+    ("nine", "9"),
+    ("plus", "+"),
+    ("dash", "-"),
+    ("hyphen", "-"),
+    ("minus", "-"),
+    ("dot", "."),
+    ("point", "."),
+    ("hash", "#"),
+    ("pound", "#"),
+    ("star", "*"),
+];
+const DIGIT_SYMBOLS: &str = "+-.#*()";
+// "double five" is 55.
+const REPEATS: &[(&str, usize)] = &[("double", 2), ("triple", 3)];
+
+impl Constraint {
+    // "digits", or {"words": ["alpha", "bravo", ...]}.
+    pub fn parse(value: &Value) -> Result<Self, String> {
+        if value.as_str() == Some("digits") {
+            return Ok(Constraint::Digits);
+        }
+        let words = value
+            .get("words")
+            .and_then(Value::as_array)
+            .ok_or(r#"constrain must be "digits" or {"words": [...]}"#)?;
+        let mut allowed = Vec::new();
+        for word in words {
+            let word = word.as_str().ok_or("constrain words must be strings")?;
+            for written in word.split_whitespace() {
+                if let Some(key) = word_key(written) {
+                    allowed.push((key, written.to_string()));
+                }
+            }
+        }
+        if allowed.is_empty() {
+            return Err("constrain words are empty".into());
+        }
+        Ok(Constraint::Words(allowed))
+    }
+
+    pub fn describe(&self) -> Value {
+        match self {
+            Constraint::Digits => json!({ "mode": "digits" }),
+            Constraint::Words(words) => json!({ "mode": "words", "words": words.len() }),
+        }
+    }
+
+    pub fn apply(&self, decoded: Decoded) -> Decoded {
+        let words: Vec<TimedPiece> = decoded
+            .text
+            .split_whitespace()
+            .map(|word| TimedPiece {
+                text: word.to_string(),
+                end_seconds: 0.0,
+                confidence: None,
+            })
+            .collect();
+        let text = self
+            .constrain(words)
+            .into_iter()
+            .map(|piece| piece.text)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Decoded {
+            text,
+            language: decoded.language,
+            pieces: self.constrain(decoded.pieces),
+        }
+    }
+
+    fn constrain(&self, pieces: Vec<TimedPiece>) -> Vec<TimedPiece> {
+        let mut out = Vec::with_capacity(pieces.len());
+        let mut repeat = 1;
+        for piece in pieces {
+            let text = match self {
+                Constraint::Digits => {
+                    let key = word_key(&piece.text).unwrap_or_default();
+                    if let Some((_, times)) = REPEATS.iter().find(|(word, _)| *word == key) {
+                        repeat = *times;
+                        continue;
+                    }
+                    let text = digits(&piece.text, &key).repeat(repeat);
+                    repeat = 1;
+                    text
+                }
+                Constraint::Words(allowed) => nearest_word(&piece.text, allowed),
+            };
+            if !text.is_empty() {
+                out.push(TimedPiece { text, ..piece });
+            }
+        }
+        out
+    }
+}
+
+// What a piece says in digits mode: written digits and symbols as they are,
+// a spoken digit as its digit, any other word (and its punctuation) nothing.
+fn digits(text: &str, key: &str) -> String {
+    if let Some((_, digit)) = DIGITS.iter().find(|(word, _)| *word == key) {
+        return digit.to_string();
+    }
+    if text.chars().any(char::is_alphabetic) {
+        return String::new();
+    }
+    text.chars()
+        .filter(|c| c.is_ascii_digit() || DIGIT_SYMBOLS.contains(*c))
+        .collect()
+}
+
+// The allowed word closest in spelling, if it's close enough to be a
+// mishearing rather than a different word.
+fn nearest_word(text: &str, allowed: &[(String, String)]) -> String {
+    let Some(key) = word_key(text) else {
+        return String::new();
+    };
+    let best = allowed
+        .iter()
+        .map(|(allowed_key, written)| (edit_distance(&key, allowed_key), allowed_key, written))
+        .min_by_key(|(distance, _, _)| *distance);
+    match best {
+        Some((distance, allowed_key, written))
+            if distance <= allowed_key.chars().count().max(key.chars().count()) / 2 =>
+        {
+            written.clone()
+        }
+        _ => String::new(),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
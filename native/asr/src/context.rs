@@ -0,0 +1,202 @@
+use crate::backend::{Decoded, TimedPiece};
+use crate::settings::write_json;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+// Per-application vocabulary, e.g. "terminal" vs "email". <dir>/<label>.json
+// holds {"hotwords": ["kubectl", ...], "replacements": {"cube control":
+// "kubectl", ...}}; <dir>/default.json applies when a stream names none.
+// Files are read on every stream_reset, so edits apply without a restart.
+//
+// Hotwords bias backends that support a prompt (whisper); replacements
+// rewrite decoded words for every backend.
+
+const DEFAULT_CONTEXT: &str = "default";
+
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    pub label: String,
+    pub hotwords: Vec<String>,
+    // Lowercased spoken words -> replacement, longest phrases first.
+    replacements: Vec<(Vec<String>, String)>,
+}
+
+impl Context {
+    fn parse(label: &str, value: &Value) -> Result<Self, String> {
+        let mut context = Context {
+            label: label.to_string(),
+            ..Context::default()
+        };
+        if let Some(hotwords) = value.get("hotwords") {
+            let hotwords = hotwords.as_array().ok_or("hotwords must be an array")?;
+            for word in hotwords {
+                let word = word.as_str().ok_or("hotwords must be strings")?.trim();
+                if !word.is_empty() {
+                    context.hotwords.push(word.to_string());
+                }
+            }
+        }
+        if let Some(replacements) = value.get("replacements") {
+            let replacements = replacements
+                .as_object()
+                .ok_or("replacements must be an object")?;
+            for (spoken, written) in replacements {
+                let written = written.as_str().ok_or("replacements must map to strings")?;
+                let words: Vec<String> = spoken.split_whitespace().map(str::to_lowercase).collect();
+                if !words.is_empty() {
+                    context.replacements.push((words, written.to_string()));
+                }
+            }
+            context
+                .replacements
+                .sort_by_key(|(words, _)| std::cmp::Reverse(words.len()));
+        }
+        Ok(context)
+    }
+
+    pub fn describe(&self) -> Value {
+        json!({
+            "context": self.label,
+            "hotwords": self.hotwords.len(),
+            "replacements": self.replacements.len(),
+        })
+    }
+
+    // Replacements match whole words, ignoring case and the punctuation
+    // around them; a word's trailing punctuation is kept. A phrase spanning
+    // several pieces becomes one piece ending where its last word did.
+    pub fn apply(&self, decoded: Decoded) -> Decoded {
+        if self.replacements.is_empty() {
+            return decoded;
+        }
+        let words: Vec<TimedPiece> = decoded
+            .text
+            .split_whitespace()
+            .map(|word| TimedPiece {
+                text: word.to_string(),
+                end_seconds: 0.0,
+                confidence: None,
+            })
+            .collect();
+        let text = self
+            .replace(words)
+            .into_iter()
+            .map(|piece| piece.text)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Decoded {
+            text,
+            language: decoded.language,
+            pieces: self.replace(decoded.pieces),
+        }
+    }
+
+    fn replace(&self, pieces: Vec<TimedPiece>) -> Vec<TimedPiece> {
+        let mut out: Vec<TimedPiece> = Vec::with_capacity(pieces.len());
+        let mut i = 0;
+        'pieces: while i < pieces.len() {
+            for (spoken, written) in &self.replacements {
+                let Some(words) = pieces.get(i..i + spoken.len()) else {
+                    continue;
+                };
+                let matches = words
+                    .iter()
+                    .zip(spoken)
+                    .all(|(piece, spoken)| bare(&piece.text).to_lowercase() == *spoken);
+                if let (true, Some(last)) = (matches, words.last()) {
+                    let trimmed = last.text.trim_end_matches(|c: char| !c.is_alphanumeric());
+                    out.push(TimedPiece {
+                        text: format!("{written}{}", &last.text[trimmed.len()..]),
+                        end_seconds: last.end_seconds,
+                        confidence: words
+                            .iter()
+                            .filter_map(|piece| piece.confidence)
+                            .reduce(f32::min),
+                    });
+                    i += spoken.len();
+                    continue 'pieces;
+                }
+            }
+            let piece = &pieces[i];
+            out.push(TimedPiece {
+                text: piece.text.clone(),
+                end_seconds: piece.end_seconds,
+                confidence: piece.confidence,
+            });
+            i += 1;
+        }
+        out
+    }
+}
+
+fn bare(word: &str) -> &str {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+}
+
+// Labels name files, so they stay plain: letters, digits, - and _.
+pub fn valid_label(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+pub struct ContextStore {
+    dir: Option<PathBuf>,
+}
+
+impl ContextStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    // Checked the same way it will be loaded, then written where load
+    // looks for it.
+    pub fn save(&self, label: &str, value: &Value) -> Result<PathBuf, String> {
+        let Some(dir) = self.dir.as_deref() else {
+            return Err(format!(
+                "cannot save context {label}: no context dir is configured"
+            ));
+        };
+        if !valid_label(label) {
+            return Err(format!("invalid context label: {label}"));
+        }
+        Context::parse(label, value)?;
+        let path = dir.join(format!("{label}.json"));
+        write_json(&path, value)?;
+        Ok(path)
+    }
+
+    // An explicit label must exist; without one, default.json is used when
+    // present.
+    pub fn load(&self, label: Option<&str>) -> Result<Option<Context>, String> {
+        let Some(dir) = self.dir.as_deref() else {
+            return match label {
+                Some(label) => Err(format!(
+                    "context {label} requested but no --context-dir is configured"
+                )),
+                None => Ok(None),
+            };
+        };
+        let name = label.unwrap_or(DEFAULT_CONTEXT);
+        if !valid_label(name) {
+            return Err(format!("invalid context label: {name}"));
+        }
+
+        let path = dir.join(format!("{name}.json"));
+        if label.is_none() && !path.exists() {
+            return Ok(None);
+        }
+        let raw = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read context {}: {err}", path.display()))?;
+        let value: Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("invalid context {}: {err}", path.display()))?;
+        Context::parse(name, &value)
+            .map(Some)
+            .map_err(|err| format!("invalid context {}: {err}", path.display()))
+    }
+}
@@ -0,0 +1,193 @@
+use dingoflow_frame::write_response;
+use serde_json::{json, Value};
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::io;
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, ThreadId};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Requests kept for a crash report, newest last.
+const RECENT_REQUESTS: usize = 16;
+
+// On a panic, writes <data dir>/crashes/<worker>-<unix ms>.json with the
+// panic message, a backtrace, the last few requests and the worker's config,
+// then answers the request that was being handled with a final error frame
+// naming the report, so the host can show something better than a closed
+// pipe. The frame goes to stdout unless the protocol points it elsewhere
+// (a --listen client) with answer_with. Request summaries carry sizes and ids only, never audio or text,
+// like the frame trace.
+//
+//   {"worker":"dingoflow-asr","message":"...","location":"src/x.rs:10:5",
+//    "thread":"main","backtrace":"...","config":{...},
+//    "recentRequests":[{"id":"...","action":"stream_push","jsonBytes":..,
+//      "audioBytes":..,"atMs":..}, ...],"atMs":...}
+#[derive(Clone)]
+pub struct CrashLog {
+    recent: Arc<Mutex<VecDeque<Value>>>,
+    answer: Arc<Mutex<Answer>>,
+}
+
+type Answer = Box<dyn Fn(&Value) + Send>;
+
+struct Crash {
+    worker: String,
+    config: Value,
+    dir: Option<PathBuf>,
+    recent: Arc<Mutex<VecDeque<Value>>>,
+    answer: Arc<Mutex<Answer>>,
+    // Only the serving thread answers in a frame: it was handling the
+    // request the host is waiting on. stdout is locked for the whole frame,
+    // so it lands between the response writer's frames, not inside one.
+    serving: ThreadId,
+}
+
+impl CrashLog {
+    // Call from the thread that serves frames.
+    pub fn install(worker: &str, config: Value) -> Self {
+        let recent = Arc::new(Mutex::new(VecDeque::with_capacity(RECENT_REQUESTS)));
+        let answer: Arc<Mutex<Answer>> = Arc::new(Mutex::new(Box::new(|frame| {
+            let _ = write_response(&mut io::stdout().lock(), frame);
+        })));
+        let crash = Crash {
+            worker: worker.to_string(),
+            config,
+            dir: crash_dir(),
+            recent: Arc::clone(&recent),
+            answer: Arc::clone(&answer),
+            serving: thread::current().id(),
+        };
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            crash.report(info);
+            default_hook(info);
+        }));
+        Self { recent, answer }
+    }
+
+    // Sends the final error frame through `answer` instead of stdout, for
+    // when stdout isn't the protocol channel. It runs inside the panic hook.
+    pub fn answer_with(&self, answer: impl Fn(&Value) + Send + 'static) {
+        *self.answer.lock().unwrap_or_else(PoisonError::into_inner) = Box::new(answer);
+    }
+
+    pub fn record(
+        &self,
+        id: Option<&str>,
+        action: Option<&str>,
+        json_bytes: usize,
+        audio_bytes: usize,
+    ) {
+        let mut recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        if recent.len() == RECENT_REQUESTS {
+            recent.pop_front();
+        }
+        recent.push_back(json!({
+            "id": id,
+            "action": action,
+            "jsonBytes": json_bytes,
+            "audioBytes": audio_bytes,
+            "atMs": now_ms(),
+        }));
+    }
+}
+
+impl Crash {
+    fn report(&self, info: &PanicHookInfo) {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => info
+                .payload()
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "non-string panic payload".to_string()),
+        };
+        let location = info.location().map(|location| location.to_string());
+        // try_lock: the panic may have come from inside record().
+        let recent: Vec<Value> = match self.recent.try_lock() {
+            Ok(recent) => recent.iter().cloned().collect(),
+            Err(_) => Vec::new(),
+        };
+        let at_ms = now_ms();
+        let report = json!({
+            "worker": self.worker,
+            "message": message,
+            "location": location,
+            "thread": thread::current().name(),
+            "backtrace": Backtrace::force_capture().to_string(),
+            "config": self.config,
+            "recentRequests": recent,
+            "atMs": at_ms,
+        });
+
+        let path = self.dir.as_ref().and_then(|dir| {
+            let path = dir.join(format!("{}-{at_ms}.json", self.worker));
+            let written = std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&path, format!("{report:#}\n")));
+            match written {
+                Ok(()) => Some(path.display().to_string()),
+                Err(err) => {
+                    eprintln!("failed to write crash report {}: {err}", path.display());
+                    None
+                }
+            }
+        });
+        let error = format!("worker panicked: {message}");
+        eprintln!(
+            "{}",
+            json!({
+                "event": "workerCrashed",
+                "error": error,
+                "location": location,
+                "crashReport": path,
+                "atMs": at_ms,
+            })
+        );
+
+        if thread::current().id() != self.serving {
+            return;
+        }
+        let last_id = recent
+            .last()
+            .and_then(|summary| summary["id"].as_str())
+            .unwrap_or("unknown");
+        let frame = json!({
+            "id": last_id,
+            "ok": false,
+            "error": error,
+            "fatal": true,
+            "crashReport": path,
+        });
+        // try_lock: the panic may have come from inside answer_with().
+        if let Ok(answer) = self.answer.try_lock() {
+            answer(&frame);
+        }
+    }
+}
+
+// $DINGOFLOW_DATA_DIR, else ~/Library/Application Support/dingoflow on macOS,
+// %LOCALAPPDATA%\dingoflow on Windows, $XDG_DATA_HOME/dingoflow (or
+// ~/.local/share/dingoflow) elsewhere.
+fn crash_dir() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).map(PathBuf::from);
+    let data = match var("DINGOFLOW_DATA_DIR") {
+        Some(dir) => dir,
+        None if cfg!(target_os = "macos") => {
+            var("HOME")?.join("Library/Application Support/dingoflow")
+        }
+        None if cfg!(windows) => var("LOCALAPPDATA")?.join("dingoflow"),
+        None => var("XDG_DATA_HOME")
+            .or_else(|| Some(var("HOME")?.join(".local/share")))?
+            .join("dingoflow"),
+    };
+    Some(data.join("crashes"))
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
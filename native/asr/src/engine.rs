@@ -0,0 +1,315 @@
+use crate::align::TimedWord;
+use crate::backend::{normalize_text, AsrBackend, Decoded, TimedPiece};
+use crate::constrain::Constraint;
+use crate::context::{Context, ContextStore};
+use crate::script::ScriptTracker;
+use crate::stream::{StreamConfig, StreamUpdate, Streamer, Undone};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+pub struct Transcript {
+    pub text: String,
+    pub language: String,
+    pub duration_seconds: f64,
+    // End times relative to the start of the audio.
+    pub pieces: Vec<TimedPiece>,
+}
+
+// A backend plus its streaming state. Everything sample-rate related is
+// checked here so backends only ever see audio at their native rate.
+pub struct Engine<B: AsrBackend> {
+    pub backend: B,
+    streamer: Streamer,
+    // The worker's flags; a preset swaps the streamer's tuning until the
+    // next reset without one.
+    stream_config: StreamConfig,
+    tuned: bool,
+    contexts: ContextStore,
+    // Picked by stream_reset; applies to the stream and to transcribes that
+    // don't name their own.
+    context: Option<Context>,
+    // What the stream's latest decode was in, for backends that pick a
+    // language per window.
+    stream_language: Option<String>,
+    // Teleprompter mode, from a stream_reset that gave a script.
+    script: Option<ScriptTracker>,
+    // From a stream_reset that restricted the output; see constrain.rs.
+    constraint: Option<Constraint>,
+    // Time spent in the backend since the last take_decode_time.
+    decode_time: Duration,
+}
+
+// Runs the backend with a context's hotwords set and its replacements
+// applied, then any output constraint, so the streamer commits the result.
+struct WithContext<'a, B: AsrBackend> {
+    backend: &'a mut B,
+    context: Option<&'a Context>,
+    constraint: Option<&'a Constraint>,
+    decode_time: &'a mut Duration,
+}
+
+impl<B: AsrBackend> AsrBackend for WithContext<'_, B> {
+    fn describe(&self) -> Value {
+        self.backend.describe()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.backend.sample_rate()
+    }
+
+    fn warmup(&mut self) -> Result<(), String> {
+        self.backend.warmup()
+    }
+
+    fn decode(&mut self, audio: &[f32]) -> Result<Decoded, String> {
+        let started = Instant::now();
+        let decoded = self.backend.decode(audio);
+        *self.decode_time += started.elapsed();
+        let decoded = match self.context {
+            Some(context) => context.apply(decoded?),
+            None => decoded?,
+        };
+        Ok(match self.constraint {
+            Some(constraint) => constraint.apply(decoded),
+            None => decoded,
+        })
+    }
+}
+
+impl<B: AsrBackend> Engine<B> {
+    pub fn new(backend: B, stream: &StreamConfig) -> Self {
+        let streamer = Streamer::new(stream, backend.sample_rate());
+        Self {
+            backend,
+            streamer,
+            stream_config: stream.clone(),
+            tuned: false,
+            contexts: ContextStore::new(None),
+            context: None,
+            stream_language: None,
+            script: None,
+            constraint: None,
+            decode_time: Duration::ZERO,
+        }
+    }
+
+    pub fn with_contexts(mut self, contexts: ContextStore) -> Self {
+        self.contexts = contexts;
+        self
+    }
+
+    pub fn take_decode_time(&mut self) -> Duration {
+        std::mem::take(&mut self.decode_time)
+    }
+
+    pub fn stream_config(&self) -> &StreamConfig {
+        &self.stream_config
+    }
+
+    pub fn contexts(&self) -> &ContextStore {
+        &self.contexts
+    }
+
+    pub fn context(&self) -> Option<&Context> {
+        self.context.as_ref()
+    }
+
+    pub fn stream_language(&self) -> Option<&str> {
+        self.stream_language.as_deref()
+    }
+
+    pub fn script(&self) -> Option<&ScriptTracker> {
+        self.script.as_ref()
+    }
+
+    pub fn constraint(&self) -> Option<&Constraint> {
+        self.constraint.as_ref()
+    }
+
+    fn use_context(&mut self, context: Option<Context>) {
+        let hotwords = context.as_ref().map_or(&[][..], |c| &c.hotwords[..]);
+        self.backend.set_hotwords(hotwords);
+        self.context = context;
+    }
+
+    fn check_sample_rate(&self, sample_rate: u32) -> Result<(), String> {
+        let expected = self.backend.sample_rate();
+        if sample_rate != expected {
+            return Err(format!(
+                "sampleRate mismatch: expected {expected}, got {sample_rate}"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn warmup(&mut self) -> Result<(), String> {
+        self.backend.warmup()
+    }
+
+    pub fn transcribe(&mut self, audio: &[f32], sample_rate: u32) -> Result<Transcript, String> {
+        self.check_sample_rate(sample_rate)?;
+        let started = Instant::now();
+        let decoded = WithContext {
+            backend: &mut self.backend,
+            context: self.context.as_ref(),
+            constraint: self.constraint.as_ref(),
+            decode_time: &mut self.decode_time,
+        }
+        .decode(audio)?;
+        Ok(Transcript {
+            text: normalize_text(&decoded.text),
+            language: decoded.language,
+            duration_seconds: started.elapsed().as_secs_f64(),
+            pieces: decoded.pieces,
+        })
+    }
+
+    // A transcribe naming a context uses it for that request only.
+    pub fn transcribe_in(
+        &mut self,
+        audio: &[f32],
+        sample_rate: u32,
+        label: &str,
+    ) -> Result<Transcript, String> {
+        let context = self.contexts.load(Some(label))?;
+        let previous = self.context.take();
+        self.use_context(context);
+        let result = self.transcribe(audio, sample_rate);
+        self.use_context(previous);
+        result
+    }
+
+    // A transcribe restricting its output does so for that request only.
+    pub fn transcribe_constrained(
+        &mut self,
+        audio: &[f32],
+        sample_rate: u32,
+        label: Option<&str>,
+        constraint: Constraint,
+    ) -> Result<Transcript, String> {
+        let previous = self.constraint.replace(constraint);
+        let result = match label {
+            Some(label) => self.transcribe_in(audio, sample_rate, label),
+            None => self.transcribe(audio, sample_rate),
+        };
+        self.constraint = previous;
+        result
+    }
+
+    // Decodes with `script` as the only hotword, so backends that take a
+    // prompt keep its spellings and proper nouns, and returns the words heard
+    // with their start and end times.
+    pub fn align_words(
+        &mut self,
+        audio: &[f32],
+        sample_rate: u32,
+        script: &str,
+    ) -> Result<Vec<TimedWord>, String> {
+        self.check_sample_rate(sample_rate)?;
+        self.backend.set_hotwords(&[script.to_string()]);
+        let started = Instant::now();
+        let words = self
+            .backend
+            .decode(audio)
+            .and_then(|_| self.backend.timed_words());
+        self.decode_time += started.elapsed();
+        let hotwords = self.context.as_ref().map_or(&[][..], |c| &c.hotwords[..]);
+        self.backend.set_hotwords(hotwords);
+        words
+    }
+
+    // The context is re-read from disk on every reset, so edits to its file
+    // apply from the next utterance on.
+    pub fn stream_reset(&mut self, sample_rate: u32, label: Option<&str>) -> Result<(), String> {
+        self.check_sample_rate(sample_rate)?;
+        let context = self.contexts.load(label)?;
+        self.use_context(context);
+        self.streamer.reset();
+        self.stream_language = None;
+        self.script = None;
+        self.constraint = None;
+        Ok(())
+    }
+
+    // Streams with this tuning from here on; None goes back to the flags'.
+    // Call before stream_reset: a new streamer starts empty.
+    pub fn stream_tune(&mut self, config: Option<&StreamConfig>) {
+        if config.is_none() && !self.tuned {
+            return;
+        }
+        self.tuned = config.is_some();
+        let config = config.unwrap_or(&self.stream_config);
+        self.streamer = Streamer::new(config, self.backend.sample_rate());
+    }
+
+    // Follows the stream through `script` until the next reset.
+    pub fn stream_align(&mut self, script: &str) -> Result<(), String> {
+        self.script = Some(ScriptTracker::new(script)?);
+        Ok(())
+    }
+
+    // Restricts the stream's output until the next reset.
+    pub fn stream_constrain(&mut self, constraint: Constraint) {
+        self.constraint = Some(constraint);
+    }
+
+    pub fn stream_push(&mut self, audio: &[f32], sample_rate: u32) -> Result<StreamUpdate, String> {
+        self.check_sample_rate(sample_rate)?;
+        let mut backend = WithContext {
+            backend: &mut self.backend,
+            context: self.context.as_ref(),
+            constraint: self.constraint.as_ref(),
+            decode_time: &mut self.decode_time,
+        };
+        let mut language = None;
+        let update = self.streamer.push(
+            &mut |window| {
+                backend.decode(window).map(|decoded| {
+                    language = Some(decoded.language);
+                    decoded.pieces
+                })
+            },
+            audio,
+        )?;
+        // Pushes that didn't decode keep the last decode's language.
+        if language.is_some() {
+            self.stream_language = language;
+        }
+        // The unsettled tail too: a teleprompter can't wait for the hold.
+        if let Some(script) = self.script.as_mut() {
+            script.follow(&update.preview_text);
+        }
+        Ok(update)
+    }
+
+    pub fn stream_flush(&mut self) -> Result<StreamUpdate, String> {
+        let mut backend = WithContext {
+            backend: &mut self.backend,
+            context: self.context.as_ref(),
+            constraint: self.constraint.as_ref(),
+            decode_time: &mut self.decode_time,
+        };
+        let mut language = None;
+        let update = self.streamer.flush(&mut |window| {
+            backend.decode(window).map(|decoded| {
+                language = Some(decoded.language);
+                decoded.pieces
+            })
+        })?;
+        if language.is_some() {
+            self.stream_language = language;
+        }
+        if let Some(script) = self.script.as_mut() {
+            script.follow(&update.committed_text);
+        }
+        Ok(update)
+    }
+
+    pub fn stream_undo_last(&mut self) -> Option<Undone> {
+        self.streamer.undo_last()
+    }
+
+    pub fn stream_close(&mut self) {
+        self.streamer.close();
+    }
+}
@@ -0,0 +1,91 @@
+use crate::protocol::pcm16_to_f32;
+use dingoflow_frame::MAX_AUDIO_BYTES;
+use std::io::Read;
+use std::process::{Child, Command, Stdio};
+
+// Decodes anything ffmpeg can open (containers, http(s) URLs, RTSP) into mono
+// PCM16 at the backend's rate. Live sources never end on their own, so every
+// decode is capped at `max_seconds` and at what a framed request could carry.
+pub struct Source<'a> {
+    pub url: Option<&'a str>,
+    // argv of a program whose stdout ffmpeg decodes, e.g. a capture tool.
+    pub command: Option<&'a [String]>,
+    pub max_seconds: Option<f64>,
+}
+
+pub fn decode(source: &Source, sample_rate: u32) -> Result<Vec<f32>, String> {
+    let cap_seconds = (MAX_AUDIO_BYTES / 2) as f64 / sample_rate as f64;
+    let seconds = source
+        .max_seconds
+        .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+        .map_or(cap_seconds, |seconds| seconds.min(cap_seconds));
+
+    let mut producer = None;
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg.args(["-hide_banner", "-loglevel", "error"]);
+    match (source.url, source.command) {
+        (Some(url), None) => {
+            ffmpeg.args(["-nostdin", "-i", url]).stdin(Stdio::null());
+        }
+        (None, Some([program, args @ ..])) => {
+            let mut child = Command::new(program)
+                .args(args)
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|err| format!("failed to start audioCommand {program}: {err}"))?;
+            let stdout = child
+                .stdout
+                .take()
+                .ok_or("audioCommand stdout unavailable")?;
+            ffmpeg.args(["-i", "pipe:0"]).stdin(stdout);
+            producer = Some(child);
+        }
+        (None, Some([])) => return Err("audioCommand must not be empty".into()),
+        _ => return Err("use either audioUrl or audioCommand".into()),
+    }
+    let rate = sample_rate.to_string();
+    let duration = format!("{seconds:.3}");
+    let mut child = ffmpeg
+        .args(["-t", &duration])
+        .args(["-f", "s16le", "-ac", "1", "-ar", &rate, "pipe:1"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| format!("ffmpeg is not available: {err}"))?;
+
+    let result = read_pcm(&mut child);
+    if let Some(mut producer) = producer {
+        // ffmpeg stops reading at the cap; don't leave the source running.
+        let _ = producer.kill();
+        let _ = producer.wait();
+    }
+    result
+}
+
+fn read_pcm(child: &mut Child) -> Result<Vec<f32>, String> {
+    let mut pcm = Vec::new();
+    if let Some(mut stdout) = child.stdout.take() {
+        stdout
+            .read_to_end(&mut pcm)
+            .map_err(|err| format!("ffmpeg read failed: {err}"))?;
+    }
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child
+        .wait()
+        .map_err(|err| format!("ffmpeg failed: {err}"))?;
+    if !status.success() {
+        return Err(format!(
+            "ffmpeg could not decode the input: {}",
+            stderr.trim()
+        ));
+    }
+    if pcm.is_empty() {
+        return Err("ffmpeg produced no audio".into());
+    }
+    Ok(pcm16_to_f32(&pcm))
+}
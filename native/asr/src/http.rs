@@ -0,0 +1,593 @@
+use crate::backend::AsrBackend;
+use crate::engine::Engine;
+use crate::protocol::{pcm16_to_f32, wav_bytes_to_f32};
+use crate::telemetry::{log, LogLevel};
+use crate::transcribe::{self, TranscribeOptions};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// `--http-port`: a small OpenAI-compatible endpoint so tools that speak the
+// OpenAI audio API can point at the local worker. One request at a time, one
+// request per connection; bound to loopback only. Live partials come either
+// as SSE (`stream=true` on an upload) or over the realtime WebSocket, which
+// holds the engine until the client disconnects.
+const MAX_BODY_BYTES: usize = 128 * 1024 * 1024;
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+const SSE_CHUNK_MS: usize = 160;
+
+// What the HTTP mode needs from the engine; every Engine has it.
+pub trait Backend {
+    // The worker's usual result object (text, language, durationSeconds).
+    fn transcribe(&mut self, upload: &Upload) -> Result<Value, String>;
+    fn stream_reset(&mut self, sample_rate: u32) -> Result<(), String>;
+    // Returns the text committed so far.
+    fn stream_push(&mut self, audio: Vec<f32>, sample_rate: u32) -> Result<String, String>;
+    // Returns the final text.
+    fn stream_flush(&mut self) -> Result<String, String>;
+    fn stream_close(&mut self);
+}
+
+impl<B: AsrBackend> Backend for Engine<B> {
+    fn transcribe(&mut self, upload: &Upload) -> Result<Value, String> {
+        // OpenAI's `language` is a hint; here it pins detection to that one.
+        // The model's own language needs no detection, so English-only
+        // engines still take `language=en`.
+        let default_language = self.backend.describe()["language"]
+            .as_str()
+            .map(str::to_string);
+        let options = TranscribeOptions {
+            word_timestamps: upload.wants_words(),
+            language_candidates: upload
+                .field("language")
+                .filter(|language| Some(*language) != default_language.as_deref())
+                .map(|language| vec![language.to_string()])
+                .unwrap_or_default(),
+            ..TranscribeOptions::default()
+        };
+        let rate = upload.sample_rate;
+        transcribe::transcribe(self, &upload.audio, rate, &options, |engine, audio| {
+            engine.transcribe(audio, rate)
+        })
+    }
+
+    fn stream_reset(&mut self, sample_rate: u32) -> Result<(), String> {
+        Engine::stream_reset(self, sample_rate, None)
+    }
+
+    fn stream_push(&mut self, audio: Vec<f32>, sample_rate: u32) -> Result<String, String> {
+        Engine::stream_push(self, &audio, sample_rate).map(|update| update.committed_text)
+    }
+
+    fn stream_flush(&mut self) -> Result<String, String> {
+        Engine::stream_flush(self).map(|update| update.committed_text)
+    }
+
+    fn stream_close(&mut self) {
+        Engine::stream_close(self)
+    }
+}
+
+// OpenAI deltas only ever append, so only committed text is sent as a delta;
+// the preview tail can still change and is left for the final.
+#[derive(Default)]
+pub struct Deltas {
+    sent: String,
+}
+
+impl Deltas {
+    pub fn next(&mut self, committed: &str) -> Option<String> {
+        let added = committed.strip_prefix(self.sent.as_str())?;
+        if added.is_empty() {
+            return None;
+        }
+        let added = added.to_string();
+        self.sent = committed.to_string();
+        Some(added)
+    }
+}
+
+pub struct Upload {
+    pub audio: Vec<f32>,
+    pub sample_rate: u32,
+    pub fields: HashMap<String, String>,
+}
+
+impl Upload {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    // `timestamp_granularities[]=word`, as the OpenAI clients send it.
+    pub fn wants_words(&self) -> bool {
+        self.fields.iter().any(|(name, value)| {
+            name.starts_with("timestamp_granularities") && value.contains("word")
+        })
+    }
+}
+
+pub struct HttpError {
+    status: u16,
+    message: String,
+    param: Option<&'static str>,
+}
+
+impl HttpError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: 400,
+            message: message.into(),
+            param: None,
+        }
+    }
+
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            param: None,
+        }
+    }
+
+    fn param(mut self, param: &'static str) -> Self {
+        self.param = Some(param);
+        self
+    }
+}
+
+impl From<String> for HttpError {
+    fn from(message: String) -> Self {
+        Self::new(500, message)
+    }
+}
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+type Reply = (u16, &'static str, Vec<u8>);
+
+pub fn serve(port: u16, model_id: &str, backend: &mut dyn Backend) -> Result<(), String> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|err| format!("failed to listen on 127.0.0.1:{port}: {err}"))?;
+    log(
+        LogLevel::Info,
+        "listening",
+        json!({ "endpoint": format!("http://127.0.0.1:{port}/v1/transcriptions") }),
+    );
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+        let reply = match read_request(&mut stream) {
+            Ok(request) => route(&mut stream, &request, model_id, backend),
+            Err(error) => Err(error),
+        };
+        let (status, content_type, body) = match reply {
+            Ok(Some(reply)) => reply,
+            // Streamed replies have already been written.
+            Ok(None) => continue,
+            Err(error) => error_reply(&error),
+        };
+        let _ = write_response(&mut stream, status, content_type, &body);
+    }
+    Ok(())
+}
+
+fn route(
+    stream: &mut TcpStream,
+    request: &HttpRequest,
+    model_id: &str,
+    backend: &mut dyn Backend,
+) -> Result<Option<Reply>, HttpError> {
+    let path = request.path.split('?').next().unwrap_or_default();
+    match (request.method.as_str(), path) {
+        ("GET", "/v1/realtime") => {
+            let upgrade = request.headers.get("upgrade").map(String::as_str);
+            let key = request.headers.get("sec-websocket-key");
+            let (Some(true), Some(key)) =
+                (upgrade.map(|u| u.eq_ignore_ascii_case("websocket")), key)
+            else {
+                return Err(HttpError::new(
+                    426,
+                    "/v1/realtime requires a WebSocket upgrade",
+                ));
+            };
+            let _ = stream.set_read_timeout(None);
+            crate::realtime::session(stream, key, model_id, backend).map_err(HttpError::from)?;
+            Ok(None)
+        }
+        ("POST", "/v1/transcriptions" | "/v1/audio/transcriptions") => {
+            let upload = parse_upload(request)?;
+            if upload.field("stream") == Some("true") {
+                stream_upload(stream, &upload, backend)?;
+                return Ok(None);
+            }
+            let format = upload
+                .field("response_format")
+                .unwrap_or("json")
+                .to_string();
+            if !matches!(format.as_str(), "json" | "text" | "verbose_json") {
+                return Err(HttpError::bad_request(format!(
+                    "response_format {format} is not supported (use json, text or verbose_json)"
+                ))
+                .param("response_format"));
+            }
+            let result = backend.transcribe(&upload)?;
+            Ok(Some(format_result(&result, &format, &upload)))
+        }
+        ("GET", "/v1/models") => {
+            let body = json!({
+                "object": "list",
+                "data": [{ "id": model_id, "object": "model", "owned_by": "dingoflow" }],
+            });
+            Ok(Some((
+                200,
+                "application/json",
+                body.to_string().into_bytes(),
+            )))
+        }
+        ("GET", "/health") => Ok(Some((200, "text/plain", b"ok".to_vec()))),
+        (
+            _,
+            "/v1/transcriptions"
+            | "/v1/audio/transcriptions"
+            | "/v1/models"
+            | "/v1/realtime"
+            | "/health",
+        ) => Err(HttpError::new(405, "method not allowed")),
+        _ => Err(HttpError::new(404, format!("no route for {path}"))),
+    }
+}
+
+// OpenAI's streamed transcription: `transcript.text.delta` events as the
+// engine commits text, then `transcript.text.done` with the whole transcript.
+fn stream_upload(
+    stream: &mut TcpStream,
+    upload: &Upload,
+    backend: &mut dyn Backend,
+) -> Result<(), HttpError> {
+    backend.stream_reset(upload.sample_rate)?;
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    if stream.write_all(head.as_bytes()).is_err() {
+        backend.stream_close();
+        return Ok(());
+    }
+
+    let chunk = (upload.sample_rate as usize * SSE_CHUNK_MS / 1000).max(1);
+    let mut deltas = Deltas::default();
+    let send = |stream: &mut TcpStream, event: Value| {
+        stream.write_all(format!("data: {event}\n\n").as_bytes())
+    };
+    for audio in upload.audio.chunks(chunk) {
+        let committed = match backend.stream_push(audio.to_vec(), upload.sample_rate) {
+            Ok(committed) => committed,
+            Err(error) => {
+                backend.stream_close();
+                let _ = send(
+                    stream,
+                    json!({ "type": "error", "error": { "message": error } }),
+                );
+                return Ok(());
+            }
+        };
+        if let Some(delta) = deltas.next(&committed) {
+            if send(
+                stream,
+                json!({ "type": "transcript.text.delta", "delta": delta }),
+            )
+            .is_err()
+            {
+                backend.stream_close();
+                return Ok(());
+            }
+        }
+    }
+    let event = match backend.stream_flush() {
+        Ok(text) => {
+            if let Some(delta) = deltas.next(&text) {
+                let _ = send(
+                    stream,
+                    json!({ "type": "transcript.text.delta", "delta": delta }),
+                );
+            }
+            json!({ "type": "transcript.text.done", "text": text })
+        }
+        Err(error) => json!({ "type": "error", "error": { "message": error } }),
+    };
+    let _ = send(stream, event);
+    let _ = stream.flush();
+    Ok(())
+}
+
+fn format_result(result: &Value, format: &str, upload: &Upload) -> Reply {
+    let text = result["text"].as_str().unwrap_or_default();
+    match format {
+        "text" => (
+            200,
+            "text/plain; charset=utf-8",
+            format!("{text}\n").into_bytes(),
+        ),
+        "verbose_json" => {
+            let duration = result["audioSeconds"]
+                .as_f64()
+                .unwrap_or(upload.audio.len() as f64 / upload.sample_rate.max(1) as f64);
+            let mut body = json!({
+                "task": "transcribe",
+                "language": result["language"].as_str().unwrap_or("en"),
+                "duration": duration,
+                "text": text,
+                "segments": [{
+                    "id": 0,
+                    "seek": 0,
+                    "start": 0.0,
+                    "end": duration,
+                    "text": text,
+                }],
+            });
+            if let Some(words) = result.get("words").filter(|words| words.is_array()) {
+                body["words"] = words.clone();
+            }
+            (200, "application/json", body.to_string().into_bytes())
+        }
+        _ => (
+            200,
+            "application/json",
+            json!({ "text": text }).to_string().into_bytes(),
+        ),
+    }
+}
+
+fn error_reply(error: &HttpError) -> Reply {
+    let kind = if error.status >= 500 {
+        "server_error"
+    } else {
+        "invalid_request_error"
+    };
+    let body = json!({
+        "error": { "message": error.message, "type": kind, "param": error.param, "code": null }
+    });
+    (
+        error.status,
+        "application/json",
+        body.to_string().into_bytes(),
+    )
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<HttpRequest, HttpError> {
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let mut head = Vec::new();
+    loop {
+        let mut line = Vec::new();
+        reader
+            .read_until(b'\n', &mut line)
+            .map_err(|e| HttpError::bad_request(format!("failed to read request: {e}")))?;
+        if line.is_empty() || line == b"\r\n" || line == b"\n" {
+            break;
+        }
+        head.extend_from_slice(&line);
+        if head.len() > MAX_HEADER_BYTES {
+            return Err(HttpError::new(431, "request headers too large"));
+        }
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    if method.is_empty() || path.is_empty() {
+        return Err(HttpError::bad_request("malformed request line"));
+    }
+    let headers: HashMap<String, String> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    if headers
+        .get("transfer-encoding")
+        .is_some_and(|value| value.eq_ignore_ascii_case("chunked"))
+    {
+        return Err(HttpError::new(
+            411,
+            "chunked request bodies are not supported",
+        ));
+    }
+    let length = match headers.get("content-length") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| HttpError::bad_request("invalid Content-Length"))?,
+        None => 0,
+    };
+    if length > MAX_BODY_BYTES {
+        return Err(HttpError::new(413, "request body too large"));
+    }
+    // curl asks before sending uploads over 1 MB.
+    if headers
+        .get("expect")
+        .is_some_and(|value| value.eq_ignore_ascii_case("100-continue"))
+    {
+        let _ = stream.write_all(b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+    let mut body = vec![0_u8; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| HttpError::bad_request(format!("failed to read request body: {e}")))?;
+
+    Ok(HttpRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        426 => "Upgrade Required",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    };
+    let head = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+fn parse_upload(request: &HttpRequest) -> Result<Upload, HttpError> {
+    let content_type = request
+        .headers
+        .get("content-type")
+        .map(String::as_str)
+        .unwrap_or_default();
+    let boundary = content_type
+        .split(';')
+        .map(str::trim)
+        .find_map(|part| part.strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"'))
+        .filter(|_| content_type.starts_with("multipart/form-data"))
+        .ok_or_else(|| HttpError::bad_request("expected a multipart/form-data body"))?;
+
+    let mut fields = HashMap::new();
+    let mut file: Option<(Option<String>, Vec<u8>)> = None;
+    for part in multipart_parts(&request.body, boundary)? {
+        match part.name.as_str() {
+            "file" => file = Some((part.filename, part.data)),
+            name => {
+                fields.insert(
+                    name.to_string(),
+                    String::from_utf8_lossy(&part.data).trim().to_string(),
+                );
+            }
+        }
+    }
+    let (filename, data) =
+        file.ok_or_else(|| HttpError::bad_request("missing file field").param("file"))?;
+    let (audio, sample_rate) = decode_upload(&data, filename.as_deref())
+        .map_err(|message| HttpError::bad_request(message).param("file"))?;
+    Ok(Upload {
+        audio,
+        sample_rate,
+        fields,
+    })
+}
+
+struct Part {
+    name: String,
+    filename: Option<String>,
+    data: Vec<u8>,
+}
+
+fn multipart_parts(body: &[u8], boundary: &str) -> Result<Vec<Part>, HttpError> {
+    let delimiter = format!("--{boundary}").into_bytes();
+    let mut parts = Vec::new();
+    let mut rest = match find(body, &delimiter) {
+        Some(start) => &body[start + delimiter.len()..],
+        None => return Err(HttpError::bad_request("multipart boundary not found")),
+    };
+    loop {
+        // `--` right after a delimiter closes the body.
+        if rest.starts_with(b"--") {
+            break;
+        }
+        rest = rest.strip_prefix(b"\r\n").unwrap_or(rest);
+        let header_end = find(rest, b"\r\n\r\n")
+            .ok_or_else(|| HttpError::bad_request("malformed multipart part"))?;
+        let headers = String::from_utf8_lossy(&rest[..header_end]).to_string();
+        let content = &rest[header_end + 4..];
+        let end = find(content, &[b"\r\n".as_slice(), &delimiter].concat())
+            .ok_or_else(|| HttpError::bad_request("unterminated multipart part"))?;
+
+        let disposition = headers
+            .lines()
+            .find(|line| {
+                line.to_ascii_lowercase()
+                    .starts_with("content-disposition:")
+            })
+            .unwrap_or_default();
+        if let Some(name) = disposition_param(disposition, "name") {
+            parts.push(Part {
+                name,
+                filename: disposition_param(disposition, "filename"),
+                data: content[..end].to_vec(),
+            });
+        }
+        rest = &content[end + 2 + delimiter.len()..];
+    }
+    Ok(parts)
+}
+
+fn disposition_param(disposition: &str, key: &str) -> Option<String> {
+    disposition.split(';').map(str::trim).find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        (name.trim() == key).then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+// WAV is read directly; anything else (mp3, m4a, webm, ...) goes through
+// ffmpeg when it's installed, as the host's recorder already relies on it.
+fn decode_upload(data: &[u8], filename: Option<&str>) -> Result<(Vec<f32>, u32), String> {
+    if data.starts_with(b"RIFF") {
+        return wav_bytes_to_f32(data);
+    }
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-loglevel", "error", "-i", "pipe:0"])
+        .args(["-f", "s16le", "-ac", "1", "-ar", "16000", "pipe:1"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| {
+            format!(
+                "{} is not WAV and ffmpeg is not available to decode it",
+                filename.unwrap_or("the upload")
+            )
+        })?;
+    let mut stdin = child.stdin.take().ok_or("ffmpeg stdin unavailable")?;
+    let input = data.to_vec();
+    let feeder = std::thread::spawn(move || {
+        let _ = stdin.write_all(&input);
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|err| format!("ffmpeg failed: {err}"))?;
+    let _ = feeder.join();
+    if !output.status.success() {
+        return Err(format!(
+            "could not decode {}: {}",
+            filename.unwrap_or("the upload"),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok((pcm16_to_f32(&output.stdout), 16_000))
+}
@@ -0,0 +1,84 @@
+use dingoflow_frame::{read_response, write_frame, MAX_JSON_BYTES};
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// Runs one batch decode in a fresh copy of this worker so that a crash inside
+// the engine (e.g. ggml on a corrupted file) only loses that file, not the
+// server. Each child reloads the model, trading throughput for isolation.
+pub struct IsolatedDecoder {
+    pub exe: PathBuf,
+    // Flags that load the same model in the child (--model, and --backend
+    // where the worker has a choice); --threads and --serve are added.
+    pub args: Vec<String>,
+    pub threads: i32,
+}
+
+impl IsolatedDecoder {
+    pub fn new(args: Vec<String>, threads: i32) -> Result<Self, String> {
+        let exe = std::env::current_exe()
+            .map_err(|err| format!("failed to resolve worker executable: {err}"))?;
+
+        Ok(Self { exe, args, threads })
+    }
+
+    // `request` carries the batch's per-request options; `share` splits the
+    // threads as in AsrBackend::fork.
+    pub fn transcribe_file(
+        &self,
+        path: &str,
+        share: usize,
+        request: &serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        let threads = (self.threads / share.max(1) as i32).max(1);
+        let mut child = Command::new(&self.exe)
+            .args(&self.args)
+            .args(["--threads", &threads.to_string(), "--serve"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| format!("failed to spawn isolated decoder: {err}"))?;
+
+        let mut request = request.clone();
+        request["id"] = json!("isolated");
+        request["action"] = json!("transcribe");
+        request["audio"] = json!(path);
+        let body = serde_json::to_vec(&request)
+            .map_err(|err| format!("failed to encode isolated request: {err}"))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // Closing stdin afterwards makes the child exit once it has answered.
+            if let Err(err) = write_frame(&mut stdin, &body, &[]) {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("failed to send isolated request: {err}"));
+            }
+        }
+
+        let response = child
+            .stdout
+            .take()
+            .and_then(|mut stdout| read_response(&mut stdout, MAX_JSON_BYTES).ok().flatten());
+        let status = child
+            .wait()
+            .map_err(|err| format!("failed to wait for isolated decoder: {err}"))?;
+
+        let Some(response) = response else {
+            return Err(format!(
+                "isolated decoder exited without a response ({status})"
+            ));
+        };
+        let response: serde_json::Value = serde_json::from_slice(&response)
+            .map_err(|err| format!("isolated decoder returned invalid JSON: {err}"))?;
+
+        if response["ok"].as_bool() == Some(true) {
+            Ok(response["result"].clone())
+        } else {
+            Err(response["error"]
+                .as_str()
+                .unwrap_or("isolated decode failed")
+                .to_string())
+        }
+    }
+}
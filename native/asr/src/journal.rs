@@ -0,0 +1,99 @@
+use crate::stream::{Commit, Undone};
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Appends every committed stream delta to a JSONL file as it happens, so a
+// transcript survives a host crash and other tools can `tail -f` it:
+//
+//   {"event":"reset","streamId":"...","atMs":...}
+//   {"event":"commit","streamId":"...","commitId":3,"text":"...",
+//    "commitStart":..,"commitEnd":..,"audioStartMs":..,"audioEndMs":..,
+//    "language":"en","atMs":...}
+//   {"event":"undo","streamId":"...","commitId":3,"text":"...","atMs":...}
+//
+// Each line is written with a single unbuffered write, so a crash never
+// leaves half an entry behind.
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    stream_id: Option<String>,
+    // Names streams the host didn't, unique per worker process.
+    streams: u64,
+}
+
+impl Journal {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| format!("failed to open transcript {}: {err}", path.display()))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            stream_id: None,
+            streams: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Starts a new stream and returns its id.
+    pub fn reset(&mut self, stream_id: Option<&str>) -> Result<String, String> {
+        self.streams += 1;
+        let stream_id = match stream_id {
+            Some(id) => id.to_string(),
+            None => format!("{}-{}", std::process::id(), self.streams),
+        };
+        self.stream_id = Some(stream_id.clone());
+        self.write("reset", json!({}))?;
+        Ok(stream_id)
+    }
+
+    pub fn commit(&mut self, commit: &Commit, text: &str, language: &str) -> Result<(), String> {
+        self.write(
+            "commit",
+            json!({
+                "commitId": commit.id,
+                "text": text,
+                "commitStart": commit.start,
+                "commitEnd": commit.end,
+                "audioStartMs": commit.audio_start_ms,
+                "audioEndMs": commit.audio_end_ms,
+                "language": language,
+            }),
+        )
+    }
+
+    pub fn undo(&mut self, undone: &Undone) -> Result<(), String> {
+        self.write(
+            "undo",
+            json!({ "commitId": undone.commit.id, "text": undone.text }),
+        )
+    }
+
+    fn write(&mut self, event: &str, fields: Value) -> Result<(), String> {
+        let Value::Object(fields) = fields else {
+            return Ok(());
+        };
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let mut entry = json!({ "event": event, "streamId": self.stream_id });
+        if let Some(entry) = entry.as_object_mut() {
+            entry.extend(fields);
+            entry.insert("atMs".into(), at_ms.into());
+        }
+        let mut line = entry.to_string();
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|err| format!("failed to append to {}: {err}", self.path.display()))
+    }
+}
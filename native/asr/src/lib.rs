@@ -0,0 +1,38 @@
+pub mod align;
+pub mod backend;
+pub mod batch;
+pub mod channels;
+#[cfg(any(feature = "parakeet", feature = "whisper"))]
+pub mod cli;
+pub mod constrain;
+pub mod context;
+pub mod crash;
+pub mod engine;
+pub mod ffmpeg;
+pub mod http;
+pub mod isolate;
+pub mod journal;
+pub mod limits;
+pub mod macros;
+#[cfg(feature = "mic")]
+pub mod mic;
+#[cfg(feature = "parakeet")]
+pub mod parakeet;
+pub mod protocol;
+pub mod realtime;
+pub mod redact;
+pub mod schema;
+pub mod script;
+pub mod settings;
+pub mod silence;
+pub mod soak;
+// The stabilization machine is its own crate so other engines can reuse it;
+// re-exported here so workers keep importing dingoflow_asr::stream.
+pub use dingoflow_streaming as stream;
+pub mod telemetry;
+pub mod trace;
+pub mod transcribe;
+pub mod transport;
+pub mod turns;
+#[cfg(feature = "whisper")]
+pub mod whisper;
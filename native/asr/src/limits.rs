@@ -0,0 +1,136 @@
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+pub const DEFAULT_CLIENT_MAX_IN_FLIGHT: usize = 8;
+pub const DEFAULT_CLIENT_MAX_AUDIO_BYTES_PER_SEC: u64 = 1024 * 1024;
+pub const MAX_CLIENT_IN_FLIGHT: usize = 1024;
+
+// What one --listen client may ask of the shared model, so a client that
+// floods requests can't starve another client's dictation stream.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientLimits {
+    pub max_in_flight: usize,
+    // Counts the JSON too, since audioBase64 carries audio there. 0 disables
+    // the cap.
+    pub max_audio_bytes_per_sec: u64,
+}
+
+impl Default for ClientLimits {
+    fn default() -> Self {
+        Self {
+            max_in_flight: DEFAULT_CLIENT_MAX_IN_FLIGHT,
+            max_audio_bytes_per_sec: DEFAULT_CLIENT_MAX_AUDIO_BYTES_PER_SEC,
+        }
+    }
+}
+
+// Held while an admitted request is queued or decoding.
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// A request turned away before it reached the decoder.
+pub struct Limited {
+    pub id: Option<String>,
+    pub action: Option<String>,
+    pub error: String,
+    pub retry_after_ms: Option<u64>,
+}
+
+impl Limited {
+    // Shaped like any failed request, plus an HTTP-style code and when the
+    // client may try again (absent when that's "once an answer arrives").
+    pub fn response(&self) -> serde_json::Value {
+        let mut response = json!({
+            "id": self.id.as_deref().unwrap_or("unknown"),
+            "ok": false,
+            "error": self.error,
+            "code": 429
+        });
+        if let Some(retry_after_ms) = self.retry_after_ms {
+            response["retryAfterMs"] = json!(retry_after_ms);
+        }
+        response
+    }
+}
+
+#[derive(Deserialize)]
+struct Peek {
+    id: Option<String>,
+    action: Option<String>,
+}
+
+// Per-connection admission: an in-flight cap plus a token bucket over request
+// bytes that holds one second's worth. A request bigger than the bucket still
+// gets in when it's full; the debt just delays the next one.
+pub struct ClientLimiter {
+    limits: ClientLimits,
+    in_flight: Arc<AtomicUsize>,
+    budget: f64,
+    refilled_at: Instant,
+}
+
+impl ClientLimiter {
+    pub fn new(limits: ClientLimits) -> Self {
+        Self {
+            limits,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            budget: limits.max_audio_bytes_per_sec as f64,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    pub fn admit(&mut self, json: &[u8], audio_len: usize) -> Result<InFlight, Limited> {
+        let rate = self.limits.max_audio_bytes_per_sec as f64;
+        let now = Instant::now();
+        self.budget =
+            (self.budget + now.duration_since(self.refilled_at).as_secs_f64() * rate).min(rate);
+        self.refilled_at = now;
+
+        if self.in_flight.load(Ordering::SeqCst) >= self.limits.max_in_flight {
+            return Err(limited(
+                json,
+                format!(
+                    "too many requests in flight (max {})",
+                    self.limits.max_in_flight
+                ),
+                None,
+            ));
+        }
+
+        if rate > 0.0 {
+            if self.budget <= 0.0 {
+                let retry_after_ms = (-self.budget / rate * 1000.0).ceil() as u64;
+                return Err(limited(
+                    json,
+                    format!(
+                        "audio rate limit exceeded ({} bytes/s)",
+                        self.limits.max_audio_bytes_per_sec
+                    ),
+                    Some(retry_after_ms.max(1)),
+                ));
+            }
+            self.budget -= (json.len() + audio_len) as f64;
+        }
+
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        Ok(InFlight(Arc::clone(&self.in_flight)))
+    }
+}
+
+fn limited(json: &[u8], error: String, retry_after_ms: Option<u64>) -> Limited {
+    let peek = serde_json::from_slice::<Peek>(json).ok();
+    Limited {
+        id: peek.as_ref().and_then(|peek| peek.id.clone()),
+        action: peek.and_then(|peek| peek.action),
+        error,
+        retry_after_ms,
+    }
+}
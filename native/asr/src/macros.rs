@@ -0,0 +1,126 @@
+use crate::stream::confidence::word_key;
+use serde_json::{json, Value};
+use std::path::Path;
+
+// Dictation macros: saying a trigger phrase ("insert signature") inserts a
+// prepared snippet instead of the words. The worker only spots the trigger in
+// committed text; the host replaces it and fills the `{name}` placeholders,
+// since things like the date or the cursor position are the host's to know.
+
+struct Macro {
+    trigger: String,
+    keys: Vec<String>,
+    expansion: String,
+    placeholders: Vec<String>,
+}
+
+pub struct Macros {
+    // Longest trigger first, so "insert signature short" beats "insert signature".
+    macros: Vec<Macro>,
+}
+
+impl Macros {
+    // A JSON object of trigger phrase -> snippet, given as a string or as an
+    // array of lines.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read macros {}: {err}", path.display()))?;
+        let parsed: Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("Invalid macros {}: {err}", path.display()))?;
+        let Some(entries) = parsed.as_object() else {
+            return Err(format!(
+                "Invalid macros {}: expected an object of trigger -> snippet",
+                path.display()
+            ));
+        };
+
+        let mut macros = Vec::new();
+        for (trigger, snippet) in entries {
+            let expansion = match snippet {
+                Value::String(text) => text.clone(),
+                Value::Array(lines) => lines
+                    .iter()
+                    .map(|line| line.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or_else(|| format!("Macro {trigger:?}: lines must be strings"))?
+                    .join("\n"),
+                _ => return Err(format!("Macro {trigger:?}: expected a string or lines")),
+            };
+            let keys: Vec<String> = trigger.split_whitespace().filter_map(word_key).collect();
+            if keys.is_empty() {
+                return Err(format!("Macro {trigger:?}: trigger has no words"));
+            }
+            macros.push(Macro {
+                trigger: trigger.clone(),
+                keys,
+                placeholders: placeholders(&expansion),
+                expansion,
+            });
+        }
+        macros.sort_by_key(|found| std::cmp::Reverse(found.keys.len()));
+        Ok(Self { macros })
+    }
+
+    // The macro whose trigger ends the committed text, if its last word was
+    // committed at or after `delta_start` (a character offset, like a commit's
+    // start). `start`/`end` are the trigger's character range to replace.
+    pub fn find(&self, committed_text: &str, delta_start: usize) -> Option<Value> {
+        let chars: Vec<char> = committed_text.chars().collect();
+        let mut words: Vec<(usize, usize, String)> = Vec::new();
+        let mut word_start = None;
+        for index in 0..=chars.len() {
+            let blank = chars.get(index).is_none_or(|ch| ch.is_whitespace());
+            match (blank, word_start) {
+                (false, None) => word_start = Some(index),
+                (true, Some(start)) => {
+                    let word: String = chars[start..index].iter().collect();
+                    if let Some(key) = word_key(&word) {
+                        words.push((start, index, key));
+                    }
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+
+        let (_, end, _) = words.last()?;
+        if *end <= delta_start {
+            return None;
+        }
+        self.macros.iter().find_map(|found| {
+            let tail = &words[words.len().checked_sub(found.keys.len())?..];
+            if !tail.iter().map(|(_, _, key)| key).eq(found.keys.iter()) {
+                return None;
+            }
+            Some(json!({
+                "trigger": found.trigger,
+                "expansion": found.expansion,
+                "placeholders": found.placeholders,
+                "start": tail[0].0,
+                "end": end,
+            }))
+        })
+    }
+}
+
+// `{name}` placeholders in order of first use.
+fn placeholders(expansion: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = expansion;
+    while let Some(open) = rest.find('{') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find('}') else {
+            break;
+        };
+        let name = &rest[..close];
+        if !name.is_empty()
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+            && !names.iter().any(|known| known == name)
+        {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
@@ -0,0 +1,3 @@
+fn main() {
+    dingoflow_asr::cli::main("dingoflow-asr", None);
+}
@@ -0,0 +1,90 @@
+use crate::backend::AsrBackend;
+use crate::engine::Engine;
+use crate::stream::ConfidenceEvent;
+use dingoflow_audio::capture::MonoCapture;
+use dingoflow_audio::source::HostSelection;
+use std::io::{self, Write};
+use std::sync::mpsc;
+use std::time::Duration;
+
+// Capture straight into the streaming engine, with no host and no IPC in
+// between: the worker's --mic mode and dingoflow-dictate both run this.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+// Streams the mic until Ctrl+C, handing every committed delta to `commit`
+// along with whether it's the first one. Returns whether anything was
+// committed.
+pub fn run_mic<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    device: Option<&str>,
+    mut commit: impl FnMut(&str, bool) -> Result<(), String>,
+) -> Result<bool, String> {
+    let sample_rate = engine.backend.sample_rate();
+    engine.warmup()?;
+    engine.stream_reset(sample_rate, None)?;
+
+    let (stop_tx, stop_rx) = mpsc::channel::<()>();
+    ctrlc::set_handler(move || {
+        let _ = stop_tx.send(());
+    })
+    .map_err(|e| format!("failed to install signal handler: {e}"))?;
+
+    let mut capture = MonoCapture::open(&HostSelection::default_host(), device, sample_rate)?;
+    eprintln!(
+        "Listening on {} ({} Hz). Press Ctrl+C to stop.",
+        capture.device_name(),
+        capture.input_sample_rate()
+    );
+
+    let mut audio = Vec::<f32>::new();
+    let mut wrote_any = false;
+    loop {
+        if stop_rx.try_recv().is_ok() {
+            break;
+        }
+
+        // Everything that piled up during the last decode goes in one push.
+        capture.recv_timeout(POLL_INTERVAL, &mut audio)?;
+        if audio.is_empty() {
+            continue;
+        }
+
+        let update = engine.stream_push(&audio, sample_rate)?;
+        audio.clear();
+        match update.confidence_event {
+            Some(ConfidenceEvent::Low { average, below_ms }) => eprintln!(
+                "\nLow confidence for {:.1}s (agreement {average:.2}): check the mic and the spoken language.",
+                below_ms as f64 / 1000.0
+            ),
+            Some(ConfidenceEvent::Recovered { .. }) => eprintln!("\nConfidence recovered."),
+            None => {}
+        }
+        let delta = update.text;
+        if !delta.is_empty() {
+            commit(&delta, !wrote_any)?;
+            wrote_any = true;
+        }
+    }
+
+    let delta = engine.stream_flush()?.text;
+    if !delta.is_empty() {
+        commit(&delta, !wrote_any)?;
+        wrote_any = true;
+    }
+    engine.stream_close();
+    Ok(wrote_any)
+}
+
+// Appends a delta to stdout as running text.
+pub fn print_commit(delta: &str, first: bool) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    if !first {
+        stdout
+            .write_all(b" ")
+            .map_err(|e| format!("failed to write text: {e}"))?;
+    }
+    stdout
+        .write_all(delta.as_bytes())
+        .and_then(|_| stdout.flush())
+        .map_err(|e| format!("failed to write text: {e}"))
+}
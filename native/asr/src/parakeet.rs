@@ -0,0 +1,96 @@
+use crate::backend::{AsrBackend, Decoded, TimedPiece};
+use parakeet_rs::{ExecutionConfig, ParakeetTDT, TimestampMode, Transcriber};
+use serde_json::{json, Value};
+use std::path::Path;
+
+pub const SAMPLE_RATE: u32 = 16_000;
+
+pub fn check_model_dir(model_path: &str) -> Result<(), String> {
+    let path = Path::new(model_path);
+    if !path.exists() {
+        return Err(format!("Parakeet model path not found: {model_path}"));
+    }
+
+    if !path.is_dir() {
+        return Err(
+            "Native Parakeet backend expects DINGOFLOW_ASR_MODEL_PATH to be a model directory."
+                .into(),
+        );
+    }
+
+    let encoder = path.join("encoder-model.onnx");
+    let encoder_alt = path.join("encoder.onnx");
+    let decoder_joint = path.join("decoder_joint-model.onnx");
+    let decoder_joint_alt = path.join("decoder_joint.onnx");
+    let vocab = path.join("vocab.txt");
+    if (!encoder.exists() && !encoder_alt.exists())
+        || (!decoder_joint.exists() && !decoder_joint_alt.exists())
+        || !vocab.exists()
+    {
+        return Err(format!(
+            "Parakeet native model directory must contain encoder-model.onnx (or encoder.onnx), decoder_joint-model.onnx (or decoder_joint.onnx), and vocab.txt: {model_path}"
+        ));
+    }
+
+    Ok(())
+}
+
+pub struct ParakeetBackend {
+    tdt: ParakeetTDT,
+}
+
+impl ParakeetBackend {
+    pub fn load(model_path: &str, threads: i32) -> Result<Self, String> {
+        let exec_config = ExecutionConfig::new()
+            .with_intra_threads(threads.max(1) as usize)
+            .with_inter_threads(1);
+
+        let tdt = ParakeetTDT::from_pretrained(model_path, Some(exec_config))
+            .map_err(|err| format!("failed to load native Parakeet TDT model: {err}"))?;
+        Ok(Self { tdt })
+    }
+}
+
+impl AsrBackend for ParakeetBackend {
+    fn describe(&self) -> Value {
+        json!({ "engine": "parakeet", "language": "en" })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn warmup(&mut self) -> Result<(), String> {
+        // Tiny warmup decode to pre-initialize ONNX kernels.
+        self.tdt
+            .transcribe_samples(
+                vec![0.0_f32; 1024],
+                SAMPLE_RATE,
+                1,
+                Some(TimestampMode::Words),
+            )
+            .map_err(|err| format!("native Parakeet warmup failed: {err}"))?;
+        Ok(())
+    }
+
+    fn decode(&mut self, audio: &[f32]) -> Result<Decoded, String> {
+        let result = self
+            .tdt
+            .transcribe_samples(audio.to_vec(), SAMPLE_RATE, 1, Some(TimestampMode::Words))
+            .map_err(|err| format!("native Parakeet transcribe failed: {err}"))?;
+
+        Ok(Decoded {
+            text: result.text,
+            language: "en".to_string(),
+            pieces: result
+                .tokens
+                .into_iter()
+                .map(|token| TimedPiece {
+                    text: token.text,
+                    end_seconds: token.end,
+                    confidence: None,
+                })
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,1125 @@
+use crate::backend::AsrBackend;
+use crate::batch::Batch;
+use crate::channels;
+use crate::constrain::Constraint;
+use crate::crash::CrashLog;
+use crate::engine::Engine;
+use crate::ffmpeg;
+use crate::isolate::IsolatedDecoder;
+use crate::journal::Journal;
+use crate::limits::{ClientLimiter, ClientLimits, InFlight, Limited};
+use crate::macros::Macros;
+use crate::script::ScriptTracker;
+use crate::settings::{Preset, Settings};
+use crate::stream::{ConfidenceEvent, StreamToken, StreamUpdate};
+use crate::telemetry::{log, LogLevel, Metrics};
+use crate::trace::FrameTrace;
+use crate::transcribe::{self, TranscribeOptions};
+use crate::transport::{ListenEndpoint, Listener};
+use crate::turns::{Priority, Turns};
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine as _;
+use dingoflow_frame::{read_frame, write_response, Frame};
+use hound::{SampleFormat, WavReader};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, Cursor, Read, Write};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const PROTOCOL_VERSION: u32 = 1;
+// Past this many decimals, rounding an f64 changes nothing but the cost.
+const MAX_PRECISION: u32 = 9;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    pub id: Option<String>,
+    pub action: Option<String>,
+    pub audio: Option<String>,
+    pub audio_base64: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub context: Option<String>,
+    pub stream_id: Option<String>,
+    pub audio_url: Option<String>,
+    pub audio_command: Option<Vec<String>>,
+    pub audio_max_seconds: Option<f64>,
+    // Decode each channel of a stereo wav on its own; see channels.rs.
+    pub split_channels: Option<bool>,
+    // stream_reset: follow this script instead of transcribing; see script.rs.
+    pub script: Option<String>,
+    // stream_reset/transcribe: "digits" or {"words": [...]}; see constrain.rs.
+    pub constrain: Option<Value>,
+    // stream_push/stream_flush: include the decode's raw pieces. transcribe:
+    // include the backend's tokens.
+    pub return_tokens: Option<bool>,
+    // transcribe: words with start and end times.
+    pub word_timestamps: Option<bool>,
+    // transcribe: decode in whichever of these the audio is in.
+    pub language_candidates: Option<Vec<String>>,
+    // transcribe: mask these kinds of personal data; see redact.rs.
+    pub redact: Option<Vec<String>>,
+    // transcribe: decode into the state the previous transcribe used, or not,
+    // whatever --reuse-state says; see whisper.rs.
+    pub reuse_state: Option<bool>,
+    // transcribe: decode only the speech; see silence.rs.
+    pub trim_silence: Option<bool>,
+    pub max_pause_seconds: Option<f64>,
+    pub silence_threshold_db: Option<f64>,
+    // align: the script to time against the audio; see align.rs.
+    pub text: Option<String>,
+    // transcribe_batch: wav paths, and how many to decode side by side.
+    pub files: Option<Vec<String>>,
+    pub concurrency: Option<usize>,
+    // Any action: "interactive" or "background"; see turns.rs.
+    pub priority: Option<String>,
+    // Any action: drop nulls and round floats to `precision` (default 3).
+    pub compact: Option<bool>,
+    // Any action: decimals to round floats to, compact or not.
+    pub precision: Option<u32>,
+    // stream_reset/transcribe: a saved preset's tuning, context and
+    // constraint, for whatever the request doesn't give itself. preset_save
+    // names the preset to write; see settings.rs.
+    pub preset: Option<String>,
+    // preset_save/context_save: what to write. context_save writes to the
+    // context dir, named by `context`.
+    pub settings: Option<Value>,
+    // Any action: add a `timings` breakdown to the response.
+    pub debug_timings: Option<bool>,
+}
+
+pub fn pcm16_to_f32(audio: &[u8]) -> Vec<f32> {
+    let mut out = Vec::with_capacity(audio.len() / 2);
+    for pair in audio.chunks_exact(2) {
+        let sample = i16::from_le_bytes([pair[0], pair[1]]);
+        out.push(sample as f32 / i16::MAX as f32);
+    }
+    out
+}
+
+pub fn wav_to_f32(path: &str) -> Result<(Vec<f32>, u32), String> {
+    let (channels, sample_rate) = wav_channels(path)?;
+    Ok((downmix(channels), sample_rate))
+}
+
+// Same as wav_to_f32 for a WAV file already in memory, e.g. an HTTP upload.
+pub fn wav_bytes_to_f32(data: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    let reader =
+        WavReader::new(Cursor::new(data)).map_err(|err| format!("invalid wav upload: {err}"))?;
+    let (channels, sample_rate) = read_channels(reader)?;
+    Ok((downmix(channels), sample_rate))
+}
+
+fn downmix(channels: Vec<Vec<f32>>) -> Vec<f32> {
+    if channels.len() <= 1 {
+        return channels.into_iter().next().unwrap_or_default();
+    }
+
+    let frames = channels[0].len();
+    (0..frames)
+        .map(|frame| {
+            channels.iter().map(|channel| channel[frame]).sum::<f32>() / channels.len() as f32
+        })
+        .collect()
+}
+
+// One buffer per channel.
+pub fn wav_channels(path: &str) -> Result<(Vec<Vec<f32>>, u32), String> {
+    let reader =
+        WavReader::open(path).map_err(|err| format!("failed to open wav audio file: {err}"))?;
+    read_channels(reader)
+}
+
+fn read_channels<R: Read>(mut reader: WavReader<R>) -> Result<(Vec<Vec<f32>>, u32), String> {
+    let spec = reader.spec();
+
+    let samples = match (spec.sample_format, spec.bits_per_sample) {
+        (SampleFormat::Int, 16) => reader
+            .samples::<i16>()
+            .map(|sample| sample.map(|v| v as f32 / i16::MAX as f32))
+            .collect::<Result<Vec<f32>, _>>(),
+        (SampleFormat::Int, bits @ 17..=32) => {
+            let scale = (1_i64 << (bits - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|v| v as f32 / scale))
+                .collect::<Result<Vec<f32>, _>>()
+        }
+        (SampleFormat::Float, _) => reader.samples::<f32>().collect::<Result<Vec<f32>, _>>(),
+        (_, bits) => return Err(format!("unsupported wav sample size: {bits} bits")),
+    }
+    .map_err(|err| format!("failed to read wav samples: {err}"))?;
+
+    let count = spec.channels.max(1) as usize;
+    let mut channels = vec![Vec::with_capacity(samples.len() / count); count];
+    for frame in samples.chunks_exact(count) {
+        for (channel, sample) in channels.iter_mut().zip(frame) {
+            channel.push(*sample);
+        }
+    }
+    Ok((channels, spec.sample_rate))
+}
+
+// audioUrl/audioCommand go through ffmpeg and are only honoured when the
+// worker was started with --ffmpeg-input, since they reach the network or
+// run programs on the host's behalf.
+pub fn decode_audio(
+    req: &Request,
+    framed_audio: &[u8],
+    default_sample_rate: u32,
+    ffmpeg_input: bool,
+) -> Result<(Vec<f32>, u32), String> {
+    if !framed_audio.is_empty() {
+        let sample_rate = req.sample_rate.unwrap_or(default_sample_rate);
+        return Ok((pcm16_to_f32(framed_audio), sample_rate));
+    }
+
+    if let Some(base64_audio) = &req.audio_base64 {
+        let raw = BASE64_STANDARD
+            .decode(base64_audio)
+            .map_err(|err| format!("invalid audioBase64: {err}"))?;
+        let sample_rate = req.sample_rate.unwrap_or(default_sample_rate);
+        return Ok((pcm16_to_f32(&raw), sample_rate));
+    }
+
+    if let Some(path) = &req.audio {
+        return wav_to_f32(path);
+    }
+
+    if req.audio_url.is_some() || req.audio_command.is_some() {
+        if !ffmpeg_input {
+            return Err(
+                "audioUrl and audioCommand need the worker to run with --ffmpeg-input".into(),
+            );
+        }
+        let source = ffmpeg::Source {
+            url: req.audio_url.as_deref(),
+            command: req.audio_command.as_deref(),
+            max_seconds: req.audio_max_seconds,
+        };
+        return Ok((
+            ffmpeg::decode(&source, default_sample_rate)?,
+            default_sample_rate,
+        ));
+    }
+
+    Err("Missing binary audio payload, audioBase64, audio path, audioUrl or audioCommand".into())
+}
+
+pub fn make_asr_result(
+    text: String,
+    language: &str,
+    duration_seconds: f64,
+    preview_text: Option<String>,
+    committed_text: Option<String>,
+) -> Value {
+    json!({
+        "text": text,
+        "language": language,
+        "durationSeconds": ((duration_seconds * 1000.0).round() / 1000.0),
+        "previewText": preview_text,
+        "committedText": committed_text
+    })
+}
+
+// Pushes that committed something also say which commit it was and where
+// it sits in committedText, so the host can undo it precisely.
+fn stream_result(update: StreamUpdate, language: &str, return_tokens: bool) -> Value {
+    let mut result = make_asr_result(
+        update.text,
+        language,
+        update.duration_seconds,
+        Some(update.preview_text),
+        Some(update.committed_text),
+    );
+    if let Some(commit) = update.commit {
+        result["commitId"] = json!(commit.id);
+        result["commitStart"] = json!(commit.start);
+        result["commitEnd"] = json!(commit.end);
+    }
+    if let Some(confidence) = update.confidence {
+        result["confidence"] = json!(round3(confidence));
+    }
+    if let Some(event) = &update.confidence_event {
+        result["confidenceEvent"] = confidence_event(event);
+    }
+    if return_tokens {
+        result["tokens"] = update.tokens.iter().map(describe_token).collect();
+    }
+    result
+}
+
+// Text exactly as decoded, leading spaces and all: spacing is the host's call.
+fn describe_token(token: &StreamToken) -> Value {
+    let mut value = json!({
+        "text": token.text,
+        "startMs": token.start_ms,
+        "endMs": token.end_ms,
+        "committed": token.committed,
+    });
+    if let Some(confidence) = token.confidence {
+        value["confidence"] = json!(round3(confidence));
+    }
+    value
+}
+
+fn find_macro(macros: Option<&Macros>, update: &StreamUpdate) -> Option<Value> {
+    let commit = update.commit.as_ref()?;
+    macros?.find(&update.committed_text, commit.start)
+}
+
+fn with_macro(mut result: Value, found: Option<Value>) -> Value {
+    if let Some(found) = found {
+        result["macro"] = found;
+    }
+    result
+}
+
+// Teleprompter pushes report where the reader is in the script, not text.
+fn align_result(script: &ScriptTracker, update: &StreamUpdate) -> Value {
+    let mut result = script.describe();
+    result["durationSeconds"] = json!((update.duration_seconds * 1000.0).round() / 1000.0);
+    result
+}
+
+// High-frequency pushes pay to serialize and parse every field, so a host can
+// ask for less. The arrays a result can carry (tokens, segments) are already
+// only there when asked for, so compact has none left to skip.
+fn shape_result(req: &Request, result: Value) -> Value {
+    let compact = req.compact.unwrap_or(false);
+    let precision = match (req.precision, compact) {
+        (Some(precision), _) => Some(precision.min(MAX_PRECISION)),
+        (None, true) => Some(3),
+        (None, false) => None,
+    };
+    if !compact && precision.is_none() {
+        return result;
+    }
+    shape_value(
+        result,
+        compact,
+        precision.map(|decimals| 10_f64.powi(decimals as i32)),
+    )
+}
+
+fn shape_value(value: Value, drop_nulls: bool, scale: Option<f64>) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .filter(|(_, field)| !(drop_nulls && field.is_null()))
+                .map(|(key, field)| (key, shape_value(field, drop_nulls, scale)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(|item| shape_value(item, drop_nulls, scale))
+                .collect(),
+        ),
+        Value::Number(number) if number.is_f64() => match (number.as_f64(), scale) {
+            (Some(float), Some(scale)) => json!((float * scale).round() / scale),
+            _ => Value::Number(number),
+        },
+        other => other,
+    }
+}
+
+fn round3(value: f32) -> f64 {
+    (value as f64 * 1000.0).round() / 1000.0
+}
+
+// Also written to stderr as a JSON event line, which the supervisor passes on
+// to the host, so it can ask whether the mic is covered or the language is
+// wrong instead of typing what the model guessed.
+pub fn confidence_event(event: &ConfidenceEvent) -> Value {
+    let mut value = match *event {
+        ConfidenceEvent::Low { average, below_ms } => json!({
+            "event": "lowConfidence",
+            "averageConfidence": round3(average),
+            "belowMs": below_ms,
+        }),
+        ConfidenceEvent::Recovered { average } => json!({
+            "event": "confidenceRecovered",
+            "averageConfidence": round3(average),
+        }),
+    };
+    value["atMs"] = json!(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0));
+    value
+}
+
+// Backends that pick a language per decode window (whisper with
+// --languages) switch mid-stream; the host hears about it the same way as
+// lowConfidence.
+fn language_changed(from: &str, to: &str) -> Value {
+    json!({
+        "event": "languageChanged",
+        "from": from,
+        "to": to,
+        "atMs": SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0),
+    })
+}
+
+// The backend's description plus the fields every worker reports.
+pub fn describe_model<B: AsrBackend>(engine: &Engine<B>, model_path: &str) -> Value {
+    let mut info = engine.backend.describe();
+    info["protocolVersion"] = json!(PROTOCOL_VERSION);
+    info["modelPath"] = json!(model_path);
+    info["streaming"] = json!(true);
+    info["sampleRate"] = json!(engine.backend.sample_rate());
+    info["contextDir"] = json!(engine.contexts().dir().map(|dir| dir.display().to_string()));
+    info
+}
+
+// The journal and frame trace are side channels: failing to write them must
+// not cost the host a transcript, so errors only go to stderr.
+fn side_write<T>(target: Option<&mut T>, write: impl FnOnce(&mut T) -> Result<(), String>) {
+    if let Some(target) = target {
+        if let Err(err) = write(target) {
+            eprintln!("{err}");
+        }
+    }
+}
+
+// What a worker's flags add to the shared server loop.
+#[derive(Default)]
+pub struct ServeOptions {
+    // Committed stream deltas are also appended here.
+    pub journal: Option<Journal>,
+    pub ffmpeg_input: bool,
+    // Every frame read and written is logged here.
+    pub trace: Option<FrameTrace>,
+    // Trigger phrases in committed text come back as a `macro` to expand.
+    pub macros: Option<Macros>,
+    // Saved presets and contexts.
+    pub settings: Option<Settings>,
+    // Remembers recent requests for the crash report.
+    pub crash: Option<CrashLog>,
+    // Serve clients on this socket instead of stdin/stdout; see transport.rs.
+    pub listen: Option<ListenEndpoint>,
+    // What each --listen client may ask of the model.
+    pub client_limits: ClientLimits,
+    // How many files a batch decodes side by side unless it asks otherwise.
+    pub batch_concurrency: usize,
+    // Decodes each batch file in a child worker instead; see isolate.rs.
+    pub isolate: Option<IsolatedDecoder>,
+}
+
+// What every client shares besides the engine.
+#[derive(Default)]
+struct Shared {
+    metrics: Mutex<Metrics>,
+    turns: Turns,
+}
+
+fn load_preset<B: AsrBackend>(
+    settings: Option<&Settings>,
+    engine: &Engine<B>,
+    name: Option<&str>,
+) -> Result<Option<Preset>, String> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let settings = settings.ok_or(NO_SETTINGS_DIR)?;
+    settings.load_preset(name, engine.stream_config()).map(Some)
+}
+
+const NO_SETTINGS_DIR: &str =
+    "no settings dir: pass --settings-dir, or set HOME (APPDATA on Windows)";
+
+fn handle<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    options: &mut ServeOptions,
+    shared: &Shared,
+    model_info: &Value,
+    req: &Request,
+    audio_bytes: &[u8],
+) -> Result<Value, String> {
+    let sample_rate = engine.backend.sample_rate();
+    let ffmpeg_input = options.ffmpeg_input;
+    let journal = options.journal.as_mut();
+    // Streaming partials carry no language of their own; report the model's.
+    let stream_language = model_info["language"].as_str().unwrap_or("en").to_string();
+
+    match req.action.as_deref().unwrap_or("transcribe") {
+        "hello" | "model_info" => Ok(model_info.clone()),
+        "metrics" => Ok(lock(&shared.metrics).snapshot()),
+        "warmup" => {
+            let started = Instant::now();
+            engine.warmup()?;
+            let warmup_ms = started.elapsed().as_secs_f64() * 1000.0;
+            Ok(json!({ "ready": true, "warmupMs": warmup_ms.round() }))
+        }
+        "stream_reset" => {
+            let preset = load_preset(options.settings.as_ref(), engine, req.preset.as_deref())?;
+            engine.stream_tune(preset.as_ref().map(|preset| &preset.stream));
+            let preset_context = preset.as_ref().and_then(|preset| preset.context.as_deref());
+            engine.stream_reset(
+                req.sample_rate.unwrap_or(sample_rate),
+                req.context.as_deref().or(preset_context),
+            )?;
+            if let Some(script) = &req.script {
+                engine.stream_align(script)?;
+            }
+            let preset_constrain = preset.as_ref().and_then(|preset| preset.constrain.as_ref());
+            if let Some(constrain) = req.constrain.as_ref().or(preset_constrain) {
+                engine.stream_constrain(Constraint::parse(constrain)?);
+            }
+            let mut result = match engine.context() {
+                Some(context) => context.describe(),
+                None => json!({ "context": null }),
+            };
+            result["ready"] = json!(true);
+            if let Some(script) = engine.script() {
+                result["align"] = script.describe();
+            }
+            if let Some(constraint) = engine.constraint() {
+                result["constrain"] = constraint.describe();
+            }
+            if let Some(preset) = &preset {
+                result["preset"] = json!(preset.name);
+            }
+            if let Some(journal) = journal {
+                match journal.reset(req.stream_id.as_deref()) {
+                    Ok(stream_id) => result["streamId"] = json!(stream_id),
+                    Err(err) => eprintln!("{err}"),
+                }
+            }
+            Ok(result)
+        }
+        "stream_push" => {
+            let (audio, rate) = decode_audio(req, audio_bytes, sample_rate, ffmpeg_input)?;
+            let previous = engine.stream_language().map(str::to_string);
+            let update = engine.stream_push(&audio, rate)?;
+            // Pushes smaller than the decode interval are coalesced until
+            // there is enough for a decode; until then the host just gets an
+            // acknowledgment rather than an empty result per 10 ms chunk. A
+            // teleprompter still gets its position on every push.
+            if let Some(buffered_ms) = update.buffered_ms {
+                return Ok(match engine.script() {
+                    Some(script) => align_result(script, &update),
+                    None => json!({ "buffered": true, "bufferedMs": buffered_ms }),
+                });
+            }
+            let language = engine.stream_language().unwrap_or(&stream_language);
+            if let Some(commit) = &update.commit {
+                side_write(journal, |journal| {
+                    journal.commit(commit, &update.text, language)
+                });
+            }
+            if let Some(event) = &update.confidence_event {
+                eprintln!("{}", confidence_event(event));
+            }
+            if let Some(previous) = previous.filter(|previous| previous != language) {
+                eprintln!("{}", language_changed(&previous, language));
+            }
+            let found = find_macro(options.macros.as_ref(), &update);
+            Ok(match engine.script() {
+                Some(script) => align_result(script, &update),
+                None => with_macro(
+                    stream_result(update, language, req.return_tokens.unwrap_or(false)),
+                    found,
+                ),
+            })
+        }
+        "stream_flush" => {
+            let update = engine.stream_flush()?;
+            let language = engine.stream_language().unwrap_or(&stream_language);
+            if let Some(commit) = &update.commit {
+                side_write(journal, |journal| {
+                    journal.commit(commit, &update.text, language)
+                });
+            }
+            let found = find_macro(options.macros.as_ref(), &update);
+            Ok(match engine.script() {
+                Some(script) => align_result(script, &update),
+                None => with_macro(
+                    stream_result(update, language, req.return_tokens.unwrap_or(false)),
+                    found,
+                ),
+            })
+        }
+        "stream_undo_last" => Ok(match engine.stream_undo_last() {
+            Some(undone) => {
+                side_write(journal, |journal| journal.undo(&undone));
+                json!({
+                "undone": true,
+                "commitId": undone.commit.id,
+                "commitStart": undone.commit.start,
+                "commitEnd": undone.commit.end,
+                "text": undone.text,
+                "committedText": undone.committed_text,
+                })
+            }
+            None => json!({ "undone": false }),
+        }),
+        "stream_close" => {
+            engine.stream_close();
+            Ok(json!({ "closed": true }))
+        }
+        "transcribe" if req.split_channels.unwrap_or(false) => {
+            let path = req
+                .audio
+                .as_deref()
+                .ok_or("splitChannels needs a wav audio path")?;
+            let (audio, rate) = wav_channels(path)?;
+            if audio.len() != 2 {
+                return Err(format!(
+                    "splitChannels needs a two-channel wav, got {} channel(s)",
+                    audio.len()
+                ));
+            }
+            let started = Instant::now();
+            let mut transcripts = Vec::with_capacity(2);
+            for channel in &audio {
+                transcripts.push(match req.context.as_deref() {
+                    Some(label) => engine.transcribe_in(channel, rate, label)?,
+                    None => engine.transcribe(channel, rate)?,
+                });
+            }
+            let turns = channels::merge([&transcripts[0].pieces, &transcripts[1].pieces]);
+            let text: Vec<&str> = turns.iter().map(|turn| turn.text.as_str()).collect();
+            let mut result = make_asr_result(
+                text.join("\n"),
+                &transcripts[0].language,
+                started.elapsed().as_secs_f64(),
+                None,
+                None,
+            );
+            result["segments"] = channels::describe(&turns);
+            Ok(result)
+        }
+        "transcribe" => {
+            let transcribe_options = TranscribeOptions::from_request(req)?;
+            let (audio, rate) = decode_audio(req, audio_bytes, sample_rate, ffmpeg_input)?;
+            let preset = load_preset(options.settings.as_ref(), engine, req.preset.as_deref())?;
+            let preset_constrain = preset.as_ref().and_then(|preset| preset.constrain.as_ref());
+            let constraint = req
+                .constrain
+                .as_ref()
+                .or(preset_constrain)
+                .map(Constraint::parse)
+                .transpose()?;
+            let preset_context = preset.as_ref().and_then(|preset| preset.context.as_deref());
+            let label = req.context.as_deref().or(preset_context);
+            transcribe::transcribe(
+                engine,
+                &audio,
+                rate,
+                &transcribe_options,
+                |engine, audio| match (label, constraint) {
+                    (label, Some(constraint)) => {
+                        engine.transcribe_constrained(audio, rate, label, constraint)
+                    }
+                    (Some(label), None) => engine.transcribe_in(audio, rate, label),
+                    (None, None) => engine.transcribe(audio, rate),
+                },
+            )
+        }
+        "align" => {
+            let (audio, rate) = decode_audio(req, audio_bytes, sample_rate, ffmpeg_input)?;
+            let script = req.text.as_deref().unwrap_or_default();
+            transcribe::align(engine, &audio, rate, script)
+        }
+        "preset_save" => {
+            let settings = options.settings.as_ref().ok_or(NO_SETTINGS_DIR)?;
+            let name = req
+                .preset
+                .as_deref()
+                .ok_or("preset_save needs a preset name")?;
+            let value = req.settings.as_ref().ok_or("preset_save needs settings")?;
+            let path = settings.save_preset(name, value, engine.stream_config())?;
+            Ok(json!({ "saved": path.display().to_string(), "preset": name }))
+        }
+        "context_save" => {
+            let label = req
+                .context
+                .as_deref()
+                .ok_or("context_save needs a context label")?;
+            let value = req.settings.as_ref().ok_or("context_save needs settings")?;
+            let path = engine.contexts().save(label, value)?;
+            Ok(json!({ "saved": path.display().to_string(), "context": label }))
+        }
+        other => Err(format!("Unsupported action: {other}")),
+    }
+}
+
+// Where a request's time went, to tell a slow worker from a slow host (whose
+// share is whatever its round trip adds to these). queueMs runs from the
+// reader finishing the frame, while earlier requests may still be decoding,
+// to the action starting; decodeMs is the model, and postprocessMs the rest
+// of the action (audio decoding, stabilizing, contexts, the journal). The
+// response is written after these are taken, so its write time goes to the
+// frame trace as writeMs instead.
+fn timings(queue: Duration, action: Duration, decode: Duration) -> Value {
+    let ms = |duration: Duration| (duration.as_secs_f64() * 10_000.0).round() / 10.0;
+    json!({
+        "queueMs": ms(queue),
+        "decodeMs": ms(decode),
+        "postprocessMs": ms(action.saturating_sub(decode)),
+    })
+}
+
+// Where a request came from and where its answer goes: the host on
+// stdin/stdout (no peer), or a --listen client.
+#[derive(Clone)]
+struct Client {
+    id: usize,
+    peer: Option<Arc<str>>,
+    replies: mpsc::Sender<Outbound>,
+}
+
+// A frame as it reaches the decoder, stamped when the reader finished
+// reading it: admitted (with its --listen in-flight slot, released once
+// answered) or already turned away by the client's limits.
+struct Inbound {
+    received: Received,
+    arrived: Instant,
+    client: Client,
+}
+
+enum Received {
+    Frame(Frame, Option<InFlight>),
+    Limited(Limited),
+}
+
+// A response on its way to the writer, with what the trace logs about it.
+struct Outbound {
+    response: Value,
+    action: Option<String>,
+    arrived: Instant,
+    _slot: Option<InFlight>,
+    // Told once the frame is written; see answer_crashes_on.
+    written: Option<mpsc::Sender<()>>,
+}
+
+// The framed loop shared by every backend, on stdin/stdout or, with
+// `listen`, for each client that connects. Reading, decoding and writing
+// each get a thread, so the next frames are read while a decode runs and a
+// slow reader of responses never stalls the decoder. Decoding stays on the
+// calling thread, the one the crash log answers from.
+pub fn serve<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    model_info: Value,
+    mut options: ServeOptions,
+) -> Result<(), String> {
+    let trace = options
+        .trace
+        .take()
+        .map(|trace| Arc::new(Mutex::new(trace)));
+    let (frames, inbound) = mpsc::channel();
+    let shared = Shared::default();
+
+    if let Some(endpoint) = options.listen.take() {
+        // The model stays loaded across clients, so a restarted host
+        // reconnects without paying the load again. Clients are read side by
+        // side, each under its own limits, and their requests decoded in
+        // the order they arrive.
+        let listener = Listener::bind(&endpoint)?;
+        log(
+            LogLevel::Info,
+            "listening",
+            json!({ "endpoint": listener.local_description() }),
+        );
+        // stdout isn't the protocol channel here; a crash with no request
+        // in hand is only logged.
+        if let Some(crash) = &options.crash {
+            crash.answer_with(|_| {});
+        }
+        let (limits, writers_trace) = (options.client_limits, trace.clone());
+        thread::spawn(move || accept(&listener, limits, writers_trace, &frames));
+        return serve_frames(
+            engine,
+            &model_info,
+            &mut options,
+            &shared,
+            trace.as_deref(),
+            inbound,
+        );
+    }
+
+    let (replies, writer) = spawn_writer(io::stdout(), trace.clone());
+    let host = Client {
+        id: 0,
+        peer: None,
+        replies,
+    };
+    spawn_reader(io::stdin(), None, host, frames);
+    let served = serve_frames(
+        engine,
+        &model_info,
+        &mut options,
+        &shared,
+        trace.as_deref(),
+        inbound,
+    );
+
+    // Everything already answered still goes out; a failed write is what
+    // stopped the decoder, so it's the error worth reporting.
+    let written = writer
+        .join()
+        .unwrap_or_else(|_| Err("response writer panicked".into()));
+    written.and(served)
+}
+
+fn accept(
+    listener: &Listener,
+    limits: ClientLimits,
+    trace: Option<Arc<Mutex<FrameTrace>>>,
+    frames: &mpsc::Sender<Result<Inbound, String>>,
+) {
+    for id in 1.. {
+        let connection = match listener.accept() {
+            Ok(connection) => connection,
+            Err(err) => {
+                log(
+                    LogLevel::Warn,
+                    "failed to accept connection",
+                    json!({ "error": err.to_string() }),
+                );
+                continue;
+            }
+        };
+        log(
+            LogLevel::Info,
+            "client connected",
+            json!({ "peer": connection.peer }),
+        );
+        let (replies, _writer) = spawn_writer(connection.writer, trace.clone());
+        let client = Client {
+            id,
+            peer: Some(connection.peer.into()),
+            replies,
+        };
+        let limiter = ClientLimiter::new(limits);
+        spawn_reader(connection.reader, Some(limiter), client, frames.clone());
+    }
+}
+
+fn serve_frames<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    model_info: &Value,
+    options: &mut ServeOptions,
+    shared: &Shared,
+    trace: Option<&Mutex<FrameTrace>>,
+    inbound: mpsc::Receiver<Result<Inbound, String>>,
+) -> Result<(), String> {
+    // The streaming state is the engine's, so only one client streams at a
+    // time: the one whose stream_reset came last.
+    let mut streaming = None;
+    let isolate = options.isolate.take();
+
+    // Batches run beside the loop and are waited for before it returns, so
+    // their answers still go out at EOF.
+    thread::scope(|batches| {
+        for inbound in inbound {
+            let Inbound {
+                received,
+                arrived,
+                client,
+            } = match inbound {
+                Ok(inbound) => inbound,
+                Err(err) => {
+                    trace_write(trace, |trace| trace.fatal(&err));
+                    return Err(err);
+                }
+            };
+            let (frame, slot) = match received {
+                Received::Frame(frame, slot) => (frame, slot),
+                Received::Limited(limited) => {
+                    let response = limited.response();
+                    let action = limited.action.as_deref().unwrap_or("transcribe");
+                    record_metrics(shared, action, &response, arrived);
+                    reply(&client, response, limited.action, arrived, None)?;
+                    continue;
+                }
+            };
+            let parsed = serde_json::from_slice::<Request>(&frame.json);
+            let action = parsed.as_ref().ok().and_then(|req| req.action.clone());
+            let id = parsed.as_ref().ok().and_then(|req| req.id.as_deref());
+            trace_write(trace, |trace| trace.request(id, action.as_deref(), &frame));
+            if let Some(crash) = &options.crash {
+                crash.record(id, action.as_deref(), frame.json.len(), frame.audio.len());
+                if client.peer.is_some() {
+                    answer_crashes_on(crash, &client);
+                }
+            }
+
+            let metrics_action = match &parsed {
+                Ok(req) => req.action.as_deref().unwrap_or("transcribe"),
+                Err(_) => "invalid",
+            }
+            .to_string();
+            let response = match parsed {
+                // A batch takes a turn on the model per file rather than the
+                // loop for its whole run, so the requests after it (a
+                // dictation push, say) get in between two files. Its answer
+                // goes out whenever it finishes.
+                Ok(req) if metrics_action == "transcribe_batch" => {
+                    match Batch::new(engine, &req, options.batch_concurrency, isolate.as_ref()) {
+                        Ok(batch) => {
+                            batches.spawn(move || {
+                                let mut emit = |event| {
+                                    send(&client, event, None, arrived, None)
+                                        .map_err(|_| "response writer stopped".to_string())
+                                };
+                                let outcome = batch.run(&shared.turns, &mut emit);
+                                let response = respond(&req, outcome);
+                                record_metrics(shared, "transcribe_batch", &response, arrived);
+                                // A stopped writer is the loop's to report.
+                                let _ = send(&client, response, action, arrived, slot);
+                            });
+                            continue;
+                        }
+                        Err(error) => respond(&req, Err(error)),
+                    }
+                }
+                Ok(req) => {
+                    let handled = Instant::now();
+                    let outcome =
+                        match Priority::for_request(req.priority.as_deref(), &metrics_action) {
+                            Err(error) => Err(error),
+                            Ok(_) if is_foreign_stream(&metrics_action, streaming, client.id) => {
+                                Err("another client's stream is open; send stream_reset first"
+                                    .into())
+                            }
+                            Ok(priority) => {
+                                let _turn = shared.turns.take(priority);
+                                handle(engine, options, shared, model_info, &req, &frame.audio)
+                            }
+                        };
+                    if metrics_action == "stream_reset" && outcome.is_ok() {
+                        streaming = Some(client.id);
+                    }
+                    let (action_time, decode_time) = (handled.elapsed(), engine.take_decode_time());
+                    let mut response = respond(&req, outcome);
+                    if req.debug_timings.unwrap_or(false) {
+                        let queue_time = handled.saturating_duration_since(arrived);
+                        response["timings"] = timings(queue_time, action_time, decode_time);
+                    }
+                    response
+                }
+                Err(err) => json!({
+                    "id": "unknown",
+                    "ok": false,
+                    "error": format!("invalid JSON request: {err}")
+                }),
+            };
+
+            record_metrics(shared, &metrics_action, &response, arrived);
+            reply(&client, response, action, arrived, slot)?;
+        }
+
+        Ok(())
+    })
+}
+
+fn respond(req: &Request, outcome: Result<Value, String>) -> Value {
+    let request_id = req.id.as_deref().unwrap_or("unknown");
+    match outcome {
+        Ok(result) => json!({ "id": request_id, "ok": true, "result": shape_result(req, result) }),
+        Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+    }
+}
+
+fn is_foreign_stream(action: &str, streaming: Option<usize>, client: usize) -> bool {
+    action.starts_with("stream_")
+        && action != "stream_reset"
+        && streaming.is_some_and(|owner| owner != client)
+}
+
+// Under --listen a panic's final frame goes to the client whose request was
+// being decoded. The process exits once the panic unwinds, so the hook waits
+// (briefly) for the client's writer to get it out.
+fn answer_crashes_on(crash: &CrashLog, client: &Client) {
+    let replies = client.replies.clone();
+    crash.answer_with(move |frame| {
+        let (written, done) = mpsc::channel();
+        let outbound = Outbound {
+            response: frame.clone(),
+            action: None,
+            arrived: Instant::now(),
+            _slot: None,
+            written: Some(written),
+        };
+        if replies.send(outbound).is_ok() {
+            let _ = done.recv_timeout(Duration::from_secs(1));
+        }
+    });
+}
+
+fn send(
+    client: &Client,
+    response: Value,
+    action: Option<String>,
+    arrived: Instant,
+    slot: Option<InFlight>,
+) -> Result<(), mpsc::SendError<Outbound>> {
+    client.replies.send(Outbound {
+        response,
+        action,
+        arrived,
+        _slot: slot,
+        written: None,
+    })
+}
+
+// A --listen client that stopped reading only loses its own answers; the
+// host on stdio going away ends the worker.
+fn reply(
+    client: &Client,
+    response: Value,
+    action: Option<String>,
+    arrived: Instant,
+    slot: Option<InFlight>,
+) -> Result<(), String> {
+    match (send(client, response, action, arrived, slot), &client.peer) {
+        (Err(_), None) => Err("response writer stopped".into()),
+        _ => Ok(()),
+    }
+}
+
+fn record_metrics(shared: &Shared, action: &str, response: &Value, arrived: Instant) {
+    let ok = response["ok"].as_bool().unwrap_or(false);
+    let latency_ms = arrived.elapsed().as_secs_f64() * 1000.0;
+    let mut metrics = lock(&shared.metrics);
+    metrics.record_request(action, ok);
+
+    if ok && matches!(action, "transcribe" | "align") {
+        let result = &response["result"];
+        metrics.record_decode(
+            latency_ms,
+            result["audioSeconds"].as_f64().unwrap_or(0.0),
+            result["durationSeconds"].as_f64().unwrap_or(0.0),
+        );
+    }
+    drop(metrics);
+
+    log(
+        LogLevel::Debug,
+        "request completed",
+        json!({
+            "action": action,
+            "id": response["id"],
+            "ok": ok,
+            "latencyMs": (latency_ms * 10.0).round() / 10.0
+        }),
+    );
+}
+
+// Reads ahead, stamping each frame as it arrives, so the limits see each
+// request as it comes in. Not joined: it ends at EOF, or at the next frame
+// once nobody is receiving. The host's read errors end the worker; a
+// client's only drop that client.
+fn spawn_reader<R: Read + Send + 'static>(
+    mut reader: R,
+    mut limiter: Option<ClientLimiter>,
+    client: Client,
+    frames: mpsc::Sender<Result<Inbound, String>>,
+) {
+    thread::spawn(move || loop {
+        let received = match read_frame(&mut reader) {
+            Ok(Some(frame)) => {
+                match limiter
+                    .as_mut()
+                    .map(|limiter| limiter.admit(&frame.json, frame.audio.len()))
+                {
+                    None => Received::Frame(frame, None),
+                    Some(Ok(slot)) => Received::Frame(frame, Some(slot)),
+                    Some(Err(limited)) => Received::Limited(limited),
+                }
+            }
+            Ok(None) => {
+                if let Some(peer) = &client.peer {
+                    log(
+                        LogLevel::Info,
+                        "client disconnected",
+                        json!({ "peer": &**peer }),
+                    );
+                }
+                break;
+            }
+            Err(err) => {
+                match &client.peer {
+                    Some(peer) => log(
+                        LogLevel::Warn,
+                        "client dropped",
+                        json!({ "peer": &**peer, "error": err }),
+                    ),
+                    None => {
+                        let _ = frames.send(Err(err));
+                    }
+                }
+                break;
+            }
+        };
+        let inbound = Inbound {
+            received,
+            arrived: Instant::now(),
+            client: client.clone(),
+        };
+        if frames.send(Ok(inbound)).is_err() {
+            break;
+        }
+    });
+}
+
+// Answers go out strictly in the order they were sent here. Each frame goes
+// out in one write_all, which holds stdout's lock while it runs, so a crash
+// report's final frame lands between two frames instead of inside one.
+fn spawn_writer<W: Write + Send + 'static>(
+    mut writer: W,
+    trace: Option<Arc<Mutex<FrameTrace>>>,
+) -> (
+    mpsc::Sender<Outbound>,
+    thread::JoinHandle<Result<(), String>>,
+) {
+    let (tx, rx) = mpsc::channel::<Outbound>();
+    let handle = thread::spawn(move || {
+        for outbound in rx {
+            let writing = Instant::now();
+            let mut frame = Vec::new();
+            write_response(&mut frame, &outbound.response)
+                .and_then(|_| writer.write_all(&frame))
+                .and_then(|_| writer.flush())
+                .map_err(|err| format!("failed to write response: {err}"))?;
+            let write_time = writing.elapsed();
+            trace_write(trace.as_deref(), |trace| {
+                trace.response(
+                    outbound.action.as_deref(),
+                    &outbound.response,
+                    outbound.arrived.elapsed(),
+                    write_time,
+                )
+            });
+            if let Some(written) = outbound.written {
+                let _ = written.send(());
+            }
+        }
+        Ok(())
+    });
+    (tx, handle)
+}
+
+// The counters only ever hold whole updates, so a panic elsewhere can't
+// leave them half-written.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// Both the decoder and the writers log to the trace.
+fn trace_write(
+    trace: Option<&Mutex<FrameTrace>>,
+    write: impl FnOnce(&mut FrameTrace) -> Result<(), String>,
+) {
+    if let Some(trace) = trace {
+        side_write(Some(&mut *lock(trace)), write);
+    }
+}
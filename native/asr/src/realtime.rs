@@ -0,0 +1,369 @@
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use crate::http::{Backend, Deltas};
+
+// `/v1/realtime` speaking the OpenAI realtime transcription events, so
+// clients written against that API get live partials from the engine:
+//
+//   in:  transcription_session.update, input_audio_buffer.append / commit / clear
+//   out: transcription_session.created / updated, input_audio_buffer.committed
+//        / cleared, conversation.item.input_audio_transcription.delta /
+//        completed, error
+//
+// Audio is base64 pcm16 mono at 24 kHz as in OpenAI's API, or at
+// `input_audio_sample_rate` (a DingoFlow extension) for 16 kHz mic capture.
+// There's no server VAD: the client commits each utterance itself.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const DEFAULT_SAMPLE_RATE: u32 = 24_000;
+const MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+struct Session<'a> {
+    stream: TcpStream,
+    backend: &'a mut dyn Backend,
+    model_id: String,
+    sample_rate: u32,
+    next_event: u64,
+    next_item: u64,
+    previous_item: Option<String>,
+    // The utterance being appended to, with what's been sent as deltas.
+    item: Option<(String, Deltas)>,
+}
+
+pub fn session(
+    stream: &mut TcpStream,
+    key: &str,
+    model_id: &str,
+    backend: &mut dyn Backend,
+) -> Result<(), String> {
+    let accept = BASE64_STANDARD.encode(sha1(format!("{key}{WEBSOCKET_GUID}").as_bytes()));
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    );
+    stream
+        .write_all(handshake.as_bytes())
+        .map_err(|err| format!("websocket handshake failed: {err}"))?;
+
+    let mut session = Session {
+        stream: stream.try_clone().map_err(|err| err.to_string())?,
+        backend,
+        model_id: model_id.to_string(),
+        sample_rate: DEFAULT_SAMPLE_RATE,
+        next_event: 0,
+        next_item: 0,
+        previous_item: None,
+        item: None,
+    };
+    let created = json!({ "type": "transcription_session.created", "session": session.describe() });
+    session.send(created)?;
+
+    let outcome = session.run();
+    if session.item.take().is_some() {
+        session.backend.stream_close();
+    }
+    outcome
+}
+
+impl Session<'_> {
+    fn run(&mut self) -> Result<(), String> {
+        loop {
+            let Some(message) = read_message(&mut self.stream)? else {
+                return Ok(());
+            };
+            let event: Value = match serde_json::from_slice(&message) {
+                Ok(event) => event,
+                Err(err) => {
+                    self.error(
+                        "invalid_request_error",
+                        None,
+                        format!("invalid JSON: {err}"),
+                    )?;
+                    continue;
+                }
+            };
+            let client_event = event["event_id"].as_str().map(str::to_string);
+            if let Err(message) = self.handle(&event) {
+                self.error("invalid_request_error", client_event, message)?;
+            }
+        }
+    }
+
+    fn handle(&mut self, event: &Value) -> Result<(), String> {
+        match event["type"].as_str().unwrap_or_default() {
+            "transcription_session.update" => {
+                let session = &event["session"];
+                if let Some(format) = session["input_audio_format"].as_str() {
+                    if format != "pcm16" {
+                        return Err(format!(
+                            "input_audio_format {format} is not supported (use pcm16)"
+                        ));
+                    }
+                }
+                if let Some(rate) = session["input_audio_sample_rate"].as_u64() {
+                    if self.item.is_some() {
+                        return Err("the sample rate can't change mid-utterance".into());
+                    }
+                    self.sample_rate = u32::try_from(rate)
+                        .ok()
+                        .filter(|rate| (8_000..=48_000).contains(rate))
+                        .ok_or("input_audio_sample_rate must be between 8000 and 48000")?;
+                }
+                let updated =
+                    json!({ "type": "transcription_session.updated", "session": self.describe() });
+                self.send(updated)
+            }
+            "input_audio_buffer.append" => {
+                let audio = event["audio"].as_str().ok_or("append requires audio")?;
+                let bytes = BASE64_STANDARD
+                    .decode(audio)
+                    .map_err(|err| format!("invalid base64 audio: {err}"))?;
+                let samples: Vec<f32> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32)
+                    .collect();
+                if self.item.is_none() {
+                    self.backend.stream_reset(self.sample_rate)?;
+                    self.next_item += 1;
+                    self.item = Some((format!("item_{:06}", self.next_item), Deltas::default()));
+                }
+                let committed = self.backend.stream_push(samples, self.sample_rate)?;
+                self.send_delta(&committed)
+            }
+            "input_audio_buffer.commit" => {
+                if self.item.is_none() {
+                    return Err("input audio buffer is empty".into());
+                }
+                let text = self.backend.stream_flush();
+                let Some((item_id, _)) = &self.item else {
+                    return Ok(());
+                };
+                let item_id = item_id.clone();
+                let committed = json!({
+                    "type": "input_audio_buffer.committed",
+                    "previous_item_id": self.previous_item,
+                    "item_id": item_id,
+                });
+                self.send(committed)?;
+                let text = match text {
+                    Ok(text) => text,
+                    Err(error) => {
+                        self.item = None;
+                        self.previous_item = Some(item_id.clone());
+                        return self.send(json!({
+                            "type": "conversation.item.input_audio_transcription.failed",
+                            "item_id": item_id,
+                            "content_index": 0,
+                            "error": { "type": "transcription_error", "message": error },
+                        }));
+                    }
+                };
+                self.send_delta(&text)?;
+                self.item = None;
+                self.previous_item = Some(item_id.clone());
+                self.send(json!({
+                    "type": "conversation.item.input_audio_transcription.completed",
+                    "item_id": item_id,
+                    "content_index": 0,
+                    "transcript": text,
+                }))
+            }
+            "input_audio_buffer.clear" => {
+                if self.item.take().is_some() {
+                    self.backend.stream_close();
+                }
+                self.send(json!({ "type": "input_audio_buffer.cleared" }))
+            }
+            other => Err(format!("unsupported event type: {other}")),
+        }
+    }
+
+    fn describe(&self) -> Value {
+        json!({
+            "object": "realtime.transcription_session",
+            "input_audio_format": "pcm16",
+            "input_audio_sample_rate": self.sample_rate,
+            "input_audio_transcription": { "model": self.model_id },
+            "turn_detection": null,
+        })
+    }
+
+    fn send_delta(&mut self, committed: &str) -> Result<(), String> {
+        let Some((item_id, deltas)) = self.item.as_mut() else {
+            return Ok(());
+        };
+        let Some(delta) = deltas.next(committed) else {
+            return Ok(());
+        };
+        let event = json!({
+            "type": "conversation.item.input_audio_transcription.delta",
+            "item_id": item_id,
+            "content_index": 0,
+            "delta": delta,
+        });
+        self.send(event)
+    }
+
+    fn error(
+        &mut self,
+        kind: &str,
+        event_id: Option<String>,
+        message: String,
+    ) -> Result<(), String> {
+        self.send(json!({
+            "type": "error",
+            "error": { "type": kind, "message": message, "event_id": event_id },
+        }))
+    }
+
+    fn send(&mut self, mut event: Value) -> Result<(), String> {
+        self.next_event += 1;
+        event["event_id"] = json!(format!("event_{:06}", self.next_event));
+        write_frame(&mut self.stream, OP_TEXT, event.to_string().as_bytes())
+            .map_err(|err| format!("websocket write failed: {err}"))
+    }
+}
+
+// One complete text or binary message, answering pings on the way. None once
+// the client closes.
+fn read_message(stream: &mut TcpStream) -> Result<Option<Vec<u8>>, String> {
+    let mut message = Vec::new();
+    loop {
+        let mut head = [0_u8; 2];
+        if stream.read_exact(&mut head).is_err() {
+            return Ok(None);
+        }
+        let fin = head[0] & 0x80 != 0;
+        let opcode = head[0] & 0x0F;
+        let masked = head[1] & 0x80 != 0;
+        let length = match head[1] & 0x7F {
+            126 => {
+                let mut ext = [0_u8; 2];
+                stream.read_exact(&mut ext).map_err(|err| err.to_string())?;
+                u16::from_be_bytes(ext) as usize
+            }
+            127 => {
+                let mut ext = [0_u8; 8];
+                stream.read_exact(&mut ext).map_err(|err| err.to_string())?;
+                usize::try_from(u64::from_be_bytes(ext)).unwrap_or(usize::MAX)
+            }
+            length => length as usize,
+        };
+        if message.len().saturating_add(length) > MAX_MESSAGE_BYTES {
+            let _ = write_frame(stream, OP_CLOSE, &1009_u16.to_be_bytes());
+            return Err("websocket message too large".into());
+        }
+        // Clients must mask every frame.
+        if !masked {
+            let _ = write_frame(stream, OP_CLOSE, &1002_u16.to_be_bytes());
+            return Err("unmasked websocket frame from client".into());
+        }
+        let mut mask = [0_u8; 4];
+        stream
+            .read_exact(&mut mask)
+            .map_err(|err| err.to_string())?;
+        let mut payload = vec![0_u8; length];
+        stream
+            .read_exact(&mut payload)
+            .map_err(|err| err.to_string())?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        match opcode {
+            OP_CLOSE => {
+                let _ = write_frame(stream, OP_CLOSE, &payload[..payload.len().min(2)]);
+                return Ok(None);
+            }
+            OP_PING => {
+                write_frame(stream, OP_PONG, &payload).map_err(|err| err.to_string())?;
+            }
+            OP_PONG => {}
+            OP_TEXT | OP_BINARY | OP_CONTINUATION => {
+                message.extend_from_slice(&payload);
+                if fin {
+                    return Ok(Some(message));
+                }
+            }
+            other => return Err(format!("unknown websocket opcode {other}")),
+        }
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)?;
+    stream.flush()
+}
+
+// Only for the handshake's Sec-WebSocket-Accept, which RFC 6455 fixes to SHA-1.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut padded = data.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in padded.chunks_exact(64) {
+        let mut w = [0_u32; 80];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0_u8; 20];
+    for (chunk, value) in digest.chunks_exact_mut(4).zip(h) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,113 @@
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PiiKind {
+    Card,
+    Phone,
+    Email,
+}
+
+impl PiiKind {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "card" | "credit_card" => Ok(Self::Card),
+            "phone" => Ok(Self::Phone),
+            "email" => Ok(Self::Email),
+            other => Err(format!("unsupported redact category: {other}")),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Card => "card",
+            Self::Phone => "phone",
+            Self::Email => "email",
+        }
+    }
+
+    fn mask(self) -> &'static str {
+        match self {
+            Self::Card => "[CARD]",
+            Self::Phone => "[PHONE]",
+            Self::Email => "[EMAIL]",
+        }
+    }
+}
+
+fn email_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        // Whisper writes most addresses literally, but dictated ones often come
+        // back spelled out ("jane dot doe at example dot com").
+        Regex::new(
+            r"(?i)[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}|\b[a-z0-9]+(?:\s+dot\s+[a-z0-9]+)*\s+at\s+[a-z0-9-]+(?:\s+dot\s+[a-z0-9-]+)*\s+dot\s+(?:com|org|net|edu|gov|io|co|de|uk)\b",
+        )
+        .expect("email pattern is valid")
+    })
+}
+
+fn digit_run_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN
+        .get_or_init(|| Regex::new(r"\+?\(?\d[\d\s().-]*\d").expect("digit run pattern is valid"))
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let mut sum = 0;
+    for (index, digit) in digits.iter().rev().enumerate() {
+        let mut value = *digit;
+        if index % 2 == 1 {
+            value *= 2;
+            if value > 9 {
+                value -= 9;
+            }
+        }
+        sum += value;
+    }
+    sum % 10 == 0
+}
+
+fn classify_digit_run(run: &str, kinds: &[PiiKind]) -> Option<PiiKind> {
+    let digits: Vec<u32> = run.chars().filter_map(|ch| ch.to_digit(10)).collect();
+    if kinds.contains(&PiiKind::Card) && (13..=19).contains(&digits.len()) && luhn_valid(&digits) {
+        return Some(PiiKind::Card);
+    }
+    if kinds.contains(&PiiKind::Phone) && (7..=15).contains(&digits.len()) {
+        return Some(PiiKind::Phone);
+    }
+    None
+}
+
+// Returns the masked text plus how many matches of each category were replaced.
+pub fn redact_text(text: &str, kinds: &[PiiKind]) -> (String, BTreeMap<&'static str, usize>) {
+    let mut counts = BTreeMap::new();
+    let mut redacted = text.to_string();
+
+    if kinds.contains(&PiiKind::Email) {
+        redacted = email_pattern()
+            .replace_all(&redacted, |_: &regex::Captures| {
+                *counts.entry(PiiKind::Email.name()).or_insert(0) += 1;
+                PiiKind::Email.mask()
+            })
+            .into_owned();
+    }
+
+    if kinds.contains(&PiiKind::Card) || kinds.contains(&PiiKind::Phone) {
+        redacted = digit_run_pattern()
+            .replace_all(&redacted, |caps: &regex::Captures| {
+                let run = &caps[0];
+                match classify_digit_run(run, kinds) {
+                    Some(kind) => {
+                        *counts.entry(kind.name()).or_insert(0) += 1;
+                        kind.mask().to_string()
+                    }
+                    None => run.to_string(),
+                }
+            })
+            .into_owned();
+    }
+
+    (redacted, counts)
+}
@@ -0,0 +1,416 @@
+use crate::batch::MAX_BATCH_CONCURRENCY;
+use crate::protocol::PROTOCOL_VERSION;
+use dingoflow_frame::FRAME_VERSION;
+use serde_json::{json, Value};
+
+// JSON Schema (2020-12) for the framed protocol: each request, the response
+// envelope and result of each action, and the event lines written to
+// stderr. Printed by --dump-schema so hosts can validate what they send and
+// generate typed clients. Kept next to protocol.rs: a field added to
+// Request or to a result belongs here too, and tests/schema.rs fails when a
+// Request field or a handled action is missing.
+
+pub const ACTIONS: &[&str] = &[
+    "hello",
+    "model_info",
+    "warmup",
+    "stream_reset",
+    "stream_push",
+    "stream_flush",
+    "stream_undo_last",
+    "stream_close",
+    "transcribe",
+    "transcribe_batch",
+    "align",
+    "metrics",
+    "preset_save",
+    "context_save",
+];
+
+pub fn protocol_schema() -> Value {
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("urn:dingoflow:protocol:v{PROTOCOL_VERSION}"),
+        "title": "dingoflow worker protocol",
+        "description": format!(
+            "Requests are framed as \"DFLW\", version byte {FRAME_VERSION}, u32 LE JSON length, \
+             u32 LE audio length, JSON, audio; responses as \"DFLW\", version byte, \
+             u32 LE JSON length, JSON. Events are JSON lines on stderr."
+        ),
+        "$defs": {
+            "request": request(),
+            "response": response(),
+            "event": event(),
+            "streamResult": stream_result(),
+            "bufferedResult": buffered_result(),
+            "transcribeResult": transcribe_result(),
+            "alignResult": align_result(),
+            "batchItem": batch_item(),
+            "streamToken": stream_token(),
+            "timedWord": timed_word(),
+            "macro": macro_found(),
+            "constrain": constrain(),
+        },
+        "oneOf": [
+            { "$ref": "#/$defs/request" },
+            { "$ref": "#/$defs/response" },
+            { "$ref": "#/$defs/batchItem" },
+            { "$ref": "#/$defs/event" },
+        ],
+    })
+}
+
+fn object(properties: Value, required: &[&str]) -> Value {
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+fn described(mut schema: Value, description: &str) -> Value {
+    schema["description"] = json!(description);
+    schema
+}
+
+fn request() -> Value {
+    let string = || json!({ "type": "string" });
+    let boolean = || json!({ "type": "boolean" });
+    described(
+        object(
+            json!({
+                "id": described(string(), "Echoed on the response."),
+                "action": {
+                    "enum": ACTIONS,
+                    "default": "transcribe",
+                },
+                "audio": described(string(), "Path to a wav file."),
+                "audioBase64": described(string(), "16-bit LE PCM, base64."),
+                "sampleRate": { "type": "integer", "minimum": 1 },
+                "context": described(string(), "Context label; see the context dir."),
+                "streamId": described(string(), "stream_reset: the journal's stream id."),
+                "audioUrl": described(string(), "Fetched with ffmpeg (--ffmpeg-input)."),
+                "audioCommand": {
+                    "type": "array",
+                    "items": string(),
+                    "description": "argv whose stdout is the audio (--ffmpeg-input).",
+                },
+                "audioMaxSeconds": { "type": "number", "exclusiveMinimum": 0 },
+                "splitChannels": described(boolean(), "transcribe: decode each channel of a stereo wav."),
+                "script": described(string(), "stream_reset: follow this script."),
+                "constrain": { "$ref": "#/$defs/constrain" },
+                "returnTokens": described(
+                    boolean(),
+                    "stream_push/stream_flush: include the raw tokens; transcribe: the backend's tokens.",
+                ),
+                "wordTimestamps": described(boolean(), "transcribe: words with start and end times."),
+                "languageCandidates": {
+                    "type": "array",
+                    "items": string(),
+                    "description": "transcribe: decode in whichever of these the audio is in.",
+                },
+                "redact": {
+                    "type": "array",
+                    "items": { "enum": ["card", "credit_card", "phone", "email"] },
+                    "description": "transcribe: mask these kinds of personal data.",
+                },
+                "reuseState": described(
+                    boolean(),
+                    "transcribe: decode into the previous transcribe's state; overrides --reuse-state.",
+                ),
+                "trimSilence": described(boolean(), "transcribe: decode only the speech."),
+                "maxPauseSeconds": { "type": "number", "minimum": 0.2, "maximum": 60 },
+                "silenceThresholdDb": { "type": "number", "minimum": -100, "maximum": 0 },
+                "text": described(string(), "align: the script to time against the audio."),
+                "files": {
+                    "type": "array",
+                    "items": string(),
+                    "minItems": 1,
+                    "description": "transcribe_batch: wav paths.",
+                },
+                "concurrency": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "maximum": MAX_BATCH_CONCURRENCY,
+                    "description": "transcribe_batch: files decoded side by side.",
+                },
+                "priority": {
+                    "enum": ["interactive", "background"],
+                    "description": "Turn on the model; transcribe_batch defaults to background.",
+                },
+                "compact": described(boolean(), "Drop nulls and round floats."),
+                "precision": {
+                    "type": "integer",
+                    "minimum": 0,
+                    "description": "Decimals to round floats to (default 3 when compact).",
+                },
+                "preset": described(string(), "stream_reset/transcribe: a saved preset; preset_save: its name."),
+                "debugTimings": described(boolean(), "Add a timings breakdown to the response."),
+                "settings": described(
+                    json!({ "type": "object" }),
+                    "preset_save/context_save: the preset or context to write.",
+                ),
+            }),
+            &[],
+        ),
+        "A request frame's JSON. Binary audio, when present, follows it in the frame.",
+    )
+}
+
+fn response() -> Value {
+    let timings = json!({
+        "type": "object",
+        "description": "debugTimings: where the request's time went, in ms.",
+        "properties": {
+            "queueMs": { "type": "number" },
+            "decodeMs": { "type": "number" },
+            "postprocessMs": { "type": "number" },
+        },
+    });
+    json!({
+        "oneOf": [
+            object(
+                json!({
+                    "id": { "type": "string" },
+                    "ok": { "const": true },
+                    "result": {
+                        "anyOf": [
+                            { "$ref": "#/$defs/streamResult" },
+                            { "$ref": "#/$defs/bufferedResult" },
+                            { "$ref": "#/$defs/transcribeResult" },
+                            { "$ref": "#/$defs/alignResult" },
+                            { "type": "object" },
+                        ],
+                    },
+                    "timings": timings,
+                }),
+                &["id", "ok", "result"],
+            ),
+            object(
+                json!({
+                    "id": { "type": "string" },
+                    "ok": { "const": false },
+                    "error": { "type": "string" },
+                    "timings": timings,
+                    "fatal": described(
+                        json!({ "const": true }),
+                        "The worker panicked and exits after this frame.",
+                    ),
+                    "crashReport": nullable("string"),
+                }),
+                &["id", "ok", "error"],
+            ),
+        ],
+    })
+}
+
+fn transcribe_result() -> Value {
+    object(
+        json!({
+            "text": { "type": "string" },
+            "language": { "type": "string" },
+            "durationSeconds": { "type": "number" },
+            "previewText": nullable("string"),
+            "committedText": nullable("string"),
+            "segments": {
+                "type": "array",
+                "description": "splitChannels: one turn per channel change.",
+            },
+            "audioSeconds": { "type": "number" },
+            "decodedAudioSeconds": described(json!({ "type": "number" }), "trimSilence: speech only."),
+            "words": { "type": "array", "items": { "$ref": "#/$defs/timedWord" } },
+            "tokens": { "type": "array", "items": { "type": "object" } },
+            "redactions": {
+                "type": "object",
+                "additionalProperties": { "type": "integer" },
+            },
+            "languageProbabilities": {
+                "type": "object",
+                "additionalProperties": { "type": "number" },
+            },
+            "stateReused": described(
+                json!({ "type": "boolean" }),
+                "--reuse-state/reuseState: whether the decoder state was reused.",
+            ),
+            "stateSavedMs": described(
+                json!({ "type": "number" }),
+                "The state setup that reuse saved.",
+            ),
+        }),
+        &["text", "language", "durationSeconds"],
+    )
+}
+
+fn stream_result() -> Value {
+    let mut properties = transcribe_result()["properties"].clone();
+    let extra = json!({
+        "commitId": { "type": "integer" },
+        "commitStart": described(json!({ "type": "integer" }), "Character offset in committedText."),
+        "commitEnd": { "type": "integer" },
+        "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+        "confidenceEvent": { "$ref": "#/$defs/event" },
+        "tokens": { "type": "array", "items": { "$ref": "#/$defs/streamToken" } },
+        "macro": { "$ref": "#/$defs/macro" },
+    });
+    merge(&mut properties, extra);
+    // previewText and committedText are always sent, but compact drops them
+    // when null like any other field.
+    object(properties, &["text", "language", "durationSeconds"])
+}
+
+fn buffered_result() -> Value {
+    described(
+        object(
+            json!({
+                "buffered": { "const": true },
+                "bufferedMs": described(
+                    json!({ "type": "integer", "minimum": 0 }),
+                    "Audio waiting for the next decode.",
+                ),
+            }),
+            &["buffered", "bufferedMs"],
+        ),
+        "stream_push without a script: too little audio since the last decode; nothing new to report.",
+    )
+}
+
+fn timed_word() -> Value {
+    object(
+        json!({
+            "word": { "type": "string" },
+            "start": { "type": "number" },
+            "end": { "type": "number" },
+            "matched": described(json!({ "type": "boolean" }), "align only."),
+        }),
+        &["word", "start", "end"],
+    )
+}
+
+fn align_result() -> Value {
+    object(
+        json!({
+            "words": { "type": "array", "items": { "$ref": "#/$defs/timedWord" } },
+            "matchedWords": { "type": "integer" },
+            "totalWords": { "type": "integer" },
+            "audioSeconds": { "type": "number" },
+            "durationSeconds": { "type": "number" },
+        }),
+        &[
+            "words",
+            "matchedWords",
+            "totalWords",
+            "audioSeconds",
+            "durationSeconds",
+        ],
+    )
+}
+
+fn batch_item() -> Value {
+    described(
+        object(
+            json!({
+                "event": { "const": "batch_item" },
+                "requestId": { "type": "string" },
+                "index": { "type": "integer", "minimum": 0 },
+                "path": { "type": "string" },
+                "ok": { "type": "boolean" },
+                "result": { "$ref": "#/$defs/transcribeResult" },
+                "error": { "type": "string" },
+            }),
+            &["event", "requestId", "index", "path", "ok"],
+        ),
+        "transcribe_batch: a response frame per file, before the request's own response.",
+    )
+}
+
+fn stream_token() -> Value {
+    object(
+        json!({
+            "text": { "type": "string" },
+            "startMs": { "type": "integer" },
+            "endMs": { "type": "integer" },
+            "committed": { "type": "boolean" },
+            "confidence": { "type": "number" },
+        }),
+        &["text", "startMs", "endMs", "committed"],
+    )
+}
+
+fn macro_found() -> Value {
+    object(
+        json!({
+            "trigger": { "type": "string" },
+            "expansion": { "type": "string" },
+            "placeholders": { "type": "array", "items": { "type": "string" } },
+            "start": { "type": "integer" },
+            "end": { "type": "integer" },
+        }),
+        &["trigger", "expansion", "placeholders", "start", "end"],
+    )
+}
+
+fn constrain() -> Value {
+    json!({
+        "oneOf": [
+            { "const": "digits" },
+            object(
+                json!({ "words": { "type": "array", "items": { "type": "string" }, "minItems": 1 } }),
+                &["words"],
+            ),
+        ],
+    })
+}
+
+fn event() -> Value {
+    let at_ms = json!({ "type": "integer", "description": "Unix time in ms." });
+    json!({
+        "oneOf": [
+            object(
+                json!({
+                    "event": { "const": "lowConfidence" },
+                    "averageConfidence": { "type": "number" },
+                    "belowMs": { "type": "integer" },
+                    "atMs": at_ms,
+                }),
+                &["event", "averageConfidence", "belowMs", "atMs"],
+            ),
+            object(
+                json!({
+                    "event": { "const": "confidenceRecovered" },
+                    "averageConfidence": { "type": "number" },
+                    "atMs": at_ms,
+                }),
+                &["event", "averageConfidence", "atMs"],
+            ),
+            object(
+                json!({
+                    "event": { "const": "languageChanged" },
+                    "from": { "type": "string" },
+                    "to": { "type": "string" },
+                    "atMs": at_ms,
+                }),
+                &["event", "from", "to", "atMs"],
+            ),
+            object(
+                json!({
+                    "event": { "const": "workerCrashed" },
+                    "error": { "type": "string" },
+                    "location": nullable("string"),
+                    "crashReport": described(nullable("string"), "Path of the crash report."),
+                    "atMs": at_ms,
+                }),
+                &["event", "error", "atMs"],
+            ),
+        ],
+    })
+}
+
+fn merge(into: &mut Value, from: Value) {
+    if let (Some(into), Value::Object(from)) = (into.as_object_mut(), from) {
+        into.extend(from);
+    }
+}
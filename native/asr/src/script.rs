@@ -0,0 +1,103 @@
+use crate::stream::confidence::word_key;
+use serde_json::{json, Value};
+
+// Teleprompter mode: follows someone reading a known script aloud and
+// reports how far they've got, so the host can scroll and highlight instead
+// of typing. Recognition errors don't matter much, since it only has to find
+// where the last few heard words sit in text it already has.
+
+// How many of the latest heard words are matched against the script.
+const HEARD_WORDS: usize = 6;
+// How far past the current position a match may jump, for skipped lines.
+const LOOKAHEAD_WORDS: usize = 40;
+// Matched words needed to move; fewer is still one word taken for another.
+const MIN_MATCHED_WORDS: usize = 2;
+
+pub struct ScriptTracker {
+    words: Vec<String>,
+    keys: Vec<Option<String>>,
+    // Index of the next word to read; words.len() once finished.
+    position: usize,
+}
+
+impl ScriptTracker {
+    pub fn new(script: &str) -> Result<Self, String> {
+        let words: Vec<String> = script.split_whitespace().map(str::to_string).collect();
+        let keys: Vec<Option<String>> = words.iter().map(|word| word_key(word)).collect();
+        if keys.iter().all(Option::is_none) {
+            return Err("script has no words to follow".into());
+        }
+        Ok(Self {
+            words,
+            keys,
+            position: 0,
+        })
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    // Moves to just past the latest heard words, if they can be placed. Only
+    // ever forward: a re-decode that hears less doesn't scroll back.
+    pub fn follow(&mut self, heard_text: &str) -> bool {
+        let heard: Vec<String> = heard_text.split_whitespace().filter_map(word_key).collect();
+        let heard = &heard[heard.len().saturating_sub(HEARD_WORDS)..];
+        let Some(last) = heard.last() else {
+            return false;
+        };
+
+        // Staying put is a candidate too, so a pause (the same words heard
+        // again) doesn't drift forward onto a later, weaker match.
+        let mut best: Option<(usize, usize)> = None;
+        let until = (self.position + LOOKAHEAD_WORDS).min(self.keys.len());
+        for end in self.position.max(1)..=until {
+            if self.keys[end - 1].as_ref() != Some(last) {
+                continue;
+            }
+            let span_start = end.saturating_sub(heard.len() + 2);
+            let matched = common_words(heard, &self.keys[span_start..end]);
+            // The nearest of equally good places wins.
+            if best.is_none_or(|(_, best_matched)| matched > best_matched) {
+                best = Some((end, matched));
+            }
+        }
+
+        match best {
+            Some((end, matched))
+                if end > self.position && matched >= MIN_MATCHED_WORDS.min(heard.len()) =>
+            {
+                self.position = end;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn describe(&self) -> Value {
+        json!({
+            "position": self.position,
+            "totalWords": self.words.len(),
+            "nextWord": self.words.get(self.position),
+            "done": self.position >= self.words.len(),
+        })
+    }
+}
+
+// Longest common subsequence of heard words and script keys.
+fn common_words(heard: &[String], script: &[Option<String>]) -> usize {
+    let mut row = vec![0_usize; script.len() + 1];
+    for word in heard {
+        let mut diagonal = 0;
+        for (index, key) in script.iter().enumerate() {
+            let above = row[index + 1];
+            row[index + 1] = if key.as_ref() == Some(word) {
+                diagonal + 1
+            } else {
+                above.max(row[index])
+            };
+            diagonal = above;
+        }
+    }
+    row[script.len()]
+}
@@ -0,0 +1,169 @@
+use crate::constrain::Constraint;
+use crate::context::valid_label;
+use crate::stream::StreamConfig;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+// The user's saved tuning, so it survives restarts without the host sending
+// it again. Under the settings dir (--settings-dir, else the platform config
+// dir):
+//
+//   presets/<model>/<name>.json  streaming tuning for one model, plus the
+//                                context and constraint it goes with
+//   contexts/<label>.json        hotwords and replacements (see context.rs);
+//                                the context dir when no --context-dir is set
+//
+// <model> is the model's file or directory name, so a preset tuned for a
+// small model isn't picked up by a large one. Files are read when used, like
+// contexts, so edits apply from the next stream_reset on.
+//
+// A preset file looks like
+//   {"stream": {"decodeIntervalMs": 120, "stabilityHoldMs": 160},
+//    "context": "terminal", "constrain": "digits"}
+// with every field optional; stream fields it leaves out keep the flags'.
+
+pub struct Settings {
+    dir: PathBuf,
+    model: String,
+}
+
+pub struct Preset {
+    pub name: String,
+    pub stream: StreamConfig,
+    pub context: Option<String>,
+    pub constrain: Option<Value>,
+}
+
+impl Settings {
+    pub fn new(dir: PathBuf, model_path: &str) -> Self {
+        let model = Path::new(model_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "default".to_string());
+        Self { dir, model }
+    }
+
+    // ~/Library/Application Support/dingoflow on macOS, %APPDATA%\dingoflow
+    // on Windows, $XDG_CONFIG_HOME/dingoflow (or ~/.config/dingoflow)
+    // elsewhere.
+    pub fn platform_dir() -> Option<PathBuf> {
+        let var = |name: &str| std::env::var_os(name).map(PathBuf::from);
+        let base = if cfg!(target_os = "macos") {
+            var("HOME")?.join("Library/Application Support")
+        } else if cfg!(windows) {
+            var("APPDATA")?
+        } else {
+            var("XDG_CONFIG_HOME").or_else(|| Some(var("HOME")?.join(".config")))?
+        };
+        Some(base.join("dingoflow"))
+    }
+
+    pub fn contexts_dir(&self) -> PathBuf {
+        self.dir.join("contexts")
+    }
+
+    fn preset_path(&self, name: &str) -> Result<PathBuf, String> {
+        if !valid_label(name) {
+            return Err(format!("invalid preset name: {name}"));
+        }
+        Ok(self
+            .dir
+            .join("presets")
+            .join(&self.model)
+            .join(format!("{name}.json")))
+    }
+
+    // `base` is the worker's own streaming config, which the preset's
+    // stream fields override.
+    pub fn load_preset(&self, name: &str, base: &StreamConfig) -> Result<Preset, String> {
+        let path = self.preset_path(name)?;
+        if !path.exists() {
+            return Err(format!(
+                "preset {name} not found for model {} ({})",
+                self.model,
+                path.display()
+            ));
+        }
+        let raw = std::fs::read_to_string(&path)
+            .map_err(|err| format!("failed to read preset {}: {err}", path.display()))?;
+        let value: Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("invalid preset {}: {err}", path.display()))?;
+        parse_preset(name, &value, base)
+            .map_err(|err| format!("invalid preset {}: {err}", path.display()))
+    }
+
+    // Checked the same way it will be loaded, so a bad preset fails here
+    // rather than at the next stream_reset.
+    pub fn save_preset(
+        &self,
+        name: &str,
+        value: &Value,
+        base: &StreamConfig,
+    ) -> Result<PathBuf, String> {
+        let path = self.preset_path(name)?;
+        parse_preset(name, value, base).map_err(|err| format!("invalid preset {name}: {err}"))?;
+        write_json(&path, value)?;
+        Ok(path)
+    }
+}
+
+fn parse_preset(name: &str, value: &Value, base: &StreamConfig) -> Result<Preset, String> {
+    if !value.is_object() {
+        return Err("a preset must be an object".into());
+    }
+    let mut stream = base.clone();
+    if let Some(fields) = value.get("stream") {
+        let fields = fields.as_object().ok_or("stream must be an object")?;
+        for (field, setting) in fields {
+            let number = setting
+                .as_f64()
+                .ok_or_else(|| format!("stream.{field} must be a number"))?;
+            let whole_ms = setting.as_u64().and_then(|ms| u32::try_from(ms).ok());
+            let ms = |target: &mut u32| -> Result<(), String> {
+                *target =
+                    whole_ms.ok_or_else(|| format!("stream.{field} must be whole milliseconds"))?;
+                Ok(())
+            };
+            match field.as_str() {
+                "minAudioMs" => ms(&mut stream.min_audio_ms)?,
+                "decodeIntervalMs" => ms(&mut stream.decode_interval_ms)?,
+                "maxWindowMs" => ms(&mut stream.max_window_ms)?,
+                "leftContextMs" => ms(&mut stream.left_context_ms)?,
+                "stabilityHoldMs" => ms(&mut stream.stability_hold_ms)?,
+                "lowConfidenceMs" => ms(&mut stream.low_confidence_ms)?,
+                "lowConfidenceThreshold" => stream.low_confidence_threshold = number as f32,
+                "silenceFloorDb" => stream.silence_floor_db = number as f32,
+                other => return Err(format!("unknown stream setting: {other}")),
+            }
+        }
+        stream.validate()?;
+    }
+    let context = match value.get("context") {
+        None | Some(Value::Null) => None,
+        Some(label) => Some(label.as_str().ok_or("context must be a label")?.to_string()),
+    };
+    let constrain = value
+        .get("constrain")
+        .filter(|value| !value.is_null())
+        .cloned();
+    if let Some(constrain) = &constrain {
+        Constraint::parse(constrain)?;
+    }
+    Ok(Preset {
+        name: name.to_string(),
+        stream,
+        context,
+        constrain,
+    })
+}
+
+pub fn write_json(path: &Path, value: &Value) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+    }
+    let body = serde_json::to_string_pretty(value)
+        .map_err(|err| format!("failed to serialize {}: {err}", path.display()))?;
+    std::fs::write(path, body + "\n")
+        .map_err(|err| format!("failed to write {}: {err}", path.display()))
+}
@@ -0,0 +1,155 @@
+// Energy-based silence trimming for file decodes: leading and trailing
+// silence goes, and pauses longer than max_pause_ms are cut down to it, so a
+// meeting recording that is mostly idle decodes in a fraction of the time.
+// Timestamps from the trimmed decode are mapped back onto the original file.
+
+const FRAME_MS: usize = 20;
+// Kept around speech so soft onsets and trailing consonants survive.
+const PAD_MS: usize = 200;
+pub const DEFAULT_MAX_PAUSE_MS: u64 = 1_000;
+// Without an explicit threshold, speech is anything this far above the noise
+// floor (the quietest tenth of the recording), but never below the absolute
+// minimum, so dead-silent files don't treat dither as speech.
+const ABOVE_NOISE_FLOOR_DB: f32 = 12.0;
+const MIN_THRESHOLD_DB: f32 = -55.0;
+const NOISE_FLOOR_PERCENTILE: usize = 10;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SilenceTrim {
+    pub max_pause_ms: u64,
+    // dBFS; None estimates it from the recording.
+    pub threshold_db: Option<f32>,
+}
+
+impl SilenceTrim {
+    // trimSilence turns it on; maxPauseSeconds and silenceThresholdDb only
+    // make sense with it.
+    pub fn from_request(
+        enabled: Option<bool>,
+        max_pause_seconds: Option<f64>,
+        threshold_db: Option<f64>,
+    ) -> Result<Option<Self>, String> {
+        if !enabled.unwrap_or(false) {
+            if max_pause_seconds.is_some() || threshold_db.is_some() {
+                return Err("maxPauseSeconds and silenceThresholdDb need trimSilence".into());
+            }
+            return Ok(None);
+        }
+        let max_pause_ms = match max_pause_seconds {
+            Some(seconds) if (0.2..=60.0).contains(&seconds) => (seconds * 1000.0).round() as u64,
+            Some(_) => return Err("maxPauseSeconds must be between 0.2 and 60".into()),
+            None => DEFAULT_MAX_PAUSE_MS,
+        };
+        let threshold_db = match threshold_db {
+            Some(db) if (-100.0..=0.0).contains(&db) => Some(db as f32),
+            Some(_) => return Err("silenceThresholdDb must be between -100 and 0".into()),
+            None => None,
+        };
+        Ok(Some(Self {
+            max_pause_ms,
+            threshold_db,
+        }))
+    }
+}
+
+// A run of kept audio: where it starts in the trimmed and in the original
+// audio, in samples.
+struct Span {
+    trimmed_start: usize,
+    source_start: usize,
+    len: usize,
+}
+
+pub struct Trimmed {
+    pub audio: Vec<f32>,
+    spans: Vec<Span>,
+    sample_rate: u32,
+}
+
+impl Trimmed {
+    // Maps a time in the trimmed audio back onto the original, in seconds.
+    // A time inside a cut lands where the kept audio before it ended.
+    pub fn source_seconds(&self, trimmed_seconds: f64) -> f64 {
+        let sample = (trimmed_seconds.max(0.0) * self.sample_rate as f64).round() as usize;
+        let source = match self
+            .spans
+            .iter()
+            .rev()
+            .find(|span| span.trimmed_start <= sample)
+        {
+            Some(span) => span.source_start + (sample - span.trimmed_start).min(span.len),
+            None => sample,
+        };
+        source as f64 / self.sample_rate as f64
+    }
+}
+
+pub fn trim(audio: &[f32], sample_rate: u32, trim: &SilenceTrim) -> Trimmed {
+    let frame = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let energies: Vec<f32> = audio
+        .chunks(frame)
+        .map(|chunk| {
+            let power =
+                chunk.iter().map(|sample| sample * sample).sum::<f32>() / chunk.len() as f32;
+            10.0 * (power + 1e-10).log10()
+        })
+        .collect();
+    let threshold = trim.threshold_db.unwrap_or_else(|| {
+        let mut sorted = energies.clone();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let floor = sorted
+            .get(sorted.len() * NOISE_FLOOR_PERCENTILE / 100)
+            .copied()
+            .unwrap_or(MIN_THRESHOLD_DB);
+        (floor + ABOVE_NOISE_FLOOR_DB).max(MIN_THRESHOLD_DB)
+    });
+
+    // Speech runs as [start, end) sample ranges.
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (index, energy) in energies.iter().enumerate() {
+        if *energy < threshold {
+            continue;
+        }
+        let (start, end) = (index * frame, ((index + 1) * frame).min(audio.len()));
+        match runs.last_mut() {
+            Some(run) if run.1 == start => run.1 = end,
+            _ => runs.push((start, end)),
+        }
+    }
+
+    let samples = |ms: usize| ms * sample_rate as usize / 1000;
+    let pad = samples(PAD_MS);
+    let half_pause = samples(trim.max_pause_ms as usize) / 2;
+    // Kept source ranges: each speech run with its padding, merged whenever
+    // the pause between them is short enough to keep whole.
+    let mut kept: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in runs {
+        match kept.last_mut() {
+            Some(last) if start - last.1 <= 2 * half_pause.max(pad) => last.1 = end,
+            Some(last) => {
+                last.1 = (last.1 + half_pause.max(pad)).min(start);
+                kept.push((start.saturating_sub(half_pause.max(pad)), end));
+            }
+            None => kept.push((start.saturating_sub(pad), end)),
+        }
+    }
+    if let Some(last) = kept.last_mut() {
+        last.1 = (last.1 + pad).min(audio.len());
+    }
+
+    let mut trimmed = Vec::with_capacity(kept.iter().map(|(start, end)| end - start).sum());
+    let mut spans = Vec::with_capacity(kept.len());
+    for (start, end) in kept {
+        spans.push(Span {
+            trimmed_start: trimmed.len(),
+            source_start: start,
+            len: end - start,
+        });
+        trimmed.extend_from_slice(&audio[start..end]);
+    }
+    Trimmed {
+        audio: trimmed,
+        spans,
+        sample_rate,
+    }
+}
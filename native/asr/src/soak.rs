@@ -0,0 +1,237 @@
+use crate::backend::AsrBackend;
+use crate::engine::Engine;
+use crate::protocol::wav_to_f32;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// --soak <hours>: streams utterances through the engine back to back, as fast
+// as it decodes them, for that long, to catch what only shows up after a day
+// of dictation: memory that keeps growing and pushes that keep getting
+// slower. Every report interval a soakReport event line goes to stderr; the
+// summary comparing the first interval with the last goes to stdout.
+//
+// Utterances are the .wav files in --soak-wavs, at the model's sample rate,
+// or else generated: voiced-like bursts between pauses, which exercise
+// decoding, commits and silence gating without needing real speech.
+
+const PUSH_MS: u32 = 160;
+const REPORT_INTERVAL: Duration = Duration::from_secs(60);
+const SYNTHETIC_UTTERANCES: usize = 8;
+
+#[derive(Debug)]
+pub struct SoakOptions {
+    pub hours: f64,
+    pub wav_dir: Option<PathBuf>,
+}
+
+struct Window {
+    started: Instant,
+    push_ms: Vec<f64>,
+    utterances: u64,
+    errors: u64,
+    rss_bytes: Option<u64>,
+}
+
+impl Window {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            push_ms: Vec::new(),
+            utterances: 0,
+            errors: 0,
+            rss_bytes: None,
+        }
+    }
+
+    fn describe(&mut self) -> Value {
+        self.push_ms.sort_by(f64::total_cmp);
+        json!({
+            "utterances": self.utterances,
+            "pushes": self.push_ms.len(),
+            "errors": self.errors,
+            "p50Ms": percentile(&self.push_ms, 0.50),
+            "p95Ms": percentile(&self.push_ms, 0.95),
+            "maxMs": self.push_ms.last().map(|ms| round1(*ms)),
+            "rssMb": self.rss_bytes.map(megabytes),
+        })
+    }
+}
+
+pub fn run<B: AsrBackend>(engine: &mut Engine<B>, options: &SoakOptions) -> Result<Value, String> {
+    if !(options.hours > 0.0 && options.hours.is_finite()) {
+        return Err("--soak hours must be a positive number".into());
+    }
+    let sample_rate = engine.backend.sample_rate();
+    let utterances = match &options.wav_dir {
+        Some(dir) => load_wavs(dir, sample_rate)?,
+        None => synthetic_utterances(sample_rate),
+    };
+    engine.warmup()?;
+
+    let deadline = Instant::now() + Duration::from_secs_f64(options.hours * 3600.0);
+    let push_samples = (sample_rate * PUSH_MS / 1000) as usize;
+    let started = Instant::now();
+    let start_rss = rss_bytes();
+    let mut first: Option<Value> = None;
+    let mut window = Window::new();
+    let mut last = None;
+
+    'soak: for audio in utterances.iter().cycle() {
+        engine.stream_reset(sample_rate, None)?;
+        for chunk in audio.chunks(push_samples) {
+            let pushed = Instant::now();
+            match engine.stream_push(chunk, sample_rate) {
+                // Buffered pushes only copy samples; timing them would hide
+                // the decodes' drift under a floor of near-zero pushes.
+                Ok(update) if update.buffered_ms.is_none() => {
+                    window.push_ms.push(pushed.elapsed().as_secs_f64() * 1000.0)
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    window.errors += 1;
+                    eprintln!("soak push failed: {err}");
+                }
+            }
+            if Instant::now() >= deadline {
+                break 'soak;
+            }
+        }
+        if let Err(err) = engine.stream_flush() {
+            window.errors += 1;
+            eprintln!("soak flush failed: {err}");
+        }
+        window.utterances += 1;
+
+        if window.started.elapsed() >= REPORT_INTERVAL {
+            window.rss_bytes = rss_bytes();
+            let mut report = window.describe();
+            report["event"] = json!("soakReport");
+            report["elapsedS"] = json!(started.elapsed().as_secs());
+            eprintln!("{report}");
+            first.get_or_insert_with(|| report.clone());
+            last = Some(report);
+            window = Window::new();
+        }
+    }
+    if !window.push_ms.is_empty() || last.is_none() {
+        window.rss_bytes = rss_bytes();
+        last = Some(window.describe());
+    }
+    let last = last.unwrap_or_default();
+    let first = first.unwrap_or_else(|| last.clone());
+
+    let end_rss = rss_bytes();
+    let drift = |field: &str| match (first[field].as_f64(), last[field].as_f64()) {
+        (Some(first), Some(last)) => Some(round1(last - first)),
+        _ => None,
+    };
+    Ok(json!({
+        "hours": options.hours,
+        "elapsedS": started.elapsed().as_secs(),
+        "source": options.wav_dir.as_ref().map_or("synthetic".to_string(), |dir| dir.display().to_string()),
+        "rssStartMb": start_rss.map(megabytes),
+        "rssEndMb": end_rss.map(megabytes),
+        "rssGrowthMb": start_rss.zip(end_rss).map(|(start, end)| round1(megabytes(end) - megabytes(start))),
+        "p95DriftMs": drift("p95Ms"),
+        "p50DriftMs": drift("p50Ms"),
+        "first": first,
+        "last": last,
+    }))
+}
+
+fn load_wavs(dir: &Path, sample_rate: u32) -> Result<Vec<Vec<f32>>, String> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|err| format!("failed to read --soak-wavs {}: {err}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("no .wav files in {}", dir.display()));
+    }
+    paths
+        .iter()
+        .map(|path| {
+            let (audio, rate) = wav_to_f32(&path.to_string_lossy())?;
+            if rate != sample_rate {
+                return Err(format!(
+                    "{} is {rate} Hz; the model takes {sample_rate} Hz",
+                    path.display()
+                ));
+            }
+            Ok(audio)
+        })
+        .collect()
+}
+
+// A few seconds each of harmonic bursts with a wobbling pitch, the way
+// voiced speech looks to a level meter, separated by pauses long enough to
+// commit. Deterministic, so two soak runs see the same audio.
+fn synthetic_utterances(sample_rate: u32) -> Vec<Vec<f32>> {
+    let rate = sample_rate as f32;
+    let mut noise = 0x2545_f491_u32;
+    (0..SYNTHETIC_UTTERANCES)
+        .map(|utterance| {
+            let seconds = 3.0 + utterance as f32 * 0.5;
+            let total = (seconds * rate) as usize;
+            (0..total)
+                .map(|index| {
+                    let t = index as f32 / rate;
+                    // 400 ms syllables, 120 ms gaps, a 600 ms pause every few.
+                    let syllable = t % 0.52 < 0.4 && t % 2.6 < 2.0;
+                    noise ^= noise << 13;
+                    noise ^= noise >> 17;
+                    noise ^= noise << 5;
+                    let hiss = (noise as f32 / u32::MAX as f32 - 0.5) * 0.01;
+                    if !syllable {
+                        return hiss;
+                    }
+                    let pitch = 120.0 + 30.0 * (t * 3.0 + utterance as f32).sin();
+                    let phase = std::f32::consts::TAU * pitch * t;
+                    let voiced = (1..=4)
+                        .map(|harmonic| (phase * harmonic as f32).sin() / harmonic as f32)
+                        .sum::<f32>();
+                    voiced * 0.2 + hiss
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[f64], quantile: f64) -> Option<f64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    Some(round1(sorted[index]))
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn megabytes(bytes: u64) -> f64 {
+    round1(bytes as f64 / (1024.0 * 1024.0))
+}
+
+// Resident memory: /proc where there is one, else ps (macOS).
+fn rss_bytes() -> Option<u64> {
+    if let Ok(status) = std::fs::read_to_string("/proc/self/status") {
+        let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        return Some(kb * 1024);
+    }
+    let output = std::process::Command::new("ps")
+        .args(["-o", "rss=", "-p", &std::process::id().to_string()])
+        .output()
+        .ok()?;
+    let kb: u64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(kb * 1024)
+}
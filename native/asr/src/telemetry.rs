@@ -0,0 +1,234 @@
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+const LATENCY_BUCKETS_MS: [f64; 9] = [
+    25.0, 50.0, 100.0, 250.0, 500.0, 1_000.0, 2_500.0, 5_000.0, 10_000.0,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "debug" => Ok(Self::Debug),
+            "info" => Ok(Self::Info),
+            "warn" => Ok(Self::Warn),
+            "error" => Ok(Self::Error),
+            _ => Err("Invalid --log-level value".into()),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err("Invalid --log-format value".into()),
+        }
+    }
+}
+
+struct Logger {
+    format: LogFormat,
+    min_level: LogLevel,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+pub fn init_logging(format: LogFormat, min_level: LogLevel) {
+    let _ = LOGGER.set(Logger { format, min_level });
+}
+
+// JSON lines use the same shape as the host's StructuredLogger
+// ({ ts, level, message, ...fields }) so they can be appended to its log as-is.
+pub fn log(level: LogLevel, message: &str, fields: serde_json::Value) {
+    let logger = LOGGER.get_or_init(|| Logger {
+        format: LogFormat::Text,
+        min_level: LogLevel::Info,
+    });
+    if level < logger.min_level {
+        return;
+    }
+
+    match logger.format {
+        LogFormat::Json => {
+            let mut entry = json!({
+                "ts": iso8601_now(),
+                "level": level.name(),
+                "message": message
+            });
+            if let (Some(entry), serde_json::Value::Object(fields)) =
+                (entry.as_object_mut(), fields)
+            {
+                entry.extend(fields);
+            }
+            eprintln!("{entry}");
+        }
+        LogFormat::Text => match fields {
+            serde_json::Value::Object(fields) if !fields.is_empty() => {
+                let detail = fields
+                    .iter()
+                    .map(|(key, value)| format!("{key}={value}"))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                eprintln!("{message} {detail}");
+            }
+            _ => eprintln!("{message}"),
+        },
+    }
+}
+
+fn iso8601_now() -> String {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs() as i64;
+    let millis = since_epoch.subsec_millis();
+    let days = secs.div_euclid(86_400);
+    let secs_of_day = secs.rem_euclid(86_400);
+
+    // Civil-from-days (Howard Hinnant), valid for the whole proleptic Gregorian calendar.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{millis:03}Z",
+        secs_of_day / 3_600,
+        (secs_of_day % 3_600) / 60,
+        secs_of_day % 60
+    )
+}
+
+#[derive(Default)]
+struct ActionCounters {
+    ok: u64,
+    error: u64,
+}
+
+pub struct Metrics {
+    started: Instant,
+    requests: BTreeMap<String, ActionCounters>,
+    latency_buckets: [u64; LATENCY_BUCKETS_MS.len()],
+    latency_count: u64,
+    latency_sum_ms: f64,
+    audio_seconds_total: f64,
+    decode_seconds_total: f64,
+    last_rtf: Option<f64>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            requests: BTreeMap::new(),
+            latency_buckets: [0; LATENCY_BUCKETS_MS.len()],
+            latency_count: 0,
+            latency_sum_ms: 0.0,
+            audio_seconds_total: 0.0,
+            decode_seconds_total: 0.0,
+            last_rtf: None,
+        }
+    }
+
+    pub fn record_request(&mut self, action: &str, ok: bool) {
+        let counters = self.requests.entry(action.to_string()).or_default();
+        if ok {
+            counters.ok += 1;
+        } else {
+            counters.error += 1;
+        }
+    }
+
+    // Real-time factor is decode time over audio time; < 1.0 is faster than real time.
+    pub fn record_decode(&mut self, latency_ms: f64, audio_seconds: f64, decode_seconds: f64) {
+        for (bucket, upper_bound) in self.latency_buckets.iter_mut().zip(LATENCY_BUCKETS_MS) {
+            if latency_ms <= upper_bound {
+                *bucket += 1;
+            }
+        }
+        self.latency_count += 1;
+        self.latency_sum_ms += latency_ms;
+
+        if audio_seconds > 0.0 {
+            self.audio_seconds_total += audio_seconds;
+            self.decode_seconds_total += decode_seconds;
+            self.last_rtf = Some(decode_seconds / audio_seconds);
+        }
+    }
+
+    pub fn snapshot(&self) -> serde_json::Value {
+        let requests: serde_json::Map<String, serde_json::Value> = self
+            .requests
+            .iter()
+            .map(|(action, counters)| {
+                (
+                    action.clone(),
+                    json!({ "ok": counters.ok, "error": counters.error }),
+                )
+            })
+            .collect();
+        let buckets: Vec<serde_json::Value> = self
+            .latency_buckets
+            .iter()
+            .zip(LATENCY_BUCKETS_MS)
+            .map(|(count, upper_bound)| json!({ "le": upper_bound, "count": count }))
+            .collect();
+        let mean_rtf = if self.audio_seconds_total > 0.0 {
+            Some(self.decode_seconds_total / self.audio_seconds_total)
+        } else {
+            None
+        };
+
+        json!({
+            "uptimeSeconds": self.started.elapsed().as_secs(),
+            "requests": requests,
+            "decodeLatencyMs": {
+                "buckets": buckets,
+                "count": self.latency_count,
+                "sum": self.latency_sum_ms.round()
+            },
+            "audioSecondsTotal": (self.audio_seconds_total * 1000.0).round() / 1000.0,
+            "realtimeFactor": {
+                "last": self.last_rtf,
+                "mean": mean_rtf
+            }
+        })
+    }
+}
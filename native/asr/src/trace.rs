@@ -0,0 +1,161 @@
+use dingoflow_frame::Frame;
+use serde_json::{json, Value};
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Rotate once the live file passes this, keeping this many older ones as
+// <path>.1 (newest) .. <path>.N.
+const MAX_TRACE_BYTES: u64 = 16 * 1024 * 1024;
+const ROTATED_FILES: usize = 3;
+// Hex digits of the payload hash kept per frame: enough to tell frames apart
+// in a reproduction without the trace carrying audio or transcripts.
+const HASH_DIGITS: usize = 12;
+
+// Logs every frame the server reads and writes, one JSONL line each:
+//
+//   {"event":"request","frame":1,"id":"...","action":"stream_push",
+//    "jsonBytes":..,"audioBytes":..,"hash":"...","atMs":...}
+//   {"event":"response","frame":1,"id":"...","action":"stream_push",
+//    "ok":true,"bytes":..,"latencyMs":..,"writeMs":..,"hash":"...","atMs":...}
+//   {"event":"fatal","error":"audio frame too large: ...","atMs":...}
+//
+// Only sizes and hashes are kept, so a trace can be attached to a bug report
+// as is.
+pub struct FrameTrace {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    frames: u64,
+    // Every request gets exactly one response, in order, so responses are
+    // numbered on their own: requests are read ahead of the answers.
+    answered: u64,
+}
+
+impl FrameTrace {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let file = open_append(path)?;
+        let written = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            frames: 0,
+            answered: 0,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Logs an inbound frame; `id` and `action` are None when its JSON didn't
+    // parse.
+    pub fn request(
+        &mut self,
+        id: Option<&str>,
+        action: Option<&str>,
+        frame: &Frame,
+    ) -> Result<(), String> {
+        self.frames += 1;
+        self.write(json!({
+            "event": "request",
+            "frame": self.frames,
+            "id": id,
+            "action": action,
+            "jsonBytes": frame.json.len(),
+            "audioBytes": frame.audio.len(),
+            "hash": payload_hash(&[&frame.json, &frame.audio]),
+        }))
+    }
+
+    // Logs the response to the oldest unanswered request, `latency` after it
+    // was read; `write` is what serializing and writing it took.
+    pub fn response(
+        &mut self,
+        action: Option<&str>,
+        response: &Value,
+        latency: Duration,
+        write: Duration,
+    ) -> Result<(), String> {
+        let body = serde_json::to_vec(response).unwrap_or_default();
+        self.answered += 1;
+        self.write(json!({
+            "event": "response",
+            "frame": self.answered,
+            "id": response["id"],
+            "action": action,
+            "ok": response["ok"],
+            "bytes": body.len(),
+            "latencyMs": latency.as_micros() as f64 / 1000.0,
+            "writeMs": write.as_micros() as f64 / 1000.0,
+            "hash": payload_hash(&[&body]),
+        }))
+    }
+
+    // Logs the framing error the server is about to exit on.
+    pub fn fatal(&mut self, error: &str) -> Result<(), String> {
+        self.write(json!({ "event": "fatal", "error": error }))
+    }
+
+    fn write(&mut self, mut entry: Value) -> Result<(), String> {
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        entry["atMs"] = at_ms.into();
+        let mut line = entry.to_string();
+        line.push('\n');
+
+        if self.written > 0 && self.written + line.len() as u64 > MAX_TRACE_BYTES {
+            self.rotate()?;
+        }
+        self.file
+            .write_all(line.as_bytes())
+            .map_err(|err| format!("failed to append to {}: {err}", self.path.display()))?;
+        self.written += line.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<(), String> {
+        for index in (1..ROTATED_FILES).rev() {
+            let _ = fs::rename(
+                rotated_path(&self.path, index),
+                rotated_path(&self.path, index + 1),
+            );
+        }
+        fs::rename(&self.path, rotated_path(&self.path, 1))
+            .map_err(|err| format!("failed to rotate {}: {err}", self.path.display()))?;
+        self.file = open_append(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|err| format!("failed to open frame trace {}: {err}", path.display()))
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(format!(".{index}"));
+    PathBuf::from(name)
+}
+
+// FNV-1a: stable across runs and platforms, unlike std's hasher.
+fn payload_hash(parts: &[&[u8]]) -> String {
+    let mut hash = 0xcbf2_9ce4_8422_2325_u64;
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    let mut hex = format!("{hash:016x}");
+    hex.truncate(HASH_DIGITS);
+    hex
+}
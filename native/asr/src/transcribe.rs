@@ -0,0 +1,232 @@
+use crate::align::{align_script, smooth_word_timings};
+use crate::backend::{normalize_text, AsrBackend};
+use crate::engine::{Engine, Transcript};
+use crate::protocol::{make_asr_result, Request};
+use crate::redact::{redact_text, PiiKind};
+use crate::silence::{self, SilenceTrim};
+use serde_json::{json, Value};
+use std::time::Instant;
+
+// What a transcribe can ask for beyond the text. Language detection, word
+// timings and tokens come from the backend, so engines without them refuse
+// the requests that ask; see AsrBackend.
+#[derive(Default, Clone)]
+pub struct TranscribeOptions {
+    pub return_tokens: bool,
+    pub word_timestamps: bool,
+    pub language_candidates: Vec<String>,
+    pub redact: Vec<PiiKind>,
+    pub trim_silence: Option<SilenceTrim>,
+    // None keeps the worker's --reuse-state.
+    pub reuse_state: Option<bool>,
+}
+
+impl TranscribeOptions {
+    pub fn from_request(req: &Request) -> Result<Self, String> {
+        let mut redact = req
+            .redact
+            .iter()
+            .flatten()
+            .map(|kind| PiiKind::parse(kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        redact.sort();
+        redact.dedup();
+
+        let return_tokens = req.return_tokens.unwrap_or(false);
+        if return_tokens && !redact.is_empty() {
+            // Raw token text would leak exactly what redaction is meant to hide.
+            return Err("returnTokens cannot be combined with redact".into());
+        }
+        let word_timestamps = req.word_timestamps.unwrap_or(false);
+        if word_timestamps && !redact.is_empty() {
+            return Err("wordTimestamps cannot be combined with redact".into());
+        }
+
+        Ok(Self {
+            return_tokens,
+            word_timestamps,
+            language_candidates: req.language_candidates.clone().unwrap_or_default(),
+            redact,
+            trim_silence: SilenceTrim::from_request(
+                req.trim_silence,
+                req.max_pause_seconds,
+                req.silence_threshold_db,
+            )?,
+            reuse_state: req.reuse_state,
+        })
+    }
+}
+
+fn audio_seconds(audio: &[f32], sample_rate: u32) -> f64 {
+    let seconds = audio.len() as f64 / sample_rate.max(1) as f64;
+    (seconds * 1000.0).round() / 1000.0
+}
+
+fn audio_millis(audio: &[f32], sample_rate: u32) -> i64 {
+    (audio.len() as i64 * 1000) / sample_rate.max(1) as i64
+}
+
+// `decode` is the engine's transcribe with whatever context or constraint
+// the request picked. With trimSilence only the speech gets decoded, and
+// times are mapped back onto the caller's audio.
+pub fn transcribe<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    audio: &[f32],
+    sample_rate: u32,
+    options: &TranscribeOptions,
+    decode: impl FnOnce(&mut Engine<B>, &[f32]) -> Result<Transcript, String>,
+) -> Result<Value, String> {
+    let started = Instant::now();
+    let trimmed = options
+        .trim_silence
+        .map(|trim| silence::trim(audio, sample_rate, &trim));
+    let speech = trimmed.as_ref().map_or(audio, |trimmed| &trimmed.audio[..]);
+    let to_source = |seconds: f64| match &trimmed {
+        Some(trimmed) => (trimmed.source_seconds(seconds) * 1000.0).round() / 1000.0,
+        None => seconds,
+    };
+    if speech.is_empty() {
+        // Nothing above the silence threshold: nothing for the model to hear.
+        let language = match options.language_candidates.first() {
+            Some(language) => language.clone(),
+            None => engine.backend.describe()["language"]
+                .as_str()
+                .unwrap_or("en")
+                .to_string(),
+        };
+        let mut result = make_asr_result(
+            String::new(),
+            &language,
+            started.elapsed().as_secs_f64(),
+            None,
+            None,
+        );
+        result["audioSeconds"] = json!(audio_seconds(audio, sample_rate));
+        result["decodedAudioSeconds"] = json!(0.0);
+        if !options.redact.is_empty() {
+            result["redactions"] = json!(redact_text("", &options.redact).1);
+        }
+        if options.return_tokens {
+            result["tokens"] = json!([]);
+        }
+        if options.word_timestamps {
+            result["words"] = json!([]);
+        }
+        return Ok(result);
+    }
+
+    // Before detection, which already decodes into the state.
+    let state = engine.backend.prepare_transcribe(options.reuse_state)?;
+    let detected = if options.language_candidates.is_empty() {
+        None
+    } else {
+        Some(
+            engine
+                .backend
+                .detect_language(speech, &options.language_candidates)?,
+        )
+    };
+    if let Some((language, _)) = &detected {
+        engine.backend.set_language(Some(language));
+    }
+    let transcript = decode(engine, speech);
+    if detected.is_some() {
+        engine.backend.set_language(None);
+    }
+    let transcript = transcript?;
+
+    let mut text = transcript.text;
+    let mut redactions = None;
+    if !options.redact.is_empty() {
+        let (redacted, counts) = redact_text(&text, &options.redact);
+        text = redacted;
+        redactions = Some(counts);
+    }
+
+    let mut result = make_asr_result(
+        text,
+        &transcript.language,
+        started.elapsed().as_secs_f64(),
+        None,
+        None,
+    );
+    result["audioSeconds"] = json!(audio_seconds(audio, sample_rate));
+    if trimmed.is_some() {
+        result["decodedAudioSeconds"] = json!(audio_seconds(speech, sample_rate));
+    }
+    if let Some(counts) = redactions {
+        result["redactions"] = json!(counts);
+    }
+    if let Some((_, probabilities)) = detected {
+        result["languageProbabilities"] = probabilities;
+    }
+    if let Some(state) = state {
+        result["stateReused"] = json!(state.reused);
+        result["stateSavedMs"] = json!(state.saved_ms.round());
+    }
+    if options.return_tokens {
+        let mut tokens = engine.backend.tokens()?;
+        if trimmed.is_some() {
+            for token in &mut tokens {
+                for edge in ["start", "end"] {
+                    token[edge] = json!(to_source(token[edge].as_f64().unwrap_or(0.0)));
+                }
+            }
+        }
+        result["tokens"] = json!(tokens);
+    }
+    if options.word_timestamps {
+        let mut words = engine.backend.timed_words()?;
+        smooth_word_timings(&mut words, audio_millis(speech, sample_rate));
+        let words: Vec<Value> = words
+            .iter()
+            .map(|word| {
+                json!({
+                    "word": word.text,
+                    "start": to_source(word.start_ms as f64 / 1000.0),
+                    "end": to_source(word.end_ms as f64 / 1000.0)
+                })
+            })
+            .collect();
+        result["words"] = json!(words);
+    }
+    Ok(result)
+}
+
+// Times each word of a known script against the audio; see align.rs.
+pub fn align<B: AsrBackend>(
+    engine: &mut Engine<B>,
+    audio: &[f32],
+    sample_rate: u32,
+    script: &str,
+) -> Result<Value, String> {
+    let script = normalize_text(script);
+    if script.is_empty() {
+        return Err("align requires non-empty text".into());
+    }
+
+    let started = Instant::now();
+    let recognized = engine.align_words(audio, sample_rate, &script)?;
+    let aligned = align_script(&script, &recognized, audio_millis(audio, sample_rate));
+    let matched_words = aligned.iter().filter(|word| word.matched).count();
+    let words: Vec<Value> = aligned
+        .iter()
+        .map(|word| {
+            json!({
+                "word": word.text,
+                "start": word.start_ms as f64 / 1000.0,
+                "end": word.end_ms as f64 / 1000.0,
+                "matched": word.matched
+            })
+        })
+        .collect();
+    let duration_seconds = started.elapsed().as_secs_f64();
+
+    Ok(json!({
+        "words": words,
+        "matchedWords": matched_words,
+        "totalWords": aligned.len(),
+        "audioSeconds": audio_seconds(audio, sample_rate),
+        "durationSeconds": ((duration_seconds * 1000.0).round() / 1000.0)
+    }))
+}
@@ -0,0 +1,126 @@
+use std::io::{self, BufReader, Read, Write};
+use std::net::TcpListener;
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub enum ListenEndpoint {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl ListenEndpoint {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if let Some(path) = value.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("--listen unix: requires a socket path".into());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        if let Some(address) = value.strip_prefix("tcp:") {
+            if address.is_empty() {
+                return Err("--listen tcp: requires host:port".into());
+            }
+            return Ok(Self::Tcp(address.to_string()));
+        }
+
+        Err(format!(
+            "Invalid --listen value: {value} (expected unix:/path/to.sock or tcp:host:port)"
+        ))
+    }
+}
+
+pub struct Connection {
+    pub reader: Box<dyn Read + Send>,
+    pub writer: Box<dyn Write + Send>,
+    pub peer: String,
+}
+
+enum ListenerKind {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    Tcp(TcpListener),
+}
+
+pub struct Listener {
+    kind: ListenerKind,
+}
+
+impl Listener {
+    pub fn bind(endpoint: &ListenEndpoint) -> Result<Self, String> {
+        match endpoint {
+            #[cfg(unix)]
+            ListenEndpoint::Unix(path) => {
+                // A previous worker that was killed leaves its socket file behind.
+                if path.exists() {
+                    std::fs::remove_file(path).map_err(|err| {
+                        format!("failed to remove stale socket {}: {err}", path.display())
+                    })?;
+                }
+                let listener = UnixListener::bind(path)
+                    .map_err(|err| format!("failed to listen on {}: {err}", path.display()))?;
+                Ok(Self {
+                    kind: ListenerKind::Unix(listener, path.clone()),
+                })
+            }
+            #[cfg(not(unix))]
+            ListenEndpoint::Unix(_) => {
+                Err("unix sockets are not supported on this platform".into())
+            }
+            ListenEndpoint::Tcp(address) => {
+                let listener = TcpListener::bind(address)
+                    .map_err(|err| format!("failed to listen on {address}: {err}"))?;
+                Ok(Self {
+                    kind: ListenerKind::Tcp(listener),
+                })
+            }
+        }
+    }
+
+    pub fn local_description(&self) -> String {
+        match &self.kind {
+            #[cfg(unix)]
+            ListenerKind::Unix(_, path) => format!("unix:{}", path.display()),
+            ListenerKind::Tcp(listener) => listener
+                .local_addr()
+                .map(|addr| format!("tcp:{addr}"))
+                .unwrap_or_else(|_| "tcp:unknown".to_string()),
+        }
+    }
+
+    pub fn accept(&self) -> io::Result<Connection> {
+        match &self.kind {
+            #[cfg(unix)]
+            ListenerKind::Unix(listener, path) => {
+                let (stream, _) = listener.accept()?;
+                let writer = stream.try_clone()?;
+                Ok(Connection {
+                    reader: Box::new(BufReader::new(stream)),
+                    writer: Box::new(writer),
+                    peer: format!("unix:{}", path.display()),
+                })
+            }
+            ListenerKind::Tcp(listener) => {
+                let (stream, addr) = listener.accept()?;
+                stream.set_nodelay(true)?;
+                let writer = stream.try_clone()?;
+                Ok(Connection {
+                    reader: Box::new(BufReader::new(stream)),
+                    writer: Box::new(writer),
+                    peer: format!("tcp:{addr}"),
+                })
+            }
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let ListenerKind::Unix(_, path) = &self.kind {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
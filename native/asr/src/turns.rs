@@ -0,0 +1,97 @@
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+// How urgently a request wants the model. Interactive is someone waiting on
+// the words (a dictation push, a one-off transcribe); background is work
+// nobody watches, like a folder of files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive,
+    Background,
+}
+
+impl Priority {
+    // Batches default to background and everything else to interactive.
+    pub fn for_request(priority: Option<&str>, action: &str) -> Result<Self, String> {
+        match priority {
+            None if action == "transcribe_batch" => Ok(Priority::Background),
+            None | Some("interactive") => Ok(Priority::Interactive),
+            Some("background") => Ok(Priority::Background),
+            Some(other) => Err(format!(
+                "Unsupported priority: {other} (expected interactive or background)"
+            )),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Gate {
+    interactive_running: bool,
+    interactive_waiting: usize,
+    background_running: usize,
+}
+
+// Who decodes next when clients share the model. An interactive turn runs
+// alone and waits only for decodes already under way; background turns run
+// side by side but none starts while an interactive one is running or
+// waiting. A batch takes a turn per file, so live dictation gets in between
+// two files instead of after the last one.
+#[derive(Default)]
+pub struct Turns {
+    gate: Mutex<Gate>,
+    changed: Condvar,
+}
+
+pub struct Turn<'a> {
+    turns: &'a Turns,
+    priority: Priority,
+}
+
+impl Turns {
+    pub fn take(&self, priority: Priority) -> Turn<'_> {
+        let mut gate = self.gate();
+        match priority {
+            Priority::Interactive => {
+                gate.interactive_waiting += 1;
+                while gate.interactive_running || gate.background_running > 0 {
+                    gate = self.wait(gate);
+                }
+                gate.interactive_waiting -= 1;
+                gate.interactive_running = true;
+            }
+            Priority::Background => {
+                while gate.interactive_running || gate.interactive_waiting > 0 {
+                    gate = self.wait(gate);
+                }
+                gate.background_running += 1;
+            }
+        }
+        Turn {
+            turns: self,
+            priority,
+        }
+    }
+
+    // The gate only holds counters, so a panic elsewhere can't leave it
+    // half-updated.
+    fn gate(&self) -> MutexGuard<'_, Gate> {
+        self.gate.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn wait<'a>(&self, gate: MutexGuard<'a, Gate>) -> MutexGuard<'a, Gate> {
+        self.changed
+            .wait(gate)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for Turn<'_> {
+    fn drop(&mut self) {
+        let mut gate = self.turns.gate();
+        match self.priority {
+            Priority::Interactive => gate.interactive_running = false,
+            Priority::Background => gate.background_running -= 1,
+        }
+        drop(gate);
+        self.turns.changed.notify_all();
+    }
+}
@@ -0,0 +1,426 @@
+use crate::align::TimedWord;
+use crate::backend::{AsrBackend, Decoded, StateReuse, TimedPiece};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+    WhisperTokenData,
+};
+
+pub const SAMPLE_RATE: u32 = 16_000;
+const WARMUP_AUDIO_MS: usize = 1_000;
+
+pub fn check_model_file(model_path: &str) -> Result<(), String> {
+    let path = Path::new(model_path);
+    if !path.exists() {
+        return Err(format!("Model path not found: {model_path}"));
+    }
+
+    if !path.is_file() {
+        return Err(
+            "Native whisper backend expects DINGOFLOW_ASR_MODEL_PATH to be a ggml model file (.bin)."
+                .into(),
+        );
+    }
+
+    Ok(())
+}
+
+fn create_state(context: &WhisperContext) -> Result<(WhisperState, f64), String> {
+    let started = Instant::now();
+    let state = context
+        .create_state()
+        .map_err(|err| format!("failed to create whisper state: {err}"))?;
+    Ok((state, started.elapsed().as_secs_f64() * 1000.0))
+}
+
+pub struct WhisperBackend {
+    // Shared with forks, which only add a state of their own.
+    context: Arc<WhisperContext>,
+    // whisper_full clears the previous run's segments and KV cache itself, so
+    // streams decode every window into one state. Transcribes get a fresh one
+    // each unless --reuse-state (or the request's reuseState) says otherwise;
+    // see prepare_transcribe.
+    state: WhisperState,
+    reuse_state: bool,
+    // What creating `state` took, which reusing it saves.
+    state_setup_ms: f64,
+    // Whether a decode has run in `state` yet.
+    state_used: bool,
+    // A failed decode may leave the state half-initialized, so it isn't
+    // reused after one.
+    state_failed: bool,
+    threads: i32,
+    language: String,
+    // With two or more, every decode picks among them first; see with_languages.
+    languages: Vec<(String, i32)>,
+    // A request's pick, overriding both; see set_language.
+    language_override: Option<String>,
+    use_gpu: bool,
+    // Hotwords of the active context, fed to the decoder as its prompt.
+    prompt: String,
+}
+
+impl WhisperBackend {
+    pub fn load(model_path: &str, threads: i32, language: &str) -> Result<Self, String> {
+        let params = WhisperContextParameters::default();
+        let use_gpu = params.use_gpu;
+        let context = WhisperContext::new_with_params(model_path, params)
+            .map_err(|err| format!("Failed to load whisper model: {err}"))?;
+        let (state, state_setup_ms) = create_state(&context)?;
+
+        Ok(Self {
+            context: Arc::new(context),
+            state,
+            reuse_state: false,
+            state_setup_ms,
+            state_used: false,
+            state_failed: false,
+            threads,
+            language: language.to_string(),
+            languages: Vec::new(),
+            language_override: None,
+            use_gpu,
+            prompt: String::new(),
+        })
+    }
+
+    // Keeps one state across transcribe requests instead of creating one per
+    // request, saving its setup; results then report stateReused and
+    // stateSavedMs.
+    pub fn with_reuse_state(mut self, reuse_state: bool) -> Self {
+        self.reuse_state = reuse_state;
+        self
+    }
+
+    // Bilingual dictation: each decode window is decoded in whichever of
+    // these whisper hears, instead of forcing --language onto a quote in the
+    // other one. Restricting detection to the speaker's languages keeps short
+    // windows from being classified as something exotic, and a window that
+    // flips language between decodes doesn't decode the same twice, so the
+    // streamer won't commit it until it settles.
+    pub fn with_languages(mut self, languages: &[String]) -> Result<Self, String> {
+        if languages.len() > 1 && !self.context.is_multilingual() {
+            return Err("--languages needs a multilingual whisper model".into());
+        }
+        self.languages = languages
+            .iter()
+            .map(|language| {
+                let code = language.trim().to_lowercase();
+                whisper_rs::get_lang_id(&code)
+                    .map(|id| (code, id))
+                    .ok_or_else(|| format!("unknown language in --languages: {language}"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(self)
+    }
+
+    fn pick_language(&mut self, audio: &[f32]) -> Result<String, String> {
+        if let Some(language) = &self.language_override {
+            return Ok(language.clone());
+        }
+        if self.languages.len() < 2 {
+            return Ok(self.language.clone());
+        }
+        let probabilities = self.language_probabilities(audio)?;
+        let probability = |id: i32| probabilities.get(id as usize).copied().unwrap_or(0.0);
+        let best = self
+            .languages
+            .iter()
+            .max_by(|a, b| probability(a.1).total_cmp(&probability(b.1)))
+            .map(|(code, _)| code.clone());
+        Ok(best.unwrap_or_else(|| self.language.clone()))
+    }
+
+    fn language_probabilities(&mut self, audio: &[f32]) -> Result<Vec<f32>, String> {
+        let threads = self.threads.max(1) as usize;
+        self.state
+            .pcm_to_mel(audio, threads)
+            .map_err(|err| format!("failed to compute mel spectrogram: {err}"))?;
+        let (_, probabilities) = self
+            .state
+            .lang_detect(0, threads)
+            .map_err(|err| format!("language detection failed: {err}"))?;
+        Ok(probabilities)
+    }
+
+    fn run(&mut self, audio: &[f32], language: &str) -> Result<(), String> {
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_n_threads(self.threads);
+        params.set_no_context(true);
+        params.set_print_special(false);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_timestamps(false);
+        params.set_language(Some(language));
+        params.set_translate(false);
+        params.set_token_timestamps(true);
+        if !self.prompt.is_empty() {
+            params.set_initial_prompt(&self.prompt);
+        }
+
+        self.state_used = true;
+        if let Err(err) = self.state.full(params, audio) {
+            self.state_failed = true;
+            return Err(format!("whisper decode failed: {err}"));
+        }
+        Ok(())
+    }
+
+    fn renew_state(&mut self) -> Result<(), String> {
+        (self.state, self.state_setup_ms) = create_state(&self.context)?;
+        self.state_used = false;
+        self.state_failed = false;
+        Ok(())
+    }
+
+    // Calls `visit` with each text token of the latest decode (special
+    // tokens skipped), its segment index and its text.
+    fn each_token(
+        &self,
+        mut visit: impl FnMut(i32, &WhisperTokenData, &str),
+    ) -> Result<(), String> {
+        let first_special_token = self.context.token_eot();
+        for i in 0..self.state.full_n_segments() {
+            let segment = self
+                .state
+                .get_segment(i)
+                .ok_or_else(|| format!("failed to read segment {i}"))?;
+
+            for t in 0..segment.n_tokens() {
+                let token = segment
+                    .get_token(t)
+                    .ok_or_else(|| format!("failed to read token {t} of segment {i}"))?;
+                let data = token.token_data();
+                if data.id >= first_special_token {
+                    continue;
+                }
+                let piece = token
+                    .to_str_lossy()
+                    .map_err(|err| format!("failed to read token text: {err}"))?;
+                visit(i, &data, &piece);
+            }
+        }
+        Ok(())
+    }
+
+    // Words with their end times, rebuilt from BPE tokens.
+    fn collect_words(&self) -> Result<Vec<TimedPiece>, String> {
+        let mut words: Vec<TimedPiece> = Vec::new();
+        self.each_token(|_, data, piece| {
+            // Whisper's BPE marks word starts with a leading space.
+            let starts_word = piece.starts_with(' ') || words.is_empty();
+            let piece = piece.trim();
+            if piece.is_empty() {
+                return;
+            }
+
+            // t1 is in centiseconds. A word is as sure as its least sure
+            // token.
+            let end_seconds = data.t1 as f32 / 100.0;
+            match words.last_mut() {
+                Some(word) if !starts_word => {
+                    word.text.push_str(piece);
+                    word.end_seconds = word.end_seconds.max(end_seconds);
+                    word.confidence = word.confidence.map(|p| p.min(data.p));
+                }
+                _ => words.push(TimedPiece {
+                    text: piece.to_string(),
+                    end_seconds,
+                    confidence: Some(data.p),
+                }),
+            }
+        })?;
+        Ok(words)
+    }
+
+    fn collect_text(&self) -> Result<String, String> {
+        let mut text = String::new();
+        for i in 0..self.state.full_n_segments() {
+            let segment = self
+                .state
+                .get_segment(i)
+                .ok_or_else(|| format!("failed to read segment {i}"))?;
+            let segment_text = segment
+                .to_str()
+                .map_err(|err| format!("failed to read segment text: {err}"))?;
+            text.push_str(segment_text);
+        }
+        Ok(text)
+    }
+}
+
+impl AsrBackend for WhisperBackend {
+    fn describe(&self) -> Value {
+        let model_type = self
+            .context
+            .model_type_readable_str_lossy()
+            .map(|value| value.into_owned())
+            .unwrap_or_else(|_| "unknown".to_string());
+        // Built with whisper-rs' `metal` feature, so GPU offload only exists
+        // on Apple targets; everything else runs on the CPU.
+        let backend = if self.use_gpu && cfg!(target_os = "macos") {
+            "Metal"
+        } else {
+            "CPU"
+        };
+
+        json!({
+            "engine": "whisper",
+            "language": self.language,
+            "languages": self.languages.iter().map(|(code, _)| code).collect::<Vec<_>>(),
+            "modelType": model_type,
+            "multilingual": self.context.is_multilingual(),
+            "vocabSize": self.context.n_vocab(),
+            "textContextSize": self.context.n_text_ctx(),
+            "audioContextSize": self.context.n_audio_ctx(),
+            "backend": backend,
+            "whisperVersion": whisper_rs::get_whisper_version()
+        })
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn warmup(&mut self) -> Result<(), String> {
+        // A short silent decode forces ggml to allocate its compute buffers
+        // and (on Metal) compile kernels.
+        let silence = vec![0.0_f32; (SAMPLE_RATE as usize * WARMUP_AUDIO_MS) / 1000];
+        let language = self.language.clone();
+        self.run(&silence, &language)
+    }
+
+    fn decode(&mut self, audio: &[f32]) -> Result<Decoded, String> {
+        let language = self.pick_language(audio)?;
+        self.run(audio, &language)?;
+        Ok(Decoded {
+            text: self.collect_text()?,
+            language,
+            pieces: self.collect_words()?,
+        })
+    }
+
+    fn set_hotwords(&mut self, hotwords: &[String]) {
+        self.prompt = hotwords.join(", ");
+    }
+
+    // Only lets detection pick among the caller's languages, so short
+    // utterances can't be classified as something exotic.
+    fn detect_language(
+        &mut self,
+        audio: &[f32],
+        candidates: &[String],
+    ) -> Result<(String, Value), String> {
+        let mut candidate_ids = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let code = candidate.trim().to_lowercase();
+            let lang_id = whisper_rs::get_lang_id(&code)
+                .ok_or_else(|| format!("unknown language in languageCandidates: {candidate}"))?;
+            candidate_ids.push((code, lang_id));
+        }
+
+        if !self.context.is_multilingual() {
+            if candidate_ids.iter().any(|(code, _)| code == "en") {
+                return Ok(("en".to_string(), json!({ "en": 1.0 })));
+            }
+            return Err("model is English-only; languageCandidates must include \"en\"".into());
+        }
+
+        let probabilities = self.language_probabilities(audio)?;
+        let mut best: Option<(&str, f32)> = None;
+        let mut scores = serde_json::Map::new();
+        for (code, lang_id) in &candidate_ids {
+            let probability = probabilities.get(*lang_id as usize).copied().unwrap_or(0.0);
+            scores.insert(code.clone(), json!(probability));
+            if best.is_none_or(|(_, best_probability)| probability > best_probability) {
+                best = Some((code, probability));
+            }
+        }
+
+        let language = best.map_or_else(|| self.language.clone(), |(code, _)| code.to_string());
+        Ok((language, Value::Object(scores)))
+    }
+
+    fn set_language(&mut self, language: Option<&str>) {
+        self.language_override = language.map(str::to_string);
+    }
+
+    // Starts and ends as whisper reports them; align.rs smooths them.
+    fn timed_words(&self) -> Result<Vec<TimedWord>, String> {
+        let mut words: Vec<TimedWord> = Vec::new();
+        self.each_token(|_, data, piece| {
+            let starts_word = piece.starts_with(' ') || words.is_empty();
+            let piece = piece.trim();
+            if piece.is_empty() {
+                return;
+            }
+
+            // t0/t1 are in centiseconds.
+            let (start_ms, end_ms) = (data.t0 * 10, data.t1 * 10);
+            match words.last_mut() {
+                Some(word) if !starts_word => {
+                    word.text.push_str(piece);
+                    word.end_ms = word.end_ms.max(end_ms);
+                }
+                _ => words.push(TimedWord {
+                    text: piece.to_string(),
+                    start_ms,
+                    end_ms,
+                }),
+            }
+        })?;
+        Ok(words)
+    }
+
+    fn tokens(&self) -> Result<Vec<Value>, String> {
+        let mut tokens = Vec::new();
+        self.each_token(|segment, data, piece| {
+            tokens.push(json!({
+                "id": data.id,
+                "text": piece,
+                "p": data.p,
+                "logp": data.plog,
+                "start": data.t0 as f64 / 100.0,
+                "end": data.t1 as f64 / 100.0,
+                "segment": segment
+            }));
+        })?;
+        Ok(tokens)
+    }
+
+    fn prepare_transcribe(&mut self, reuse: Option<bool>) -> Result<Option<StateReuse>, String> {
+        let reuse = reuse.unwrap_or(self.reuse_state);
+        if !reuse || self.state_failed {
+            self.renew_state()?;
+        }
+        if !reuse {
+            return Ok(None);
+        }
+        let reused = self.state_used;
+        Ok(Some(StateReuse {
+            reused,
+            saved_ms: if reused { self.state_setup_ms } else { 0.0 },
+        }))
+    }
+
+    fn fork(&self, share: usize) -> Result<Box<dyn AsrBackend + Send>, String> {
+        let (state, state_setup_ms) = create_state(&self.context)?;
+        Ok(Box::new(Self {
+            context: Arc::clone(&self.context),
+            state,
+            reuse_state: self.reuse_state,
+            state_setup_ms,
+            state_used: false,
+            state_failed: false,
+            threads: (self.threads / share.max(1) as i32).max(1),
+            language: self.language.clone(),
+            languages: self.languages.clone(),
+            language_override: None,
+            use_gpu: self.use_gpu,
+            prompt: self.prompt.clone(),
+        }))
+    }
+}
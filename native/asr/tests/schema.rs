@@ -0,0 +1,104 @@
+use dingoflow_asr::schema::{protocol_schema, ACTIONS};
+
+// The schema is written by hand, so these read protocol.rs for what the
+// worker accepts and check the schema lists it all.
+const PROTOCOL: &str = include_str!("../src/protocol.rs");
+
+fn camel_case(field: &str) -> String {
+    let mut out = String::new();
+    let mut upper = false;
+    for ch in field.chars() {
+        if ch == '_' {
+            upper = true;
+        } else if upper {
+            out.push(ch.to_ascii_uppercase());
+            upper = false;
+        } else {
+            out.push(ch);
+        }
+    }
+    out
+}
+
+fn body_of<'a>(source: &'a str, opening: &str) -> &'a str {
+    let start = source.find(opening).expect(opening);
+    let body = &source[start..];
+    let mut depth = 0;
+    for (at, ch) in body.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 1 => return &body[..at],
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    panic!("unterminated {opening}");
+}
+
+fn request_fields() -> Vec<String> {
+    body_of(PROTOCOL, "pub struct Request {")
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("pub ")?.split_once(':'))
+        .map(|(field, _)| camel_case(field))
+        .collect()
+}
+
+// The string arms of handle's action match (guards dropped), plus transcribe_batch, which
+// serve_frames runs itself.
+fn handled_actions() -> Vec<String> {
+    let mut actions = vec!["transcribe_batch".to_string()];
+    for line in body_of(PROTOCOL, "fn handle<").lines() {
+        let Some((arms, _)) = line.trim().split_once("=>") else {
+            continue;
+        };
+        if !arms.starts_with('"') {
+            continue;
+        }
+        let arms = arms.split(" if ").next().unwrap_or(arms);
+        for arm in arms.split('|') {
+            actions.push(arm.trim().trim_matches('"').to_string());
+        }
+    }
+    actions
+}
+
+#[test]
+fn every_request_field_is_in_the_schema() {
+    let schema = protocol_schema();
+    let properties = schema["$defs"]["request"]["properties"]
+        .as_object()
+        .expect("request properties");
+    let fields = request_fields();
+    assert!(fields.contains(&"audioBase64".to_string()), "{fields:?}");
+    for field in &fields {
+        assert!(
+            properties.contains_key(field),
+            "{field} is missing from the schema"
+        );
+    }
+    for property in properties.keys() {
+        assert!(
+            fields.contains(property),
+            "{property} is not a Request field"
+        );
+    }
+}
+
+#[test]
+fn every_handled_action_is_in_the_schema() {
+    let actions = handled_actions();
+    assert!(actions.contains(&"stream_push".to_string()), "{actions:?}");
+    for action in &actions {
+        assert!(
+            ACTIONS.contains(&action.as_str()),
+            "{action} is missing from ACTIONS"
+        );
+    }
+    for action in ACTIONS {
+        assert!(
+            actions.iter().any(|handled| handled == action),
+            "{action} is not handled"
+        );
+    }
+    assert!(PROTOCOL.contains("metrics_action == \"transcribe_batch\""));
+}
@@ -0,0 +1,553 @@
+use crate::convert::to_mono_f32;
+use crate::resample::LinearResampler;
+use crate::source::{select_input_device, CaptureDevice, HostSelection};
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{
+    BufferSize, SampleFormat, SampleRate, StreamConfig, StreamInstant, SupportedBufferSize,
+};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+
+// Which input channels make up the mono signal. Interfaces often carry the
+// mic on a single channel, where averaging everything would halve its level.
+#[derive(Clone)]
+pub enum ChannelMap {
+    Mix,
+    // Zero-based channel indices, averaged.
+    Select(Vec<usize>),
+}
+
+impl ChannelMap {
+    // Numbers on the command line are one-based, matching interface labels.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "mix" => return Ok(Self::Mix),
+            "left" => return Ok(Self::Select(vec![0])),
+            "right" => return Ok(Self::Select(vec![1])),
+            _ => {}
+        }
+        let channels = value
+            .split(',')
+            .map(|part| {
+                part.trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|channel| *channel >= 1)
+                    .map(|channel| channel - 1)
+            })
+            .collect::<Option<Vec<_>>>()
+            .filter(|channels| !channels.is_empty())
+            .ok_or("Invalid --channels value (expected left, right, mix or N[,M])")?;
+        Ok(Self::Select(channels))
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Mix => "mix".into(),
+            Self::Select(channels) => channels
+                .iter()
+                .map(|channel| (channel + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+        }
+    }
+
+    // Falls back to a full mix when the device lacks a requested channel
+    // (e.g. after failing over to a mono default), rather than going silent.
+    fn resolve(&self, device_name: &str, channels: usize, notice: &impl Fn(Notice)) -> Vec<usize> {
+        match self {
+            Self::Mix => Vec::new(),
+            Self::Select(selected) if selected.iter().all(|channel| *channel < channels) => {
+                selected.clone()
+            }
+            Self::Select(_) => {
+                notice(Notice::ChannelFallback {
+                    device: device_name.to_string(),
+                    channels,
+                    requested: self.name(),
+                });
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum BufferRequest {
+    Auto,
+    Minimum,
+    Frames(u32),
+    LatencyMs(u32),
+}
+
+impl BufferRequest {
+    fn frames(self, sample_rate: u32) -> u32 {
+        match self {
+            Self::Auto => (sample_rate / 200).clamp(64, 1024),
+            // Clamped up to the device's minimum when it reports a range.
+            Self::Minimum => (sample_rate / 500).max(32),
+            Self::Frames(frames) => frames,
+            Self::LatencyMs(ms) => ((sample_rate as u64 * ms as u64) / 1000).max(1) as u32,
+        }
+    }
+}
+
+// What opening and running a stream has to report. The audio loop turns
+// these into its status events; simpler tools can ignore all but errors.
+pub enum Notice {
+    // The device lacks a requested channel, so everything is mixed.
+    ChannelFallback {
+        device: String,
+        channels: usize,
+        requested: String,
+    },
+    // A fixed buffer size was refused; the driver default is tried next.
+    BufferRejected {
+        frames: u32,
+        error: String,
+    },
+    // A whole config was refused; `next` is tried instead.
+    ConfigRejected {
+        rejected: String,
+        next: String,
+        error: String,
+    },
+    // cpal can't tell what the driver granted, so the first callback reports
+    // the block size it really delivers.
+    BufferGranted {
+        device: String,
+        requested: String,
+        granted: usize,
+        latency_ms: f64,
+    },
+    // The stream opened with something other than the device's default.
+    ConfigFallback {
+        device: String,
+        sample_rate: u32,
+        channels: u16,
+        sample_format: SampleFormat,
+        description: String,
+    },
+    Stream(cpal::StreamError),
+}
+
+pub struct CaptureStream {
+    stream: cpal::Stream,
+    pub device_name: String,
+    pub input_sample_rate: u32,
+    pub channels: usize,
+}
+
+impl CaptureStream {
+    // Builds a paused capture stream that hands mono f32 blocks at the
+    // device's native rate to `sink`. Callers size their resamplers from
+    // `input_sample_rate` before calling `play`.
+    pub fn open<S, N>(
+        capture: &CaptureDevice,
+        buffer: BufferRequest,
+        channel_map: &ChannelMap,
+        notice: N,
+        sink: S,
+    ) -> Result<Self, String>
+    where
+        S: FnMut(&[f32], StreamInstant) + Send + Clone + 'static,
+        N: Fn(Notice) + Send + Clone + 'static,
+    {
+        let device = &capture.device;
+        let device_name = device.name().unwrap_or_else(|_| "<unknown>".into());
+        let default_cfg = if capture.loopback {
+            device.default_output_config()
+        } else {
+            device.default_input_config()
+        }
+        .map_err(|e| format!("failed to query default input config: {e}"))?;
+
+        let error_notice = notice.clone();
+        let error_callback = move |error| error_notice(Notice::Stream(error));
+
+        let mut last_error = String::new();
+        let mut previous: Option<StreamCandidate> = None;
+        for candidate in stream_candidates(capture, &default_cfg, buffer) {
+            match previous {
+                Some(StreamCandidate {
+                    buffer_size: BufferSize::Fixed(frames),
+                    fallback: false,
+                    ..
+                }) if !candidate.fallback => notice(Notice::BufferRejected {
+                    frames,
+                    error: last_error.clone(),
+                }),
+                Some(rejected) => notice(Notice::ConfigRejected {
+                    rejected: rejected.describe(),
+                    next: candidate.describe(),
+                    error: last_error.clone(),
+                }),
+                None => {}
+            }
+            previous = Some(candidate);
+
+            let input_sample_rate = candidate.sample_rate;
+            let channels = candidate.channels as usize;
+            let selected = channel_map.resolve(&device_name, channels, &notice);
+            let stream_config = StreamConfig {
+                channels: candidate.channels,
+                sample_rate: SampleRate(input_sample_rate),
+                buffer_size: candidate.buffer_size,
+            };
+
+            let requested = match candidate.buffer_size {
+                BufferSize::Fixed(frames) => frames.to_string(),
+                BufferSize::Default => "default".to_string(),
+            };
+            let mut reported = false;
+            let reporting_name = device_name.clone();
+            let reporting_notice = notice.clone();
+            let mut sink = sink.clone();
+            let reporting_sink = move |mono: &[f32], capture: StreamInstant| {
+                if !reported {
+                    reported = true;
+                    reporting_notice(Notice::BufferGranted {
+                        device: reporting_name.clone(),
+                        requested: requested.clone(),
+                        granted: mono.len(),
+                        latency_ms: mono.len() as f64 * 1000.0 / input_sample_rate as f64,
+                    });
+                }
+                sink(mono, capture);
+            };
+
+            match build_input_stream(
+                device,
+                &stream_config,
+                candidate.sample_format,
+                &selected,
+                reporting_sink,
+                error_callback.clone(),
+            ) {
+                Ok(stream) => {
+                    if candidate.fallback {
+                        notice(Notice::ConfigFallback {
+                            device: device_name.clone(),
+                            sample_rate: candidate.sample_rate,
+                            channels: candidate.channels,
+                            sample_format: candidate.sample_format,
+                            description: candidate.describe(),
+                        });
+                    }
+                    return Ok(Self {
+                        stream,
+                        device_name,
+                        input_sample_rate,
+                        channels,
+                    });
+                }
+                Err(error) => last_error = error,
+            }
+        }
+
+        Err(format!(
+            "no usable stream config for {device_name:?}: {last_error}"
+        ))
+    }
+
+    pub fn play(self) -> Result<Self, String> {
+        self.stream
+            .play()
+            .map_err(|e| format!("failed to start input stream: {e}"))?;
+        Ok(self)
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        self.stream
+            .pause()
+            .map_err(|e| format!("failed to stop input stream: {e}"))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct StreamCandidate {
+    channels: u16,
+    sample_rate: u32,
+    sample_format: SampleFormat,
+    buffer_size: BufferSize,
+    // Not the device's default config.
+    fallback: bool,
+}
+
+impl StreamCandidate {
+    fn describe(&self) -> String {
+        let buffer = match self.buffer_size {
+            BufferSize::Fixed(frames) => frames.to_string(),
+            BufferSize::Default => "default".to_string(),
+        };
+        format!(
+            "sample_rate={} channels={} format={:?} buffer_frames={buffer}",
+            self.sample_rate, self.channels, self.sample_format
+        )
+    }
+}
+
+// Everything build_input_stream knows how to convert.
+const SUPPORTED_SAMPLE_FORMATS: [SampleFormat; 7] = [
+    SampleFormat::F32,
+    SampleFormat::I8,
+    SampleFormat::I16,
+    SampleFormat::I32,
+    SampleFormat::U8,
+    SampleFormat::U16,
+    SampleFormat::U32,
+];
+
+// Rates tried, in order, when a supported range doesn't cover the default.
+const FALLBACK_SAMPLE_RATES: [u32; 4] = [48_000, 44_100, 16_000, 32_000];
+
+// The default config goes first (with our buffer request, then the driver's
+// own buffer, since some drivers reject fixed sizes outright). After that,
+// every other supported config the device advertises, because several USB
+// mics only open at specific rates or formats. The pipeline resamples from
+// whatever rate ends up open.
+fn stream_candidates(
+    capture: &CaptureDevice,
+    default_cfg: &cpal::SupportedStreamConfig,
+    buffer: BufferRequest,
+) -> Vec<StreamCandidate> {
+    let default_rate = default_cfg.sample_rate().0;
+    let mut candidates = Vec::new();
+    let default_candidate = StreamCandidate {
+        channels: default_cfg.channels(),
+        sample_rate: default_rate,
+        sample_format: default_cfg.sample_format(),
+        buffer_size: BufferSize::Default,
+        fallback: false,
+    };
+    // Loopback streams follow the render engine's period, not ours.
+    if !capture.loopback {
+        let mut requested_frames = buffer.frames(default_rate);
+        if let SupportedBufferSize::Range { min, max } = default_cfg.buffer_size() {
+            requested_frames = requested_frames.clamp(*min, *max);
+        }
+        candidates.push(StreamCandidate {
+            buffer_size: BufferSize::Fixed(requested_frames),
+            ..default_candidate
+        });
+    }
+    candidates.push(default_candidate);
+
+    let supported = if capture.loopback {
+        capture
+            .device
+            .supported_output_configs()
+            .map(|configs| configs.collect::<Vec<_>>())
+    } else {
+        capture
+            .device
+            .supported_input_configs()
+            .map(|configs| configs.collect::<Vec<_>>())
+    }
+    .unwrap_or_default();
+
+    for range in supported {
+        if !SUPPORTED_SAMPLE_FORMATS.contains(&range.sample_format()) {
+            continue;
+        }
+        let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+        let sample_rate = std::iter::once(default_rate)
+            .chain(FALLBACK_SAMPLE_RATES)
+            .find(|rate| (min..=max).contains(rate))
+            .unwrap_or(max);
+        let candidate = StreamCandidate {
+            channels: range.channels(),
+            sample_rate,
+            sample_format: range.sample_format(),
+            buffer_size: BufferSize::Default,
+            fallback: true,
+        };
+        let duplicate = candidates.iter().any(|existing| {
+            existing.channels == candidate.channels
+                && existing.sample_rate == candidate.sample_rate
+                && existing.sample_format == candidate.sample_format
+                && existing.buffer_size == candidate.buffer_size
+        });
+        if !duplicate {
+            candidates.push(candidate);
+        }
+    }
+
+    candidates
+}
+
+fn build_input_stream<S, E>(
+    device: &cpal::Device,
+    stream_config: &StreamConfig,
+    sample_format: SampleFormat,
+    selected: &[usize],
+    sink: S,
+    error_callback: E,
+) -> Result<cpal::Stream, String>
+where
+    S: FnMut(&[f32], StreamInstant) + Send + Clone + 'static,
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    // Integer formats are scaled to [-1, 1). 24-bit-in-32 devices deliver
+    // their samples left-justified, so they come through as I32.
+    match sample_format {
+        SampleFormat::F32 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: f32| v,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::I8 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: i8| v as f32 / 128.0,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::I16 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: i16| v as f32 / i16::MAX as f32,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::I32 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: i32| (v as f64 / 2_147_483_648.0) as f32,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::U8 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: u8| (v as f32 - 128.0) / 128.0,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::U16 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: u16| (v as f32 / u16::MAX as f32) * 2.0 - 1.0,
+            sink,
+            error_callback,
+        ),
+        SampleFormat::U32 => typed_input_stream(
+            device,
+            stream_config,
+            selected,
+            |v: u32| ((v as f64 - 2_147_483_648.0) / 2_147_483_648.0) as f32,
+            sink,
+            error_callback,
+        ),
+        unsupported => Err(format!("unsupported sample format: {unsupported:?}")),
+    }
+}
+
+fn typed_input_stream<T, F, S, E>(
+    device: &cpal::Device,
+    stream_config: &StreamConfig,
+    selected: &[usize],
+    to_f32: F,
+    mut sink: S,
+    error_callback: E,
+) -> Result<cpal::Stream, String>
+where
+    T: cpal::SizedSample,
+    F: Fn(T) -> f32 + Send + 'static,
+    S: FnMut(&[f32], StreamInstant) + Send + 'static,
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    let channels = stream_config.channels as usize;
+    let selected = selected.to_vec();
+    let mut mono = Vec::<f32>::new();
+    device
+        .build_input_stream(
+            stream_config,
+            move |data: &[T], info: &cpal::InputCallbackInfo| {
+                mono.clear();
+                to_mono_f32(data, channels, &selected, &to_f32, &mut mono);
+                sink(&mono, info.timestamp().capture);
+            },
+            error_callback,
+            None,
+        )
+        .map_err(|e| format!("failed to build input stream: {e}"))
+}
+
+// The whole capture path for tools that run ASR in-process (the parakeet
+// worker's --mic mode, dingoflow-dictate): one input device mixed to mono
+// and resampled to the rate the engine wants, with none of the VAD, AGC or
+// framing the audio loop adds for the host.
+pub struct MonoCapture {
+    // Capture stops when this is dropped.
+    stream: CaptureStream,
+    resampler: LinearResampler,
+    blocks: Receiver<Vec<f32>>,
+}
+
+impl MonoCapture {
+    // `device` takes the same selectors as the audio loop's --device (a
+    // --list-devices id, an index or a name substring). Stream errors go to
+    // stderr.
+    pub fn open(
+        host: &HostSelection,
+        device: Option<&str>,
+        target_sample_rate: u32,
+    ) -> Result<Self, String> {
+        let capture = select_input_device(&host.open()?, device.or(host.route_device))?;
+        let (tx, blocks) = mpsc::channel();
+        let stream = CaptureStream::open(
+            &capture,
+            BufferRequest::Auto,
+            &ChannelMap::Mix,
+            |notice| {
+                if let Notice::Stream(error) = notice {
+                    eprintln!("stream-error: {error}");
+                }
+            },
+            move |mono, _| {
+                let _ = tx.send(mono.to_vec());
+            },
+        )?;
+        let resampler = LinearResampler::new(stream.input_sample_rate, target_sample_rate);
+        Ok(Self {
+            stream: stream.play()?,
+            resampler,
+            blocks,
+        })
+    }
+
+    pub fn device_name(&self) -> &str {
+        &self.stream.device_name
+    }
+
+    pub fn input_sample_rate(&self) -> u32 {
+        self.stream.input_sample_rate
+    }
+
+    // Waits up to `timeout` for audio, then also takes whatever else has
+    // piled up, so a slow consumer catches up in one go.
+    pub fn recv_timeout(&mut self, timeout: Duration, out: &mut Vec<f32>) -> Result<(), String> {
+        let block = match self.blocks.recv_timeout(timeout) {
+            Ok(block) => block,
+            Err(RecvTimeoutError::Timeout) => return Ok(()),
+            Err(RecvTimeoutError::Disconnected) => return Err("input stream closed".into()),
+        };
+        self.resampler.process(&block, out);
+        loop {
+            match self.blocks.try_recv() {
+                Ok(block) => self.resampler.process(&block, out),
+                Err(TryRecvError::Empty) => return Ok(()),
+                Err(TryRecvError::Disconnected) => return Err("input stream closed".into()),
+            }
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// An empty `selected` averages every channel.
+pub fn to_mono_f32<T, F>(
+    input: &[T],
+    channels: usize,
+    selected: &[usize],
+    to_f32: F,
+    out: &mut Vec<f32>,
+) where
+    F: Fn(T) -> f32,
+    T: Copy,
+{
+    if channels <= 1 {
+        out.extend(input.iter().copied().map(to_f32));
+        return;
+    }
+
+    for frame in input.chunks(channels) {
+        if frame.len() < channels {
+            continue;
+        }
+
+        if selected.is_empty() {
+            let sum = frame.iter().copied().map(&to_f32).sum::<f32>();
+            out.push(sum / channels as f32);
+        } else {
+            let sum = selected
+                .iter()
+                .map(|&channel| to_f32(frame[channel]))
+                .sum::<f32>();
+            out.push(sum / selected.len() as f32);
+        }
+    }
+}
+
+pub fn f32_to_i16(input: &[f32], out: &mut Vec<i16>) {
+    out.reserve(input.len());
+    for sample in input {
+        let clamped = sample.clamp(-1.0, 1.0);
+        out.push((clamped * i16::MAX as f32) as i16);
+    }
+}
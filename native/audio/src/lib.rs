@@ -0,0 +1,8 @@
+// Audio capture shared by the native tools: device selection, opening a
+// cpal input stream whatever format the device insists on, downmixing and
+// resampling. dingoflow-audio-loop adds its processing chain on top;
+// in-process dictation only needs MonoCapture.
+pub mod capture;
+pub mod convert;
+pub mod resample;
+pub mod source;
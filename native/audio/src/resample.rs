@@ -0,0 +1,55 @@
+// Linear interpolation between the device rate and the output rate; cheap
+// enough for the capture callback and good enough for speech.
+pub struct LinearResampler {
+    ratio: f64,
+    position: f64,
+    carry: Vec<f32>,
+    passthrough: bool,
+}
+
+impl LinearResampler {
+    pub fn new(input_rate: u32, target_rate: u32) -> Self {
+        let passthrough = input_rate == target_rate;
+        Self {
+            ratio: input_rate as f64 / target_rate as f64,
+            position: 0.0,
+            carry: Vec::with_capacity(8192),
+            passthrough,
+        }
+    }
+
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        if input.is_empty() {
+            return;
+        }
+
+        if self.passthrough {
+            out.extend_from_slice(input);
+            return;
+        }
+
+        self.carry.extend_from_slice(input);
+        let carry_len = self.carry.len() as f64;
+
+        while self.position + 1.0 < carry_len {
+            let index = self.position.floor() as usize;
+            let frac = (self.position - index as f64) as f32;
+            let a = self.carry[index];
+            let b = self.carry[index + 1];
+            out.push(a + (b - a) * frac);
+            self.position += self.ratio;
+        }
+
+        let drop_count = self.position.floor() as usize;
+        if drop_count > 0 && drop_count <= self.carry.len() {
+            let remaining = self.carry.len() - drop_count;
+            self.carry.copy_within(drop_count.., 0);
+            self.carry.truncate(remaining);
+            self.position -= drop_count as f64;
+        }
+    }
+
+    pub fn carry_len(&self) -> usize {
+        self.carry.len()
+    }
+}
@@ -0,0 +1,221 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+    Mic,
+    System,
+    Both,
+}
+
+impl CaptureSource {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "mic" => Ok(Self::Mic),
+            "system" => Ok(Self::System),
+            "both" => Ok(Self::Both),
+            _ => Err("Invalid --source value".into()),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mic => "mic",
+            Self::System => "system",
+            Self::Both => "both",
+        }
+    }
+}
+
+// `--audio-host` picks among the hosts cpal was built with (JACK and ASIO only
+// exist when cpal is built with those features). "pipewire" and "pulse" are
+// not cpal hosts: they select ALSA and open the sound server's ALSA plugin
+// device, so capture follows the user's routing instead of the raw hardware.
+pub struct HostSelection {
+    id: Option<cpal::HostId>,
+    pub route_device: Option<&'static str>,
+}
+
+impl HostSelection {
+    pub fn default_host() -> Self {
+        Self {
+            id: None,
+            route_device: None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let needle = value.trim().to_lowercase();
+        if needle == "default" {
+            return Ok(Self::default_host());
+        }
+
+        let route_device = match needle.as_str() {
+            "pipewire" => Some("pipewire"),
+            "pulse" | "pulseaudio" => Some("pulse"),
+            _ => None,
+        };
+        let host_name = if route_device.is_some() {
+            "alsa"
+        } else {
+            needle.as_str()
+        };
+        let id = cpal::available_hosts()
+            .into_iter()
+            .find(|id| id.name().to_lowercase() == host_name);
+        match id {
+            Some(id) => Ok(Self {
+                id: Some(id),
+                route_device,
+            }),
+            None => {
+                let available = cpal::available_hosts()
+                    .iter()
+                    .map(|id| id.name().to_lowercase())
+                    .collect::<Vec<_>>();
+                Err(format!(
+                    "audio host \"{value}\" is not available in this build; available: [{}]",
+                    available.join(", ")
+                ))
+            }
+        }
+    }
+
+    pub fn open(&self) -> Result<cpal::Host, String> {
+        match self.id {
+            Some(id) => cpal::host_from_id(id)
+                .map_err(|e| format!("failed to open audio host {}: {e}", id.name())),
+            None => Ok(cpal::default_host()),
+        }
+    }
+}
+
+pub struct CaptureDevice {
+    pub device: cpal::Device,
+    // WASAPI loopback: an output device opened as an input stream.
+    pub loopback: bool,
+}
+
+impl CaptureDevice {
+    fn input(device: cpal::Device) -> Self {
+        Self {
+            device,
+            loopback: false,
+        }
+    }
+}
+
+// cpal exposes no native device UID, so the id is the host plus the device
+// name, with "#2", "#3", ... telling identically named devices apart in
+// enumeration order. Unlike the list index it survives other devices coming
+// and going, and on ALSA the name already is the stable PCM identifier.
+pub fn device_ids(host_id: cpal::HostId, names: &[String]) -> Vec<String> {
+    let host = host_id.name().to_lowercase();
+    names
+        .iter()
+        .enumerate()
+        .map(|(index, name)| {
+            let occurrence = names[..index].iter().filter(|other| *other == name).count();
+            if occurrence == 0 {
+                format!("{host}:{name}")
+            } else {
+                format!("{host}:{name}#{}", occurrence + 1)
+            }
+        })
+        .collect()
+}
+
+// A selector that equals a device id from `--list-devices` picks that device;
+// one that parses as a number picks by position in the host's input device
+// list; anything else is a case-insensitive name substring.
+pub fn select_input_device(
+    host: &cpal::Host,
+    selector: Option<&str>,
+) -> Result<CaptureDevice, String> {
+    let Some(selector) = selector else {
+        return host
+            .default_input_device()
+            .map(CaptureDevice::input)
+            .ok_or_else(|| "default input device not available".to_string());
+    };
+
+    let mut devices = named_input_devices(host)?;
+    let names = devices
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    let ids = device_ids(host.id(), &names);
+    let needle = selector.trim().to_lowercase();
+    let selected = match ids.iter().position(|id| id == selector.trim()) {
+        Some(index) => Some(index),
+        None => match needle.parse::<usize>() {
+            Ok(index) => (index < devices.len()).then_some(index),
+            Err(_) => names
+                .iter()
+                .position(|name| name.to_lowercase().contains(&needle)),
+        },
+    };
+    if let Some(index) = selected {
+        return Ok(CaptureDevice::input(devices.swap_remove(index).1));
+    }
+
+    let available = ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| format!("{index}: {id}"))
+        .collect::<Vec<_>>();
+    Err(format!(
+        "no input device matches --device \"{selector}\"; available: [{}]",
+        available.join(", ")
+    ))
+}
+
+fn named_input_devices(host: &cpal::Host) -> Result<Vec<(String, cpal::Device)>, String> {
+    Ok(host
+        .input_devices()
+        .map_err(|e| format!("failed to enumerate input devices: {e}"))?
+        .map(|device| (device.name().unwrap_or_else(|_| "<unknown>".into()), device))
+        .collect())
+}
+
+// Windows can loop back the default output directly. Elsewhere cpal only sees
+// input devices, so system audio has to come from a monitor/loopback device
+// that the OS or a driver exposes; an explicit --device always wins.
+pub fn select_system_device(
+    host: &cpal::Host,
+    selector: Option<&str>,
+) -> Result<CaptureDevice, String> {
+    if selector.is_some() {
+        return select_input_device(host, selector);
+    }
+
+    if cfg!(target_os = "windows") {
+        return host
+            .default_output_device()
+            .map(|device| CaptureDevice {
+                device,
+                loopback: true,
+            })
+            .ok_or_else(|| "default output device not available for loopback".to_string());
+    }
+
+    let (keywords, hint): (&[&str], &str) = if cfg!(target_os = "macos") {
+        (
+            &["blackhole", "loopback", "soundflower"],
+            "install a loopback driver such as BlackHole and route output through it",
+        )
+    } else {
+        (
+            &["monitor"],
+            "expose a PulseAudio/PipeWire monitor source as an input device",
+        )
+    };
+
+    named_input_devices(host)?
+        .into_iter()
+        .find(|(name, _)| {
+            let name = name.to_lowercase();
+            keywords.iter().any(|keyword| name.contains(keyword))
+        })
+        .map(|(_, device)| CaptureDevice::input(device))
+        .ok_or_else(|| format!("no system audio capture device found; {hint}, or pass --device"))
+}
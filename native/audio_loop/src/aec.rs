@@ -0,0 +1,65 @@
+// Time-domain NLMS echo canceller. The system-audio loopback is the far-end
+// reference; the filter learns the speaker-to-mic echo path and subtracts its
+// estimate from the mic. A Geigel detector freezes adaptation while the local
+// talker is louder than the echo could be, so double talk doesn't make the
+// filter unlearn the path.
+pub struct EchoCanceller {
+    weights: Vec<f32>,
+    // Each reference sample is stored twice (at `pos` and `pos + taps`) so
+    // the most recent `taps` samples are always one contiguous slice.
+    history: Vec<f32>,
+    pos: usize,
+    energy: f32,
+    far_peak: f32,
+    peak_decay: f32,
+    hold_remaining: usize,
+    hold_samples: usize,
+}
+
+const STEP_SIZE: f32 = 0.2;
+const GEIGEL_THRESHOLD: f32 = 0.5;
+const REGULARIZATION: f32 = 1e-3;
+
+impl EchoCanceller {
+    pub fn new(sample_rate: u32, tail_ms: usize) -> Self {
+        let taps = ((sample_rate as usize * tail_ms) / 1000).max(1);
+        Self {
+            weights: vec![0.0; taps],
+            history: vec![0.0; taps * 2],
+            pos: 0,
+            energy: 0.0,
+            far_peak: 0.0,
+            // The peak decays over roughly one tail length.
+            peak_decay: 1.0 - 1.0 / taps as f32,
+            hold_remaining: 0,
+            hold_samples: (sample_rate as usize * 30) / 1000,
+        }
+    }
+
+    pub fn process(&mut self, mic: f32, reference: f32) -> f32 {
+        let taps = self.weights.len();
+        let oldest = self.history[self.pos];
+        self.energy = (self.energy + reference * reference - oldest * oldest).max(0.0);
+        self.history[self.pos] = reference;
+        self.history[self.pos + taps] = reference;
+        self.pos = (self.pos + 1) % taps;
+
+        let window = &self.history[self.pos..self.pos + taps];
+        let estimate: f32 = self.weights.iter().zip(window).map(|(w, x)| w * x).sum();
+        let error = mic - estimate;
+
+        self.far_peak = reference.abs().max(self.far_peak * self.peak_decay);
+        if mic.abs() > GEIGEL_THRESHOLD * self.far_peak {
+            self.hold_remaining = self.hold_samples;
+        } else if self.hold_remaining > 0 {
+            self.hold_remaining -= 1;
+        } else if self.energy > 0.0 {
+            let step = STEP_SIZE * error / (self.energy + REGULARIZATION);
+            for (weight, x) in self.weights.iter_mut().zip(window) {
+                *weight += step * x;
+            }
+        }
+
+        error
+    }
+}
@@ -0,0 +1,62 @@
+// Feed-forward AGC: a smoothed power envelope sets the gain needed to bring
+// speech to the target level. Gain drops at the attack rate (so a sudden loud
+// word is caught quickly) and rises at the release rate, and it is never
+// raised while the input is below the noise floor so silence isn't pumped up
+// into hiss.
+pub struct AutoGain {
+    target: f32,
+    attack_coef: f32,
+    release_coef: f32,
+    envelope_coef: f32,
+    envelope: f32,
+    gain: f32,
+}
+
+const MAX_GAIN_DB: f32 = 30.0;
+const MIN_GAIN_DB: f32 = -20.0;
+const NOISE_FLOOR_DBFS: f32 = -60.0;
+const ENVELOPE_MS: f32 = 50.0;
+
+fn time_constant(sample_rate: u32, ms: f32) -> f32 {
+    let samples = (sample_rate as f32 * ms / 1000.0).max(1.0);
+    1.0 - (-1.0 / samples).exp()
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+impl AutoGain {
+    pub fn new(sample_rate: u32, target_dbfs: f32, attack_ms: f32, release_ms: f32) -> Self {
+        Self {
+            target: db_to_linear(target_dbfs),
+            attack_coef: time_constant(sample_rate, attack_ms),
+            release_coef: time_constant(sample_rate, release_ms),
+            envelope_coef: time_constant(sample_rate, ENVELOPE_MS),
+            envelope: 0.0,
+            gain: 1.0,
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        let noise_floor = db_to_linear(NOISE_FLOOR_DBFS);
+        let (min_gain, max_gain) = (db_to_linear(MIN_GAIN_DB), db_to_linear(MAX_GAIN_DB));
+
+        for sample in samples.iter_mut() {
+            self.envelope += self.envelope_coef * (*sample * *sample - self.envelope);
+            let level = self.envelope.sqrt();
+
+            if level > noise_floor {
+                let desired = (self.target / level).clamp(min_gain, max_gain);
+                let coef = if desired < self.gain {
+                    self.attack_coef
+                } else {
+                    self.release_coef
+                };
+                self.gain += coef * (desired - self.gain);
+            }
+
+            *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use serde_json::{json, Value};
+
+// Level survey behind `--calibrate` and `--self-test`: the raw input is cut
+// into 20 ms frames and the quiet and loud ends of the level distribution
+// stand in for the room's noise floor and the user's speaking level.
+const FRAME_MS: usize = 20;
+const NOISE_PERCENTILE: f32 = 0.10;
+const SPEECH_PERCENTILE: f32 = 0.95;
+// Same level the AGC aims for by default.
+const TARGET_SPEECH_DBFS: f32 = -20.0;
+// Keep the loudest peak we heard below full scale after the gain.
+const PEAK_HEADROOM_DBFS: f32 = -1.0;
+// Less separation than this and the loud frames are just louder noise.
+const MIN_SPEECH_SNR_DB: f32 = 10.0;
+const FLOOR_DBFS: f32 = -120.0;
+// Below one 16-bit LSB: what a mic blocked by the OS delivers.
+const SILENCE_LEVEL: f32 = 1.0 / 32_768.0;
+const CLIP_LEVEL: f32 = 0.999;
+const MAX_CLIPPED_RATIO: f64 = 0.01;
+// A working mic in a quiet room still shows some self-noise above this.
+const MIN_NOISE_DBFS: f32 = -90.0;
+
+pub struct Calibrator {
+    frame_len: usize,
+    frame_energy: f64,
+    frame_samples: usize,
+    frame_dbfs: Vec<f32>,
+    peak: f32,
+    samples: u64,
+    clipped: u64,
+}
+
+impl Calibrator {
+    pub fn new() -> Self {
+        Self {
+            frame_len: 48_000 * FRAME_MS / 1000,
+            frame_energy: 0.0,
+            frame_samples: 0,
+            frame_dbfs: Vec::new(),
+            peak: 0.0,
+            samples: 0,
+            clipped: 0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.frame_len = (sample_rate as usize * FRAME_MS / 1000).max(1);
+        self.frame_energy = 0.0;
+        self.frame_samples = 0;
+    }
+
+    pub fn process(&mut self, block: &[f32]) {
+        self.samples += block.len() as u64;
+        for &sample in block {
+            if sample.abs() >= CLIP_LEVEL {
+                self.clipped += 1;
+            }
+            self.peak = self.peak.max(sample.abs());
+            self.frame_energy += (sample as f64) * (sample as f64);
+            self.frame_samples += 1;
+            if self.frame_samples == self.frame_len {
+                let rms = (self.frame_energy / self.frame_samples as f64).sqrt() as f32;
+                self.frame_dbfs.push(to_dbfs(rms));
+                self.frame_energy = 0.0;
+                self.frame_samples = 0;
+            }
+        }
+    }
+
+    fn sorted_levels(&self) -> Vec<f32> {
+        let mut levels = self.frame_dbfs.clone();
+        levels.sort_by(f32::total_cmp);
+        levels
+    }
+
+    // Gain is suggested against the raw input, i.e. as a replacement for
+    // --gain rather than a multiplier on top of it.
+    pub fn report(&self, duration_ms: u64, max_gain: f32) -> Value {
+        let levels = self.sorted_levels();
+        let noise_floor = percentile(&levels, NOISE_PERCENTILE);
+        let speech = percentile(&levels, SPEECH_PERCENTILE);
+        let peak = to_dbfs(self.peak);
+        let snr = speech - noise_floor;
+        let speech_detected = snr >= MIN_SPEECH_SNR_DB;
+
+        let suggested_gain = speech_detected.then(|| {
+            let gain = db_to_linear(TARGET_SPEECH_DBFS - speech)
+                .min(db_to_linear(PEAK_HEADROOM_DBFS - peak))
+                .clamp(0.0, max_gain);
+            (gain * 100.0).round() / 100.0
+        });
+
+        json!({
+            "event": "calibration",
+            "durationMs": duration_ms,
+            "frames": levels.len(),
+            "noiseFloorDbfs": round1(noise_floor),
+            "speechDbfs": round1(speech),
+            "peakDbfs": round1(peak),
+            "snrDb": round1(snr),
+            "speechDetected": speech_detected,
+            "targetSpeechDbfs": TARGET_SPEECH_DBFS,
+            "suggestedGain": suggested_gain,
+        })
+    }
+
+    // Pass/fail for an installer: did audio arrive at all, is it more than
+    // the digital zeros a permission-blocked mic produces, and is it neither
+    // pinned at the floor nor clipping.
+    pub fn verdict(&self) -> (&'static str, &'static str) {
+        let levels = self.sorted_levels();
+        let clipped_ratio = self.clipped as f64 / self.samples.max(1) as f64;
+        if self.samples == 0 {
+            (
+                "no-audio",
+                "the device delivered no audio; check microphone permission",
+            )
+        } else if self.peak < SILENCE_LEVEL {
+            (
+                "silent",
+                "the microphone only delivers silence; microphone access may be blocked or the input muted",
+            )
+        } else if percentile(&levels, SPEECH_PERCENTILE) < MIN_NOISE_DBFS {
+            (
+                "too-quiet",
+                "the input level is barely above silence; check the mic and its input level",
+            )
+        } else if clipped_ratio >= MAX_CLIPPED_RATIO {
+            (
+                "clipping",
+                "the input is clipping; lower the microphone input level",
+            )
+        } else {
+            ("ok", "microphone is delivering audio")
+        }
+    }
+
+    pub fn summary(&self) -> Value {
+        let levels = self.sorted_levels();
+        json!({
+            "framesCaptured": self.samples,
+            "noiseFloorDbfs": round1(percentile(&levels, NOISE_PERCENTILE)),
+            "speechDbfs": round1(percentile(&levels, SPEECH_PERCENTILE)),
+            "peakDbfs": round1(to_dbfs(self.peak)),
+            "clippedPct": round1((self.clipped as f64 * 100.0 / self.samples.max(1) as f64) as f32),
+        })
+    }
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return FLOOR_DBFS;
+    }
+    sorted[((sorted.len() - 1) as f32 * p).round() as usize]
+}
+
+fn to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return FLOOR_DBFS;
+    }
+    (20.0 * amplitude.log10()).max(FLOOR_DBFS)
+}
+
+fn db_to_linear(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+fn round1(value: f32) -> f32 {
+    (value * 10.0).round() / 10.0
+}
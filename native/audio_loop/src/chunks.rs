@@ -0,0 +1,136 @@
+use std::time::Duration;
+
+pub struct AudioChunk {
+    pub sequence: u64,
+    pub capture_ms: f64,
+    pub wall_ms: f64,
+    pub samples: Vec<f32>,
+}
+
+// Re-cuts whatever block sizes the driver delivers into fixed `--chunk-ms`
+// chunks. A partial chunk is emitted early when the audio it would continue
+// with isn't contiguous (the VAD gate closed, capture paused) or when
+// nothing more arrives for a while, so the end of an utterance isn't held.
+pub struct ChunkCoalescer {
+    chunk_samples: usize,
+    sample_rate: u32,
+    // The last block the driver delivered; see flush_after.
+    block_ms: f64,
+    pending: Option<AudioChunk>,
+    sequence: u64,
+}
+
+impl ChunkCoalescer {
+    pub fn new(sample_rate: u32, chunk_ms: usize) -> Self {
+        Self {
+            chunk_samples: (sample_rate as usize * chunk_ms / 1000).max(1),
+            sample_rate,
+            block_ms: 0.0,
+            pending: None,
+            sequence: 0,
+        }
+    }
+
+    fn chunk_ms(&self) -> f64 {
+        self.chunk_samples as f64 * 1000.0 / self.sample_rate as f64
+    }
+
+    // How long to wait for more audio before a partial chunk goes out: two
+    // chunks, or two of the driver's blocks when those are longer, so a
+    // driver handing over 250 ms at a time doesn't get every block's tail
+    // flushed as a short chunk while the next block is still on its way.
+    pub fn flush_after(&self) -> Duration {
+        Duration::from_secs_f64(self.chunk_ms().max(self.block_ms) * 2.0 / 1000.0)
+    }
+
+    pub fn push(&mut self, chunk: AudioChunk, out: &mut Vec<AudioChunk>) {
+        self.block_ms = chunk.samples.len() as f64 * 1000.0 / self.sample_rate as f64;
+        if let Some(pending) = &self.pending {
+            let pending_ms = pending.samples.len() as f64 * 1000.0 / self.sample_rate as f64;
+            if (chunk.capture_ms - (pending.capture_ms + pending_ms)).abs() > self.chunk_ms() / 2.0
+            {
+                self.flush(out);
+            }
+        }
+
+        let mut offset = 0;
+        while offset < chunk.samples.len() {
+            let elapsed_ms = offset as f64 * 1000.0 / self.sample_rate as f64;
+            let pending = self.pending.get_or_insert_with(|| AudioChunk {
+                sequence: 0,
+                capture_ms: chunk.capture_ms + elapsed_ms,
+                wall_ms: chunk.wall_ms + elapsed_ms,
+                samples: Vec::with_capacity(self.chunk_samples),
+            });
+            let take =
+                (self.chunk_samples - pending.samples.len()).min(chunk.samples.len() - offset);
+            pending
+                .samples
+                .extend_from_slice(&chunk.samples[offset..offset + take]);
+            offset += take;
+            if pending.samples.len() == self.chunk_samples {
+                self.flush(out);
+            }
+        }
+    }
+
+    pub fn flush(&mut self, out: &mut Vec<AudioChunk>) {
+        if let Some(mut pending) = self.pending.take() {
+            pending.sequence = self.sequence;
+            self.sequence += 1;
+            out.push(pending);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    fn block(capture_ms: f64, ms: usize) -> AudioChunk {
+        AudioChunk {
+            sequence: 0,
+            capture_ms,
+            wall_ms: capture_ms,
+            samples: vec![0.0; RATE as usize * ms / 1000],
+        }
+    }
+
+    #[test]
+    fn waits_longer_than_a_block_larger_than_two_chunks() {
+        let mut coalescer = ChunkCoalescer::new(RATE, 100);
+        assert_eq!(coalescer.flush_after(), Duration::from_millis(200));
+
+        let mut out = Vec::new();
+        coalescer.push(block(0.0, 250), &mut out);
+        assert!(coalescer.flush_after() > Duration::from_millis(250));
+        assert_eq!(out.len(), 2);
+
+        // The next block arrives 250 ms later, within the deadline, and
+        // finishes the held 50 ms rather than following a short chunk.
+        coalescer.push(block(250.0, 250), &mut out);
+        coalescer.push(block(500.0, 250), &mut out);
+        coalescer.push(block(750.0, 250), &mut out);
+        assert_eq!(out.len(), 10);
+        for (sequence, chunk) in out.iter().enumerate() {
+            assert_eq!(chunk.sequence, sequence as u64);
+            assert_eq!(chunk.samples.len(), 1600);
+            assert_eq!(chunk.capture_ms, sequence as f64 * 100.0);
+        }
+    }
+
+    #[test]
+    fn a_gap_flushes_the_partial_chunk() {
+        let mut coalescer = ChunkCoalescer::new(RATE, 100);
+        let mut out = Vec::new();
+        coalescer.push(block(0.0, 150), &mut out);
+        coalescer.push(block(1000.0, 100), &mut out);
+        coalescer.flush(&mut out);
+
+        let sizes: Vec<usize> = out.iter().map(|chunk| chunk.samples.len()).collect();
+        assert_eq!(sizes, [1600, 800, 1600]);
+        assert_eq!(out[2].capture_ms, 1000.0);
+    }
+}
@@ -0,0 +1,177 @@
+use std::io::{self, BufRead};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::events;
+
+pub const MAX_GAIN: f32 = 8.0;
+
+// Shared between the stdin reader and the capture callback, so everything is
+// lock-free; the gain is stored as f32 bits.
+pub struct CaptureControls {
+    paused: AtomicBool,
+    muted: AtomicBool,
+    gain_bits: AtomicU32,
+    reset_requested: AtomicBool,
+    // Closed by an external VAD or the ASR worker's endpointing; audio keeps
+    // flowing through the pipeline but nothing is emitted.
+    gate_open: AtomicBool,
+    // With a wake word configured nothing is emitted while listening; the
+    // detector clears it and "sleep" (or the end of the utterance) sets it.
+    wake_word: bool,
+    listening: AtomicBool,
+}
+
+impl CaptureControls {
+    pub fn new(gain: f32, wake_word: bool) -> Self {
+        Self {
+            paused: AtomicBool::new(false),
+            muted: AtomicBool::new(false),
+            gain_bits: AtomicU32::new(gain.to_bits()),
+            reset_requested: AtomicBool::new(false),
+            gate_open: AtomicBool::new(true),
+            wake_word,
+            listening: AtomicBool::new(wake_word),
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted.load(Ordering::Relaxed)
+    }
+
+    pub fn gain(&self) -> f32 {
+        f32::from_bits(self.gain_bits.load(Ordering::Relaxed))
+    }
+
+    pub fn is_gate_open(&self) -> bool {
+        self.gate_open.load(Ordering::Relaxed)
+    }
+
+    pub fn is_listening(&self) -> bool {
+        self.listening.load(Ordering::Relaxed)
+    }
+
+    // Waking or going back to sleep also resets the VAD gate, like pausing.
+    pub fn set_listening(&self, listening: bool) {
+        if self.listening.swap(listening, Ordering::Relaxed) != listening {
+            self.reset_requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Pausing or resuming drops whatever the VAD gate was holding, so speech
+    // from before a push-to-talk release never leaks into the next press.
+    pub fn take_reset_request(&self) -> bool {
+        self.reset_requested.swap(false, Ordering::Relaxed)
+    }
+
+    fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::Relaxed) != paused {
+            self.reset_requested.store(true, Ordering::Relaxed);
+        }
+    }
+
+    fn set_gain(&self, gain: f32) -> Result<(), String> {
+        if !gain.is_finite() || !(0.0..=MAX_GAIN).contains(&gain) {
+            return Err(format!("gain must be between 0 and {MAX_GAIN}"));
+        }
+        self.gain_bits.store(gain.to_bits(), Ordering::Relaxed);
+        Ok(())
+    }
+
+    // Accepts {"action":"pause"|"resume"|"mute"|"unmute"|"sleep"|"wake"},
+    // {"action":"setGain","value":1.5}, {"action":"gate","open":false} and the
+    // shorthand {"setGain":1.5}.
+    pub fn apply_command(&self, line: &str) -> Result<(), String> {
+        let command: serde_json::Value =
+            serde_json::from_str(line).map_err(|err| format!("invalid control JSON: {err}"))?;
+
+        if let Some(gain) = command.get("setGain") {
+            let gain = gain.as_f64().ok_or("setGain must be a number")?;
+            return self.set_gain(gain as f32);
+        }
+
+        match command.get("action").and_then(|action| action.as_str()) {
+            Some("pause") => self.set_paused(true),
+            Some("resume") => self.set_paused(false),
+            Some("mute") => self.muted.store(true, Ordering::Relaxed),
+            Some("unmute") => self.muted.store(false, Ordering::Relaxed),
+            Some("gate") => {
+                let open = command
+                    .get("open")
+                    .and_then(|open| open.as_bool())
+                    .ok_or("gate requires a boolean open")?;
+                self.gate_open.store(open, Ordering::Relaxed);
+            }
+            Some(action @ ("sleep" | "wake")) => {
+                if !self.wake_word {
+                    return Err(format!("{action} requires --wake-word"));
+                }
+                self.set_listening(action == "sleep");
+            }
+            Some("setGain") => {
+                let gain = command
+                    .get("value")
+                    .and_then(|value| value.as_f64())
+                    .ok_or("setGain requires a numeric value")?;
+                self.set_gain(gain as f32)?;
+            }
+            Some(other) => return Err(format!("unsupported control action: {other}")),
+            None => return Err("control command requires an action".into()),
+        }
+        Ok(())
+    }
+
+    fn report(&self) {
+        let gate = if self.is_gate_open() {
+            "open"
+        } else {
+            "closed"
+        };
+        events::emit(
+            "control",
+            serde_json::json!({
+                "paused": self.is_paused(),
+                "muted": self.is_muted(),
+                "gain": self.gain(),
+                "gate": gate,
+                "listening": self.is_listening(),
+            }),
+            || {
+                format!(
+                    "CONTROL paused={} muted={} gain={} gate={gate} listening={}",
+                    self.is_paused(),
+                    self.is_muted(),
+                    self.gain(),
+                    self.is_listening()
+                )
+            },
+        );
+    }
+}
+
+// The host may spawn us with stdin closed; EOF just ends the listener and
+// capture carries on uncontrolled.
+pub fn spawn_stdin_listener(controls: Arc<CaptureControls>) {
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match controls.apply_command(line) {
+                Ok(()) => controls.report(),
+                Err(error) => events::error("control", error),
+            }
+        }
+    });
+}
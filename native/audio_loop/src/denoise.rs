@@ -0,0 +1,52 @@
+use crate::LinearResampler;
+use nnnoiseless::DenoiseState;
+
+// RNNoise only runs on 10 ms frames at 48 kHz, so capture is brought to 48 kHz
+// first and the pipeline's main resampler then takes it from 48 kHz to the
+// target rate.
+pub const DENOISE_SAMPLE_RATE: u32 = 48_000;
+
+pub struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    to_denoise_rate: LinearResampler,
+    pending: Vec<f32>,
+    frame_out: Vec<f32>,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self {
+            state: DenoiseState::new(),
+            to_denoise_rate: LinearResampler::new(DENOISE_SAMPLE_RATE, DENOISE_SAMPLE_RATE),
+            pending: Vec::with_capacity(DenoiseState::FRAME_SIZE * 4),
+            frame_out: vec![0.0; DenoiseState::FRAME_SIZE],
+        }
+    }
+
+    pub fn set_input_rate(&mut self, input_rate: u32) {
+        self.to_denoise_rate = LinearResampler::new(input_rate, DENOISE_SAMPLE_RATE);
+        self.pending.clear();
+    }
+
+    // Output is at DENOISE_SAMPLE_RATE and lags the input by up to one frame.
+    pub fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
+        self.to_denoise_rate.process(input, &mut self.pending);
+
+        let frame_size = DenoiseState::FRAME_SIZE;
+        let mut frame_in = [0.0_f32; DenoiseState::FRAME_SIZE];
+        let mut consumed = 0;
+        while self.pending.len() - consumed >= frame_size {
+            // RNNoise expects samples on the i16 scale.
+            for (dst, src) in frame_in
+                .iter_mut()
+                .zip(&self.pending[consumed..consumed + frame_size])
+            {
+                *dst = src * i16::MAX as f32;
+            }
+            self.state.process_frame(&mut self.frame_out, &frame_in);
+            out.extend(self.frame_out.iter().map(|sample| sample / i16::MAX as f32));
+            consumed += frame_size;
+        }
+        self.pending.drain(..consumed);
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// Status lines on stderr. By default each one keeps the `TAG key=value` or
+// `source-error: message` text the host has always matched on; with
+// `--json-events` the same information goes out as one JSON object per line,
+// `event` first, so the host can parse instead of pattern-matching.
+static JSON_EVENTS: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_json() {
+    JSON_EVENTS.store(true, Ordering::Relaxed);
+}
+
+pub fn json_enabled() -> bool {
+    JSON_EVENTS.load(Ordering::Relaxed)
+}
+
+// `fields` must be a JSON object; `text` is only built in text mode.
+pub fn emit(event: &str, fields: Value, text: impl FnOnce() -> String) {
+    if !json_enabled() {
+        eprintln!("{}", text());
+        return;
+    }
+    let event = Value::from(event);
+    match fields.as_object() {
+        Some(fields) if !fields.is_empty() => {
+            let fields = Value::Object(fields.clone()).to_string();
+            eprintln!("{{\"event\":{event},{}", &fields[1..]);
+        }
+        _ => eprintln!("{{\"event\":{event}}}"),
+    }
+}
+
+pub fn error(source: &str, message: impl Display) {
+    report("error", source, message);
+}
+
+pub fn warning(source: &str, message: impl Display) {
+    report("warning", source, message);
+}
+
+fn report(level: &str, source: &str, message: impl Display) {
+    let message = message.to_string();
+    emit(
+        level,
+        serde_json::json!({ "source": source, "message": message }),
+        || format!("{source}-{level}: {message}"),
+    );
+}
+
+// The error `run` gives up with has always been printed bare.
+pub fn fatal(message: impl Display) {
+    let message = message.to_string();
+    emit(
+        "error",
+        serde_json::json!({ "source": "fatal", "message": message }),
+        || message.clone(),
+    );
+}
+
+// Timestamps and levels carry one decimal, as in the text lines.
+pub fn tenths(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
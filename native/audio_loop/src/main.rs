@@ -1,69 +1,150 @@
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{BufferSize, SampleFormat, SampleRate, StreamConfig};
+mod aec;
+mod agc;
+mod calibrate;
+mod chunks;
+mod control;
+mod denoise;
+mod events;
+#[cfg(feature = "opus")]
+mod opus;
+mod output;
+mod record;
+mod resample_check;
+mod signal;
+mod stats;
+#[cfg(feature = "wake-word")]
+mod wake;
+
+use aec::EchoCanceller;
+use agc::AutoGain;
+use calibrate::Calibrator;
+use chunks::{AudioChunk, ChunkCoalescer};
+use control::{spawn_stdin_listener, CaptureControls, MAX_GAIN};
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::StreamInstant;
+use denoise::{Denoiser, DENOISE_SAMPLE_RATE};
+use dingoflow_audio::capture::{BufferRequest, CaptureStream, ChannelMap, Notice};
+use dingoflow_audio::convert::f32_to_i16;
+use dingoflow_audio::resample::LinearResampler;
+use dingoflow_audio::source::{
+    device_ids, select_input_device, select_system_device, CaptureDevice, CaptureSource,
+    HostSelection,
+};
+use dingoflow_frame::frame_header;
+#[cfg(feature = "opus")]
+use opus::OpusStream;
+use output::{open_output, OutputSink, OutputTarget};
+use record::spawn_wav_recorder;
+use serde_json::json;
+use signal::SignalMonitor;
+use stats::{spawn_stats_reporter, CaptureStats};
 use std::collections::VecDeque;
 use std::env;
 use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+#[cfg(feature = "wake-word")]
+use wake::{spawn_wake_listener, WakeDetector};
 use webrtc_vad::{SampleRate as VadSampleRate, Vad, VadMode};
 
+struct AgcSettings {
+    target_dbfs: f32,
+    attack_ms: f32,
+    release_ms: f32,
+}
+
+// Parsed in every build so the flag gives a clear error without the feature.
+#[cfg_attr(not(feature = "wake-word"), allow(dead_code))]
+struct WakeSettings {
+    model: PathBuf,
+    threshold: f32,
+}
+
 struct Config {
     target_sample_rate: u32,
+    audio_host: HostSelection,
+    device: Option<String>,
+    channel_map: ChannelMap,
+    source: CaptureSource,
+    aec: bool,
+    denoise: bool,
+    highpass_hz: Option<f32>,
+    buffer: BufferRequest,
+    stall_timeout_ms: u64,
+    stats_interval_ms: u64,
+    silence_warning_ms: u64,
+    gain: f32,
+    agc: Option<AgcSettings>,
+    vad_enabled: bool,
+    list_devices: bool,
+    calibrate_ms: Option<u64>,
+    self_test: bool,
+    calibrate_save: Option<PathBuf>,
+    // --resample-check; the rate, when given, stands in for the device's.
+    resample_check: Option<Option<u32>>,
+    framed: bool,
+    output_target: OutputTarget,
+    meter_interval_ms: usize,
+    record_path: Option<PathBuf>,
+    output_format: OutputFormat,
+    opus_bitrate: Option<u32>,
+    wake_word: Option<WakeSettings>,
     vad_mode: VadMode,
     vad_frame_ms: usize,
     onset_ms: usize,
     hangover_ms: usize,
     preroll_ms: usize,
+    pause_preroll_ms: usize,
+    chunk_ms: Option<usize>,
 }
 
-struct LinearResampler {
-    ratio: f64,
-    position: f64,
-    carry: Vec<f32>,
-    passthrough: bool,
+const AEC_TAIL_MS: usize = 128;
+const SELF_TEST_MS: u64 = 2_000;
+const WATCHDOG_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const DEVICE_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+// A callback arriving later than two blocks plus this slack counts as a gap.
+const CALLBACK_GAP_SLACK_MS: u64 = 20;
+
+#[derive(Clone, Copy)]
+enum OutputFormat {
+    S16le,
+    F32le,
 }
 
-impl LinearResampler {
-    fn new(input_rate: u32, target_rate: u32) -> Self {
-        let passthrough = input_rate == target_rate;
-        Self {
-            ratio: input_rate as f64 / target_rate as f64,
-            position: 0.0,
-            carry: Vec::with_capacity(8192),
-            passthrough,
+impl OutputFormat {
+    fn name(self) -> &'static str {
+        match self {
+            Self::S16le => "s16le",
+            Self::F32le => "f32le",
         }
     }
 
-    fn process(&mut self, input: &[f32], out: &mut Vec<f32>) {
-        if input.is_empty() {
-            return;
-        }
-
-        if self.passthrough {
-            out.extend_from_slice(input);
-            return;
-        }
-
-        self.carry.extend_from_slice(input);
-        let carry_len = self.carry.len() as f64;
-
-        while self.position + 1.0 < carry_len {
-            let index = self.position.floor() as usize;
-            let frac = (self.position - index as f64) as f32;
-            let a = self.carry[index];
-            let b = self.carry[index + 1];
-            out.push(a + (b - a) * frac);
-            self.position += self.ratio;
+    fn bytes_per_sample(self) -> usize {
+        match self {
+            Self::S16le => 2,
+            Self::F32le => 4,
         }
+    }
 
-        let drop_count = self.position.floor() as usize;
-        if drop_count > 0 && drop_count <= self.carry.len() {
-            let remaining = self.carry.len() - drop_count;
-            self.carry.copy_within(drop_count.., 0);
-            self.carry.truncate(remaining);
-            self.position -= drop_count as f64;
+    fn encode(self, samples: &[f32], out: &mut Vec<u8>) {
+        out.reserve(samples.len() * self.bytes_per_sample());
+        match self {
+            Self::S16le => {
+                let mut pcm = Vec::with_capacity(samples.len());
+                f32_to_i16(samples, &mut pcm);
+                for sample in pcm {
+                    out.extend_from_slice(&sample.to_le_bytes());
+                }
+            }
+            Self::F32le => {
+                for sample in samples {
+                    out.extend_from_slice(&sample.clamp(-1.0, 1.0).to_le_bytes());
+                }
+            }
         }
     }
 }
@@ -92,18 +173,242 @@ impl DcBlocker {
     }
 }
 
+// Second-order Butterworth high-pass (RBJ biquad). Coefficients depend on the
+// device rate, so they are recomputed whenever a stream is opened.
+struct HighPassFilter {
+    cutoff_hz: f32,
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl HighPassFilter {
+    fn new(cutoff_hz: f32) -> Self {
+        let mut filter = Self {
+            cutoff_hz,
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            x1: 0.0,
+            x2: 0.0,
+            y1: 0.0,
+            y2: 0.0,
+        };
+        filter.set_sample_rate(48_000);
+        filter
+    }
+
+    fn set_sample_rate(&mut self, sample_rate: u32) {
+        let omega = 2.0 * std::f32::consts::PI * self.cutoff_hz / sample_rate as f32;
+        let alpha = omega.sin() / std::f32::consts::SQRT_2;
+        let cos = omega.cos();
+        let a0 = 1.0 + alpha;
+
+        self.b0 = (1.0 + cos) / 2.0 / a0;
+        self.b1 = -(1.0 + cos) / a0;
+        self.b2 = (1.0 + cos) / 2.0 / a0;
+        self.a1 = -2.0 * cos / a0;
+        self.a2 = (1.0 - alpha) / a0;
+        self.x1 = 0.0;
+        self.x2 = 0.0;
+        self.y1 = 0.0;
+        self.y2 = 0.0;
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples.iter_mut() {
+            let x = *sample;
+            let y = self.b0 * x + self.b1 * self.x1 + self.b2 * self.x2
+                - self.a1 * self.y1
+                - self.a2 * self.y2;
+            self.x2 = self.x1;
+            self.x1 = x;
+            self.y2 = self.y1;
+            self.y1 = y;
+            *sample = y;
+        }
+    }
+}
+
+struct InputPipeline {
+    resampler: Mutex<LinearResampler>,
+    dc_blocker: Mutex<DcBlocker>,
+    signal_monitor: Mutex<SignalMonitor>,
+    highpass: Option<Mutex<HighPassFilter>>,
+    denoiser: Option<Mutex<Denoiser>>,
+    auto_gain: Option<Mutex<AutoGain>>,
+    vad_gate: Option<Mutex<NativeVadGate>>,
+    meter: Option<Mutex<LevelMeter>>,
+    recording: Option<mpsc::Sender<Vec<i16>>>,
+    system_mix: Option<Mutex<SystemMix>>,
+    pause_ring: Option<Mutex<PauseRing>>,
+    // Audio for the wake-word thread, with its captureMs.
+    wake: Option<mpsc::Sender<(f64, Vec<f32>)>>,
+    controls: Arc<CaptureControls>,
+    sequence: AtomicU64,
+    stream_origin: Mutex<Option<StreamInstant>>,
+    // Unix-epoch ms that corresponds to captureMs 0.
+    wall_origin_ms: Mutex<Option<f64>>,
+    last_capture_ms_bits: AtomicU64,
+    clock: Instant,
+    last_callback_ms: AtomicU64,
+    stats: Arc<CaptureStats>,
+}
+
+impl InputPipeline {
+    fn mark_callback(&self) {
+        self.last_callback_ms
+            .store(self.clock.elapsed().as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn ms_since_last_callback(&self) -> u64 {
+        (self.clock.elapsed().as_millis() as u64)
+            .saturating_sub(self.last_callback_ms.load(Ordering::Relaxed))
+    }
+}
+
+// Accumulates RMS/peak over a fixed number of output samples. A reading stuck
+// at the floor usually means a dead or placeholder microphone.
+struct LevelMeter {
+    interval_samples: usize,
+    sum_squares: f64,
+    peak: f32,
+    count: usize,
+}
+
+impl LevelMeter {
+    fn new(sample_rate: u32, interval_ms: usize) -> Self {
+        Self {
+            interval_samples: ((sample_rate as usize * interval_ms) / 1000).max(1),
+            sum_squares: 0.0,
+            peak: 0.0,
+            count: 0,
+        }
+    }
+
+    fn process(&mut self, samples: &[f32], capture_ms: f64) {
+        for &sample in samples {
+            let magnitude = sample.abs().min(1.0);
+            self.sum_squares += (magnitude as f64) * (magnitude as f64);
+            self.peak = self.peak.max(magnitude);
+            self.count += 1;
+
+            if self.count >= self.interval_samples {
+                let rms_dbfs =
+                    amplitude_to_dbfs((self.sum_squares / self.count as f64).sqrt() as f32);
+                let peak_dbfs = amplitude_to_dbfs(self.peak);
+                events::emit(
+                    "level",
+                    json!({
+                        "rmsDbfs": events::tenths(rms_dbfs as f64),
+                        "peakDbfs": events::tenths(peak_dbfs as f64),
+                        "captureMs": events::tenths(capture_ms),
+                    }),
+                    || {
+                        format!(
+                            "LEVEL rms_dbfs={rms_dbfs:.1} peak_dbfs={peak_dbfs:.1} capture_ms={capture_ms:.1}"
+                        )
+                    },
+                );
+                self.sum_squares = 0.0;
+                self.peak = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+}
+
+// System audio captured alongside the mic in `--source both`, already at the
+// target rate. The mic stream drains it sample-for-sample; the cap keeps clock
+// drift between the two devices from turning into growing latency.
+struct SystemMix {
+    resampler: LinearResampler,
+    queue: VecDeque<f32>,
+    max_queued: usize,
+    echo_canceller: Option<EchoCanceller>,
+}
+
+impl SystemMix {
+    fn push(&mut self, mono: &[f32]) {
+        let mut resampled = Vec::with_capacity(mono.len());
+        self.resampler.process(mono, &mut resampled);
+        self.queue.extend(resampled);
+        let excess = self.queue.len().saturating_sub(self.max_queued);
+        self.queue.drain(..excess);
+    }
+
+    // With AEC on, the system audio also serves as the echo reference, so
+    // the mic is cleaned before the two are summed.
+    fn mix_into(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let system = self.queue.pop_front().unwrap_or(0.0);
+            if let Some(echo_canceller) = &mut self.echo_canceller {
+                *sample = echo_canceller.process(*sample, system);
+            }
+            *sample += system;
+        }
+    }
+}
+
+// Keeps the last few hundred ms of processed audio while capture is paused so
+// a push-to-talk resume can start slightly before the keypress.
+struct PauseRing {
+    samples: VecDeque<f32>,
+    capacity: usize,
+    sample_rate: u32,
+}
+
+impl PauseRing {
+    fn new(sample_rate: u32, ms: usize) -> Self {
+        let capacity = sample_rate as usize * ms / 1000;
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+            sample_rate,
+        }
+    }
+
+    fn push(&mut self, block: &[f32]) {
+        self.samples.extend(block);
+        let excess = self.samples.len().saturating_sub(self.capacity);
+        self.samples.drain(..excess);
+    }
+
+    // Returns the held audio and how many ms it spans.
+    fn take(&mut self) -> (Vec<f32>, f64) {
+        let held: Vec<f32> = self.samples.drain(..).collect();
+        let ms = held.len() as f64 * 1000.0 / self.sample_rate as f64;
+        (held, ms)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum SpeechEvent {
+    Start,
+    End,
+}
+
 struct NativeVadGate {
     vad: Vad,
     frame_samples: usize,
     onset_frames: usize,
     hangover_frames: usize,
     preroll_frames: usize,
-    pending: Vec<i16>,
-    preroll: VecDeque<Vec<i16>>,
+    pending: Vec<f32>,
+    preroll: VecDeque<Vec<f32>>,
     active: bool,
     consecutive_speech: usize,
     consecutive_silence: usize,
     noise_floor_dbfs: f32,
+    events: Vec<SpeechEvent>,
 }
 
 // The VAD handle is only accessed behind the recorder's callback-thread mutex.
@@ -144,10 +449,11 @@ impl NativeVadGate {
             consecutive_speech: 0,
             consecutive_silence: 0,
             noise_floor_dbfs: -90.0,
+            events: Vec::new(),
         })
     }
 
-    fn process_block(&mut self, block: &[i16], output: &mut Vec<i16>) {
+    fn process_block(&mut self, block: &[f32], output: &mut Vec<f32>) {
         if block.is_empty() {
             return;
         }
@@ -161,9 +467,11 @@ impl NativeVadGate {
         }
     }
 
-    fn process_frame(&mut self, mut frame: Vec<i16>, output: &mut Vec<i16>) {
+    fn process_frame(&mut self, mut frame: Vec<f32>, output: &mut Vec<f32>) {
         let rms_dbfs = frame_rms_dbfs(&frame);
-        let voiced = self.vad.is_voice_segment(&frame).unwrap_or(false);
+        let mut vad_frame = Vec::with_capacity(frame.len());
+        f32_to_i16(&frame, &mut vad_frame);
+        let voiced = self.vad.is_voice_segment(&vad_frame).unwrap_or(false);
 
         if !voiced {
             self.update_noise_floor(rms_dbfs);
@@ -182,6 +490,7 @@ impl NativeVadGate {
             if self.consecutive_speech >= self.onset_frames {
                 self.active = true;
                 self.consecutive_silence = 0;
+                self.events.push(SpeechEvent::Start);
                 while let Some(preroll_frame) = self.preroll.pop_front() {
                     output.extend_from_slice(&preroll_frame);
                 }
@@ -199,6 +508,7 @@ impl NativeVadGate {
 
         if self.consecutive_silence >= self.hangover_frames {
             self.active = false;
+            self.events.push(SpeechEvent::End);
             self.consecutive_speech = 0;
             self.consecutive_silence = 0;
             self.preroll.clear();
@@ -206,7 +516,26 @@ impl NativeVadGate {
         }
     }
 
-    fn push_preroll(&mut self, frame: Vec<i16>) {
+    // On shutdown, an utterance still in progress keeps its unfinished frame.
+    fn flush(&mut self, output: &mut Vec<f32>) {
+        if self.active {
+            output.append(&mut self.pending);
+        }
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        if self.active {
+            self.events.push(SpeechEvent::End);
+        }
+        self.pending.clear();
+        self.preroll.clear();
+        self.active = false;
+        self.consecutive_speech = 0;
+        self.consecutive_silence = 0;
+    }
+
+    fn push_preroll(&mut self, frame: Vec<f32>) {
         if self.preroll_frames == 0 {
             return;
         }
@@ -238,6 +567,43 @@ fn parse_config() -> Result<Config, String> {
     let mut onset_ms = 120_usize;
     let mut hangover_ms = 360_usize;
     let mut preroll_ms = 180_usize;
+    let mut pause_preroll_ms = 0_usize;
+    let mut chunk_ms: Option<usize> = None;
+    let mut device: Option<String> = None;
+    let mut source = CaptureSource::Mic;
+    let mut aec = false;
+    let mut denoise = false;
+    let mut highpass_hz: Option<f32> = None;
+    let mut audio_host = HostSelection::default_host();
+    let mut channel_map = ChannelMap::Mix;
+    let mut buffer = BufferRequest::Auto;
+    let mut stall_timeout_ms = 2_000_u64;
+    let mut stats_interval_ms = 0_u64;
+    let mut silence_warning_ms = 10_000_u64;
+    let mut gain = 1.0_f32;
+    let mut agc = false;
+    let mut agc_target_dbfs = -20.0_f32;
+    let mut agc_attack_ms = 10.0_f32;
+    let mut agc_release_ms = 500.0_f32;
+    // The host only passes the VAD tuning flags when it wants native gating,
+    // so any of them switches the gate on just like --vad.
+    let mut vad_enabled = false;
+    let mut list_devices = false;
+    let mut calibrate = false;
+    let mut self_test = false;
+    let mut calibrate_ms = 5_000_u64;
+    let mut calibrate_save: Option<PathBuf> = None;
+    let mut resample_check = false;
+    let mut resample_check_rate: Option<u32> = None;
+    let mut framed = false;
+    let mut output_target = OutputTarget::Stdout;
+    let mut meter_interval_ms = 0_usize;
+    let mut record_path: Option<PathBuf> = None;
+    let mut output_format = OutputFormat::S16le;
+    let mut encode_opus = false;
+    let mut bitrate = 24_000_u32;
+    let mut wake_model: Option<PathBuf> = None;
+    let mut wake_threshold = 0.5_f32;
     let args: Vec<String> = env::args().collect();
     let mut i = 1;
 
@@ -253,6 +619,7 @@ fn parse_config() -> Result<Config, String> {
                 i += 2;
             }
             "--vad-mode" => {
+                vad_enabled = true;
                 if i + 1 >= args.len() {
                     return Err("Missing value for --vad-mode".into());
                 }
@@ -266,6 +633,7 @@ fn parse_config() -> Result<Config, String> {
                 i += 2;
             }
             "--vad-frame-ms" => {
+                vad_enabled = true;
                 if i + 1 >= args.len() {
                     return Err("Missing value for --vad-frame-ms".into());
                 }
@@ -275,6 +643,7 @@ fn parse_config() -> Result<Config, String> {
                 i += 2;
             }
             "--speech-onset-ms" => {
+                vad_enabled = true;
                 if i + 1 >= args.len() {
                     return Err("Missing value for --speech-onset-ms".into());
                 }
@@ -284,6 +653,7 @@ fn parse_config() -> Result<Config, String> {
                 i += 2;
             }
             "--speech-hangover-ms" => {
+                vad_enabled = true;
                 if i + 1 >= args.len() {
                     return Err("Missing value for --speech-hangover-ms".into());
                 }
@@ -292,7 +662,28 @@ fn parse_config() -> Result<Config, String> {
                     .map_err(|_| "Invalid --speech-hangover-ms value".to_string())?;
                 i += 2;
             }
+            "--preroll-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --preroll-ms".into());
+                }
+                pause_preroll_ms = args[i + 1]
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid --preroll-ms value".to_string())?;
+                i += 2;
+            }
+            "--chunk-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --chunk-ms".into());
+                }
+                chunk_ms = Some(
+                    args[i + 1]
+                        .parse::<usize>()
+                        .map_err(|_| "Invalid --chunk-ms value".to_string())?,
+                );
+                i += 2;
+            }
             "--speech-preroll-ms" => {
+                vad_enabled = true;
                 if i + 1 >= args.len() {
                     return Err("Missing value for --speech-preroll-ms".into());
                 }
@@ -301,9 +692,294 @@ fn parse_config() -> Result<Config, String> {
                     .map_err(|_| "Invalid --speech-preroll-ms value".to_string())?;
                 i += 2;
             }
+            "--device" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --device".into());
+                }
+                device = Some(args[i + 1].clone());
+                i += 2;
+            }
+            "--source" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --source".into());
+                }
+                source = CaptureSource::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--aec" => {
+                aec = true;
+                i += 1;
+            }
+            "--denoise" => {
+                denoise = true;
+                i += 1;
+            }
+            "--highpass" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --highpass".into());
+                }
+                highpass_hz = Some(
+                    args[i + 1]
+                        .parse::<f32>()
+                        .map_err(|_| "Invalid --highpass value".to_string())?,
+                );
+                i += 2;
+            }
+            "--buffer-frames" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --buffer-frames".into());
+                }
+                buffer = BufferRequest::Frames(
+                    args[i + 1]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|frames| *frames > 0)
+                        .ok_or("Invalid --buffer-frames value")?,
+                );
+                i += 2;
+            }
+            "--latency-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --latency-ms".into());
+                }
+                buffer = BufferRequest::LatencyMs(
+                    args[i + 1]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|ms| (1..=500).contains(ms))
+                        .ok_or("Invalid --latency-ms value")?,
+                );
+                i += 2;
+            }
+            // cpal only opens WASAPI in shared mode, so the closest thing to
+            // an exclusive mode is asking every host for its smallest period.
+            "--low-latency" => {
+                buffer = BufferRequest::Minimum;
+                i += 1;
+            }
+            "--channels" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --channels".into());
+                }
+                channel_map = ChannelMap::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--audio-host" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --audio-host".into());
+                }
+                audio_host = HostSelection::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--stall-timeout-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --stall-timeout-ms".into());
+                }
+                stall_timeout_ms = args[i + 1]
+                    .parse::<u64>()
+                    .map_err(|_| "Invalid --stall-timeout-ms value".to_string())?;
+                i += 2;
+            }
+            "--silence-warning-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --silence-warning-ms".into());
+                }
+                silence_warning_ms = args[i + 1]
+                    .parse::<u64>()
+                    .map_err(|_| "Invalid --silence-warning-ms value".to_string())?;
+                i += 2;
+            }
+            "--stats-interval-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --stats-interval-ms".into());
+                }
+                stats_interval_ms = args[i + 1]
+                    .parse::<u64>()
+                    .map_err(|_| "Invalid --stats-interval-ms value".to_string())?;
+                i += 2;
+            }
+            "--gain" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --gain".into());
+                }
+                gain = args[i + 1]
+                    .parse::<f32>()
+                    .map_err(|_| "Invalid --gain value".to_string())?;
+                i += 2;
+            }
+            "--agc" => {
+                agc = true;
+                i += 1;
+            }
+            "--agc-target-dbfs" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --agc-target-dbfs".into());
+                }
+                agc_target_dbfs = args[i + 1]
+                    .parse::<f32>()
+                    .map_err(|_| "Invalid --agc-target-dbfs value".to_string())?;
+                i += 2;
+            }
+            "--agc-attack-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --agc-attack-ms".into());
+                }
+                agc_attack_ms = args[i + 1]
+                    .parse::<f32>()
+                    .map_err(|_| "Invalid --agc-attack-ms value".to_string())?;
+                i += 2;
+            }
+            "--agc-release-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --agc-release-ms".into());
+                }
+                agc_release_ms = args[i + 1]
+                    .parse::<f32>()
+                    .map_err(|_| "Invalid --agc-release-ms value".to_string())?;
+                i += 2;
+            }
+            // Switched on in `run` before parsing so argument errors follow it.
+            "--json-events" => {
+                i += 1;
+            }
+            "--list-devices" => {
+                list_devices = true;
+                i += 1;
+            }
+            "--vad" => {
+                vad_enabled = true;
+                i += 1;
+            }
+            "--framed" => {
+                framed = true;
+                i += 1;
+            }
+            "--output" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --output".into());
+                }
+                output_target = OutputTarget::parse(&args[i + 1])?;
+                i += 2;
+            }
+            "--meter-interval-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --meter-interval-ms".into());
+                }
+                meter_interval_ms = args[i + 1]
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid --meter-interval-ms value".to_string())?;
+                i += 2;
+            }
+            "--record" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --record".into());
+                }
+                record_path = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--format" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --format".into());
+                }
+                output_format = match args[i + 1].as_str() {
+                    "s16le" => OutputFormat::S16le,
+                    "f32le" => OutputFormat::F32le,
+                    _ => return Err("Invalid --format value".into()),
+                };
+                i += 2;
+            }
+            "--encode" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --encode".into());
+                }
+                encode_opus = match args[i + 1].as_str() {
+                    "pcm" => false,
+                    "opus" => true,
+                    _ => return Err("Invalid --encode value".into()),
+                };
+                i += 2;
+            }
+            "--bitrate" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --bitrate".into());
+                }
+                let value = args[i + 1].to_lowercase();
+                bitrate = match value.strip_suffix('k') {
+                    Some(kbps) => kbps.parse::<u32>().map(|kbps| kbps * 1000),
+                    None => value.parse::<u32>(),
+                }
+                .ok()
+                .filter(|bps| (6_000..=510_000).contains(bps))
+                .ok_or("Invalid --bitrate value (6k..510k)")?;
+                i += 2;
+            }
+            "--self-test" => {
+                self_test = true;
+                i += 1;
+            }
+            "--calibrate" => {
+                calibrate = true;
+                i += 1;
+            }
+            "--resample-check" => {
+                resample_check = true;
+                i += 1;
+            }
+            "--resample-check-rate" => {
+                resample_check = true;
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --resample-check-rate".into());
+                }
+                resample_check_rate = Some(
+                    args[i + 1]
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|rate| (8_000..=384_000).contains(rate))
+                        .ok_or("Invalid --resample-check-rate value (8000..384000)")?,
+                );
+                i += 2;
+            }
+            "--calibrate-ms" => {
+                calibrate = true;
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --calibrate-ms".into());
+                }
+                calibrate_ms = args[i + 1]
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| (1_000..=30_000).contains(ms))
+                    .ok_or("Invalid --calibrate-ms value (1000..30000)")?;
+                i += 2;
+            }
+            "--calibrate-save" => {
+                calibrate = true;
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --calibrate-save".into());
+                }
+                calibrate_save = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--wake-word" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --wake-word".into());
+                }
+                wake_model = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--wake-threshold" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --wake-threshold".into());
+                }
+                wake_threshold = args[i + 1]
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|threshold| (0.0..=1.0).contains(threshold))
+                    .ok_or("Invalid --wake-threshold value (0..1)")?;
+                i += 2;
+            }
             "--help" | "-h" => {
                 return Err(
-                    "usage: dingoflow-audio-loop [--sample-rate 16000] [--vad-mode very-aggressive] [--vad-frame-ms 20] [--speech-onset-ms 120] [--speech-hangover-ms 360] [--speech-preroll-ms 180]"
+                    "usage: dingoflow-audio-loop [--sample-rate 16000] [--device <id, index or name substring>] [--channels mix|left|right|N[,M]] [--source mic|system|both] [--aec] [--denoise] [--highpass <hz>] [--audio-host default|alsa|jack|wasapi|asio|coreaudio|pipewire|pulse] [--buffer-frames <n> | --latency-ms <ms> | --low-latency] [--stall-timeout-ms 2000] [--stats-interval-ms 0] [--silence-warning-ms 10000] [--gain 1.0] [--agc] [--agc-target-dbfs -20] [--agc-attack-ms 10] [--agc-release-ms 500] [--json-events] [--list-devices] [--self-test] [--calibrate] [--calibrate-ms 5000] [--calibrate-save <path>] [--resample-check] [--resample-check-rate <hz>] [--framed] [--output stdout|unix:<path>|pipe:<name>] [--vad] [--meter-interval-ms 0] [--record /path/session.wav] [--format s16le] [--encode pcm|opus] [--bitrate 24k] [--wake-word <model.onnx>] [--wake-threshold 0.5] [--vad-mode very-aggressive] [--vad-frame-ms 20] [--speech-onset-ms 120] [--speech-hangover-ms 360] [--speech-preroll-ms 180] [--preroll-ms 0] [--chunk-ms 20|40|80]"
                         .into(),
                 );
             }
@@ -316,17 +992,204 @@ fn parse_config() -> Result<Config, String> {
     if !(8_000..=96_000).contains(&target_sample_rate) {
         return Err("sample rate must be between 8000 and 96000".into());
     }
+    // Opus packets have no length of their own, so they are always framed.
+    if encode_opus {
+        if !cfg!(feature = "opus") {
+            return Err("this build has no Opus support; rebuild with --features opus".into());
+        }
+        framed = true;
+    }
+    if wake_model.is_some() {
+        if !cfg!(feature = "wake-word") {
+            return Err(
+                "this build has no wake-word support; rebuild with --features wake-word".into(),
+            );
+        }
+        // openWakeWord models are trained on 16 kHz audio only.
+        if target_sample_rate != 16_000 {
+            return Err("--wake-word requires --sample-rate 16000".into());
+        }
+    }
+    if highpass_hz.is_some_and(|hz| !(10.0..=1_000.0).contains(&hz)) {
+        return Err("high-pass cutoff must be between 10 and 1000 Hz".into());
+    }
+    if !gain.is_finite() || !(0.0..=MAX_GAIN).contains(&gain) {
+        return Err(format!("gain must be between 0 and {MAX_GAIN}"));
+    }
+    if !(-60.0..=0.0).contains(&agc_target_dbfs) {
+        return Err("AGC target must be between -60 and 0 dBFS".into());
+    }
+    if agc_attack_ms <= 0.0 || agc_release_ms <= 0.0 {
+        return Err("AGC attack and release must be positive".into());
+    }
+    if aec && source != CaptureSource::Both {
+        return Err("--aec needs the system audio reference from --source both".into());
+    }
+    if chunk_ms.is_some_and(|ms| !(10..=500).contains(&ms)) {
+        return Err("chunk size must be between 10 and 500 milliseconds".into());
+    }
     if !matches!(vad_frame_ms, 10 | 20 | 30) {
         return Err("vad frame size must be 10, 20, or 30 milliseconds".into());
     }
 
     Ok(Config {
         target_sample_rate,
+        device,
+        source,
+        aec,
+        denoise,
+        highpass_hz,
+        audio_host,
+        channel_map,
+        buffer,
+        stall_timeout_ms,
+        stats_interval_ms,
+        silence_warning_ms,
+        gain,
+        agc: agc.then_some(AgcSettings {
+            target_dbfs: agc_target_dbfs,
+            attack_ms: agc_attack_ms,
+            release_ms: agc_release_ms,
+        }),
+        vad_enabled,
+        list_devices,
+        calibrate_ms: calibrate.then_some(calibrate_ms),
+        calibrate_save,
+        self_test,
+        resample_check: resample_check.then_some(resample_check_rate),
+        framed,
+        output_target,
+        meter_interval_ms,
+        record_path,
+        output_format,
+        opus_bitrate: encode_opus.then_some(bitrate),
+        wake_word: wake_model.map(|model| WakeSettings {
+            model,
+            threshold: wake_threshold,
+        }),
         vad_mode,
         vad_frame_ms,
         onset_ms,
         hangover_ms,
         preroll_ms,
+        pause_preroll_ms,
+        chunk_ms,
+    })
+}
+
+const COMMON_SAMPLE_RATES: [u32; 9] = [
+    8_000, 11_025, 16_000, 22_050, 24_000, 32_000, 44_100, 48_000, 96_000,
+];
+
+// Device ids and indices are per host and match what --device accepts for the
+// same host (the default one unless --audio-host is given).
+fn list_devices() -> Result<(), String> {
+    let default_host_id = cpal::default_host().id();
+    let mut hosts = Vec::new();
+
+    for host_id in cpal::available_hosts() {
+        let host = match cpal::host_from_id(host_id) {
+            Ok(host) => host,
+            Err(error) => {
+                hosts.push(json!({
+                    "id": host_id.name(),
+                    "isDefault": host_id == default_host_id,
+                    "error": error.to_string()
+                }));
+                continue;
+            }
+        };
+        let default_name = host
+            .default_input_device()
+            .and_then(|device| device.name().ok());
+        let inputs = host
+            .input_devices()
+            .map_err(|e| {
+                format!(
+                    "failed to enumerate input devices on {}: {e}",
+                    host_id.name()
+                )
+            })?
+            .collect::<Vec<_>>();
+        let names = inputs
+            .iter()
+            .map(|device| device.name().unwrap_or_else(|_| "<unknown>".into()))
+            .collect::<Vec<_>>();
+        let devices = inputs
+            .iter()
+            .zip(device_ids(host_id, &names))
+            .enumerate()
+            .map(|(index, (device, id))| {
+                describe_input_device(index, &id, device, default_name.as_deref())
+            })
+            .collect::<Vec<_>>();
+
+        hosts.push(json!({
+            "id": host_id.name(),
+            "isDefault": host_id == default_host_id,
+            "devices": devices
+        }));
+    }
+
+    println!("{}", json!({ "hosts": hosts }));
+    Ok(())
+}
+
+fn describe_input_device(
+    index: usize,
+    id: &str,
+    device: &cpal::Device,
+    default_name: Option<&str>,
+) -> serde_json::Value {
+    let name = device.name().unwrap_or_else(|_| "<unknown>".into());
+    let default_config = device.default_input_config().ok().map(|cfg| {
+        json!({
+            "sampleRate": cfg.sample_rate().0,
+            "channels": cfg.channels(),
+            "sampleFormat": cfg.sample_format().to_string()
+        })
+    });
+
+    let mut sample_rates = Vec::new();
+    let mut sample_formats = Vec::new();
+    let mut max_channels = 0;
+    let mut ranges = Vec::new();
+    if let Ok(configs) = device.supported_input_configs() {
+        for range in configs {
+            let (min_rate, max_rate) = (range.min_sample_rate().0, range.max_sample_rate().0);
+            for rate in COMMON_SAMPLE_RATES
+                .into_iter()
+                .filter(|rate| (min_rate..=max_rate).contains(rate))
+            {
+                if !sample_rates.contains(&rate) {
+                    sample_rates.push(rate);
+                }
+            }
+            let format = range.sample_format().to_string();
+            if !sample_formats.contains(&format) {
+                sample_formats.push(format.clone());
+            }
+            max_channels = max_channels.max(range.channels());
+            ranges.push(json!({
+                "channels": range.channels(),
+                "minSampleRate": min_rate,
+                "maxSampleRate": max_rate,
+                "sampleFormat": format
+            }));
+        }
+    }
+    sample_rates.sort_unstable();
+
+    json!({
+        "index": index,
+        "id": id,
+        "name": name,
+        "isDefault": default_name == Some(name.as_str()),
+        "defaultConfig": default_config,
+        "maxChannels": max_channels,
+        "sampleRates": sample_rates,
+        "sampleFormats": sample_formats,
+        "supportedConfigs": ranges
     })
 }
 
@@ -355,42 +1218,14 @@ fn vad_mode_name(mode: &VadMode) -> &'static str {
     }
 }
 
-fn to_mono_f32<T, F>(input: &[T], channels: usize, to_f32: F, out: &mut Vec<f32>)
-where
-    F: Fn(T) -> f32,
-    T: Copy,
-{
-    if channels <= 1 {
-        out.extend(input.iter().copied().map(to_f32));
-        return;
-    }
-
-    for frame in input.chunks(channels) {
-        if frame.is_empty() {
-            continue;
-        }
-
-        let sum = frame.iter().copied().map(&to_f32).sum::<f32>();
-        out.push(sum / channels as f32);
-    }
-}
-
-fn f32_to_i16(input: &[f32], out: &mut Vec<i16>) {
-    out.reserve(input.len());
-    for sample in input {
-        let clamped = sample.clamp(-1.0, 1.0);
-        out.push((clamped * i16::MAX as f32) as i16);
-    }
-}
-
-fn frame_rms_dbfs(frame: &[i16]) -> f32 {
+fn frame_rms_dbfs(frame: &[f32]) -> f32 {
     if frame.is_empty() {
         return -90.0;
     }
 
     let mut sum_squares = 0.0_f64;
     for &sample in frame {
-        let normalized = sample as f64 / i16::MAX as f64;
+        let normalized = sample.clamp(-1.0, 1.0) as f64;
         sum_squares += normalized * normalized;
     }
 
@@ -402,7 +1237,15 @@ fn frame_rms_dbfs(frame: &[i16]) -> f32 {
     }
 }
 
-fn apply_noise_suppression(frame: &mut [i16], rms_dbfs: f32, noise_floor_dbfs: f32) {
+fn amplitude_to_dbfs(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        -90.0
+    } else {
+        (20.0 * amplitude.log10()).max(-90.0)
+    }
+}
+
+fn apply_noise_suppression(frame: &mut [f32], rms_dbfs: f32, noise_floor_dbfs: f32) {
     if frame.is_empty() || !rms_dbfs.is_finite() || !noise_floor_dbfs.is_finite() {
         return;
     }
@@ -417,211 +1260,1031 @@ fn apply_noise_suppression(frame: &mut [i16], rms_dbfs: f32, noise_floor_dbfs: f
     };
 
     for sample in frame.iter_mut() {
-        let adjusted = (*sample as f32 * gain).round();
-        *sample = adjusted.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+        *sample = (*sample * gain).clamp(-1.0, 1.0);
     }
 }
 
+enum LoopEvent {
+    DeviceLost,
+    Shutdown,
+}
+
+// Turns capture notices into the status events the host has always seen;
+// a vanished device also wakes the main loop through `device_lost`.
+fn report_notice(notice: Notice, device_lost: Option<&mpsc::Sender<LoopEvent>>) {
+    match notice {
+        Notice::ChannelFallback {
+            device,
+            channels,
+            requested,
+        } => events::warning(
+            "channel",
+            format!(
+                "{device:?} has {channels} channel(s); --channels {requested} ignored, mixing all"
+            ),
+        ),
+        Notice::BufferRejected { frames, error } => events::warning(
+            "buffer",
+            format!("fixed buffer of {frames} frames rejected ({error}); using the driver default"),
+        ),
+        Notice::ConfigRejected {
+            rejected,
+            next,
+            error,
+        } => events::warning(
+            "config",
+            format!("{rejected} rejected ({error}); trying {next}"),
+        ),
+        Notice::BufferGranted {
+            device,
+            requested,
+            granted,
+            latency_ms,
+        } => events::emit(
+            "buffer",
+            json!({
+                "device": device,
+                "requestedFrames": requested,
+                "grantedFrames": granted,
+                "latencyMs": events::tenths(latency_ms),
+            }),
+            || {
+                format!(
+                    "BUFFER device={device:?} requested_frames={requested} granted_frames={granted} latency_ms={latency_ms:.1}"
+                )
+            },
+        ),
+        Notice::ConfigFallback {
+            device,
+            sample_rate,
+            channels,
+            sample_format,
+            description,
+        } => events::emit(
+            "configFallback",
+            json!({
+                "device": device,
+                "sampleRate": sample_rate,
+                "channels": channels,
+                "sampleFormat": sample_format.to_string(),
+            }),
+            || format!("CONFIG_FALLBACK device={device:?} {description}"),
+        ),
+        Notice::Stream(error) => {
+            events::error("stream", &error);
+            if matches!(error, cpal::StreamError::DeviceNotAvailable) {
+                if let Some(device_lost) = device_lost {
+                    let _ = device_lost.send(LoopEvent::DeviceLost);
+                }
+            }
+        }
+    }
+}
+
+fn select_primary_device(host: &cpal::Host, config: &Config) -> Result<CaptureDevice, String> {
+    match config.source {
+        CaptureSource::System => select_system_device(host, config.device.as_deref()),
+        CaptureSource::Mic | CaptureSource::Both => select_input_device(
+            host,
+            config.device.as_deref().or(config.audio_host.route_device),
+        ),
+    }
+}
+
+// Runs the device for `duration_ms` with every block going to a Calibrator.
+fn survey_input(
+    capture: &CaptureDevice,
+    config: &Config,
+    duration_ms: u64,
+    announce: impl FnOnce(&CaptureStream),
+) -> Result<(CaptureStream, Calibrator), String> {
+    let calibrator = Arc::new(Mutex::new(Calibrator::new()));
+    let sink_calibrator = Arc::clone(&calibrator);
+    let active = CaptureStream::open(
+        capture,
+        config.buffer,
+        &config.channel_map,
+        |notice| report_notice(notice, None),
+        move |mono, _| {
+            if let Ok(mut calibrator) = sink_calibrator.lock() {
+                calibrator.process(mono);
+            }
+        },
+    )?;
+    if let Ok(mut calibrator) = calibrator.lock() {
+        calibrator.set_sample_rate(active.input_sample_rate);
+    }
+
+    announce(&active);
+    let active = active.play()?;
+    thread::sleep(Duration::from_millis(duration_ms));
+    active.pause()?;
+    let calibrator = std::mem::replace(
+        &mut *calibrator
+            .lock()
+            .map_err(|_| "level survey state poisoned".to_string())?,
+        Calibrator::new(),
+    );
+    Ok((active, calibrator))
+}
+
+// Listens for a few seconds on the selected device and prints the levels and
+// a suggested --gain as one JSON line on stdout.
+fn calibrate(capture: &CaptureDevice, config: &Config, duration_ms: u64) -> Result<(), String> {
+    let (_, calibrator) = survey_input(capture, config, duration_ms, |active| {
+        let message = "stay quiet for a moment, then speak normally until it finishes";
+        events::emit(
+            "calibrating",
+            json!({
+                "device": active.device_name,
+                "durationMs": duration_ms,
+                "message": message,
+            }),
+            || {
+                format!(
+                    "CALIBRATING device={:?} duration_ms={duration_ms} message={message:?}",
+                    active.device_name
+                )
+            },
+        );
+    })?;
+
+    let report = calibrator.report(duration_ms, MAX_GAIN).to_string();
+    if let Some(path) = &config.calibrate_save {
+        std::fs::write(path, format!("{report}\n"))
+            .map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+    }
+    println!("{report}");
+    Ok(())
+}
+
+// Prints a single JSON verdict on stdout and exits non-zero unless the
+// microphone delivered real audio. A device that can't even be opened is a
+// verdict too, since that is often how a permission denial shows up.
+fn self_test(host: &cpal::Host, config: &Config) -> Result<(), String> {
+    let survey = select_primary_device(host, config).and_then(|capture| {
+        survey_input(&capture, config, SELF_TEST_MS, |active| {
+            events::emit(
+                "selfTest",
+                json!({ "device": active.device_name, "durationMs": SELF_TEST_MS }),
+                || {
+                    format!(
+                        "SELF_TEST device={:?} duration_ms={SELF_TEST_MS}",
+                        active.device_name
+                    )
+                },
+            );
+        })
+    });
+
+    let mut report = json!({ "event": "self-test", "durationMs": SELF_TEST_MS });
+    let (verdict, message) = match &survey {
+        Ok((active, calibrator)) => {
+            report["device"] = json!(active.device_name);
+            report["sampleRate"] = json!(active.input_sample_rate);
+            report["channels"] = json!(active.channels);
+            if let (Some(report), Some(summary)) =
+                (report.as_object_mut(), calibrator.summary().as_object())
+            {
+                report.extend(summary.clone());
+            }
+            let (verdict, message) = calibrator.verdict();
+            (verdict, message.to_string())
+        }
+        Err(error) => ("open-failed", error.clone()),
+    };
+    report["ok"] = json!(verdict == "ok");
+    report["verdict"] = json!(verdict);
+    report["message"] = json!(message);
+    println!("{report}");
+
+    if verdict == "ok" {
+        Ok(())
+    } else {
+        Err(format!("self-test failed: {message}"))
+    }
+}
+
+// Prints the sweep's report as one JSON line on stdout and, like
+// --self-test, exits non-zero unless the verdict is ok.
+fn resample_check(input_rate: u32, config: &Config) -> Result<(), String> {
+    let report = resample_check::run(input_rate, config);
+    println!("{report}");
+    if report["ok"] == true {
+        Ok(())
+    } else {
+        Err(format!(
+            "resample check failed: {}",
+            report["message"].as_str().unwrap_or_default()
+        ))
+    }
+}
+
+fn open_primary_stream(
+    capture: &CaptureDevice,
+    config: &Config,
+    pipeline: &Arc<InputPipeline>,
+    tx: &mpsc::Sender<AudioChunk>,
+    device_lost: &mpsc::Sender<LoopEvent>,
+) -> Result<CaptureStream, String> {
+    let sink_pipeline = Arc::clone(pipeline);
+    let tx = tx.clone();
+    let device_lost = device_lost.clone();
+    let active = CaptureStream::open(
+        capture,
+        config.buffer,
+        &config.channel_map,
+        move |notice| report_notice(notice, Some(&device_lost)),
+        move |mono, capture| {
+            process_input_block(mono, capture, &sink_pipeline, &tx);
+        },
+    )?;
+
+    if let Some(Ok(mut highpass)) = pipeline.highpass.as_ref().map(Mutex::lock) {
+        highpass.set_sample_rate(active.input_sample_rate);
+    }
+    if let Ok(mut monitor) = pipeline.signal_monitor.lock() {
+        monitor.set_sample_rate(active.input_sample_rate);
+    }
+    let mut resampler_input_rate = active.input_sample_rate;
+    if let Some(Ok(mut denoiser)) = pipeline.denoiser.as_ref().map(Mutex::lock) {
+        denoiser.set_input_rate(active.input_sample_rate);
+        resampler_input_rate = DENOISE_SAMPLE_RATE;
+    }
+    if let Ok(mut resampler) = pipeline.resampler.lock() {
+        *resampler = LinearResampler::new(resampler_input_rate, config.target_sample_rate);
+    }
+    pipeline
+        .stats
+        .input_sample_rate
+        .store(active.input_sample_rate, Ordering::Relaxed);
+    pipeline
+        .stats
+        .resampler_input_rate
+        .store(resampler_input_rate, Ordering::Relaxed);
+    // Opening counts as activity so the watchdog measures from here.
+    pipeline.mark_callback();
+    active.play()
+}
+
 fn run() -> Result<(), String> {
+    if env::args().any(|arg| arg == "--json-events") {
+        events::enable_json();
+    }
     let config = parse_config()?;
-    let host = cpal::default_host();
-    let device = host
-        .default_input_device()
-        .ok_or("default input device not available")?;
-
-    let default_cfg = device
-        .default_input_config()
-        .map_err(|e| format!("failed to query default input config: {e}"))?;
-
-    let input_sample_rate = default_cfg.sample_rate().0;
-    let channels = default_cfg.channels() as usize;
-    let target_buffer_frames = (input_sample_rate / 200).clamp(64, 1024);
-    let stream_config = StreamConfig {
-        channels: default_cfg.channels(),
-        sample_rate: SampleRate(input_sample_rate),
-        buffer_size: BufferSize::Fixed(target_buffer_frames),
+    if config.list_devices {
+        return list_devices();
+    }
+    if let Some(Some(input_rate)) = config.resample_check {
+        return resample_check(input_rate, &config);
+    }
+    let host = config.audio_host.open()?;
+    if config.self_test {
+        return self_test(&host, &config);
+    }
+    let primary_device = select_primary_device(&host, &config)?;
+    if config.resample_check.is_some() {
+        let input_rate = CaptureStream::open(
+            &primary_device,
+            config.buffer,
+            &config.channel_map,
+            |notice| report_notice(notice, None),
+            |_, _| {},
+        )?
+        .input_sample_rate;
+        return resample_check(input_rate, &config);
+    }
+    if let Some(duration_ms) = config.calibrate_ms {
+        return calibrate(&primary_device, &config, duration_ms);
+    }
+
+    let (recording, recorder_thread) = match config.record_path.as_deref() {
+        Some(path) => {
+            let (recording, handle) = spawn_wav_recorder(path, config.target_sample_rate)?;
+            (Some(recording), Some(handle))
+        }
+        None => (None, None),
     };
 
-    let resampler = Arc::new(Mutex::new(LinearResampler::new(
-        input_sample_rate,
-        config.target_sample_rate,
-    )));
-    let dc_blocker = Arc::new(Mutex::new(DcBlocker::new()));
-    let vad_gate = Arc::new(Mutex::new(NativeVadGate::new(
-        config.target_sample_rate,
-        &config,
-    )?));
-
-    let (tx, rx) = mpsc::channel::<Vec<i16>>();
-    let _writer_thread = thread::spawn(move || {
-        let stdout = io::stdout();
-        let mut writer = BufWriter::with_capacity(64 * 1024, stdout.lock());
-        let mut bytes = Vec::<u8>::with_capacity(64 * 1024);
+    let controls = Arc::new(CaptureControls::new(
+        config.gain,
+        config.wake_word.is_some(),
+    ));
+    #[cfg(feature = "wake-word")]
+    let wake = match &config.wake_word {
+        Some(wake) => {
+            let detector = WakeDetector::new(&wake.model, wake.threshold)?;
+            Some(spawn_wake_listener(detector, Arc::clone(&controls)))
+        }
+        None => None,
+    };
+    #[cfg(not(feature = "wake-word"))]
+    let wake = None;
+    let stats = Arc::new(CaptureStats::default());
+    let pipeline = Arc::new(InputPipeline {
+        // Replaced with the device's real rate once the stream is opened.
+        resampler: Mutex::new(LinearResampler::new(
+            config.target_sample_rate,
+            config.target_sample_rate,
+        )),
+        dc_blocker: Mutex::new(DcBlocker::new()),
+        signal_monitor: Mutex::new(SignalMonitor::new(config.silence_warning_ms)),
+        highpass: config
+            .highpass_hz
+            .map(|hz| Mutex::new(HighPassFilter::new(hz))),
+        denoiser: config.denoise.then(|| Mutex::new(Denoiser::new())),
+        auto_gain: config.agc.as_ref().map(|agc| {
+            Mutex::new(AutoGain::new(
+                config.target_sample_rate,
+                agc.target_dbfs,
+                agc.attack_ms,
+                agc.release_ms,
+            ))
+        }),
+        vad_gate: if config.vad_enabled {
+            Some(Mutex::new(NativeVadGate::new(
+                config.target_sample_rate,
+                &config,
+            )?))
+        } else {
+            None
+        },
+        meter: (config.meter_interval_ms > 0).then(|| {
+            Mutex::new(LevelMeter::new(
+                config.target_sample_rate,
+                config.meter_interval_ms,
+            ))
+        }),
+        recording,
+        system_mix: (config.source == CaptureSource::Both).then(|| {
+            Mutex::new(SystemMix {
+                resampler: LinearResampler::new(
+                    config.target_sample_rate,
+                    config.target_sample_rate,
+                ),
+                queue: VecDeque::new(),
+                max_queued: config.target_sample_rate as usize,
+                echo_canceller: config
+                    .aec
+                    .then(|| EchoCanceller::new(config.target_sample_rate, AEC_TAIL_MS)),
+            })
+        }),
+        pause_ring: (config.pause_preroll_ms > 0).then(|| {
+            Mutex::new(PauseRing::new(
+                config.target_sample_rate,
+                config.pause_preroll_ms,
+            ))
+        }),
+        wake,
+        controls: Arc::clone(&controls),
+        sequence: AtomicU64::new(0),
+        stream_origin: Mutex::new(None),
+        wall_origin_ms: Mutex::new(None),
+        last_capture_ms_bits: AtomicU64::new(0.0_f64.to_bits()),
+        clock: Instant::now(),
+        last_callback_ms: AtomicU64::new(0),
+        stats: Arc::clone(&stats),
+    });
 
-        while let Ok(block) = rx.recv() {
-            if block.is_empty() {
-                continue;
+    let (tx, rx) = mpsc::channel::<AudioChunk>();
+    let framed = config.framed;
+    let output_format = config.output_format;
+    let target_sample_rate = config.target_sample_rate;
+    let writer_stats = Arc::clone(&stats);
+    let (sink, _output_guard) = open_output(&config.output_target)?;
+    let encoded = config.opus_bitrate.is_some();
+    #[cfg(feature = "opus")]
+    let mut opus = match config.opus_bitrate {
+        Some(bitrate) => Some(OpusFraming {
+            stream: OpusStream::new(target_sample_rate, bitrate)?,
+            sequence: 0,
+            next_capture_ms: 0.0,
+            wall_origin_ms: 0.0,
+        }),
+        None => None,
+    };
+    let mut coalescer = config
+        .chunk_ms
+        .map(|chunk_ms| ChunkCoalescer::new(target_sample_rate, chunk_ms));
+    let writer_thread = thread::spawn(move || {
+        let mut writer: Box<dyn Write> = match sink {
+            OutputSink::Stdout => {
+                Box::new(BufWriter::with_capacity(64 * 1024, io::stdout().lock()))
             }
-
-            bytes.clear();
-            bytes.reserve(block.len() * 2);
-            for sample in block {
-                bytes.extend_from_slice(&sample.to_le_bytes());
+            OutputSink::FanOut(fan_out) => Box::new(fan_out),
+        };
+        let mut bytes = Vec::<u8>::with_capacity(64 * 1024);
+        let mut ready = Vec::<AudioChunk>::new();
+        let mut open = true;
+
+        'writer: while open {
+            match coalescer.as_mut() {
+                Some(coalescer) => match rx.recv_timeout(coalescer.flush_after()) {
+                    Ok(chunk) => coalescer.push(chunk, &mut ready),
+                    Err(mpsc::RecvTimeoutError::Timeout) => coalescer.flush(&mut ready),
+                    Err(mpsc::RecvTimeoutError::Disconnected) => {
+                        coalescer.flush(&mut ready);
+                        open = false;
+                    }
+                },
+                None => match rx.recv() {
+                    Ok(chunk) => ready.push(chunk),
+                    Err(_) => open = false,
+                },
             }
 
-            if writer.write_all(&bytes).is_err() {
-                break;
+            for chunk in ready.drain(..) {
+                if chunk.samples.is_empty() {
+                    continue;
+                }
+
+                bytes.clear();
+                #[cfg(feature = "opus")]
+                if let Some(opus) = opus.as_mut() {
+                    if let Err(error) = opus.encode(Some(&chunk), target_sample_rate, &mut bytes) {
+                        events::error("encode", error);
+                        break 'writer;
+                    }
+                }
+                if !encoded {
+                    if framed {
+                        write_frame_header(
+                            FrameHeader {
+                                sequence: chunk.sequence,
+                                capture_ms: chunk.capture_ms,
+                                wall_ms: chunk.wall_ms,
+                                samples: chunk.samples.len(),
+                                format: output_format.name(),
+                                audio_len: chunk.samples.len() * output_format.bytes_per_sample(),
+                            },
+                            target_sample_rate,
+                            &mut bytes,
+                        );
+                    }
+                    output_format.encode(&chunk.samples, &mut bytes);
+                }
+
+                if writer.write_all(&bytes).is_err() {
+                    break 'writer;
+                }
+
+                if writer.flush().is_err() {
+                    break 'writer;
+                }
+                writer_stats
+                    .frames_written
+                    .fetch_add(chunk.samples.len() as u64, Ordering::Relaxed);
             }
+        }
 
-            if writer.flush().is_err() {
-                break;
+        #[cfg(feature = "opus")]
+        if let Some(opus) = opus.as_mut() {
+            bytes.clear();
+            if opus.encode(None, target_sample_rate, &mut bytes).is_ok() {
+                let _ = writer.write_all(&bytes).and_then(|_| writer.flush());
             }
         }
     });
 
-    let error_callback = |error| {
-        eprintln!("stream-error: {error}");
+    let (events_tx, events_rx) = mpsc::channel::<LoopEvent>();
+    let shutdown_tx = events_tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = shutdown_tx.send(LoopEvent::Shutdown);
+    })
+    .map_err(|e| format!("failed to install signal handler: {e}"))?;
+
+    let primary = open_primary_stream(&primary_device, &config, &pipeline, &tx, &events_tx)?;
+
+    // The system side of `--source both` is best effort: if it drops, the mic
+    // keeps streaming on its own.
+    let system_stream = match config.source {
+        CaptureSource::Both => {
+            let mix_pipeline = Arc::clone(&pipeline);
+            let system = CaptureStream::open(
+                &select_system_device(&host, None)?,
+                config.buffer,
+                &ChannelMap::Mix,
+                |notice| report_notice(notice, None),
+                move |mono, _| {
+                    if let Some(Ok(mut mix)) = mix_pipeline.system_mix.as_ref().map(Mutex::lock) {
+                        mix.push(mono);
+                    }
+                },
+            )?;
+            if let Some(Ok(mut mix)) = pipeline.system_mix.as_ref().map(Mutex::lock) {
+                mix.resampler =
+                    LinearResampler::new(system.input_sample_rate, config.target_sample_rate);
+            }
+            Some(system.play()?)
+        }
+        CaptureSource::Mic | CaptureSource::System => None,
     };
 
-    let stream = match default_cfg.sample_format() {
-        SampleFormat::F32 => {
-            let tx = tx.clone();
-            let resampler = Arc::clone(&resampler);
-            let dc_blocker = Arc::clone(&dc_blocker);
-            let vad_gate = Arc::clone(&vad_gate);
-            device
-                .build_input_stream(
-                    &stream_config,
-                    move |data: &[f32], _| {
-                        process_input_block(
-                            data,
-                            channels,
-                            |v| v,
-                            &resampler,
-                            &dc_blocker,
-                            &vad_gate,
-                            &tx,
-                        );
-                    },
-                    error_callback,
-                    None,
-                )
-                .map_err(|e| format!("failed to build input stream: {e}"))?
-        }
-        SampleFormat::I16 => {
-            let tx = tx.clone();
-            let resampler = Arc::clone(&resampler);
-            let dc_blocker = Arc::clone(&dc_blocker);
-            let vad_gate = Arc::clone(&vad_gate);
-            device
-                .build_input_stream(
-                    &stream_config,
-                    move |data: &[i16], _| {
-                        process_input_block(
-                            data,
-                            channels,
-                            |v| v as f32 / i16::MAX as f32,
-                            &resampler,
-                            &dc_blocker,
-                            &vad_gate,
-                            &tx,
-                        );
+    let host_name = host.id().name().to_lowercase();
+    let vad = if config.vad_enabled { "on" } else { "off" };
+    let wake = if config.wake_word.is_some() {
+        "listening"
+    } else {
+        "off"
+    };
+    let chunk_ms = config
+        .chunk_ms
+        .map_or_else(|| "driver".to_string(), |ms| ms.to_string());
+    let output = if config.framed { "framed" } else { "raw" };
+    let format = if config.opus_bitrate.is_some() {
+        "opus"
+    } else {
+        config.output_format.name()
+    };
+    events::emit(
+        "ready",
+        json!({
+            "inputSampleRate": primary.input_sample_rate,
+            "targetSampleRate": config.target_sample_rate,
+            "device": primary.device_name,
+            "channels": primary.channels,
+            "channelMap": config.channel_map.name(),
+            "host": host_name,
+            "source": config.source.name(),
+            "vad": config.vad_enabled,
+            "vadMode": vad_mode_name(&config.vad_mode),
+            "vadFrameMs": config.vad_frame_ms,
+            "wake": config.wake_word.is_some(),
+            "chunkMs": config.chunk_ms,
+            "output": output,
+            "format": format,
+            "target": config.output_target.name(),
+        }),
+        || {
+            format!(
+                "READY input_sample_rate={} target_sample_rate={} channels={} channel_map={} host={host_name} source={} vad={vad} vad_mode={} vad_frame_ms={} wake={wake} chunk_ms={chunk_ms} output={output} format={format} target={}",
+                primary.input_sample_rate,
+                config.target_sample_rate,
+                primary.channels,
+                config.channel_map.name(),
+                config.source.name(),
+                vad_mode_name(&config.vad_mode),
+                config.vad_frame_ms,
+                config.output_target.name()
+            )
+        },
+    );
+    spawn_stdin_listener(controls);
+    if config.stats_interval_ms > 0 {
+        spawn_stats_reporter(
+            Arc::clone(&stats),
+            config.target_sample_rate,
+            Duration::from_millis(config.stats_interval_ms),
+        );
+    }
+    let mut active = Some(primary);
+
+    let mut restarts = 0_u64;
+    loop {
+        let event = match events_rx.recv_timeout(WATCHDOG_POLL_INTERVAL) {
+            Ok(event) => Some(event),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+
+        match event {
+            Some(LoopEvent::Shutdown) => break,
+            // When a device disappears (e.g. a Bluetooth headset drops) fall
+            // back to whatever the system default input (or system audio
+            // device) is now rather than going quiet.
+            Some(LoopEvent::DeviceLost) => {
+                drop(active.take());
+                let Some(reopened) =
+                    reopen_primary_stream(&config, &pipeline, &tx, &events_tx, &events_rx, false)
+                else {
+                    break;
+                };
+                events::emit(
+                    "deviceChanged",
+                    json!({
+                        "name": reopened.device_name,
+                        "inputSampleRate": reopened.input_sample_rate,
+                        "channels": reopened.channels,
+                    }),
+                    || {
+                        format!(
+                            "DEVICE_CHANGED name={:?} input_sample_rate={} channels={}",
+                            reopened.device_name, reopened.input_sample_rate, reopened.channels
+                        )
                     },
-                    error_callback,
-                    None,
-                )
-                .map_err(|e| format!("failed to build input stream: {e}"))?
-        }
-        SampleFormat::U16 => {
-            let tx = tx.clone();
-            let resampler = Arc::clone(&resampler);
-            let dc_blocker = Arc::clone(&dc_blocker);
-            let vad_gate = Arc::clone(&vad_gate);
-            device
-                .build_input_stream(
-                    &stream_config,
-                    move |data: &[u16], _| {
-                        process_input_block(
-                            data,
-                            channels,
-                            |v| (v as f32 / u16::MAX as f32) * 2.0 - 1.0,
-                            &resampler,
-                            &dc_blocker,
-                            &vad_gate,
-                            &tx,
-                        );
+                );
+                active = Some(reopened);
+            }
+            // Some backends (CoreAudio after sleep, WASAPI on default-device
+            // churn) leave a stream "playing" that never calls back again.
+            None => {
+                let stalled_ms = pipeline.ms_since_last_callback();
+                if config.stall_timeout_ms == 0 || stalled_ms < config.stall_timeout_ms {
+                    continue;
+                }
+
+                // Audio is missing from the last callback until the new stream runs.
+                let gap_started = Instant::now() - Duration::from_millis(stalled_ms);
+                drop(active.take());
+                let Some(reopened) =
+                    reopen_primary_stream(&config, &pipeline, &tx, &events_tx, &events_rx, true)
+                else {
+                    break;
+                };
+                restarts += 1;
+                let dropped_ms = gap_started.elapsed().as_millis() as u64;
+                events::emit(
+                    "recovered",
+                    json!({
+                        "reason": "stall",
+                        "name": reopened.device_name,
+                        "stalledMs": stalled_ms,
+                        "droppedMs": dropped_ms,
+                        "restarts": restarts,
+                    }),
+                    || {
+                        format!(
+                            "RECOVERED reason=stall name={:?} stalled_ms={stalled_ms} dropped_ms={dropped_ms} restarts={restarts}",
+                            reopened.device_name
+                        )
                     },
-                    error_callback,
-                    None,
-                )
-                .map_err(|e| format!("failed to build input stream: {e}"))?
+                );
+                active = Some(reopened);
+            }
         }
-        unsupported => {
-            return Err(format!("unsupported sample format: {unsupported:?}"));
+    }
+
+    // SIGINT/SIGTERM: stop capturing, push out whatever the gate is still
+    // holding, then let the writer and recorder drain before exiting 0 so the
+    // end of the last utterance isn't lost.
+    drop(active);
+    drop(system_stream);
+    let mut tail = Vec::new();
+    if let Some(Ok(mut gate)) = pipeline.vad_gate.as_ref().map(Mutex::lock) {
+        gate.flush(&mut tail);
+        let capture_ms = f64::from_bits(pipeline.last_capture_ms_bits.load(Ordering::Relaxed));
+        report_speech_events(&mut gate.events, capture_ms);
+    }
+    if !tail.is_empty() {
+        send_chunk(
+            &pipeline,
+            &tx,
+            f64::from_bits(pipeline.last_capture_ms_bits.load(Ordering::Relaxed)),
+            tail,
+        );
+    }
+
+    drop(tx);
+    drop(pipeline);
+    let _ = writer_thread.join();
+    if let Some(handle) = recorder_thread {
+        let _ = handle.join();
+    }
+    if config.stats_interval_ms > 0 {
+        eprintln!("{}", stats.snapshot(config.target_sample_rate));
+    }
+
+    Ok(())
+}
+
+// Keeps retrying until a stream opens; None means a shutdown arrived first.
+// `keep_selection` re-resolves the configured device (stall restarts) instead
+// of falling back to the system default (device loss).
+fn reopen_primary_stream(
+    config: &Config,
+    pipeline: &Arc<InputPipeline>,
+    tx: &mpsc::Sender<AudioChunk>,
+    events_tx: &mpsc::Sender<LoopEvent>,
+    events_rx: &mpsc::Receiver<LoopEvent>,
+    keep_selection: bool,
+) -> Option<CaptureStream> {
+    let mut shutdown = false;
+    while let Ok(event) = events_rx.try_recv() {
+        shutdown |= matches!(event, LoopEvent::Shutdown);
+    }
+    if let Some(Ok(mut gate)) = pipeline.vad_gate.as_ref().map(Mutex::lock) {
+        gate.reset();
+    }
+
+    while !shutdown {
+        let device =
+            config
+                .audio_host
+                .open()
+                .and_then(|host| match (config.source, keep_selection) {
+                    (_, true) => select_primary_device(&host, config),
+                    (CaptureSource::System, false) => select_system_device(&host, None),
+                    (CaptureSource::Mic | CaptureSource::Both, false) => {
+                        select_input_device(&host, config.audio_host.route_device)
+                    }
+                });
+        match device
+            .and_then(|device| open_primary_stream(&device, config, pipeline, tx, events_tx))
+        {
+            Ok(active) => return Some(active),
+            Err(error) => {
+                events::error("device", error);
+                shutdown = matches!(
+                    events_rx.recv_timeout(DEVICE_RETRY_INTERVAL),
+                    Ok(LoopEvent::Shutdown)
+                );
+            }
         }
+    }
+    None
+}
+
+// Gate transitions go to stderr next to READY so the host can start and
+// finish ASR streams off them.
+fn report_speech_events(events: &mut Vec<SpeechEvent>, capture_ms: f64) {
+    for event in events.drain(..) {
+        let (event, tag) = match event {
+            SpeechEvent::Start => ("speechStart", "SPEECH_START"),
+            SpeechEvent::End => ("speechEnd", "SPEECH_END"),
+        };
+        events::emit(
+            event,
+            json!({ "captureMs": events::tenths(capture_ms) }),
+            || format!("{tag} capture_ms={capture_ms:.1}"),
+        );
+    }
+}
+
+// Same layout as the worker request frames: the JSON header, then the PCM
+// payload in `format`.
+struct FrameHeader<'a> {
+    sequence: u64,
+    capture_ms: f64,
+    wall_ms: f64,
+    samples: usize,
+    format: &'a str,
+    audio_len: usize,
+}
+
+fn write_frame_header(header: FrameHeader, sample_rate: u32, out: &mut Vec<u8>) {
+    let json = json!({
+        "seq": header.sequence,
+        "captureMs": (header.capture_ms * 1000.0).round() / 1000.0,
+        "wallMs": (header.wall_ms * 1000.0).round() / 1000.0,
+        "samples": header.samples,
+        "sampleRate": sample_rate,
+        "format": header.format
+    })
+    .to_string();
+    out.extend_from_slice(&frame_header(json.len(), header.audio_len));
+    out.extend_from_slice(json.as_bytes());
+}
+
+// One frame per 20 ms packet. Packets get their own sequence numbers, and
+// captureMs advances by the frame length from the chunk that started them.
+#[cfg(feature = "opus")]
+struct OpusFraming {
+    stream: OpusStream,
+    sequence: u64,
+    next_capture_ms: f64,
+    wall_origin_ms: f64,
+}
+
+#[cfg(feature = "opus")]
+impl OpusFraming {
+    // `None` flushes the final partial frame.
+    fn encode(
+        &mut self,
+        chunk: Option<&AudioChunk>,
+        sample_rate: u32,
+        out: &mut Vec<u8>,
+    ) -> Result<(), String> {
+        let frame_samples = self.stream.frame_samples();
+        let frame_ms = self.stream.frame_ms();
+        if let Some(chunk) = chunk {
+            if self.stream.pending_samples() == 0 {
+                self.next_capture_ms = chunk.capture_ms;
+            }
+            self.wall_origin_ms = chunk.wall_ms - chunk.capture_ms;
+        }
+        let wall_origin_ms = self.wall_origin_ms;
+        let sequence = &mut self.sequence;
+        let next_capture_ms = &mut self.next_capture_ms;
+        let mut on_packet = |packet: &[u8]| {
+            write_frame_header(
+                FrameHeader {
+                    sequence: *sequence,
+                    capture_ms: *next_capture_ms,
+                    wall_ms: wall_origin_ms + *next_capture_ms,
+                    samples: frame_samples,
+                    format: "opus",
+                    audio_len: packet.len(),
+                },
+                sample_rate,
+                out,
+            );
+            out.extend_from_slice(packet);
+            *sequence += 1;
+            *next_capture_ms += frame_ms;
+        };
+        match chunk {
+            Some(chunk) => self.stream.push(&chunk.samples, &mut on_packet),
+            None => self.stream.finish(&mut on_packet),
+        }
+    }
+}
+
+// captureMs is the capture time of the input block that released the chunk,
+// relative to the first block of the stream.
+fn process_input_block(
+    mono: &[f32],
+    capture: StreamInstant,
+    pipeline: &InputPipeline,
+    tx: &mpsc::Sender<AudioChunk>,
+) {
+    let capture_ms = match pipeline.stream_origin.lock() {
+        Ok(mut origin) => {
+            let origin = *origin.get_or_insert(capture);
+            capture
+                .duration_since(&origin)
+                .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+                .unwrap_or(0.0)
+        }
+        Err(_) => 0.0,
     };
+    // The wall clock is read once and then advanced by the device clock, so
+    // NTP steps mid-session don't make timestamps jump or run backwards.
+    if let Ok(mut wall_origin) = pipeline.wall_origin_ms.lock() {
+        wall_origin.get_or_insert_with(|| unix_epoch_ms() - capture_ms);
+    }
+    pipeline
+        .last_capture_ms_bits
+        .store(capture_ms.to_bits(), Ordering::Relaxed);
+    let stats = &pipeline.stats;
+    let input_rate = stats.input_sample_rate.load(Ordering::Relaxed).max(1) as u64;
+    let block_ms = mono.len() as u64 * 1000 / input_rate;
+    let since_last_ms = pipeline.ms_since_last_callback();
+    if since_last_ms > block_ms * 2 + CALLBACK_GAP_SLACK_MS {
+        stats.record_gap(since_last_ms);
+    }
+    stats
+        .frames_captured
+        .fetch_add(mono.len() as u64, Ordering::Relaxed);
+    pipeline.mark_callback();
+    let controls = &pipeline.controls;
+    if controls.take_reset_request() {
+        if let Some(Ok(mut gate)) = pipeline.vad_gate.as_ref().map(Mutex::lock) {
+            gate.reset();
+            report_speech_events(&mut gate.events, capture_ms);
+        }
+    }
+    let paused = controls.is_paused();
+    if paused && pipeline.pause_ring.is_none() {
+        return;
+    }
+    // Judged on the raw input, before gain, so it reflects the device itself.
+    if !paused {
+        if let Ok(mut monitor) = pipeline.signal_monitor.lock() {
+            monitor.process(mono);
+        }
+    }
 
-    stream
-        .play()
-        .map_err(|e| format!("failed to start input stream: {e}"))?;
-
-    eprintln!(
-        "READY input_sample_rate={} target_sample_rate={} channels={} vad_mode={} vad_frame_ms={}",
-        input_sample_rate,
-        config.target_sample_rate,
-        channels,
-        vad_mode_name(&config.vad_mode),
-        config.vad_frame_ms
-    );
+    let mut filtered = Vec::<f32>::with_capacity(mono.len());
+    let mut out = Vec::<f32>::with_capacity(mono.len());
+    let mut gated = Vec::<f32>::with_capacity(mono.len());
 
-    loop {
-        thread::sleep(Duration::from_secs(60));
-    }
-}
-
-fn process_input_block<T, F>(
-    data: &[T],
-    channels: usize,
-    to_f32: F,
-    resampler: &Arc<Mutex<LinearResampler>>,
-    dc_blocker: &Arc<Mutex<DcBlocker>>,
-    vad_gate: &Arc<Mutex<NativeVadGate>>,
-    tx: &mpsc::Sender<Vec<i16>>,
-) where
-    F: Fn(T) -> f32,
-    T: Copy,
-{
-    let mut mono = Vec::<f32>::with_capacity(data.len() / channels.max(1));
-    let mut filtered = Vec::<f32>::with_capacity(mono.capacity());
-    let mut out = Vec::<f32>::with_capacity(mono.capacity());
-    let mut pcm = Vec::<i16>::with_capacity(mono.capacity());
-    let mut gated = Vec::<i16>::with_capacity(mono.capacity());
-
-    to_mono_f32(data, channels, to_f32, &mut mono);
-    if let Ok(mut blocker) = dc_blocker.lock() {
-        blocker.process(&mono, &mut filtered);
+    if let Ok(mut blocker) = pipeline.dc_blocker.lock() {
+        blocker.process(mono, &mut filtered);
     } else {
-        filtered.extend_from_slice(&mono);
+        filtered.extend_from_slice(mono);
+    }
+    if let Some(Ok(mut highpass)) = pipeline.highpass.as_ref().map(Mutex::lock) {
+        highpass.process(&mut filtered);
+    }
+    if let Some(Ok(mut denoiser)) = pipeline.denoiser.as_ref().map(Mutex::lock) {
+        let mut denoised = Vec::<f32>::with_capacity(filtered.len() * 2);
+        denoiser.process(&filtered, &mut denoised);
+        filtered = denoised;
     }
-    if let Ok(mut rs) = resampler.lock() {
+    if let Ok(mut rs) = pipeline.resampler.lock() {
         rs.process(&filtered, &mut out);
+        stats.record_carry(rs.carry_len());
+    }
+    if paused {
+        if let Some(Ok(mut ring)) = pipeline.pause_ring.as_ref().map(Mutex::lock) {
+            ring.push(&out);
+        }
+        return;
     }
     if out.is_empty() {
         return;
     }
-    f32_to_i16(&out, &mut pcm);
-    if let Ok(mut gate) = vad_gate.lock() {
-        gate.process_block(&pcm, &mut gated);
+    if let Some(Ok(mut mix)) = pipeline.system_mix.as_ref().map(Mutex::lock) {
+        mix.mix_into(&mut out);
+    }
+    // The first block after a resume carries the held pre-roll in front of
+    // it, with its timestamp moved back to match.
+    let mut capture_ms = capture_ms;
+    if let Some(Ok(mut ring)) = pipeline.pause_ring.as_ref().map(Mutex::lock) {
+        let (mut held, held_ms) = ring.take();
+        if !held.is_empty() {
+            held.extend_from_slice(&out);
+            out = held;
+            capture_ms = (capture_ms - held_ms).max(0.0);
+        }
+    }
+    // Muting keeps the stream flowing as silence so timing stays continuous.
+    if controls.is_muted() {
+        out.fill(0.0);
+    } else {
+        let gain = controls.gain();
+        if gain != 1.0 {
+            out.iter_mut().for_each(|sample| *sample *= gain);
+        }
+        if let Some(Ok(mut auto_gain)) = pipeline.auto_gain.as_ref().map(Mutex::lock) {
+            auto_gain.process(&mut out);
+        }
+    }
+    if let Some(Ok(mut meter)) = pipeline.meter.as_ref().map(Mutex::lock) {
+        meter.process(&out, capture_ms);
+    }
+    // Until the wake word fires the audio only goes to the detector.
+    if controls.is_listening() {
+        if let Some(wake) = &pipeline.wake {
+            let _ = wake.send((capture_ms, out));
+        }
+        return;
+    }
+    // The recording keeps the whole session, silence included, so it can be
+    // re-transcribed later independently of the gate.
+    if let Some(recording) = &pipeline.recording {
+        let mut pcm = Vec::<i16>::with_capacity(out.len());
+        f32_to_i16(&out, &mut pcm);
+        let _ = recording.send(pcm);
+    }
+    match pipeline.vad_gate.as_ref().map(Mutex::lock) {
+        Some(Ok(mut gate)) => {
+            gate.process_block(&out, &mut gated);
+            let ended = gate
+                .events
+                .iter()
+                .any(|event| matches!(event, SpeechEvent::End));
+            report_speech_events(&mut gate.events, capture_ms);
+            // Hands-free: one utterance per wake word.
+            if ended && pipeline.wake.is_some() {
+                controls.set_listening(true);
+            }
+        }
+        Some(Err(_)) => {}
+        None => gated = out,
     }
-    if gated.is_empty() {
+    // The external gate sits after the native one so both can be combined.
+    if gated.is_empty() || !controls.is_gate_open() {
         return;
     }
-    let _ = tx.send(gated);
+
+    send_chunk(pipeline, tx, capture_ms, gated);
+}
+
+// A failed send means the writer has gone (stdout closed); count it so the
+// stats line shows audio was lost rather than never captured.
+fn send_chunk(
+    pipeline: &InputPipeline,
+    tx: &mpsc::Sender<AudioChunk>,
+    capture_ms: f64,
+    samples: Vec<f32>,
+) {
+    let frames = samples.len() as u64;
+    let wall_origin_ms = pipeline
+        .wall_origin_ms
+        .lock()
+        .ok()
+        .and_then(|origin| *origin)
+        .unwrap_or(0.0);
+    let chunk = AudioChunk {
+        sequence: pipeline.sequence.fetch_add(1, Ordering::Relaxed),
+        capture_ms,
+        wall_ms: wall_origin_ms + capture_ms,
+        samples,
+    };
+    match tx.send(chunk) {
+        Ok(()) => {
+            pipeline
+                .stats
+                .frames_emitted
+                .fetch_add(frames, Ordering::Relaxed);
+        }
+        Err(_) => {
+            pipeline.stats.send_failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+fn unix_epoch_ms() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
 }
 
 fn main() {
     if let Err(error) = run() {
-        eprintln!("{error}");
+        events::fatal(error);
         std::process::exit(1);
     }
 }
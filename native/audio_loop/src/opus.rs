@@ -0,0 +1,134 @@
+use std::os::raw::{c_int, c_uchar};
+
+// Minimal binding to the system libopus; only the encoder calls we use.
+#[repr(C)]
+struct OpusEncoder {
+    _private: [u8; 0],
+}
+
+#[link(name = "opus")]
+extern "C" {
+    fn opus_encoder_create(
+        sample_rate: i32,
+        channels: c_int,
+        application: c_int,
+        error: *mut c_int,
+    ) -> *mut OpusEncoder;
+    fn opus_encoder_ctl(encoder: *mut OpusEncoder, request: c_int, ...) -> c_int;
+    fn opus_encode_float(
+        encoder: *mut OpusEncoder,
+        pcm: *const f32,
+        frame_size: c_int,
+        data: *mut c_uchar,
+        max_data_bytes: i32,
+    ) -> i32;
+    fn opus_encoder_destroy(encoder: *mut OpusEncoder);
+}
+
+const OPUS_APPLICATION_VOIP: c_int = 2048;
+const OPUS_SET_BITRATE_REQUEST: c_int = 4002;
+const FRAME_MS: usize = 20;
+// Recommended ceiling for a single packet.
+const MAX_PACKET_BYTES: usize = 1275;
+
+pub const SUPPORTED_RATES: [u32; 5] = [8_000, 12_000, 16_000, 24_000, 48_000];
+
+// Mono VoIP encoder fed arbitrary-sized chunks; emits one packet per 20 ms
+// frame and keeps the remainder for the next chunk.
+pub struct OpusStream {
+    encoder: *mut OpusEncoder,
+    frame_samples: usize,
+    pending: Vec<f32>,
+    packet: Vec<u8>,
+}
+
+// The encoder is only ever touched from the writer thread that owns it.
+unsafe impl Send for OpusStream {}
+
+impl OpusStream {
+    pub fn new(sample_rate: u32, bitrate: u32) -> Result<Self, String> {
+        if !SUPPORTED_RATES.contains(&sample_rate) {
+            return Err(format!(
+                "Opus cannot encode at {sample_rate} Hz; use one of {SUPPORTED_RATES:?}"
+            ));
+        }
+
+        let mut error: c_int = 0;
+        let encoder = unsafe {
+            opus_encoder_create(sample_rate as i32, 1, OPUS_APPLICATION_VOIP, &mut error)
+        };
+        if encoder.is_null() || error != 0 {
+            return Err(format!("failed to create Opus encoder (error {error})"));
+        }
+        let status = unsafe { opus_encoder_ctl(encoder, OPUS_SET_BITRATE_REQUEST, bitrate as i32) };
+        if status != 0 {
+            unsafe { opus_encoder_destroy(encoder) };
+            return Err(format!("Opus rejected bitrate {bitrate} (error {status})"));
+        }
+
+        let frame_samples = sample_rate as usize * FRAME_MS / 1000;
+        Ok(Self {
+            encoder,
+            frame_samples,
+            pending: Vec::with_capacity(frame_samples * 2),
+            packet: vec![0; MAX_PACKET_BYTES],
+        })
+    }
+
+    pub fn frame_samples(&self) -> usize {
+        self.frame_samples
+    }
+
+    pub fn frame_ms(&self) -> f64 {
+        FRAME_MS as f64
+    }
+
+    pub fn pending_samples(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn push<F>(&mut self, samples: &[f32], mut on_packet: F) -> Result<(), String>
+    where
+        F: FnMut(&[u8]),
+    {
+        self.pending.extend_from_slice(samples);
+        let mut offset = 0;
+        while self.pending.len() - offset >= self.frame_samples {
+            let frame = &self.pending[offset..offset + self.frame_samples];
+            let written = unsafe {
+                opus_encode_float(
+                    self.encoder,
+                    frame.as_ptr(),
+                    self.frame_samples as c_int,
+                    self.packet.as_mut_ptr(),
+                    self.packet.len() as i32,
+                )
+            };
+            if written < 0 {
+                return Err(format!("Opus encode failed (error {written})"));
+            }
+            on_packet(&self.packet[..written as usize]);
+            offset += self.frame_samples;
+        }
+        self.pending.drain(..offset);
+        Ok(())
+    }
+
+    // Pads the last partial frame with silence so nothing is left behind.
+    pub fn finish<F>(&mut self, on_packet: F) -> Result<(), String>
+    where
+        F: FnMut(&[u8]),
+    {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let padding = self.frame_samples - self.pending.len();
+        self.push(&vec![0.0; padding], on_packet)
+    }
+}
+
+impl Drop for OpusStream {
+    fn drop(&mut self) {
+        unsafe { opus_encoder_destroy(self.encoder) };
+    }
+}
@@ -0,0 +1,227 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::events;
+
+// Where the PCM stream goes. Socket and pipe targets let the capture process
+// outlive a restarting host and serve more than one reader at a time.
+pub enum OutputTarget {
+    Stdout,
+    Unix(PathBuf),
+    Pipe(String),
+}
+
+impl OutputTarget {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value == "stdout" || value == "-" {
+            return Ok(Self::Stdout);
+        }
+        if let Some(path) = value.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("Missing socket path in --output unix:<path>".into());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+        if let Some(name) = value.strip_prefix("pipe:") {
+            if name.is_empty() {
+                return Err("Missing pipe name in --output pipe:<name>".into());
+            }
+            // Accept a bare name as shorthand for the local pipe namespace.
+            return Ok(Self::Pipe(if name.starts_with(r"\\") {
+                name.to_string()
+            } else {
+                format!(r"\\.\pipe\{name}")
+            }));
+        }
+        Err("Invalid --output value (expected stdout, unix:<path> or pipe:<name>)".into())
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Self::Stdout => "stdout".into(),
+            Self::Unix(path) => format!("unix:{}", path.display()),
+            Self::Pipe(name) => format!("pipe:{name}"),
+        }
+    }
+}
+
+pub enum OutputSink {
+    Stdout,
+    FanOut(FanOut),
+}
+
+// Copies every write to all connected readers. A reader that errors or
+// stalls is dropped; with nobody connected the audio is simply discarded.
+// Each chunk is written in one call, so a reader that joins mid-stream
+// always starts on a chunk boundary.
+#[derive(Clone, Default)]
+pub struct FanOut {
+    clients: Arc<Mutex<Vec<Box<dyn Write + Send>>>>,
+}
+
+impl FanOut {
+    fn add(&self, client: Box<dyn Write + Send>) {
+        if let Ok(mut clients) = self.clients.lock() {
+            clients.push(client);
+            report_clients("connected", clients.len());
+        }
+    }
+}
+
+impl Write for FanOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Ok(mut clients) = self.clients.lock() {
+            let before = clients.len();
+            clients.retain_mut(|client| client.write_all(buf).and_then(|_| client.flush()).is_ok());
+            if clients.len() < before {
+                report_clients("disconnected", clients.len());
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn report_clients(change: &str, clients: usize) {
+    events::emit(
+        "outputClient",
+        serde_json::json!({ "change": change, "clients": clients }),
+        || format!("OUTPUT_CLIENT event={change} clients={clients}"),
+    );
+}
+
+// Removes the socket file on shutdown so the next run can bind again.
+pub struct OutputGuard {
+    socket_path: Option<PathBuf>,
+}
+
+impl Drop for OutputGuard {
+    fn drop(&mut self) {
+        if let Some(path) = &self.socket_path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+pub fn open_output(target: &OutputTarget) -> Result<(OutputSink, OutputGuard), String> {
+    match target {
+        OutputTarget::Stdout => Ok((OutputSink::Stdout, OutputGuard { socket_path: None })),
+        OutputTarget::Unix(path) => {
+            let fan_out = listen_unix(path, FanOut::default())?;
+            Ok((
+                OutputSink::FanOut(fan_out),
+                OutputGuard {
+                    socket_path: Some(path.clone()),
+                },
+            ))
+        }
+        OutputTarget::Pipe(name) => {
+            let fan_out = listen_pipe(name, FanOut::default())?;
+            Ok((
+                OutputSink::FanOut(fan_out),
+                OutputGuard { socket_path: None },
+            ))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn listen_unix(path: &std::path::Path, fan_out: FanOut) -> Result<FanOut, String> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+    use std::time::Duration;
+
+    // A socket left behind by a crashed run would block the bind; anything
+    // that isn't a socket is left alone.
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()));
+        }
+        let _ = std::fs::remove_file(path);
+    }
+    let listener =
+        UnixListener::bind(path).map_err(|e| format!("failed to bind {}: {e}", path.display()))?;
+
+    let accepted = fan_out.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+                    accepted.add(Box::new(stream));
+                }
+                Err(e) => events::error("output", format!("accept failed: {e}")),
+            }
+        }
+    });
+    Ok(fan_out)
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: &std::path::Path, _fan_out: FanOut) -> Result<FanOut, String> {
+    Err("--output unix:<path> is not supported on this platform; use pipe:<name>".into())
+}
+
+#[cfg(windows)]
+fn listen_pipe(name: &str, fan_out: FanOut) -> Result<FanOut, String> {
+    use std::fs::File;
+    use std::os::windows::io::FromRawHandle;
+    use windows_sys::Win32::Foundation::{
+        GetLastError, ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE,
+    };
+    use windows_sys::Win32::Storage::FileSystem::PIPE_ACCESS_OUTBOUND;
+    use windows_sys::Win32::System::Pipes::{
+        ConnectNamedPipe, CreateNamedPipeW, PIPE_REJECT_REMOTE_CLIENTS, PIPE_TYPE_BYTE,
+        PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+    };
+
+    // Handles are carried as isize so the accept thread can own them.
+    let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+    let create = move || unsafe {
+        let handle = CreateNamedPipeW(
+            wide.as_ptr(),
+            PIPE_ACCESS_OUTBOUND,
+            PIPE_TYPE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            64 * 1024,
+            0,
+            0,
+            std::ptr::null(),
+        );
+        (handle != INVALID_HANDLE_VALUE).then_some(handle as isize)
+    };
+
+    // The first instance is created up front so a bad name fails at startup.
+    let mut next = create().ok_or_else(|| format!("failed to create pipe {name}"))?;
+    let accepted = fan_out.clone();
+    let name = name.to_string();
+    thread::spawn(move || loop {
+        let connected = unsafe {
+            ConnectNamedPipe(next as _, std::ptr::null_mut()) != 0
+                || GetLastError() == ERROR_PIPE_CONNECTED
+        };
+        // Either way the File owns the handle and closes it when dropped.
+        let pipe = unsafe { File::from_raw_handle(next as _) };
+        if connected {
+            accepted.add(Box::new(pipe));
+        }
+        match create() {
+            Some(handle) => next = handle,
+            None => {
+                events::error("output", format!("failed to create pipe {name}"));
+                return;
+            }
+        }
+    });
+    Ok(fan_out)
+}
+
+#[cfg(not(windows))]
+fn listen_pipe(_name: &str, _fan_out: FanOut) -> Result<FanOut, String> {
+    Err("--output pipe:<name> is only supported on Windows; use unix:<path>".into())
+}
@@ -0,0 +1,55 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+
+use crate::events;
+
+// The recorder is normally stopped with SIGINT, so the WAV header is
+// rewritten about once a second; a killed session still leaves a playable file
+// missing at most the last second.
+// Dropping the sender finalizes the file; join the handle to wait for that.
+pub fn spawn_wav_recorder(
+    path: &Path,
+    sample_rate: u32,
+) -> Result<(mpsc::Sender<Vec<i16>>, JoinHandle<()>), String> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut writer: WavWriter<BufWriter<File>> = WavWriter::create(path, spec)
+        .map_err(|e| format!("failed to create recording {}: {e}", path.display()))?;
+
+    let (tx, rx) = mpsc::channel::<Vec<i16>>();
+    let flush_every = sample_rate as usize;
+    let handle = thread::spawn(move || {
+        let mut unflushed = 0_usize;
+        while let Ok(block) = rx.recv() {
+            for &sample in &block {
+                if let Err(error) = writer.write_sample(sample) {
+                    events::error("record", error);
+                    return;
+                }
+            }
+
+            unflushed += block.len();
+            if unflushed >= flush_every {
+                if let Err(error) = writer.flush() {
+                    events::error("record", error);
+                    return;
+                }
+                unflushed = 0;
+            }
+        }
+
+        if let Err(error) = writer.finalize() {
+            events::error("record", error);
+        }
+    });
+
+    Ok((tx, handle))
+}
@@ -0,0 +1,260 @@
+use crate::{
+    Config, DcBlocker, HighPassFilter, LinearResampler, OutputFormat, DENOISE_SAMPLE_RATE,
+};
+use dingoflow_audio::convert::f32_to_i16;
+use serde_json::{json, Value};
+use std::f64::consts::TAU;
+
+// Behind `--resample-check`: a logarithmic sine sweep at the device's rate
+// goes through the stages capture audio takes on its way to the output, and
+// the output is compared with what an ideal chain would give. While the tone
+// is below the output Nyquist, each 20 ms window is fitted to the tone and
+// whatever doesn't fit (interpolation error, images, quantization) is the
+// noise in its SNR. Once the tone is above it, the output should be silent,
+// so anything left is aliasing folded into the band the model hears.
+//
+// RNNoise, gain and AGC are left out: they change levels on purpose, and a
+// steady tone is exactly what RNNoise is built to remove. --denoise still
+// counts for the hop through 48 kHz it puts in front of the resampler.
+const SWEEP_SECONDS: f64 = 4.0;
+const SWEEP_START_HZ: f64 = 50.0;
+// Of the input Nyquist.
+const SWEEP_END: f64 = 0.95;
+const AMPLITUDE: f64 = 0.5;
+const BLOCK_MS: usize = 10;
+const WINDOW_MS: usize = 20;
+// The DC blocker and high-pass settle before anything is measured.
+const SETTLE_MS: usize = 250;
+// Right under the output Nyquist the tone is neither clearly in band nor out.
+const BAND_EDGE: f64 = 0.95;
+const SPEECH_BAND_HZ: (f64, f64) = (300.0, 3_400.0);
+const MIN_SPEECH_SNR_DB: f64 = 30.0;
+const MAX_ALIASING_DB: f64 = -30.0;
+const FLOOR_DB: f64 = -120.0;
+
+struct Window {
+    hz: f64,
+    snr_db: f64,
+    gain_db: f64,
+}
+
+pub fn run(input_rate: u32, config: &Config) -> Value {
+    let output_rate = config.target_sample_rate;
+    let end_hz = input_rate as f64 / 2.0 * SWEEP_END;
+    let sweep = Sweep::new(end_hz);
+    let (output, stages) = process(&sweep.render(input_rate), input_rate, config);
+
+    let window_len = (output_rate as usize * WINDOW_MS / 1000).max(1);
+    let settle = output_rate as usize * SETTLE_MS / 1000;
+    let output_nyquist = output_rate as f64 / 2.0;
+    let mut in_band = Vec::new();
+    let mut aliasing: Option<(f64, f64)> = None;
+    for start in (settle..output.len().saturating_sub(window_len)).step_by(window_len) {
+        let window = &output[start..start + window_len];
+        let times = (start..start + window_len).map(|n| n as f64 / output_rate as f64);
+        let hz = sweep.frequency((start as f64 + window_len as f64 / 2.0) / output_rate as f64);
+        if hz < output_nyquist * BAND_EDGE {
+            in_band.push(fit(&sweep, window, times, hz));
+        } else if hz > output_nyquist {
+            let power =
+                window.iter().map(|&y| (y as f64).powi(2)).sum::<f64>() / window.len() as f64;
+            let level = to_db(power / tone_power());
+            if aliasing.is_none_or(|(worst, _)| level > worst) {
+                aliasing = Some((level, hz));
+            }
+        }
+    }
+
+    let mut speech: Vec<&Window> = in_band
+        .iter()
+        .filter(|window| (SPEECH_BAND_HZ.0..=SPEECH_BAND_HZ.1).contains(&window.hz))
+        .collect();
+    speech.sort_by(|a, b| a.snr_db.total_cmp(&b.snr_db));
+    let speech_min_snr = speech.first().map(|window| window.snr_db);
+    let worst_in_band = in_band.iter().min_by(|a, b| a.snr_db.total_cmp(&b.snr_db));
+    let gains = || speech.iter().map(|window| window.gain_db);
+
+    let (verdict, message) = match (speech_min_snr, aliasing) {
+        (None, _) => (
+            "no-band",
+            "the output rate leaves no speech band to measure".to_string(),
+        ),
+        (Some(snr), _) if snr < MIN_SPEECH_SNR_DB => (
+            "noisy",
+            format!(
+                "the chain adds noise to speech-band tones ({snr:.1} dB SNR); \
+                 check the device and output rates"
+            ),
+        ),
+        (_, Some((level, hz))) if level > MAX_ALIASING_DB => (
+            "aliasing",
+            format!(
+                "a {hz:.0} Hz tone folds back into the band the model hears at {level:.1} dB; \
+                 set the device to {output_rate} Hz so capture isn't downsampled"
+            ),
+        ),
+        _ => (
+            "ok",
+            "the capture chain keeps the speech band clean".to_string(),
+        ),
+    };
+
+    json!({
+        "event": "resample-check",
+        "inputRate": input_rate,
+        "outputRate": output_rate,
+        "stages": stages,
+        "sweepHz": [SWEEP_START_HZ, round1(end_hz)],
+        "speechBand": {
+            "minSnrDb": speech_min_snr.map(round1),
+            "medianSnrDb": speech.get(speech.len() / 2).map(|window| round1(window.snr_db)),
+            "minGainDb": gains().reduce(f64::min).map(round1),
+            "maxGainDb": gains().reduce(f64::max).map(round1),
+        },
+        "inBand": worst_in_band.map(|window| json!({
+            "minSnrDb": round1(window.snr_db),
+            "atHz": window.hz.round(),
+        })),
+        "aliasing": aliasing.map(|(level, hz)| json!({
+            "maxDb": round1(level),
+            "atHz": hz.round(),
+        })),
+        "ok": verdict == "ok",
+        "verdict": verdict,
+        "message": message,
+    })
+}
+
+// The same stages, in the same order and block by block, as
+// process_input_block.
+fn process(input: &[f32], input_rate: u32, config: &Config) -> (Vec<f32>, Vec<String>) {
+    let mut stages = vec!["dc-blocker".to_string()];
+    let mut blocker = DcBlocker::new();
+    let mut highpass = config.highpass_hz.map(|hz| {
+        stages.push(format!("highpass-{hz}hz"));
+        let mut filter = HighPassFilter::new(hz);
+        filter.set_sample_rate(input_rate);
+        filter
+    });
+    let mut hop = config.denoise.then(|| {
+        stages.push(format!("resample-{input_rate}-{DENOISE_SAMPLE_RATE}"));
+        LinearResampler::new(input_rate, DENOISE_SAMPLE_RATE)
+    });
+    let resampler_input_rate = if hop.is_some() {
+        DENOISE_SAMPLE_RATE
+    } else {
+        input_rate
+    };
+    stages.push(format!(
+        "resample-{resampler_input_rate}-{}",
+        config.target_sample_rate
+    ));
+    let mut resampler = LinearResampler::new(resampler_input_rate, config.target_sample_rate);
+    let quantize = matches!(config.output_format, OutputFormat::S16le);
+    if quantize {
+        stages.push("s16le".to_string());
+    }
+
+    let block_len = (input_rate as usize * BLOCK_MS / 1000).max(1);
+    let mut output = Vec::with_capacity(input.len());
+    let mut filtered = Vec::with_capacity(block_len);
+    let mut hopped = Vec::with_capacity(block_len * 2);
+    for block in input.chunks(block_len) {
+        filtered.clear();
+        blocker.process(block, &mut filtered);
+        if let Some(highpass) = highpass.as_mut() {
+            highpass.process(&mut filtered);
+        }
+        if let Some(hop) = hop.as_mut() {
+            hopped.clear();
+            hop.process(&filtered, &mut hopped);
+            std::mem::swap(&mut filtered, &mut hopped);
+        }
+        resampler.process(&filtered, &mut output);
+    }
+    if quantize {
+        let mut pcm = Vec::with_capacity(output.len());
+        f32_to_i16(&output, &mut pcm);
+        output = pcm
+            .into_iter()
+            .map(|sample| sample as f32 / i16::MAX as f32)
+            .collect();
+    }
+    (output, stages)
+}
+
+// Least-squares fit of the window to the sweep's own phase, with a free
+// amplitude and phase offset for whatever the filters did to it.
+fn fit(sweep: &Sweep, window: &[f32], times: impl Iterator<Item = f64>, hz: f64) -> Window {
+    let (mut ss, mut sc, mut cc, mut ys, mut yc) = (0.0, 0.0, 0.0, 0.0, 0.0);
+    let basis: Vec<(f64, f64)> = times.map(|t| sweep.phase(t).sin_cos()).collect();
+    for (&y, &(s, c)) in window.iter().zip(&basis) {
+        let y = y as f64;
+        ss += s * s;
+        sc += s * c;
+        cc += c * c;
+        ys += y * s;
+        yc += y * c;
+    }
+    let det = ss * cc - sc * sc;
+    let (a, b) = if det.abs() > f64::EPSILON {
+        ((ys * cc - yc * sc) / det, (yc * ss - ys * sc) / det)
+    } else {
+        (0.0, 0.0)
+    };
+    let (mut signal, mut residual) = (0.0, 0.0);
+    for (&y, &(s, c)) in window.iter().zip(&basis) {
+        let fitted = a * s + b * c;
+        signal += fitted * fitted;
+        residual += (y as f64 - fitted).powi(2);
+    }
+    let len = window.len() as f64;
+    Window {
+        hz,
+        snr_db: to_db(signal / residual.max(f64::MIN_POSITIVE)).min(-FLOOR_DB),
+        gain_db: to_db(signal / len / tone_power()),
+    }
+}
+
+// f(t) = start * (end / start)^(t / T), so every octave gets the same time.
+struct Sweep {
+    rate: f64,
+}
+
+impl Sweep {
+    fn new(end_hz: f64) -> Self {
+        Self {
+            rate: (end_hz / SWEEP_START_HZ).ln() / SWEEP_SECONDS,
+        }
+    }
+
+    fn frequency(&self, t: f64) -> f64 {
+        SWEEP_START_HZ * (self.rate * t).exp()
+    }
+
+    fn phase(&self, t: f64) -> f64 {
+        TAU * SWEEP_START_HZ * ((self.rate * t).exp() - 1.0) / self.rate
+    }
+
+    fn render(&self, sample_rate: u32) -> Vec<f32> {
+        let len = (SWEEP_SECONDS * sample_rate as f64) as usize;
+        (0..len)
+            .map(|n| (AMPLITUDE * self.phase(n as f64 / sample_rate as f64).sin()) as f32)
+            .collect()
+    }
+}
+
+fn tone_power() -> f64 {
+    AMPLITUDE * AMPLITUDE / 2.0
+}
+
+fn to_db(power_ratio: f64) -> f64 {
+    if power_ratio <= 0.0 {
+        return FLOOR_DB;
+    }
+    (10.0 * power_ratio.log10()).max(FLOOR_DB)
+}
+
+fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
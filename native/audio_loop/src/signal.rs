@@ -0,0 +1,135 @@
+// Watches the raw device input for the two conditions that most often turn
+// into empty or garbled transcripts: a clipping ADC and a mic that delivers
+// nothing but digital zeros (muted in the OS, privacy switch, wrong device).
+// Each condition is reported once when it starts and once when it clears.
+
+use crate::events;
+
+// Anything at or above this magnitude counts as a clipped sample.
+const CLIP_LEVEL: f32 = 0.999;
+const CLIP_WINDOW_MS: usize = 1_000;
+// Share of samples in a window that must clip for the window to count.
+const CLIP_WINDOW_RATIO: f32 = 0.01;
+const CLIP_SUSTAIN_WINDOWS: u32 = 2;
+// Below one 16-bit LSB: true digital silence, not just a quiet room.
+const SILENCE_LEVEL: f32 = 1.0 / 32_768.0;
+
+pub struct SignalMonitor {
+    sample_rate: u32,
+    silence_limit_ms: u64,
+    silent_samples: u64,
+    silence_warned: bool,
+    window_samples: usize,
+    window_clipped: usize,
+    clipped_windows: u32,
+    clip_warned: bool,
+}
+
+impl SignalMonitor {
+    pub fn new(silence_limit_ms: u64) -> Self {
+        Self {
+            sample_rate: 48_000,
+            silence_limit_ms,
+            silent_samples: 0,
+            silence_warned: false,
+            window_samples: 0,
+            window_clipped: 0,
+            clipped_windows: 0,
+            clip_warned: false,
+        }
+    }
+
+    // A new stream starts a fresh measurement; an outstanding warning stays
+    // raised until the new device proves otherwise.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate.max(1);
+        self.silent_samples = 0;
+        self.window_samples = 0;
+        self.window_clipped = 0;
+        self.clipped_windows = 0;
+    }
+
+    pub fn process(&mut self, block: &[f32]) {
+        self.track_silence(block);
+        self.track_clipping(block);
+    }
+
+    fn track_silence(&mut self, block: &[f32]) {
+        if self.silence_limit_ms == 0 {
+            return;
+        }
+        if block.iter().any(|sample| sample.abs() >= SILENCE_LEVEL) {
+            self.silent_samples = 0;
+            if self.silence_warned {
+                self.silence_warned = false;
+                report_ok("silence");
+            }
+            return;
+        }
+
+        self.silent_samples += block.len() as u64;
+        let silent_ms = self.silent_samples * 1000 / self.sample_rate as u64;
+        if !self.silence_warned && silent_ms >= self.silence_limit_ms {
+            self.silence_warned = true;
+            let message = format!(
+                "no signal for {}s — is the mic muted in the OS?",
+                silent_ms / 1000
+            );
+            events::emit(
+                "signalWarning",
+                serde_json::json!({ "kind": "silence", "silentMs": silent_ms, "message": message }),
+                || format!("SIGNAL_WARNING kind=silence silent_ms={silent_ms} message={message:?}"),
+            );
+        }
+    }
+
+    fn track_clipping(&mut self, block: &[f32]) {
+        let window_len = self.sample_rate as usize * CLIP_WINDOW_MS / 1000;
+        for sample in block {
+            self.window_samples += 1;
+            if sample.abs() >= CLIP_LEVEL {
+                self.window_clipped += 1;
+            }
+            if self.window_samples < window_len {
+                continue;
+            }
+
+            let clipped_pct = self.window_clipped as f32 / self.window_samples as f32;
+            self.window_samples = 0;
+            self.window_clipped = 0;
+            if clipped_pct >= CLIP_WINDOW_RATIO {
+                self.clipped_windows += 1;
+                if !self.clip_warned && self.clipped_windows >= CLIP_SUSTAIN_WINDOWS {
+                    self.clip_warned = true;
+                    let clipped_pct = clipped_pct * 100.0;
+                    let message = "input clipping — lower the microphone input level";
+                    events::emit(
+                        "signalWarning",
+                        serde_json::json!({
+                            "kind": "clipping",
+                            "clippedPct": events::tenths(clipped_pct as f64),
+                            "message": message,
+                        }),
+                        || {
+                            format!(
+                                "SIGNAL_WARNING kind=clipping clipped_pct={clipped_pct:.1} message={message:?}"
+                            )
+                        },
+                    );
+                }
+            } else {
+                self.clipped_windows = 0;
+                if self.clip_warned {
+                    self.clip_warned = false;
+                    report_ok("clipping");
+                }
+            }
+        }
+    }
+}
+
+fn report_ok(kind: &str) {
+    events::emit("signalOk", serde_json::json!({ "kind": kind }), || {
+        format!("SIGNAL_OK kind={kind}")
+    });
+}
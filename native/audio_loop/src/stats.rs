@@ -0,0 +1,67 @@
+use serde_json::json;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// Counters shared by the capture callback, the writer thread and the periodic
+// reporter. "Frames" are mono samples: captured at the device rate, emitted
+// and written at the target rate.
+#[derive(Default)]
+pub struct CaptureStats {
+    pub frames_captured: AtomicU64,
+    pub frames_emitted: AtomicU64,
+    pub frames_written: AtomicU64,
+    pub send_failures: AtomicU64,
+    pub callback_gaps: AtomicU64,
+    pub max_gap_ms: AtomicU64,
+    pub resampler_carry: AtomicU64,
+    pub max_resampler_carry: AtomicU64,
+    pub input_sample_rate: AtomicU32,
+    pub resampler_input_rate: AtomicU32,
+}
+
+impl CaptureStats {
+    pub fn record_gap(&self, gap_ms: u64) {
+        self.callback_gaps.fetch_add(1, Ordering::Relaxed);
+        self.max_gap_ms.fetch_max(gap_ms, Ordering::Relaxed);
+    }
+
+    pub fn record_carry(&self, carry: usize) {
+        self.resampler_carry.store(carry as u64, Ordering::Relaxed);
+        self.max_resampler_carry
+            .fetch_max(carry as u64, Ordering::Relaxed);
+    }
+
+    // Latency is audio handed to the writer but not yet on stdout, plus what
+    // the resampler is still holding back.
+    pub fn snapshot(&self, target_sample_rate: u32) -> serde_json::Value {
+        let emitted = self.frames_emitted.load(Ordering::Relaxed);
+        let written = self.frames_written.load(Ordering::Relaxed);
+        let carry_rate = self.resampler_input_rate.load(Ordering::Relaxed).max(1);
+        let queued_ms =
+            emitted.saturating_sub(written) as f64 * 1000.0 / target_sample_rate.max(1) as f64;
+        let carry_ms =
+            self.resampler_carry.load(Ordering::Relaxed) as f64 * 1000.0 / carry_rate as f64;
+
+        json!({
+            "event": "stats",
+            "framesCaptured": self.frames_captured.load(Ordering::Relaxed),
+            "framesEmitted": emitted,
+            "framesWritten": written,
+            "drops": self.send_failures.load(Ordering::Relaxed),
+            "callbackGaps": self.callback_gaps.load(Ordering::Relaxed),
+            "maxGapMs": self.max_gap_ms.load(Ordering::Relaxed),
+            "resamplerCarry": self.resampler_carry.load(Ordering::Relaxed),
+            "maxResamplerCarry": self.max_resampler_carry.load(Ordering::Relaxed),
+            "latencyMs": ((queued_ms + carry_ms) * 10.0).round() / 10.0
+        })
+    }
+}
+
+pub fn spawn_stats_reporter(stats: Arc<CaptureStats>, target_sample_rate: u32, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        eprintln!("{}", stats.snapshot(target_sample_rate));
+    });
+}
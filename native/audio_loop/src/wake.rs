@@ -0,0 +1,193 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use crate::control::CaptureControls;
+use crate::events;
+
+// openWakeWord's three-stage pipeline, all at 16 kHz: a shared melspectrogram
+// model, a shared speech-embedding model, and the per-phrase classifier.
+// The two shared models ship next to every classifier, so only the
+// classifier path is configured.
+const MELSPEC_MODEL: &str = "melspectrogram.onnx";
+const EMBEDDING_MODEL: &str = "embedding_model.onnx";
+// 80 ms of audio per step, with three hops of context for the STFT window.
+const CHUNK_SAMPLES: usize = 1_280;
+const CONTEXT_SAMPLES: usize = 480;
+const MEL_BINS: usize = 32;
+const EMBEDDING_WINDOW: usize = 76;
+const EMBEDDING_DIM: usize = 96;
+// Every stock openWakeWord classifier looks at the last 16 embeddings.
+const FEATURE_FRAMES: usize = 16;
+
+pub struct WakeDetector {
+    melspec: Session,
+    embedding: Session,
+    classifier: Session,
+    threshold: f32,
+    audio: Vec<f32>,
+    context: VecDeque<f32>,
+    mels: VecDeque<[f32; MEL_BINS]>,
+    features: VecDeque<Vec<f32>>,
+}
+
+impl WakeDetector {
+    pub fn new(classifier_path: &Path, threshold: f32) -> Result<Self, String> {
+        let directory = classifier_path.parent().unwrap_or(Path::new("."));
+        Ok(Self {
+            melspec: load_model(&directory.join(MELSPEC_MODEL))?,
+            embedding: load_model(&directory.join(EMBEDDING_MODEL))?,
+            classifier: load_model(classifier_path)?,
+            threshold,
+            audio: Vec::with_capacity(CHUNK_SAMPLES * 2),
+            context: VecDeque::from(vec![0.0; CONTEXT_SAMPLES]),
+            mels: VecDeque::with_capacity(EMBEDDING_WINDOW + 8),
+            features: VecDeque::with_capacity(FEATURE_FRAMES),
+        })
+    }
+
+    // Clears the model history so the phrase that just fired, or audio from
+    // before a pause, can't trigger again.
+    pub fn reset(&mut self) {
+        self.audio.clear();
+        self.context.iter_mut().for_each(|sample| *sample = 0.0);
+        self.mels.clear();
+        self.features.clear();
+    }
+
+    // Returns the score of the first step that crossed the threshold.
+    pub fn process(&mut self, samples: &[f32]) -> Result<Option<f32>, String> {
+        self.audio.extend_from_slice(samples);
+        let mut offset = 0;
+        let mut triggered = None;
+        while self.audio.len() - offset >= CHUNK_SAMPLES {
+            let chunk: Vec<f32> = self.audio[offset..offset + CHUNK_SAMPLES].to_vec();
+            offset += CHUNK_SAMPLES;
+            if let Some(score) = self.step(&chunk)? {
+                triggered = Some(score);
+                break;
+            }
+        }
+        if triggered.is_some() {
+            self.reset();
+        } else {
+            self.audio.drain(..offset);
+        }
+        Ok(triggered)
+    }
+
+    fn step(&mut self, chunk: &[f32]) -> Result<Option<f32>, String> {
+        // The melspectrogram model expects int16-scaled input.
+        let mut input: Vec<f32> = self.context.iter().copied().collect();
+        input.extend(
+            chunk
+                .iter()
+                .map(|sample| sample.clamp(-1.0, 1.0) * 32_767.0),
+        );
+        self.context.drain(..CONTEXT_SAMPLES);
+        self.context
+            .extend(input[input.len() - CONTEXT_SAMPLES..].iter().copied());
+
+        let frames = run_model(&mut self.melspec, vec![1, input.len() as i64], input)?;
+        for frame in frames.chunks_exact(MEL_BINS) {
+            let mut mel = [0.0; MEL_BINS];
+            // Same rescaling openWakeWord applies before the embedding model.
+            for (out, value) in mel.iter_mut().zip(frame) {
+                *out = value / 10.0 + 2.0;
+            }
+            self.mels.push_back(mel);
+        }
+        while self.mels.len() > EMBEDDING_WINDOW {
+            self.mels.pop_front();
+        }
+        if self.mels.len() < EMBEDDING_WINDOW {
+            return Ok(None);
+        }
+
+        let window: Vec<f32> = self.mels.iter().flatten().copied().collect();
+        let shape = vec![1, EMBEDDING_WINDOW as i64, MEL_BINS as i64, 1];
+        let embedding = run_model(&mut self.embedding, shape, window)?;
+        if embedding.len() != EMBEDDING_DIM {
+            return Err(format!(
+                "embedding model returned {} values, expected {EMBEDDING_DIM}",
+                embedding.len()
+            ));
+        }
+        self.features.push_back(embedding);
+        if self.features.len() > FEATURE_FRAMES {
+            self.features.pop_front();
+        }
+        if self.features.len() < FEATURE_FRAMES {
+            return Ok(None);
+        }
+
+        let features: Vec<f32> = self.features.iter().flatten().copied().collect();
+        let shape = vec![1, FEATURE_FRAMES as i64, EMBEDDING_DIM as i64];
+        let score = run_model(&mut self.classifier, shape, features)?
+            .first()
+            .copied()
+            .unwrap_or(0.0);
+        Ok((score >= self.threshold).then_some(score))
+    }
+}
+
+fn load_model(path: &Path) -> Result<Session, String> {
+    Session::builder()
+        .and_then(|builder| builder.with_intra_threads(1))
+        .and_then(|builder| builder.commit_from_file(path))
+        .map_err(|e| format!("failed to load wake model {}: {e}", path.display()))
+}
+
+fn run_model(session: &mut Session, shape: Vec<i64>, data: Vec<f32>) -> Result<Vec<f32>, String> {
+    let input = Tensor::from_array((shape, data)).map_err(|e| e.to_string())?;
+    let outputs = session
+        .run(ort::inputs![input])
+        .map_err(|e| e.to_string())?;
+    let (_, values) = outputs[0]
+        .try_extract_tensor::<f32>()
+        .map_err(|e| e.to_string())?;
+    Ok(values.to_vec())
+}
+
+// Inference runs off the capture callback; the callback hands over audio
+// only while the controls say we're listening for the phrase.
+pub fn spawn_wake_listener(
+    mut detector: WakeDetector,
+    controls: Arc<CaptureControls>,
+) -> mpsc::Sender<(f64, Vec<f32>)> {
+    let (tx, rx) = mpsc::channel::<(f64, Vec<f32>)>();
+    thread::spawn(move || {
+        let mut was_listening = true;
+        for (capture_ms, samples) in rx {
+            let listening = controls.is_listening();
+            if listening && !was_listening {
+                detector.reset();
+            }
+            was_listening = listening;
+            if !listening {
+                continue;
+            }
+            match detector.process(&samples) {
+                Ok(Some(score)) => {
+                    controls.set_listening(false);
+                    was_listening = false;
+                    events::emit(
+                        "wake",
+                        serde_json::json!({
+                            "score": (score as f64 * 1000.0).round() / 1000.0,
+                            "captureMs": events::tenths(capture_ms),
+                        }),
+                        || format!("WAKE score={score:.3} capture_ms={capture_ms:.1}"),
+                    );
+                }
+                Ok(None) => {}
+                Err(error) => events::error("wake", error),
+            }
+        }
+    });
+    tx
+}
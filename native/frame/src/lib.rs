@@ -0,0 +1,152 @@
+// The framing every dingoflow worker speaks on stdio. A request (and an
+// audio_loop --framed chunk) is magic, version, u32 LE JSON length, u32 LE
+// audio length, the JSON, then PCM; a response is magic, version, u32 LE JSON
+// length, then the JSON.
+use serde_json::Value;
+use std::io::{self, Read, Write};
+
+// Frames open with a magic and a protocol version, so a desynchronized pipe
+// or the wrong binary fails on its first bytes instead of being read as
+// lengths.
+pub const FRAME_MAGIC: &[u8; 4] = b"DFLW";
+pub const FRAME_VERSION: u8 = 1;
+pub const FRAME_HEADER_BYTES: usize = 13;
+pub const RESPONSE_HEADER_BYTES: usize = 9;
+pub const MAX_JSON_BYTES: usize = 8 * 1024 * 1024;
+pub const MAX_AUDIO_BYTES: usize = 128 * 1024 * 1024;
+
+pub fn check_frame_prefix(header: &[u8]) -> Result<(), String> {
+    if &header[..4] != FRAME_MAGIC {
+        return Err(format!(
+            "bad frame magic {:02x?}, expected \"DFLW\": the stream is out of sync or the peer is not a dingoflow process",
+            &header[..4]
+        ));
+    }
+    if header[4] != FRAME_VERSION {
+        return Err(format!(
+            "unsupported frame version {}, expected {FRAME_VERSION}",
+            header[4]
+        ));
+    }
+    Ok(())
+}
+
+pub fn read_exact_allow_eof<R: Read>(reader: &mut R, size: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut buf = vec![0_u8; size];
+    let mut offset = 0_usize;
+
+    while offset < size {
+        let read = reader.read(&mut buf[offset..])?;
+        if read == 0 {
+            if offset == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "incomplete frame",
+            ));
+        }
+        offset += read;
+    }
+
+    Ok(Some(buf))
+}
+
+pub fn read_exact_required<R: Read>(reader: &mut R, size: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0_u8; size];
+    reader.read_exact(&mut buf).map_err(|err| {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "incomplete frame body")
+        } else {
+            err
+        }
+    })?;
+    Ok(buf)
+}
+
+// The JSON body and the (possibly empty) PCM payload of one request.
+pub struct Frame {
+    pub json: Vec<u8>,
+    pub audio: Vec<u8>,
+}
+
+// Reads one request frame; Ok(None) on a clean EOF between frames.
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Option<Frame>, String> {
+    let header = match read_exact_allow_eof(reader, FRAME_HEADER_BYTES) {
+        Ok(Some(value)) => value,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(format!("failed to read frame header: {err}")),
+    };
+
+    check_frame_prefix(&header)?;
+    let json_len = u32::from_le_bytes([header[5], header[6], header[7], header[8]]) as usize;
+    let audio_len = u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+
+    if json_len == 0 || json_len > MAX_JSON_BYTES {
+        return Err(format!("invalid json frame size: {json_len}"));
+    }
+
+    if audio_len > MAX_AUDIO_BYTES {
+        return Err(format!("audio frame too large: {audio_len}"));
+    }
+
+    let json = read_exact_required(reader, json_len)
+        .map_err(|err| format!("frame json read failed: {err}"))?;
+    let audio = if audio_len > 0 {
+        read_exact_required(reader, audio_len)
+            .map_err(|err| format!("frame audio read failed: {err}"))?
+    } else {
+        Vec::new()
+    };
+
+    Ok(Some(Frame { json, audio }))
+}
+
+pub fn frame_header(json_len: usize, audio_len: usize) -> [u8; FRAME_HEADER_BYTES] {
+    let mut header = [0_u8; FRAME_HEADER_BYTES];
+    header[..4].copy_from_slice(FRAME_MAGIC);
+    header[4] = FRAME_VERSION;
+    header[5..9].copy_from_slice(&(json_len as u32).to_le_bytes());
+    header[9..].copy_from_slice(&(audio_len as u32).to_le_bytes());
+    header
+}
+
+// Written in one piece, so a frame is never interleaved with another
+// writer's on a shared pipe.
+pub fn write_frame<W: Write>(writer: &mut W, json: &[u8], audio: &[u8]) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_BYTES + json.len() + audio.len());
+    frame.extend_from_slice(&frame_header(json.len(), audio.len()));
+    frame.extend_from_slice(json);
+    frame.extend_from_slice(audio);
+    writer.write_all(&frame)?;
+    writer.flush()
+}
+
+// Reads one response body; Ok(None) on a clean EOF between frames.
+pub fn read_response<R: Read>(reader: &mut R, max_bytes: usize) -> Result<Option<Vec<u8>>, String> {
+    let header = match read_exact_allow_eof(reader, RESPONSE_HEADER_BYTES) {
+        Ok(Some(value)) => value,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(format!("failed to read response: {err}")),
+    };
+    check_frame_prefix(&header)?;
+    let len = u32::from_le_bytes([header[5], header[6], header[7], header[8]]) as usize;
+    if len == 0 || len > max_bytes {
+        return Err(format!("invalid response size: {len}"));
+    }
+    read_exact_required(reader, len)
+        .map(Some)
+        .map_err(|err| format!("failed to read response: {err}"))
+}
+
+pub fn write_response<W: Write>(writer: &mut W, response: &Value) -> io::Result<()> {
+    let body = serde_json::to_vec(response)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+
+    let len = body.len() as u32;
+    writer.write_all(FRAME_MAGIC)?;
+    writer.write_all(&[FRAME_VERSION])?;
+    writer.write_all(&len.to_le_bytes())?;
+    writer.write_all(&body)?;
+    writer.flush()
+}
@@ -0,0 +1,113 @@
+use dingoflow_frame::{
+    frame_header, read_frame, read_response, write_frame, write_response, FRAME_HEADER_BYTES,
+    FRAME_VERSION, MAX_AUDIO_BYTES, MAX_JSON_BYTES,
+};
+use serde_json::json;
+use std::io::Cursor;
+
+fn framed(json: &[u8], audio: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_frame(&mut out, json, audio).unwrap();
+    out
+}
+
+#[test]
+fn request_frames_round_trip() {
+    let mut stream = framed(br#"{"id":"1"}"#, &[1, 2, 3, 4]);
+    stream.extend(framed(br#"{"id":"2"}"#, &[]));
+    let mut reader = Cursor::new(stream);
+
+    let first = read_frame(&mut reader).unwrap().unwrap();
+    assert_eq!(first.json, br#"{"id":"1"}"#);
+    assert_eq!(first.audio, [1, 2, 3, 4]);
+    let second = read_frame(&mut reader).unwrap().unwrap();
+    assert_eq!(second.json, br#"{"id":"2"}"#);
+    assert!(second.audio.is_empty());
+    assert!(read_frame(&mut reader).unwrap().is_none());
+}
+
+#[test]
+fn responses_round_trip() {
+    let mut stream = Vec::new();
+    write_response(&mut stream, &json!({ "id": "1", "ok": true })).unwrap();
+    let mut reader = Cursor::new(stream);
+
+    let body = read_response(&mut reader, MAX_JSON_BYTES).unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_slice::<serde_json::Value>(&body).unwrap(),
+        json!({ "id": "1", "ok": true })
+    );
+    assert!(read_response(&mut reader, MAX_JSON_BYTES)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn bad_magic_is_rejected() {
+    let mut stream = framed(b"{}", &[]);
+    stream[..4].copy_from_slice(b"JSON");
+    let err = read_frame(&mut Cursor::new(stream)).err().unwrap();
+    assert!(err.contains("bad frame magic"), "{err}");
+}
+
+#[test]
+fn unsupported_version_is_rejected() {
+    let mut stream = framed(b"{}", &[]);
+    stream[4] = FRAME_VERSION + 1;
+    let err = read_frame(&mut Cursor::new(stream.clone())).err().unwrap();
+    assert!(err.contains("unsupported frame version"), "{err}");
+
+    let mut response = Vec::new();
+    write_response(&mut response, &json!({})).unwrap();
+    response[4] = FRAME_VERSION + 1;
+    let err = read_response(&mut Cursor::new(response), MAX_JSON_BYTES)
+        .err()
+        .unwrap();
+    assert!(err.contains("unsupported frame version"), "{err}");
+}
+
+#[test]
+fn truncated_frames_are_errors() {
+    let stream = framed(br#"{"id":"1"}"#, &[1, 2, 3, 4]);
+
+    let header = &stream[..FRAME_HEADER_BYTES - 1];
+    let err = read_frame(&mut Cursor::new(header)).err().unwrap();
+    assert!(err.contains("failed to read frame header"), "{err}");
+
+    let json = &stream[..FRAME_HEADER_BYTES + 3];
+    let err = read_frame(&mut Cursor::new(json)).err().unwrap();
+    assert!(err.contains("frame json read failed"), "{err}");
+
+    let audio = &stream[..stream.len() - 1];
+    let err = read_frame(&mut Cursor::new(audio)).err().unwrap();
+    assert!(err.contains("frame audio read failed"), "{err}");
+}
+
+#[test]
+fn json_size_is_limited() {
+    let mut at_limit = frame_header(MAX_JSON_BYTES, 0).to_vec();
+    at_limit.extend(vec![b' '; MAX_JSON_BYTES]);
+    let frame = read_frame(&mut Cursor::new(at_limit)).unwrap().unwrap();
+    assert_eq!(frame.json.len(), MAX_JSON_BYTES);
+
+    // Rejected from the header alone, before the body is read.
+    let over = frame_header(MAX_JSON_BYTES + 1, 0);
+    let err = read_frame(&mut Cursor::new(over)).err().unwrap();
+    assert!(err.contains("invalid json frame size"), "{err}");
+
+    let empty = frame_header(0, 0);
+    let err = read_frame(&mut Cursor::new(empty)).err().unwrap();
+    assert!(err.contains("invalid json frame size"), "{err}");
+
+    let audio = frame_header(2, MAX_AUDIO_BYTES + 1);
+    let err = read_frame(&mut Cursor::new(audio)).err().unwrap();
+    assert!(err.contains("audio frame too large"), "{err}");
+}
+
+#[test]
+fn response_size_is_limited_by_the_caller() {
+    let mut stream = Vec::new();
+    write_response(&mut stream, &json!({ "text": "0123456789" })).unwrap();
+    let err = read_response(&mut Cursor::new(stream), 8).err().unwrap();
+    assert!(err.contains("invalid response size"), "{err}");
+}
@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .build_client(false)
+        .compile_protos(&["../proto/dingoflow/asr/v1/asr.proto"], &["../proto"])?;
+    Ok(())
+}
@@ -0,0 +1,337 @@
+mod worker;
+
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Mutex, OwnedMutexGuard};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use worker::WorkerClient;
+
+pub mod pb {
+    tonic::include_proto!("dingoflow.asr.v1");
+}
+
+use pb::asr_server::{Asr, AsrServer};
+use pb::streaming_recognize_request::Request as StreamRequest;
+use pb::{
+    ListModelsRequest, ListModelsResponse, Model, StreamingRecognizeRequest,
+    StreamingRecognizeResponse, TranscribeRequest, TranscribeResponse,
+};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:50051";
+const DEFAULT_SAMPLE_RATE: u32 = 16_000;
+
+// Serves `dingoflow.asr.v1` (see native/proto) in front of either ASR
+// worker, so Python scripts and editor plugins get a typed API without
+// learning the framed stdio protocol. The worker holds one stream state, so
+// calls take turns: a StreamingRecognize keeps the worker until it ends.
+#[derive(Debug)]
+struct Config {
+    listen: SocketAddr,
+    worker_bin: PathBuf,
+    worker_args: Vec<String>,
+}
+
+type Guard = OwnedMutexGuard<WorkerClient>;
+
+struct Gateway {
+    worker: Arc<Mutex<WorkerClient>>,
+    model: Model,
+}
+
+// Runs one blocking worker request off the async threads. The guard comes
+// back so a stream can keep the worker across requests.
+async fn call(mut worker: Guard, request: Value, audio: Vec<u8>) -> Result<(Guard, Value), Status> {
+    let joined = tokio::task::spawn_blocking(move || {
+        let result = worker.request(request, &audio);
+        (worker, result)
+    })
+    .await
+    .map_err(|e| Status::internal(format!("worker call panicked: {e}")))?;
+    match joined {
+        (worker, Ok(result)) => Ok((worker, result)),
+        (_, Err(error)) => Err(Status::internal(error)),
+    }
+}
+
+fn options(options_json: &str) -> Result<Value, Status> {
+    if options_json.trim().is_empty() {
+        return Ok(json!({}));
+    }
+    match serde_json::from_str(options_json) {
+        Ok(Value::Object(options)) => Ok(Value::Object(options)),
+        _ => Err(Status::invalid_argument(
+            "options_json must be a JSON object",
+        )),
+    }
+}
+
+fn sample_rate(value: u32) -> u32 {
+    if value == 0 {
+        DEFAULT_SAMPLE_RATE
+    } else {
+        value
+    }
+}
+
+fn stream_response(result: &Value, is_final: bool) -> StreamingRecognizeResponse {
+    let text = if is_final {
+        result["text"].as_str()
+    } else {
+        result["previewText"].as_str().or(result["text"].as_str())
+    };
+    StreamingRecognizeResponse {
+        text: text.unwrap_or_default().to_string(),
+        committed_text: result["committedText"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        is_final,
+        duration_seconds: result["durationSeconds"].as_f64().unwrap_or(0.0),
+    }
+}
+
+#[tonic::async_trait]
+impl Asr for Gateway {
+    async fn transcribe(
+        &self,
+        request: Request<TranscribeRequest>,
+    ) -> Result<Response<TranscribeResponse>, Status> {
+        let request = request.into_inner();
+        let mut frame = options(&request.options_json)?;
+        frame["action"] = json!("transcribe");
+        frame["sampleRate"] = json!(sample_rate(request.sample_rate));
+
+        let worker = self.worker.clone().lock_owned().await;
+        let (_, result) = call(worker, frame, request.audio).await?;
+        Ok(Response::new(TranscribeResponse {
+            text: result["text"].as_str().unwrap_or_default().to_string(),
+            duration_seconds: result["durationSeconds"].as_f64().unwrap_or(0.0),
+            result_json: result.to_string(),
+        }))
+    }
+
+    type StreamingRecognizeStream = ReceiverStream<Result<StreamingRecognizeResponse, Status>>;
+
+    async fn streaming_recognize(
+        &self,
+        request: Request<Streaming<StreamingRecognizeRequest>>,
+    ) -> Result<Response<Self::StreamingRecognizeStream>, Status> {
+        let mut inbound = request.into_inner();
+        let sample_rate = match inbound.message().await? {
+            Some(StreamingRecognizeRequest {
+                request: Some(StreamRequest::Config(config)),
+            }) => sample_rate(config.sample_rate),
+            _ => {
+                return Err(Status::invalid_argument(
+                    "the first StreamingRecognize message must be a config",
+                ))
+            }
+        };
+
+        let (tx, rx) = mpsc::channel(16);
+        let worker = self.worker.clone();
+        let streaming = self.model.streaming;
+        tokio::spawn(async move {
+            let worker = worker.lock_owned().await;
+            let outcome = if streaming {
+                recognize_streaming(worker, &mut inbound, &tx, sample_rate).await
+            } else {
+                recognize_buffered(worker, &mut inbound, &tx, sample_rate).await
+            };
+            if let Err(status) = outcome {
+                let _ = tx.send(Err(status)).await;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn list_models(
+        &self,
+        _request: Request<ListModelsRequest>,
+    ) -> Result<Response<ListModelsResponse>, Status> {
+        Ok(Response::new(ListModelsResponse {
+            models: vec![self.model.clone()],
+        }))
+    }
+}
+
+type Outbound = mpsc::Sender<Result<StreamingRecognizeResponse, Status>>;
+
+// Parakeet: stream_reset / stream_push per audio message / stream_flush.
+async fn recognize_streaming(
+    worker: Guard,
+    inbound: &mut Streaming<StreamingRecognizeRequest>,
+    tx: &Outbound,
+    sample_rate: u32,
+) -> Result<(), Status> {
+    let reset = json!({ "action": "stream_reset", "sampleRate": sample_rate });
+    let (mut worker, _) = call(worker, reset, Vec::new()).await?;
+    loop {
+        let audio = match inbound.message().await {
+            Ok(Some(StreamingRecognizeRequest {
+                request: Some(StreamRequest::Audio(audio)),
+            })) => audio,
+            Ok(Some(_)) => {
+                close(worker).await;
+                return Err(Status::invalid_argument(
+                    "only the first StreamingRecognize message may be a config",
+                ));
+            }
+            Ok(None) => break,
+            // The client went away; drop its half-finished utterance.
+            Err(status) => {
+                close(worker).await;
+                return Err(status);
+            }
+        };
+        if audio.is_empty() {
+            continue;
+        }
+        let push = json!({ "action": "stream_push", "sampleRate": sample_rate });
+        let (next, result) = call(worker, push, audio).await?;
+        worker = next;
+        // Still waiting for enough audio to decode: no new partial to send.
+        if result["buffered"] == true {
+            continue;
+        }
+        if tx.send(Ok(stream_response(&result, false))).await.is_err() {
+            close(worker).await;
+            return Ok(());
+        }
+    }
+    let (_, result) = call(worker, json!({ "action": "stream_flush" }), Vec::new()).await?;
+    let _ = tx.send(Ok(stream_response(&result, true))).await;
+    Ok(())
+}
+
+async fn close(worker: Guard) {
+    let _ = call(worker, json!({ "action": "stream_close" }), Vec::new()).await;
+}
+
+// Whisper has no stream state: collect the audio, transcribe once at the end.
+async fn recognize_buffered(
+    worker: Guard,
+    inbound: &mut Streaming<StreamingRecognizeRequest>,
+    tx: &Outbound,
+    sample_rate: u32,
+) -> Result<(), Status> {
+    let mut audio = Vec::new();
+    while let Some(message) = inbound.message().await? {
+        match message.request {
+            Some(StreamRequest::Audio(chunk)) => audio.extend_from_slice(&chunk),
+            _ => {
+                return Err(Status::invalid_argument(
+                    "only the first StreamingRecognize message may be a config",
+                ))
+            }
+        }
+    }
+    let request = json!({ "action": "transcribe", "sampleRate": sample_rate });
+    let (_, result) = call(worker, request, audio).await?;
+    let _ = tx.send(Ok(stream_response(&result, true))).await;
+    Ok(())
+}
+
+// What ListModels reports, from the worker's hello/model_info answer.
+fn describe(info: Value, worker_args: &[String]) -> Model {
+    let model_path = info["modelPath"].as_str().map(str::to_string).or_else(|| {
+        worker_args
+            .iter()
+            .position(|arg| arg == "--model")
+            .and_then(|i| worker_args.get(i + 1).cloned())
+    });
+    let engine = info["engine"].as_str().unwrap_or("unknown").to_string();
+    let id = model_path
+        .as_deref()
+        .and_then(|path| Path::new(path).file_name())
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| engine.clone());
+    Model {
+        id,
+        engine,
+        streaming: info["streaming"].as_bool().unwrap_or(false),
+        info_json: info.to_string(),
+    }
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut listen = DEFAULT_LISTEN.to_string();
+    let mut worker_bin: Option<PathBuf> = None;
+    let mut worker_args = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if flag == "--help" || flag == "-h" {
+            return Err(format!(
+                "usage: dingoflow-grpc-gateway --worker-bin <asr worker> [--worker-arg <arg>]... [--listen {DEFAULT_LISTEN}]"
+            ));
+        }
+        let Some(value) = args.get(i + 1) else {
+            return Err(format!("Missing value for {flag}"));
+        };
+        match flag {
+            "--listen" => listen = value.clone(),
+            "--worker-bin" => worker_bin = Some(PathBuf::from(value)),
+            "--worker-arg" => worker_args.push(value.clone()),
+            other => return Err(format!("Unknown argument: {other}")),
+        }
+        i += 2;
+    }
+
+    Ok(Config {
+        listen: listen
+            .parse()
+            .map_err(|_| format!("Invalid --listen address: {listen}"))?,
+        worker_bin: worker_bin.ok_or("--worker-bin is required")?,
+        worker_args,
+    })
+}
+
+async fn run() -> Result<(), String> {
+    let config = parse_args()?;
+    let worker = Arc::new(Mutex::new(WorkerClient::new(
+        config.worker_bin.clone(),
+        config.worker_args.clone(),
+    )));
+
+    let guard = worker.clone().lock_owned().await;
+    let (guard, info) = call(guard, json!({ "action": "model_info" }), Vec::new())
+        .await
+        .map_err(|status| format!("worker did not answer model_info: {}", status.message()))?;
+    call(guard, json!({ "action": "warmup" }), Vec::new())
+        .await
+        .map_err(|status| format!("worker warmup failed: {}", status.message()))?;
+    let model = describe(info, &config.worker_args);
+
+    eprintln!(
+        "dingoflow-grpc-gateway serving {} ({}) on {}",
+        model.id, model.engine, config.listen
+    );
+    let gateway = Gateway {
+        worker: worker.clone(),
+        model,
+    };
+    let served = Server::builder()
+        .add_service(AsrServer::new(gateway))
+        .serve_with_shutdown(config.listen, async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await;
+
+    worker.lock().await.shutdown();
+    served.map_err(|e| format!("gRPC server failed: {e}"))
+}
+
+#[tokio::main]
+async fn main() {
+    if let Err(err) = run().await {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
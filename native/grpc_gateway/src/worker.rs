@@ -0,0 +1,107 @@
+use dingoflow_frame::{read_response, write_frame};
+use serde_json::{json, Value};
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+// One ASR worker child spoken to over its framed stdio protocol. A worker
+// that died is started again on the next request rather than taking the
+// gateway down with it.
+pub struct WorkerClient {
+    bin: PathBuf,
+    args: Vec<String>,
+    process: Option<Process>,
+    next_id: u64,
+}
+
+struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl WorkerClient {
+    pub fn new(bin: PathBuf, mut args: Vec<String>) -> Self {
+        if !args.iter().any(|arg| arg == "--serve") {
+            args.push("--serve".into());
+        }
+        Self {
+            bin,
+            args,
+            process: None,
+            next_id: 0,
+        }
+    }
+
+    fn process(&mut self) -> Result<&mut Process, String> {
+        if self.process.is_none() {
+            let mut child = Command::new(&self.bin)
+                .args(&self.args)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::inherit())
+                .spawn()
+                .map_err(|e| format!("failed to start {}: {e}", self.bin.display()))?;
+            let stdin = child.stdin.take().ok_or("worker stdin unavailable")?;
+            let stdout = child.stdout.take().ok_or("worker stdout unavailable")?;
+            self.process = Some(Process {
+                child,
+                stdin,
+                stdout: BufReader::new(stdout),
+            });
+        }
+        self.process
+            .as_mut()
+            .ok_or_else(|| "worker not running".into())
+    }
+
+    // Sends one request and returns its `result`, or the worker's `error`.
+    pub fn request(&mut self, mut request: Value, audio: &[u8]) -> Result<Value, String> {
+        self.next_id += 1;
+        request["id"] = json!(format!("grpc-{}", self.next_id));
+        let exchanged = self
+            .process()
+            .and_then(|process| process.exchange(&request, audio));
+        let response = match exchanged {
+            Ok(response) => response,
+            Err(err) => {
+                if let Some(mut process) = self.process.take() {
+                    let _ = process.child.kill();
+                    let _ = process.child.wait();
+                }
+                return Err(err);
+            }
+        };
+        if response["ok"].as_bool() == Some(true) {
+            Ok(response["result"].clone())
+        } else {
+            Err(response["error"]
+                .as_str()
+                .unwrap_or("asr request failed")
+                .to_string())
+        }
+    }
+
+    pub fn shutdown(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            // Closing stdin is the workers' cue to exit.
+            drop(process.stdin);
+            let _ = process.child.wait();
+        }
+    }
+}
+
+impl Process {
+    fn exchange(&mut self, request: &Value, audio: &[u8]) -> Result<Value, String> {
+        let json = request.to_string();
+        write_frame(&mut self.stdin, json.as_bytes(), audio)
+            .map_err(|e| format!("failed to write to worker: {e}"))?;
+
+        let body = read_response(&mut self.stdout, MAX_RESPONSE_BYTES)
+            .map_err(|e| format!("bad worker response: {e}"))?
+            .ok_or("worker exited")?;
+        serde_json::from_slice(&body).map_err(|e| format!("invalid worker response: {e}"))
+    }
+}
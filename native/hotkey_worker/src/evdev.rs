@@ -0,0 +1,174 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::hotkey::Key;
+
+// Reads keyboards straight from /dev/input so hotkeys work the same under
+// X11, Wayland and a bare console. The user needs read access to the event
+// devices (usually membership of the `input` group).
+pub const BACKEND: &str = "evdev";
+
+const EV_KEY: u16 = 1;
+const KEY_A: usize = 30;
+const KEY_SPACE: usize = 57;
+// struct input_event: a timeval (two longs), u16 type, u16 code, i32 value.
+const EVENT_SIZE: usize = 2 * std::mem::size_of::<usize>() + 8;
+
+pub fn probe() -> Result<String, String> {
+    let keyboards = open_keyboards()?;
+    Ok(format!("{BACKEND} ({} keyboards)", keyboards.len()))
+}
+
+pub fn listen(
+    on_ready: &mut dyn FnMut(String),
+    on_key: &mut dyn FnMut(Key, bool),
+) -> Result<(), String> {
+    let keyboards = open_keyboards()?;
+    on_ready(format!("{BACKEND} ({} keyboards)", keyboards.len()));
+    let (tx, rx) = mpsc::channel();
+    for (path, mut file) in keyboards {
+        let tx = tx.clone();
+        thread::spawn(move || {
+            let mut buf = [0_u8; EVENT_SIZE];
+            while file.read_exact(&mut buf).is_ok() {
+                let offset = EVENT_SIZE - 8;
+                let kind = u16::from_ne_bytes([buf[offset], buf[offset + 1]]);
+                let code = u16::from_ne_bytes([buf[offset + 2], buf[offset + 3]]);
+                let value = i32::from_ne_bytes([
+                    buf[offset + 4],
+                    buf[offset + 5],
+                    buf[offset + 6],
+                    buf[offset + 7],
+                ]);
+                // value 2 is key repeat; the matcher only wants edges.
+                if kind != EV_KEY || value == 2 {
+                    continue;
+                }
+                if let Some(key) = key_for(code) {
+                    if tx.send((key, value == 1)).is_err() {
+                        return;
+                    }
+                }
+            }
+            eprintln!("stopped reading {}", path.display());
+        });
+    }
+    drop(tx);
+
+    for (key, pressed) in rx {
+        on_key(key, pressed);
+    }
+    Err("all keyboard devices were closed".into())
+}
+
+fn open_keyboards() -> Result<Vec<(PathBuf, File)>, String> {
+    let entries =
+        fs::read_dir("/dev/input").map_err(|e| format!("failed to list /dev/input: {e}"))?;
+    let mut keyboards = Vec::new();
+    let mut denied = false;
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !name.starts_with("event") || !is_keyboard(&name) {
+            continue;
+        }
+        match File::open(entry.path()) {
+            Ok(file) => keyboards.push((entry.path(), file)),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => denied = true,
+            Err(e) => eprintln!("failed to open {}: {e}", entry.path().display()),
+        }
+    }
+    if keyboards.is_empty() {
+        return Err(if denied {
+            "no permission to read keyboards in /dev/input; add your user to the input group".into()
+        } else {
+            "no keyboard found in /dev/input".into()
+        });
+    }
+    Ok(keyboards)
+}
+
+// Mice, power buttons and lid switches also report EV_KEY; a keyboard is a
+// device that can at least produce letters and a space bar.
+fn is_keyboard(event_name: &str) -> bool {
+    let caps = fs::read_to_string(format!(
+        "/sys/class/input/{event_name}/device/capabilities/key"
+    ))
+    .unwrap_or_default();
+    // Space-separated hex words, most significant first, one per long.
+    let words: Vec<u64> = caps
+        .split_whitespace()
+        .rev()
+        .filter_map(|word| u64::from_str_radix(word, 16).ok())
+        .collect();
+    let bits = usize::BITS as usize;
+    let has = |code: usize| {
+        words
+            .get(code / bits)
+            .is_some_and(|word| word & (1 << (code % bits)) != 0)
+    };
+    has(KEY_A) && has(KEY_SPACE)
+}
+
+// Codes from linux/input-event-codes.h.
+fn key_for(code: u16) -> Option<Key> {
+    const LETTERS: [(u16, char); 26] = [
+        (16, 'q'),
+        (17, 'w'),
+        (18, 'e'),
+        (19, 'r'),
+        (20, 't'),
+        (21, 'y'),
+        (22, 'u'),
+        (23, 'i'),
+        (24, 'o'),
+        (25, 'p'),
+        (30, 'a'),
+        (31, 's'),
+        (32, 'd'),
+        (33, 'f'),
+        (34, 'g'),
+        (35, 'h'),
+        (36, 'j'),
+        (37, 'k'),
+        (38, 'l'),
+        (44, 'z'),
+        (45, 'x'),
+        (46, 'c'),
+        (47, 'v'),
+        (48, 'b'),
+        (49, 'n'),
+        (50, 'm'),
+    ];
+    let key = match code {
+        1 => Key::Escape,
+        2..=10 => Key::Digit((b'1' + (code - 2) as u8) as char),
+        11 => Key::Digit('0'),
+        14 => Key::Backspace,
+        15 => Key::Tab,
+        28 => Key::Enter,
+        29 => Key::LeftCtrl,
+        42 => Key::LeftShift,
+        54 => Key::RightShift,
+        56 => Key::LeftAlt,
+        57 => Key::Space,
+        59..=68 => Key::F((code - 58) as u8),
+        87 => Key::F(11),
+        88 => Key::F(12),
+        97 => Key::RightCtrl,
+        100 => Key::RightAlt,
+        111 => Key::Delete,
+        125 => Key::LeftMeta,
+        126 => Key::RightMeta,
+        183..=194 => Key::F((code - 170) as u8),
+        _ => {
+            return LETTERS
+                .iter()
+                .find(|(letter_code, _)| *letter_code == code)
+                .map(|(_, letter)| Key::Letter(*letter))
+        }
+    };
+    Some(key)
+}
@@ -0,0 +1,176 @@
+// Accelerator parsing and press/release tracking shared by every backend.
+// Accelerators use the same syntax as DINGOFLOW_HOTKEY
+// ("CommandOrControl+Shift+Space"), so one string configures both the
+// in-process listener and this worker.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    Letter(char),
+    Digit(char),
+    F(u8),
+    Space,
+    Enter,
+    Tab,
+    Escape,
+    Backspace,
+    Delete,
+    LeftCtrl,
+    RightCtrl,
+    LeftShift,
+    RightShift,
+    LeftAlt,
+    RightAlt,
+    LeftMeta,
+    RightMeta,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PushToTalk,
+    Toggle,
+    Cancel,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::PushToTalk => "pushToTalk",
+            Action::Toggle => "toggle",
+            Action::Cancel => "cancel",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Hotkey {
+    pub source: String,
+    trigger: Key,
+    // Each group is satisfied by any one of its keys (left or right side).
+    modifiers: Vec<&'static [Key]>,
+}
+
+const CTRL: &[Key] = &[Key::LeftCtrl, Key::RightCtrl];
+const SHIFT: &[Key] = &[Key::LeftShift, Key::RightShift];
+const ALT: &[Key] = &[Key::LeftAlt, Key::RightAlt];
+const META: &[Key] = &[Key::LeftMeta, Key::RightMeta];
+const META_OR_CTRL: &[Key] = &[Key::LeftMeta, Key::RightMeta, Key::LeftCtrl, Key::RightCtrl];
+
+impl Hotkey {
+    pub fn parse(accelerator: &str) -> Result<Self, String> {
+        let mut trigger = None;
+        let mut modifiers = Vec::new();
+        for token in accelerator
+            .split('+')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+        {
+            let group = match token.to_lowercase().as_str() {
+                "command" | "cmd" | "meta" | "super" => Some(META),
+                "control" | "ctrl" => Some(CTRL),
+                "shift" => Some(SHIFT),
+                "alt" | "option" => Some(ALT),
+                "commandorcontrol" | "cmdorctrl" => Some(META_OR_CTRL),
+                _ => None,
+            };
+            if let Some(group) = group {
+                modifiers.push(group);
+                continue;
+            }
+            let key = parse_trigger(token)
+                .ok_or_else(|| format!("Unsupported hotkey token '{token}' in {accelerator}"))?;
+            if trigger.replace(key).is_some() {
+                return Err(format!(
+                    "Hotkey must define exactly one non-modifier key: {accelerator}"
+                ));
+            }
+        }
+        let trigger =
+            trigger.ok_or_else(|| format!("Hotkey missing a trigger key: {accelerator}"))?;
+        Ok(Self {
+            source: accelerator.to_string(),
+            trigger,
+            modifiers,
+        })
+    }
+}
+
+fn parse_trigger(token: &str) -> Option<Key> {
+    let lower = token.to_lowercase();
+    let key = match lower.as_str() {
+        "space" => Key::Space,
+        "enter" | "return" => Key::Enter,
+        "tab" => Key::Tab,
+        "escape" | "esc" => Key::Escape,
+        "backspace" => Key::Backspace,
+        "delete" => Key::Delete,
+        _ => {
+            let mut chars = lower.chars();
+            match (chars.next(), chars.as_str()) {
+                (Some(c), "") if c.is_ascii_lowercase() => Key::Letter(c),
+                (Some(c), "") if c.is_ascii_digit() => Key::Digit(c),
+                (Some('f'), n) => Key::F(n.parse().ok().filter(|n| (1..=24).contains(n))?),
+                _ => return None,
+            }
+        }
+    };
+    Some(key)
+}
+
+struct Binding {
+    action: Action,
+    hotkey: Hotkey,
+    active: bool,
+}
+
+// Follows the physical key state and reports when a binding goes down or
+// comes back up. A binding stays down until its trigger or any required
+// modifier is released, and key repeat never fires it twice.
+pub struct Matcher {
+    bindings: Vec<Binding>,
+    held: Vec<Key>,
+}
+
+impl Matcher {
+    pub fn new(bindings: Vec<(Action, Hotkey)>) -> Self {
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(action, hotkey)| Binding {
+                    action,
+                    hotkey,
+                    active: false,
+                })
+                .collect(),
+            held: Vec::new(),
+        }
+    }
+
+    pub fn key(&mut self, key: Key, pressed: bool) -> Vec<(Action, bool)> {
+        let mut changes = Vec::new();
+        if pressed {
+            if self.held.contains(&key) {
+                return changes;
+            }
+            self.held.push(key);
+        } else {
+            self.held.retain(|held| *held != key);
+        }
+
+        for binding in &mut self.bindings {
+            let modifiers_held = binding
+                .hotkey
+                .modifiers
+                .iter()
+                .all(|group| group.iter().any(|key| self.held.contains(key)));
+            let combo_held = modifiers_held && self.held.contains(&binding.hotkey.trigger);
+            if pressed && !binding.active && key == binding.hotkey.trigger && modifiers_held {
+                binding.active = true;
+                changes.push((binding.action, true));
+            } else if binding.active && !combo_held {
+                binding.active = false;
+                changes.push((binding.action, false));
+            }
+        }
+        changes
+    }
+}
@@ -0,0 +1,226 @@
+use std::os::raw::c_void;
+
+use crate::hotkey::Key;
+
+// A listen-only Quartz event tap: it sees every key event in the login
+// session without consuming any, and needs the Input Monitoring permission.
+pub const BACKEND: &str = "macos";
+
+type CFMachPortRef = *mut c_void;
+type CFRunLoopSourceRef = *mut c_void;
+type CFRunLoopRef = *mut c_void;
+type CGEventRef = *mut c_void;
+type TapCallback = extern "C" fn(*mut c_void, u32, CGEventRef, *mut c_void) -> CGEventRef;
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn CGPreflightListenEventAccess() -> bool;
+    fn CGRequestListenEventAccess() -> bool;
+    fn CGEventTapCreate(
+        tap: u32,
+        place: u32,
+        options: u32,
+        events_of_interest: u64,
+        callback: TapCallback,
+        user_info: *mut c_void,
+    ) -> CFMachPortRef;
+    fn CGEventTapEnable(tap: CFMachPortRef, enable: bool);
+    fn CGEventGetIntegerValueField(event: CGEventRef, field: u32) -> i64;
+    fn CGEventGetFlags(event: CGEventRef) -> u64;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    static kCFRunLoopCommonModes: *const c_void;
+    fn CFMachPortCreateRunLoopSource(
+        allocator: *const c_void,
+        port: CFMachPortRef,
+        order: isize,
+    ) -> CFRunLoopSourceRef;
+    fn CFRunLoopGetCurrent() -> CFRunLoopRef;
+    fn CFRunLoopAddSource(run_loop: CFRunLoopRef, source: CFRunLoopSourceRef, mode: *const c_void);
+    fn CFRunLoopRun();
+}
+
+const SESSION_EVENT_TAP: u32 = 1;
+const HEAD_INSERT: u32 = 0;
+const LISTEN_ONLY: u32 = 1;
+const KEY_DOWN: u32 = 10;
+const KEY_UP: u32 = 11;
+const FLAGS_CHANGED: u32 = 12;
+const TAP_DISABLED_BY_TIMEOUT: u32 = 0xFFFF_FFFE;
+const TAP_DISABLED_BY_USER_INPUT: u32 = 0xFFFF_FFFF;
+const KEYCODE_FIELD: u32 = 9;
+const AUTOREPEAT_FIELD: u32 = 8;
+
+// Device-dependent flag bits (IOLLEvent.h) tell left and right modifiers apart.
+const LEFT_CTRL: u64 = 0x0001;
+const LEFT_SHIFT: u64 = 0x0002;
+const RIGHT_SHIFT: u64 = 0x0004;
+const LEFT_CMD: u64 = 0x0008;
+const RIGHT_CMD: u64 = 0x0010;
+const LEFT_ALT: u64 = 0x0020;
+const RIGHT_ALT: u64 = 0x0040;
+const RIGHT_CTRL: u64 = 0x2000;
+
+struct Tap<'a> {
+    port: CFMachPortRef,
+    on_key: &'a mut dyn FnMut(Key, bool),
+}
+
+pub fn probe() -> Result<String, String> {
+    if unsafe { CGPreflightListenEventAccess() } {
+        Ok(BACKEND.into())
+    } else {
+        Err("input monitoring permission is required for global hotkeys".into())
+    }
+}
+
+pub fn listen(
+    on_ready: &mut dyn FnMut(String),
+    on_key: &mut dyn FnMut(Key, bool),
+) -> Result<(), String> {
+    // Shows the system prompt the first time; the tap fails until granted.
+    if !unsafe { CGPreflightListenEventAccess() } {
+        unsafe { CGRequestListenEventAccess() };
+    }
+
+    let mut tap = Tap {
+        port: std::ptr::null_mut(),
+        on_key,
+    };
+    let mask = (1 << KEY_DOWN) | (1 << KEY_UP) | (1 << FLAGS_CHANGED);
+    unsafe {
+        tap.port = CGEventTapCreate(
+            SESSION_EVENT_TAP,
+            HEAD_INSERT,
+            LISTEN_ONLY,
+            mask,
+            callback,
+            &mut tap as *mut Tap as *mut c_void,
+        );
+        if tap.port.is_null() {
+            return Err("unable to create event tap; grant Input Monitoring permission".into());
+        }
+        let source = CFMachPortCreateRunLoopSource(std::ptr::null(), tap.port, 0);
+        if source.is_null() {
+            return Err("unable to create run loop source for event tap".into());
+        }
+        CFRunLoopAddSource(CFRunLoopGetCurrent(), source, kCFRunLoopCommonModes);
+        CGEventTapEnable(tap.port, true);
+        on_ready(BACKEND.into());
+        CFRunLoopRun();
+    }
+    Err("event tap run loop exited".into())
+}
+
+extern "C" fn callback(
+    _proxy: *mut c_void,
+    kind: u32,
+    event: CGEventRef,
+    user_info: *mut c_void,
+) -> CGEventRef {
+    let tap = unsafe { &mut *(user_info as *mut Tap) };
+    match kind {
+        // Quartz turns off taps that it thinks are stalling; turn it back on.
+        TAP_DISABLED_BY_TIMEOUT | TAP_DISABLED_BY_USER_INPUT => unsafe {
+            CGEventTapEnable(tap.port, true)
+        },
+        KEY_DOWN | KEY_UP => {
+            let repeat = unsafe { CGEventGetIntegerValueField(event, AUTOREPEAT_FIELD) } != 0;
+            let code = unsafe { CGEventGetIntegerValueField(event, KEYCODE_FIELD) } as u16;
+            if let Some(key) = key_for(code).filter(|_| !repeat) {
+                (tap.on_key)(key, kind == KEY_DOWN);
+            }
+        }
+        FLAGS_CHANGED => {
+            let code = unsafe { CGEventGetIntegerValueField(event, KEYCODE_FIELD) } as u16;
+            let flags = unsafe { CGEventGetFlags(event) };
+            if let Some((key, bit)) = modifier_for(code) {
+                (tap.on_key)(key, flags & bit != 0);
+            }
+        }
+        _ => {}
+    }
+    event
+}
+
+fn modifier_for(code: u16) -> Option<(Key, u64)> {
+    Some(match code {
+        55 => (Key::LeftMeta, LEFT_CMD),
+        54 => (Key::RightMeta, RIGHT_CMD),
+        56 => (Key::LeftShift, LEFT_SHIFT),
+        60 => (Key::RightShift, RIGHT_SHIFT),
+        58 => (Key::LeftAlt, LEFT_ALT),
+        61 => (Key::RightAlt, RIGHT_ALT),
+        59 => (Key::LeftCtrl, LEFT_CTRL),
+        62 => (Key::RightCtrl, RIGHT_CTRL),
+        _ => return None,
+    })
+}
+
+// Virtual keycodes from HIToolbox's Events.h (ANSI layout).
+fn key_for(code: u16) -> Option<Key> {
+    const LETTERS: [(u16, char); 26] = [
+        (0, 'a'),
+        (1, 's'),
+        (2, 'd'),
+        (3, 'f'),
+        (4, 'h'),
+        (5, 'g'),
+        (6, 'z'),
+        (7, 'x'),
+        (8, 'c'),
+        (9, 'v'),
+        (11, 'b'),
+        (12, 'q'),
+        (13, 'w'),
+        (14, 'e'),
+        (15, 'r'),
+        (16, 'y'),
+        (17, 't'),
+        (31, 'o'),
+        (32, 'u'),
+        (34, 'i'),
+        (35, 'p'),
+        (37, 'l'),
+        (38, 'j'),
+        (40, 'k'),
+        (45, 'n'),
+        (46, 'm'),
+    ];
+    const DIGITS: [(u16, char); 10] = [
+        (18, '1'),
+        (19, '2'),
+        (20, '3'),
+        (21, '4'),
+        (23, '5'),
+        (22, '6'),
+        (26, '7'),
+        (28, '8'),
+        (25, '9'),
+        (29, '0'),
+    ];
+    const FUNCTION: [u16; 20] = [
+        122, 120, 99, 118, 96, 97, 98, 100, 101, 109, 103, 111, 105, 107, 113, 106, 64, 79, 80, 90,
+    ];
+    let key = match code {
+        36 => Key::Enter,
+        48 => Key::Tab,
+        49 => Key::Space,
+        51 => Key::Backspace,
+        53 => Key::Escape,
+        117 => Key::Delete,
+        _ => {
+            if let Some((_, letter)) = LETTERS.iter().find(|(c, _)| *c == code) {
+                Key::Letter(*letter)
+            } else if let Some((_, digit)) = DIGITS.iter().find(|(c, _)| *c == code) {
+                Key::Digit(*digit)
+            } else {
+                let index = FUNCTION.iter().position(|c| *c == code)?;
+                Key::F(index as u8 + 1)
+            }
+        }
+    };
+    Some(key)
+}
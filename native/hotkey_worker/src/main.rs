@@ -0,0 +1,182 @@
+#[cfg(target_os = "linux")]
+mod evdev;
+mod hotkey;
+#[cfg(target_os = "macos")]
+mod macos;
+
+use hotkey::{Action, Hotkey, Key, Matcher};
+use serde_json::{json, Map, Value};
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(target_os = "linux")]
+use evdev as platform;
+#[cfg(target_os = "macos")]
+use macos as platform;
+
+const DEFAULT_PUSH_TO_TALK: &str = "CommandOrControl+Shift+Space";
+
+// Watches global hotkeys and reports them as JSON lines on stdout, so the
+// host or supervisor hears about them while another app has focus. Exits
+// when stdin closes, which is how the parent says it is gone.
+struct Config {
+    bindings: Vec<(Action, Hotkey)>,
+    healthcheck: bool,
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut push_to_talk = Some(DEFAULT_PUSH_TO_TALK.to_string());
+    let mut toggle: Option<String> = None;
+    let mut cancel: Option<String> = None;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--push-to-talk" | "--toggle" | "--cancel") => {
+                if i + 1 >= args.len() {
+                    return Err(format!("Missing value for {flag}"));
+                }
+                // An empty accelerator turns the binding off.
+                let value = Some(args[i + 1].clone()).filter(|value| !value.trim().is_empty());
+                match flag {
+                    "--push-to-talk" => push_to_talk = value,
+                    "--toggle" => toggle = value,
+                    _ => cancel = value,
+                }
+                i += 2;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-hotkey-worker [--push-to-talk CommandOrControl+Shift+Space] [--toggle <accelerator>] [--cancel <accelerator>] [--healthcheck]"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    let mut bindings = Vec::new();
+    for (action, accelerator) in [
+        (Action::PushToTalk, push_to_talk),
+        (Action::Toggle, toggle),
+        (Action::Cancel, cancel),
+    ] {
+        if let Some(accelerator) = accelerator {
+            bindings.push((action, Hotkey::parse(&accelerator)?));
+        }
+    }
+    if bindings.is_empty() {
+        return Err("at least one hotkey must be bound".into());
+    }
+
+    Ok(Config {
+        bindings,
+        healthcheck,
+    })
+}
+
+fn emit(event: &str, fields: Value) {
+    let Value::Object(mut fields) = fields else {
+        return;
+    };
+    let at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    fields.insert("atMs".into(), at_ms.into());
+    // `event` leads the line, matching the supervisor's host events.
+    let fields = Value::Object(fields).to_string();
+    let line = format!("{{\"event\":{},{}", Value::from(event), &fields[1..]);
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    // Nobody is listening any more once stdout is gone.
+    if writeln!(out, "{line}").and_then(|_| out.flush()).is_err() {
+        std::process::exit(0);
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn probe() -> Result<String, String> {
+    platform::probe()
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn listen(
+    on_ready: &mut dyn FnMut(String),
+    on_key: &mut dyn FnMut(Key, bool),
+) -> Result<(), String> {
+    platform::listen(on_ready, on_key)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn probe() -> Result<String, String> {
+    Err("global hotkeys are not supported on this platform yet".into())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn listen(
+    _on_ready: &mut dyn FnMut(String),
+    _on_key: &mut dyn FnMut(Key, bool),
+) -> Result<(), String> {
+    probe().map(|_| ())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        match probe() {
+            Ok(name) => println!("ok {name}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    std::thread::spawn(|| {
+        let mut sink = [0_u8; 256];
+        let mut stdin = io::stdin();
+        while matches!(stdin.read(&mut sink), Ok(read) if read > 0) {}
+        std::process::exit(0);
+    });
+
+    let bindings: Map<String, Value> = cfg
+        .bindings
+        .iter()
+        .map(|(action, hotkey)| (action.name().to_string(), hotkey.source.clone().into()))
+        .collect();
+    let mut matcher = Matcher::new(cfg.bindings);
+    let mut on_key = |key: Key, pressed: bool| {
+        for (action, down) in matcher.key(key, pressed) {
+            let state = if down { "down" } else { "up" };
+            emit("hotkey", json!({ "action": action.name(), "state": state }));
+        }
+    };
+
+    let mut on_ready = |backend: String| {
+        emit("ready", json!({ "backend": backend, "bindings": bindings }));
+    };
+    if let Err(err) = listen(&mut on_ready, &mut on_key) {
+        emit("error", json!({ "message": err }));
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
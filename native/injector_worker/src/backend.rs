@@ -0,0 +1,59 @@
+use crate::keys::{Chord, Key};
+
+pub trait Backend {
+    fn name(&self) -> &'static str;
+    fn type_text(&mut self, text: &str) -> Result<(), String>;
+    fn press(&mut self, chord: &Chord) -> Result<(), String>;
+}
+
+// Keystroke emulation types characters one by one, and a typed '\n' or '\t'
+// lands as a literal character in some apps and nothing in others, so line
+// breaks and tabs are sent as real key presses.
+pub fn type_with_keys(backend: &mut dyn Backend, text: &str) -> Result<(), String> {
+    let mut run_start = 0;
+    for (index, ch) in text.char_indices() {
+        let key = match ch {
+            '\n' => Key::Enter,
+            '\t' => Key::Tab,
+            _ => continue,
+        };
+        if run_start < index {
+            backend.type_text(&text[run_start..index])?;
+        }
+        backend.press(&Chord::new(key, 1))?;
+        run_start = index + ch.len_utf8();
+    }
+    if run_start < text.len() {
+        backend.type_text(&text[run_start..])?;
+    }
+    Ok(())
+}
+
+pub fn select(name: &str, key_delay_ms: u32) -> Result<Box<dyn Backend>, String> {
+    match name {
+        "auto" => auto(key_delay_ms),
+        #[cfg(target_os = "macos")]
+        "macos" => Ok(Box::new(crate::macos::QuartzBackend::new(key_delay_ms)?)),
+        #[cfg(all(unix, not(target_os = "macos")))]
+        "xdotool" | "wtype" | "ydotool" => Ok(Box::new(crate::tools::ToolBackend::new(
+            name,
+            key_delay_ms,
+        )?)),
+        other => Err(format!("backend {other} is not available on this platform")),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn auto(key_delay_ms: u32) -> Result<Box<dyn Backend>, String> {
+    Ok(Box::new(crate::macos::QuartzBackend::new(key_delay_ms)?))
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn auto(key_delay_ms: u32) -> Result<Box<dyn Backend>, String> {
+    Ok(Box::new(crate::tools::ToolBackend::detect(key_delay_ms)?))
+}
+
+#[cfg(not(unix))]
+fn auto(_key_delay_ms: u32) -> Result<Box<dyn Backend>, String> {
+    Err("keystroke injection is not supported on this platform yet".into())
+}
@@ -0,0 +1,161 @@
+use serde_json::{json, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// For sessions where synthetic keystrokes are blocked (most Wayland
+// compositors, locked-down machines): the transcript goes on the clipboard
+// and the user pastes it. Same per-platform tools a shell user would reach for.
+pub struct Clipboard {
+    program: &'static str,
+    args: &'static [&'static str],
+    history: Option<History>,
+}
+
+impl Clipboard {
+    pub fn new(history: Option<History>) -> Result<Self, String> {
+        let (program, args) = detect()?;
+        Ok(Self {
+            program,
+            args,
+            history,
+        })
+    }
+
+    pub fn tool(&self) -> &'static str {
+        self.program
+    }
+
+    pub fn copy(&mut self, text: &str) -> Result<(), String> {
+        // xclip and wl-copy fork a helper that owns the selection until
+        // something else is copied; it must not hold any of our pipes open.
+        let mut child = Command::new(self.program)
+            .args(self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("failed to run {}: {e}", self.program))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(text.as_bytes())
+                .map_err(|e| format!("failed to write to {}: {e}", self.program))?;
+        }
+        let status = child
+            .wait()
+            .map_err(|e| format!("failed to wait for {}: {e}", self.program))?;
+        if !status.success() {
+            return Err(format!("{} exited with {status}", self.program));
+        }
+
+        if let Some(history) = self.history.as_ref() {
+            history.append(text)?;
+        }
+        Ok(())
+    }
+
+    pub fn history(&self, limit: usize) -> Result<Vec<Value>, String> {
+        match self.history.as_ref() {
+            Some(history) => {
+                let entries = history.read()?;
+                let skip = entries.len().saturating_sub(limit);
+                Ok(entries.into_iter().skip(skip).collect())
+            }
+            None => Err("clipboard history is off; start with --clipboard-history <path>".into()),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn detect() -> Result<(&'static str, &'static [&'static str]), String> {
+    Ok(("pbcopy", &[]))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn detect() -> Result<(&'static str, &'static [&'static str]), String> {
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let candidates: &[(&str, &[&str])] = if wayland {
+        &[("wl-copy", &[])]
+    } else {
+        &[
+            ("xclip", &["-selection", "clipboard"]),
+            ("xsel", &["--clipboard", "--input"]),
+        ]
+    };
+    candidates
+        .iter()
+        .find(|(program, _)| on_path(program))
+        .copied()
+        .ok_or_else(|| {
+            let names: Vec<&str> = candidates.iter().map(|(program, _)| *program).collect();
+            format!(
+                "no clipboard tool found; install one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(not(target_os = "macos"))]
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+// One JSON object per line, oldest first, capped at `limit` entries so the
+// file never grows without bound.
+pub struct History {
+    path: PathBuf,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(path: PathBuf, limit: usize) -> Self {
+        Self { path, limit }
+    }
+
+    fn read(&self) -> Result<Vec<Value>, String> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+        let contents = fs::read_to_string(&self.path)
+            .map_err(|e| format!("failed to read {}: {e}", self.path.display()))?;
+        // A line cut short by a crash is skipped rather than failing the copy.
+        Ok(contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    fn append(&self, text: &str) -> Result<(), String> {
+        let mut entries = self.read()?;
+        let at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        entries.push(json!({ "atMs": at_ms, "text": text }));
+        let skip = entries.len().saturating_sub(self.limit);
+
+        let mut contents = String::new();
+        for entry in &entries[skip..] {
+            contents.push_str(&entry.to_string());
+            contents.push('\n');
+        }
+        write_atomically(&self.path, &contents)
+    }
+}
+
+fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, path).map_err(|e| format!("failed to replace {}: {e}", path.display()))
+}
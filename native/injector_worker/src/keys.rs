@@ -0,0 +1,106 @@
+// Named keys and modifiers the worker can press, plus the spoken commands
+// that map onto them.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Enter,
+    Tab,
+    Escape,
+    Backspace,
+    Delete,
+    Space,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+impl Key {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let key = match name.trim().to_lowercase().replace(['_', '-'], " ").as_str() {
+            "enter" | "return" => Key::Enter,
+            "tab" => Key::Tab,
+            "escape" | "esc" => Key::Escape,
+            "backspace" | "back space" => Key::Backspace,
+            "delete" | "forward delete" => Key::Delete,
+            "space" => Key::Space,
+            "up" | "arrow up" | "up arrow" => Key::Up,
+            "down" | "arrow down" | "down arrow" => Key::Down,
+            "left" | "arrow left" | "left arrow" => Key::Left,
+            "right" | "arrow right" | "right arrow" => Key::Right,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "page up" | "pageup" => Key::PageUp,
+            "page down" | "pagedown" => Key::PageDown,
+            other => return Err(format!("unsupported key: {other}")),
+        };
+        Ok(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Shift,
+    Control,
+    Alt,
+    // Command on macOS, Super elsewhere.
+    Meta,
+}
+
+impl Modifier {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "shift" => Ok(Modifier::Shift),
+            "ctrl" | "control" => Ok(Modifier::Control),
+            "alt" | "option" => Ok(Modifier::Alt),
+            "meta" | "cmd" | "command" | "super" => Ok(Modifier::Meta),
+            other => Err(format!("unsupported modifier: {other}")),
+        }
+    }
+}
+
+// A key press with its modifiers held, repeated `count` times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub key: Key,
+    pub modifiers: Vec<Modifier>,
+    pub count: u32,
+}
+
+impl Chord {
+    pub fn new(key: Key, count: u32) -> Self {
+        Self {
+            key,
+            modifiers: Vec::new(),
+            count,
+        }
+    }
+}
+
+// Same phrases the host's spoken formatting understands for line breaks, plus
+// "press <key>" for anything with a name.
+pub fn spoken_command(phrase: &str) -> Result<Chord, String> {
+    let phrase = phrase
+        .trim()
+        .trim_end_matches(['.', '!', '?'])
+        .to_lowercase();
+    match phrase.as_str() {
+        "new line" | "newline" => return Ok(Chord::new(Key::Enter, 1)),
+        "new paragraph" => return Ok(Chord::new(Key::Enter, 2)),
+        _ => {}
+    }
+
+    let Some(name) = phrase
+        .strip_prefix("press ")
+        .or_else(|| phrase.strip_prefix("hit "))
+    else {
+        return Err(format!("unsupported command: {phrase}"));
+    };
+    let name = name.strip_prefix("the ").unwrap_or(name);
+    let name = name.strip_suffix(" key").unwrap_or(name);
+    Ok(Chord::new(Key::parse(name)?, 1))
+}
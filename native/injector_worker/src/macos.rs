@@ -0,0 +1,144 @@
+use std::os::raw::c_void;
+use std::thread;
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::keys::{Chord, Key, Modifier};
+
+// Minimal binding to Quartz event services; only the calls we use.
+type CGEventRef = *mut c_void;
+type CGEventSourceRef = *mut c_void;
+
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> bool;
+    fn CGEventSourceCreate(state: i32) -> CGEventSourceRef;
+    fn CGEventCreateKeyboardEvent(
+        source: CGEventSourceRef,
+        keycode: u16,
+        key_down: bool,
+    ) -> CGEventRef;
+    fn CGEventKeyboardSetUnicodeString(event: CGEventRef, length: usize, string: *const u16);
+    fn CGEventSetFlags(event: CGEventRef, flags: u64);
+    fn CGEventPost(tap: u32, event: CGEventRef);
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRelease(cf: *const c_void);
+}
+
+const HID_SYSTEM_STATE: i32 = 1;
+const HID_EVENT_TAP: u32 = 0;
+const FLAG_SHIFT: u64 = 0x0002_0000;
+const FLAG_CONTROL: u64 = 0x0004_0000;
+const FLAG_ALTERNATE: u64 = 0x0008_0000;
+const FLAG_COMMAND: u64 = 0x0010_0000;
+// Quartz drops anything past about 20 UTF-16 units on a single event.
+const MAX_UNITS_PER_EVENT: usize = 20;
+
+pub struct QuartzBackend {
+    source: CGEventSourceRef,
+    key_delay: Duration,
+}
+
+impl QuartzBackend {
+    pub fn new(key_delay_ms: u32) -> Result<Self, String> {
+        if !unsafe { AXIsProcessTrusted() } {
+            return Err("accessibility permission is required for keystroke injection".into());
+        }
+        let source = unsafe { CGEventSourceCreate(HID_SYSTEM_STATE) };
+        if source.is_null() {
+            return Err("unable to create CGEvent source".into());
+        }
+        Ok(Self {
+            source,
+            key_delay: Duration::from_millis(key_delay_ms as u64),
+        })
+    }
+
+    fn post(&self, keycode: u16, flags: u64, text: Option<&[u16]>) -> Result<(), String> {
+        for key_down in [true, false] {
+            let event = unsafe { CGEventCreateKeyboardEvent(self.source, keycode, key_down) };
+            if event.is_null() {
+                return Err("unable to create keyboard CGEvent".into());
+            }
+            unsafe {
+                if let Some(text) = text {
+                    CGEventKeyboardSetUnicodeString(event, text.len(), text.as_ptr());
+                }
+                CGEventSetFlags(event, flags);
+                CGEventPost(HID_EVENT_TAP, event);
+                CFRelease(event);
+            }
+        }
+        if !self.key_delay.is_zero() {
+            thread::sleep(self.key_delay);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for QuartzBackend {
+    fn drop(&mut self) {
+        unsafe { CFRelease(self.source) };
+    }
+}
+
+impl Backend for QuartzBackend {
+    fn name(&self) -> &'static str {
+        "macos"
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        let units: Vec<u16> = text.encode_utf16().collect();
+        let mut start = 0;
+        while start < units.len() {
+            let mut end = (start + MAX_UNITS_PER_EVENT).min(units.len());
+            // Never split a surrogate pair across two events.
+            if end < units.len() && (0xD800..0xDC00).contains(&units[end - 1]) {
+                end -= 1;
+            }
+            self.post(0, 0, Some(&units[start..end]))?;
+            start = end;
+        }
+        Ok(())
+    }
+
+    fn press(&mut self, chord: &Chord) -> Result<(), String> {
+        let flags = chord
+            .modifiers
+            .iter()
+            .map(|modifier| match modifier {
+                Modifier::Shift => FLAG_SHIFT,
+                Modifier::Control => FLAG_CONTROL,
+                Modifier::Alt => FLAG_ALTERNATE,
+                Modifier::Meta => FLAG_COMMAND,
+            })
+            .fold(0, |flags, flag| flags | flag);
+        for _ in 0..chord.count {
+            self.post(keycode(chord.key), flags, None)?;
+        }
+        Ok(())
+    }
+}
+
+// Virtual keycodes from HIToolbox's Events.h (ANSI layout).
+fn keycode(key: Key) -> u16 {
+    match key {
+        Key::Enter => 36,
+        Key::Tab => 48,
+        Key::Space => 49,
+        Key::Backspace => 51,
+        Key::Escape => 53,
+        Key::Delete => 117,
+        Key::Home => 115,
+        Key::PageUp => 116,
+        Key::End => 119,
+        Key::PageDown => 121,
+        Key::Left => 123,
+        Key::Right => 124,
+        Key::Down => 125,
+        Key::Up => 126,
+    }
+}
@@ -0,0 +1,327 @@
+mod backend;
+mod clipboard;
+mod keys;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(all(unix, not(target_os = "macos")))]
+mod tools;
+
+use backend::{type_with_keys, Backend};
+use clipboard::{Clipboard, History};
+use dingoflow_frame::{read_frame, write_response};
+use keys::{spoken_command, Chord, Key, Modifier};
+use serde::Deserialize;
+use serde_json::json;
+use std::io;
+use std::path::PathBuf;
+
+const MAX_REPEAT: u32 = 50;
+const DEFAULT_HISTORY_LIMIT: usize = 50;
+const DEFAULT_HISTORY_PAGE: usize = 20;
+
+// Types committed text into whatever app has focus. Requests use the same
+// frames as the ASR workers (the audio length is always 0) and get the same
+// length-prefixed JSON responses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Keys,
+    // `type` puts the text on the clipboard instead of typing it.
+    Clipboard,
+}
+
+#[derive(Debug)]
+struct Config {
+    backend: String,
+    key_delay_ms: u32,
+    output: Output,
+    clipboard_history: Option<PathBuf>,
+    history_limit: usize,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    text: Option<String>,
+    key: Option<String>,
+    modifiers: Option<Vec<String>>,
+    count: Option<u32>,
+    command: Option<String>,
+    limit: Option<usize>,
+}
+
+// The backend is opened on first use and retried after a failure, so a
+// permission granted while we're running takes effect without a restart.
+struct Injector {
+    backend_name: String,
+    key_delay_ms: u32,
+    backend: Option<Box<dyn Backend>>,
+    output: Output,
+    history_path: Option<PathBuf>,
+    history_limit: usize,
+    clipboard: Option<Clipboard>,
+}
+
+impl Injector {
+    fn backend(&mut self) -> Result<&mut Box<dyn Backend>, String> {
+        if self.backend.is_none() {
+            self.backend = Some(backend::select(&self.backend_name, self.key_delay_ms)?);
+        }
+        self.backend
+            .as_mut()
+            .ok_or_else(|| "backend unavailable".to_string())
+    }
+
+    fn clipboard(&mut self) -> Result<&mut Clipboard, String> {
+        if self.clipboard.is_none() {
+            let history = self
+                .history_path
+                .clone()
+                .map(|path| History::new(path, self.history_limit));
+            self.clipboard = Some(Clipboard::new(history)?);
+        }
+        self.clipboard
+            .as_mut()
+            .ok_or_else(|| "clipboard unavailable".to_string())
+    }
+
+    fn copy(&mut self, req: &Request) -> Result<serde_json::Value, String> {
+        let text = req.text.as_deref().ok_or("copy requires text")?;
+        self.clipboard()?.copy(text)?;
+        Ok(json!({ "copiedChars": text.chars().count(), "output": "clipboard" }))
+    }
+
+    fn handle(&mut self, req: &Request) -> Result<serde_json::Value, String> {
+        match req.action.as_deref().unwrap_or("type") {
+            "hello" if self.output == Output::Clipboard => {
+                let clipboard = self.clipboard()?;
+                Ok(json!({ "backend": "clipboard", "tool": clipboard.tool() }))
+            }
+            "hello" => {
+                let backend = self.backend()?;
+                Ok(json!({ "backend": backend.name() }))
+            }
+            "type" if self.output == Output::Clipboard => self.copy(req),
+            "copy" => self.copy(req),
+            "history" => {
+                let limit = req.limit.unwrap_or(DEFAULT_HISTORY_PAGE);
+                let entries = self.clipboard()?.history(limit)?;
+                Ok(json!({ "entries": entries }))
+            }
+            "type" => {
+                let text = req.text.as_deref().ok_or("type requires text")?;
+                let backend = self.backend()?;
+                type_with_keys(backend.as_mut(), text)?;
+                Ok(json!({ "typedChars": text.chars().count() }))
+            }
+            "key" => {
+                let key = Key::parse(req.key.as_deref().ok_or("key requires a key name")?)?;
+                let modifiers = req
+                    .modifiers
+                    .iter()
+                    .flatten()
+                    .map(|name| Modifier::parse(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let count = req.count.unwrap_or(1);
+                if !(1..=MAX_REPEAT).contains(&count) {
+                    return Err(format!("count must be between 1 and {MAX_REPEAT}"));
+                }
+                self.backend()?.press(&Chord {
+                    key,
+                    modifiers,
+                    count,
+                })?;
+                Ok(json!({ "pressed": true }))
+            }
+            "command" => {
+                let phrase = req.command.as_deref().ok_or("command requires a phrase")?;
+                let chord = spoken_command(phrase)?;
+                self.backend()?.press(&chord)?;
+                Ok(
+                    json!({ "key": format!("{:?}", chord.key).to_lowercase(), "count": chord.count }),
+                )
+            }
+            other => Err(format!("Unsupported action: {other}")),
+        }
+    }
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut backend = "auto".to_string();
+    let mut key_delay_ms = 0_u32;
+    let mut output = Output::Keys;
+    let mut clipboard_history: Option<PathBuf> = None;
+    let mut history_limit = DEFAULT_HISTORY_LIMIT;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--backend" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --backend".into());
+                }
+                backend = args[i + 1].clone();
+                i += 2;
+            }
+            "--key-delay-ms" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --key-delay-ms".into());
+                }
+                key_delay_ms = args[i + 1]
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|ms| *ms <= 200)
+                    .ok_or("--key-delay-ms must be between 0 and 200")?;
+                i += 2;
+            }
+            "--output" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --output".into());
+                }
+                output = match args[i + 1].as_str() {
+                    "keys" => Output::Keys,
+                    "clipboard" => Output::Clipboard,
+                    other => {
+                        return Err(format!(
+                            "Invalid --output value: {other} (expected keys or clipboard)"
+                        ))
+                    }
+                };
+                i += 2;
+            }
+            "--clipboard-history" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --clipboard-history".into());
+                }
+                clipboard_history = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--history-limit" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --history-limit".into());
+                }
+                history_limit = args[i + 1]
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|limit| (1..=10_000).contains(limit))
+                    .ok_or("--history-limit must be between 1 and 10000")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-injector-worker [--backend auto|macos|xdotool|wtype|ydotool] [--key-delay-ms 0] [--output keys|clipboard] [--clipboard-history <path.jsonl>] [--history-limit 50] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    Ok(Config {
+        backend,
+        key_delay_ms,
+        output,
+        clipboard_history,
+        history_limit,
+        serve,
+        healthcheck,
+    })
+}
+
+fn run_server(mut injector: Injector) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        if !frame.audio.is_empty() {
+            return Err(format!(
+                "unexpected audio payload: {} bytes",
+                frame.audio.len()
+            ));
+        }
+
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match injector.handle(&req) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut injector = Injector {
+        backend_name: cfg.backend,
+        key_delay_ms: cfg.key_delay_ms,
+        backend: None,
+        output: cfg.output,
+        history_path: cfg.clipboard_history,
+        history_limit: cfg.history_limit,
+        clipboard: None,
+    };
+
+    if cfg.healthcheck {
+        let ready = match injector.output {
+            Output::Keys => injector.backend().map(|backend| backend.name()),
+            Output::Clipboard => injector.clipboard().map(|clipboard| clipboard.tool()),
+        };
+        match ready {
+            Ok(name) => println!("ok {name}"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    if let Err(err) = run_server(injector) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
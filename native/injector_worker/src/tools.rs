@@ -0,0 +1,215 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::backend::Backend;
+use crate::keys::{Chord, Key, Modifier};
+
+// Linux has no single keystroke API: X11 sessions go through xdotool, Wayland
+// compositors that implement virtual-keyboard through wtype, and anything
+// else through ydotool's uinput daemon.
+#[derive(Debug, Clone, Copy)]
+enum Tool {
+    Xdotool,
+    Wtype,
+    Ydotool,
+}
+
+pub struct ToolBackend {
+    tool: Tool,
+    key_delay_ms: u32,
+}
+
+impl ToolBackend {
+    pub fn new(name: &str, key_delay_ms: u32) -> Result<Self, String> {
+        let tool = match name {
+            "xdotool" => Tool::Xdotool,
+            "wtype" => Tool::Wtype,
+            "ydotool" => Tool::Ydotool,
+            other => return Err(format!("unsupported backend: {other}")),
+        };
+        if !on_path(name) {
+            return Err(format!("{name} was not found on PATH"));
+        }
+        Ok(Self { tool, key_delay_ms })
+    }
+
+    pub fn detect(key_delay_ms: u32) -> Result<Self, String> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let candidates: &[&str] = if wayland {
+            &["wtype", "ydotool"]
+        } else {
+            &["xdotool", "ydotool"]
+        };
+        candidates
+            .iter()
+            .find(|name| on_path(name))
+            .map(|name| Self::new(name, key_delay_ms))
+            .unwrap_or_else(|| {
+                Err(format!(
+                    "no keystroke tool found; install one of {}",
+                    candidates.join(", ")
+                ))
+            })
+    }
+
+    fn run(&self, args: &[String]) -> Result<(), String> {
+        let program = self.name();
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .map_err(|e| format!("failed to run {program}: {e}"))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "{program} exited with {}: {}",
+                output.status,
+                stderr.trim()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Backend for ToolBackend {
+    fn name(&self) -> &'static str {
+        match self.tool {
+            Tool::Xdotool => "xdotool",
+            Tool::Wtype => "wtype",
+            Tool::Ydotool => "ydotool",
+        }
+    }
+
+    fn type_text(&mut self, text: &str) -> Result<(), String> {
+        let delay = self.key_delay_ms.to_string();
+        let args: Vec<String> = match self.tool {
+            Tool::Xdotool => vec![
+                "type".into(),
+                "--clearmodifiers".into(),
+                "--delay".into(),
+                delay,
+                "--".into(),
+                text.into(),
+            ],
+            Tool::Wtype => vec!["-d".into(), delay, "--".into(), text.into()],
+            Tool::Ydotool => vec![
+                "type".into(),
+                "--key-delay".into(),
+                delay,
+                "--".into(),
+                text.into(),
+            ],
+        };
+        self.run(&args)
+    }
+
+    fn press(&mut self, chord: &Chord) -> Result<(), String> {
+        let mut args: Vec<String> = Vec::new();
+        match self.tool {
+            Tool::Xdotool => {
+                args.extend(["key".into(), "--clearmodifiers".into()]);
+                let combo = chord
+                    .modifiers
+                    .iter()
+                    .map(|modifier| xdotool_modifier(*modifier))
+                    .chain([keysym(chord.key)])
+                    .collect::<Vec<_>>()
+                    .join("+");
+                args.extend((0..chord.count).map(|_| combo.clone()));
+            }
+            Tool::Wtype => {
+                for modifier in &chord.modifiers {
+                    args.extend(["-M".into(), wtype_modifier(*modifier).into()]);
+                }
+                for _ in 0..chord.count {
+                    args.extend(["-k".into(), keysym(chord.key).into()]);
+                }
+                for modifier in chord.modifiers.iter().rev() {
+                    args.extend(["-m".into(), wtype_modifier(*modifier).into()]);
+                }
+            }
+            // ydotool speaks raw evdev keycodes as code:1 (down) / code:0 (up).
+            Tool::Ydotool => {
+                args.push("key".into());
+                let held: Vec<u16> = chord.modifiers.iter().map(|m| evdev_modifier(*m)).collect();
+                args.extend(held.iter().map(|code| format!("{code}:1")));
+                for _ in 0..chord.count {
+                    let code = evdev_key(chord.key);
+                    args.extend([format!("{code}:1"), format!("{code}:0")]);
+                }
+                args.extend(held.iter().rev().map(|code| format!("{code}:0")));
+            }
+        }
+        self.run(&args)
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| Path::new(&dir).join(program).is_file())
+    })
+}
+
+fn keysym(key: Key) -> &'static str {
+    match key {
+        Key::Enter => "Return",
+        Key::Tab => "Tab",
+        Key::Escape => "Escape",
+        Key::Backspace => "BackSpace",
+        Key::Delete => "Delete",
+        Key::Space => "space",
+        Key::Up => "Up",
+        Key::Down => "Down",
+        Key::Left => "Left",
+        Key::Right => "Right",
+        Key::Home => "Home",
+        Key::End => "End",
+        Key::PageUp => "Prior",
+        Key::PageDown => "Next",
+    }
+}
+
+fn xdotool_modifier(modifier: Modifier) -> &'static str {
+    match modifier {
+        Modifier::Shift => "shift",
+        Modifier::Control => "ctrl",
+        Modifier::Alt => "alt",
+        Modifier::Meta => "super",
+    }
+}
+
+fn wtype_modifier(modifier: Modifier) -> &'static str {
+    match modifier {
+        Modifier::Shift => "shift",
+        Modifier::Control => "ctrl",
+        Modifier::Alt => "alt",
+        Modifier::Meta => "logo",
+    }
+}
+
+fn evdev_key(key: Key) -> u16 {
+    match key {
+        Key::Enter => 28,
+        Key::Tab => 15,
+        Key::Escape => 1,
+        Key::Backspace => 14,
+        Key::Delete => 111,
+        Key::Space => 57,
+        Key::Up => 103,
+        Key::Down => 108,
+        Key::Left => 105,
+        Key::Right => 106,
+        Key::Home => 102,
+        Key::End => 107,
+        Key::PageUp => 104,
+        Key::PageDown => 109,
+    }
+}
+
+fn evdev_modifier(modifier: Modifier) -> u16 {
+    match modifier {
+        Modifier::Shift => 42,
+        Modifier::Control => 29,
+        Modifier::Alt => 56,
+        Modifier::Meta => 125,
+    }
+}
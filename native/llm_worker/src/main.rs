@@ -0,0 +1,310 @@
+mod prompts;
+mod server;
+
+use dingoflow_frame::{read_frame, write_response};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use server::{LlamaServer, SpawnOptions};
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const MAX_TEXT_CHARS: usize = 50_000;
+
+// Polishes finished transcripts with a local GGUF model through llama.cpp.
+// Requests use the same frames as the ASR workers (the audio length is
+// always 0) and get the same length-prefixed JSON responses.
+#[derive(Debug)]
+struct Config {
+    model_path: Option<PathBuf>,
+    url: Option<String>,
+    llama_server: String,
+    ctx: u32,
+    threads: u32,
+    gpu_layers: u32,
+    max_tokens: u32,
+    temperature: f32,
+    startup_timeout_s: u64,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    text: Option<String>,
+    max_tokens: Option<u32>,
+    temperature: Option<f32>,
+}
+
+struct Worker {
+    server: LlamaServer,
+    max_tokens: u32,
+    temperature: f32,
+}
+
+impl Worker {
+    fn run(&mut self, action: &str, req: &Request) -> Result<Value, String> {
+        let system = prompts::system_prompt(action)
+            .ok_or_else(|| format!("Unsupported action: {action}"))?;
+        let text = req
+            .text
+            .as_deref()
+            .filter(|text| !text.trim().is_empty())
+            .ok_or_else(|| format!("{action} requires text"))?;
+        if text.chars().count() > MAX_TEXT_CHARS {
+            return Err(format!("text is longer than {MAX_TEXT_CHARS} characters"));
+        }
+        let max_tokens = req.max_tokens.unwrap_or(self.max_tokens);
+        if !(16..=8192).contains(&max_tokens) {
+            return Err("maxTokens must be between 16 and 8192".into());
+        }
+        let temperature = req.temperature.unwrap_or(self.temperature);
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err("temperature must be between 0 and 2".into());
+        }
+
+        let started = Instant::now();
+        let (generated, usage) = self.server.chat(
+            &system,
+            &prompts::user_prompt(text),
+            max_tokens,
+            temperature,
+        )?;
+        Ok(json!({
+            "text": prompts::extract_final(&generated),
+            "action": action,
+            "promptTokens": usage.get("prompt_tokens"),
+            "completionTokens": usage.get("completion_tokens"),
+            "elapsedMs": started.elapsed().as_millis() as u64,
+        }))
+    }
+
+    fn handle(&mut self, req: &Request) -> Result<Value, String> {
+        match req.action.as_deref().unwrap_or("cleanup") {
+            // Loads the weights into memory and fills the prompt cache.
+            "warmup" => {
+                self.server
+                    .chat("Reply with <final>ok</final>.", "ping", 16, 0.0)?;
+                Ok(json!({ "ready": true, "actions": prompts::ACTIONS }))
+            }
+            action => self.run(action, req),
+        }
+    }
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut model_path: Option<PathBuf> = None;
+    let mut url: Option<String> = None;
+    let mut llama_server = "llama-server".to_string();
+    let mut ctx = 4096_u32;
+    let mut threads = 4_u32;
+    let mut gpu_layers = 99_u32;
+    let mut max_tokens = 512_u32;
+    let mut temperature = 0.2_f32;
+    let mut startup_timeout_s = 120_u64;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--model" => {
+                model_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--url" => {
+                url = Some(value()?);
+                i += 2;
+            }
+            "--llama-server" => {
+                llama_server = value()?;
+                i += 2;
+            }
+            "--ctx" => {
+                ctx = value()?
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| (512..=131_072).contains(n))
+                    .ok_or("--ctx must be between 512 and 131072")?;
+                i += 2;
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| (1..=64).contains(n))
+                    .ok_or("--threads must be between 1 and 64")?;
+                i += 2;
+            }
+            "--gpu-layers" => {
+                gpu_layers = value()?
+                    .parse::<u32>()
+                    .map_err(|_| "--gpu-layers must be a whole number")?;
+                i += 2;
+            }
+            "--max-tokens" => {
+                max_tokens = value()?
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| (16..=8192).contains(n))
+                    .ok_or("--max-tokens must be between 16 and 8192")?;
+                i += 2;
+            }
+            "--temperature" => {
+                temperature = value()?
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|t| (0.0..=2.0).contains(t))
+                    .ok_or("--temperature must be between 0 and 2")?;
+                i += 2;
+            }
+            "--startup-timeout-s" => {
+                startup_timeout_s = value()?
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|s| (1..=1800).contains(s))
+                    .ok_or("--startup-timeout-s must be between 1 and 1800")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-llm-worker (--model <model.gguf> | --url http://127.0.0.1:8080) [--llama-server llama-server] [--ctx 4096] [--threads 4] [--gpu-layers 99] [--max-tokens 512] [--temperature 0.2] [--startup-timeout-s 120] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    if model_path.is_some() && url.is_some() {
+        return Err("use either --model or --url, not both".into());
+    }
+    if model_path.is_none() && url.is_none() && !healthcheck {
+        return Err("--model or --url is required unless --healthcheck is used".into());
+    }
+
+    Ok(Config {
+        model_path,
+        url,
+        llama_server,
+        ctx,
+        threads,
+        gpu_layers,
+        max_tokens,
+        temperature,
+        startup_timeout_s,
+        serve,
+        healthcheck,
+    })
+}
+
+fn run_server(mut worker: Worker) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        if !frame.audio.is_empty() {
+            return Err(format!(
+                "unexpected audio payload: {} bytes",
+                frame.audio.len()
+            ));
+        }
+
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match worker.handle(&req) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        println!("ok");
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    let startup_timeout = Duration::from_secs(cfg.startup_timeout_s);
+    let server = match (&cfg.url, &cfg.model_path) {
+        (Some(url), _) => LlamaServer::attach(url, startup_timeout),
+        (None, Some(model)) => LlamaServer::spawn(&SpawnOptions {
+            bin: &cfg.llama_server,
+            model,
+            ctx: cfg.ctx,
+            threads: cfg.threads,
+            gpu_layers: cfg.gpu_layers,
+            startup_timeout,
+        }),
+        (None, None) => Err("--model or --url is required".into()),
+    };
+    let server = match server {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let worker = Worker {
+        server,
+        max_tokens: cfg.max_tokens,
+        temperature: cfg.temperature,
+    };
+
+    // Returning (rather than exiting) drops the worker, which stops the
+    // llama-server we started.
+    if let Err(err) = run_server(worker) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
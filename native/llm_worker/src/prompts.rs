@@ -0,0 +1,48 @@
+// System prompts per action. Like the MLX formatter, the model is asked to
+// wrap its answer in <final> tags so any preamble it adds can be dropped.
+const BASE_RULES: &str = "You are an offline dictation post-processor. \
+Keep the language of the input unchanged. \
+Do not add facts that were not stated. \
+Return output only between <final> and </final> tags.";
+
+pub const ACTIONS: &[&str] = &["cleanup", "summarize", "format_as_email"];
+
+pub fn system_prompt(action: &str) -> Option<String> {
+    let rules = match action {
+        "cleanup" => {
+            "Task cleanup: Remove filler words, false starts and repetitions, repair \
+             punctuation and casing, and keep the same meaning, tone and wording otherwise."
+        }
+        "summarize" => {
+            "Task summarize: Summarize the transcript in a few short sentences, or short \
+             bullet points if it lists several items. Keep names, numbers and decisions."
+        }
+        "format_as_email" => {
+            "Task format_as_email: Turn the transcript into a ready-to-send email with a \
+             greeting, short paragraphs and a sign-off. Put a subject line first as \
+             'Subject: ...' when the transcript makes one clear."
+        }
+        _ => return None,
+    };
+    Some(format!("{BASE_RULES} {rules}"))
+}
+
+pub fn user_prompt(transcript: &str) -> String {
+    format!(
+        "Input transcript starts now.\n\n{}\n\nProduce final output now.",
+        transcript.trim()
+    )
+}
+
+pub fn extract_final(generated: &str) -> String {
+    let lower = generated.to_ascii_lowercase();
+    let Some(start) = lower.find("<final>") else {
+        return generated.trim().to_string();
+    };
+    let body = &generated[start + "<final>".len()..];
+    let end = body
+        .to_ascii_lowercase()
+        .find("</final>")
+        .unwrap_or(body.len());
+    body[..end].trim().to_string()
+}
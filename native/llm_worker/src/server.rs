@@ -0,0 +1,224 @@
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Runs llama.cpp's `llama-server` on a private localhost port (or attaches
+// to one the user already runs) and talks to its OpenAI-style chat endpoint,
+// which applies the GGUF's own chat template.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(2);
+const GENERATE_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct LlamaServer {
+    host: String,
+    child: Option<Child>,
+}
+
+pub struct SpawnOptions<'a> {
+    pub bin: &'a str,
+    pub model: &'a Path,
+    pub ctx: u32,
+    pub threads: u32,
+    pub gpu_layers: u32,
+    pub startup_timeout: Duration,
+}
+
+impl LlamaServer {
+    pub fn spawn(options: &SpawnOptions) -> Result<Self, String> {
+        if !options.model.is_file() {
+            return Err(format!("model not found: {}", options.model.display()));
+        }
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .map(|addr| addr.port())
+            .map_err(|e| format!("failed to pick a port for llama-server: {e}"))?;
+        // Our stdout carries responses, so the server's stdout goes nowhere;
+        // its log on stderr ends up in ours.
+        let child = Command::new(options.bin)
+            .arg("--model")
+            .arg(options.model)
+            .args(["--host", "127.0.0.1", "--port", &port.to_string()])
+            .args(["--ctx-size", &options.ctx.to_string()])
+            .args(["--threads", &options.threads.to_string()])
+            .args(["--n-gpu-layers", &options.gpu_layers.to_string()])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| format!("failed to run {}: {e}", options.bin))?;
+
+        let mut server = Self {
+            host: format!("127.0.0.1:{port}"),
+            child: Some(child),
+        };
+        server.wait_ready(options.startup_timeout)?;
+        Ok(server)
+    }
+
+    pub fn attach(url: &str, startup_timeout: Duration) -> Result<Self, String> {
+        let host = url
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+            .to_string();
+        if host.contains('/') || url.starts_with("https://") {
+            return Err(format!("--url must look like http://host:port, got {url}"));
+        }
+        let mut server = Self { host, child: None };
+        server.wait_ready(startup_timeout)?;
+        Ok(server)
+    }
+
+    // /health answers 503 while the model is still loading.
+    fn wait_ready(&mut self, timeout: Duration) -> Result<(), String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(child) = self.child.as_mut() {
+                if let Ok(Some(status)) = child.try_wait() {
+                    return Err(format!("llama-server exited during startup with {status}"));
+                }
+            }
+            if let Ok((200, _)) = self.request("GET", "/health", None, HEALTH_TIMEOUT) {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "llama-server at {} was not ready within {}s",
+                    self.host,
+                    timeout.as_secs()
+                ));
+            }
+            thread::sleep(Duration::from_millis(250));
+        }
+    }
+
+    pub fn chat(
+        &mut self,
+        system: &str,
+        user: &str,
+        max_tokens: u32,
+        temperature: f32,
+    ) -> Result<(String, Value), String> {
+        let body = serde_json::json!({
+            "messages": [
+                { "role": "system", "content": system },
+                { "role": "user", "content": user },
+            ],
+            "max_tokens": max_tokens,
+            "temperature": temperature,
+            "cache_prompt": true,
+        });
+        let (status, response) = self.request(
+            "POST",
+            "/v1/chat/completions",
+            Some(&body),
+            GENERATE_TIMEOUT,
+        )?;
+        let response: Value = serde_json::from_slice(&response)
+            .map_err(|e| format!("invalid response from llama-server: {e}"))?;
+        if status != 200 {
+            let message = response
+                .pointer("/error/message")
+                .and_then(Value::as_str)
+                .unwrap_or("request failed");
+            return Err(format!("llama-server returned {status}: {message}"));
+        }
+        let content = response
+            .pointer("/choices/0/message/content")
+            .and_then(Value::as_str)
+            .ok_or("llama-server response had no message content")?;
+        let usage = response.get("usage").cloned().unwrap_or(Value::Null);
+        Ok((content.to_string(), usage))
+    }
+
+    fn request(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&Value>,
+        timeout: Duration,
+    ) -> Result<(u16, Vec<u8>), String> {
+        let mut stream = TcpStream::connect(&self.host)
+            .map_err(|e| format!("failed to reach llama-server at {}: {e}", self.host))?;
+        stream
+            .set_read_timeout(Some(timeout))
+            .map_err(|e| format!("failed to configure llama-server socket: {e}"))?;
+        let payload = body.map(Value::to_string).unwrap_or_default();
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{payload}",
+            self.host,
+            payload.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("failed to send to llama-server: {e}"))?;
+        read_response(stream).map_err(|e| format!("failed to read from llama-server: {e}"))
+    }
+}
+
+impl Drop for LlamaServer {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_response(stream: TcpStream) -> std::io::Result<(u16, Vec<u8>)> {
+    let invalid = |msg: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string());
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| invalid("malformed status line"))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        reader.read_line(&mut line)?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.eq_ignore_ascii_case("chunked");
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| invalid("malformed chunk size"))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = content_length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+    Ok((status, body))
+}
@@ -0,0 +1,90 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::sha256;
+use crate::store::part_path;
+
+// Downloads go through curl: it follows Hugging Face and GitHub redirects,
+// retries, and resumes into `<file>.part` with `-C -`, so an interrupted
+// multi-gigabyte download picks up where it stopped.
+const CURL_RANGE_ERROR: i32 = 33;
+
+pub struct Fetched {
+    pub sha256: String,
+    pub size: u64,
+}
+
+pub fn fetch(
+    url: &str,
+    target: &Path,
+    expected_sha256: &str,
+    expected_size: u64,
+    show_progress: bool,
+) -> Result<Fetched, String> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let part = part_path(target);
+
+    let mut code = curl(url, &part, true, show_progress)?;
+    // The server ignored the range request; start the file over.
+    if code == Some(CURL_RANGE_ERROR) {
+        let _ = fs::remove_file(&part);
+        code = curl(url, &part, false, show_progress)?;
+    }
+    if code != Some(0) {
+        return Err(match code {
+            Some(code) => format!("curl failed with exit code {code} for {url}"),
+            None => format!("curl was interrupted while fetching {url}"),
+        });
+    }
+
+    let size = fs::metadata(&part)
+        .map_err(|e| format!("failed to stat {}: {e}", part.display()))?
+        .len();
+    if size != expected_size {
+        let _ = fs::remove_file(&part);
+        return Err(format!(
+            "size mismatch for {url}: expected {expected_size} bytes, got {size}"
+        ));
+    }
+    let sha256 = sha256::file_hex(&part)?;
+    if !expected_sha256.eq_ignore_ascii_case(&sha256) {
+        let _ = fs::remove_file(&part);
+        return Err(format!(
+            "checksum mismatch for {url}: expected {expected_sha256}, got {sha256}"
+        ));
+    }
+
+    fs::rename(&part, target)
+        .map_err(|e| format!("failed to move {} into place: {e}", target.display()))?;
+    Ok(Fetched { sha256, size })
+}
+
+fn curl(url: &str, part: &Path, resume: bool, show_progress: bool) -> Result<Option<i32>, String> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--fail", "--location", "--retry", "3", "--retry-delay", "2"])
+        .arg(if show_progress {
+            "--progress-bar"
+        } else {
+            "--silent"
+        })
+        .arg("--show-error");
+    if resume {
+        command.args(["--continue-at", "-"]);
+    }
+    command
+        .arg("--output")
+        .arg(part)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit());
+    let status = command
+        .status()
+        .map_err(|e| format!("failed to run curl: {e}"))?;
+    Ok(status.code())
+}
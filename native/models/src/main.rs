@@ -0,0 +1,456 @@
+mod fetch;
+mod manifest;
+mod sha256;
+mod store;
+
+use manifest::{Manifest, Model};
+use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use store::{part_path, InstalledFile, InstalledModel, Status, Store};
+
+// One place that knows where models come from and what is installed, so the
+// download scripts and the host stop each carrying their own copy of the
+// URLs. `--json` makes every command machine-readable for the host.
+#[derive(Debug)]
+struct Config {
+    command: Command,
+    models_dir: PathBuf,
+    manifest_path: Option<PathBuf>,
+    json: bool,
+}
+
+#[derive(Debug)]
+enum Command {
+    List,
+    Download { ids: Vec<String>, force: bool },
+    Verify { ids: Vec<String> },
+    Prune { dry_run: bool },
+    Path { id: String },
+}
+
+struct Output {
+    json: bool,
+}
+
+impl Output {
+    // Progress during downloads, one JSON line per step with `event` first
+    // like the workers' event lines.
+    fn event(&self, event: &str, fields: Value, human: impl FnOnce() -> String) {
+        if !self.json {
+            eprintln!("{}", human());
+            return;
+        }
+        let fields = fields.to_string();
+        if fields == "{}" {
+            println!("{{\"event\":{}}}", Value::from(event));
+        } else {
+            println!("{{\"event\":{},{}", Value::from(event), &fields[1..]);
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+fn list(manifest: &Manifest, store: &Store, out: &Output) {
+    let rows: Vec<Value> = manifest
+        .models
+        .iter()
+        .map(|model| {
+            let (status, installed) = store.status(model);
+            let pinned = model.files.iter().all(|file| file.pin(&model.id).is_ok());
+            json!({
+                "id": model.id,
+                "kind": model.kind,
+                "version": model.version,
+                "status": status.name(),
+                "pinned": pinned,
+                "installedVersion": installed.map(|i| i.version),
+                "path": store.root().join(&model.dir),
+            })
+        })
+        .collect();
+
+    if out.json {
+        println!("{}", json!({ "models": rows }));
+        return;
+    }
+    for row in &rows {
+        let installed = match row["installedVersion"].as_str() {
+            Some(v) if Some(v) != row["version"].as_str() => format!(" (have {v})"),
+            _ => String::new(),
+        };
+        let unpinned = if row["pinned"] == true {
+            ""
+        } else {
+            " (unpinned)"
+        };
+        println!(
+            "{:<32} {:<8} {:<10} {}{installed}{unpinned}",
+            row["id"].as_str().unwrap_or_default(),
+            row["kind"].as_str().unwrap_or_default(),
+            row["status"].as_str().unwrap_or_default(),
+            row["version"].as_str().unwrap_or_default(),
+        );
+    }
+}
+
+fn download(model: &Model, store: &Store, force: bool, out: &Output) -> Result<(), String> {
+    let (status, _) = store.status(model);
+    if status == Status::Installed && !force {
+        out.event(
+            "upToDate",
+            json!({ "id": model.id, "version": model.version }),
+            || format!("{} {} is already installed", model.id, model.version),
+        );
+        return Ok(());
+    }
+
+    // Checked for every file before fetching any, so an unpinned entry
+    // doesn't leave half a model behind.
+    let pins = model
+        .files
+        .iter()
+        .map(|file| file.pin(&model.id))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut files = Vec::with_capacity(model.files.len());
+    for (file, pin) in model.files.iter().zip(pins) {
+        let target = store.file_path(&model.dir, &file.name);
+
+        // A file that's already on disk and matches the pins, e.g. from one
+        // of the old download scripts, is adopted as-is.
+        if !force && target.is_file() {
+            let size = fs::metadata(&target)
+                .map_err(|e| format!("failed to stat {}: {e}", target.display()))?
+                .len();
+            if size == pin.size {
+                let actual = sha256::file_hex(&target)?;
+                if actual.eq_ignore_ascii_case(pin.sha256) {
+                    out.event(
+                        "fileReused",
+                        json!({ "id": model.id, "file": file.name, "bytes": size }),
+                        || format!("{}: {} already present", model.id, file.name),
+                    );
+                    files.push(InstalledFile {
+                        name: file.name.clone(),
+                        url: file.url.clone(),
+                        sha256: actual,
+                        size,
+                    });
+                    continue;
+                }
+            }
+        }
+
+        out.event(
+            "fileStarted",
+            json!({ "id": model.id, "file": file.name, "url": file.url }),
+            || format!("{}: fetching {}", model.id, file.name),
+        );
+        let fetched = fetch::fetch(&file.url, &target, pin.sha256, pin.size, !out.json)?;
+        out.event(
+            "fileDone",
+            json!({
+                "id": model.id,
+                "file": file.name,
+                "bytes": fetched.size,
+                "sha256": fetched.sha256,
+            }),
+            || format!("{}: {} ({} bytes)", model.id, file.name, fetched.size),
+        );
+        files.push(InstalledFile {
+            name: file.name.clone(),
+            url: file.url.clone(),
+            sha256: fetched.sha256,
+            size: fetched.size,
+        });
+    }
+
+    store.record(&InstalledModel {
+        id: model.id.clone(),
+        version: model.version.clone(),
+        dir: model.dir.clone(),
+        files,
+        installed_at_ms: now_ms(),
+    })?;
+    let path = store.root().join(&model.dir);
+    out.event(
+        "installed",
+        json!({ "id": model.id, "version": model.version, "path": path }),
+        || {
+            format!(
+                "Installed {} {} at {}",
+                model.id,
+                model.version,
+                path.display()
+            )
+        },
+    );
+    Ok(())
+}
+
+// Re-hashes installed files against what was recorded at install time.
+fn verify(model: &Model, store: &Store, out: &Output) -> Result<bool, String> {
+    let installed = store
+        .installed()
+        .into_iter()
+        .find(|i| i.id == model.id && i.version == model.version)
+        .ok_or_else(|| format!("{} {} is not installed", model.id, model.version))?;
+
+    let mut ok = true;
+    let mut files = Vec::with_capacity(installed.files.len());
+    for file in &installed.files {
+        let path = store.file_path(&installed.dir, &file.name);
+        let actual = if path.is_file() {
+            Some(sha256::file_hex(&path)?)
+        } else {
+            None
+        };
+        let matches = actual.as_deref() == Some(file.sha256.as_str());
+        ok &= matches;
+        if !out.json {
+            let state = match (&actual, matches) {
+                (None, _) => "missing",
+                (Some(_), true) => "ok",
+                (Some(_), false) => "MISMATCH",
+            };
+            println!("{:<8} {}/{}", state, model.id, file.name);
+        }
+        files.push(json!({
+            "name": file.name,
+            "ok": matches,
+            "expected": file.sha256,
+            "actual": actual,
+        }));
+    }
+    if out.json {
+        println!(
+            "{}",
+            json!({ "id": model.id, "version": model.version, "ok": ok, "files": files })
+        );
+    }
+    Ok(ok)
+}
+
+// Deletes files from versions the manifest no longer points at, plus
+// half-finished `.part` downloads. Files still claimed by a current install
+// (the same name in the same dir across versions) are kept.
+fn prune(manifest: &Manifest, store: &Store, dry_run: bool, out: &Output) -> Result<(), String> {
+    let installed = store.installed();
+    let is_current = |i: &InstalledModel| {
+        manifest
+            .models
+            .iter()
+            .any(|m| m.id == i.id && m.version == i.version)
+    };
+    let keep: HashSet<PathBuf> = installed
+        .iter()
+        .filter(|i| is_current(i))
+        .flat_map(|i| i.files.iter().map(|f| store.file_path(&i.dir, &f.name)))
+        .collect();
+
+    let mut removed: Vec<PathBuf> = Vec::new();
+    let mut freed = 0_u64;
+    let mut remove = |path: PathBuf| -> Result<(), String> {
+        let Ok(meta) = fs::metadata(&path) else {
+            return Ok(());
+        };
+        if !dry_run {
+            fs::remove_file(&path)
+                .map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+        }
+        freed += meta.len();
+        removed.push(path);
+        Ok(())
+    };
+
+    let mut forgotten = Vec::new();
+    for old in installed.iter().filter(|i| !is_current(i)) {
+        for file in &old.files {
+            let path = store.file_path(&old.dir, &file.name);
+            if !keep.contains(&path) {
+                remove(path)?;
+            }
+        }
+        if !dry_run {
+            store.forget(&old.id, &old.version)?;
+        }
+        forgotten.push(format!("{}@{}", old.id, old.version));
+    }
+    for model in &manifest.models {
+        for file in &model.files {
+            remove(part_path(&store.file_path(&model.dir, &file.name)))?;
+        }
+    }
+
+    if out.json {
+        println!(
+            "{}",
+            json!({
+                "dryRun": dry_run,
+                "versions": forgotten,
+                "removed": removed,
+                "freedBytes": freed,
+            })
+        );
+    } else {
+        let verb = if dry_run { "Would remove" } else { "Removed" };
+        for path in &removed {
+            println!("{verb} {}", path.display());
+        }
+        println!("{verb} {} file(s), {freed} bytes", removed.len());
+    }
+    Ok(())
+}
+
+fn run(config: &Config) -> Result<bool, String> {
+    let manifest = Manifest::load(config.manifest_path.as_deref())?;
+    let store = Store::new(config.models_dir.clone());
+    let out = Output { json: config.json };
+
+    match &config.command {
+        Command::List => list(&manifest, &store, &out),
+        Command::Download { ids, force } => {
+            for id in ids {
+                let model = manifest.find(id)?;
+                if let Err(err) = download(model, &store, *force, &out) {
+                    if out.json {
+                        out.event("error", json!({ "id": id, "error": err }), String::new);
+                        return Ok(false);
+                    }
+                    return Err(err);
+                }
+            }
+        }
+        Command::Verify { ids } => {
+            let mut ok = true;
+            for id in ids {
+                ok &= verify(manifest.find(id)?, &store, &out)?;
+            }
+            return Ok(ok);
+        }
+        Command::Prune { dry_run } => prune(&manifest, &store, *dry_run, &out)?,
+        Command::Path { id } => {
+            let model = manifest.find(id)?;
+            println!("{}", store.root().join(&model.dir).display());
+        }
+    }
+    Ok(true)
+}
+
+fn usage() -> &'static str {
+    "usage: dingoflow-models [--models-dir DIR] [--manifest FILE] [--json] <command>\n\
+     commands:\n  \
+       list\n  \
+       download <id>... [--force]\n  \
+       verify <id>...\n  \
+       prune [--dry-run]\n  \
+       path <id>"
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut models_dir = PathBuf::from("models");
+    let mut manifest_path = std::env::var_os("DINGOFLOW_MODELS_MANIFEST").map(PathBuf::from);
+    let mut json = false;
+    let mut force = false;
+    let mut dry_run = false;
+    let mut positional: Vec<String> = Vec::new();
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--models-dir" => {
+                models_dir = PathBuf::from(value()?);
+                i += 2;
+            }
+            "--manifest" => {
+                manifest_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--json" => {
+                json = true;
+                i += 1;
+            }
+            "--force" => {
+                force = true;
+                i += 1;
+            }
+            "--dry-run" => {
+                dry_run = true;
+                i += 1;
+            }
+            "--help" | "-h" => return Err(usage().to_string()),
+            _ if flag.starts_with("--") => return Err(format!("Unknown argument: {flag}")),
+            _ => {
+                positional.push(flag.to_string());
+                i += 1;
+            }
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match positional.next().as_deref() {
+        Some("list") | None => Command::List,
+        Some("download") => Command::Download {
+            ids: positional.by_ref().collect(),
+            force,
+        },
+        Some("verify") => Command::Verify {
+            ids: positional.by_ref().collect(),
+        },
+        Some("prune") => Command::Prune { dry_run },
+        Some("path") => Command::Path {
+            id: positional.next().ok_or("path needs a model id")?,
+        },
+        Some(other) => return Err(format!("Unknown command: {other}\n{}", usage())),
+    };
+    if let Command::Download { ids, .. } | Command::Verify { ids } = &command {
+        if ids.is_empty() {
+            return Err("expected at least one model id".to_string());
+        }
+    }
+    if let Some(extra) = positional.next() {
+        return Err(format!("Unexpected argument: {extra}"));
+    }
+
+    Ok(Config {
+        command,
+        models_dir,
+        manifest_path,
+        json,
+    })
+}
+
+fn main() {
+    let config = match parse_args() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+    match run(&config) {
+        Ok(true) => {}
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path};
+
+// The catalog of downloadable models. The built-in list ships with the
+// binary; a user manifest adds entries or replaces built-ins with the same
+// id (a pinned mirror, a different voice, a checksum). `dir` is relative to
+// the models directory, "." meaning the files sit directly in it. Only
+// pinned entries download; `list` shows which are. The built-in entries
+// aren't pinned yet, so scripts/download_*.sh still fetch those models.
+const BUILTIN: &str = include_str!("../manifest.json");
+
+#[derive(Deserialize, Clone)]
+pub struct Manifest {
+    pub models: Vec<Model>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct Model {
+    pub id: String,
+    pub kind: String,
+    pub version: String,
+    pub dir: String,
+    pub files: Vec<ModelFile>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ModelFile {
+    pub name: String,
+    pub url: String,
+    // Every file is downloaded against all three pins; see ModelFile::pin.
+    pub sha256: Option<String>,
+    pub size: Option<u64>,
+}
+
+// What a download is checked against.
+pub struct Pin<'a> {
+    pub sha256: &'a str,
+    pub size: u64,
+}
+
+impl ModelFile {
+    // A file without its checksum, its size and a URL at a fixed revision
+    // could be anything the host serves today, so it isn't downloaded. A
+    // branch like resolve/main moves under the checksum and breaks it anyway.
+    pub fn pin(&self, model: &str) -> Result<Pin<'_>, String> {
+        let unpinned = |what: &str| format!("{model}: {} is not pinned: {what}", self.name);
+        let sha256 = self
+            .sha256
+            .as_deref()
+            .ok_or_else(|| unpinned("no sha256"))?;
+        let size = self.size.ok_or_else(|| unpinned("no size"))?;
+        if !is_revision_pinned(&self.url) {
+            return Err(unpinned("the url names a branch, not a commit"));
+        }
+        Ok(Pin { sha256, size })
+    }
+}
+
+impl Manifest {
+    pub fn load(user: Option<&Path>) -> Result<Self, String> {
+        let mut manifest: Manifest = serde_json::from_str(BUILTIN)
+            .map_err(|e| format!("built-in manifest is invalid: {e}"))?;
+        if let Some(path) = user {
+            let raw = fs::read_to_string(path)
+                .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+            let extra: Manifest = serde_json::from_str(&raw)
+                .map_err(|e| format!("invalid manifest {}: {e}", path.display()))?;
+            for model in extra.models {
+                match manifest.models.iter_mut().find(|m| m.id == model.id) {
+                    Some(existing) => *existing = model,
+                    None => manifest.models.push(model),
+                }
+            }
+        }
+        for model in &manifest.models {
+            model.validate()?;
+        }
+        Ok(manifest)
+    }
+
+    pub fn find(&self, id: &str) -> Result<&Model, String> {
+        self.models.iter().find(|m| m.id == id).ok_or_else(|| {
+            let ids: Vec<&str> = self.models.iter().map(|m| m.id.as_str()).collect();
+            format!("unknown model {id}; known models: {}", ids.join(", "))
+        })
+    }
+}
+
+impl Model {
+    fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty() || self.id.contains(['/', '\\', '@']) {
+            return Err(format!("invalid model id {:?}", self.id));
+        }
+        if self.files.is_empty() {
+            return Err(format!("{} lists no files", self.id));
+        }
+        if !is_plain_relative(Path::new(&self.dir)) {
+            return Err(format!(
+                "{}: dir must stay inside the models directory",
+                self.id
+            ));
+        }
+        for file in &self.files {
+            if !is_plain_relative(Path::new(&file.name)) || file.name.is_empty() {
+                return Err(format!("{}: invalid file name {:?}", self.id, file.name));
+            }
+            if let Some(sha) = &file.sha256 {
+                if sha.len() != 64 || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("{}: {} has a malformed sha256", self.id, file.name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Hugging Face `resolve/<rev>/` and GitHub `raw/<rev>/` (or
+// raw.githubusercontent.com) URLs must name a full commit hash. Release
+// assets and other hosts are held by the checksum alone.
+fn is_revision_pinned(url: &str) -> bool {
+    let is_commit = |rev: Option<&str>| {
+        rev.is_some_and(|rev| rev.len() == 40 && rev.chars().all(|c| c.is_ascii_hexdigit()))
+    };
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let segments: Vec<&str> = path.split('/').collect();
+    if segments[0] == "raw.githubusercontent.com" {
+        // host/owner/repo/rev/...
+        return is_commit(segments.get(3).copied());
+    }
+    match segments
+        .iter()
+        .position(|segment| *segment == "resolve" || *segment == "raw")
+    {
+        Some(at) => is_commit(segments.get(at + 1).copied()),
+        None => true,
+    }
+}
+
+fn is_plain_relative(path: &Path) -> bool {
+    path.components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "3535fa6548036ea653b681ab59e6ec04f0a44efa40b43815a7d0e2940d15319b";
+    const COMMIT: &str = "5359861c739e955e79d9a303bcbc70fb988958b1";
+
+    fn file(url: &str, sha256: Option<&str>, size: Option<u64>) -> ModelFile {
+        ModelFile {
+            name: "model.onnx".into(),
+            url: url.into(),
+            sha256: sha256.map(str::to_string),
+            size,
+        }
+    }
+
+    fn pinned(url: &str) -> bool {
+        file(url, Some(SHA), Some(5000)).pin("m").is_ok()
+    }
+
+    #[test]
+    fn a_pin_needs_the_checksum_and_the_size() {
+        let url = format!("https://huggingface.co/o/r/resolve/{COMMIT}/model.onnx");
+        let pinned = file(&url, Some(SHA), Some(5000));
+        let pin = pinned.pin("m").unwrap();
+        assert_eq!((pin.sha256, pin.size), (SHA, 5000));
+
+        let err = file(&url, None, Some(5000)).pin("m").err().unwrap();
+        assert_eq!(err, "m: model.onnx is not pinned: no sha256");
+        let err = file(&url, Some(SHA), None).pin("m").err().unwrap();
+        assert_eq!(err, "m: model.onnx is not pinned: no size");
+    }
+
+    #[test]
+    fn branch_urls_are_not_pinned() {
+        assert!(pinned(&format!(
+            "https://huggingface.co/o/r/resolve/{COMMIT}/a.onnx"
+        )));
+        assert!(pinned(&format!(
+            "https://github.com/o/r/raw/{COMMIT}/a.onnx"
+        )));
+        assert!(pinned(&format!(
+            "https://raw.githubusercontent.com/o/r/{COMMIT}/a.onnx"
+        )));
+        assert!(pinned("https://github.com/o/r/releases/download/v1/a.onnx"));
+
+        assert!(!pinned("https://huggingface.co/o/r/resolve/main/a.onnx"));
+        assert!(!pinned("https://github.com/o/r/raw/master/a.onnx"));
+        assert!(!pinned("https://raw.githubusercontent.com/o/r/main/a.onnx"));
+        assert!(!pinned(&format!(
+            "https://huggingface.co/o/r/resolve/{}/a.onnx",
+            &COMMIT[..12]
+        )));
+    }
+}
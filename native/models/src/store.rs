@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::manifest::Model;
+
+// What was actually installed, one record per id@version under
+// <models>/.dingoflow-state. Only files named in a record are ever deleted,
+// so models placed by hand are never touched.
+const STATE_DIR: &str = ".dingoflow-state";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledModel {
+    pub id: String,
+    pub version: String,
+    pub dir: String,
+    pub files: Vec<InstalledFile>,
+    pub installed_at_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InstalledFile {
+    pub name: String,
+    pub url: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Installed,
+    // An older version is installed and the manifest has moved on.
+    Outdated,
+    // Files are there but weren't installed by us, e.g. by an older script.
+    Unmanaged,
+    Partial,
+    Missing,
+}
+
+impl Status {
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Installed => "installed",
+            Status::Outdated => "outdated",
+            Status::Unmanaged => "unmanaged",
+            Status::Partial => "partial",
+            Status::Missing => "missing",
+        }
+    }
+}
+
+pub struct Store {
+    root: PathBuf,
+}
+
+impl Store {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn file_path(&self, dir: &str, name: &str) -> PathBuf {
+        self.root.join(dir).join(name)
+    }
+
+    fn state_path(&self, id: &str, version: &str) -> PathBuf {
+        self.root
+            .join(STATE_DIR)
+            .join(format!("{id}@{}.json", version.replace(['/', '\\'], "_")))
+    }
+
+    pub fn installed(&self) -> Vec<InstalledModel> {
+        let Ok(entries) = fs::read_dir(self.root.join(STATE_DIR)) else {
+            return Vec::new();
+        };
+        let mut installed: Vec<InstalledModel> = entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|raw| serde_json::from_str(&raw).ok())
+            .collect();
+        installed.sort_by(|a, b| (&a.id, &a.version).cmp(&(&b.id, &b.version)));
+        installed
+    }
+
+    pub fn record(&self, model: &InstalledModel) -> Result<(), String> {
+        let path = self.state_path(&model.id, &model.version);
+        let contents = serde_json::to_string_pretty(model)
+            .map_err(|e| format!("failed to encode install record: {e}"))?;
+        write_atomically(&path, &contents)
+    }
+
+    pub fn forget(&self, id: &str, version: &str) -> Result<(), String> {
+        let path = self.state_path(id, version);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(format!("failed to remove {}: {e}", path.display()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn status(&self, model: &Model) -> (Status, Option<InstalledModel>) {
+        let installed = self.installed();
+        let current = installed
+            .iter()
+            .find(|i| i.id == model.id && i.version == model.version);
+        if let Some(current) = current {
+            let intact = current.files.iter().all(|file| {
+                fs::metadata(self.file_path(&current.dir, &file.name))
+                    .is_ok_and(|meta| meta.len() == file.size)
+            });
+            let status = if intact {
+                Status::Installed
+            } else {
+                Status::Partial
+            };
+            return (status, Some(current.clone()));
+        }
+        if let Some(older) = installed.iter().find(|i| i.id == model.id) {
+            return (Status::Outdated, Some(older.clone()));
+        }
+
+        let present = model
+            .files
+            .iter()
+            .filter(|file| self.file_path(&model.dir, &file.name).is_file())
+            .count();
+        let downloading = model
+            .files
+            .iter()
+            .any(|file| part_path(&self.file_path(&model.dir, &file.name)).is_file());
+        let status = if present == model.files.len() {
+            Status::Unmanaged
+        } else if present > 0 || downloading {
+            Status::Partial
+        } else {
+            Status::Missing
+        };
+        (status, None)
+    }
+}
+
+pub fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(".part");
+    PathBuf::from(part)
+}
+
+fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, path).map_err(|e| format!("failed to replace {}: {e}", path.display()))
+}
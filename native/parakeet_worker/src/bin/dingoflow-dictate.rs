@@ -0,0 +1,130 @@
+use dingoflow_parakeet_worker::engine::{check_model_dir, load_engine, EngineConfig};
+use dingoflow_parakeet_worker::mic::{print_commit, run_mic};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// Terminal dictation without the host: the default mic (or --device), mixed to
+// mono and resampled to 16 kHz through audio_loop's capture code, fed straight
+// into the streaming engine. Committed text is printed as it settles, or typed
+// into the focused app through the native injector with --inject.
+
+struct Config {
+    model_path: String,
+    threads: i32,
+    device: Option<String>,
+    inject: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut model_path = std::env::var("DINGOFLOW_ASR_MODEL_PATH").unwrap_or_default();
+    let mut threads = 4_i32;
+    let mut device: Option<String> = None;
+    let mut inject: Option<PathBuf> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if flag == "--help" || flag == "-h" {
+            return Err(
+                "usage: dingoflow-dictate [--model /path/to/parakeet-tdt-onnx-dir] [--threads 4] [--device <id, index or name substring>] [--inject /path/to/dingoflow-text-injector]"
+                    .into(),
+            );
+        }
+        let Some(value) = args.get(i + 1) else {
+            return Err(format!("Missing value for {flag}"));
+        };
+        match flag {
+            "--model" => model_path = value.clone(),
+            "--threads" => {
+                threads = value
+                    .parse::<i32>()
+                    .map_err(|_| "Invalid --threads value".to_string())?;
+            }
+            "--device" => device = Some(value.clone()),
+            "--inject" => inject = Some(PathBuf::from(value)),
+            other => return Err(format!("Unsupported argument: {other}")),
+        }
+        i += 2;
+    }
+
+    if model_path.is_empty() {
+        return Err("--model or DINGOFLOW_ASR_MODEL_PATH is required".into());
+    }
+
+    if !(1..=64).contains(&threads) {
+        return Err("--threads must be between 1 and 64".into());
+    }
+
+    Ok(Config {
+        model_path,
+        threads,
+        device,
+        inject,
+    })
+}
+
+enum Output {
+    Stdout,
+    Inject(PathBuf),
+}
+
+impl Output {
+    fn commit(&self, delta: &str, first: bool) -> Result<(), String> {
+        match self {
+            Output::Stdout => print_commit(delta, first),
+            // Same call the host makes for a native insert.
+            Output::Inject(injector) => {
+                let text = if first {
+                    delta.to_string()
+                } else {
+                    format!(" {delta}")
+                };
+                let mut child = Command::new(injector)
+                    .args(["--mode", "insert"])
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|e| format!("failed to start {}: {e}", injector.display()))?;
+                if let Some(mut stdin) = child.stdin.take() {
+                    stdin
+                        .write_all(text.as_bytes())
+                        .map_err(|e| format!("failed to send text to injector: {e}"))?;
+                }
+                let status = child
+                    .wait()
+                    .map_err(|e| format!("failed to wait for injector: {e}"))?;
+                if !status.success() {
+                    return Err(format!("text injector exited with {status}"));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let cfg = parse_args()?;
+    check_model_dir(&cfg.model_path)?;
+
+    let mut engine = load_engine(&EngineConfig::new(cfg.model_path.clone(), cfg.threads))?;
+    let output = match cfg.inject {
+        Some(injector) => Output::Inject(injector),
+        None => Output::Stdout,
+    };
+    let wrote_any = run_mic(&mut engine, cfg.device.as_deref(), |delta, first| {
+        output.commit(delta, first)
+    })?;
+    if wrote_any && matches!(output, Output::Stdout) {
+        println!();
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,41 @@
+use dingoflow_asr::context::ContextStore;
+use dingoflow_asr::engine::Engine;
+use dingoflow_asr::parakeet::ParakeetBackend;
+use dingoflow_asr::stream::StreamConfig;
+use std::path::PathBuf;
+
+pub use dingoflow_asr::parakeet::{check_model_dir, SAMPLE_RATE as INPUT_SAMPLE_RATE};
+pub use dingoflow_asr::stream::{
+    ConfidenceEvent, DEFAULT_STREAM_DECODE_INTERVAL_MS, DEFAULT_STREAM_LEFT_CONTEXT_MS,
+    DEFAULT_STREAM_MAX_WINDOW_MS, DEFAULT_STREAM_MIN_AUDIO_MS, DEFAULT_STREAM_STABILITY_HOLD_MS,
+};
+
+// The backend, streaming state machine and flags live in dingoflow-asr; this
+// crate only adds dingoflow-dictate on top.
+pub type NativeParakeetEngine = Engine<ParakeetBackend>;
+
+// Shared by the framed worker and dingoflow-dictate, which runs the same
+// streaming engine in-process.
+#[derive(Debug, Clone)]
+pub struct EngineConfig {
+    pub model_path: String,
+    pub threads: i32,
+    pub stream: StreamConfig,
+    pub context_dir: Option<PathBuf>,
+}
+
+impl EngineConfig {
+    pub fn new(model_path: String, threads: i32) -> Self {
+        Self {
+            model_path,
+            threads,
+            stream: StreamConfig::default(),
+            context_dir: None,
+        }
+    }
+}
+
+pub fn load_engine(cfg: &EngineConfig) -> Result<NativeParakeetEngine, String> {
+    let backend = ParakeetBackend::load(&cfg.model_path, cfg.threads)?;
+    Ok(Engine::new(backend, &cfg.stream).with_contexts(ContextStore::new(cfg.context_dir.clone())))
+}
@@ -0,0 +1,3 @@
+pub mod engine;
+#[cfg(feature = "mic")]
+pub use dingoflow_asr::mic;
@@ -0,0 +1,181 @@
+// Minimal worker that follows the frame protocol exactly like the real workers' run_server
+// loops. It backs the crate's own tests, and `--violate <rule>` reproduces the regressions
+// the suite is meant to catch.
+
+use std::io::{self, Read};
+
+use dingoflow_frame::write_response;
+use dingoflow_protocol_conformance::{
+    FRAME_MAGIC, FRAME_VERSION, HEADER_BYTES, MAX_AUDIO_BYTES, MAX_JSON_BYTES, UNKNOWN_ID,
+};
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Violation {
+    None,
+    WrongId,
+    ExitOnInvalidJson,
+    ReadOversizedBody,
+    NoResult,
+    ExitZeroOnTruncation,
+    ErrorExitOnEof,
+    IgnoreFramePrefix,
+}
+
+fn parse_violation(value: &str) -> Result<Violation, String> {
+    match value {
+        "wrong-id" => Ok(Violation::WrongId),
+        "exit-on-invalid-json" => Ok(Violation::ExitOnInvalidJson),
+        "read-oversized-body" => Ok(Violation::ReadOversizedBody),
+        "no-result" => Ok(Violation::NoResult),
+        "exit-zero-on-truncation" => Ok(Violation::ExitZeroOnTruncation),
+        "error-exit-on-eof" => Ok(Violation::ErrorExitOnEof),
+        "ignore-frame-prefix" => Ok(Violation::IgnoreFramePrefix),
+        other => Err(format!("unknown violation: {other}")),
+    }
+}
+
+fn read_exact_allow_eof<R: Read>(reader: &mut R, size: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut buf = vec![0_u8; size];
+    let mut offset = 0_usize;
+
+    while offset < size {
+        let read = reader.read(&mut buf[offset..])?;
+        if read == 0 {
+            if offset == 0 {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "incomplete frame",
+            ));
+        }
+        offset += read;
+    }
+
+    Ok(Some(buf))
+}
+
+fn handle(request: &Value, audio: &[u8], violation: Violation) -> Result<Value, String> {
+    match request.get("action").and_then(Value::as_str).unwrap_or("") {
+        "warmup" if violation == Violation::NoResult => Ok(Value::Null),
+        "warmup" => Ok(json!({ "warmed": true })),
+        "echo" => Ok(json!({ "audioBytes": audio.len() })),
+        other => Err(format!("Unsupported action: {other}")),
+    }
+}
+
+fn run_server(violation: Violation) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    loop {
+        let header = match read_exact_allow_eof(&mut reader, HEADER_BYTES) {
+            Ok(Some(value)) => value,
+            Ok(None) if violation == Violation::ErrorExitOnEof => {
+                return Err("stdin closed".to_string())
+            }
+            Ok(None) => break,
+            Err(_) if violation == Violation::ExitZeroOnTruncation => break,
+            Err(err) => return Err(format!("failed to read frame header: {err}")),
+        };
+
+        if violation != Violation::IgnoreFramePrefix {
+            if &header[..4] != FRAME_MAGIC {
+                return Err(format!("bad frame magic {:02x?}", &header[..4]));
+            }
+            if header[4] != FRAME_VERSION {
+                return Err(format!("unsupported frame version {}", header[4]));
+            }
+        }
+        let json_len = u32::from_le_bytes([header[5], header[6], header[7], header[8]]) as usize;
+        let audio_len =
+            u32::from_le_bytes([header[9], header[10], header[11], header[12]]) as usize;
+
+        if violation != Violation::ReadOversizedBody {
+            if json_len == 0 || json_len > MAX_JSON_BYTES {
+                return Err(format!("invalid json frame size: {json_len}"));
+            }
+            if audio_len > MAX_AUDIO_BYTES {
+                return Err(format!("audio frame too large: {audio_len}"));
+            }
+        }
+
+        let mut json_bytes = vec![0_u8; json_len];
+        let mut audio_bytes = vec![0_u8; audio_len];
+        let body = reader
+            .read_exact(&mut json_bytes)
+            .and_then(|_| reader.read_exact(&mut audio_bytes));
+        match body {
+            Ok(()) => {}
+            Err(_) if violation == Violation::ExitZeroOnTruncation => break,
+            Err(err) => return Err(format!("frame read failed: {err}")),
+        }
+
+        let response = match serde_json::from_slice::<Value>(&json_bytes) {
+            Ok(request) if request.is_object() => {
+                let request_id = request
+                    .get("id")
+                    .and_then(Value::as_str)
+                    .unwrap_or(UNKNOWN_ID)
+                    .to_string();
+                let request_id = if violation == Violation::WrongId {
+                    format!("{request_id}-x")
+                } else {
+                    request_id
+                };
+                match handle(&request, &audio_bytes, violation) {
+                    Ok(Value::Null) => json!({ "id": request_id, "ok": true }),
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Ok(_) => json!({
+                "id": UNKNOWN_ID,
+                "ok": false,
+                "error": "invalid JSON request: expected an object"
+            }),
+            Err(_) if violation == Violation::ExitOnInvalidJson => {
+                return Err("invalid JSON request".to_string())
+            }
+            Err(err) => json!({
+                "id": UNKNOWN_ID,
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut violation = Violation::None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let parsed = match arg.as_str() {
+            "--violate" => args
+                .next()
+                .ok_or_else(|| "--violate requires a value".to_string())
+                .and_then(|value| parse_violation(&value)),
+            other => Err(format!("unknown argument: {other}")),
+        };
+        match parsed {
+            Ok(value) => violation = value,
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Err(err) = run_server(violation) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,617 @@
+// Conformance suite for the length-prefixed frame protocol shared by the native workers.
+//
+// Request frame:  "DFLW" | u8 version | u32 LE json length | u32 LE audio length | json | pcm16 audio
+// Response frame: "DFLW" | u8 version | u32 LE json length | json {"id", "ok", "result" | "error"}
+//
+// The magic and version come first so a desynchronized pipe or a worker from another build
+// fails on its first bytes instead of reading garbage as lengths.
+//
+// The suite only depends on behaviour every worker must share, so it can be pointed at any
+// worker binary (plus whatever flags it needs to start) and at the reference worker in
+// src/bin, which is what the host-side client is written against.
+
+use std::io::{self, Read, Write};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+// The limits and prefix come from the workers' own framing crate; the
+// frames themselves are built here by hand so malformed ones can be sent.
+pub use dingoflow_frame::{
+    FRAME_HEADER_BYTES as HEADER_BYTES, FRAME_MAGIC, FRAME_VERSION, MAX_AUDIO_BYTES, MAX_JSON_BYTES,
+};
+pub const UNKNOWN_ID: &str = "unknown";
+
+const UNKNOWN_ACTION: &str = "__conformance_unknown_action__";
+
+pub fn header(json_len: u32, audio_len: u32) -> Vec<u8> {
+    versioned_header(FRAME_VERSION, json_len, audio_len)
+}
+
+pub fn versioned_header(version: u8, json_len: u32, audio_len: u32) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_BYTES);
+    out.extend_from_slice(FRAME_MAGIC);
+    out.push(version);
+    out.extend_from_slice(&json_len.to_le_bytes());
+    out.extend_from_slice(&audio_len.to_le_bytes());
+    out
+}
+
+pub fn encode_frame(json_bytes: &[u8], audio: &[u8]) -> Vec<u8> {
+    let mut out = header(json_bytes.len() as u32, audio.len() as u32);
+    out.extend_from_slice(json_bytes);
+    out.extend_from_slice(audio);
+    out
+}
+
+pub fn encode_request(request: &Value, audio: &[u8]) -> Vec<u8> {
+    encode_frame(request.to_string().as_bytes(), audio)
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub id: String,
+    pub ok: bool,
+    pub result: Option<Value>,
+    pub error: Option<String>,
+}
+
+// Every response must be an object with a string id, a boolean ok, a result when ok and a
+// non-empty error string otherwise.
+pub fn check_response(value: &Value) -> Result<Response, String> {
+    let Some(object) = value.as_object() else {
+        return Err(format!("response is not a JSON object: {value}"));
+    };
+    let Some(id) = object.get("id").and_then(Value::as_str) else {
+        return Err(format!("response has no string id: {value}"));
+    };
+    let Some(ok) = object.get("ok").and_then(Value::as_bool) else {
+        return Err(format!("response has no boolean ok: {value}"));
+    };
+    let result = object.get("result").cloned();
+    let error = object
+        .get("error")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+    if ok && result.is_none() {
+        return Err(format!("ok response has no result: {value}"));
+    }
+    if !ok && error.as_deref().is_none_or(str::is_empty) {
+        return Err(format!("error response has no error message: {value}"));
+    }
+    Ok(Response {
+        id: id.to_string(),
+        ok,
+        result,
+        error,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub bin: String,
+    pub args: Vec<String>,
+    // Action that must succeed without audio; every worker implements warmup.
+    pub probe_action: String,
+    pub timeout: Duration,
+}
+
+impl Target {
+    pub fn new(bin: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            bin: bin.into(),
+            args,
+            probe_action: "warmup".to_string(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    pub fn spawn(&self) -> Result<Worker, String> {
+        Worker::spawn(self)
+    }
+
+    fn probe(&self, id: &str) -> Value {
+        json!({ "id": id, "action": self.probe_action })
+    }
+}
+
+enum Frame {
+    Response(Value),
+    Malformed(String),
+}
+
+pub struct Worker {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    frames: Receiver<Frame>,
+    stderr: Arc<Mutex<Vec<u8>>>,
+    timeout: Duration,
+}
+
+impl Worker {
+    fn spawn(target: &Target) -> Result<Self, String> {
+        let mut child = Command::new(&target.bin)
+            .args(&target.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("failed to spawn {}: {err}", target.bin))?;
+
+        let stdin = child.stdin.take();
+        let mut stdout = child.stdout.take().ok_or("worker stdout unavailable")?;
+        let mut stderr_pipe = child.stderr.take().ok_or("worker stderr unavailable")?;
+
+        let (tx, frames) = mpsc::channel();
+        thread::spawn(move || loop {
+            let frame = match read_response_frame(&mut stdout) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(err) => Frame::Malformed(err),
+            };
+            let stop = matches!(frame, Frame::Malformed(_));
+            if tx.send(frame).is_err() || stop {
+                break;
+            }
+        });
+
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&stderr);
+        thread::spawn(move || {
+            let mut chunk = [0_u8; 4096];
+            while let Ok(read) = stderr_pipe.read(&mut chunk) {
+                if read == 0 {
+                    break;
+                }
+                if let Ok(mut buf) = sink.lock() {
+                    buf.extend_from_slice(&chunk[..read]);
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            frames,
+            stderr,
+            timeout: target.timeout,
+        })
+    }
+
+    pub fn send_raw(&mut self, bytes: &[u8]) -> Result<(), String> {
+        let stdin = self.stdin.as_mut().ok_or("worker stdin already closed")?;
+        let written = stdin.write_all(bytes).and_then(|_| stdin.flush());
+        written.map_err(|err| self.describe(&format!("failed to write to worker: {err}")))
+    }
+
+    pub fn send(&mut self, request: &Value, audio: &[u8]) -> Result<(), String> {
+        self.send_raw(&encode_request(request, audio))
+    }
+
+    pub fn close_stdin(&mut self) {
+        self.stdin = None;
+    }
+
+    // None means the worker closed stdout without answering.
+    pub fn recv(&mut self) -> Result<Option<Response>, String> {
+        match self.frames.recv_timeout(self.timeout) {
+            Ok(Frame::Response(value)) => check_response(&value).map(Some),
+            Ok(Frame::Malformed(err)) => Err(err),
+            Err(RecvTimeoutError::Disconnected) => Ok(None),
+            Err(RecvTimeoutError::Timeout) => Err(format!(
+                "no response within {} ms",
+                self.timeout.as_millis()
+            )),
+        }
+    }
+
+    pub fn expect_response(&mut self) -> Result<Response, String> {
+        match self.recv()? {
+            Some(response) => Ok(response),
+            None => Err(self.describe("worker closed stdout instead of responding")),
+        }
+    }
+
+    pub fn request(&mut self, request: &Value, audio: &[u8]) -> Result<Response, String> {
+        self.send(request, audio)?;
+        self.expect_response()
+    }
+
+    pub fn wait(&mut self) -> Result<ExitStatus, String> {
+        let deadline = Instant::now() + self.timeout;
+        loop {
+            match self.child.try_wait() {
+                Ok(Some(status)) => return Ok(status),
+                Ok(None) if Instant::now() >= deadline => {
+                    return Err(format!(
+                        "worker still running after {} ms",
+                        self.timeout.as_millis()
+                    ))
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(10)),
+                Err(err) => return Err(format!("failed to wait for worker: {err}")),
+            }
+        }
+    }
+
+    pub fn stderr(&self) -> String {
+        self.stderr
+            .lock()
+            .map(|buf| String::from_utf8_lossy(&buf).trim().to_string())
+            .unwrap_or_default()
+    }
+
+    fn describe(&self, message: &str) -> String {
+        let stderr = self.stderr();
+        if stderr.is_empty() {
+            message.to_string()
+        } else {
+            format!("{message} (stderr: {stderr})")
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.stdin = None;
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_response_frame<R: Read>(reader: &mut R) -> Result<Option<Frame>, String> {
+    let mut prefix = [0_u8; 9];
+    let mut offset = 0;
+    while offset < prefix.len() {
+        match reader.read(&mut prefix[offset..]) {
+            Ok(0) if offset == 0 => return Ok(None),
+            Ok(0) => return Err("worker closed stdout mid response header".to_string()),
+            Ok(read) => offset += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(format!("failed to read response header: {err}")),
+        }
+    }
+
+    if &prefix[..4] != FRAME_MAGIC {
+        return Err(format!("response has bad magic {:02x?}", &prefix[..4]));
+    }
+    if prefix[4] != FRAME_VERSION {
+        return Err(format!(
+            "response has version {}, expected {FRAME_VERSION}",
+            prefix[4]
+        ));
+    }
+    let len = u32::from_le_bytes([prefix[5], prefix[6], prefix[7], prefix[8]]) as usize;
+    if len == 0 || len > MAX_JSON_BYTES {
+        return Err(format!("invalid response frame size: {len}"));
+    }
+    let mut body = vec![0_u8; len];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| format!("failed to read response body: {err}"))?;
+    match serde_json::from_slice::<Value>(&body) {
+        Ok(value) => Ok(Some(Frame::Response(value))),
+        Err(err) => Ok(Some(Frame::Malformed(format!(
+            "response is not valid JSON: {err}"
+        )))),
+    }
+}
+
+pub struct Case {
+    pub name: &'static str,
+    pub description: &'static str,
+    run: fn(&Target) -> Result<(), String>,
+}
+
+impl Case {
+    pub fn run(&self, target: &Target) -> Result<(), String> {
+        (self.run)(target)
+    }
+}
+
+pub struct Outcome {
+    pub name: &'static str,
+    pub error: Option<String>,
+    pub elapsed: Duration,
+}
+
+pub fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "probe_echoes_id",
+            description: "a valid request succeeds and echoes its id",
+            run: probe_echoes_id,
+        },
+        Case {
+            name: "pipelined_requests_in_order",
+            description: "requests written back to back are answered in order",
+            run: pipelined_requests_in_order,
+        },
+        Case {
+            name: "split_writes_reassembled",
+            description: "a frame delivered a few bytes at a time is still one request",
+            run: split_writes_reassembled,
+        },
+        Case {
+            name: "unknown_action_is_error",
+            description: "an unknown action is an error response and the worker keeps serving",
+            run: unknown_action_is_error,
+        },
+        Case {
+            name: "invalid_json_is_error",
+            description: "unparseable JSON gets an error response with id \"unknown\"",
+            run: invalid_json_is_error,
+        },
+        Case {
+            name: "non_object_json_is_error",
+            description: "JSON that is not an object gets an error response",
+            run: non_object_json_is_error,
+        },
+        Case {
+            name: "missing_id_is_unknown",
+            description: "a request without an id is answered with id \"unknown\"",
+            run: missing_id_is_unknown,
+        },
+        Case {
+            name: "bad_magic_is_fatal",
+            description: "a frame that doesn't start with the magic terminates the worker",
+            run: bad_magic_is_fatal,
+        },
+        Case {
+            name: "unsupported_version_is_fatal",
+            description: "a frame with another protocol version terminates the worker",
+            run: unsupported_version_is_fatal,
+        },
+        Case {
+            name: "zero_length_json_is_fatal",
+            description: "a frame with an empty JSON body terminates the worker",
+            run: zero_length_json_is_fatal,
+        },
+        Case {
+            name: "oversized_json_is_fatal",
+            description: "a JSON length above the limit terminates the worker before reading it",
+            run: oversized_json_is_fatal,
+        },
+        Case {
+            name: "oversized_audio_is_fatal",
+            description: "an audio length above the limit terminates the worker before reading it",
+            run: oversized_audio_is_fatal,
+        },
+        Case {
+            name: "truncated_header_is_fatal",
+            description: "EOF inside a frame header is an error exit",
+            run: truncated_header_is_fatal,
+        },
+        Case {
+            name: "truncated_body_is_fatal",
+            description: "EOF inside a frame body is an error exit",
+            run: truncated_body_is_fatal,
+        },
+        Case {
+            name: "clean_eof_exits_zero",
+            description: "EOF between frames is a clean exit",
+            run: clean_eof_exits_zero,
+        },
+    ]
+}
+
+pub fn run_suite(target: &Target, only: &[String]) -> Vec<Outcome> {
+    cases()
+        .into_iter()
+        .filter(|case| only.is_empty() || only.iter().any(|name| name == case.name))
+        .map(|case| {
+            let started = Instant::now();
+            let error = case.run(target).err();
+            Outcome {
+                name: case.name,
+                error,
+                elapsed: started.elapsed(),
+            }
+        })
+        .collect()
+}
+
+fn expect_ok(response: &Response, id: &str) -> Result<(), String> {
+    if response.id != id {
+        return Err(format!("expected id {id:?}, got {:?}", response.id));
+    }
+    if !response.ok {
+        return Err(format!(
+            "request {id:?} failed: {}",
+            response.error.as_deref().unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+fn expect_error(response: &Response, id: &str) -> Result<(), String> {
+    if response.id != id {
+        return Err(format!("expected id {id:?}, got {:?}", response.id));
+    }
+    if response.ok {
+        return Err(format!("expected an error response for {id:?}, got ok"));
+    }
+    Ok(())
+}
+
+// After a recoverable error the same process must still answer a valid request.
+fn expect_still_serving(worker: &mut Worker, target: &Target) -> Result<(), String> {
+    let response = worker.request(&target.probe("after-error"), &[])?;
+    expect_ok(&response, "after-error")
+}
+
+// Fatal cases start from a worker that has answered once, so one that never came up (bad
+// flags, missing model) fails here instead of passing for exiting non-zero.
+fn spawn_serving(target: &Target) -> Result<Worker, String> {
+    let mut worker = target.spawn()?;
+    let response = worker.request(&target.probe("ready"), &[])?;
+    expect_ok(&response, "ready")?;
+    Ok(worker)
+}
+
+fn expect_fatal(worker: &mut Worker) -> Result<(), String> {
+    if let Some(response) = worker.recv()? {
+        return Err(format!(
+            "expected the worker to exit, got a response for {:?}",
+            response.id
+        ));
+    }
+    let status = worker.wait()?;
+    if status.success() {
+        return Err("expected a non-zero exit status, got 0".to_string());
+    }
+    Ok(())
+}
+
+// Exiting isn't enough when the point is a clear error: the worker has to say what was wrong.
+fn expect_fatal_naming(worker: &mut Worker, word: &str) -> Result<(), String> {
+    expect_fatal(worker)?;
+    let stderr = worker.stderr();
+    if !stderr.to_lowercase().contains(word) {
+        return Err(format!(
+            "expected the exit error to mention {word:?}, got {stderr:?}"
+        ));
+    }
+    Ok(())
+}
+
+fn probe_echoes_id(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    let response = worker.request(&target.probe("conformance-1"), &[])?;
+    expect_ok(&response, "conformance-1")
+}
+
+fn pipelined_requests_in_order(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    let ids = ["pipe-a", "pipe-b", "pipe-c"];
+    let mut bytes = Vec::new();
+    for id in ids {
+        bytes.extend(encode_request(&target.probe(id), &[]));
+    }
+    worker.send_raw(&bytes)?;
+    for id in ids {
+        expect_ok(&worker.expect_response()?, id)?;
+    }
+    Ok(())
+}
+
+fn split_writes_reassembled(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    let bytes = encode_request(&target.probe("split"), &[]);
+    for chunk in bytes.chunks(3) {
+        worker.send_raw(chunk)?;
+        thread::sleep(Duration::from_millis(2));
+    }
+    expect_ok(&worker.expect_response()?, "split")
+}
+
+fn unknown_action_is_error(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    let response = worker.request(
+        &json!({ "id": "unknown-action", "action": UNKNOWN_ACTION }),
+        &[],
+    )?;
+    expect_error(&response, "unknown-action")?;
+    expect_still_serving(&mut worker, target)
+}
+
+fn invalid_json_is_error(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    worker.send_raw(&encode_frame(b"{\"id\": \"broken\", \"action\":", &[]))?;
+    expect_error(&worker.expect_response()?, UNKNOWN_ID)?;
+    expect_still_serving(&mut worker, target)
+}
+
+fn non_object_json_is_error(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    worker.send_raw(&encode_frame(b"[1, 2, 3]", &[]))?;
+    let response = worker.expect_response()?;
+    if response.ok {
+        return Err("expected an error response for a JSON array, got ok".to_string());
+    }
+    expect_still_serving(&mut worker, target)
+}
+
+fn missing_id_is_unknown(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    let response = worker.request(&json!({ "action": target.probe_action }), &[])?;
+    if response.id != UNKNOWN_ID {
+        return Err(format!("expected id {UNKNOWN_ID:?}, got {:?}", response.id));
+    }
+    Ok(())
+}
+
+// An old client's bare length header, which is also what a desynchronized stream looks like.
+fn bad_magic_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    let body = target.probe("unframed").to_string();
+    let mut bytes = (body.len() as u32).to_le_bytes().to_vec();
+    bytes.extend_from_slice(&0_u32.to_le_bytes());
+    bytes.extend_from_slice(&[0_u8; 5]);
+    bytes.extend_from_slice(body.as_bytes());
+    worker.send_raw(&bytes)?;
+    expect_fatal_naming(&mut worker, "magic")
+}
+
+fn unsupported_version_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    let body = target.probe("next-version").to_string();
+    let mut bytes = versioned_header(FRAME_VERSION + 1, body.len() as u32, 0);
+    bytes.extend_from_slice(body.as_bytes());
+    worker.send_raw(&bytes)?;
+    expect_fatal_naming(&mut worker, "version")
+}
+
+fn zero_length_json_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    worker.send_raw(&header(0, 0))?;
+    expect_fatal(&mut worker)
+}
+
+// The oversized cases keep stdin open so a worker that tries to read the body hangs and
+// fails on the timeout instead of passing on EOF.
+fn oversized_json_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    worker.send_raw(&header(MAX_JSON_BYTES as u32 + 1, 0))?;
+    expect_fatal(&mut worker)
+}
+
+fn oversized_audio_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    worker.send_raw(&header(2, MAX_AUDIO_BYTES as u32 + 1))?;
+    expect_fatal(&mut worker)
+}
+
+fn truncated_header_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    worker.send_raw(&header(16, 0)[..9])?;
+    worker.close_stdin();
+    expect_fatal(&mut worker)
+}
+
+fn truncated_body_is_fatal(target: &Target) -> Result<(), String> {
+    let mut worker = spawn_serving(target)?;
+    let body = target.probe("truncated").to_string();
+    let frame = encode_frame(body.as_bytes(), &[0_u8; 64]);
+    worker.send_raw(&frame[..HEADER_BYTES + body.len() / 2])?;
+    worker.close_stdin();
+    expect_fatal(&mut worker)
+}
+
+fn clean_eof_exits_zero(target: &Target) -> Result<(), String> {
+    let mut worker = target.spawn()?;
+    expect_ok(&worker.request(&target.probe("last"), &[])?, "last")?;
+    worker.close_stdin();
+    if let Some(response) = worker.recv()? {
+        return Err(format!("unexpected extra response for {:?}", response.id));
+    }
+    let status = worker.wait()?;
+    if !status.success() {
+        return Err(worker.describe(&format!("expected exit status 0, got {status}")));
+    }
+    Ok(())
+}
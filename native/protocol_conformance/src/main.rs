@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use dingoflow_protocol_conformance::{cases, run_suite, Target};
+use serde_json::json;
+
+struct Config {
+    target: Option<Target>,
+    only: Vec<String>,
+    list: bool,
+    json: bool,
+}
+
+fn parse_args() -> Result<Config, String> {
+    let mut bin = None;
+    let mut worker_args = Vec::new();
+    let mut probe_action = None;
+    let mut timeout_ms = None;
+    let mut only = Vec::new();
+    let mut list = false;
+    let mut json = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--worker-bin" => bin = Some(args.next().ok_or("--worker-bin requires a value")?),
+            "--worker-arg" => worker_args.push(args.next().ok_or("--worker-arg requires a value")?),
+            "--probe-action" => {
+                probe_action = Some(args.next().ok_or("--probe-action requires a value")?)
+            }
+            "--timeout-ms" => {
+                let value = args.next().ok_or("--timeout-ms requires a value")?;
+                timeout_ms = Some(
+                    value
+                        .parse::<u64>()
+                        .map_err(|_| format!("invalid --timeout-ms: {value}"))?,
+                );
+            }
+            "--case" => only.push(args.next().ok_or("--case requires a value")?),
+            "--list" => list = true,
+            "--json" => json = true,
+            "--" => {
+                worker_args.extend(args.by_ref());
+            }
+            other => return Err(format!("unknown argument: {other}")),
+        }
+    }
+
+    let known = cases();
+    if let Some(name) = only
+        .iter()
+        .find(|name| !known.iter().any(|case| case.name == name.as_str()))
+    {
+        return Err(format!("unknown case: {name}"));
+    }
+
+    let target = bin.map(|bin| {
+        let mut target = Target::new(bin, worker_args);
+        if let Some(action) = probe_action {
+            target.probe_action = action;
+        }
+        if let Some(ms) = timeout_ms {
+            target.timeout = Duration::from_millis(ms);
+        }
+        target
+    });
+
+    Ok(Config {
+        target,
+        only,
+        list,
+        json,
+    })
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(2);
+        }
+    };
+
+    if cfg.list {
+        for case in cases() {
+            println!("{:<30} {}", case.name, case.description);
+        }
+        return;
+    }
+
+    let Some(target) = cfg.target else {
+        eprintln!("--worker-bin is required");
+        std::process::exit(2);
+    };
+
+    let outcomes = run_suite(&target, &cfg.only);
+    let failed = outcomes
+        .iter()
+        .filter(|outcome| outcome.error.is_some())
+        .count();
+
+    if cfg.json {
+        let cases: Vec<_> = outcomes
+            .iter()
+            .map(|outcome| {
+                json!({
+                    "name": outcome.name,
+                    "ok": outcome.error.is_none(),
+                    "error": outcome.error,
+                    "elapsedMs": outcome.elapsed.as_millis() as u64,
+                })
+            })
+            .collect();
+        println!(
+            "{}",
+            json!({
+                "workerBin": target.bin,
+                "passed": outcomes.len() - failed,
+                "failed": failed,
+                "cases": cases,
+            })
+        );
+    } else {
+        for outcome in &outcomes {
+            match &outcome.error {
+                None => println!(
+                    "ok    {} ({} ms)",
+                    outcome.name,
+                    outcome.elapsed.as_millis()
+                ),
+                Some(err) => println!("FAIL  {}: {err}", outcome.name),
+            }
+        }
+        println!(
+            "{}: {} passed, {} failed",
+            target.bin,
+            outcomes.len() - failed,
+            failed
+        );
+    }
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use dingoflow_protocol_conformance::{run_suite, Target};
+
+const REFERENCE_WORKER: &str = env!("CARGO_BIN_EXE_dingoflow-reference-worker");
+
+fn reference(args: &[&str]) -> Target {
+    let mut target = Target::new(
+        REFERENCE_WORKER,
+        args.iter().map(|arg| arg.to_string()).collect(),
+    );
+    target.timeout = Duration::from_secs(2);
+    target
+}
+
+fn failures(target: &Target) -> Vec<&'static str> {
+    run_suite(target, &[])
+        .into_iter()
+        .filter(|outcome| outcome.error.is_some())
+        .map(|outcome| outcome.name)
+        .collect()
+}
+
+#[test]
+fn reference_worker_passes_every_case() {
+    let outcomes = run_suite(&reference(&[]), &[]);
+    for outcome in &outcomes {
+        assert!(
+            outcome.error.is_none(),
+            "{}: {:?}",
+            outcome.name,
+            outcome.error
+        );
+    }
+}
+
+#[test]
+fn detects_wrong_id() {
+    let failed = failures(&reference(&["--violate", "wrong-id"]));
+    assert!(failed.contains(&"probe_echoes_id"), "{failed:?}");
+    assert!(
+        failed.contains(&"pipelined_requests_in_order"),
+        "{failed:?}"
+    );
+    assert!(failed.contains(&"unknown_action_is_error"), "{failed:?}");
+}
+
+#[test]
+fn detects_exit_on_invalid_json() {
+    let failed = failures(&reference(&["--violate", "exit-on-invalid-json"]));
+    assert_eq!(failed, vec!["invalid_json_is_error"]);
+}
+
+#[test]
+fn detects_reading_oversized_body() {
+    let failed = failures(&reference(&["--violate", "read-oversized-body"]));
+    assert!(failed.contains(&"oversized_json_is_fatal"), "{failed:?}");
+    assert!(failed.contains(&"oversized_audio_is_fatal"), "{failed:?}");
+}
+
+#[test]
+fn detects_missing_result() {
+    let failed = failures(&reference(&["--violate", "no-result"]));
+    assert!(failed.contains(&"probe_echoes_id"), "{failed:?}");
+}
+
+#[test]
+fn detects_clean_exit_on_truncation() {
+    let failed = failures(&reference(&["--violate", "exit-zero-on-truncation"]));
+    assert_eq!(
+        failed,
+        vec!["truncated_header_is_fatal", "truncated_body_is_fatal"]
+    );
+}
+
+#[test]
+fn detects_error_exit_on_eof() {
+    let failed = failures(&reference(&["--violate", "error-exit-on-eof"]));
+    assert_eq!(failed, vec!["clean_eof_exits_zero"]);
+}
+
+#[test]
+fn detects_ignoring_the_frame_prefix() {
+    let failed = failures(&reference(&["--violate", "ignore-frame-prefix"]));
+    assert_eq!(
+        failed,
+        vec!["bad_magic_is_fatal", "unsupported_version_is_fatal"]
+    );
+}
+
+// Real workers need models, so they are opt-in: DINGOFLOW_CONFORMANCE_WORKERS holds one
+// worker command per line, e.g. "/path/to/dingoflow-vad-worker --serve --model vad.onnx".
+#[test]
+fn configured_workers_pass_every_case() {
+    let Ok(workers) = std::env::var("DINGOFLOW_CONFORMANCE_WORKERS") else {
+        return;
+    };
+    for line in workers
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let mut parts = line.split_whitespace().map(str::to_string);
+        let bin = parts.next().unwrap_or_default();
+        let target = Target::new(bin, parts.collect());
+        let failed: Vec<_> = run_suite(&target, &[])
+            .into_iter()
+            .filter_map(|outcome| outcome.error.map(|err| format!("{}: {err}", outcome.name)))
+            .collect();
+        assert!(failed.is_empty(), "{line}\n{}", failed.join("\n"));
+    }
+}
@@ -0,0 +1,197 @@
+mod model;
+mod restore;
+
+use dingoflow_frame::{read_frame, write_response};
+use model::Model;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io;
+use std::path::PathBuf;
+
+const MAX_TEXT_CHARS: usize = 100_000;
+// Well inside a 512-token BERT window even for long sub-word splits.
+const WORDS_PER_CHUNK: usize = 150;
+
+// Restores punctuation and casing for transcripts from either ASR backend.
+// Requests use the same frames as the ASR workers (the audio length is
+// always 0) and get the same length-prefixed JSON responses.
+#[derive(Debug)]
+struct Config {
+    model_dir: Option<PathBuf>,
+    threads: usize,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    text: Option<String>,
+}
+
+fn punctuate(model: &mut Model, text: &str) -> Result<Value, String> {
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(format!("text is longer than {MAX_TEXT_CHARS} characters"));
+    }
+    let words = restore::words(text);
+    let mut labels = Vec::with_capacity(words.len());
+    for chunk in words.chunks(WORDS_PER_CHUNK) {
+        let lowered: Vec<String> = chunk.iter().map(|word| word.to_lowercase()).collect();
+        let refs: Vec<&str> = lowered.iter().map(String::as_str).collect();
+        labels.extend(model.predict(&refs)?);
+    }
+    Ok(json!({
+        "text": restore::apply(&words, &labels),
+        "words": words.len(),
+    }))
+}
+
+fn handle(model: &mut Model, req: &Request) -> Result<Value, String> {
+    match req.action.as_deref().unwrap_or("punctuate") {
+        "warmup" => {
+            punctuate(model, "warming up the punctuation model")?;
+            Ok(json!({ "ready": true }))
+        }
+        "punctuate" => {
+            let text = req.text.as_deref().ok_or("punctuate requires text")?;
+            punctuate(model, text)
+        }
+        other => Err(format!("Unsupported action: {other}")),
+    }
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut model_dir: Option<PathBuf> = None;
+    let mut threads = 2_usize;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--model" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --model".into());
+                }
+                model_dir = Some(PathBuf::from(&args[i + 1]));
+                i += 2;
+            }
+            "--threads" => {
+                if i + 1 >= args.len() {
+                    return Err("Missing value for --threads".into());
+                }
+                threads = args[i + 1]
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=16).contains(n))
+                    .ok_or("--threads must be between 1 and 16")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-punctuate-worker --model <dir with model.onnx, tokenizer.json, config.json> [--threads 2] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    if model_dir.is_none() && !healthcheck {
+        return Err("--model is required unless --healthcheck is used".into());
+    }
+
+    Ok(Config {
+        model_dir,
+        threads,
+        serve,
+        healthcheck,
+    })
+}
+
+fn run_server(mut model: Model) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        if !frame.audio.is_empty() {
+            return Err(format!(
+                "unexpected audio payload: {} bytes",
+                frame.audio.len()
+            ));
+        }
+
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match handle(&mut model, &req) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        println!("ok");
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    let Some(model_dir) = cfg.model_dir else {
+        eprintln!("--model is required");
+        std::process::exit(1);
+    };
+    let model = match Model::load(&model_dir, cfg.threads) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(err) = run_server(model) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,165 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tokenizers::Tokenizer;
+
+// A token-classification export (model.onnx, tokenizer.json, config.json)
+// whose labels pair the punctuation that follows a word with that word's
+// casing, e.g. ".U" or ",O" as in felflare/bert-restore-punctuation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub punctuation: String,
+    pub case: Case,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Capitalize,
+    Upper,
+}
+
+impl Label {
+    fn parse(raw: &str) -> Self {
+        let mut chars: Vec<char> = raw.chars().collect();
+        let case = match chars.last() {
+            Some('U') => Case::Capitalize,
+            Some('A') => Case::Upper,
+            _ => Case::Lower,
+        };
+        chars.pop();
+        let punctuation: String = chars.into_iter().collect();
+        Self {
+            punctuation: if punctuation == "O" {
+                String::new()
+            } else {
+                punctuation
+            },
+            case,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelConfig {
+    id2label: HashMap<String, String>,
+}
+
+pub struct Model {
+    session: Session,
+    tokenizer: Tokenizer,
+    labels: Vec<Label>,
+    wants_token_types: bool,
+}
+
+impl Model {
+    pub fn load(dir: &Path, threads: usize) -> Result<Self, String> {
+        let model_path = dir.join("model.onnx");
+        let tokenizer_path = dir.join("tokenizer.json");
+        let config_path = dir.join("config.json");
+        for path in [&model_path, &tokenizer_path, &config_path] {
+            if !path.is_file() {
+                return Err(format!("missing {}", path.display()));
+            }
+        }
+
+        let raw = fs::read_to_string(&config_path)
+            .map_err(|e| format!("failed to read {}: {e}", config_path.display()))?;
+        let config: ModelConfig = serde_json::from_str(&raw)
+            .map_err(|e| format!("invalid {}: {e}", config_path.display()))?;
+        let mut labels = vec![Label::parse("OO"); config.id2label.len()];
+        for (id, raw) in &config.id2label {
+            let id = id
+                .parse::<usize>()
+                .ok()
+                .filter(|id| *id < labels.len())
+                .ok_or_else(|| format!("invalid label id {id} in config.json"))?;
+            labels[id] = Label::parse(raw);
+        }
+
+        let tokenizer = Tokenizer::from_file(&tokenizer_path)
+            .map_err(|e| format!("failed to load {}: {e}", tokenizer_path.display()))?;
+        let session = Session::builder()
+            .and_then(|builder| builder.with_intra_threads(threads))
+            .and_then(|builder| builder.commit_from_file(&model_path))
+            .map_err(|err| format!("failed to load punctuation model: {err}"))?;
+        let wants_token_types = session
+            .inputs()
+            .iter()
+            .any(|input| input.name() == "token_type_ids");
+
+        Ok(Self {
+            session,
+            tokenizer,
+            labels,
+            wants_token_types,
+        })
+    }
+
+    // One label per word, read from the word's first sub-token.
+    pub fn predict(&mut self, words: &[&str]) -> Result<Vec<Label>, String> {
+        let encoding = self
+            .tokenizer
+            .encode(words.to_vec(), true)
+            .map_err(|e| format!("tokenization failed: {e}"))?;
+        let ids: Vec<i64> = encoding.get_ids().iter().map(|id| *id as i64).collect();
+        let mask: Vec<i64> = encoding
+            .get_attention_mask()
+            .iter()
+            .map(|m| *m as i64)
+            .collect();
+        let len = ids.len();
+
+        let to_err = |err: ort::Error| format!("punctuation inference failed: {err}");
+        let mut inputs = vec![
+            (
+                "input_ids",
+                Tensor::from_array(([1, len], ids)).map_err(to_err)?,
+            ),
+            (
+                "attention_mask",
+                Tensor::from_array(([1, len], mask)).map_err(to_err)?,
+            ),
+        ];
+        if self.wants_token_types {
+            inputs.push((
+                "token_type_ids",
+                Tensor::from_array(([1, len], vec![0_i64; len])).map_err(to_err)?,
+            ));
+        }
+        let outputs = self.session.run(inputs).map_err(to_err)?;
+        let (_, logits) = outputs[0].try_extract_tensor::<f32>().map_err(to_err)?;
+        let classes = self.labels.len();
+        if logits.len() != len * classes {
+            return Err(format!(
+                "unexpected logits size {} for {len} tokens and {classes} labels",
+                logits.len()
+            ));
+        }
+
+        let mut labels = vec![None; words.len()];
+        for (token, word) in encoding.get_word_ids().iter().enumerate() {
+            let Some(word) = word.map(|w| w as usize).filter(|w| *w < words.len()) else {
+                continue;
+            };
+            if labels[word].is_some() {
+                continue;
+            }
+            let row = &logits[token * classes..(token + 1) * classes];
+            let best = row
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(index, _)| index)
+                .unwrap_or(0);
+            labels[word] = Some(self.labels[best].clone());
+        }
+        Ok(labels
+            .into_iter()
+            .map(|label| label.unwrap_or_else(|| Label::parse("OO")))
+            .collect())
+    }
+}
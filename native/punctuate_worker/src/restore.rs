@@ -0,0 +1,46 @@
+use crate::model::{Case, Label};
+
+// The model was trained on bare lowercase words, so incoming punctuation is
+// stripped and casing reset before prediction. Words with capitals past the
+// first letter (iPhone, NASA, McDonald) are names the model can't spell, so
+// their casing is kept.
+const STRIP: &[char] = &['.', ',', '?', '!', ':', ';', '"', '(', ')'];
+
+pub fn words(text: &str) -> Vec<String> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c| STRIP.contains(&c)).to_string())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+pub fn apply(words: &[String], labels: &[Label]) -> String {
+    let mut out = String::new();
+    for (word, label) in words.iter().zip(labels) {
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        let keep = word.chars().skip(1).any(char::is_uppercase);
+        let cased = if keep {
+            word.clone()
+        } else if word == "i" || word.starts_with("i'") {
+            capitalize(word)
+        } else {
+            match label.case {
+                Case::Lower => word.to_lowercase(),
+                Case::Capitalize => capitalize(&word.to_lowercase()),
+                Case::Upper => word.to_uppercase(),
+            }
+        };
+        out.push_str(&cased);
+        out.push_str(&label.punctuation);
+    }
+    out
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
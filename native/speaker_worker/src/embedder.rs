@@ -0,0 +1,63 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use std::path::Path;
+
+use crate::fbank::{Fbank, NUM_BINS};
+
+// A WeSpeaker-style speaker embedding model: fbank frames [1, T, 80] in,
+// one embedding per utterance out. Embeddings are unit length so a dot
+// product is their cosine similarity.
+pub struct Embedder {
+    session: Session,
+    fbank: Fbank,
+}
+
+impl Embedder {
+    pub fn new(model_path: &Path, threads: usize) -> Result<Self, String> {
+        if !model_path.is_file() {
+            return Err(format!("speaker model not found: {}", model_path.display()));
+        }
+        let session = Session::builder()
+            .and_then(|builder| builder.with_intra_threads(threads))
+            .and_then(|builder| builder.commit_from_file(model_path))
+            .map_err(|err| format!("failed to load speaker model: {err}"))?;
+        Ok(Self {
+            session,
+            fbank: Fbank::new(),
+        })
+    }
+
+    pub fn embed(&mut self, samples: &[f32]) -> Result<Vec<f32>, String> {
+        let frames = self.fbank.compute(samples);
+        if frames.is_empty() {
+            return Err("audio is too short for a speaker embedding".into());
+        }
+        let len = frames.len();
+        let feats: Vec<f32> = frames.into_iter().flatten().collect();
+
+        let to_err = |err: ort::Error| format!("speaker embedding failed: {err}");
+        let input_name = self
+            .session
+            .inputs()
+            .first()
+            .map(|input| input.name().to_string())
+            .ok_or("speaker model has no inputs")?;
+        let feats = Tensor::from_array(([1, len, NUM_BINS], feats)).map_err(to_err)?;
+        let outputs = self
+            .session
+            .run(vec![(input_name, feats)])
+            .map_err(to_err)?;
+        let (_, embedding) = outputs[0].try_extract_tensor::<f32>().map_err(to_err)?;
+        Ok(normalize(embedding.to_vec()))
+    }
+}
+
+pub fn normalize(mut v: Vec<f32>) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt().max(1e-8);
+    v.iter_mut().for_each(|x| *x /= norm);
+    v
+}
+
+pub fn cosine(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
@@ -0,0 +1,130 @@
+use rustfft::num_complex::Complex32;
+use rustfft::FftPlanner;
+
+// Kaldi-compatible log-mel filterbank (the features WeSpeaker models are
+// trained on): 25 ms Povey windows every 10 ms, 512-point FFT, 80 mel bins
+// from 20 Hz to Nyquist, then per-utterance mean normalization.
+pub const SAMPLE_RATE: u32 = 16_000;
+pub const NUM_BINS: usize = 80;
+const FRAME_LEN: usize = 400;
+const FRAME_SHIFT: usize = 160;
+const FFT_LEN: usize = 512;
+const PREEMPH: f32 = 0.97;
+const LOW_HZ: f32 = 20.0;
+
+pub struct Fbank {
+    window: Vec<f32>,
+    // Per mel bin: first FFT bin and its weights.
+    filters: Vec<(usize, Vec<f32>)>,
+    planner: FftPlanner<f32>,
+}
+
+fn mel(hz: f32) -> f32 {
+    1127.0 * (1.0 + hz / 700.0).ln()
+}
+
+impl Fbank {
+    pub fn new() -> Self {
+        let window = (0..FRAME_LEN)
+            .map(|i| {
+                let hann = 0.5
+                    - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FRAME_LEN - 1) as f32).cos();
+                hann.powf(0.85)
+            })
+            .collect();
+
+        let high_hz = SAMPLE_RATE as f32 / 2.0;
+        let (mel_low, mel_high) = (mel(LOW_HZ), mel(high_hz));
+        let delta = (mel_high - mel_low) / (NUM_BINS + 1) as f32;
+        let bin_hz = SAMPLE_RATE as f32 / FFT_LEN as f32;
+        let filters = (0..NUM_BINS)
+            .map(|m| {
+                let left = mel_low + m as f32 * delta;
+                let center = left + delta;
+                let right = center + delta;
+                let mut first = None;
+                let mut weights = Vec::new();
+                for k in 0..FFT_LEN / 2 {
+                    let f = mel(k as f32 * bin_hz);
+                    if f <= left || f >= right {
+                        continue;
+                    }
+                    first.get_or_insert(k);
+                    weights.push(if f <= center {
+                        (f - left) / (center - left)
+                    } else {
+                        (right - f) / (right - center)
+                    });
+                }
+                (first.unwrap_or(0), weights)
+            })
+            .collect();
+
+        Self {
+            window,
+            filters,
+            planner: FftPlanner::new(),
+        }
+    }
+
+    // `samples` are 16 kHz mono in [-1, 1]; returns frames x NUM_BINS.
+    pub fn compute(&mut self, samples: &[f32]) -> Vec<[f32; NUM_BINS]> {
+        if samples.len() < FRAME_LEN {
+            return Vec::new();
+        }
+        let fft = self.planner.plan_fft_forward(FFT_LEN);
+        let frames = 1 + (samples.len() - FRAME_LEN) / FRAME_SHIFT;
+        let mut out = Vec::with_capacity(frames);
+        let mut buf = vec![Complex32::new(0.0, 0.0); FFT_LEN];
+
+        for f in 0..frames {
+            // Kaldi works on int16-scaled samples.
+            let mut frame: Vec<f32> = samples[f * FRAME_SHIFT..f * FRAME_SHIFT + FRAME_LEN]
+                .iter()
+                .map(|s| s * 32768.0)
+                .collect();
+            let mean = frame.iter().sum::<f32>() / FRAME_LEN as f32;
+            frame.iter_mut().for_each(|s| *s -= mean);
+            for i in (1..FRAME_LEN).rev() {
+                frame[i] -= PREEMPH * frame[i - 1];
+            }
+            frame[0] -= PREEMPH * frame[0];
+
+            for (slot, value) in buf.iter_mut().enumerate() {
+                *value = Complex32::new(
+                    if slot < FRAME_LEN {
+                        frame[slot] * self.window[slot]
+                    } else {
+                        0.0
+                    },
+                    0.0,
+                );
+            }
+            fft.process(&mut buf);
+
+            let mut row = [0.0_f32; NUM_BINS];
+            for (bin, (first, weights)) in self.filters.iter().enumerate() {
+                let energy: f32 = weights
+                    .iter()
+                    .enumerate()
+                    .map(|(offset, w)| w * buf[first + offset].norm_sqr())
+                    .sum();
+                row[bin] = energy.max(f32::EPSILON).ln();
+            }
+            out.push(row);
+        }
+
+        let mut means = [0.0_f32; NUM_BINS];
+        for row in &out {
+            for (mean, value) in means.iter_mut().zip(row) {
+                *mean += value / frames as f32;
+            }
+        }
+        for row in &mut out {
+            for (value, mean) in row.iter_mut().zip(&means) {
+                *value -= mean;
+            }
+        }
+        out
+    }
+}
@@ -0,0 +1,381 @@
+mod embedder;
+mod fbank;
+mod profiles;
+
+use dingoflow_frame::{read_frame, write_response};
+use embedder::{cosine, normalize, Embedder};
+use profiles::Profiles;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io;
+use std::path::PathBuf;
+
+const MIN_ENROLL_SECONDS: f32 = 2.0;
+const MIN_IDENTIFY_SECONDS: f32 = 0.5;
+const MAX_CANDIDATES: usize = 3;
+
+// Tells enrolled speakers apart by voice. Audio comes in the same frames as
+// the ASR workers, so the host can send an utterance here and to ASR alike
+// and label the transcript "Alice:" instead of an anonymous S1.
+#[derive(Debug)]
+struct Config {
+    model_path: Option<PathBuf>,
+    profiles_path: Option<PathBuf>,
+    threads: usize,
+    threshold: f32,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    name: Option<String>,
+    sample_rate: Option<u32>,
+    threshold: Option<f32>,
+}
+
+// Voices that matched no profile this session, so the same stranger keeps
+// the same S-number until `reset`.
+struct Unknown {
+    label: String,
+    embedding: Vec<f32>,
+    clips: u32,
+}
+
+struct SpeakerId {
+    embedder: Embedder,
+    profiles: Profiles,
+    threshold: f32,
+    unknown: Vec<Unknown>,
+}
+
+impl SpeakerId {
+    fn embed(
+        &mut self,
+        req: &Request,
+        audio: &[u8],
+        min_seconds: f32,
+    ) -> Result<(Vec<f32>, f32), String> {
+        let sample_rate = req.sample_rate.unwrap_or(fbank::SAMPLE_RATE);
+        if sample_rate == 0 {
+            return Err("sampleRate must be positive".into());
+        }
+        let samples = resample_linear(&pcm16_to_f32(audio), sample_rate, fbank::SAMPLE_RATE);
+        let seconds = samples.len() as f32 / fbank::SAMPLE_RATE as f32;
+        if seconds < min_seconds {
+            return Err(format!(
+                "need at least {min_seconds:.1}s of audio, got {seconds:.2}s"
+            ));
+        }
+        Ok((self.embedder.embed(&samples)?, seconds))
+    }
+
+    fn enroll(&mut self, req: &Request, audio: &[u8]) -> Result<Value, String> {
+        let name = req
+            .name
+            .as_deref()
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .ok_or("enroll requires a name")?
+            .to_string();
+        let (embedding, seconds) = self.embed(req, audio, MIN_ENROLL_SECONDS)?;
+        let profile = self.profiles.enroll(&name, &embedding, seconds)?;
+        Ok(json!({
+            "name": profile.name,
+            "clips": profile.clips,
+            "enrolledSeconds": profile.enrolled_seconds,
+        }))
+    }
+
+    fn identify(&mut self, req: &Request, audio: &[u8]) -> Result<Value, String> {
+        let threshold = req.threshold.unwrap_or(self.threshold);
+        if !(-1.0..=1.0).contains(&threshold) {
+            return Err("threshold must be between -1 and 1".into());
+        }
+        let (embedding, seconds) = self.embed(req, audio, MIN_IDENTIFY_SECONDS)?;
+
+        let mut candidates: Vec<(String, f32)> = self
+            .profiles
+            .all()
+            .iter()
+            .filter(|profile| profile.embedding.len() == embedding.len())
+            .map(|profile| (profile.name.clone(), cosine(&profile.embedding, &embedding)))
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let candidates_json: Vec<Value> = candidates
+            .iter()
+            .take(MAX_CANDIDATES)
+            .map(|(name, score)| json!({ "name": name, "score": round(*score) }))
+            .collect();
+
+        if let Some((name, score)) = candidates.first().filter(|(_, score)| *score >= threshold) {
+            return Ok(json!({
+                "speaker": name,
+                "known": true,
+                "score": round(*score),
+                "seconds": seconds,
+                "candidates": candidates_json,
+            }));
+        }
+
+        let best_unknown = self
+            .unknown
+            .iter_mut()
+            .map(|unknown| {
+                let score = cosine(&unknown.embedding, &embedding);
+                (unknown, score)
+            })
+            .filter(|(_, score)| *score >= threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        let (label, score) = match best_unknown {
+            Some((unknown, score)) => {
+                let n = unknown.clips as f32;
+                let mean = unknown
+                    .embedding
+                    .iter()
+                    .zip(&embedding)
+                    .map(|(old, new)| (old * n + new) / (n + 1.0))
+                    .collect();
+                unknown.embedding = normalize(mean);
+                unknown.clips += 1;
+                (unknown.label.clone(), Some(score))
+            }
+            None => {
+                let label = format!("S{}", self.unknown.len() + 1);
+                self.unknown.push(Unknown {
+                    label: label.clone(),
+                    embedding,
+                    clips: 1,
+                });
+                (label, None)
+            }
+        };
+        Ok(json!({
+            "speaker": label,
+            "known": false,
+            "score": score.map(round),
+            "seconds": seconds,
+            "candidates": candidates_json,
+        }))
+    }
+
+    fn handle(&mut self, req: &Request, audio: &[u8]) -> Result<Value, String> {
+        match req.action.as_deref().unwrap_or("identify") {
+            "warmup" => {
+                let silence = vec![0.0; fbank::SAMPLE_RATE as usize];
+                self.embedder.embed(&silence)?;
+                Ok(json!({ "ready": true, "profiles": self.profiles.all().len() }))
+            }
+            "enroll" => self.enroll(req, audio),
+            "identify" => self.identify(req, audio),
+            "list" => Ok(json!({
+                "profiles": self
+                    .profiles
+                    .all()
+                    .iter()
+                    .map(|p| json!({
+                        "name": p.name,
+                        "clips": p.clips,
+                        "enrolledSeconds": p.enrolled_seconds,
+                        "updatedAtMs": p.updated_at_ms,
+                    }))
+                    .collect::<Vec<_>>()
+            })),
+            "remove" => {
+                let name = req.name.as_deref().ok_or("remove requires a name")?;
+                Ok(json!({ "removed": self.profiles.remove(name.trim())? }))
+            }
+            // Forgets this session's anonymous speakers.
+            "reset" => {
+                self.unknown.clear();
+                Ok(json!({ "reset": true }))
+            }
+            other => Err(format!("Unsupported action: {other}")),
+        }
+    }
+}
+
+fn round(score: f32) -> f32 {
+    (score * 1000.0).round() / 1000.0
+}
+
+fn pcm16_to_f32(audio: &[u8]) -> Vec<f32> {
+    audio
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0)
+        .collect()
+}
+
+fn resample_linear(samples: &[f32], from: u32, to: u32) -> Vec<f32> {
+    if from == to || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let out_len = (samples.len() as u64 * to as u64 / from as u64) as usize;
+    let step = from as f64 / to as f64;
+    (0..out_len)
+        .map(|i| {
+            let pos = i as f64 * step;
+            let index = pos as usize;
+            let frac = (pos - index as f64) as f32;
+            let a = samples[index.min(samples.len() - 1)];
+            let b = samples[(index + 1).min(samples.len() - 1)];
+            a + (b - a) * frac
+        })
+        .collect()
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut model_path: Option<PathBuf> = None;
+    let mut profiles_path: Option<PathBuf> = None;
+    let mut threads = 2_usize;
+    let mut threshold = 0.5_f32;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--model" => {
+                model_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--profiles" => {
+                profiles_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=16).contains(n))
+                    .ok_or("--threads must be between 1 and 16")?;
+                i += 2;
+            }
+            "--threshold" => {
+                threshold = value()?
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|t| (-1.0..=1.0).contains(t))
+                    .ok_or("--threshold must be between -1 and 1")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-speaker-worker --model <speaker_embedding.onnx> --profiles <speakers.json> [--threads 2] [--threshold 0.5] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    if !healthcheck && (model_path.is_none() || profiles_path.is_none()) {
+        return Err("--model and --profiles are required unless --healthcheck is used".into());
+    }
+
+    Ok(Config {
+        model_path,
+        profiles_path,
+        threads,
+        threshold,
+        serve,
+        healthcheck,
+    })
+}
+
+fn run_server(mut speaker_id: SpeakerId) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match speaker_id.handle(&req, &frame.audio) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        println!("ok");
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    let (Some(model_path), Some(profiles_path)) = (cfg.model_path, cfg.profiles_path) else {
+        eprintln!("--model and --profiles are required");
+        std::process::exit(1);
+    };
+    let setup = Profiles::load(profiles_path).and_then(|profiles| {
+        Embedder::new(&model_path, cfg.threads).map(|embedder| (embedder, profiles))
+    });
+    let (embedder, profiles) = match setup {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let speaker_id = SpeakerId {
+        embedder,
+        profiles,
+        threshold: cfg.threshold,
+        unknown: Vec::new(),
+    };
+
+    if let Err(err) = run_server(speaker_id) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
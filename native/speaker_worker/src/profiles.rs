@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::embedder::normalize;
+
+// Enrolled voices, kept in one local JSON file. A profile's embedding is
+// the normalized mean of every clip enrolled under that name, so enrolling
+// again in a different room or mood makes it more robust, not replaced.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub name: String,
+    pub embedding: Vec<f32>,
+    pub clips: u32,
+    pub enrolled_seconds: f32,
+    pub updated_at_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ProfileFile {
+    profiles: Vec<Profile>,
+}
+
+pub struct Profiles {
+    path: PathBuf,
+    profiles: Vec<Profile>,
+}
+
+impl Profiles {
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let profiles = if path.exists() {
+            let raw = fs::read_to_string(&path)
+                .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+            serde_json::from_str::<ProfileFile>(&raw)
+                .map_err(|e| format!("invalid profiles file {}: {e}", path.display()))?
+                .profiles
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, profiles })
+    }
+
+    pub fn all(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    pub fn enroll(
+        &mut self,
+        name: &str,
+        embedding: &[f32],
+        seconds: f32,
+    ) -> Result<&Profile, String> {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+        let index = match self.profiles.iter().position(|p| p.name == name) {
+            Some(index) => {
+                let profile = &mut self.profiles[index];
+                if profile.embedding.len() != embedding.len() {
+                    return Err(format!(
+                        "{name} was enrolled with a different speaker model; remove it first"
+                    ));
+                }
+                let n = profile.clips as f32;
+                let mean = profile
+                    .embedding
+                    .iter()
+                    .zip(embedding)
+                    .map(|(old, new)| (old * n + new) / (n + 1.0))
+                    .collect();
+                profile.embedding = normalize(mean);
+                profile.clips += 1;
+                profile.enrolled_seconds += seconds;
+                profile.updated_at_ms = now_ms;
+                index
+            }
+            None => {
+                self.profiles.push(Profile {
+                    name: name.to_string(),
+                    embedding: embedding.to_vec(),
+                    clips: 1,
+                    enrolled_seconds: seconds,
+                    updated_at_ms: now_ms,
+                });
+                self.profiles.len() - 1
+            }
+        };
+        self.save()?;
+        Ok(&self.profiles[index])
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<bool, String> {
+        let before = self.profiles.len();
+        self.profiles.retain(|p| p.name != name);
+        if self.profiles.len() == before {
+            return Ok(false);
+        }
+        self.save()?;
+        Ok(true)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let contents = serde_json::to_string_pretty(&ProfileFile {
+            profiles: self.profiles.clone(),
+        })
+        .map_err(|e| format!("failed to encode profiles: {e}"))?;
+        write_atomically(&self.path, &contents)
+    }
+}
+
+fn write_atomically(path: &Path, contents: &str) -> Result<(), String> {
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, path).map_err(|e| format!("failed to replace {}: {e}", path.display()))
+}
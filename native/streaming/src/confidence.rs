@@ -0,0 +1,118 @@
+use std::collections::VecDeque;
+
+// How long the rolling average looks back, in stream audio.
+pub const CONFIDENCE_WINDOW_MS: u64 = 2_000;
+
+// How much two decodes of the same audio agree, 0..=1: the Dice score of
+// their longest common word sequence. Engines don't all report token
+// probabilities, but every one re-decodes the sliding window, and speech the
+// model can make sense of decodes the same way twice while noise, a muffled
+// mic or another language flips from one decode to the next.
+pub fn agreement(previous: &[String], current: &[String]) -> Option<f32> {
+    let total = previous.len() + current.len();
+    if total == 0 {
+        return None;
+    }
+    Some(2.0 * common_words(previous, current) as f32 / total as f32)
+}
+
+fn common_words(a: &[String], b: &[String]) -> usize {
+    let mut row = vec![0_usize; b.len() + 1];
+    for word in a {
+        let mut diagonal = 0;
+        for (index, other) in b.iter().enumerate() {
+            let above = row[index + 1];
+            row[index + 1] = if word == other {
+                diagonal + 1
+            } else {
+                above.max(row[index])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+// Lowercased letters and digits, so casing and punctuation the model moves
+// around between decodes don't count as disagreement.
+pub fn word_key(text: &str) -> Option<String> {
+    let key: String = text
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    (!key.is_empty()).then_some(key)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfidenceEvent {
+    // The rolling average has been under the threshold for `below_ms`.
+    Low { average: f32, below_ms: u64 },
+    // Back over the threshold after a Low.
+    Recovered { average: f32 },
+}
+
+// Watches the per-decode agreement and fires once when its rolling average
+// stays under `threshold` for `hold_ms` of stream audio, then once more when
+// it recovers. Decodes with nothing to compare (silence) don't count either
+// way.
+pub struct ConfidenceMonitor {
+    threshold: f32,
+    hold_ms: u64,
+    scores: VecDeque<(u64, f32)>,
+    below_since_ms: Option<u64>,
+    alerted: bool,
+}
+
+impl ConfidenceMonitor {
+    pub fn new(threshold: f32, hold_ms: u32) -> Self {
+        Self {
+            threshold,
+            hold_ms: hold_ms as u64,
+            scores: VecDeque::new(),
+            below_since_ms: None,
+            alerted: false,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.scores.clear();
+        self.below_since_ms = None;
+        self.alerted = false;
+    }
+
+    pub fn observe(&mut self, at_ms: u64, score: Option<f32>) -> Option<ConfidenceEvent> {
+        if let Some(score) = score {
+            self.scores.push_back((at_ms, score));
+        }
+        while self
+            .scores
+            .front()
+            .is_some_and(|(scored_at, _)| scored_at + CONFIDENCE_WINDOW_MS < at_ms)
+        {
+            self.scores.pop_front();
+        }
+        if self.scores.is_empty() {
+            self.below_since_ms = None;
+            return None;
+        }
+        let average =
+            self.scores.iter().map(|(_, score)| score).sum::<f32>() / self.scores.len() as f32;
+
+        if average >= self.threshold {
+            self.below_since_ms = None;
+            if self.alerted {
+                self.alerted = false;
+                return Some(ConfidenceEvent::Recovered { average });
+            }
+            return None;
+        }
+        let below_since_ms = *self.below_since_ms.get_or_insert(at_ms);
+        let below_ms = at_ms - below_since_ms;
+        if !self.alerted && below_ms >= self.hold_ms {
+            self.alerted = true;
+            return Some(ConfidenceEvent::Low { average, below_ms });
+        }
+        None
+    }
+}
@@ -0,0 +1,17 @@
+// The streaming stabilization shared by every ASR engine: a sliding decode
+// window whose text is only committed once it settles. It knows nothing about
+// models; engines hand it a decode function that returns timed pieces.
+pub mod confidence;
+pub mod silence;
+pub mod stabilize;
+pub mod streamer;
+
+pub use confidence::{ConfidenceEvent, ConfidenceMonitor};
+pub use silence::DEFAULT_STREAM_SILENCE_FLOOR_DB;
+pub use stabilize::{normalize_text, SeamPiece, TimedPiece};
+pub use streamer::{
+    Commit, DecodeFn, StreamConfig, StreamToken, StreamUpdate, Streamer, Undone,
+    DEFAULT_STREAM_DECODE_INTERVAL_MS, DEFAULT_STREAM_LEFT_CONTEXT_MS,
+    DEFAULT_STREAM_LOW_CONFIDENCE_MS, DEFAULT_STREAM_MAX_WINDOW_MS, DEFAULT_STREAM_MIN_AUDIO_MS,
+    DEFAULT_STREAM_STABILITY_HOLD_MS,
+};
@@ -0,0 +1,49 @@
+use crate::confidence::word_key;
+
+// Models trained on captioned video fill silence with the captions they saw
+// most, so a mic left open in a quiet room slowly types "the" and "thank you".
+// Windows whose new audio has nothing louder than the floor aren't decoded at
+// all, and ones only a little louder don't commit a lone stock phrase.
+
+pub const DEFAULT_STREAM_SILENCE_FLOOR_DB: f32 = -60.0;
+// A stock phrase this far over the floor was really said.
+const HALLUCINATION_MARGIN_DB: f32 = 20.0;
+const FRAME_MS: usize = 20;
+
+// As word keys, joined with spaces.
+const SILENCE_HALLUCINATIONS: &[&str] = &[
+    "the",
+    "you",
+    "so",
+    "bye",
+    "okay",
+    "thank you",
+    "thank you very much",
+    "thank you so much",
+    "thanks for watching",
+    "thank you for watching",
+    "thanks for watching bye",
+    "please subscribe",
+    "subtitles by the amaraorg community",
+];
+
+// The loudest frame's RMS level in dBFS; -inf for no audio.
+pub fn peak_db(audio: &[f32], sample_rate: u32) -> f32 {
+    let frame = (sample_rate as usize * FRAME_MS / 1000).max(1);
+    let peak_power = audio
+        .chunks(frame)
+        .map(|chunk| chunk.iter().map(|sample| sample * sample).sum::<f32>() / chunk.len() as f32)
+        .fold(0.0_f32, f32::max);
+    10.0 * peak_power.log10()
+}
+
+pub fn is_silence_hallucination(text: &str) -> bool {
+    let words: Vec<String> = text.split_whitespace().filter_map(word_key).collect();
+    !words.is_empty() && SILENCE_HALLUCINATIONS.contains(&words.join(" ").as_str())
+}
+
+// Whether audio peaking at `peak_db` is quiet enough that a stock phrase
+// decoded from it is more likely made up than said.
+pub fn near_floor(peak_db: f32, floor_db: f32) -> bool {
+    peak_db < floor_db + HALLUCINATION_MARGIN_DB
+}
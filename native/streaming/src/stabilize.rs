@@ -0,0 +1,246 @@
+use crate::confidence::word_key;
+
+// A token or word with the stream-relative time it ends at, so the streaming
+// machine can tell settled text from text still near the decode edge.
+#[derive(Debug, Clone)]
+pub struct TimedPiece {
+    pub text: String,
+    pub end_seconds: f32,
+    // The model's probability for the piece, 0..=1, from engines that report
+    // one.
+    pub confidence: Option<f32>,
+}
+
+// A committed piece close to the committed edge. The next decode hears the
+// same words from a different window, and its timestamps for them can land
+// either side of the edge; these settle which of its pieces are new.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeamPiece {
+    pub key: String,
+    pub end_sample: usize,
+    pub confidence: Option<f32>,
+}
+
+pub fn normalize_text(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim()
+        .to_string()
+}
+
+fn push_text_piece(out: &mut String, piece: &str, wrote_any: &mut bool) {
+    let is_standalone_punct = piece.len() == 1
+        && piece
+            .chars()
+            .all(|ch| matches!(ch, '.' | ',' | '!' | '?' | ';' | ':' | ')'));
+    if *wrote_any && !is_standalone_punct {
+        out.push(' ');
+    }
+    out.push_str(piece);
+    *wrote_any = true;
+}
+
+pub(crate) fn seconds_to_samples(sample_rate: u32, seconds: f32) -> usize {
+    if !seconds.is_finite() || seconds <= 0.0 {
+        return 0;
+    }
+
+    (seconds * sample_rate as f32).round() as usize
+}
+
+// Joins the pieces that end after what's already committed and no later than
+// the cutoff. Returns the text and the end sample of the newest piece taken,
+// or `committed_until_sample` if none.
+//
+// Near the committed edge a piece may be a word already committed, re-timed
+// by this decode, or a new word. One matching a seam piece's text is the
+// former, even if its timestamp drifted past the tolerance. One that doesn't
+// match but ends within the tolerance past the edge is new only if this
+// decode is surer of it than the previous one was of the last word it
+// committed; without confidences (or a seam) it's treated as re-timed.
+pub fn collect_new_stable_text(
+    pieces: &[TimedPiece],
+    window_start_sample: usize,
+    committed_until_sample: usize,
+    stable_cutoff_sample: usize,
+    sample_rate: u32,
+    timestamp_tolerance_samples: usize,
+    seam: &[SeamPiece],
+) -> (String, usize) {
+    let mut out = String::new();
+    let mut wrote_any = false;
+    let mut newest_sample = committed_until_sample;
+
+    let effective_tolerance_samples = if committed_until_sample == 0 {
+        0
+    } else {
+        timestamp_tolerance_samples
+    };
+    let seam_reach = effective_tolerance_samples * 2;
+    // Seam pieces match in order, each at most once, so a word said twice
+    // across the edge still comes through the second time.
+    let mut seam_matched = 0;
+
+    for piece in pieces {
+        let end_sample =
+            window_start_sample.saturating_add(seconds_to_samples(sample_rate, piece.end_seconds));
+
+        if end_sample > stable_cutoff_sample {
+            break;
+        }
+
+        let text = piece.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        if end_sample <= committed_until_sample.saturating_add(seam_reach) {
+            let key = word_key(text);
+            let matched = seam[seam_matched..].iter().position(|seamed| {
+                Some(&seamed.key) == key.as_ref()
+                    && seamed.end_sample.abs_diff(end_sample) <= seam_reach
+            });
+            if let Some(index) = matched {
+                seam_matched += index + 1;
+                continue;
+            }
+        }
+
+        if end_sample <= committed_until_sample {
+            continue;
+        }
+
+        if end_sample <= committed_until_sample.saturating_add(effective_tolerance_samples) {
+            let surer = match (
+                piece.confidence,
+                seam.last().and_then(|last| last.confidence),
+            ) {
+                (Some(current), Some(committed)) => current > committed,
+                _ => false,
+            };
+            if !surer {
+                continue;
+            }
+        }
+
+        push_text_piece(&mut out, text, &mut wrote_any);
+        newest_sample = end_sample;
+    }
+
+    (normalize_text(&out), newest_sample)
+}
+
+// The committed pieces of a decode within reach of the new committed edge.
+pub fn seam_pieces(
+    pieces: &[TimedPiece],
+    window_start_sample: usize,
+    committed_until_sample: usize,
+    sample_rate: u32,
+    timestamp_tolerance_samples: usize,
+) -> Vec<SeamPiece> {
+    let reach_start = committed_until_sample.saturating_sub(timestamp_tolerance_samples * 2);
+    pieces
+        .iter()
+        .filter_map(|piece| {
+            let end_sample = window_start_sample
+                .saturating_add(seconds_to_samples(sample_rate, piece.end_seconds));
+            let key = word_key(&piece.text)?;
+            (end_sample > reach_start && end_sample <= committed_until_sample).then_some(
+                SeamPiece {
+                    key,
+                    end_sample,
+                    confidence: piece.confidence,
+                },
+            )
+        })
+        .collect()
+}
+
+// The unsettled tail: every piece past the committed text, cutoff or not.
+pub fn collect_preview_text(
+    pieces: &[TimedPiece],
+    window_start_sample: usize,
+    committed_until_sample: usize,
+    sample_rate: u32,
+    timestamp_tolerance_samples: usize,
+) -> String {
+    let mut out = String::new();
+    let mut wrote_any = false;
+
+    for piece in pieces {
+        let end_sample =
+            window_start_sample.saturating_add(seconds_to_samples(sample_rate, piece.end_seconds));
+
+        if end_sample <= committed_until_sample.saturating_add(timestamp_tolerance_samples) {
+            continue;
+        }
+
+        let text = piece.text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        push_text_piece(&mut out, text, &mut wrote_any);
+    }
+
+    normalize_text(&out)
+}
+
+pub fn join_preview_text(committed_text: &str, preview_suffix: &str) -> String {
+    let committed = normalize_text(committed_text);
+    let suffix = normalize_text(preview_suffix);
+
+    if committed.is_empty() {
+        return suffix;
+    }
+
+    if suffix.is_empty() {
+        return committed;
+    }
+
+    format!("{committed} {suffix}")
+}
+
+pub fn append_committed_delta(committed_text: &mut String, delta: &str) {
+    if delta.is_empty() {
+        return;
+    }
+
+    if committed_text.is_empty() {
+        committed_text.push_str(delta);
+        return;
+    }
+
+    let needs_space = !committed_text.ends_with([' ', '\n'])
+        && !delta.starts_with(['.', ',', '!', '?', ';', ':', ')']);
+    if needs_space {
+        committed_text.push(' ');
+    }
+    committed_text.push_str(delta);
+}
+
+// Drops buffered audio older than `keep_samples` before the committed edge;
+// the decode window never reaches back further than that. `audio_start_sample`
+// is the stream position of `audio[0]`.
+pub fn trim_stream_buffer(
+    audio: &mut Vec<f32>,
+    audio_start_sample: &mut usize,
+    committed_until_sample: usize,
+    keep_samples: usize,
+) {
+    let trim_until_sample = committed_until_sample.saturating_sub(keep_samples);
+    if trim_until_sample <= *audio_start_sample {
+        return;
+    }
+
+    let trim_samples = trim_until_sample - *audio_start_sample;
+    if trim_samples >= audio.len() {
+        audio.clear();
+        *audio_start_sample = trim_until_sample;
+        return;
+    }
+
+    audio.drain(0..trim_samples);
+    *audio_start_sample = trim_until_sample;
+}
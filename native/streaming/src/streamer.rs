@@ -0,0 +1,573 @@
+use crate::confidence::{agreement, word_key, ConfidenceEvent, ConfidenceMonitor};
+use crate::silence::{
+    is_silence_hallucination, near_floor, peak_db, DEFAULT_STREAM_SILENCE_FLOOR_DB,
+};
+use crate::stabilize::{
+    append_committed_delta, collect_new_stable_text, collect_preview_text, join_preview_text,
+    normalize_text, seam_pieces, seconds_to_samples, trim_stream_buffer, SeamPiece, TimedPiece,
+};
+use std::time::Instant;
+
+pub const DEFAULT_STREAM_MIN_AUDIO_MS: u32 = 120;
+pub const DEFAULT_STREAM_DECODE_INTERVAL_MS: u32 = 160;
+pub const DEFAULT_STREAM_MAX_WINDOW_MS: u32 = 6_000;
+pub const DEFAULT_STREAM_LEFT_CONTEXT_MS: u32 = 1_000;
+pub const DEFAULT_STREAM_STABILITY_HOLD_MS: u32 = 220;
+pub const DEFAULT_STREAM_LOW_CONFIDENCE_MS: u32 = 3_000;
+const STREAM_TIMESTAMP_TOLERANCE_MS: u32 = 120;
+
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    pub min_audio_ms: u32,
+    pub decode_interval_ms: u32,
+    pub max_window_ms: u32,
+    pub left_context_ms: u32,
+    pub stability_hold_ms: u32,
+    // Rolling decode agreement under this for low_confidence_ms raises a
+    // ConfidenceEvent::Low; 0 turns the check off.
+    pub low_confidence_threshold: f32,
+    pub low_confidence_ms: u32,
+    // dBFS; new audio with no 20 ms frame louder than this isn't decoded.
+    // See silence.rs.
+    pub silence_floor_db: f32,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        Self {
+            min_audio_ms: DEFAULT_STREAM_MIN_AUDIO_MS,
+            decode_interval_ms: DEFAULT_STREAM_DECODE_INTERVAL_MS,
+            max_window_ms: DEFAULT_STREAM_MAX_WINDOW_MS,
+            left_context_ms: DEFAULT_STREAM_LEFT_CONTEXT_MS,
+            stability_hold_ms: DEFAULT_STREAM_STABILITY_HOLD_MS,
+            low_confidence_threshold: 0.0,
+            low_confidence_ms: DEFAULT_STREAM_LOW_CONFIDENCE_MS,
+            silence_floor_db: DEFAULT_STREAM_SILENCE_FLOOR_DB,
+        }
+    }
+}
+
+impl StreamConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(40..=1000).contains(&self.min_audio_ms) {
+            return Err("--stream-min-audio-ms must be between 40 and 1000".into());
+        }
+
+        if !(40..=1500).contains(&self.decode_interval_ms) {
+            return Err("--stream-decode-interval-ms must be between 40 and 1500".into());
+        }
+
+        if !(800..=30000).contains(&self.max_window_ms) {
+            return Err("--stream-max-window-ms must be between 800 and 30000".into());
+        }
+
+        if !(200..=5000).contains(&self.left_context_ms) {
+            return Err("--stream-left-context-ms must be between 200 and 5000".into());
+        }
+
+        if !(80..=1200).contains(&self.stability_hold_ms) {
+            return Err("--stream-stability-hold-ms must be between 80 and 1200".into());
+        }
+
+        if !(0.0..1.0).contains(&self.low_confidence_threshold) {
+            return Err("--low-confidence-threshold must be at least 0 and below 1".into());
+        }
+
+        if !(500..=60000).contains(&self.low_confidence_ms) {
+            return Err("--low-confidence-ms must be between 500 and 60000".into());
+        }
+
+        if !(-120.0..=-20.0).contains(&self.silence_floor_db) {
+            return Err("--stream-silence-floor-db must be between -120 and -20".into());
+        }
+
+        if self.left_context_ms >= self.max_window_ms {
+            return Err("--stream-left-context-ms must be less than --stream-max-window-ms".into());
+        }
+
+        if self.stability_hold_ms >= self.max_window_ms {
+            return Err(
+                "--stream-stability-hold-ms must be less than --stream-max-window-ms".into(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+// Decodes one window into pieces with end times relative to its start.
+pub type DecodeFn<'a> = dyn FnMut(&[f32]) -> Result<Vec<TimedPiece>, String> + 'a;
+
+// A committed delta's id and its [start, end) character offsets into the
+// stream's committed text. Ids keep increasing across resets, so the host
+// never confuses a commit from an earlier stream with a current one. The
+// audio range is stream milliseconds since the reset.
+#[derive(Debug, Clone, Copy)]
+pub struct Commit {
+    pub id: u64,
+    pub start: usize,
+    pub end: usize,
+    pub audio_start_ms: u64,
+    pub audio_end_ms: u64,
+}
+
+// A piece of the decode behind an update, as the model gave it, for hosts
+// that space and format text themselves. Times are stream milliseconds since
+// the reset; pieces only carry end times, so one starts where the previous
+// ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamToken {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub confidence: Option<f32>,
+    // Behind the committed edge, so later decodes won't change it.
+    pub committed: bool,
+}
+
+// One stream_push/stream_flush answer: the newly settled delta, the full
+// preview (committed text plus the unsettled tail) and everything committed.
+#[derive(Debug, Default)]
+pub struct StreamUpdate {
+    pub text: String,
+    pub preview_text: String,
+    pub committed_text: String,
+    pub duration_seconds: f64,
+    pub commit: Option<Commit>,
+    // How well this decode agreed with the previous one over the audio both
+    // covered; None when there was nothing to compare.
+    pub confidence: Option<f32>,
+    pub confidence_event: Option<ConfidenceEvent>,
+    // The whole window's pieces; empty when the update didn't decode.
+    pub tokens: Vec<StreamToken>,
+    // Set when the push only added to the audio waiting for the next decode:
+    // how much is waiting, in ms.
+    pub buffered_ms: Option<u64>,
+}
+
+// What stream_undo_last took back out of the committed text.
+#[derive(Debug)]
+pub struct Undone {
+    pub commit: Commit,
+    pub text: String,
+    pub committed_text: String,
+}
+
+struct StreamState {
+    audio: Vec<f32>,
+    audio_start_sample: usize,
+    pending_samples: usize,
+    committed_text: String,
+    committed_until_sample: usize,
+    commits: Vec<Commit>,
+    // The previous decode's words with their stream end samples, and where
+    // its window ended.
+    last_words: Vec<(String, usize)>,
+    last_decode_end_sample: Option<usize>,
+    // What the last commit left at the committed edge.
+    seam: Vec<SeamPiece>,
+}
+
+impl StreamState {
+    fn new() -> Self {
+        Self {
+            audio: Vec::new(),
+            audio_start_sample: 0,
+            pending_samples: 0,
+            committed_text: String::new(),
+            committed_until_sample: 0,
+            commits: Vec::new(),
+            last_words: Vec::new(),
+            last_decode_end_sample: None,
+            seam: Vec::new(),
+        }
+    }
+
+    // The buffered audio past the committed edge: what a decode could commit.
+    fn uncommitted_audio(&self) -> &[f32] {
+        let from = self
+            .committed_until_sample
+            .saturating_sub(self.audio_start_sample)
+            .min(self.audio.len());
+        &self.audio[from..]
+    }
+}
+
+// Re-decodes a sliding window over the live audio and commits text once its
+// end time falls behind the stability hold, so partials never rewrite words
+// the host has already typed. Backend-agnostic: all it needs are piece end
+// times from the decode function the engine passes in, relative to the start
+// of the window it was given.
+pub struct Streamer {
+    sample_rate: u32,
+    state: Option<StreamState>,
+    min_stream_samples: usize,
+    decode_interval_samples: usize,
+    max_decode_window_samples: usize,
+    left_context_samples: usize,
+    stability_hold_samples: usize,
+    timestamp_tolerance_samples: usize,
+    trim_keep_samples: usize,
+    next_commit_id: u64,
+    confidence: Option<ConfidenceMonitor>,
+    silence_floor_db: f32,
+}
+
+impl Streamer {
+    pub fn new(cfg: &StreamConfig, sample_rate: u32) -> Self {
+        let samples = |ms: u32| ((ms as u64 * sample_rate as u64) / 1000) as usize;
+
+        let min_stream_samples = samples(cfg.min_audio_ms);
+        let max_decode_window_samples = samples(cfg.max_window_ms).max(min_stream_samples).max(1);
+        let left_context_samples = samples(cfg.left_context_ms)
+            .min(max_decode_window_samples.saturating_sub(1))
+            .max(1);
+        let stability_hold_samples = samples(cfg.stability_hold_ms)
+            .min(max_decode_window_samples.saturating_sub(1))
+            .max(1);
+        let trim_keep_samples = left_context_samples
+            .saturating_add((sample_rate as usize * 3) / 2)
+            .max(left_context_samples + 1);
+
+        Self {
+            sample_rate,
+            state: None,
+            min_stream_samples: min_stream_samples.max(1),
+            decode_interval_samples: samples(cfg.decode_interval_ms).max(1),
+            max_decode_window_samples,
+            left_context_samples,
+            stability_hold_samples,
+            timestamp_tolerance_samples: samples(STREAM_TIMESTAMP_TOLERANCE_MS).max(1),
+            trim_keep_samples,
+            next_commit_id: 1,
+            confidence: (cfg.low_confidence_threshold > 0.0).then(|| {
+                ConfidenceMonitor::new(cfg.low_confidence_threshold, cfg.low_confidence_ms)
+            }),
+            silence_floor_db: cfg.silence_floor_db,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.state = Some(StreamState::new());
+        if let Some(monitor) = self.confidence.as_mut() {
+            monitor.reset();
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.state = None;
+    }
+
+    pub fn push(
+        &mut self,
+        decode: &mut DecodeFn<'_>,
+        audio_chunk: &[f32],
+    ) -> Result<StreamUpdate, String> {
+        let state = self.state.get_or_insert_with(StreamState::new);
+        state.audio.extend_from_slice(audio_chunk);
+        state.pending_samples += audio_chunk.len();
+
+        if state.audio.len() < self.min_stream_samples
+            || state.pending_samples < self.decode_interval_samples
+        {
+            let buffered_ms = (state.pending_samples as u64 * 1000) / self.sample_rate as u64;
+            return Ok(StreamUpdate {
+                committed_text: state.committed_text.clone(),
+                buffered_ms: Some(buffered_ms),
+                ..StreamUpdate::default()
+            });
+        }
+
+        state.pending_samples = 0;
+        let stream_end_sample = state.audio_start_sample + state.audio.len();
+        let min_window_start = stream_end_sample.saturating_sub(self.max_decode_window_samples);
+        let context_window_start = state
+            .committed_until_sample
+            .saturating_sub(self.left_context_samples);
+        let window_start_sample = context_window_start
+            .max(min_window_start)
+            .max(state.audio_start_sample);
+        let window = &state.audio[window_start_sample - state.audio_start_sample..];
+        let window_samples = window.len().max(1);
+
+        let peak = peak_db(state.uncommitted_audio(), self.sample_rate);
+        if peak < self.silence_floor_db {
+            // Nothing to compare next time either.
+            state.last_words.clear();
+            state.last_decode_end_sample = None;
+            let at_ms = (stream_end_sample as u64 * 1000) / self.sample_rate as u64;
+            let confidence_event = self
+                .confidence
+                .as_mut()
+                .and_then(|monitor| monitor.observe(at_ms, None));
+            let committed_text = normalize_text(&state.committed_text);
+            return Ok(StreamUpdate {
+                preview_text: committed_text.clone(),
+                committed_text,
+                confidence_event,
+                ..StreamUpdate::default()
+            });
+        }
+
+        let started = Instant::now();
+        let mut pieces = decode(window)?;
+        let duration_seconds = started.elapsed().as_secs_f64();
+        if near_floor(peak, self.silence_floor_db) {
+            self.drop_silence_hallucination(&mut pieces, window_start_sample);
+        }
+
+        let stable_cutoff_sample = window_start_sample
+            .saturating_add(window_samples.saturating_sub(self.stability_hold_samples));
+        let (delta_text, commit) = self.commit(&pieces, window_start_sample, stable_cutoff_sample);
+
+        let state = self
+            .state
+            .as_mut()
+            .ok_or_else(|| "stream state unavailable".to_string())?;
+        let preview_suffix = collect_preview_text(
+            &pieces,
+            window_start_sample,
+            state.committed_until_sample,
+            self.sample_rate,
+            self.timestamp_tolerance_samples,
+        );
+        let preview_text = join_preview_text(&state.committed_text, &preview_suffix);
+        let committed_text = normalize_text(&state.committed_text);
+        let committed_until_sample = state.committed_until_sample;
+        let tokens = stream_tokens(
+            &pieces,
+            window_start_sample,
+            self.sample_rate,
+            |end_sample| end_sample <= committed_until_sample,
+        );
+
+        let words: Vec<(String, usize)> = pieces
+            .iter()
+            .filter_map(|piece| {
+                let end_sample = window_start_sample
+                    .saturating_add(seconds_to_samples(self.sample_rate, piece.end_seconds));
+                word_key(&piece.text).map(|word| (word, end_sample))
+            })
+            .collect();
+        let confidence = state.last_decode_end_sample.and_then(|last_end_sample| {
+            // Words right at either edge are still being cut differently
+            // from one window to the next, so only the middle is compared.
+            let from = window_start_sample + self.timestamp_tolerance_samples;
+            let until = last_end_sample.saturating_sub(self.stability_hold_samples);
+            let shared = |words: &[(String, usize)]| -> Vec<String> {
+                words
+                    .iter()
+                    .filter(|(_, end_sample)| (from..=until).contains(end_sample))
+                    .map(|(word, _)| word.clone())
+                    .collect()
+            };
+            agreement(&shared(&state.last_words), &shared(&words))
+        });
+        state.last_words = words;
+        state.last_decode_end_sample = Some(stream_end_sample);
+        let at_ms = (stream_end_sample as u64 * 1000) / self.sample_rate as u64;
+        let confidence_event = self
+            .confidence
+            .as_mut()
+            .and_then(|monitor| monitor.observe(at_ms, confidence));
+
+        trim_stream_buffer(
+            &mut state.audio,
+            &mut state.audio_start_sample,
+            state.committed_until_sample,
+            self.trim_keep_samples,
+        );
+
+        Ok(StreamUpdate {
+            text: delta_text,
+            preview_text,
+            committed_text,
+            duration_seconds,
+            commit,
+            confidence,
+            confidence_event,
+            tokens,
+            buffered_ms: None,
+        })
+    }
+
+    pub fn flush(&mut self, decode: &mut DecodeFn<'_>) -> Result<StreamUpdate, String> {
+        let Some(state) = self.state.as_ref() else {
+            return Ok(StreamUpdate::default());
+        };
+
+        if state.audio.is_empty() {
+            return Ok(StreamUpdate {
+                preview_text: state.committed_text.clone(),
+                committed_text: state.committed_text.clone(),
+                ..StreamUpdate::default()
+            });
+        }
+
+        let window_start_sample = state.audio_start_sample;
+        let flush_cutoff_sample = window_start_sample.saturating_add(state.audio.len());
+
+        let peak = peak_db(state.uncommitted_audio(), self.sample_rate);
+        if peak < self.silence_floor_db {
+            let committed_text = normalize_text(&state.committed_text);
+            return Ok(StreamUpdate {
+                preview_text: committed_text.clone(),
+                committed_text,
+                ..StreamUpdate::default()
+            });
+        }
+
+        let started = Instant::now();
+        let mut pieces = decode(&state.audio)?;
+        let duration_seconds = started.elapsed().as_secs_f64();
+        if near_floor(peak, self.silence_floor_db) {
+            self.drop_silence_hallucination(&mut pieces, window_start_sample);
+        }
+
+        let (delta_text, commit) = self.commit(&pieces, window_start_sample, flush_cutoff_sample);
+        let committed_text = self
+            .state
+            .as_ref()
+            .map(|state| normalize_text(&state.committed_text))
+            .unwrap_or_default();
+
+        // A flush commits everything it heard.
+        let tokens = stream_tokens(&pieces, window_start_sample, self.sample_rate, |_| true);
+
+        Ok(StreamUpdate {
+            text: delta_text,
+            preview_text: committed_text.clone(),
+            committed_text,
+            duration_seconds,
+            commit,
+            tokens,
+            buffered_ms: None,
+            ..StreamUpdate::default()
+        })
+    }
+
+    // Takes the last commit back out of the committed text, for "scratch
+    // that". Its audio stays consumed, so the words don't come back on the
+    // next decode.
+    pub fn undo_last(&mut self) -> Option<Undone> {
+        let state = self.state.as_mut()?;
+        let commit = state.commits.pop()?;
+        let byte_start = state
+            .committed_text
+            .char_indices()
+            .nth(commit.start)
+            .map_or(state.committed_text.len(), |(index, _)| index);
+        let text = state.committed_text.split_off(byte_start);
+        let kept = state.committed_text.trim_end().len();
+        state.committed_text.truncate(kept);
+        Some(Undone {
+            commit,
+            text,
+            committed_text: state.committed_text.clone(),
+        })
+    }
+
+    // Drops the pieces past the committed edge when all they say is a stock
+    // silence phrase.
+    fn drop_silence_hallucination(&self, pieces: &mut Vec<TimedPiece>, window_start_sample: usize) {
+        let Some(state) = self.state.as_ref() else {
+            return;
+        };
+        let tolerance = match state.committed_until_sample {
+            0 => 0,
+            _ => self.timestamp_tolerance_samples,
+        };
+        let new_from_sample =
+            (state.committed_until_sample + tolerance).saturating_sub(window_start_sample);
+        let is_new = |piece: &TimedPiece| {
+            seconds_to_samples(self.sample_rate, piece.end_seconds) > new_from_sample
+        };
+        let new_text: Vec<&str> = pieces
+            .iter()
+            .filter(|piece| is_new(piece))
+            .map(|piece| piece.text.as_str())
+            .collect();
+        if is_silence_hallucination(&new_text.join(" ")) {
+            pieces.retain(|piece| !is_new(piece));
+        }
+    }
+
+    // Appends the pieces that ended after the last commit and before the
+    // cutoff, returning the normalized delta and its commit.
+    fn commit(
+        &mut self,
+        pieces: &[TimedPiece],
+        window_start_sample: usize,
+        cutoff_sample: usize,
+    ) -> (String, Option<Commit>) {
+        let Some(state) = self.state.as_mut() else {
+            return (String::new(), None);
+        };
+
+        let (delta_text, delta_end_sample) = collect_new_stable_text(
+            pieces,
+            window_start_sample,
+            state.committed_until_sample,
+            cutoff_sample,
+            self.sample_rate,
+            self.timestamp_tolerance_samples,
+            &state.seam,
+        );
+
+        if delta_text.is_empty() {
+            return (delta_text, None);
+        }
+
+        let audio_start_sample = state.committed_until_sample;
+        append_committed_delta(&mut state.committed_text, &delta_text);
+        if delta_end_sample > state.committed_until_sample {
+            state.committed_until_sample = delta_end_sample;
+        }
+        state.seam = seam_pieces(
+            pieces,
+            window_start_sample,
+            state.committed_until_sample,
+            self.sample_rate,
+            self.timestamp_tolerance_samples,
+        );
+        let end = state.committed_text.chars().count();
+        let ms = |sample: usize| (sample as u64 * 1000) / self.sample_rate as u64;
+        let commit = Commit {
+            id: self.next_commit_id,
+            start: end - delta_text.chars().count(),
+            end,
+            audio_start_ms: ms(audio_start_sample),
+            audio_end_ms: ms(state.committed_until_sample),
+        };
+        self.next_commit_id += 1;
+        state.commits.push(commit);
+
+        (delta_text, Some(commit))
+    }
+}
+
+fn stream_tokens(
+    pieces: &[TimedPiece],
+    window_start_sample: usize,
+    sample_rate: u32,
+    committed: impl Fn(usize) -> bool,
+) -> Vec<StreamToken> {
+    let ms = |sample: usize| (sample as u64 * 1000) / sample_rate as u64;
+    let mut start_sample = window_start_sample;
+    pieces
+        .iter()
+        .map(|piece| {
+            let end_sample = window_start_sample
+                .saturating_add(seconds_to_samples(sample_rate, piece.end_seconds));
+            let token = StreamToken {
+                text: piece.text.clone(),
+                start_ms: ms(start_sample.min(end_sample)),
+                end_ms: ms(end_sample),
+                confidence: piece.confidence,
+                committed: committed(end_sample),
+            };
+            start_sample = start_sample.max(end_sample);
+            token
+        })
+        .collect()
+}
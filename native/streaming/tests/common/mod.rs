@@ -0,0 +1,100 @@
+#![allow(dead_code)]
+
+use dingoflow_streaming::TimedPiece;
+
+pub const SAMPLE_RATE: u32 = 16_000;
+
+// xorshift64*: deterministic, so a failing seed reproduces exactly.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // Uniform in [low, high].
+    pub fn range(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next() % (high - low + 1) as u64) as usize
+    }
+
+    pub fn chance(&mut self, percent: u64) -> bool {
+        self.next() % 100 < percent
+    }
+}
+
+pub fn ms(value: usize) -> usize {
+    value * SAMPLE_RATE as usize / 1000
+}
+
+// A spoken script: unique words with the absolute sample each one ends at.
+pub struct Script {
+    pub words: Vec<(String, usize)>,
+    pub total_samples: usize,
+}
+
+impl Script {
+    // Words at least `min_gap_ms` apart, so timestamp jitter can't make two
+    // of them look like the same piece.
+    pub fn random(rng: &mut Rng, min_gap_ms: usize, max_gap_ms: usize, seconds: usize) -> Self {
+        let total_samples = ms(seconds * 1000);
+        let mut words = Vec::new();
+        let mut end = 0;
+        loop {
+            end += ms(rng.range(min_gap_ms, max_gap_ms));
+            if end > total_samples {
+                break;
+            }
+            words.push((format!("w{}", words.len()), end));
+        }
+        Self {
+            words,
+            total_samples,
+        }
+    }
+
+    // Audio whose samples carry their own stream position, so the fake
+    // decoder knows where any window sits.
+    pub fn audio(&self, start: usize, end: usize) -> Vec<f32> {
+        (start..end).map(|sample| sample as f32).collect()
+    }
+
+    // Every word that ends inside the window, timed relative to its start and
+    // shifted by up to `jitter_ms` either way, like a real decoder's
+    // timestamps drifting between passes. Like a real decoder, it never puts
+    // a piece past the end of the audio it was given.
+    pub fn decode(&self, window: &[f32], rng: &mut Rng, jitter_ms: usize) -> Vec<TimedPiece> {
+        let Some(first) = window.first() else {
+            return Vec::new();
+        };
+        let start = *first as usize;
+        let end = start + window.len();
+        self.words
+            .iter()
+            .filter(|(_, word_end)| *word_end > start && *word_end <= end)
+            .map(|(text, word_end)| {
+                let jitter = rng.range(0, ms(jitter_ms) * 2) as f32 - ms(jitter_ms) as f32;
+                let relative = ((*word_end - start) as f32 + jitter).min(window.len() as f32);
+                TimedPiece {
+                    text: text.clone(),
+                    end_seconds: relative.max(0.0) / SAMPLE_RATE as f32,
+                    confidence: None,
+                }
+            })
+            .collect()
+    }
+
+    pub fn text(&self) -> String {
+        self.words
+            .iter()
+            .map(|(text, _)| text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
@@ -0,0 +1,212 @@
+mod common;
+
+use common::{ms, Rng, Script, SAMPLE_RATE};
+use dingoflow_streaming::confidence::{agreement, word_key};
+use dingoflow_streaming::{
+    ConfidenceEvent, ConfidenceMonitor, DecodeFn, StreamConfig, StreamUpdate, Streamer,
+};
+
+fn words(text: &str) -> Vec<String> {
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+#[test]
+fn agreement_is_one_for_identical_decodes() {
+    assert_eq!(agreement(&words("a b c"), &words("a b c")), Some(1.0));
+}
+
+#[test]
+fn agreement_is_zero_for_unrelated_decodes() {
+    assert_eq!(agreement(&words("a b c"), &words("x y")), Some(0.0));
+    assert_eq!(agreement(&words("a b"), &[]), Some(0.0));
+}
+
+#[test]
+fn agreement_needs_something_to_compare() {
+    assert_eq!(agreement(&[], &[]), None);
+}
+
+#[test]
+fn agreement_counts_words_in_order() {
+    // "a c" is common: 2 * 2 / (3 + 3).
+    let score = agreement(&words("a b c"), &words("a c d")).unwrap();
+    assert!((score - 2.0 / 3.0).abs() < 1e-6, "{score}");
+    // Reordered words only count once.
+    let score = agreement(&words("a b"), &words("b a")).unwrap();
+    assert!((score - 0.5).abs() < 1e-6, "{score}");
+}
+
+#[test]
+fn word_keys_ignore_case_and_punctuation() {
+    assert_eq!(word_key(" Hello,").as_deref(), Some("hello"));
+    assert_eq!(word_key("don't").as_deref(), Some("dont"));
+    assert_eq!(word_key("..."), None);
+}
+
+#[test]
+fn monitor_waits_for_the_hold() {
+    let mut monitor = ConfidenceMonitor::new(0.5, 3_000);
+    for at_ms in (0..3_000).step_by(160) {
+        assert_eq!(monitor.observe(at_ms, Some(0.1)), None, "{at_ms}");
+    }
+    match monitor.observe(3_000, Some(0.1)) {
+        Some(ConfidenceEvent::Low { below_ms, .. }) => assert_eq!(below_ms, 3_000),
+        other => panic!("expected Low, got {other:?}"),
+    }
+    // Only once per dip.
+    assert_eq!(monitor.observe(3_160, Some(0.1)), None);
+}
+
+#[test]
+fn monitor_recovers_once_the_average_does() {
+    let mut monitor = ConfidenceMonitor::new(0.5, 1_000);
+    let mut at_ms = 0;
+    while !matches!(
+        monitor.observe(at_ms, Some(0.0)),
+        Some(ConfidenceEvent::Low { .. })
+    ) {
+        at_ms += 160;
+    }
+    let mut recovered = None;
+    for _ in 0..40 {
+        at_ms += 160;
+        if let Some(event) = monitor.observe(at_ms, Some(1.0)) {
+            recovered = Some(event);
+            break;
+        }
+    }
+    match recovered {
+        Some(ConfidenceEvent::Recovered { average }) => assert!(average >= 0.5),
+        other => panic!("expected Recovered, got {other:?}"),
+    }
+}
+
+#[test]
+fn monitor_ignores_a_short_dip() {
+    let mut monitor = ConfidenceMonitor::new(0.5, 3_000);
+    let mut at_ms = 0;
+    for score in [1.0, 1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0, 1.0, 1.0] {
+        assert_eq!(monitor.observe(at_ms, Some(score)), None);
+        at_ms += 500;
+    }
+}
+
+#[test]
+fn silence_restarts_the_hold() {
+    let mut monitor = ConfidenceMonitor::new(0.5, 3_000);
+    for at_ms in (0..1_000).step_by(160) {
+        assert_eq!(monitor.observe(at_ms, Some(0.0)), None);
+    }
+    // Long enough for every score to leave the rolling window.
+    for at_ms in (1_000..4_000).step_by(160) {
+        assert_eq!(monitor.observe(at_ms, None), None, "{at_ms}");
+    }
+    for at_ms in (4_000..7_000).step_by(160) {
+        assert_eq!(monitor.observe(at_ms, Some(0.0)), None, "{at_ms}");
+    }
+}
+
+fn watched() -> Streamer {
+    let cfg = StreamConfig {
+        low_confidence_threshold: 0.5,
+        low_confidence_ms: 2_000,
+        ..StreamConfig::default()
+    };
+    let mut streamer = Streamer::new(&cfg, SAMPLE_RATE);
+    streamer.reset();
+    streamer
+}
+
+fn push_all(
+    streamer: &mut Streamer,
+    script: &Script,
+    decode: &mut DecodeFn<'_>,
+) -> Vec<StreamUpdate> {
+    let mut updates = Vec::new();
+    let mut position = 0;
+    while position < script.total_samples {
+        let end = (position + ms(160)).min(script.total_samples);
+        updates.push(streamer.push(decode, &script.audio(position, end)).unwrap());
+        position = end;
+    }
+    updates
+}
+
+#[test]
+fn steady_decodes_score_high() {
+    for seed in 0..16 {
+        let mut rng = Rng::new(seed);
+        let script = Script::random(&mut rng, 250, 600, 12);
+        let mut streamer = watched();
+        let mut decode_rng = Rng::new(rng.next());
+        let updates = push_all(&mut streamer, &script, &mut |window| {
+            Ok(script.decode(window, &mut decode_rng, 40))
+        });
+
+        let scores: Vec<f32> = updates.iter().filter_map(|u| u.confidence).collect();
+        assert!(!scores.is_empty(), "seed {seed}");
+        let average = scores.iter().sum::<f32>() / scores.len() as f32;
+        assert!(average > 0.9, "seed {seed}: {average}");
+        assert!(
+            updates.iter().all(|u| u.confidence_event.is_none()),
+            "seed {seed}"
+        );
+    }
+}
+
+#[test]
+fn decodes_that_keep_changing_raise_low_confidence() {
+    let mut rng = Rng::new(7);
+    let script = Script::random(&mut rng, 250, 600, 12);
+    let mut streamer = watched();
+    // Same timing, different words every pass: what noise or another
+    // language looks like to the model.
+    let mut decode_rng = Rng::new(rng.next());
+    let updates = push_all(&mut streamer, &script, &mut |window| {
+        let mut pieces = script.decode(window, &mut decode_rng, 0);
+        for piece in &mut pieces {
+            piece.text = format!("g{}", decode_rng.next() % 1000);
+        }
+        Ok(pieces)
+    });
+
+    let lows: Vec<usize> = updates
+        .iter()
+        .enumerate()
+        .filter(|(_, u)| matches!(u.confidence_event, Some(ConfidenceEvent::Low { .. })))
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(lows.len(), 1, "{lows:?}");
+    // Within the hold plus a couple of decodes of the stream starting.
+    assert!(lows[0] * 160 <= 2_000 + 1_000, "{lows:?}");
+}
+
+#[test]
+fn silence_has_no_score() {
+    let mut streamer = watched();
+    let mut decode = |_: &[f32]| Ok(Vec::new());
+    for _ in 0..40 {
+        let update = streamer.push(&mut decode, &vec![0.0; ms(160)]).unwrap();
+        assert_eq!(update.confidence, None);
+        assert!(update.confidence_event.is_none());
+    }
+}
+
+#[test]
+fn confidence_is_off_by_default() {
+    let mut rng = Rng::new(3);
+    let script = Script::random(&mut rng, 250, 600, 8);
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    let mut decode_rng = Rng::new(rng.next());
+    let updates = push_all(&mut streamer, &script, &mut |window| {
+        let mut pieces = script.decode(window, &mut decode_rng, 0);
+        for piece in &mut pieces {
+            piece.text = format!("g{}", decode_rng.next() % 1000);
+        }
+        Ok(pieces)
+    });
+    // Still scored, never raised.
+    assert!(updates.iter().any(|u| u.confidence.is_some()));
+    assert!(updates.iter().all(|u| u.confidence_event.is_none()));
+}
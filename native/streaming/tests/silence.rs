@@ -0,0 +1,131 @@
+mod common;
+
+use common::{ms, SAMPLE_RATE};
+use dingoflow_streaming::silence::{is_silence_hallucination, near_floor, peak_db};
+use dingoflow_streaming::{StreamConfig, Streamer, TimedPiece, DEFAULT_STREAM_SILENCE_FLOOR_DB};
+
+// A constant level `db` dBFS.
+fn level(db: f32, samples: usize) -> Vec<f32> {
+    vec![10_f32.powf(db / 20.0); samples]
+}
+
+fn said(text: &str, end_seconds: f32) -> Vec<TimedPiece> {
+    text.split_whitespace()
+        .map(|word| TimedPiece {
+            text: word.to_string(),
+            end_seconds,
+            confidence: None,
+        })
+        .collect()
+}
+
+#[test]
+fn peak_is_the_loudest_frame() {
+    let mut audio = level(-70.0, ms(1000));
+    audio.extend(level(-20.0, ms(20)));
+    assert!((peak_db(&audio, SAMPLE_RATE) + 20.0).abs() < 0.1);
+    assert_eq!(peak_db(&[], SAMPLE_RATE), f32::NEG_INFINITY);
+    assert_eq!(peak_db(&[0.0; 320], SAMPLE_RATE), f32::NEG_INFINITY);
+}
+
+#[test]
+fn stock_phrases_match_whole_and_loosely() {
+    assert!(is_silence_hallucination("Thank you."));
+    assert!(is_silence_hallucination(" the "));
+    assert!(is_silence_hallucination("Thanks for watching!"));
+    assert!(!is_silence_hallucination("thank you for the notes"));
+    assert!(!is_silence_hallucination(""));
+}
+
+#[test]
+fn near_floor_has_a_margin() {
+    assert!(near_floor(-50.0, -60.0));
+    assert!(!near_floor(-30.0, -60.0));
+}
+
+#[test]
+fn silent_windows_are_not_decoded() {
+    let mut calls = 0;
+    let mut decode = |_: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        calls += 1;
+        Ok(said("thank you", 0.5))
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    for _ in 0..40 {
+        let update = streamer.push(&mut decode, &level(-80.0, ms(160))).unwrap();
+        assert!(update.commit.is_none());
+        assert_eq!(update.preview_text, "");
+    }
+    let update = streamer.flush(&mut decode).unwrap();
+    assert!(update.commit.is_none());
+    assert_eq!(calls, 0);
+}
+
+#[test]
+fn stock_phrases_from_room_noise_are_dropped() {
+    // Over the floor, but not by much: a fan, not a voice.
+    let mut decode = |window: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        Ok(said(
+            "thank you",
+            window.len() as f32 / SAMPLE_RATE as f32 * 0.2,
+        ))
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    let noise = DEFAULT_STREAM_SILENCE_FLOOR_DB + 10.0;
+    for _ in 0..40 {
+        let update = streamer.push(&mut decode, &level(noise, ms(160))).unwrap();
+        assert!(update.commit.is_none(), "{update:?}");
+    }
+    assert_eq!(streamer.flush(&mut decode).unwrap().committed_text, "");
+}
+
+#[test]
+fn stock_phrases_really_said_are_kept() {
+    let mut decode = |window: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        Ok(said(
+            "thank you",
+            window.len() as f32 / SAMPLE_RATE as f32 * 0.2,
+        ))
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    streamer.push(&mut decode, &level(-20.0, ms(500))).unwrap();
+    assert_eq!(
+        streamer.flush(&mut decode).unwrap().committed_text,
+        "thank you"
+    );
+}
+
+#[test]
+fn other_words_near_the_floor_are_kept() {
+    let mut decode = |window: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        Ok(said(
+            "whisper",
+            window.len() as f32 / SAMPLE_RATE as f32 * 0.2,
+        ))
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    streamer
+        .push(
+            &mut decode,
+            &level(DEFAULT_STREAM_SILENCE_FLOOR_DB + 10.0, ms(500)),
+        )
+        .unwrap();
+    assert_eq!(
+        streamer.flush(&mut decode).unwrap().committed_text,
+        "whisper"
+    );
+}
+
+#[test]
+fn floor_is_validated() {
+    let cfg = StreamConfig {
+        silence_floor_db: -10.0,
+        ..StreamConfig::default()
+    };
+    assert!(cfg.validate().is_err());
+    assert!(StreamConfig::default().validate().is_ok());
+}
@@ -0,0 +1,273 @@
+mod common;
+
+use common::{ms, Rng, SAMPLE_RATE};
+use dingoflow_streaming::stabilize::{
+    append_committed_delta, collect_new_stable_text, collect_preview_text, join_preview_text,
+    seam_pieces, trim_stream_buffer,
+};
+use dingoflow_streaming::{normalize_text, SeamPiece, TimedPiece};
+
+const TOLERANCE: usize = 1_920;
+
+fn pieces(items: &[(&str, f32)]) -> Vec<TimedPiece> {
+    items
+        .iter()
+        .map(|(text, end_seconds)| TimedPiece {
+            text: text.to_string(),
+            end_seconds: *end_seconds,
+            confidence: None,
+        })
+        .collect()
+}
+
+#[test]
+fn normalize_collapses_whitespace() {
+    assert_eq!(normalize_text("  hello \n  world\t"), "hello world");
+    assert_eq!(normalize_text(" \n "), "");
+}
+
+#[test]
+fn stable_text_stops_at_the_cutoff() {
+    let pieces = pieces(&[("one", 0.2), ("two", 0.5), ("three", 0.9)]);
+    let (text, until) =
+        collect_new_stable_text(&pieces, 0, 0, ms(600), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "one two");
+    assert_eq!(until, ms(500));
+}
+
+#[test]
+fn stable_text_skips_what_is_already_committed() {
+    let pieces = pieces(&[("one", 0.2), ("two", 0.5), ("three", 0.9)]);
+    let (text, until) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "three");
+    assert_eq!(until, ms(900));
+}
+
+#[test]
+fn stable_text_absorbs_timestamp_jitter() {
+    // "two" was committed ending at 0.5s; a later pass puts it at 0.58s.
+    let pieces = pieces(&[("two", 0.58), ("three", 0.9)]);
+    let (text, _) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "three");
+}
+
+#[test]
+fn stable_text_has_no_tolerance_before_the_first_commit() {
+    let pieces = pieces(&[("hi", 0.05)]);
+    let (text, until) =
+        collect_new_stable_text(&pieces, 0, 0, ms(500), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "hi");
+    assert_eq!(until, ms(50));
+}
+
+#[test]
+fn stable_text_is_relative_to_the_window() {
+    let pieces = pieces(&[("later", 0.3)]);
+    let start = ms(10_000);
+    let (text, until) = collect_new_stable_text(
+        &pieces,
+        start,
+        ms(9_000),
+        start + ms(500),
+        SAMPLE_RATE,
+        TOLERANCE,
+        &[],
+    );
+    assert_eq!(text, "later");
+    assert_eq!(until, start + ms(300));
+}
+
+#[test]
+fn stable_text_keeps_committed_edge_when_nothing_settles() {
+    let pieces = pieces(&[("edge", 0.9)]);
+    let (text, until) =
+        collect_new_stable_text(&pieces, 0, ms(200), ms(600), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "");
+    assert_eq!(until, ms(200));
+}
+
+#[test]
+fn stable_text_attaches_punctuation_and_drops_blanks() {
+    let pieces = pieces(&[("hello", 0.2), (",", 0.25), ("  ", 0.3), ("world", 0.4)]);
+    let (text, _) = collect_new_stable_text(&pieces, 0, 0, ms(1000), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "hello, world");
+}
+
+#[test]
+fn stable_text_ignores_invalid_timestamps() {
+    let pieces = pieces(&[("nan", f32::NAN), ("neg", -1.0), ("ok", 0.3)]);
+    let (text, _) = collect_new_stable_text(&pieces, 0, ms(100), ms(1000), SAMPLE_RATE, 0, &[]);
+    assert_eq!(text, "ok");
+}
+
+fn confident(items: &[(&str, f32, f32)]) -> Vec<TimedPiece> {
+    items
+        .iter()
+        .map(|(text, end_seconds, confidence)| TimedPiece {
+            text: text.to_string(),
+            end_seconds: *end_seconds,
+            confidence: Some(*confidence),
+        })
+        .collect()
+}
+
+fn seam(items: &[(&str, f32, Option<f32>)]) -> Vec<SeamPiece> {
+    items
+        .iter()
+        .map(|(key, end_seconds, confidence)| SeamPiece {
+            key: key.to_string(),
+            end_sample: (*end_seconds * SAMPLE_RATE as f32) as usize,
+            confidence: *confidence,
+        })
+        .collect()
+}
+
+#[test]
+fn seam_drops_a_committed_word_that_drifted_past_the_tolerance() {
+    // "two" was committed ending at 0.5s; this decode puts it at 0.7s.
+    let pieces = pieces(&[("two", 0.7), ("three", 0.9)]);
+    let seam = seam(&[("two", 0.5, None)]);
+    let (text, until) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &seam);
+    assert_eq!(text, "three");
+    assert_eq!(until, ms(900));
+    // Without the seam it came out twice.
+    let (text, _) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &[]);
+    assert_eq!(text, "two three");
+}
+
+#[test]
+fn seam_matches_each_committed_word_once() {
+    let pieces = pieces(&[("that", 0.52), ("that", 0.7)]);
+    let seam = seam(&[("that", 0.5, None)]);
+    let (text, _) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &seam);
+    assert_eq!(text, "that");
+}
+
+#[test]
+fn seam_keeps_a_new_word_the_decode_is_surer_of() {
+    // "the" ends inside the tolerance; timestamps alone would drop it.
+    let pieces = confident(&[("to", 0.5, 0.9), ("the", 0.58, 0.8), ("end", 0.9, 0.9)]);
+    let seam = seam(&[("going", 0.3, Some(0.9)), ("to", 0.5, Some(0.4))]);
+    let (text, _) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &seam);
+    assert_eq!(text, "the end");
+}
+
+#[test]
+fn seam_drops_a_less_sure_rehearing_of_the_last_word() {
+    // "gonna" is the decode re-hearing the committed "to", and less sure of it.
+    let pieces = confident(&[("gonna", 0.56, 0.3), ("end", 0.9, 0.9)]);
+    let seam = seam(&[("going", 0.3, Some(0.9)), ("to", 0.5, Some(0.8))]);
+    let (text, _) =
+        collect_new_stable_text(&pieces, 0, ms(500), ms(1000), SAMPLE_RATE, TOLERANCE, &seam);
+    assert_eq!(text, "end");
+}
+
+#[test]
+fn seam_pieces_are_the_committed_words_near_the_edge() {
+    let pieces = confident(&[
+        ("far", 0.1, 0.9),
+        ("Near,", 0.35, 0.7),
+        ("edge", 0.5, 0.6),
+        ("later", 0.8, 0.9),
+    ]);
+    let found = seam_pieces(&pieces, 0, ms(500), SAMPLE_RATE, TOLERANCE);
+    assert_eq!(
+        found,
+        seam(&[("near", 0.35, Some(0.7)), ("edge", 0.5, Some(0.6))])
+    );
+}
+
+#[test]
+fn preview_includes_the_unsettled_tail() {
+    let pieces = pieces(&[("one", 0.2), ("two", 0.5), ("three", 0.9)]);
+    let preview = collect_preview_text(&pieces, 0, ms(200), SAMPLE_RATE, TOLERANCE);
+    assert_eq!(preview, "two three");
+    assert_eq!(join_preview_text("one", &preview), "one two three");
+}
+
+#[test]
+fn join_preview_handles_empty_sides() {
+    assert_eq!(join_preview_text("", " tail "), "tail");
+    assert_eq!(join_preview_text(" head ", ""), "head");
+    assert_eq!(join_preview_text("", ""), "");
+}
+
+#[test]
+fn append_delta_spaces_words_but_not_punctuation() {
+    let mut text = String::new();
+    append_committed_delta(&mut text, "hello");
+    append_committed_delta(&mut text, "world");
+    append_committed_delta(&mut text, ".");
+    append_committed_delta(&mut text, "");
+    assert_eq!(text, "hello world.");
+
+    let mut text = "line\n".to_string();
+    append_committed_delta(&mut text, "next");
+    assert_eq!(text, "line\nnext");
+}
+
+#[test]
+fn append_delta_only_ever_extends() {
+    let mut rng = Rng::new(7);
+    let tokens = ["a", "bc", ",", ".", "def", "?", "g h", ")"];
+    for _ in 0..200 {
+        let mut text = String::new();
+        for _ in 0..rng.range(1, 20) {
+            let before = text.clone();
+            let delta = tokens[rng.range(0, tokens.len() - 1)];
+            append_committed_delta(&mut text, delta);
+            assert!(text.starts_with(&before), "{before:?} -> {text:?}");
+            assert!(text.ends_with(delta));
+            assert!(text.len() - before.len() <= delta.len() + 1);
+        }
+    }
+}
+
+#[test]
+fn trim_keeps_the_tail_and_the_stream_position() {
+    let mut rng = Rng::new(11);
+    for _ in 0..2_000 {
+        let start = rng.range(0, 50_000);
+        let len = rng.range(0, 50_000);
+        let committed = rng.range(0, 120_000);
+        let keep = rng.range(1, 40_000);
+
+        let original: Vec<f32> = (start..start + len).map(|sample| sample as f32).collect();
+        let mut audio = original.clone();
+        let mut audio_start = start;
+        trim_stream_buffer(&mut audio, &mut audio_start, committed, keep);
+
+        let trim_until = committed.saturating_sub(keep);
+        assert_eq!(audio_start, start.max(trim_until));
+        if audio_start <= start + len {
+            // The buffer still ends where it did and holds the original tail.
+            assert_eq!(audio_start + audio.len(), start + len);
+            assert_eq!(audio[..], original[audio_start - start..]);
+        } else {
+            assert!(audio.is_empty());
+        }
+        if let Some(first) = audio.first() {
+            assert_eq!(*first as usize, audio_start);
+        }
+    }
+}
+
+#[test]
+fn trim_never_drops_the_keep_region() {
+    let mut audio: Vec<f32> = (0..10_000).map(|sample| sample as f32).collect();
+    let mut audio_start = 0;
+    trim_stream_buffer(&mut audio, &mut audio_start, 6_000, 4_000);
+    assert_eq!(audio_start, 2_000);
+    assert_eq!(audio.len(), 8_000);
+
+    // A committed edge that moved backwards must not grow the buffer back.
+    trim_stream_buffer(&mut audio, &mut audio_start, 3_000, 4_000);
+    assert_eq!(audio_start, 2_000);
+    assert_eq!(audio.len(), 8_000);
+}
@@ -0,0 +1,365 @@
+mod common;
+
+use common::{ms, Rng, Script, SAMPLE_RATE};
+use dingoflow_streaming::{Commit, StreamConfig, StreamToken, StreamUpdate, Streamer, TimedPiece};
+
+const SEEDS: u64 = 64;
+
+// Checks one update against everything committed so far.
+struct Ledger {
+    committed_text: String,
+    last: Option<Commit>,
+    deltas: Vec<String>,
+}
+
+impl Ledger {
+    fn new() -> Self {
+        Self {
+            committed_text: String::new(),
+            last: None,
+            deltas: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, update: &StreamUpdate) {
+        // Committed text is append-only: nothing the host typed is rewritten.
+        assert!(
+            update.committed_text.starts_with(&self.committed_text),
+            "{:?} rewrote {:?}",
+            update.committed_text,
+            self.committed_text
+        );
+        // Pushes too short to decode carry no preview at all.
+        assert!(
+            update.preview_text.is_empty()
+                || update.preview_text.starts_with(&update.committed_text),
+            "preview {:?} doesn't extend {:?}",
+            update.preview_text,
+            update.committed_text
+        );
+
+        let Some(commit) = update.commit else {
+            assert!(update.text.is_empty());
+            assert_eq!(update.committed_text, self.committed_text);
+            return;
+        };
+        assert!(!update.text.is_empty());
+
+        let chars: Vec<char> = update.committed_text.chars().collect();
+        assert_eq!(commit.end, chars.len());
+        assert_eq!(
+            chars[commit.start..commit.end].iter().collect::<String>(),
+            update.text
+        );
+        if let Some(last) = self.last {
+            assert_eq!(commit.id, last.id + 1);
+            assert!(commit.start == last.end || commit.start == last.end + 1);
+            assert_eq!(commit.audio_start_ms, last.audio_end_ms);
+        } else {
+            assert!(commit.start <= 1);
+        }
+        assert!(commit.audio_end_ms >= commit.audio_start_ms);
+
+        self.committed_text = update.committed_text.clone();
+        self.last = Some(commit);
+        self.deltas.push(update.text.clone());
+    }
+}
+
+// Feeds a script in random-sized chunks, then flushes, checking every update.
+fn run_script(script: &Script, rng: &mut Rng, jitter_ms: usize) -> Ledger {
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    let mut ledger = Ledger::new();
+    let mut decode_rng = Rng::new(rng.next());
+    let mut decode = |window: &[f32]| Ok(script.decode(window, &mut decode_rng, jitter_ms));
+
+    let mut position = 0;
+    while position < script.total_samples {
+        let end = (position + ms(rng.range(10, 400))).min(script.total_samples);
+        let update = streamer
+            .push(&mut decode, &script.audio(position, end))
+            .unwrap();
+        ledger.record(&update);
+        position = end;
+    }
+    let update = streamer.flush(&mut decode).unwrap();
+    ledger.record(&update);
+    ledger
+}
+
+#[test]
+fn commits_every_word_exactly_once() {
+    for seed in 0..SEEDS {
+        let mut rng = Rng::new(seed);
+        let script = Script::random(&mut rng, 250, 900, 20);
+        let ledger = run_script(&script, &mut rng, 0);
+        assert_eq!(ledger.committed_text, script.text(), "seed {seed}");
+    }
+}
+
+#[test]
+fn jittered_timestamps_never_duplicate_or_lose_words() {
+    for seed in 0..SEEDS {
+        let mut rng = Rng::new(1_000 + seed);
+        let script = Script::random(&mut rng, 250, 900, 20);
+        let ledger = run_script(&script, &mut rng, 40);
+        assert_eq!(ledger.committed_text, script.text(), "seed {seed}");
+    }
+}
+
+#[test]
+fn deltas_rebuild_the_committed_text() {
+    for seed in 0..SEEDS {
+        let mut rng = Rng::new(2_000 + seed);
+        let script = Script::random(&mut rng, 250, 600, 10);
+        let ledger = run_script(&script, &mut rng, 20);
+        assert_eq!(
+            ledger.deltas.join(" "),
+            ledger.committed_text,
+            "seed {seed}"
+        );
+    }
+}
+
+#[test]
+fn commits_wait_for_the_stability_hold() {
+    let script = Script {
+        words: vec![("early".into(), ms(300)), ("late".into(), ms(950))],
+        total_samples: ms(1000),
+    };
+    let mut rng = Rng::new(3);
+    let mut decode = |window: &[f32]| Ok(script.decode(window, &mut rng, 0));
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+
+    let update = streamer
+        .push(&mut decode, &script.audio(0, ms(1000)))
+        .unwrap();
+    // "late" ends inside the last 220 ms, so it's only previewed.
+    assert_eq!(update.text, "early");
+    assert_eq!(update.preview_text, "early late");
+
+    let update = streamer.flush(&mut decode).unwrap();
+    assert_eq!(update.text, "late");
+    assert_eq!(update.committed_text, "early late");
+}
+
+#[test]
+fn tokens_are_the_raw_pieces_flagged_by_the_committed_edge() {
+    let mut decode = |window: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        let end = window.len() as f32 / SAMPLE_RATE as f32;
+        Ok(vec![
+            TimedPiece {
+                text: " Hello".into(),
+                end_seconds: 0.3,
+                confidence: Some(0.9),
+            },
+            TimedPiece {
+                text: ",".into(),
+                end_seconds: 0.32,
+                confidence: None,
+            },
+            TimedPiece {
+                text: " world".into(),
+                end_seconds: end - 0.05,
+                confidence: None,
+            },
+        ])
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+
+    let update = streamer.push(&mut decode, &vec![0.1; ms(1000)]).unwrap();
+    assert_eq!(update.text, "Hello,");
+    assert_eq!(
+        update.tokens,
+        vec![
+            StreamToken {
+                text: " Hello".into(),
+                start_ms: 0,
+                end_ms: 300,
+                confidence: Some(0.9),
+                committed: true,
+            },
+            StreamToken {
+                text: ",".into(),
+                start_ms: 300,
+                end_ms: 320,
+                confidence: None,
+                committed: true,
+            },
+            StreamToken {
+                text: " world".into(),
+                start_ms: 320,
+                end_ms: 950,
+                confidence: None,
+                committed: false,
+            },
+        ]
+    );
+
+    // Pushes that don't decode have no tokens.
+    let update = streamer.push(&mut decode, &vec![0.1; ms(10)]).unwrap();
+    assert!(update.tokens.is_empty());
+
+    let update = streamer.flush(&mut decode).unwrap();
+    assert!(update.tokens.iter().all(|token| token.committed));
+}
+
+#[test]
+fn short_pushes_wait_for_enough_audio() {
+    let mut calls = 0;
+    let mut decode = |_: &[f32]| -> Result<Vec<TimedPiece>, String> {
+        calls += 1;
+        Ok(Vec::new())
+    };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    // Loud enough to get past the silence floor.
+    for _ in 0..3 {
+        let update = streamer.push(&mut decode, &vec![0.1; ms(40)]).unwrap();
+        assert!(update.commit.is_none());
+    }
+    streamer.push(&mut decode, &vec![0.1; ms(40)]).unwrap();
+    assert_eq!(calls, 1);
+}
+
+#[test]
+fn buffered_pushes_say_how_much_audio_is_waiting() {
+    let mut decode = |_: &[f32]| -> Result<Vec<TimedPiece>, String> { Ok(Vec::new()) };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    let mut waiting = 0;
+    loop {
+        let update = streamer.push(&mut decode, &vec![0.1; ms(10)]).unwrap();
+        match update.buffered_ms {
+            Some(buffered_ms) => {
+                waiting += 10;
+                assert_eq!(buffered_ms, waiting);
+            }
+            None => break,
+        }
+    }
+    assert!(waiting > 0);
+    // A decode starts the count over.
+    let update = streamer.push(&mut decode, &vec![0.1; ms(10)]).unwrap();
+    assert_eq!(update.buffered_ms, Some(10));
+}
+
+#[test]
+fn decode_errors_are_returned() {
+    let mut decode = |_: &[f32]| -> Result<Vec<TimedPiece>, String> { Err("boom".into()) };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+    let result = streamer.push(&mut decode, &vec![0.1; ms(500)]);
+    assert_eq!(result.err().as_deref(), Some("boom"));
+}
+
+#[test]
+fn flush_without_a_stream_is_empty() {
+    let mut decode = |_: &[f32]| -> Result<Vec<TimedPiece>, String> { unreachable!() };
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    let update = streamer.flush(&mut decode).unwrap();
+    assert!(update.text.is_empty() && update.commit.is_none());
+
+    streamer.reset();
+    let update = streamer.flush(&mut decode).unwrap();
+    assert!(update.committed_text.is_empty());
+}
+
+#[test]
+fn commit_ids_keep_increasing_across_resets() {
+    let script = Script {
+        words: vec![("one".into(), ms(300)), ("two".into(), ms(900))],
+        total_samples: ms(1000),
+    };
+    let mut rng = Rng::new(5);
+    let mut decode = |window: &[f32]| Ok(script.decode(window, &mut rng, 0));
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+
+    let mut ids = Vec::new();
+    for _ in 0..3 {
+        streamer.reset();
+        streamer
+            .push(&mut decode, &script.audio(0, ms(1000)))
+            .unwrap();
+        let update = streamer.flush(&mut decode).unwrap();
+        assert_eq!(update.committed_text, "one two");
+        ids.push(update.commit.map(|commit| commit.id));
+    }
+    assert_eq!(ids, vec![Some(2), Some(4), Some(6)]);
+}
+
+#[test]
+fn undo_takes_back_the_last_commit_only() {
+    let script = Script {
+        words: vec![
+            ("keep".into(), ms(300)),
+            (",".into(), ms(500)),
+            ("scratch".into(), ms(1200)),
+            ("after".into(), ms(2000)),
+        ],
+        total_samples: ms(2400),
+    };
+    let mut rng = Rng::new(9);
+    let mut decode = |window: &[f32]| Ok(script.decode(window, &mut rng, 0));
+    let mut streamer = Streamer::new(&StreamConfig::default(), SAMPLE_RATE);
+    streamer.reset();
+
+    streamer
+        .push(&mut decode, &script.audio(0, ms(800)))
+        .unwrap();
+    let update = streamer
+        .push(&mut decode, &script.audio(ms(800), ms(1600)))
+        .unwrap();
+    assert_eq!(update.committed_text, "keep, scratch");
+
+    let undone = streamer.undo_last().unwrap();
+    assert_eq!(undone.text, "scratch");
+    assert_eq!(undone.committed_text, "keep,");
+    assert_eq!(undone.commit.id, update.commit.unwrap().id);
+
+    // The undone words' audio stays consumed; later commits carry on from it.
+    let update = streamer
+        .push(&mut decode, &script.audio(ms(1600), ms(2400)))
+        .unwrap();
+    let update = if update.commit.is_some() {
+        update
+    } else {
+        streamer.flush(&mut decode).unwrap()
+    };
+    assert_eq!(update.committed_text, "keep, after");
+    assert_eq!(update.commit.unwrap().start, "keep, ".chars().count());
+
+    assert!(streamer.undo_last().is_some());
+    assert!(streamer.undo_last().is_some());
+    assert!(streamer.undo_last().is_none());
+}
+
+#[test]
+fn long_streams_keep_the_buffer_bounded() {
+    // An hour-scale stream must not re-decode from the start: every window
+    // stays within max_window_ms no matter how much was pushed.
+    let cfg = StreamConfig::default();
+    let max_window = ms(cfg.max_window_ms as usize);
+    let mut rng = Rng::new(13);
+    let script = Script::random(&mut rng, 250, 900, 120);
+    let mut decode_rng = Rng::new(14);
+    let mut longest = 0;
+    let mut decode = |window: &[f32]| {
+        longest = longest.max(window.len());
+        Ok(script.decode(window, &mut decode_rng, 0))
+    };
+    let mut streamer = Streamer::new(&cfg, SAMPLE_RATE);
+    streamer.reset();
+    let mut position = 0;
+    while position < script.total_samples {
+        let end = (position + ms(200)).min(script.total_samples);
+        streamer
+            .push(&mut decode, &script.audio(position, end))
+            .unwrap();
+        position = end;
+    }
+    assert!(longest <= max_window, "{longest} > {max_window}");
+}
@@ -0,0 +1,397 @@
+use crate::locale::Locale;
+use crate::spoken::{bare, capitalize, Commands, Piece};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+// Named bundles of text post-processing the host picks per stream, e.g.
+// "code" while a terminal has focus and "prose" in a document. Spoken
+// commands ("comma", "new line") are interpreted first, and what they insert
+// is never recased or stripped by the profile. Partials get
+// everything except the final-only touches (terminal punctuation, trailing
+// space), so a partial is always a prefix of what the final will look like.
+
+const FILLERS: &[&str] = &["um", "umm", "uh", "uhh", "er", "erm", "hmm", "mm"];
+const CLOSING_PUNCT: &[char] = &['.', ',', '!', '?', ';', ':', ')'];
+const SENTENCE_END: &[char] = &['.', '!', '?'];
+const STRIP_PUNCT: &[char] = &['.', ',', '!', '?', ';', ':'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Punctuation {
+    Keep,
+    // Remove sentence punctuation entirely (shell commands, identifiers).
+    Strip,
+    // Make sure a final ends like a sentence.
+    Terminal,
+    // Drop a final's trailing period, as chat messages rarely have one.
+    DropFinalPeriod,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Capitalization {
+    Keep,
+    Sentence,
+    Lower,
+}
+
+#[derive(Debug, Clone)]
+pub struct Profile {
+    remove_fillers: bool,
+    punctuation: Punctuation,
+    capitalization: Capitalization,
+    // A final ends with a space so the next utterance doesn't run into it.
+    trailing_space: bool,
+    spoken_commands: bool,
+    // Lowercased spoken phrase -> replacement, longest phrases tried first.
+    dictionary: Vec<(Vec<String>, String)>,
+}
+
+impl Profile {
+    fn new(
+        remove_fillers: bool,
+        punctuation: Punctuation,
+        capitalization: Capitalization,
+        trailing_space: bool,
+        spoken_commands: bool,
+    ) -> Self {
+        Self {
+            remove_fillers,
+            punctuation,
+            capitalization,
+            trailing_space,
+            spoken_commands,
+            dictionary: Vec::new(),
+        }
+    }
+
+    // Unset fields fall back to `base` (the built-in profile of the same
+    // name, or an identity profile for new names).
+    fn parse(value: &Value, base: &Profile) -> Result<Self, String> {
+        let Some(fields) = value.as_object() else {
+            return Err("a profile must be an object".into());
+        };
+        let mut profile = base.clone();
+        for (key, value) in fields {
+            match key.as_str() {
+                "removeFillers" => {
+                    profile.remove_fillers =
+                        value.as_bool().ok_or("removeFillers must be a bool")?
+                }
+                "punctuation" => {
+                    profile.punctuation = match value.as_str() {
+                        Some("keep") => Punctuation::Keep,
+                        Some("strip") => Punctuation::Strip,
+                        Some("terminal") => Punctuation::Terminal,
+                        Some("dropFinalPeriod") => Punctuation::DropFinalPeriod,
+                        _ => {
+                            return Err(
+                                "punctuation must be keep, strip, terminal or dropFinalPeriod"
+                                    .into(),
+                            )
+                        }
+                    }
+                }
+                "capitalization" => {
+                    profile.capitalization = match value.as_str() {
+                        Some("keep") => Capitalization::Keep,
+                        Some("sentence") => Capitalization::Sentence,
+                        Some("lower") => Capitalization::Lower,
+                        _ => return Err("capitalization must be keep, sentence or lower".into()),
+                    }
+                }
+                "trailingSpace" => {
+                    profile.trailing_space =
+                        value.as_bool().ok_or("trailingSpace must be a bool")?
+                }
+                "spokenCommands" => {
+                    profile.spoken_commands =
+                        value.as_bool().ok_or("spokenCommands must be a bool")?
+                }
+                "dictionary" => {
+                    let entries = value.as_object().ok_or("dictionary must be an object")?;
+                    for (spoken, written) in entries {
+                        let written = written
+                            .as_str()
+                            .ok_or("dictionary values must be strings")?;
+                        profile.add_word(spoken, written);
+                    }
+                }
+                other => return Err(format!("unknown profile field {other}")),
+            }
+        }
+        Ok(profile)
+    }
+
+    fn add_word(&mut self, spoken: &str, written: &str) {
+        let words: Vec<String> = spoken.split_whitespace().map(str::to_lowercase).collect();
+        if words.is_empty() {
+            return;
+        }
+        self.dictionary.retain(|(existing, _)| *existing != words);
+        self.dictionary.push((words, written.to_string()));
+        self.dictionary
+            .sort_by_key(|(spoken, _)| std::cmp::Reverse(spoken.len()));
+    }
+
+    fn describe(&self) -> Value {
+        let punctuation = match self.punctuation {
+            Punctuation::Keep => "keep",
+            Punctuation::Strip => "strip",
+            Punctuation::Terminal => "terminal",
+            Punctuation::DropFinalPeriod => "dropFinalPeriod",
+        };
+        let capitalization = match self.capitalization {
+            Capitalization::Keep => "keep",
+            Capitalization::Sentence => "sentence",
+            Capitalization::Lower => "lower",
+        };
+        let dictionary: Map<String, Value> = self
+            .dictionary
+            .iter()
+            .map(|(spoken, written)| (spoken.join(" "), json!(written)))
+            .collect();
+        json!({
+            "removeFillers": self.remove_fillers,
+            "punctuation": punctuation,
+            "capitalization": capitalization,
+            "trailingSpace": self.trailing_space,
+            "spokenCommands": self.spoken_commands,
+            "dictionary": dictionary,
+        })
+    }
+
+    pub fn apply(
+        &self,
+        commands: &Commands,
+        locale: &Locale,
+        text: &str,
+        is_final: bool,
+    ) -> String {
+        let mut pieces: Vec<Piece> = text.split_whitespace().map(Piece::word).collect();
+        if self.remove_fillers {
+            pieces.retain(|piece| match piece {
+                Piece::Word { text, .. } => !FILLERS.contains(&bare(text).to_lowercase().as_str()),
+                _ => true,
+            });
+        }
+        // Dictionary entries come out fixed, so they keep their own spelling
+        // (GitHub, iOS) through casing.
+        let mut pieces = self.replace_phrases(pieces);
+        if self.spoken_commands {
+            pieces = commands.interpret(pieces, locale);
+        }
+        self.recase(&mut pieces);
+
+        let mut out = render(pieces, self.punctuation == Punctuation::Strip);
+        if is_final && !out.is_empty() && !out.ends_with('\n') {
+            match self.punctuation {
+                Punctuation::Terminal if !out.ends_with(SENTENCE_END) => {
+                    out = out.trim_end_matches([',', ';', ':']).to_string();
+                    out.push('.');
+                }
+                Punctuation::DropFinalPeriod if out.ends_with('.') && !out.ends_with("..") => {
+                    out.pop();
+                }
+                _ => {}
+            }
+            if self.trailing_space {
+                out.push(' ');
+            }
+        }
+        out
+    }
+
+    fn recase(&self, pieces: &mut [Piece]) {
+        let mut sentence_start = true;
+        for piece in pieces {
+            match piece {
+                Piece::Word { text, fixed } => {
+                    if !*fixed {
+                        match self.capitalization {
+                            Capitalization::Keep => {}
+                            Capitalization::Lower => *text = text.to_lowercase(),
+                            Capitalization::Sentence if sentence_start => *text = capitalize(text),
+                            Capitalization::Sentence => {}
+                        }
+                    }
+                    sentence_start = text.ends_with(SENTENCE_END);
+                }
+                Piece::Mark { text, .. } => {
+                    if text.ends_with(SENTENCE_END) {
+                        sentence_start = true;
+                    }
+                }
+                Piece::Break(_) => sentence_start = true,
+            }
+        }
+    }
+
+    // Dictionary phrases match whole words, ignoring case and the
+    // punctuation around them; trailing punctuation is kept.
+    fn replace_phrases(&self, pieces: Vec<Piece>) -> Vec<Piece> {
+        if self.dictionary.is_empty() {
+            return pieces;
+        }
+        let mut out = Vec::with_capacity(pieces.len());
+        let mut i = 0;
+        'pieces: while i < pieces.len() {
+            for (spoken, written) in &self.dictionary {
+                let Some(words) = pieces.get(i..i + spoken.len()) else {
+                    continue;
+                };
+                let matches = words.iter().zip(spoken).all(|(piece, spoken)| match piece {
+                    Piece::Word { text, fixed: false } => bare(text).to_lowercase() == *spoken,
+                    _ => false,
+                });
+                if let (true, Some(Piece::Word { text: last, .. })) = (matches, words.last()) {
+                    let trailing = &last[last.trim_end_matches(STRIP_PUNCT).len()..];
+                    out.push(Piece::fixed(format!("{written}{trailing}")));
+                    i += spoken.len();
+                    continue 'pieces;
+                }
+            }
+            out.push(pieces[i].clone());
+            i += 1;
+        }
+        out
+    }
+}
+
+fn render(pieces: Vec<Piece>, strip_punct: bool) -> String {
+    let mut out = String::new();
+    // Whether the next piece goes right up against what's already written.
+    let mut glued = true;
+    for piece in pieces {
+        match piece {
+            Piece::Word { text, fixed } => {
+                let text = if strip_punct && !fixed {
+                    text.trim_end_matches(STRIP_PUNCT).to_string()
+                } else {
+                    text
+                };
+                if text.is_empty() {
+                    continue;
+                }
+                // Stray ASR punctuation tokens attach to the previous word.
+                if !glued && !text.starts_with(CLOSING_PUNCT) {
+                    out.push(' ');
+                }
+                out.push_str(&text);
+                glued = false;
+            }
+            Piece::Mark {
+                text,
+                space_before,
+                space_after,
+            } => {
+                if space_before && !glued {
+                    out.push(' ');
+                }
+                out.push_str(&text);
+                glued = glued && text.is_empty() || !space_after;
+            }
+            Piece::Break(text) => {
+                out.truncate(out.trim_end_matches([' ', '\t']).len());
+                out.push_str(&text);
+                glued = true;
+            }
+        }
+    }
+    out
+}
+
+#[derive(Debug)]
+pub struct Profiles {
+    profiles: BTreeMap<String, Profile>,
+    commands: Commands,
+}
+
+impl Profiles {
+    pub fn builtin() -> Self {
+        let mut profiles = BTreeMap::new();
+        profiles.insert(
+            "prose".to_string(),
+            Profile::new(
+                true,
+                Punctuation::Terminal,
+                Capitalization::Sentence,
+                true,
+                true,
+            ),
+        );
+        profiles.insert(
+            "code".to_string(),
+            Profile::new(true, Punctuation::Strip, Capitalization::Lower, false, true),
+        );
+        profiles.insert(
+            "chat".to_string(),
+            Profile::new(
+                true,
+                Punctuation::DropFinalPeriod,
+                Capitalization::Sentence,
+                false,
+                true,
+            ),
+        );
+        Self {
+            profiles,
+            commands: Commands::builtin(),
+        }
+    }
+
+    // A JSON object of profile name -> fields; see Profile::parse.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let value: Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("invalid format profiles {}: {err}", path.display()))?;
+        let Some(entries) = value.as_object() else {
+            return Err(format!("{} must hold a JSON object", path.display()));
+        };
+
+        let mut profiles = Self::builtin();
+        let identity = Profile::new(false, Punctuation::Keep, Capitalization::Keep, false, false);
+        for (name, fields) in entries {
+            let base = profiles.profiles.get(name).unwrap_or(&identity);
+            let profile = Profile::parse(fields, base)
+                .map_err(|err| format!("format profile {name}: {err}"))?;
+            profiles.profiles.insert(name.clone(), profile);
+        }
+        Ok(profiles)
+    }
+
+    pub fn set_commands(&mut self, commands: Commands) {
+        self.commands = commands;
+    }
+
+    pub fn commands(&self) -> &Commands {
+        &self.commands
+    }
+
+    // Unknown or no profile passes the text through untouched.
+    pub fn format(
+        &self,
+        name: Option<&str>,
+        locale: &Locale,
+        text: &str,
+        is_final: bool,
+    ) -> String {
+        match name.and_then(|name| self.profiles.get(name)) {
+            Some(profile) => profile.apply(&self.commands, locale, text, is_final),
+            None => text.to_string(),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.profiles.contains_key(name)
+    }
+
+    pub fn describe(&self) -> Value {
+        let profiles: Map<String, Value> = self
+            .profiles
+            .iter()
+            .map(|(name, profile)| (name.clone(), profile.describe()))
+            .collect();
+        Value::Object(profiles)
+    }
+}
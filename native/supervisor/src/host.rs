@@ -0,0 +1,269 @@
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+#[cfg(target_os = "linux")]
+use std::os::fd::{FromRawFd, IntoRawFd};
+#[cfg(unix)]
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::Message;
+
+// Where the host talks to us: newline-delimited JSON commands in, one JSON
+// event per line out. On stdio the supervisor lives and dies with the host;
+// on a socket it keeps the workers running across host reconnects.
+#[derive(Debug, Clone)]
+pub enum HostEndpoint {
+    Stdio,
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Unix(PathBuf),
+    Tcp(String),
+    // The socket systemd passed in (socket activation), Linux only.
+    Systemd,
+}
+
+impl HostEndpoint {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        if value == "stdio" {
+            return Ok(Self::Stdio);
+        }
+
+        if value == "systemd" {
+            return Ok(Self::Systemd);
+        }
+
+        if let Some(path) = value.strip_prefix("unix:") {
+            if path.is_empty() {
+                return Err("--listen unix: requires a socket path".into());
+            }
+            return Ok(Self::Unix(PathBuf::from(path)));
+        }
+
+        if let Some(address) = value.strip_prefix("tcp:") {
+            if address.is_empty() {
+                return Err("--listen tcp: requires host:port".into());
+            }
+            return Ok(Self::Tcp(address.to_string()));
+        }
+
+        Err(format!(
+            "Invalid --listen value: {value} (expected stdio, systemd, unix:/path/to.sock or tcp:host:port)"
+        ))
+    }
+}
+
+impl fmt::Display for HostEndpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stdio => write!(f, "stdio"),
+            Self::Unix(path) => write!(f, "unix:{}", path.display()),
+            Self::Tcp(address) => write!(f, "tcp:{address}"),
+            Self::Systemd => write!(f, "systemd"),
+        }
+    }
+}
+
+// Events written while no host is connected are dropped; `status` lets a
+// reconnecting host catch up.
+#[derive(Clone)]
+pub struct HostLink {
+    writer: Arc<Mutex<Option<Box<dyn Write + Send>>>>,
+}
+
+impl HostLink {
+    fn new() -> Self {
+        Self {
+            writer: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn attach(&self, writer: Box<dyn Write + Send>) {
+        if let Ok(mut current) = self.writer.lock() {
+            *current = Some(writer);
+        }
+    }
+
+    fn detach(&self) {
+        if let Ok(mut current) = self.writer.lock() {
+            *current = None;
+        }
+    }
+
+    // `event` goes first on the line, as in audio_loop's --json-events.
+    pub fn send(&self, event: &str, fields: Map<String, Value>) {
+        let Ok(mut current) = self.writer.lock() else {
+            return;
+        };
+        let Some(writer) = current.as_mut() else {
+            return;
+        };
+        let event = Value::from(event);
+        let mut line = if fields.is_empty() {
+            format!("{{\"event\":{event}}}")
+        } else {
+            let fields = Value::Object(fields).to_string();
+            format!("{{\"event\":{event},{}", &fields[1..])
+        };
+        line.push('\n');
+        if writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush())
+            .is_err()
+        {
+            *current = None;
+        }
+    }
+}
+
+pub fn serve_host(endpoint: &HostEndpoint, tx: Sender<Message>) -> Result<HostLink, String> {
+    let link = HostLink::new();
+    match endpoint {
+        HostEndpoint::Stdio => {
+            link.attach(Box::new(io::stdout()));
+            thread::spawn(move || {
+                read_commands(io::stdin().lock(), &tx);
+                // The host closing our stdin means it is gone.
+                let _ = tx.send(Message::Shutdown);
+            });
+        }
+        #[cfg(unix)]
+        HostEndpoint::Unix(path) => {
+            // A supervisor that was killed leaves its socket file behind.
+            if path.exists() {
+                std::fs::remove_file(path).map_err(|err| {
+                    format!("failed to remove stale socket {}: {err}", path.display())
+                })?;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+            }
+            let listener = UnixListener::bind(path)
+                .map_err(|err| format!("failed to listen on {}: {err}", path.display()))?;
+            accept_unix(listener, link.clone(), tx);
+        }
+        #[cfg(not(unix))]
+        HostEndpoint::Unix(_) => {
+            return Err("unix sockets are not supported on this platform".into());
+        }
+        HostEndpoint::Tcp(address) => {
+            let listener = TcpListener::bind(address)
+                .map_err(|err| format!("failed to listen on {address}: {err}"))?;
+            accept_tcp(listener, link.clone(), tx);
+        }
+        #[cfg(target_os = "linux")]
+        HostEndpoint::Systemd => match activated_listener()? {
+            Activated::Unix(listener) => accept_unix(listener, link.clone(), tx),
+            Activated::Tcp(listener) => accept_tcp(listener, link.clone(), tx),
+        },
+        #[cfg(not(target_os = "linux"))]
+        HostEndpoint::Systemd => {
+            return Err("--listen systemd is only supported on Linux".into());
+        }
+    }
+    Ok(link)
+}
+
+#[cfg(unix)]
+fn accept_unix(listener: UnixListener, link: HostLink, tx: Sender<Message>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let Ok(writer) = stream.try_clone() else {
+                continue;
+            };
+            serve_client(&link, Box::new(writer), stream, &tx);
+        }
+    });
+}
+
+fn accept_tcp(listener: TcpListener, link: HostLink, tx: Sender<Message>) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let _ = stream.set_nodelay(true);
+            let Ok(writer) = stream.try_clone() else {
+                continue;
+            };
+            serve_client(&link, Box::new(writer), stream, &tx);
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+enum Activated {
+    Unix(UnixListener),
+    Tcp(TcpListener),
+}
+
+// sd_listen_fds(3) without libsystemd: the first passed socket is fd 3, and
+// only ours if LISTEN_PID names this process.
+#[cfg(target_os = "linux")]
+fn activated_listener() -> Result<Activated, String> {
+    const SD_LISTEN_FDS_START: i32 = 3;
+    let ours = std::env::var("LISTEN_PID").ok() == Some(std::process::id().to_string());
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .unwrap_or(0);
+    if !ours || count == 0 {
+        return Err("--listen systemd needs a socket passed by systemd (LISTEN_FDS)".into());
+    }
+    // Workers must not think the socket was meant for them.
+    for name in ["LISTEN_PID", "LISTEN_FDS", "LISTEN_FDNAMES"] {
+        std::env::remove_var(name);
+    }
+
+    // SAFETY: systemd hands us this descriptor and nothing else owns it.
+    let listener = unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+    // try_clone dups with close-on-exec, so the workers don't inherit the
+    // listening socket; dropping the original closes fd 3.
+    if listener.local_addr().is_ok() {
+        let clone = listener
+            .try_clone()
+            .map_err(|err| format!("failed to take the systemd socket: {err}"))?;
+        return Ok(Activated::Unix(clone));
+    }
+    // SAFETY: same descriptor, now owned as the TCP socket it turned out to be.
+    let listener = unsafe { TcpListener::from_raw_fd(listener.into_raw_fd()) };
+    let clone = listener
+        .try_clone()
+        .map_err(|err| format!("failed to take the systemd socket: {err}"))?;
+    Ok(Activated::Tcp(clone))
+}
+
+// One host at a time, like the ASR worker's --listen.
+fn serve_client<R: Read>(
+    link: &HostLink,
+    writer: Box<dyn Write + Send>,
+    reader: R,
+    tx: &Sender<Message>,
+) {
+    link.attach(writer);
+    let _ = tx.send(Message::HostConnected);
+    read_commands(BufReader::new(reader), tx);
+    link.detach();
+}
+
+fn read_commands<R: BufRead>(reader: R, tx: &Sender<Message>) {
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if tx.send(Message::Host(line.to_string())).is_err() {
+            break;
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use serde_json::{json, Value};
+
+// How numbers the "numeral" command writes look in a region: 1,234.5 or
+// 1.234,5, $5 or 5 €, 3/14/2026 or 14.03.2026. Which currency comes from the
+// spoken word ("dollars", "euros"); the locale only decides where the symbol
+// goes.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placement {
+    Prefix,
+    // With a space: 5 €.
+    Suffix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateOrder {
+    Mdy,
+    Dmy,
+    Ymd,
+}
+
+impl DateOrder {
+    fn name(self) -> &'static str {
+        match self {
+            DateOrder::Mdy => "mdy",
+            DateOrder::Dmy => "dmy",
+            DateOrder::Ymd => "ymd",
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Spec {
+    tag: &'static str,
+    decimal: &'static str,
+    group: &'static str,
+    currency: Placement,
+    date_order: DateOrder,
+    date_separator: &'static str,
+    // Zero-pad day and month: 03.04.2026 rather than 3.4.2026.
+    date_padded: bool,
+}
+
+// One of the built-in locales below; cheap to copy into every queued
+// utterance.
+#[derive(Debug, Clone, Copy)]
+pub struct Locale(&'static Spec);
+
+// The first entry for a language is what the bare language ("de") means.
+static LOCALES: [Spec; 10] = [
+    Spec {
+        tag: "en-US",
+        decimal: ".",
+        group: ",",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Mdy,
+        date_separator: "/",
+        date_padded: false,
+    },
+    Spec {
+        tag: "en-GB",
+        decimal: ".",
+        group: ",",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: true,
+    },
+    Spec {
+        tag: "en-AU",
+        decimal: ".",
+        group: ",",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: false,
+    },
+    Spec {
+        tag: "de-DE",
+        decimal: ",",
+        group: ".",
+        currency: Placement::Suffix,
+        date_order: DateOrder::Dmy,
+        date_separator: ".",
+        date_padded: true,
+    },
+    Spec {
+        tag: "fr-FR",
+        decimal: ",",
+        group: "\u{202f}",
+        currency: Placement::Suffix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: true,
+    },
+    Spec {
+        tag: "es-ES",
+        decimal: ",",
+        group: ".",
+        currency: Placement::Suffix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: false,
+    },
+    Spec {
+        tag: "it-IT",
+        decimal: ",",
+        group: ".",
+        currency: Placement::Suffix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: true,
+    },
+    Spec {
+        tag: "nl-NL",
+        decimal: ",",
+        group: ".",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Dmy,
+        date_separator: "-",
+        date_padded: false,
+    },
+    Spec {
+        tag: "pt-BR",
+        decimal: ",",
+        group: ".",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Dmy,
+        date_separator: "/",
+        date_padded: true,
+    },
+    Spec {
+        tag: "ja-JP",
+        decimal: ".",
+        group: ",",
+        currency: Placement::Prefix,
+        date_order: DateOrder::Ymd,
+        date_separator: "/",
+        date_padded: false,
+    },
+];
+
+// Grouping starts at five digits, so years and four-digit numbers stay as
+// written (2026, not 2,026).
+const GROUP_FROM_DIGITS: usize = 5;
+
+impl Default for Locale {
+    fn default() -> Self {
+        Locale(&LOCALES[0])
+    }
+}
+
+impl Locale {
+    // BCP 47-ish: case doesn't matter, "_" works for "-", and a bare
+    // language picks its first region.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let tag = value.trim().replace('_', "-");
+        let found = LOCALES
+            .iter()
+            .find(|locale| locale.tag.eq_ignore_ascii_case(&tag))
+            .or_else(|| {
+                LOCALES.iter().find(|locale| {
+                    locale
+                        .tag
+                        .split('-')
+                        .next()
+                        .is_some_and(|language| language.eq_ignore_ascii_case(&tag))
+                })
+            });
+        found.map(Locale).ok_or_else(|| {
+            let known: Vec<&str> = LOCALES.iter().map(|locale| locale.tag).collect();
+            format!("unknown locale: {value} (expected {})", known.join(", "))
+        })
+    }
+
+    pub fn tag(&self) -> &'static str {
+        self.0.tag
+    }
+
+    // `fraction` is the digits after the decimal point, as spoken.
+    pub fn number(&self, whole: u64, fraction: Option<&str>) -> String {
+        let digits = whole.to_string();
+        let mut out = String::new();
+        if digits.len() < GROUP_FROM_DIGITS {
+            out.push_str(&digits);
+        } else {
+            for (index, digit) in digits.chars().enumerate() {
+                if index > 0 && (digits.len() - index).is_multiple_of(3) {
+                    out.push_str(self.0.group);
+                }
+                out.push(digit);
+            }
+        }
+        if let Some(fraction) = fraction {
+            out.push_str(self.0.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    pub fn money(&self, amount: &str, symbol: &str) -> String {
+        match self.0.currency {
+            Placement::Prefix => format!("{symbol}{amount}"),
+            Placement::Suffix => format!("{amount} {symbol}"),
+        }
+    }
+
+    pub fn date(&self, day: u32, month: u32, year: Option<u64>) -> String {
+        let part = |value: u32| match self.0.date_padded {
+            true => format!("{value:02}"),
+            false => value.to_string(),
+        };
+        let (day, month) = (part(day), part(month));
+        let year = year.map(|year| year.to_string());
+        let parts = match (self.0.date_order, year) {
+            (DateOrder::Mdy, Some(year)) => vec![month, day, year],
+            (DateOrder::Mdy | DateOrder::Ymd, None) => vec![month, day],
+            (DateOrder::Dmy, Some(year)) => vec![day, month, year],
+            (DateOrder::Dmy, None) => vec![day, month],
+            (DateOrder::Ymd, Some(year)) => vec![year, month, day],
+        };
+        parts.join(self.0.date_separator)
+    }
+
+    pub fn describe(&self) -> Value {
+        json!({
+            "decimal": self.0.decimal,
+            "group": self.0.group,
+            "currency": match self.0.currency {
+                Placement::Prefix => "prefix",
+                Placement::Suffix => "suffix",
+            },
+            "dateOrder": self.0.date_order.name(),
+            "dateSeparator": self.0.date_separator,
+        })
+    }
+}
+
+pub fn describe_all() -> Value {
+    let locales: serde_json::Map<String, Value> = LOCALES
+        .iter()
+        .map(|spec| (spec.tag.to_string(), Locale(spec).describe()))
+        .collect();
+    Value::Object(locales)
+}
@@ -0,0 +1,1737 @@
+mod format;
+mod host;
+mod locale;
+mod refine;
+mod search;
+mod segmenter;
+mod service;
+mod sessions;
+mod spoken;
+mod worker;
+
+use format::Profiles;
+use host::{serve_host, HostEndpoint, HostLink};
+use locale::Locale;
+use search::Filter;
+use segmenter::{Segment, Segmenter};
+use serde_json::{json, Map, Value};
+use sessions::{Recording, SessionStore};
+use spoken::Commands;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use worker::{Backoff, Role, Worker};
+
+const DEFAULT_PUSH_MS: u32 = 160;
+const DEFAULT_INITIAL_BACKOFF_MS: u64 = 250;
+const DEFAULT_MAX_BACKOFF_MS: u64 = 10_000;
+const DEFAULT_SAMPLE_RATE: u32 = 16_000;
+const DEFAULT_SESSION_LIST_LIMIT: usize = 50;
+// Nothing to restart: wake up now and then anyway so a missed message can't
+// stall the loop forever.
+const IDLE_POLL: Duration = Duration::from_secs(1);
+
+// Everything the hub reacts to, from the host link, the workers' reader
+// threads and the signal handler.
+pub enum Message {
+    Host(String),
+    HostConnected,
+    AudioFrame {
+        generation: u64,
+        header: Value,
+        audio: Vec<u8>,
+    },
+    Response {
+        role: Role,
+        generation: u64,
+        response: Value,
+    },
+    WorkerLine {
+        role: Role,
+        generation: u64,
+        line: String,
+    },
+    Closed {
+        role: Role,
+        generation: u64,
+        reason: Option<String>,
+    },
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AsrMode {
+    // stream_reset / stream_push / stream_flush, partials as they come.
+    Stream,
+    // One transcribe request with the whole utterance on stop.
+    Batch,
+    // The VAD cuts the mic stream into utterances while listening; each one
+    // is a transcribe request to the segment ASR worker (whisper).
+    Segmented,
+    // Stream for instant partials, then the whole utterance again through
+    // the segment ASR worker in the background for a `refined` event.
+    Hybrid,
+}
+
+impl AsrMode {
+    fn name(self) -> &'static str {
+        match self {
+            AsrMode::Stream => "stream",
+            AsrMode::Batch => "batch",
+            AsrMode::Segmented => "segmented",
+            AsrMode::Hybrid => "hybrid",
+        }
+    }
+
+    fn streams(self) -> bool {
+        matches!(self, AsrMode::Stream | AsrMode::Hybrid)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        match value {
+            "stream" => Some(AsrMode::Stream),
+            "batch" => Some(AsrMode::Batch),
+            "segmented" => Some(AsrMode::Segmented),
+            "hybrid" => Some(AsrMode::Hybrid),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Config {
+    audio_bin: PathBuf,
+    audio_args: Vec<String>,
+    asr_bin: PathBuf,
+    asr_args: Vec<String>,
+    asr_mode: AsrMode,
+    segment_asr_bin: PathBuf,
+    segment_asr_args: Vec<String>,
+    vad_bin: Option<PathBuf>,
+    vad_args: Vec<String>,
+    listen: HostEndpoint,
+    // Running as a login service: lifecycle events also go to stderr, which
+    // the service manager keeps as the log.
+    daemon: bool,
+    push_ms: u32,
+    initial_backoff_ms: u64,
+    max_backoff_ms: u64,
+    sessions_dir: Option<PathBuf>,
+    format_profiles: Profiles,
+    format_profile: Option<String>,
+    // What a start without its own locale uses.
+    locale: Locale,
+}
+
+impl Config {
+    // Segmented mode runs its own (batch) ASR worker when one is given.
+    fn asr_command(&self, mode: AsrMode) -> (&Path, &[String]) {
+        match mode {
+            AsrMode::Segmented => (&self.segment_asr_bin, &self.segment_asr_args),
+            AsrMode::Stream | AsrMode::Batch | AsrMode::Hybrid => (&self.asr_bin, &self.asr_args),
+        }
+    }
+
+    // Hybrid needs a second model next to the streaming one.
+    fn can_refine(&self) -> bool {
+        !(self.segment_asr_bin == self.asr_bin && self.segment_asr_args == self.asr_args)
+    }
+}
+
+// What an in-flight ASR request was for; the worker answers strictly in order.
+enum Purpose {
+    Warmup,
+    Reset,
+    Push,
+    Flush,
+    Close,
+    Transcribe,
+    Segment(Cut),
+    Host(Value),
+    Retranscribe { id: Value, session: String },
+}
+
+enum VadPurpose {
+    Warmup,
+    Reset,
+    // Tagged with the listening run, so a reply that lands after a cancel
+    // and a fresh start isn't applied to the new run.
+    Push(u64),
+    Flush(u64),
+}
+
+enum RefinePurpose {
+    Warmup,
+    Refine(RefineJob),
+}
+
+// A finished hybrid utterance waiting for (or in) its refine request. It
+// keeps its audio while in flight so a worker crash can retry it.
+struct RefineJob {
+    utterance: u64,
+    // Already formatted, like the final the host showed.
+    live_text: String,
+    format_profile: Option<String>,
+    locale: Locale,
+    session_id: Option<String>,
+    audio: Vec<u8>,
+    sample_rate: u32,
+}
+
+// A VAD-cut utterance waiting for (or in) its transcribe request.
+struct Cut {
+    utterance: u64,
+    format_profile: Option<String>,
+    locale: Locale,
+    segment: Segment,
+    recording: Option<Recording>,
+}
+
+#[derive(Default)]
+struct Session {
+    utterance: u64,
+    active: bool,
+    stopping: bool,
+    needs_reset: bool,
+    needs_close: bool,
+    pending: Vec<u8>,
+    // Picked at start; None passes ASR text through untouched.
+    format_profile: Option<String>,
+    locale: Locale,
+    // Hybrid mode: the whole utterance, kept for the refine pass.
+    utterance_audio: Vec<u8>,
+    // Segmented mode: set from start until the VAD has been flushed.
+    segmented: bool,
+    listen: u64,
+    vad_needs_reset: bool,
+    vad_pending: Vec<u8>,
+    // Created with the first frame, once the capture rate is known.
+    segmenter: Option<Segmenter>,
+}
+
+struct Supervisor {
+    config: Config,
+    tx: Sender<Message>,
+    link: HostLink,
+    audio: Worker,
+    asr: Worker,
+    vad: Option<Worker>,
+    refine: Option<Worker>,
+    mode: AsrMode,
+    // What a start without its own formatProfile uses.
+    format_profile: Option<String>,
+    session: Session,
+    sessions: Option<SessionStore>,
+    recording: Option<Recording>,
+    sample_rate: u32,
+    host_requests: VecDeque<(Purpose, Value, Vec<u8>)>,
+    in_flight: Option<Purpose>,
+    vad_in_flight: Option<VadPurpose>,
+    segments: VecDeque<Cut>,
+    refine_queue: VecDeque<RefineJob>,
+    refine_in_flight: Option<RefinePurpose>,
+    next_request: u64,
+}
+
+impl Supervisor {
+    fn emit(&self, event: &str, fields: Value) {
+        if self.config.daemon && DAEMON_LOG_EVENTS.contains(&event) {
+            log_event(event, &fields);
+        }
+        match fields {
+            Value::Object(fields) => self.link.send(event, fields),
+            _ => self.link.send(event, Map::new()),
+        }
+    }
+
+    fn error(&self, source: &str, message: impl std::fmt::Display) {
+        self.emit(
+            "error",
+            json!({ "source": source, "message": message.to_string() }),
+        );
+    }
+
+    fn format_text(
+        &self,
+        profile: Option<&str>,
+        locale: &Locale,
+        text: &str,
+        is_final: bool,
+    ) -> String {
+        self.config
+            .format_profiles
+            .format(profile, locale, text, is_final)
+    }
+
+    // "raw" (or null) turns formatting off; anything else must be a known
+    // profile.
+    fn parse_format_profile(&self, value: &Value) -> Result<Option<String>, String> {
+        match value.as_str() {
+            None if value.is_null() => Ok(None),
+            Some("raw") => Ok(None),
+            Some(name) if self.config.format_profiles.contains(name) => Ok(Some(name.to_string())),
+            Some(name) => Err(format!("unknown format profile: {name}")),
+            None => Err("formatProfile must be a profile name".into()),
+        }
+    }
+
+    // None only for the VAD when segmented mode isn't configured.
+    fn worker_mut(&mut self, role: Role) -> Option<&mut Worker> {
+        match role {
+            Role::Audio => Some(&mut self.audio),
+            Role::Asr => Some(&mut self.asr),
+            Role::Vad => self.vad.as_mut(),
+            Role::Refine => self.refine.as_mut(),
+        }
+    }
+
+    fn is_current(&mut self, role: Role, generation: u64) -> bool {
+        self.worker_mut(role)
+            .is_some_and(|worker| worker.generation == generation)
+    }
+
+    fn start_worker(&mut self, role: Role) {
+        let tx = self.tx.clone();
+        let Some(worker) = self.worker_mut(role) else {
+            return;
+        };
+        match worker.spawn(&tx) {
+            Ok(pid) => {
+                let generation = worker.generation;
+                self.emit(
+                    "workerStarted",
+                    json!({ "worker": role.name(), "pid": pid, "generation": generation }),
+                );
+                match role {
+                    // Capture only runs between start and stop.
+                    Role::Audio if !self.session.active => {
+                        self.send_capture(&json!({ "action": "pause" }))
+                    }
+                    Role::Audio => {}
+                    Role::Asr => {
+                        self.in_flight = None;
+                        self.send_asr(json!({ "action": "warmup" }), &[], Purpose::Warmup);
+                    }
+                    Role::Vad => {
+                        self.vad_in_flight = None;
+                        self.send_vad(json!({ "action": "warmup" }), &[], VadPurpose::Warmup);
+                    }
+                    Role::Refine => {
+                        self.refine_in_flight = None;
+                        self.send_refine(json!({ "action": "warmup" }), RefinePurpose::Warmup);
+                    }
+                }
+            }
+            Err(error) => {
+                self.error(role.name(), &error);
+                let Some(worker) = self.worker_mut(role) else {
+                    return;
+                };
+                let (_, delay) = worker.reap();
+                self.emit(
+                    "workerExited",
+                    json!({ "worker": role.name(), "status": error, "restartInMs": delay.as_millis() as u64 }),
+                );
+            }
+        }
+    }
+
+    fn on_closed(&mut self, role: Role, reason: Option<String>) {
+        if let Some(reason) = reason {
+            self.error(role.name(), reason);
+        }
+        let Some(worker) = self.worker_mut(role) else {
+            return;
+        };
+        let (status, delay) = worker.reap();
+        self.emit(
+            "workerExited",
+            json!({ "worker": role.name(), "status": status, "restartInMs": delay.as_millis() as u64 }),
+        );
+
+        if role == Role::Asr {
+            // The worker's stream state died with it; whatever was in flight
+            // is lost, so end the utterance instead of stitching halves.
+            match self.in_flight.take() {
+                Some(Purpose::Host(id)) => self.emit(
+                    "asrResponse",
+                    json!({ "id": id, "response": { "ok": false, "error": "asr worker exited" } }),
+                ),
+                Some(Purpose::Retranscribe { id, session }) => self.emit(
+                    "sessionRetranscribed",
+                    json!({ "id": id, "sessionId": session, "ok": false, "error": "asr worker exited" }),
+                ),
+                Some(Purpose::Segment(cut)) => {
+                    self.segment_failed(cut, "asr worker exited")
+                }
+                _ => {}
+            }
+            // Segmented listening doesn't depend on ASR state; queued
+            // utterances go to the restarted worker.
+            if !self.session.segmented && (self.session.active || self.session.stopping) {
+                self.error(
+                    "asr",
+                    "asr worker restarted; the current utterance was lost",
+                );
+                self.end_session();
+                // The audio is still worth keeping; it can be re-transcribed.
+                self.finish_recording("", Some("asr worker exited"));
+            }
+        }
+
+        if role == Role::Refine {
+            // Unlike the live pass nothing waits on it, so the utterance is
+            // simply retried once the worker is back.
+            if let Some(RefinePurpose::Refine(job)) = self.refine_in_flight.take() {
+                self.refine_queue.push_front(job);
+            }
+        }
+
+        if role == Role::Vad {
+            self.vad_in_flight = None;
+            if self.session.segmented {
+                // Whatever the VAD had seen is gone; start cutting afresh
+                // once it is back.
+                self.error(
+                    "vad",
+                    "vad worker restarted; the utterance in progress was dropped",
+                );
+                self.session.segmenter = None;
+                self.session.vad_pending.clear();
+                self.session.vad_needs_reset = true;
+            }
+        }
+    }
+
+    fn restart_due(&mut self) {
+        let now = Instant::now();
+        for role in [Role::Audio, Role::Asr, Role::Vad, Role::Refine] {
+            let due = self
+                .worker_mut(role)
+                .is_some_and(|worker| worker.restart_at.is_some_and(|at| at <= now));
+            if due {
+                self.start_worker(role);
+            }
+        }
+    }
+
+    fn next_deadline(&self) -> Duration {
+        let vad = self.vad.as_ref().and_then(|vad| vad.restart_at);
+        let refine = self.refine.as_ref().and_then(|refine| refine.restart_at);
+        [self.audio.restart_at, self.asr.restart_at, vad, refine]
+            .into_iter()
+            .flatten()
+            .min()
+            .map(|at| at.saturating_duration_since(Instant::now()))
+            .unwrap_or(IDLE_POLL)
+    }
+
+    fn send_capture(&mut self, command: &Value) {
+        if let Err(err) = self.audio.write_line(&command.to_string()) {
+            self.error("audio", format!("failed to send capture command: {err}"));
+        }
+    }
+
+    fn send_asr(&mut self, mut request: Value, audio: &[u8], purpose: Purpose) {
+        self.next_request += 1;
+        request["id"] = json!(format!("sup-{}", self.next_request));
+        match self.asr.write_frame(&request, audio) {
+            Ok(()) => self.in_flight = Some(purpose),
+            // The reader thread reports the exit and the restart follows.
+            Err(err) => self.error("asr", format!("failed to send request: {err}")),
+        }
+    }
+
+    fn send_vad(&mut self, mut request: Value, audio: &[u8], purpose: VadPurpose) {
+        let Some(vad) = self.vad.as_mut() else {
+            return;
+        };
+        self.next_request += 1;
+        request["id"] = json!(format!("sup-{}", self.next_request));
+        match vad.write_frame(&request, audio) {
+            Ok(()) => self.vad_in_flight = Some(purpose),
+            Err(err) => self.error("vad", format!("failed to send request: {err}")),
+        }
+    }
+
+    fn send_refine(&mut self, mut request: Value, purpose: RefinePurpose) {
+        let Some(refine) = self.refine.as_mut() else {
+            return;
+        };
+        self.next_request += 1;
+        request["id"] = json!(format!("sup-{}", self.next_request));
+        let audio: &[u8] = match &purpose {
+            RefinePurpose::Refine(job) => &job.audio,
+            RefinePurpose::Warmup => &[],
+        };
+        match refine.write_frame(&request, audio) {
+            Ok(()) => self.refine_in_flight = Some(purpose),
+            Err(err) => {
+                self.error("refine", format!("failed to send request: {err}"));
+                // Retried once the restarted worker is up.
+                if let RefinePurpose::Refine(job) = purpose {
+                    self.refine_queue.push_front(job);
+                }
+            }
+        }
+    }
+
+    // Sends the next request once the worker is idle: host requests first,
+    // then cut utterances, then whatever the current utterance needs.
+    fn pump(&mut self) {
+        self.pump_vad();
+        self.pump_refine();
+        if self.in_flight.is_some() || !self.asr.is_running() {
+            return;
+        }
+
+        if let Some((purpose, request, audio)) = self.host_requests.pop_front() {
+            self.send_asr(request, &audio, purpose);
+            return;
+        }
+
+        if let Some(mut cut) = self.segments.pop_front() {
+            let audio = std::mem::take(&mut cut.segment.audio);
+            self.send_asr(
+                json!({ "action": "transcribe", "sampleRate": self.sample_rate }),
+                &audio,
+                Purpose::Segment(cut),
+            );
+            return;
+        }
+
+        let sample_rate = self.sample_rate;
+        if self.session.needs_close {
+            self.session.needs_close = false;
+            self.send_asr(json!({ "action": "stream_close" }), &[], Purpose::Close);
+            return;
+        }
+        if self.session.needs_reset {
+            self.session.needs_reset = false;
+            self.send_asr(
+                json!({ "action": "stream_reset", "sampleRate": sample_rate }),
+                &[],
+                Purpose::Reset,
+            );
+            return;
+        }
+
+        match self.mode {
+            AsrMode::Stream | AsrMode::Hybrid => {
+                let push_bytes =
+                    (sample_rate as usize * self.config.push_ms as usize / 1000).max(1) * 2;
+                let ready = self.session.pending.len() >= push_bytes
+                    || (self.session.stopping && !self.session.pending.is_empty());
+                if ready {
+                    let audio = std::mem::take(&mut self.session.pending);
+                    self.send_asr(
+                        json!({ "action": "stream_push", "sampleRate": sample_rate }),
+                        &audio,
+                        Purpose::Push,
+                    );
+                } else if self.session.stopping {
+                    self.session.stopping = false;
+                    self.send_asr(json!({ "action": "stream_flush" }), &[], Purpose::Flush);
+                }
+            }
+            AsrMode::Batch => {
+                if !self.session.stopping {
+                    return;
+                }
+                self.session.stopping = false;
+                if self.session.pending.is_empty() {
+                    let utterance = self.session.utterance;
+                    self.emit("final", json!({ "utterance": utterance, "text": "" }));
+                    self.finish_recording("", None);
+                    return;
+                }
+                let audio = std::mem::take(&mut self.session.pending);
+                self.send_asr(
+                    json!({ "action": "transcribe", "sampleRate": sample_rate }),
+                    &audio,
+                    Purpose::Transcribe,
+                );
+            }
+            // Driven by the VAD's replies instead.
+            AsrMode::Segmented => {}
+        }
+    }
+
+    fn pump_refine(&mut self) {
+        if self.refine_in_flight.is_some() || !self.refine.as_ref().is_some_and(Worker::is_running)
+        {
+            return;
+        }
+        if let Some(job) = self.refine_queue.pop_front() {
+            let request = json!({ "action": "transcribe", "sampleRate": job.sample_rate });
+            self.send_refine(request, RefinePurpose::Refine(job));
+        }
+    }
+
+    fn on_refine_response(&mut self, response: Value) {
+        let job = match self.refine_in_flight.take() {
+            Some(RefinePurpose::Refine(job)) => job,
+            Some(RefinePurpose::Warmup) => {
+                if response["ok"].as_bool() == Some(true) {
+                    self.emit("refineReady", json!({}));
+                } else {
+                    let message = response["error"].as_str().unwrap_or("refine warmup failed");
+                    self.error("refine", message);
+                }
+                return;
+            }
+            None => return,
+        };
+        if response["ok"].as_bool() != Some(true) {
+            let message = response["error"]
+                .as_str()
+                .unwrap_or("refine request failed");
+            self.emit(
+                "refined",
+                json!({ "utterance": job.utterance, "ok": false, "error": message }),
+            );
+            return;
+        }
+        let raw_text = response["result"]["text"].as_str().unwrap_or("").trim();
+        let text = self.format_text(job.format_profile.as_deref(), &job.locale, raw_text, true);
+        let corrections = refine::corrections(&job.live_text, &text);
+        if let (Some(store), Some(id)) = (self.sessions.as_mut(), job.session_id.as_deref()) {
+            if let Err(err) = store.add_retranscription(id, raw_text) {
+                self.error("sessions", err);
+            }
+        }
+        self.emit(
+            "refined",
+            json!({
+                "utterance": job.utterance,
+                "ok": true,
+                "text": text,
+                "rawText": raw_text,
+                "liveText": job.live_text,
+                "changed": !corrections.is_empty(),
+                "corrections": corrections,
+                "sessionId": job.session_id,
+            }),
+        );
+    }
+
+    // Hybrid mode keeps the refine worker running; other modes don't pay for
+    // a second model.
+    fn sync_refine_worker(&mut self) {
+        let wanted = self.mode == AsrMode::Hybrid;
+        match (&mut self.refine, wanted) {
+            (None, true) => {
+                let (program, args) = self.config.asr_command(AsrMode::Segmented);
+                let backoff = Backoff::new(
+                    Duration::from_millis(self.config.initial_backoff_ms),
+                    Duration::from_millis(self.config.max_backoff_ms),
+                );
+                self.refine = Some(Worker::new(
+                    Role::Refine,
+                    program.to_path_buf(),
+                    args.to_vec(),
+                    backoff,
+                ));
+                self.start_worker(Role::Refine);
+            }
+            (Some(refine), false) => {
+                refine.shutdown();
+                self.refine = None;
+                self.refine_in_flight = None;
+                self.refine_queue.clear();
+            }
+            _ => {}
+        }
+    }
+
+    // Feeds the VAD in push-sized chunks while listening; once stopped, the
+    // remainder goes out and a flush closes any open utterance.
+    fn pump_vad(&mut self) {
+        if self.vad_in_flight.is_some() || !self.vad.as_ref().is_some_and(Worker::is_running) {
+            return;
+        }
+        let sample_rate = self.sample_rate;
+        let listen = self.session.listen;
+        if !self.session.segmented {
+            return;
+        }
+        if self.session.vad_needs_reset {
+            // Wait for the first frame so the reset carries the real rate.
+            if self.session.segmenter.is_none() && !self.session.stopping {
+                return;
+            }
+            self.session.vad_needs_reset = false;
+            self.send_vad(
+                json!({ "action": "reset", "sampleRate": sample_rate }),
+                &[],
+                VadPurpose::Reset,
+            );
+            return;
+        }
+        let push_bytes = (sample_rate as usize * self.config.push_ms as usize / 1000).max(1) * 2;
+        let ready = self.session.vad_pending.len() >= push_bytes
+            || (self.session.stopping && !self.session.vad_pending.is_empty());
+        if ready {
+            let audio = std::mem::take(&mut self.session.vad_pending);
+            self.send_vad(
+                json!({ "action": "push", "sampleRate": sample_rate }),
+                &audio,
+                VadPurpose::Push(listen),
+            );
+        } else if self.session.stopping {
+            self.session.stopping = false;
+            self.send_vad(json!({ "action": "flush" }), &[], VadPurpose::Flush(listen));
+        }
+    }
+
+    fn on_vad_response(&mut self, response: Value) {
+        let Some(purpose) = self.vad_in_flight.take() else {
+            return;
+        };
+        let listen = match purpose {
+            VadPurpose::Warmup => {
+                if response["ok"].as_bool() == Some(true) {
+                    self.emit("vadReady", json!({}));
+                } else {
+                    let message = response["error"].as_str().unwrap_or("vad warmup failed");
+                    self.error("vad", message);
+                }
+                return;
+            }
+            VadPurpose::Reset => {
+                if response["ok"].as_bool() != Some(true) {
+                    let message = response["error"].as_str().unwrap_or("vad reset failed");
+                    self.error("vad", message);
+                    self.stop_listening();
+                }
+                return;
+            }
+            VadPurpose::Push(listen) | VadPurpose::Flush(listen) => listen,
+        };
+        if listen != self.session.listen || !self.session.segmented {
+            return;
+        }
+        if response["ok"].as_bool() != Some(true) {
+            let message = response["error"].as_str().unwrap_or("vad request failed");
+            self.error("vad", message);
+        }
+
+        let events = response["result"]["events"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        for event in events {
+            let at_ms = event["atMs"].as_f64().unwrap_or(0.0);
+            match event["type"].as_str() {
+                Some("speechStart") => {
+                    let Some(segmenter) = self.session.segmenter.as_mut() else {
+                        continue;
+                    };
+                    if segmenter.in_speech() {
+                        continue;
+                    }
+                    segmenter.speech_start(at_ms);
+                    self.session.utterance += 1;
+                    let utterance = self.session.utterance;
+                    self.emit(
+                        "started",
+                        json!({ "utterance": utterance, "atMs": at_ms as u64 }),
+                    );
+                }
+                Some("speechEnd") => {
+                    let segment = self
+                        .session
+                        .segmenter
+                        .as_mut()
+                        .and_then(|segmenter| segmenter.speech_end(at_ms));
+                    if let Some(segment) = segment {
+                        self.queue_segment(segment);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if matches!(purpose, VadPurpose::Flush(_)) {
+            self.stop_listening();
+        }
+    }
+
+    fn stop_listening(&mut self) {
+        if !self.session.segmented {
+            return;
+        }
+        self.session.segmented = false;
+        self.session.active = false;
+        self.session.stopping = false;
+        self.session.segmenter = None;
+        self.session.vad_pending.clear();
+        self.send_capture(&json!({ "action": "pause" }));
+        self.emit(
+            "listeningStopped",
+            json!({ "queuedSegments": self.segments.len() }),
+        );
+    }
+
+    fn queue_segment(&mut self, segment: Segment) {
+        let utterance = self.session.utterance;
+        let recording = self.sessions.as_ref().and_then(|store| {
+            let mut recording = store
+                .begin(utterance, self.sample_rate, AsrMode::Segmented.name())
+                .map_err(|err| self.error("sessions", err))
+                .ok()?;
+            match recording.append(&segment.audio, self.sample_rate) {
+                Ok(()) => Some(recording),
+                Err(err) => {
+                    self.error("sessions", err);
+                    store.discard(recording);
+                    None
+                }
+            }
+        });
+        self.emit(
+            "segment",
+            json!({
+                "utterance": utterance,
+                "startMs": segment.start_ms,
+                "endMs": segment.end_ms,
+            }),
+        );
+        self.segments.push_back(Cut {
+            utterance,
+            format_profile: self.session.format_profile.clone(),
+            locale: self.session.locale,
+            segment,
+            recording,
+        });
+    }
+
+    fn on_segment_transcribed(&mut self, cut: Cut, response: &Value) {
+        if response["ok"].as_bool() != Some(true) {
+            let message = response["error"].as_str().unwrap_or("asr request failed");
+            self.error("asr", message);
+            self.segment_failed(cut, message);
+            return;
+        }
+        let text = response["result"]["text"].as_str().unwrap_or("");
+        self.emit(
+            "final",
+            json!({
+                "utterance": cut.utterance,
+                "text": self.format_text(cut.format_profile.as_deref(), &cut.locale, text, true),
+                "rawText": text,
+                "formatProfile": cut.format_profile,
+                "startMs": cut.segment.start_ms,
+                "endMs": cut.segment.end_ms,
+                "durationSeconds": response["result"]["durationSeconds"],
+            }),
+        );
+        if let Some(recording) = cut.recording {
+            self.save_recording(recording, text, None);
+        }
+    }
+
+    fn segment_failed(&mut self, cut: Cut, message: &str) {
+        self.emit(
+            "final",
+            json!({ "utterance": cut.utterance, "text": "", "error": message }),
+        );
+        if let Some(recording) = cut.recording {
+            self.save_recording(recording, "", Some(message));
+        }
+    }
+
+    fn on_asr_response(&mut self, response: Value) {
+        let Some(purpose) = self.in_flight.take() else {
+            return;
+        };
+        let ok = response["ok"].as_bool() == Some(true);
+        let result = &response["result"];
+        let utterance = self.session.utterance;
+
+        match purpose {
+            Purpose::Host(id) => {
+                self.emit("asrResponse", json!({ "id": id, "response": response }))
+            }
+            Purpose::Retranscribe { id, session } => self.on_retranscribed(id, session, &response),
+            Purpose::Segment(cut) => self.on_segment_transcribed(cut, &response),
+            _ if !ok => {
+                let message = response["error"].as_str().unwrap_or("asr request failed");
+                self.error("asr", message);
+                if matches!(purpose, Purpose::Flush | Purpose::Transcribe) {
+                    self.emit(
+                        "final",
+                        json!({ "utterance": utterance, "text": "", "error": message }),
+                    );
+                    self.finish_recording("", Some(message));
+                }
+            }
+            Purpose::Warmup => self.emit("asrReady", json!({})),
+            Purpose::Reset | Purpose::Close => {}
+            // Nothing decoded yet; the last partial still stands.
+            Purpose::Push if result["buffered"] == true => {}
+            Purpose::Push => {
+                let text = result["previewText"].as_str().unwrap_or("");
+                let committed = result["committedText"].as_str().unwrap_or("");
+                if let Some(recording) = self.recording.as_mut() {
+                    recording.partial(text, committed);
+                }
+                let profile = self.session.format_profile.as_deref();
+                let locale = &self.session.locale;
+                let mut partial = json!({
+                    "utterance": utterance,
+                    "text": self.format_text(profile, locale, text, false),
+                    "committedText": self.format_text(profile, locale, committed, false),
+                });
+                forward_macro(&mut partial, result);
+                self.emit("partial", partial)
+            }
+            Purpose::Flush | Purpose::Transcribe => {
+                let text = result["text"].as_str().unwrap_or("").to_string();
+                let profile = self.session.format_profile.clone();
+                let locale = self.session.locale;
+                let formatted = self.format_text(profile.as_deref(), &locale, &text, true);
+                let mut event = json!({
+                    "utterance": utterance,
+                    "text": formatted,
+                    "rawText": text,
+                    "formatProfile": profile,
+                    "durationSeconds": result["durationSeconds"],
+                    "refining": self.refine.is_some() && !self.session.utterance_audio.is_empty(),
+                });
+                forward_macro(&mut event, result);
+                self.emit("final", event);
+                let session_id = self
+                    .recording
+                    .take()
+                    .and_then(|recording| self.save_recording(recording, &text, None));
+                let audio = std::mem::take(&mut self.session.utterance_audio);
+                if self.refine.is_some() && !audio.is_empty() {
+                    self.refine_queue.push_back(RefineJob {
+                        utterance,
+                        live_text: formatted,
+                        format_profile: profile,
+                        locale,
+                        session_id,
+                        audio,
+                        sample_rate: self.sample_rate,
+                    });
+                }
+            }
+        }
+    }
+
+    fn on_audio_frame(&mut self, header: &Value, audio: &[u8]) {
+        if let Some(sample_rate) = header["sampleRate"].as_u64() {
+            self.sample_rate = sample_rate as u32;
+        }
+        if self.session.segmented && self.session.active {
+            self.session.vad_pending.extend_from_slice(audio);
+            let sample_rate = self.sample_rate;
+            let segmenter = self
+                .session
+                .segmenter
+                .get_or_insert_with(|| Segmenter::new(sample_rate));
+            // Past the longest utterance whisper takes in one go: cut here
+            // and carry on as the next utterance.
+            if let Some(segment) = segmenter.push(audio) {
+                let at_ms = segment.end_ms;
+                self.queue_segment(segment);
+                self.session.utterance += 1;
+                let utterance = self.session.utterance;
+                self.emit("started", json!({ "utterance": utterance, "atMs": at_ms }));
+            }
+            return;
+        }
+        if self.session.active {
+            self.session.pending.extend_from_slice(audio);
+            if self.mode == AsrMode::Hybrid {
+                self.session.utterance_audio.extend_from_slice(audio);
+            }
+            if let Some(recording) = self.recording.as_mut() {
+                if let Err(err) = recording.append(audio, self.sample_rate) {
+                    // A full disk shouldn't stop dictation; just stop recording.
+                    self.error("sessions", err);
+                    if let (Some(store), Some(recording)) =
+                        (self.sessions.as_ref(), self.recording.take())
+                    {
+                        store.discard(recording);
+                    }
+                }
+            }
+        }
+    }
+
+    fn begin_recording(&mut self) {
+        let Some(store) = self.sessions.as_ref() else {
+            return;
+        };
+        let began = store.begin(self.session.utterance, self.sample_rate, self.mode.name());
+        match began {
+            Ok(recording) => self.recording = Some(recording),
+            Err(err) => self.error("sessions", err),
+        }
+    }
+
+    fn finish_recording(&mut self, text: &str, error: Option<&str>) {
+        if let Some(recording) = self.recording.take() {
+            self.save_recording(recording, text, error);
+        }
+    }
+
+    // Returns the saved session's id.
+    fn save_recording(
+        &mut self,
+        recording: Recording,
+        text: &str,
+        error: Option<&str>,
+    ) -> Option<String> {
+        let store = self.sessions.as_mut()?;
+        match store.finish(recording, text, error) {
+            Ok(entry) => {
+                let id = entry["id"].as_str().map(str::to_string);
+                let path = id.as_deref().map(|id| store.path(id));
+                self.emit("sessionSaved", json!({ "session": entry, "path": path }));
+                id
+            }
+            Err(err) => {
+                self.error("sessions", err);
+                None
+            }
+        }
+    }
+
+    fn discard_recording(&mut self) {
+        if let (Some(store), Some(recording)) = (self.sessions.as_ref(), self.recording.take()) {
+            store.discard(recording);
+        }
+    }
+
+    fn on_retranscribed(&mut self, id: Value, session: String, response: &Value) {
+        if response["ok"].as_bool() != Some(true) {
+            let message = response["error"].as_str().unwrap_or("asr request failed");
+            self.emit(
+                "sessionRetranscribed",
+                json!({ "id": id, "sessionId": session, "ok": false, "error": message }),
+            );
+            return;
+        }
+        let text = response["result"]["text"].as_str().unwrap_or("");
+        let saved = match self.sessions.as_mut() {
+            Some(store) => store.add_retranscription(&session, text),
+            None => Err("sessions are not enabled".to_string()),
+        };
+        let reply = match saved {
+            Ok(()) => json!({ "id": id, "sessionId": session, "ok": true, "text": text }),
+            Err(err) => json!({ "id": id, "sessionId": session, "ok": false, "error": err }),
+        };
+        self.emit("sessionRetranscribed", reply);
+    }
+
+    // list / search / get / delete / retranscribe against --sessions-dir. Replies carry
+    // the command's id back so the host can match them up.
+    fn on_session_command(&mut self, action: &str, command: &Value) {
+        let id = command.get("id").cloned().unwrap_or(Value::Null);
+        let Some(store) = self.sessions.as_mut() else {
+            self.error(
+                "sessions",
+                "sessions are not enabled (start with --sessions-dir)",
+            );
+            return;
+        };
+        let session = command["sessionId"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match action {
+            "sessions" => {
+                let limit = command["limit"]
+                    .as_u64()
+                    .map_or(DEFAULT_SESSION_LIST_LIMIT, |n| n as usize);
+                let sessions = store.list(command["query"].as_str(), limit);
+                self.emit("sessions", json!({ "id": id, "sessions": sessions }));
+            }
+            "search" => {
+                let Some(query) = command["query"].as_str() else {
+                    self.error("sessions", "search requires a query");
+                    return;
+                };
+                let filter = Filter {
+                    from_ms: command["fromMs"].as_u64(),
+                    to_ms: command["toMs"].as_u64(),
+                    limit: command["limit"]
+                        .as_u64()
+                        .map_or(DEFAULT_SESSION_LIST_LIMIT, |n| n as usize),
+                };
+                let results = store.search(query, &filter);
+                self.emit("searchResults", json!({ "id": id, "results": results }));
+            }
+            "session" => match store.get(&session) {
+                Ok(details) => self.emit("session", json!({ "id": id, "session": details })),
+                Err(err) => self.error("sessions", err),
+            },
+            "deleteSession" => match store.delete(&session) {
+                Ok(()) => self.emit("sessionDeleted", json!({ "id": id, "sessionId": session })),
+                Err(err) => self.error("sessions", err),
+            },
+            "retranscribeSession" => match store.audio(&session) {
+                // Queued like a host ASR request, so it never interleaves
+                // with a live utterance's stream requests.
+                Ok((audio, sample_rate)) => self.host_requests.push_back((
+                    Purpose::Retranscribe { id, session },
+                    json!({ "action": "transcribe", "sampleRate": sample_rate }),
+                    audio,
+                )),
+                Err(err) => self.error("sessions", err),
+            },
+            _ => {}
+        }
+    }
+
+    // Capture stderr is already one JSON event per line (--json-events), so
+    // it's passed through tagged with the worker; anything else is a log line.
+    fn on_worker_line(&self, role: Role, line: String) {
+        match serde_json::from_str::<Value>(&line) {
+            Ok(Value::Object(mut fields)) if fields.get("event").is_some_and(Value::is_string) => {
+                let event = fields.remove("event").unwrap_or_default();
+                fields.insert("worker".into(), json!(role.name()));
+                self.link.send(event.as_str().unwrap_or_default(), fields);
+            }
+            _ => self.emit("log", json!({ "worker": role.name(), "line": line })),
+        }
+    }
+
+    fn start_session(&mut self, command: &Value) {
+        if self.session.active {
+            return;
+        }
+        let format_profile = match command.get("formatProfile") {
+            Some(value) => self.parse_format_profile(value),
+            None => Ok(self.format_profile.clone()),
+        };
+        match format_profile {
+            Ok(profile) => self.session.format_profile = profile,
+            Err(err) => {
+                self.error("control", err);
+                return;
+            }
+        }
+        // Null, like leaving it out, means the --locale default.
+        let locale = match command.get("locale").filter(|value| !value.is_null()) {
+            Some(value) => value
+                .as_str()
+                .ok_or_else(|| "locale must be a locale tag".to_string())
+                .and_then(Locale::parse),
+            None => Ok(self.config.locale),
+        };
+        match locale {
+            Ok(locale) => self.session.locale = locale,
+            Err(err) => {
+                self.error("control", err);
+                return;
+            }
+        }
+        if self.mode == AsrMode::Segmented {
+            self.start_listening();
+            return;
+        }
+        self.session.utterance += 1;
+        self.session.active = true;
+        self.session.stopping = false;
+        self.session.pending.clear();
+        self.session.needs_reset = self.mode.streams();
+        self.session.utterance_audio.clear();
+        self.send_capture(&json!({ "action": "resume" }));
+        self.discard_recording();
+        self.begin_recording();
+        let utterance = self.session.utterance;
+        self.emit(
+            "started",
+            json!({
+                "utterance": utterance,
+                "formatProfile": self.session.format_profile,
+                "locale": self.session.locale.tag(),
+            }),
+        );
+    }
+
+    // Segmented mode: utterances start and end with the VAD from here until
+    // stop.
+    fn start_listening(&mut self) {
+        if self.session.stopping {
+            self.error("control", "still flushing the previous listening run");
+            return;
+        }
+        if self.vad.is_none() {
+            self.error("control", "segmented mode requires --vad-bin");
+            return;
+        }
+        self.session.listen += 1;
+        self.session.active = true;
+        self.session.segmented = true;
+        self.session.vad_needs_reset = true;
+        self.session.vad_pending.clear();
+        self.session.segmenter = None;
+        self.send_capture(&json!({ "action": "resume" }));
+        self.emit(
+            "listening",
+            json!({
+                "asrMode": self.mode.name(),
+                "formatProfile": self.session.format_profile,
+                "locale": self.session.locale.tag(),
+            }),
+        );
+    }
+
+    fn stop_session(&mut self) {
+        if !self.session.active {
+            return;
+        }
+        self.session.active = false;
+        self.session.stopping = true;
+        self.send_capture(&json!({ "action": "pause" }));
+    }
+
+    fn end_session(&mut self) {
+        self.session.active = false;
+        self.session.stopping = false;
+        self.session.needs_reset = false;
+        self.session.pending.clear();
+        self.session.utterance_audio.clear();
+        self.session.segmented = false;
+        self.session.vad_pending.clear();
+        self.session.segmenter = None;
+        self.send_capture(&json!({ "action": "pause" }));
+    }
+
+    // Switching is refused mid-utterance. When the new mode uses a
+    // different ASR worker, the current one is replaced right away so the
+    // model is loaded before the next start.
+    fn set_asr_mode(&mut self, command: &Value) {
+        let Some(mode) = command["mode"].as_str().and_then(AsrMode::parse) else {
+            self.error(
+                "control",
+                "setAsrMode requires mode stream, batch, segmented or hybrid",
+            );
+            return;
+        };
+        let busy = self.session.active
+            || self.session.stopping
+            || !self.segments.is_empty()
+            || matches!(self.in_flight, Some(Purpose::Segment(_)));
+        if busy && mode != self.mode {
+            self.error("control", "can't switch ASR mode during an utterance");
+            return;
+        }
+        if mode == AsrMode::Segmented && self.vad.is_none() {
+            self.error("control", "segmented mode requires --vad-bin");
+            return;
+        }
+        if mode == AsrMode::Hybrid && !self.config.can_refine() {
+            self.error("control", "hybrid mode requires --segment-asr-bin");
+            return;
+        }
+
+        self.mode = mode;
+        self.sync_refine_worker();
+        let (program, args) = self.config.asr_command(mode);
+        let restarting = !self.asr.runs(program, args);
+        if restarting {
+            let (program, args) = (program.to_path_buf(), args.to_vec());
+            self.asr.set_command(program, args);
+            self.asr.kill(true);
+        }
+        self.emit(
+            "asrMode",
+            json!({ "asrMode": mode.name(), "restartingAsr": restarting }),
+        );
+    }
+
+    // Changes the default for later starts and, since the host calls this
+    // when focus moves, the stream that is running right now.
+    fn set_format_profile(&mut self, command: &Value) {
+        let profile = match self.parse_format_profile(&command["profile"]) {
+            Ok(profile) => profile,
+            Err(err) => {
+                self.error("control", err);
+                return;
+            }
+        };
+        self.format_profile = profile.clone();
+        let running = self.session.active || self.session.stopping || self.session.segmented;
+        if running {
+            self.session.format_profile = profile.clone();
+        }
+        self.emit(
+            "formatProfile",
+            json!({ "formatProfile": profile, "appliedToCurrent": running }),
+        );
+    }
+
+    fn status(&self) -> Value {
+        let worker = |worker: &Worker| {
+            json!({
+                "running": worker.is_running(),
+                "pid": worker.pid(),
+                "restarts": worker.restarts,
+                "generation": worker.generation,
+            })
+        };
+        json!({
+            "audio": worker(&self.audio),
+            "asr": worker(&self.asr),
+            "vad": self.vad.as_ref().map(worker),
+            "refine": self.refine.as_ref().map(worker),
+            "queuedRefines": self.refine_queue.len() + usize::from(self.refine_in_flight.is_some()),
+            "asrMode": self.mode.name(),
+            "formatProfile": self.format_profile,
+            "locale": self.config.locale.tag(),
+            "utterance": self.session.utterance,
+            "active": self.session.active,
+            "listening": self.session.segmented,
+            "queuedSegments": self.segments.len(),
+            "sessions": self.sessions.is_some(),
+        })
+    }
+
+    // Returns false on shutdown.
+    fn on_host_command(&mut self, line: &str) -> bool {
+        let command: Value = match serde_json::from_str(line) {
+            Ok(command) => command,
+            Err(err) => {
+                self.error("control", format!("invalid control JSON: {err}"));
+                return true;
+            }
+        };
+
+        match command["action"].as_str() {
+            Some("start") => self.start_session(&command),
+            Some("stop") => self.stop_session(),
+            Some("cancel") if self.session.segmented => {
+                self.end_session();
+                for cut in std::mem::take(&mut self.segments) {
+                    if let (Some(store), Some(recording)) = (self.sessions.as_ref(), cut.recording)
+                    {
+                        store.discard(recording);
+                    }
+                }
+                let utterance = self.session.utterance;
+                self.emit("cancelled", json!({ "utterance": utterance }));
+            }
+            Some("cancel") => {
+                let stream = self.mode.streams();
+                let was_running = self.session.active || self.session.stopping;
+                self.end_session();
+                self.discard_recording();
+                self.session.needs_close = stream && was_running;
+                let utterance = self.session.utterance;
+                self.emit("cancelled", json!({ "utterance": utterance }));
+            }
+            Some("status") => self.emit("status", self.status()),
+            Some("setAsrMode") => self.set_asr_mode(&command),
+            Some("setFormatProfile") => self.set_format_profile(&command),
+            Some("formatProfiles") => {
+                let id = command.get("id").cloned().unwrap_or(Value::Null);
+                self.emit(
+                    "formatProfiles",
+                    json!({
+                        "id": id,
+                        "profiles": self.config.format_profiles.describe(),
+                        "spokenCommands": self.config.format_profiles.commands().describe(),
+                        "default": self.format_profile,
+                        "locales": locale::describe_all(),
+                        "defaultLocale": self.config.locale.tag(),
+                    }),
+                );
+            }
+            // Capture controls the supervisor doesn't own go straight through.
+            Some("mute" | "unmute" | "setGain" | "gate" | "sleep" | "wake") => {
+                self.send_capture(&command)
+            }
+            Some("asr") => match command.get("request") {
+                Some(request) if request.is_object() => {
+                    let id = command.get("id").cloned().unwrap_or(Value::Null);
+                    self.host_requests
+                        .push_back((Purpose::Host(id), request.clone(), Vec::new()));
+                }
+                _ => self.error("control", "asr requires a request object"),
+            },
+            Some("restart") => {
+                let role = match command["worker"].as_str() {
+                    Some("audio") => Some(Role::Audio),
+                    Some("asr") => Some(Role::Asr),
+                    Some("vad") => Some(Role::Vad),
+                    Some("refine") => Some(Role::Refine),
+                    _ => None,
+                };
+                match role.and_then(|role| self.worker_mut(role)) {
+                    Some(worker) => worker.kill(true),
+                    None => self.error(
+                        "control",
+                        "restart requires worker audio, asr, vad or refine",
+                    ),
+                }
+            }
+            Some(
+                action @ ("sessions"
+                | "search"
+                | "session"
+                | "deleteSession"
+                | "retranscribeSession"),
+            ) => self.on_session_command(action, &command),
+            Some("shutdown") => return false,
+            Some(other) => self.error("control", format!("unsupported control action: {other}")),
+            None => self.error("control", "control command requires an action"),
+        }
+        true
+    }
+
+    fn shutdown(&mut self) {
+        self.audio.shutdown();
+        self.asr.shutdown();
+        for worker in [self.vad.as_mut(), self.refine.as_mut()]
+            .into_iter()
+            .flatten()
+        {
+            worker.shutdown();
+        }
+        if let HostEndpoint::Unix(path) = &self.config.listen {
+            let _ = std::fs::remove_file(path);
+        }
+        self.emit("shutdown", json!({}));
+    }
+}
+
+// What a daemon logs; transcripts stay out of the log.
+const DAEMON_LOG_EVENTS: [&str; 6] = [
+    "ready",
+    "error",
+    "log",
+    "workerStarted",
+    "workerExited",
+    "shutdown",
+];
+
+fn log_event(event: &str, fields: &Value) {
+    let mut line = match fields {
+        Value::Object(fields) => fields.clone(),
+        _ => Map::new(),
+    };
+    line.insert("event".into(), event.into());
+    let at_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0);
+    line.insert("atMs".into(), at_ms.into());
+    eprintln!("{}", Value::Object(line));
+}
+
+// A dictation macro the worker spotted. Its start/end index the worker's
+// unformatted committed text, the same text rawText carries on finals.
+fn forward_macro(event: &mut Value, result: &Value) {
+    if !result["macro"].is_null() {
+        event["macro"] = result["macro"].clone();
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Config, String> {
+    let mut audio_bin: Option<PathBuf> = None;
+    let mut audio_args = Vec::new();
+    let mut asr_bin: Option<PathBuf> = None;
+    let mut asr_args = Vec::new();
+    let mut asr_mode = AsrMode::Stream;
+    let mut listen: Option<HostEndpoint> = None;
+    let mut daemon = false;
+    let mut push_ms = DEFAULT_PUSH_MS;
+    let mut initial_backoff_ms = DEFAULT_INITIAL_BACKOFF_MS;
+    let mut max_backoff_ms = DEFAULT_MAX_BACKOFF_MS;
+    let mut sessions_dir: Option<PathBuf> = None;
+    let mut segment_asr_bin: Option<PathBuf> = None;
+    let mut segment_asr_args = Vec::new();
+    let mut vad_bin: Option<PathBuf> = None;
+    let mut vad_args = Vec::new();
+    let mut format_profiles_path: Option<PathBuf> = None;
+    let mut format_profile: Option<String> = None;
+    let mut spoken_commands_path: Option<PathBuf> = None;
+    let mut locale = Locale::default();
+
+    let mut i = 0;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        if flag == "--daemon" {
+            daemon = true;
+            i += 1;
+            continue;
+        }
+        if flag == "--help" || flag == "-h" {
+            return Err(
+                "usage: dingoflow-supervisor [service install|uninstall [--dry-run]] --audio-bin <dingoflow-audio-loop> --asr-bin <worker> [--audio-arg <arg>]... [--asr-arg <arg>]... [--asr-mode stream|batch|segmented|hybrid] [--vad-bin <dingoflow-vad-worker>] [--vad-arg <arg>]... [--segment-asr-bin <worker>] [--segment-asr-arg <arg>]... [--listen stdio|systemd|unix:/path.sock|tcp:127.0.0.1:7071] [--daemon] [--push-ms 160] [--initial-backoff-ms 250] [--max-backoff-ms 10000] [--sessions-dir <dir>] [--format-profiles <profiles.json>] [--format-profile prose|code|chat|raw|<name>] [--spoken-commands <commands.json>] [--locale en-US|en-GB|de-DE|fr-FR|...]"
+                    .into(),
+            );
+        }
+        let Some(value) = args.get(i + 1) else {
+            return Err(format!("Missing value for {flag}"));
+        };
+        match flag {
+            "--audio-bin" => audio_bin = Some(PathBuf::from(value)),
+            "--audio-arg" => audio_args.push(value.clone()),
+            "--asr-bin" => asr_bin = Some(PathBuf::from(value)),
+            "--asr-arg" => asr_args.push(value.clone()),
+            "--asr-mode" => {
+                asr_mode = AsrMode::parse(value).ok_or_else(|| {
+                    format!(
+                        "Invalid --asr-mode value: {value} (expected stream, batch, segmented or hybrid)"
+                    )
+                })?
+            }
+            "--segment-asr-bin" => segment_asr_bin = Some(PathBuf::from(value)),
+            "--segment-asr-arg" => segment_asr_args.push(value.clone()),
+            "--vad-bin" => vad_bin = Some(PathBuf::from(value)),
+            "--vad-arg" => vad_args.push(value.clone()),
+            "--listen" => listen = Some(HostEndpoint::parse(value)?),
+            "--push-ms" => {
+                push_ms = value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|ms| (20..=2_000).contains(ms))
+                    .ok_or("--push-ms must be between 20 and 2000")?;
+            }
+            "--initial-backoff-ms" => {
+                initial_backoff_ms = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or("Invalid --initial-backoff-ms value")?;
+            }
+            "--max-backoff-ms" => {
+                max_backoff_ms = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|ms| *ms > 0)
+                    .ok_or("Invalid --max-backoff-ms value")?;
+            }
+            "--sessions-dir" => sessions_dir = Some(PathBuf::from(value)),
+            "--format-profiles" => format_profiles_path = Some(PathBuf::from(value)),
+            "--format-profile" => format_profile = Some(value.clone()),
+            "--spoken-commands" => spoken_commands_path = Some(PathBuf::from(value)),
+            "--locale" => locale = Locale::parse(value)?,
+            other => return Err(format!("Unknown argument: {other}")),
+        }
+        i += 2;
+    }
+
+    let audio_bin = audio_bin.ok_or("--audio-bin is required")?;
+    let mut format_profiles = match &format_profiles_path {
+        Some(path) => Profiles::load(path)?,
+        None => Profiles::builtin(),
+    };
+    if let Some(path) = &spoken_commands_path {
+        format_profiles.set_commands(Commands::load(path)?);
+    }
+    let format_profile = format_profile.filter(|name| name != "raw");
+    if let Some(name) = format_profile.as_deref() {
+        if !format_profiles.contains(name) {
+            return Err(format!("Unknown --format-profile: {name}"));
+        }
+    }
+    let asr_bin = asr_bin.ok_or("--asr-bin is required")?;
+
+    // The supervisor reads 16-bit framed audio and structured events, and
+    // talks to the ASR worker over its framed stdio protocol.
+    audio_args.extend(
+        ["--framed", "--json-events", "--format", "s16le"]
+            .into_iter()
+            .map(String::from),
+    );
+    if asr_mode == AsrMode::Segmented && vad_bin.is_none() {
+        return Err("--asr-mode segmented requires --vad-bin".into());
+    }
+    if asr_mode == AsrMode::Hybrid && segment_asr_bin.is_none() {
+        return Err("--asr-mode hybrid requires --segment-asr-bin".into());
+    }
+    if !segment_asr_args.is_empty() && segment_asr_bin.is_none() {
+        return Err("--segment-asr-arg requires --segment-asr-bin".into());
+    }
+
+    for args in [&mut asr_args, &mut segment_asr_args, &mut vad_args] {
+        if !args.iter().any(|arg| arg == "--serve") {
+            args.push("--serve".into());
+        }
+    }
+    // Without a dedicated worker, segmented mode sends its transcribe
+    // requests to the regular one.
+    let (segment_asr_bin, segment_asr_args) = match segment_asr_bin {
+        Some(bin) => (bin, segment_asr_args),
+        None => (asr_bin.clone(), asr_args.clone()),
+    };
+
+    // A daemon has no host on stdin, so it listens where the service
+    // manager expects unless told otherwise.
+    let listen = match (listen, daemon) {
+        (Some(HostEndpoint::Stdio), true) => {
+            return Err("--daemon needs a socket; --listen stdio is not allowed".into());
+        }
+        (Some(listen), _) => listen,
+        (None, true) => service::default_endpoint()?,
+        (None, false) => HostEndpoint::Stdio,
+    };
+
+    Ok(Config {
+        audio_bin,
+        audio_args,
+        asr_bin,
+        asr_args,
+        asr_mode,
+        segment_asr_bin,
+        segment_asr_args,
+        vad_bin,
+        vad_args,
+        listen,
+        daemon,
+        push_ms,
+        initial_backoff_ms,
+        max_backoff_ms: max_backoff_ms.max(initial_backoff_ms),
+        sessions_dir,
+        format_profiles,
+        format_profile,
+        locale,
+    })
+}
+
+fn run() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("service") {
+        return service::run(&args[1..]);
+    }
+    let config = parse_args(&args)?;
+    let (tx, rx) = mpsc::channel::<Message>();
+    let shutdown_tx = tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = shutdown_tx.send(Message::Shutdown);
+    })
+    .map_err(|e| format!("failed to install signal handler: {e}"))?;
+
+    let sessions = config
+        .sessions_dir
+        .clone()
+        .map(SessionStore::open)
+        .transpose()?;
+    let link = serve_host(&config.listen, tx.clone())?;
+    let backoff = || {
+        Backoff::new(
+            Duration::from_millis(config.initial_backoff_ms),
+            Duration::from_millis(config.max_backoff_ms),
+        )
+    };
+    let audio = Worker::new(
+        Role::Audio,
+        config.audio_bin.clone(),
+        config.audio_args.clone(),
+        backoff(),
+    );
+    let (asr_bin, asr_args) = config.asr_command(config.asr_mode);
+    let asr = Worker::new(Role::Asr, asr_bin.into(), asr_args.to_vec(), backoff());
+    let vad = config
+        .vad_bin
+        .clone()
+        .map(|bin| Worker::new(Role::Vad, bin, config.vad_args.clone(), backoff()));
+    let mode = config.asr_mode;
+    let format_profile = config.format_profile.clone();
+    let mut supervisor = Supervisor {
+        config,
+        tx,
+        link,
+        audio,
+        asr,
+        vad,
+        refine: None,
+        mode,
+        format_profile,
+        session: Session::default(),
+        sessions,
+        recording: None,
+        sample_rate: DEFAULT_SAMPLE_RATE,
+        host_requests: VecDeque::new(),
+        in_flight: None,
+        vad_in_flight: None,
+        segments: VecDeque::new(),
+        refine_queue: VecDeque::new(),
+        refine_in_flight: None,
+        next_request: 0,
+    };
+
+    supervisor.emit(
+        "ready",
+        json!({ "asrMode": supervisor.config.asr_mode.name() }),
+    );
+    supervisor.start_worker(Role::Audio);
+    supervisor.start_worker(Role::Asr);
+    supervisor.start_worker(Role::Vad);
+    supervisor.sync_refine_worker();
+
+    loop {
+        let message = match rx.recv_timeout(supervisor.next_deadline()) {
+            Ok(message) => message,
+            Err(RecvTimeoutError::Timeout) => {
+                supervisor.restart_due();
+                supervisor.pump();
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        match message {
+            Message::Host(line) => {
+                let running = supervisor.on_host_command(&line);
+                if !running {
+                    break;
+                }
+            }
+            Message::HostConnected => supervisor.emit("status", supervisor.status()),
+            Message::AudioFrame {
+                generation,
+                header,
+                audio,
+            } if generation == supervisor.audio.generation => {
+                supervisor.on_audio_frame(&header, &audio)
+            }
+            Message::Response {
+                role,
+                generation,
+                response,
+            } if supervisor.is_current(role, generation) => match role {
+                Role::Asr => supervisor.on_asr_response(response),
+                Role::Vad => supervisor.on_vad_response(response),
+                Role::Refine => supervisor.on_refine_response(response),
+                Role::Audio => {}
+            },
+            Message::WorkerLine {
+                role,
+                generation,
+                line,
+            } if supervisor.is_current(role, generation) => supervisor.on_worker_line(role, line),
+            Message::Closed {
+                role,
+                generation,
+                reason,
+            } if supervisor.is_current(role, generation) => supervisor.on_closed(role, reason),
+            Message::Shutdown => break,
+            // Leftovers from a child that has already been replaced.
+            _ => {}
+        }
+        supervisor.restart_due();
+        supervisor.pump();
+    }
+
+    supervisor.shutdown();
+    Ok(())
+}
+
+fn main() {
+    if let Err(err) = run() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
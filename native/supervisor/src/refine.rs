@@ -0,0 +1,49 @@
+use serde_json::{json, Value};
+
+// Word-level differences between the live (parakeet) text and the refined
+// (whisper) one: each correction replaces `from` with `to` starting at word
+// `index` of the live text, so the UI can patch what it already shows. An
+// empty `from` is an insertion, an empty `to` a deletion.
+pub fn corrections(live: &str, refined: &str) -> Vec<Value> {
+    let a: Vec<&str> = live.split_whitespace().collect();
+    let b: Vec<&str> = refined.split_whitespace().collect();
+
+    // lcs[i][j]: longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0_u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+            continue;
+        }
+        let (start_i, start_j) = (i, j);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                break;
+            }
+            if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+                j += 1;
+            } else {
+                i += 1;
+            }
+        }
+        out.push(json!({
+            "index": start_i,
+            "from": a[start_i..i].join(" "),
+            "to": b[start_j..j].join(" "),
+        }));
+    }
+    out
+}
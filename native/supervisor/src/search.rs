@@ -0,0 +1,448 @@
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Full-text index over stored sessions: each session is a list of timed
+// segments (what was committed when, plus any re-transcriptions), ranked with
+// BM25 per session and answered with the best-matching segments as snippets.
+// Documents are persisted as one JSON file next to the session index; the
+// postings are rebuilt in memory on open, which stays cheap at the scale of
+// one person's dictation history.
+//
+// Not tantivy: at a few thousand sessions a full scan of the postings is
+// instant, while tantivy would bring an on-disk segment format with its own
+// merge threads and schema migrations, and most of a dependency tree the
+// supervisor (serde_json and ctrlc otherwise) doesn't have. The index is the
+// sessions' text, so moving to tantivy later is a rebuild from the store.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+const SNIPPET_CHARS: usize = 160;
+const MAX_SNIPPETS: usize = 3;
+
+#[derive(Clone)]
+pub struct Segment {
+    pub at_ms: u64,
+    pub text: String,
+}
+
+struct Doc {
+    session: String,
+    started_at_ms: u64,
+    segments: Vec<Segment>,
+    terms: usize,
+}
+
+pub struct SearchIndex {
+    path: PathBuf,
+    docs: Vec<Doc>,
+    // term -> (doc, segment, occurrences)
+    postings: HashMap<String, Vec<(usize, usize, u32)>>,
+}
+
+pub struct Filter {
+    pub from_ms: Option<u64>,
+    pub to_ms: Option<u64>,
+    pub limit: usize,
+}
+
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+impl SearchIndex {
+    pub fn open(path: PathBuf) -> Result<Option<Self>, String> {
+        let raw = match fs::read_to_string(&path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("failed to read {}: {e}", path.display())),
+        };
+        let stored: Value = serde_json::from_str(&raw)
+            .map_err(|e| format!("invalid search index {}: {e}", path.display()))?;
+        let mut index = Self::empty(path);
+        for doc in stored["docs"].as_array().into_iter().flatten() {
+            let segments = doc["segments"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|segment| Segment {
+                    at_ms: segment["atMs"].as_u64().unwrap_or(0),
+                    text: segment["text"].as_str().unwrap_or_default().to_string(),
+                })
+                .collect();
+            index.insert(
+                doc["session"].as_str().unwrap_or_default(),
+                doc["startedAtMs"].as_u64().unwrap_or(0),
+                segments,
+            );
+        }
+        Ok(Some(index))
+    }
+
+    pub fn empty(path: PathBuf) -> Self {
+        Self {
+            path,
+            docs: Vec::new(),
+            postings: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, session: &str, started_at_ms: u64, segments: Vec<Segment>) {
+        self.insert(session, started_at_ms, segments);
+    }
+
+    pub fn append(&mut self, session: &str, segment: Segment) {
+        let Some(doc) = self.docs.iter().position(|d| d.session == session) else {
+            return;
+        };
+        let seg = self.docs[doc].segments.len();
+        self.docs[doc].terms += self.post(doc, seg, &segment.text);
+        self.docs[doc].segments.push(segment);
+    }
+
+    pub fn remove(&mut self, session: &str) {
+        let Some(doc) = self.docs.iter().position(|d| d.session == session) else {
+            return;
+        };
+        self.docs.remove(doc);
+        // Doc numbers past the removed one shift down by one.
+        for postings in self.postings.values_mut() {
+            postings.retain(|(d, _, _)| *d != doc);
+            for posting in postings.iter_mut().filter(|(d, _, _)| *d > doc) {
+                posting.0 -= 1;
+            }
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let docs: Vec<Value> = self
+            .docs
+            .iter()
+            .map(|doc| {
+                json!({
+                    "session": doc.session,
+                    "startedAtMs": doc.started_at_ms,
+                    "segments": doc.segments.iter().map(|s| json!({ "atMs": s.at_ms, "text": s.text })).collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let contents = json!({ "version": 1, "docs": docs }).to_string();
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .map_err(|e| format!("failed to replace {}: {e}", self.path.display()))
+    }
+
+    // Sessions ranked by BM25 over the query terms; each hit carries up to
+    // three segments, best first, with the matched words' surroundings.
+    pub fn search(&self, query: &str, filter: &Filter) -> Vec<Value> {
+        let mut terms: Vec<String> = tokenize(query).collect();
+        terms.sort();
+        terms.dedup();
+        if terms.is_empty() || self.docs.is_empty() {
+            return Vec::new();
+        }
+
+        let docs = self.docs.len() as f32;
+        let average = self.docs.iter().map(|d| d.terms).sum::<usize>() as f32 / docs;
+        let mut scores: HashMap<usize, f32> = HashMap::new();
+        let mut hits: HashMap<(usize, usize), u32> = HashMap::new();
+        for term in &terms {
+            let Some(postings) = self.postings.get(term) else {
+                continue;
+            };
+            let mut per_doc: HashMap<usize, u32> = HashMap::new();
+            for &(doc, segment, count) in postings {
+                *per_doc.entry(doc).or_default() += count;
+                *hits.entry((doc, segment)).or_default() += 1;
+            }
+            let df = per_doc.len() as f32;
+            let idf = ((docs - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (doc, tf) in per_doc {
+                let tf = tf as f32;
+                let length = self.docs[doc].terms as f32 / average.max(1.0);
+                let norm = tf * (BM25_K1 + 1.0) / (tf + BM25_K1 * (1.0 - BM25_B + BM25_B * length));
+                *scores.entry(doc).or_default() += idf * norm;
+            }
+        }
+
+        let mut ranked: Vec<(usize, f32)> = scores
+            .into_iter()
+            .filter(|(doc, _)| {
+                let at = self.docs[*doc].started_at_ms;
+                filter.from_ms.is_none_or(|from| at >= from)
+                    && filter.to_ms.is_none_or(|to| at < to)
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.1.total_cmp(&a.1).then(
+                self.docs[b.0]
+                    .started_at_ms
+                    .cmp(&self.docs[a.0].started_at_ms),
+            )
+        });
+
+        ranked
+            .into_iter()
+            .take(filter.limit)
+            .map(|(doc, score)| {
+                let mut segments: Vec<(usize, u32)> = hits
+                    .iter()
+                    .filter(|((d, _), _)| *d == doc)
+                    .map(|((_, segment), matched)| (*segment, *matched))
+                    .collect();
+                segments.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+                let snippets: Vec<Value> = segments
+                    .into_iter()
+                    .take(MAX_SNIPPETS)
+                    .map(|(segment, _)| {
+                        let segment = &self.docs[doc].segments[segment];
+                        json!({ "atMs": segment.at_ms, "snippet": snippet(&segment.text, &terms) })
+                    })
+                    .collect();
+                json!({
+                    "sessionId": self.docs[doc].session,
+                    "startedAtMs": self.docs[doc].started_at_ms,
+                    "score": score,
+                    "snippets": snippets,
+                })
+            })
+            .collect()
+    }
+
+    fn insert(&mut self, session: &str, started_at_ms: u64, segments: Vec<Segment>) {
+        self.remove(session);
+        let doc = self.docs.len();
+        let mut terms = 0;
+        for (seg, segment) in segments.iter().enumerate() {
+            terms += self.post(doc, seg, &segment.text);
+        }
+        self.docs.push(Doc {
+            session: session.to_string(),
+            started_at_ms,
+            segments,
+            terms,
+        });
+    }
+
+    fn post(&mut self, doc: usize, segment: usize, text: &str) -> usize {
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for term in tokenize(text) {
+            *counts.entry(term).or_default() += 1;
+        }
+        let total = counts.values().sum::<u32>() as usize;
+        for (term, count) in counts {
+            self.postings
+                .entry(term)
+                .or_default()
+                .push((doc, segment, count));
+        }
+        total
+    }
+}
+
+// Up to SNIPPET_CHARS around the first matched word, cut on word boundaries,
+// with the matches wrapped in ** so a terminal or UI can highlight them.
+fn snippet(text: &str, terms: &[String]) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let matches = |word: &str| tokenize(word).any(|t| terms.contains(&t));
+    let first = words.iter().position(|w| matches(w)).unwrap_or(0);
+
+    let mut start = first;
+    let mut length = 0;
+    while start > 0 && length + words[start - 1].len() < SNIPPET_CHARS / 3 {
+        start -= 1;
+        length += words[start].len() + 1;
+    }
+    let mut end = first;
+    while end < words.len() && length + words[end].len() < SNIPPET_CHARS {
+        length += words[end].len() + 1;
+        end += 1;
+    }
+    let end = end.max(first + 1).min(words.len());
+
+    let mut out: Vec<String> = words[start..end]
+        .iter()
+        .map(|w| {
+            if matches(w) {
+                format!("**{w}**")
+            } else {
+                w.to_string()
+            }
+        })
+        .collect();
+    if start > 0 {
+        out.insert(0, "…".into());
+    }
+    if end < words.len() {
+        out.push("…".into());
+    }
+    out.join(" ")
+}
+
+pub fn index_path(sessions_dir: &Path) -> PathBuf {
+    sessions_dir.join("search.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(sessions: &[(&str, u64, &[&str])]) -> SearchIndex {
+        let mut index = SearchIndex::empty(PathBuf::from("search.json"));
+        for (session, started_at_ms, texts) in sessions {
+            let segments = texts
+                .iter()
+                .enumerate()
+                .map(|(at, text)| Segment {
+                    at_ms: at as u64 * 1000,
+                    text: text.to_string(),
+                })
+                .collect();
+            index.add(session, *started_at_ms, segments);
+        }
+        index
+    }
+
+    fn all() -> Filter {
+        Filter {
+            from_ms: None,
+            to_ms: None,
+            limit: 10,
+        }
+    }
+
+    fn ids(hits: &[Value]) -> Vec<&str> {
+        hits.iter()
+            .map(|hit| hit["sessionId"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn ranks_more_and_rarer_matches_first() {
+        let index = index(&[
+            ("once", 1, &["the invoice went out", "then lunch"]),
+            ("twice", 2, &["invoice for march", "resend the invoice"]),
+            ("both", 3, &["invoice overdue from acme"]),
+            ("none", 4, &["nothing about money"]),
+        ]);
+        assert_eq!(
+            ids(&index.search("invoice acme", &all())),
+            ["both", "twice", "once"]
+        );
+        assert_eq!(
+            ids(&index.search("INVOICE", &all())),
+            ["twice", "both", "once"]
+        );
+    }
+
+    #[test]
+    fn equal_scores_put_the_newest_session_first() {
+        let index = index(&[("old", 1, &["send invoice"]), ("new", 2, &["send invoice"])]);
+        assert_eq!(ids(&index.search("invoice", &all())), ["new", "old"]);
+    }
+
+    #[test]
+    fn snippets_are_the_best_segments_with_their_times() {
+        let index = index(&[(
+            "s",
+            1,
+            &[
+                "lunch plans",
+                "invoice from acme",
+                "acme called",
+                "acme invoice again",
+            ],
+        )]);
+        let hits = index.search("acme invoice", &all());
+        let snippets = hits[0]["snippets"].as_array().unwrap();
+        assert_eq!(snippets.len(), MAX_SNIPPETS);
+        assert_eq!(snippets[0]["atMs"], 1000);
+        assert_eq!(snippets[0]["snippet"], "**invoice** from **acme**");
+        assert_eq!(snippets[1]["atMs"], 3000);
+        assert_eq!(snippets[2]["snippet"], "**acme** called");
+    }
+
+    #[test]
+    fn long_snippets_are_cut_around_the_first_match() {
+        let before = "word ".repeat(60);
+        let after = " more".repeat(60);
+        let text = format!("{before}invoice{after}");
+        let snippet = snippet(&text, &["invoice".to_string()]);
+        assert!(snippet.starts_with("… word"), "{snippet}");
+        assert!(snippet.ends_with("more …"), "{snippet}");
+        assert!(snippet.contains("**invoice**"), "{snippet}");
+        assert!(snippet.chars().count() <= SNIPPET_CHARS + 8, "{snippet}");
+        assert!(snippet.find("**invoice**").unwrap() < SNIPPET_CHARS / 2);
+    }
+
+    #[test]
+    fn date_range_bounds_the_session_start() {
+        let index = index(&[
+            ("monday", 100, &["invoice"]),
+            ("tuesday", 200, &["invoice"]),
+            ("wednesday", 300, &["invoice"]),
+        ]);
+        let range = Filter {
+            from_ms: Some(200),
+            to_ms: Some(300),
+            limit: 10,
+        };
+        assert_eq!(ids(&index.search("invoice", &range)), ["tuesday"]);
+        let since = Filter {
+            from_ms: Some(200),
+            to_ms: None,
+            limit: 10,
+        };
+        assert_eq!(
+            ids(&index.search("invoice", &since)),
+            ["wednesday", "tuesday"]
+        );
+        let limited = Filter { limit: 1, ..all() };
+        assert_eq!(ids(&index.search("invoice", &limited)), ["wednesday"]);
+    }
+
+    #[test]
+    fn removed_and_replaced_sessions_follow_the_store() {
+        let mut index = index(&[
+            ("a", 1, &["invoice"]),
+            ("b", 2, &["receipt"]),
+            ("c", 3, &["invoice receipt"]),
+        ]);
+        index.remove("a");
+        assert_eq!(ids(&index.search("invoice", &all())), ["c"]);
+        assert_eq!(ids(&index.search("receipt", &all())), ["b", "c"]);
+
+        index.add(
+            "b",
+            2,
+            vec![Segment {
+                at_ms: 0,
+                text: "invoice".into(),
+            }],
+        );
+        assert_eq!(ids(&index.search("receipt", &all())), ["c"]);
+        index.append(
+            "c",
+            Segment {
+                at_ms: 5000,
+                text: "paid".into(),
+            },
+        );
+        let hits = index.search("paid", &all());
+        assert_eq!(hits[0]["snippets"][0]["atMs"], 5000);
+    }
+
+    #[test]
+    fn an_empty_query_finds_nothing() {
+        let index = index(&[("s", 1, &["invoice"])]);
+        assert!(index.search("", &all()).is_empty());
+        assert!(index.search("  ,. ", &all()).is_empty());
+        assert!(index.search("unknown", &all()).is_empty());
+        let empty = SearchIndex::empty(PathBuf::from("search.json"));
+        assert!(empty.search("invoice", &all()).is_empty());
+    }
+}
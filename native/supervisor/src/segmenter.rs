@@ -0,0 +1,110 @@
+// Cuts the mic stream into utterances from the VAD worker's speechStart /
+// speechEnd events. Event times are stream milliseconds since the last VAD
+// reset, and the VAD always lags the audio we hold, so a cut never asks for
+// audio that hasn't arrived yet.
+
+// Kept while nobody is talking: speechStart is reported only after
+// minSpeechMs of speech plus the VAD round trip, and points back to where
+// the speech began.
+const IDLE_KEEP_MS: f64 = 2_000.0;
+// Breathing room around the detected speech, so whisper doesn't lose a soft
+// onset or the last consonant.
+const PRE_ROLL_MS: f64 = 200.0;
+const POST_ROLL_MS: f64 = 200.0;
+// Whisper decodes 30 s windows; longer monologues are cut there and carry on
+// as a new utterance.
+const MAX_SEGMENT_MS: f64 = 30_000.0;
+
+pub struct Segment {
+    pub audio: Vec<u8>,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+pub struct Segmenter {
+    sample_rate: u32,
+    buffer: Vec<u8>,
+    // Stream time of buffer[0].
+    buffer_start_ms: f64,
+    speech_start_ms: Option<f64>,
+}
+
+impl Segmenter {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            buffer: Vec::new(),
+            buffer_start_ms: 0.0,
+            speech_start_ms: None,
+        }
+    }
+
+    pub fn in_speech(&self) -> bool {
+        self.speech_start_ms.is_some()
+    }
+
+    fn bytes_for(&self, ms: f64) -> usize {
+        (ms.max(0.0) * self.sample_rate as f64 / 1000.0) as usize * 2
+    }
+
+    fn end_ms(&self) -> f64 {
+        self.buffer_start_ms + self.buffer.len() as f64 * 1000.0 / (self.sample_rate as f64 * 2.0)
+    }
+
+    pub fn push(&mut self, pcm16: &[u8]) -> Option<Segment> {
+        self.buffer.extend_from_slice(pcm16);
+        match self.speech_start_ms {
+            Some(start) if self.end_ms() - start >= MAX_SEGMENT_MS => {
+                let end = self.end_ms();
+                let segment = self.cut(start, end);
+                self.speech_start_ms = Some(end);
+                Some(segment)
+            }
+            Some(_) => None,
+            None => {
+                let excess = self.end_ms() - self.buffer_start_ms - IDLE_KEEP_MS;
+                if excess > 0.0 {
+                    self.drop_before(self.buffer_start_ms + excess);
+                }
+                None
+            }
+        }
+    }
+
+    pub fn speech_start(&mut self, at_ms: f64) {
+        if self.speech_start_ms.is_none() {
+            self.speech_start_ms = Some((at_ms - PRE_ROLL_MS).max(self.buffer_start_ms));
+        }
+    }
+
+    pub fn speech_end(&mut self, at_ms: f64) -> Option<Segment> {
+        let start = self.speech_start_ms.take()?;
+        let end = (at_ms + POST_ROLL_MS).min(self.end_ms());
+        Some(self.cut(start, end))
+    }
+
+    // Returns [start, end) and forgets everything before end.
+    fn cut(&mut self, start_ms: f64, end_ms: f64) -> Segment {
+        let from = self
+            .bytes_for(start_ms - self.buffer_start_ms)
+            .min(self.buffer.len());
+        let to = self
+            .bytes_for(end_ms - self.buffer_start_ms)
+            .clamp(from, self.buffer.len());
+        let audio = self.buffer[from..to].to_vec();
+        self.drop_before(end_ms);
+        Segment {
+            audio,
+            start_ms: start_ms.max(0.0).round() as u64,
+            end_ms: end_ms.max(0.0).round() as u64,
+        }
+    }
+
+    fn drop_before(&mut self, ms: f64) {
+        let bytes = self
+            .bytes_for(ms - self.buffer_start_ms)
+            .min(self.buffer.len());
+        self.buffer.drain(..bytes);
+        self.buffer_start_ms += bytes as f64 * 1000.0 / (self.sample_rate as f64 * 2.0);
+    }
+}
@@ -0,0 +1,473 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::host::HostEndpoint;
+
+const LAUNCHD_LABEL: &str = "com.dingoflow.supervisor";
+const SYSTEMD_UNIT: &str = "dingoflow-supervisor";
+const WINDOWS_TASK: &str = "DingoFlow\\Supervisor";
+const WINDOWS_HOST_ADDRESS: &str = "127.0.0.1:7071";
+
+// Flags whose values the service manager resolves from its own working
+// directory, not the shell's.
+const PATH_FLAGS: [&str; 7] = [
+    "--audio-bin",
+    "--asr-bin",
+    "--segment-asr-bin",
+    "--vad-bin",
+    "--sessions-dir",
+    "--format-profiles",
+    "--spoken-commands",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Platform {
+    MacOs,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    fn current() -> Result<Self, String> {
+        if cfg!(target_os = "macos") {
+            Ok(Self::MacOs)
+        } else if cfg!(target_os = "linux") {
+            Ok(Self::Linux)
+        } else if cfg!(windows) {
+            Ok(Self::Windows)
+        } else {
+            Err("service install is supported on macOS, Linux and Windows only".into())
+        }
+    }
+}
+
+// What install/uninstall does: files to write (or remove) and service
+// manager commands to run. `--dry-run` prints it instead.
+#[derive(Default)]
+struct Plan {
+    dirs: Vec<PathBuf>,
+    write: Vec<(PathBuf, String)>,
+    // argv, and whether a failure aborts (unloading what isn't loaded fails).
+    run: Vec<(Vec<String>, bool)>,
+    remove: Vec<PathBuf>,
+    // Runs once the files are gone.
+    then: Vec<(Vec<String>, bool)>,
+    notes: Vec<String>,
+}
+
+impl Plan {
+    fn print(&self) {
+        for dir in &self.dirs {
+            println!("would create {}", dir.display());
+        }
+        for (path, contents) in &self.write {
+            println!("would write {}:\n{contents}", path.display());
+        }
+        for (argv, _) in &self.run {
+            println!("would run: {}", argv.join(" "));
+        }
+        for path in &self.remove {
+            println!("would remove {}", path.display());
+        }
+        for (argv, _) in &self.then {
+            println!("would run: {}", argv.join(" "));
+        }
+        for note in &self.notes {
+            println!("{note}");
+        }
+    }
+
+    fn apply(&self) -> Result<(), String> {
+        for dir in &self.dirs {
+            std::fs::create_dir_all(dir)
+                .map_err(|err| format!("failed to create {}: {err}", dir.display()))?;
+        }
+        for (path, contents) in &self.write {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| format!("failed to create {}: {err}", parent.display()))?;
+            }
+            std::fs::write(path, contents)
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+            println!("wrote {}", path.display());
+        }
+        run_all(&self.run)?;
+        for path in &self.remove {
+            match std::fs::remove_file(path) {
+                Ok(()) => println!("removed {}", path.display()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(format!("failed to remove {}: {err}", path.display())),
+            }
+        }
+        run_all(&self.then)?;
+        for note in &self.notes {
+            println!("{note}");
+        }
+        Ok(())
+    }
+}
+
+fn run_all(commands: &[(Vec<String>, bool)]) -> Result<(), String> {
+    for (argv, required) in commands {
+        let status = Command::new(&argv[0])
+            .args(&argv[1..])
+            .status()
+            .map_err(|err| format!("failed to run {}: {err}", argv[0]));
+        match status {
+            Ok(status) if status.success() => {}
+            Ok(status) if *required => {
+                return Err(format!("{} failed with {status}", argv.join(" ")))
+            }
+            Err(err) if *required => return Err(err),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+// `dingoflow-supervisor service install|uninstall [--dry-run] [flags...]`:
+// registers the supervisor with the platform's service manager so the worker
+// stack runs from login without the GUI host, which then connects to the
+// daemon's socket instead of spawning its own supervisor.
+pub fn run(args: &[String]) -> Result<(), String> {
+    let usage = "usage: dingoflow-supervisor service install [--dry-run] <supervisor flags>... | service uninstall [--dry-run]";
+    let (command, rest) = args.split_first().ok_or(usage)?;
+    let dry_run = rest.first().is_some_and(|arg| arg == "--dry-run");
+    let flags = if dry_run { &rest[1..] } else { rest };
+    let platform = Platform::current()?;
+
+    let plan = match command.as_str() {
+        "install" => install_plan(platform, flags)?,
+        "uninstall" if flags.is_empty() => uninstall_plan(platform)?,
+        _ => return Err(usage.into()),
+    };
+    if dry_run {
+        plan.print();
+        Ok(())
+    } else {
+        plan.apply()
+    }
+}
+
+fn install_plan(platform: Platform, flags: &[String]) -> Result<Plan, String> {
+    let mut argv = vec!["--daemon".to_string()];
+    argv.extend(flags.iter().cloned());
+    if platform == Platform::Linux {
+        if flags.iter().any(|flag| flag == "--listen") {
+            return Err("the systemd socket unit owns the endpoint; drop --listen".into());
+        }
+        argv.extend(["--listen".to_string(), "systemd".to_string()]);
+    }
+    for pair in flags.windows(2) {
+        if PATH_FLAGS.contains(&pair[0].as_str()) && !Path::new(&pair[1]).is_absolute() {
+            return Err(format!("{} needs an absolute path for a service", pair[0]));
+        }
+    }
+    // Catch a broken command line now rather than in the service's log.
+    let config = crate::parse_args(&argv)?;
+
+    let exe = std::env::current_exe()
+        .map_err(|err| format!("failed to locate the supervisor binary: {err}"))?;
+    let mut command = vec![exe.to_string_lossy().into_owned()];
+    command.extend(argv);
+
+    let mut plan = match platform {
+        Platform::MacOs => launchd_install(&command)?,
+        Platform::Linux => systemd_install(&command)?,
+        Platform::Windows => windows_install(&command)?,
+    };
+    plan.notes.push(format!(
+        "host endpoint: {}",
+        match &config.listen {
+            HostEndpoint::Systemd => format!("unix:{}", systemd_socket_path()),
+            endpoint => endpoint.to_string(),
+        }
+    ));
+    Ok(plan)
+}
+
+fn uninstall_plan(platform: Platform) -> Result<Plan, String> {
+    let mut plan = Plan::default();
+    match platform {
+        Platform::MacOs => {
+            let plist = launch_agent_path()?;
+            plan.run
+                .push((argv(&["launchctl", "unload", "-w"], &plist), false));
+            plan.remove.push(plist);
+        }
+        Platform::Linux => {
+            let units = systemd_unit_dir()?;
+            plan.run.push((
+                strings(&[
+                    "systemctl",
+                    "--user",
+                    "disable",
+                    "--now",
+                    &format!("{SYSTEMD_UNIT}.service"),
+                    &format!("{SYSTEMD_UNIT}.socket"),
+                ]),
+                false,
+            ));
+            plan.remove
+                .push(units.join(format!("{SYSTEMD_UNIT}.service")));
+            plan.remove
+                .push(units.join(format!("{SYSTEMD_UNIT}.socket")));
+            plan.then
+                .push((strings(&["systemctl", "--user", "daemon-reload"]), false));
+        }
+        Platform::Windows => {
+            plan.run
+                .push((strings(&["schtasks", "/End", "/TN", WINDOWS_TASK]), false));
+            plan.run.push((
+                strings(&["schtasks", "/Delete", "/TN", WINDOWS_TASK, "/F"]),
+                false,
+            ));
+            plan.remove.push(windows_script_path()?);
+        }
+    }
+    Ok(plan)
+}
+
+// Where --daemon listens unless told otherwise: a per-user socket, or a
+// loopback port on Windows.
+pub fn default_endpoint() -> Result<HostEndpoint, String> {
+    let path = match Platform::current()? {
+        Platform::MacOs => home()?.join("Library/Application Support/dingoflow/supervisor.sock"),
+        Platform::Linux => match std::env::var_os("XDG_RUNTIME_DIR") {
+            Some(dir) => PathBuf::from(dir).join("dingoflow/supervisor.sock"),
+            None => home()?.join(".cache/dingoflow/supervisor.sock"),
+        },
+        Platform::Windows => return Ok(HostEndpoint::Tcp(WINDOWS_HOST_ADDRESS.into())),
+    };
+    Ok(HostEndpoint::Unix(path))
+}
+
+fn launchd_install(command: &[String]) -> Result<Plan, String> {
+    let plist = launch_agent_path()?;
+    let log_dir = home()?.join("Library/Logs/dingoflow");
+    let arguments: String = command
+        .iter()
+        .map(|arg| format!("    <string>{}</string>\n", xml_escape(arg)))
+        .collect();
+    let log = xml_escape(&log_dir.join("supervisor.log").to_string_lossy());
+    let contents = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+  <key>Label</key>
+  <string>{LAUNCHD_LABEL}</string>
+  <key>ProgramArguments</key>
+  <array>
+{arguments}  </array>
+  <key>RunAtLoad</key>
+  <true/>
+  <key>KeepAlive</key>
+  <dict>
+    <key>SuccessfulExit</key>
+    <false/>
+  </dict>
+  <key>ProcessType</key>
+  <string>Interactive</string>
+  <key>StandardOutPath</key>
+  <string>{log}</string>
+  <key>StandardErrorPath</key>
+  <string>{log}</string>
+</dict>
+</plist>
+"#
+    );
+
+    let mut plan = Plan {
+        dirs: vec![log_dir],
+        ..Plan::default()
+    };
+    // Reinstalling replaces a loaded agent.
+    plan.run
+        .push((argv(&["launchctl", "unload"], &plist), false));
+    plan.run
+        .push((argv(&["launchctl", "load", "-w"], &plist), true));
+    plan.write.push((plist, contents));
+    plan.notes.push(format!("logs: {log}"));
+    Ok(plan)
+}
+
+fn systemd_install(command: &[String]) -> Result<Plan, String> {
+    let units = systemd_unit_dir()?;
+    let exec_start = command
+        .iter()
+        .map(|arg| systemd_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let socket = "[Unit]
+Description=DingoFlow supervisor socket
+
+[Socket]
+ListenStream=%t/dingoflow/supervisor.sock
+SocketMode=0600
+DirectoryMode=0700
+
+[Install]
+WantedBy=sockets.target
+"
+    .to_string();
+    // Also wanted by default.target, so the stack is up at login rather than
+    // on the first connection.
+    let service = format!(
+        "[Unit]
+Description=DingoFlow dictation worker stack
+Requires={SYSTEMD_UNIT}.socket
+After={SYSTEMD_UNIT}.socket
+
+[Service]
+ExecStart={exec_start}
+Restart=on-failure
+StandardOutput=journal
+StandardError=journal
+
+[Install]
+WantedBy=default.target
+"
+    );
+
+    let mut plan = Plan::default();
+    plan.write
+        .push((units.join(format!("{SYSTEMD_UNIT}.socket")), socket));
+    plan.write
+        .push((units.join(format!("{SYSTEMD_UNIT}.service")), service));
+    plan.run
+        .push((strings(&["systemctl", "--user", "daemon-reload"]), true));
+    plan.run.push((
+        strings(&[
+            "systemctl",
+            "--user",
+            "enable",
+            "--now",
+            &format!("{SYSTEMD_UNIT}.socket"),
+            &format!("{SYSTEMD_UNIT}.service"),
+        ]),
+        true,
+    ));
+    plan.notes
+        .push(format!("logs: journalctl --user -u {SYSTEMD_UNIT}"));
+    Ok(plan)
+}
+
+// A logon task rather than an SCM service: the stack needs the user's audio
+// devices and desktop session, which services don't get.
+fn windows_install(command: &[String]) -> Result<Plan, String> {
+    let script = windows_script_path()?;
+    let log_dir = local_app_data()?.join("dingoflow\\logs");
+    let log = log_dir.join("supervisor.log");
+    let line = command
+        .iter()
+        .map(|arg| cmd_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let contents = format!(
+        "@echo off\r\n{line} >> {} 2>&1\r\n",
+        cmd_quote(&log.to_string_lossy())
+    );
+
+    let mut plan = Plan {
+        dirs: vec![log_dir],
+        ..Plan::default()
+    };
+    plan.run.push((
+        vec![
+            "schtasks".into(),
+            "/Create".into(),
+            "/TN".into(),
+            WINDOWS_TASK.into(),
+            "/SC".into(),
+            "ONLOGON".into(),
+            "/RL".into(),
+            "LIMITED".into(),
+            "/F".into(),
+            "/TR".into(),
+            format!("\"{}\"", script.display()),
+        ],
+        true,
+    ));
+    plan.run
+        .push((strings(&["schtasks", "/Run", "/TN", WINDOWS_TASK]), false));
+    plan.write.push((script, contents));
+    plan.notes.push(format!("logs: {}", log.display()));
+    Ok(plan)
+}
+
+fn launch_agent_path() -> Result<PathBuf, String> {
+    Ok(home()?.join(format!("Library/LaunchAgents/{LAUNCHD_LABEL}.plist")))
+}
+
+fn systemd_unit_dir() -> Result<PathBuf, String> {
+    Ok(match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => home()?.join(".config"),
+    }
+    .join("systemd/user"))
+}
+
+// Where the socket unit's %t/dingoflow/supervisor.sock ends up.
+fn systemd_socket_path() -> String {
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir)
+            .join("dingoflow/supervisor.sock")
+            .display()
+            .to_string(),
+        None => "$XDG_RUNTIME_DIR/dingoflow/supervisor.sock".into(),
+    }
+}
+
+fn windows_script_path() -> Result<PathBuf, String> {
+    Ok(local_app_data()?.join("dingoflow\\supervisor-service.cmd"))
+}
+
+fn home() -> Result<PathBuf, String> {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .ok_or_else(|| "HOME is not set".to_string())
+}
+
+fn local_app_data() -> Result<PathBuf, String> {
+    std::env::var_os("LOCALAPPDATA")
+        .map(PathBuf::from)
+        .ok_or_else(|| "LOCALAPPDATA is not set".to_string())
+}
+
+fn strings(argv: &[&str]) -> Vec<String> {
+    argv.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn argv(command: &[&str], path: &Path) -> Vec<String> {
+    let mut argv = strings(command);
+    argv.push(path.to_string_lossy().into_owned());
+    argv
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// ExecStart= splits on spaces and expands % specifiers and $ variables.
+fn systemd_quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    if escaped.is_empty() || escaped.contains(char::is_whitespace) {
+        format!("\"{escaped}\"")
+    } else {
+        escaped
+    }
+}
+
+// cmd.exe expands %VARS% even inside quotes.
+fn cmd_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('%', "%%").replace('"', "\"\""))
+}
@@ -0,0 +1,408 @@
+use crate::search::{self, Filter, SearchIndex, Segment};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+// Every utterance recorded under --sessions-dir, one directory each:
+//
+//   <dir>/index.json              newest first, what list reads
+//   <dir>/search.json             the full-text index behind `search`
+//   <dir>/<id>/audio.wav          the PCM16 mono audio the ASR saw
+//   <dir>/<id>/transcript.json    partials and the final, timed from start
+//   <dir>/<id>/meta.json          the index entry plus file names
+//
+// Audio goes to disk as it arrives, so a long dictation never sits in memory
+// and a crash still leaves a playable (if unterminated) WAV behind.
+const INDEX_FILE: &str = "index.json";
+const AUDIO_FILE: &str = "audio.wav";
+const TRANSCRIPT_FILE: &str = "transcript.json";
+const META_FILE: &str = "meta.json";
+const WAV_HEADER_BYTES: u64 = 44;
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub struct SessionStore {
+    dir: PathBuf,
+    index: Vec<Value>,
+    search: SearchIndex,
+}
+
+pub struct Recording {
+    id: String,
+    dir: PathBuf,
+    audio: File,
+    audio_bytes: u64,
+    sample_rate: u32,
+    utterance: u64,
+    asr_mode: &'static str,
+    started: Instant,
+    started_at_ms: u64,
+    partials: Vec<Value>,
+}
+
+impl Recording {
+    pub fn append(&mut self, pcm16: &[u8], sample_rate: u32) -> Result<(), String> {
+        // Capture may report its real rate only with the first frame.
+        if self.audio_bytes == 0 {
+            self.sample_rate = sample_rate;
+        }
+        self.audio
+            .write_all(pcm16)
+            .map_err(|e| format!("failed to write session audio: {e}"))?;
+        self.audio_bytes += pcm16.len() as u64;
+        Ok(())
+    }
+
+    // Only changes are kept, so a quiet stretch of identical previews doesn't
+    // bloat the transcript.
+    pub fn partial(&mut self, text: &str, committed: &str) {
+        let last = self.partials.last();
+        if last.is_some_and(|p| p["text"] == text && p["committedText"] == committed) {
+            return;
+        }
+        self.partials.push(json!({
+            "atMs": self.started.elapsed().as_millis() as u64,
+            "text": text,
+            "committedText": committed,
+        }));
+    }
+
+    fn duration_ms(&self) -> u64 {
+        self.audio_bytes * 1000 / (u64::from(self.sample_rate) * 2).max(1)
+    }
+}
+
+impl SessionStore {
+    pub fn open(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("failed to create sessions dir {}: {e}", dir.display()))?;
+        let index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(raw) => match serde_json::from_str::<Value>(&raw) {
+                Ok(Value::Array(entries)) => entries,
+                _ => return Err(format!("invalid session index in {}", dir.display())),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("failed to read session index: {e}")),
+        };
+        let search = match SearchIndex::open(search::index_path(&dir))? {
+            Some(search) => search,
+            None => Self::rebuild_search(&dir, &index)?,
+        };
+        Ok(Self { dir, index, search })
+    }
+
+    // Sessions recorded before the search index existed get indexed from
+    // their transcripts the first time the store is opened.
+    fn rebuild_search(dir: &Path, index: &[Value]) -> Result<SearchIndex, String> {
+        let mut search = SearchIndex::empty(search::index_path(dir));
+        for entry in index {
+            let Some(id) = entry["id"].as_str() else {
+                continue;
+            };
+            let Ok(transcript) = read_json(&dir.join(id).join(TRANSCRIPT_FILE)) else {
+                continue;
+            };
+            let started_at_ms = entry["startedAtMs"].as_u64().unwrap_or(0);
+            search.add(id, started_at_ms, segments(&transcript));
+        }
+        search.save()?;
+        Ok(search)
+    }
+
+    pub fn begin(
+        &self,
+        utterance: u64,
+        sample_rate: u32,
+        asr_mode: &'static str,
+    ) -> Result<Recording, String> {
+        let started_at_ms = now_ms();
+        let id = format!("{started_at_ms}-{utterance}");
+        let dir = self.dir.join(&id);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("failed to create session dir {}: {e}", dir.display()))?;
+        let mut audio = File::create(dir.join(AUDIO_FILE))
+            .map_err(|e| format!("failed to create session audio: {e}"))?;
+        audio
+            .write_all(&wav_header(sample_rate, 0))
+            .map_err(|e| format!("failed to write session audio: {e}"))?;
+        Ok(Recording {
+            id,
+            dir,
+            audio,
+            audio_bytes: 0,
+            sample_rate,
+            utterance,
+            asr_mode,
+            started: Instant::now(),
+            started_at_ms,
+            partials: Vec::new(),
+        })
+    }
+
+    // Closes the WAV and writes the transcript, metadata and index entry.
+    pub fn finish(
+        &mut self,
+        mut recording: Recording,
+        text: &str,
+        error: Option<&str>,
+    ) -> Result<Value, String> {
+        recording
+            .audio
+            .seek(SeekFrom::Start(0))
+            .and_then(|_| {
+                recording
+                    .audio
+                    .write_all(&wav_header(recording.sample_rate, recording.audio_bytes))
+            })
+            .and_then(|_| recording.audio.flush())
+            .map_err(|e| format!("failed to finalize session audio: {e}"))?;
+
+        let transcript = json!({
+            "partials": recording.partials,
+            "final": {
+                "atMs": recording.started.elapsed().as_millis() as u64,
+                "text": text,
+                "error": error,
+            },
+            "retranscriptions": [],
+        });
+        write_json(&recording.dir.join(TRANSCRIPT_FILE), &transcript)?;
+
+        let entry = json!({
+            "id": recording.id,
+            "utterance": recording.utterance,
+            "startedAtMs": recording.started_at_ms,
+            "durationMs": recording.duration_ms(),
+            "sampleRate": recording.sample_rate,
+            "asrMode": recording.asr_mode,
+            "text": text,
+            "error": error,
+        });
+        let mut meta = entry.clone();
+        meta["audio"] = json!(AUDIO_FILE);
+        meta["transcript"] = json!(TRANSCRIPT_FILE);
+        write_json(&recording.dir.join(META_FILE), &meta)?;
+
+        self.index.insert(0, entry.clone());
+        self.save_index()?;
+        self.search.add(
+            &recording.id,
+            recording.started_at_ms,
+            segments(&transcript),
+        );
+        self.search.save()?;
+        Ok(entry)
+    }
+
+    // A cancelled utterance leaves nothing behind.
+    pub fn discard(&self, recording: Recording) {
+        let dir = recording.dir.clone();
+        drop(recording);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    pub fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    // Case-insensitive substring search over final and re-transcribed text.
+    pub fn list(&self, query: Option<&str>, limit: usize) -> Vec<Value> {
+        let query = query.map(str::to_lowercase).filter(|q| !q.is_empty());
+        self.index
+            .iter()
+            .filter(|entry| match &query {
+                Some(query) => ["text", "retranscribedText"].iter().any(|field| {
+                    entry[*field]
+                        .as_str()
+                        .is_some_and(|text| text.to_lowercase().contains(query))
+                }),
+                None => true,
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Result<Value, String> {
+        let dir = self.session_dir(id)?;
+        let meta = read_json(&dir.join(META_FILE))?;
+        let transcript = read_json(&dir.join(TRANSCRIPT_FILE))?;
+        Ok(json!({
+            "meta": meta,
+            "transcript": transcript,
+            "audioPath": dir.join(AUDIO_FILE),
+        }))
+    }
+
+    pub fn delete(&mut self, id: &str) -> Result<(), String> {
+        let dir = self.session_dir(id)?;
+        fs::remove_dir_all(&dir).map_err(|e| format!("failed to delete session {id}: {e}"))?;
+        self.index.retain(|entry| entry["id"] != id);
+        self.save_index()?;
+        self.search.remove(id);
+        self.search.save()
+    }
+
+    // Ranked full-text hits with timed snippets, each joined with its index
+    // entry. `from`/`to` bound the session start so the host can turn "last
+    // Tuesday" into a range.
+    pub fn search(&self, query: &str, filter: &Filter) -> Vec<Value> {
+        self.search
+            .search(query, filter)
+            .into_iter()
+            .map(|mut hit| {
+                let session = self
+                    .index
+                    .iter()
+                    .find(|entry| entry["id"] == hit["sessionId"]);
+                hit["session"] = session.cloned().unwrap_or(Value::Null);
+                hit
+            })
+            .collect()
+    }
+
+    // The recorded PCM16 payload and its sample rate, for a new transcribe.
+    pub fn audio(&self, id: &str) -> Result<(Vec<u8>, u32), String> {
+        let path = self.session_dir(id)?.join(AUDIO_FILE);
+        let bytes =
+            fs::read(&path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        if bytes.len() < WAV_HEADER_BYTES as usize || &bytes[0..4] != b"RIFF" {
+            return Err(format!("{} is not a session recording", path.display()));
+        }
+        let sample_rate = u32::from_le_bytes([bytes[24], bytes[25], bytes[26], bytes[27]]);
+        let mut audio = bytes;
+        audio.drain(..WAV_HEADER_BYTES as usize);
+        Ok((audio, sample_rate))
+    }
+
+    // Kept next to the original rather than replacing it, so a newer model's
+    // output can be compared with what was dictated at the time.
+    pub fn add_retranscription(&mut self, id: &str, text: &str) -> Result<(), String> {
+        let path = self.session_dir(id)?.join(TRANSCRIPT_FILE);
+        let mut transcript = read_json(&path)?;
+        let entry = json!({ "atMs": now_ms(), "text": text });
+        match transcript["retranscriptions"].as_array_mut() {
+            Some(list) => list.push(entry),
+            None => transcript["retranscriptions"] = json!([entry]),
+        }
+        write_json(&path, &transcript)?;
+
+        if let Some(entry) = self.index.iter_mut().find(|entry| entry["id"] == id) {
+            entry["retranscribedText"] = json!(text);
+        }
+        self.save_index()?;
+        self.search.append(
+            id,
+            Segment {
+                at_ms: 0,
+                text: text.to_string(),
+            },
+        );
+        self.search.save()
+    }
+
+    fn session_dir(&self, id: &str) -> Result<PathBuf, String> {
+        let known = self.index.iter().any(|entry| entry["id"] == id);
+        if !known || id.contains(['/', '\\']) || id.starts_with('.') {
+            return Err(format!("unknown session {id}"));
+        }
+        Ok(self.dir.join(id))
+    }
+
+    fn save_index(&self) -> Result<(), String> {
+        write_json(
+            &self.dir.join(INDEX_FILE),
+            &Value::Array(self.index.clone()),
+        )
+    }
+}
+
+// Splits a transcript into timed segments: each growth of the committed text
+// is stamped with when it was committed, and whatever the final adds after
+// that with when the final came. Batch sessions, or a final that rewrote the
+// committed text, index as one segment from the start.
+fn segments(transcript: &Value) -> Vec<Segment> {
+    let final_text = transcript["final"]["text"].as_str().unwrap_or_default();
+    let final_at = transcript["final"]["atMs"].as_u64().unwrap_or(0);
+    let mut segments = Vec::new();
+    let mut committed = "";
+    for partial in transcript["partials"].as_array().into_iter().flatten() {
+        let text = partial["committedText"].as_str().unwrap_or_default();
+        if let Some(added) = text
+            .strip_prefix(committed)
+            .filter(|a| !a.trim().is_empty())
+        {
+            segments.push(Segment {
+                at_ms: partial["atMs"].as_u64().unwrap_or(0),
+                text: added.trim().to_string(),
+            });
+            committed = text;
+        }
+    }
+    match final_text.strip_prefix(committed) {
+        Some(rest) if !rest.trim().is_empty() => segments.push(Segment {
+            at_ms: if committed.is_empty() { 0 } else { final_at },
+            text: rest.trim().to_string(),
+        }),
+        Some(_) => {}
+        None => {
+            segments = vec![Segment {
+                at_ms: 0,
+                text: final_text.to_string(),
+            }]
+        }
+    }
+    for retranscription in transcript["retranscriptions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+    {
+        if let Some(text) = retranscription["text"].as_str() {
+            segments.push(Segment {
+                at_ms: 0,
+                text: text.to_string(),
+            });
+        }
+    }
+    segments
+}
+
+fn read_json(path: &Path) -> Result<Value, String> {
+    let raw =
+        fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    serde_json::from_str(&raw).map_err(|e| format!("invalid JSON in {}: {e}", path.display()))
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<(), String> {
+    let contents = serde_json::to_string_pretty(value)
+        .map_err(|e| format!("failed to encode {}: {e}", path.display()))?;
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, contents).map_err(|e| format!("failed to write {}: {e}", tmp.display()))?;
+    fs::rename(&tmp, path).map_err(|e| format!("failed to replace {}: {e}", path.display()))
+}
+
+fn wav_header(sample_rate: u32, data_bytes: u64) -> [u8; WAV_HEADER_BYTES as usize] {
+    let data = data_bytes.min(u64::from(u32::MAX) - 36) as u32;
+    let mut header = [0_u8; WAV_HEADER_BYTES as usize];
+    header[0..4].copy_from_slice(b"RIFF");
+    header[4..8].copy_from_slice(&(36 + data).to_le_bytes());
+    header[8..12].copy_from_slice(b"WAVE");
+    header[12..16].copy_from_slice(b"fmt ");
+    header[16..20].copy_from_slice(&16_u32.to_le_bytes());
+    header[20..22].copy_from_slice(&1_u16.to_le_bytes());
+    header[22..24].copy_from_slice(&1_u16.to_le_bytes());
+    header[24..28].copy_from_slice(&sample_rate.to_le_bytes());
+    header[28..32].copy_from_slice(&(sample_rate * 2).to_le_bytes());
+    header[32..34].copy_from_slice(&2_u16.to_le_bytes());
+    header[34..36].copy_from_slice(&16_u16.to_le_bytes());
+    header[36..40].copy_from_slice(b"data");
+    header[40..44].copy_from_slice(&data.to_le_bytes());
+    header
+}
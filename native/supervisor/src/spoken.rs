@@ -0,0 +1,649 @@
+use crate::locale::Locale;
+use serde_json::{json, Map, Value};
+use std::path::Path;
+
+// Turns spoken commands ("comma", "open quote", "new paragraph", "all caps
+// next word", "numeral seven") into characters and casing. Words come in as
+// Pieces and go out as Pieces; a Fixed word is final text the formatting
+// profile must not recase or strip. Numerals are written for the stream's
+// locale ("numeral three point five euros" is 3,5 € in de-DE).
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    Word {
+        text: String,
+        fixed: bool,
+    },
+    // Inserted text with its spacing: a comma hugs the word before it, an
+    // opening bracket the word after it.
+    Mark {
+        text: String,
+        space_before: bool,
+        space_after: bool,
+    },
+    Break(String),
+}
+
+impl Piece {
+    pub fn word(text: &str) -> Self {
+        Piece::Word {
+            text: text.to_string(),
+            fixed: false,
+        }
+    }
+
+    pub fn fixed(text: String) -> Self {
+        Piece::Word { text, fixed: true }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Modifier {
+    CapNext,
+    AllCapsNext,
+    LowerNext,
+    AllCapsOn,
+    AllCapsOff,
+    Numeral,
+    Literal,
+}
+
+impl Modifier {
+    const ALL: [(&'static str, Modifier); 7] = [
+        ("capNext", Modifier::CapNext),
+        ("allCapsNext", Modifier::AllCapsNext),
+        ("lowerNext", Modifier::LowerNext),
+        ("allCapsOn", Modifier::AllCapsOn),
+        ("allCapsOff", Modifier::AllCapsOff),
+        ("numeral", Modifier::Numeral),
+        ("literal", Modifier::Literal),
+    ];
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map(|(_, modifier)| *modifier)
+    }
+
+    fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, modifier)| *modifier == self)
+            .map_or("", |(name, _)| name)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Action {
+    Insert {
+        text: String,
+        space_before: bool,
+        space_after: bool,
+    },
+    Break(String),
+    Modifier(Modifier),
+}
+
+impl Action {
+    // Spacing follows from the characters unless the mapping file says
+    // otherwise: closing marks attach left, opening marks attach right.
+    fn insert(text: &str) -> Self {
+        if text.contains('\n') && text.trim_matches('\n').is_empty() {
+            return Action::Break(text.to_string());
+        }
+        let opening = text.ends_with(['(', '[', '{', '<']);
+        let closing = text.starts_with(['.', ',', '!', '?', ';', ':', ')', ']', '}', '>', '%']);
+        Action::Insert {
+            text: text.to_string(),
+            space_before: !closing,
+            space_after: !opening,
+        }
+    }
+
+    fn attached(text: &str, space_before: bool, space_after: bool) -> Self {
+        Action::Insert {
+            text: text.to_string(),
+            space_before,
+            space_after,
+        }
+    }
+
+    fn parse(value: &Value) -> Result<Self, String> {
+        if let Some(text) = value.as_str() {
+            return Ok(Action::insert(text));
+        }
+        let Some(fields) = value.as_object() else {
+            return Err("a command must be a string, an object or null".into());
+        };
+        if let Some(name) = fields.get("action") {
+            let name = name.as_str().unwrap_or_default();
+            return Modifier::parse(name)
+                .map(Action::Modifier)
+                .ok_or_else(|| format!("unknown action {name}"));
+        }
+        let text = fields
+            .get("text")
+            .and_then(Value::as_str)
+            .ok_or("a command object needs text or action")?;
+        match Action::insert(text) {
+            Action::Insert {
+                space_before,
+                space_after,
+                ..
+            } => {
+                let flag = |key: &str, default: bool| {
+                    fields.get(key).and_then(Value::as_bool).unwrap_or(default)
+                };
+                Ok(Action::attached(
+                    text,
+                    flag("spaceBefore", space_before),
+                    flag("spaceAfter", space_after),
+                ))
+            }
+            other => Ok(other),
+        }
+    }
+
+    fn describe(&self) -> Value {
+        match self {
+            Action::Insert {
+                text,
+                space_before,
+                space_after,
+            } => json!({ "text": text, "spaceBefore": space_before, "spaceAfter": space_after }),
+            Action::Break(text) => json!({ "text": text }),
+            Action::Modifier(modifier) => json!({ "action": modifier.name() }),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Commands {
+    // Lowercased phrase words, longest phrases first.
+    commands: Vec<(Vec<String>, Action)>,
+}
+
+impl Commands {
+    pub fn builtin() -> Self {
+        let mut commands = Self {
+            commands: Vec::new(),
+        };
+        for (phrase, text) in [
+            ("comma", ","),
+            ("period", "."),
+            ("full stop", "."),
+            ("question mark", "?"),
+            ("exclamation mark", "!"),
+            ("exclamation point", "!"),
+            ("colon", ":"),
+            ("semicolon", ";"),
+            ("ellipsis", "..."),
+            ("open parenthesis", "("),
+            ("close parenthesis", ")"),
+            ("open paren", "("),
+            ("close paren", ")"),
+            ("open bracket", "["),
+            ("close bracket", "]"),
+            ("open brace", "{"),
+            ("close brace", "}"),
+            ("dash", "-"),
+            ("ampersand", "&"),
+            ("at sign", "@"),
+            ("hashtag", "#"),
+            ("percent sign", "%"),
+            ("new line", "\n"),
+            ("newline", "\n"),
+            ("new paragraph", "\n\n"),
+        ] {
+            commands.set(phrase, Action::insert(text));
+        }
+        commands.set("open quote", Action::attached("\"", true, false));
+        commands.set("close quote", Action::attached("\"", false, true));
+        commands.set("hyphen", Action::attached("-", false, false));
+        commands.set("apostrophe", Action::attached("'", false, false));
+        commands.set("slash", Action::attached("/", false, false));
+        commands.set("underscore", Action::attached("_", false, false));
+        commands.set("no space", Action::attached("", false, false));
+        for (phrase, modifier) in [
+            ("cap next word", Modifier::CapNext),
+            ("capitalize next word", Modifier::CapNext),
+            ("all caps next word", Modifier::AllCapsNext),
+            ("lowercase next word", Modifier::LowerNext),
+            ("no caps next word", Modifier::LowerNext),
+            ("all caps on", Modifier::AllCapsOn),
+            ("all caps off", Modifier::AllCapsOff),
+            ("numeral", Modifier::Numeral),
+            ("literal", Modifier::Literal),
+        ] {
+            commands.set(phrase, Action::Modifier(modifier));
+        }
+        commands
+    }
+
+    // A JSON object of spoken phrase -> "text", {"text", "spaceBefore",
+    // "spaceAfter"}, {"action": "capNext" | "allCapsNext" | "lowerNext" |
+    // "allCapsOn" | "allCapsOff" | "numeral" | "literal"}, or null to drop a
+    // built-in phrase.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+        let value: Value = serde_json::from_str(&raw)
+            .map_err(|err| format!("invalid spoken commands {}: {err}", path.display()))?;
+        let Some(entries) = value.as_object() else {
+            return Err(format!("{} must hold a JSON object", path.display()));
+        };
+
+        let mut commands = Self::builtin();
+        for (phrase, value) in entries {
+            if value.is_null() {
+                commands.remove(phrase);
+                continue;
+            }
+            let action =
+                Action::parse(value).map_err(|err| format!("spoken command {phrase}: {err}"))?;
+            commands.set(phrase, action);
+        }
+        Ok(commands)
+    }
+
+    fn key(phrase: &str) -> Vec<String> {
+        phrase.split_whitespace().map(str::to_lowercase).collect()
+    }
+
+    fn set(&mut self, phrase: &str, action: Action) {
+        let key = Self::key(phrase);
+        if key.is_empty() {
+            return;
+        }
+        self.commands.retain(|(existing, _)| *existing != key);
+        self.commands.push((key, action));
+        self.commands
+            .sort_by_key(|(phrase, _)| std::cmp::Reverse(phrase.len()));
+    }
+
+    fn remove(&mut self, phrase: &str) {
+        let key = Self::key(phrase);
+        self.commands.retain(|(existing, _)| *existing != key);
+    }
+
+    pub fn describe(&self) -> Value {
+        let commands: Map<String, Value> = self
+            .commands
+            .iter()
+            .map(|(phrase, action)| (phrase.join(" "), action.describe()))
+            .collect();
+        Value::Object(commands)
+    }
+
+    // The longest command starting at pieces[i], if any. Only plain words
+    // can be spoken commands; ASR punctuation around them is ignored.
+    fn match_at(&self, pieces: &[Piece], i: usize) -> Option<(&Action, usize)> {
+        self.commands.iter().find_map(|(phrase, action)| {
+            let words = pieces.get(i..i + phrase.len())?;
+            let matches = words.iter().zip(phrase).all(|(piece, spoken)| match piece {
+                Piece::Word { text, fixed: false } => bare(text).to_lowercase() == *spoken,
+                _ => false,
+            });
+            matches.then_some((action, phrase.len()))
+        })
+    }
+
+    pub fn interpret(&self, pieces: Vec<Piece>, locale: &Locale) -> Vec<Piece> {
+        let mut out = Vec::with_capacity(pieces.len());
+        let mut pending: Option<Modifier> = None;
+        let mut all_caps = false;
+        let mut i = 0;
+
+        while i < pieces.len() {
+            if pending == Some(Modifier::Literal) {
+                pending = None;
+                if let Piece::Word { text, .. } = &pieces[i] {
+                    out.push(Piece::fixed(bare(text).to_string()));
+                    i += 1;
+                    continue;
+                }
+            }
+            if pending == Some(Modifier::Numeral) {
+                pending = None;
+                if let Some((text, used)) = parse_numeral(&pieces[i..], locale) {
+                    out.push(Piece::fixed(text));
+                    i += used;
+                    continue;
+                }
+            }
+
+            if let Some((action, used)) = self.match_at(&pieces, i) {
+                i += used;
+                match action {
+                    Action::Insert {
+                        text,
+                        space_before,
+                        space_after,
+                    } => {
+                        // "comma" after ASR already wrote one shouldn't double it.
+                        strip_trailing_punct(&mut out, text);
+                        out.push(Piece::Mark {
+                            text: text.clone(),
+                            space_before: *space_before,
+                            space_after: *space_after,
+                        });
+                    }
+                    Action::Break(text) => out.push(Piece::Break(text.clone())),
+                    Action::Modifier(Modifier::AllCapsOn) => all_caps = true,
+                    Action::Modifier(Modifier::AllCapsOff) => all_caps = false,
+                    Action::Modifier(modifier) => pending = Some(*modifier),
+                }
+                continue;
+            }
+
+            let piece = pieces[i].clone();
+            i += 1;
+            let Piece::Word { text, fixed } = piece else {
+                out.push(piece);
+                continue;
+            };
+            let cased = match pending.take() {
+                Some(Modifier::CapNext) => Some(capitalize(&text)),
+                Some(Modifier::AllCapsNext) => Some(text.to_uppercase()),
+                Some(Modifier::LowerNext) => Some(text.to_lowercase()),
+                _ if all_caps && !fixed => Some(text.to_uppercase()),
+                _ => None,
+            };
+            out.push(match cased {
+                Some(text) => Piece::fixed(text),
+                None => Piece::Word { text, fixed },
+            });
+        }
+        out
+    }
+}
+
+// ASR models punctuate on their own, so a spoken "period" often arrives as
+// "period." and the word before it may already end in one.
+fn strip_trailing_punct(out: &mut [Piece], inserted: &str) {
+    let Some(Piece::Word { text, fixed: false }) = out.last_mut() else {
+        return;
+    };
+    if inserted.starts_with(['.', ',', '!', '?', ';', ':']) {
+        let trimmed = text.trim_end_matches(['.', ',', '!', '?', ';', ':']).len();
+        if trimmed > 0 {
+            text.truncate(trimmed);
+        }
+    }
+}
+
+pub fn bare(word: &str) -> &str {
+    word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+}
+
+pub fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn number_word(word: &str) -> Option<u64> {
+    const SMALL: [&str; 20] = [
+        "zero",
+        "one",
+        "two",
+        "three",
+        "four",
+        "five",
+        "six",
+        "seven",
+        "eight",
+        "nine",
+        "ten",
+        "eleven",
+        "twelve",
+        "thirteen",
+        "fourteen",
+        "fifteen",
+        "sixteen",
+        "seventeen",
+        "eighteen",
+        "nineteen",
+    ];
+    const TENS: [&str; 8] = [
+        "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+    ];
+    if let Some(n) = SMALL.iter().position(|known| *known == word) {
+        return Some(n as u64);
+    }
+    TENS.iter()
+        .position(|known| *known == word)
+        .map(|n| (n as u64 + 2) * 10)
+}
+
+const MONTHS: [&str; 12] = [
+    "january",
+    "february",
+    "march",
+    "april",
+    "may",
+    "june",
+    "july",
+    "august",
+    "september",
+    "october",
+    "november",
+    "december",
+];
+
+const ORDINALS: [&str; 19] = [
+    "first",
+    "second",
+    "third",
+    "fourth",
+    "fifth",
+    "sixth",
+    "seventh",
+    "eighth",
+    "ninth",
+    "tenth",
+    "eleventh",
+    "twelfth",
+    "thirteenth",
+    "fourteenth",
+    "fifteenth",
+    "sixteenth",
+    "seventeenth",
+    "eighteenth",
+    "nineteenth",
+];
+
+fn currency_symbol(word: &str) -> Option<&'static str> {
+    match word {
+        "dollar" | "dollars" => Some("$"),
+        "euro" | "euros" => Some("€"),
+        "pound" | "pounds" => Some("£"),
+        "yen" => Some("¥"),
+        "rupee" | "rupees" => Some("₹"),
+        _ => None,
+    }
+}
+
+// What follows "numeral": a date ("march third twenty twenty six"), an
+// amount ("twelve point five euros") or a plain number, written for
+// `locale`. Returns the text and how many pieces it used.
+fn parse_numeral(pieces: &[Piece], locale: &Locale) -> Option<(String, usize)> {
+    let words: Vec<String> = pieces
+        .iter()
+        .map_while(|piece| match piece {
+            Piece::Word { text, fixed: false } => Some(bare(text).to_lowercase()),
+            _ => None,
+        })
+        .collect();
+    if let Some((day, month, year, used)) = parse_date(&words) {
+        return Some((locale.date(day, month, year), used));
+    }
+
+    let (whole, fraction, used) = parse_decimal(&words)?;
+    let number = locale.number(whole, fraction.as_deref());
+    match words.get(used).and_then(|word| currency_symbol(word)) {
+        Some(symbol) => Some((locale.money(&number, symbol), used + 1)),
+        None => Some((number, used)),
+    }
+}
+
+// "three point one four", or a number the ASR already wrote ("3.14",
+// "12,000"; English models write English separators).
+fn parse_decimal(words: &[String]) -> Option<(u64, Option<String>, usize)> {
+    let written = words.first()?.replace(',', "");
+    let (whole, fraction) = written.split_once('.').unwrap_or((&written, ""));
+    if let Ok(whole) = whole.parse::<u64>() {
+        if fraction.chars().all(|c| c.is_ascii_digit()) {
+            let fraction = (!fraction.is_empty()).then(|| fraction.to_string());
+            return Some((whole, fraction, 1));
+        }
+    }
+
+    let (whole, used) = parse_number(words)?;
+    if words.get(used).map(String::as_str) != Some("point") {
+        return Some((whole, None, used));
+    }
+    let mut fraction = String::new();
+    let mut digits = 0;
+    for word in &words[used + 1..] {
+        match number_word(word) {
+            Some(digit) if digit < 10 => fraction.push_str(&digit.to_string()),
+            None if !word.is_empty() && word.chars().all(|c| c.is_ascii_digit()) => {
+                fraction.push_str(word)
+            }
+            _ => break,
+        }
+        digits += 1;
+    }
+    match digits {
+        0 => Some((whole, None, used)),
+        _ => Some((whole, Some(fraction), used + 1 + digits)),
+    }
+}
+
+// "march third", "march 3rd 2026", "third of march twenty twenty six".
+// Returns day, month, year and how many words it used.
+fn parse_date(words: &[String]) -> Option<(u32, u32, Option<u64>, usize)> {
+    let month_number = |word: &String| {
+        MONTHS
+            .iter()
+            .position(|month| month == word)
+            .map(|n| n as u32 + 1)
+    };
+    let (day, month, mut used) = match month_number(words.first()?) {
+        Some(month) => {
+            let (day, used) = parse_day(&words[1..])?;
+            (day, month, 1 + used)
+        }
+        None => {
+            let (day, used) = parse_day(words)?;
+            if words.get(used).map(String::as_str) != Some("of") {
+                return None;
+            }
+            (day, month_number(words.get(used + 1)?)?, used + 2)
+        }
+    };
+    let year = parse_year(&words[used..]).map(|(year, more)| {
+        used += more;
+        year
+    });
+    Some((day, month, year, used))
+}
+
+fn parse_day(words: &[String]) -> Option<(u32, usize)> {
+    let first = words.first()?;
+    let ordinal = |word: &str| {
+        ORDINALS
+            .iter()
+            .position(|known| *known == word)
+            .map(|n| n as u32 + 1)
+    };
+    let (day, used) = if let Some(day) = ordinal(first) {
+        (day, 1)
+    } else if first == "twentieth" || first == "thirtieth" {
+        (if first == "twentieth" { 20 } else { 30 }, 1)
+    } else if let Ok(day) = first
+        .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+        .parse::<u32>()
+    {
+        (day, 1)
+    } else {
+        // "twenty first", or a cardinal: "march fourteen".
+        let tens = number_word(first).filter(|n| *n == 20 || *n == 30);
+        match (tens, words.get(1).and_then(|word| ordinal(word))) {
+            (Some(tens), Some(unit)) if unit < 10 => (tens as u32 + unit, 2),
+            _ => {
+                let (day, used) = two_digit(words)?;
+                (day as u32, used)
+            }
+        }
+    };
+    (1..=31).contains(&day).then_some((day, used))
+}
+
+// "two thousand five", "2026", or said in pairs: "nineteen ninety nine".
+fn parse_year(words: &[String]) -> Option<(u64, usize)> {
+    if let Some(year) = parse_number(words).filter(|(year, _)| *year >= 1000) {
+        return Some(year);
+    }
+    let (century, used) = two_digit(words).filter(|(century, _)| *century >= 10)?;
+    let (rest, more) = two_digit(&words[used..]).filter(|(rest, _)| *rest >= 10)?;
+    Some((century * 100 + rest, used + more))
+}
+
+// 0..=99 in words: "seven", "fifteen", "forty", "forty two".
+fn two_digit(words: &[String]) -> Option<(u64, usize)> {
+    let first = number_word(words.first()?)?;
+    let unit = words
+        .get(1)
+        .and_then(|word| number_word(word))
+        .filter(|unit| first >= 20 && (1..10).contains(unit));
+    match unit {
+        Some(unit) => Some((first + unit, 2)),
+        None => Some((first, 1)),
+    }
+}
+
+// "seven" -> 7, "twenty one" -> 21, "three hundred and five" -> 305, and
+// digits the ASR already wrote pass through. Returns the value and how many
+// words it used.
+fn parse_number(words: &[String]) -> Option<(u64, usize)> {
+    if let Some(first) = words.first() {
+        if let Ok(value) = first.parse::<u64>() {
+            return Some((value, 1));
+        }
+    }
+
+    let (mut total, mut current) = (0_u64, 0_u64);
+    // Words up to and including the last number word; a dangling "and"
+    // isn't part of the number.
+    let mut used = 0;
+    let mut last_was_number = false;
+    for (index, word) in words.iter().enumerate() {
+        match word.as_str() {
+            "and" if last_was_number => {
+                last_was_number = false;
+                continue;
+            }
+            "hundred" if last_was_number => current = current.max(1) * 100,
+            "thousand" if last_was_number => {
+                total += current * 1_000;
+                current = 0;
+            }
+            "million" if last_was_number => {
+                total += current * 1_000_000;
+                current = 0;
+            }
+            _ => match number_word(word) {
+                Some(n) => current += n,
+                None => break,
+            },
+        }
+        last_was_number = true;
+        used = index + 1;
+    }
+    (used > 0).then_some((total + current, used))
+}
@@ -0,0 +1,378 @@
+use dingoflow_frame::{read_frame, read_response, write_frame, Frame, MAX_JSON_BYTES};
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::Message;
+
+// A worker that stayed up this long crashed for a new reason, so its next
+// restart starts from the initial delay again.
+const STABLE_AFTER: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Audio,
+    Asr,
+    Vad,
+    Refine,
+}
+
+impl Role {
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Audio => "audio",
+            Role::Asr => "asr",
+            Role::Vad => "vad",
+            Role::Refine => "refine",
+        }
+    }
+}
+
+// The restart policy, kept apart from the processes so it can be tested:
+// each quick exit doubles the delay up to `max`, which is as often as a
+// worker stuck in a crash loop gets restarted, and a worker that stayed up
+// STABLE_AFTER starts over from `initial`.
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            next: initial,
+        }
+    }
+
+    // The delay before restarting a worker that exited after `uptime`, or
+    // that failed to start at all (None).
+    fn after_exit(&mut self, uptime: Option<Duration>) -> Duration {
+        if uptime.is_some_and(|uptime| uptime >= STABLE_AFTER) {
+            self.reset();
+        }
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+// One child process plus the restart bookkeeping around it. Reader threads
+// tag everything they send with the generation they were spawned for, so
+// output from a child that has already been replaced is dropped.
+pub struct Worker {
+    pub role: Role,
+    program: PathBuf,
+    args: Vec<String>,
+    child: Option<Child>,
+    stdin: Option<ChildStdin>,
+    pub generation: u64,
+    pub restarts: u64,
+    backoff: Backoff,
+    started_at: Option<Instant>,
+    pub restart_at: Option<Instant>,
+}
+
+impl Worker {
+    pub fn new(role: Role, program: PathBuf, args: Vec<String>, backoff: Backoff) -> Self {
+        Self {
+            role,
+            program,
+            args,
+            child: None,
+            stdin: None,
+            generation: 0,
+            restarts: 0,
+            backoff,
+            started_at: None,
+            restart_at: None,
+        }
+    }
+
+    // Takes effect on the next spawn.
+    pub fn set_command(&mut self, program: PathBuf, args: Vec<String>) {
+        self.program = program;
+        self.args = args;
+    }
+
+    pub fn runs(&self, program: &Path, args: &[String]) -> bool {
+        self.program == program && self.args == args
+    }
+
+    pub fn pid(&self) -> Option<u32> {
+        self.child.as_ref().map(Child::id)
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.child.is_some()
+    }
+
+    pub fn spawn(&mut self, tx: &Sender<Message>) -> Result<u32, String> {
+        self.restart_at = None;
+        self.generation += 1;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| {
+                format!(
+                    "failed to start {} worker {}: {err}",
+                    self.role.name(),
+                    self.program.display()
+                )
+            })?;
+
+        let stdout = child.stdout.take().ok_or("worker stdout unavailable")?;
+        let stderr = child.stderr.take().ok_or("worker stderr unavailable")?;
+        let (role, generation) = (self.role, self.generation);
+        match role {
+            Role::Audio => spawn_frame_reader(stdout, generation, tx.clone()),
+            Role::Asr | Role::Vad | Role::Refine => {
+                spawn_response_reader(stdout, role, generation, tx.clone())
+            }
+        }
+        spawn_line_reader(stderr, role, generation, tx.clone());
+
+        let pid = child.id();
+        self.stdin = child.stdin.take();
+        self.child = Some(child);
+        self.started_at = Some(Instant::now());
+        Ok(pid)
+    }
+
+    // Called once the child's stdout closed. The child is killed in case it
+    // only closed the pipe, then reaped; the returned delay is when to restart.
+    pub fn reap(&mut self) -> (String, Duration) {
+        self.stdin = None;
+        let status = match self.child.take() {
+            Some(mut child) => {
+                let _ = child.kill();
+                match child.wait() {
+                    Ok(status) => status.to_string(),
+                    Err(err) => format!("wait failed: {err}"),
+                }
+            }
+            None => "not running".to_string(),
+        };
+        let uptime = self.started_at.take().map(|started| started.elapsed());
+        let delay = self.backoff.after_exit(uptime);
+        self.restarts += 1;
+        self.restart_at = Some(Instant::now() + delay);
+        (status, delay)
+    }
+
+    // Used for an explicit restart: the exit that follows restarts at once.
+    pub fn kill(&mut self, reset_backoff: bool) {
+        if reset_backoff {
+            self.backoff.reset();
+            self.started_at = None;
+        }
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    pub fn shutdown(&mut self) {
+        self.stdin = None;
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.restart_at = None;
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        let stdin = self.stdin_mut()?;
+        stdin.write_all(line.as_bytes())?;
+        stdin.write_all(b"\n")?;
+        stdin.flush()
+    }
+
+    // Same request layout the workers read on --serve: magic, version, u32 LE
+    // JSON length, u32 LE audio length, the JSON, then PCM.
+    pub fn write_frame(&mut self, request: &Value, audio: &[u8]) -> io::Result<()> {
+        let json = request.to_string();
+        write_frame(self.stdin_mut()?, json.as_bytes(), audio)
+    }
+
+    fn stdin_mut(&mut self) -> io::Result<&mut ChildStdin> {
+        self.stdin
+            .as_mut()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotConnected, "worker is not running"))
+    }
+}
+
+// audio_loop --framed: the same 13-byte header, a JSON chunk header, then PCM.
+fn spawn_frame_reader<R: Read + Send + 'static>(stdout: R, generation: u64, tx: Sender<Message>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::with_capacity(64 * 1024, stdout);
+        let reason = loop {
+            let Frame { json, audio } = match read_frame(&mut reader) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break None,
+                Err(err) => break Some(format!("invalid audio frame: {err}")),
+            };
+            let header = match serde_json::from_slice::<Value>(&json) {
+                Ok(header) => header,
+                Err(err) => break Some(format!("invalid audio frame header: {err}")),
+            };
+            if tx
+                .send(Message::AudioFrame {
+                    generation,
+                    header,
+                    audio,
+                })
+                .is_err()
+            {
+                return;
+            }
+        };
+        let _ = tx.send(Message::Closed {
+            role: Role::Audio,
+            generation,
+            reason,
+        });
+    });
+}
+
+// Worker responses: magic, version, u32 LE JSON length, then the JSON.
+fn spawn_response_reader<R: Read + Send + 'static>(
+    stdout: R,
+    role: Role,
+    generation: u64,
+    tx: Sender<Message>,
+) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        let reason = loop {
+            let body = match read_response(&mut reader, MAX_JSON_BYTES) {
+                Ok(Some(body)) => body,
+                Ok(None) => break None,
+                Err(err) => break Some(format!("invalid response: {err}")),
+            };
+            let response = serde_json::from_slice::<Value>(&body)
+                .map_err(|err| format!("invalid response JSON: {err}"));
+            match response {
+                Ok(response) => {
+                    if tx
+                        .send(Message::Response {
+                            role,
+                            generation,
+                            response,
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Err(err) => break Some(err),
+            }
+        };
+        let _ = tx.send(Message::Closed {
+            role,
+            generation,
+            reason,
+        });
+    });
+}
+
+fn spawn_line_reader<R: Read + Send + 'static>(
+    stderr: R,
+    role: Role,
+    generation: u64,
+    tx: Sender<Message>,
+) {
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            if tx
+                .send(Message::WorkerLine {
+                    role,
+                    generation,
+                    line,
+                })
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: Duration = Duration::from_millis(1);
+
+    fn delays(backoff: &mut Backoff, uptimes: &[Option<Duration>]) -> Vec<Duration> {
+        uptimes
+            .iter()
+            .map(|uptime| backoff.after_exit(*uptime))
+            .collect()
+    }
+
+    #[test]
+    fn quick_exits_double_the_delay_up_to_the_max() {
+        let mut backoff = Backoff::new(250 * MS, 1800 * MS);
+        let quick = Some(Duration::from_secs(1));
+        assert_eq!(
+            delays(&mut backoff, &[quick; 6]),
+            [
+                250 * MS,
+                500 * MS,
+                1000 * MS,
+                1800 * MS,
+                1800 * MS,
+                1800 * MS
+            ]
+        );
+    }
+
+    #[test]
+    fn failing_to_start_counts_as_a_quick_exit() {
+        let mut backoff = Backoff::new(250 * MS, 10_000 * MS);
+        assert_eq!(
+            delays(&mut backoff, &[None, None, Some(MS)]),
+            [250 * MS, 500 * MS, 1000 * MS]
+        );
+    }
+
+    #[test]
+    fn a_stable_run_starts_over_from_the_initial_delay() {
+        let mut backoff = Backoff::new(250 * MS, 10_000 * MS);
+        let quick = Some(Duration::from_secs(1));
+        delays(&mut backoff, &[quick, quick, quick]);
+        assert_eq!(backoff.after_exit(Some(STABLE_AFTER)), 250 * MS);
+        assert_eq!(backoff.after_exit(quick), 500 * MS);
+        assert_eq!(
+            backoff.after_exit(Some(STABLE_AFTER - Duration::from_secs(1))),
+            1000 * MS
+        );
+    }
+
+    #[test]
+    fn an_explicit_reset_starts_over() {
+        let mut backoff = Backoff::new(250 * MS, 10_000 * MS);
+        delays(&mut backoff, &[None, None, None]);
+        backoff.reset();
+        assert_eq!(backoff.after_exit(None), 250 * MS);
+    }
+}
@@ -0,0 +1,328 @@
+mod phonemize;
+mod voice;
+
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use dingoflow_frame::{read_frame, write_response};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use phonemize::Phonemizer;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use voice::Voice;
+
+const MAX_TEXT_CHARS: usize = 4_000;
+const DEFAULT_SENTENCE_SILENCE_MS: u32 = 200;
+
+// Speaks short confirmations, errors and read-backs with a local Piper
+// voice. Requests use the same frames as the ASR workers (the audio length
+// is always 0); the response carries the audio as base64 PCM16 or WAV.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Pcm16,
+    Wav,
+}
+
+#[derive(Debug)]
+struct Config {
+    voice_path: Option<PathBuf>,
+    voice_config: Option<PathBuf>,
+    espeak_bin: String,
+    threads: usize,
+    sentence_silence_ms: u32,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    text: Option<String>,
+    format: Option<String>,
+    length_scale: Option<f32>,
+    speaker: Option<u32>,
+}
+
+struct Tts {
+    voice: Voice,
+    phonemizer: Phonemizer,
+    sentence_silence_ms: u32,
+}
+
+impl Tts {
+    fn synthesize(&mut self, req: &Request) -> Result<Value, String> {
+        let text = req.text.as_deref().ok_or("synthesize requires text")?;
+        if text.chars().count() > MAX_TEXT_CHARS {
+            return Err(format!("text is longer than {MAX_TEXT_CHARS} characters"));
+        }
+        let format = match req.format.as_deref().unwrap_or("pcm16") {
+            "pcm16" => Format::Pcm16,
+            "wav" => Format::Wav,
+            other => return Err(format!("unsupported format {other}; expected pcm16 or wav")),
+        };
+        let mut scales = self.voice.inference();
+        if let Some(length_scale) = req.length_scale {
+            if !(0.25..=4.0).contains(&length_scale) {
+                return Err("lengthScale must be between 0.25 and 4".into());
+            }
+            scales.length_scale = length_scale;
+        }
+
+        let sample_rate = self.voice.sample_rate();
+        let silence =
+            vec![0_i16; (sample_rate as u64 * self.sentence_silence_ms as u64 / 1000) as usize];
+        let sentences = self.phonemizer.sentences(text)?;
+        let mut samples: Vec<i16> = Vec::new();
+        for (index, phonemes) in sentences.iter().enumerate() {
+            if index > 0 {
+                samples.extend_from_slice(&silence);
+            }
+            let audio = self
+                .voice
+                .synthesize(phonemes, scales, req.speaker.unwrap_or(0))?;
+            samples.extend(to_pcm16(&audio));
+        }
+
+        let bytes = match format {
+            Format::Pcm16 => samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            Format::Wav => to_wav(&samples, sample_rate)?,
+        };
+        Ok(json!({
+            "format": if format == Format::Wav { "wav" } else { "pcm16" },
+            "sampleRate": sample_rate,
+            "durationMs": samples.len() as u64 * 1000 / sample_rate as u64,
+            "sentences": sentences.len(),
+            "audioBase64": BASE64_STANDARD.encode(bytes),
+        }))
+    }
+
+    fn handle(&mut self, req: &Request) -> Result<Value, String> {
+        match req.action.as_deref().unwrap_or("synthesize") {
+            "warmup" => {
+                self.synthesize(&Request {
+                    text: Some("Ready.".into()),
+                    ..Default::default()
+                })?;
+                Ok(json!({
+                    "ready": true,
+                    "sampleRate": self.voice.sample_rate(),
+                    "speakers": self.voice.speakers(),
+                }))
+            }
+            "synthesize" => self.synthesize(req),
+            other => Err(format!("Unsupported action: {other}")),
+        }
+    }
+}
+
+// Piper peak-normalizes every sentence before converting to 16-bit.
+fn to_pcm16(audio: &[f32]) -> impl Iterator<Item = i16> + '_ {
+    let peak = audio.iter().fold(0.01_f32, |peak, s| peak.max(s.abs()));
+    let gain = i16::MAX as f32 / peak;
+    audio
+        .iter()
+        .map(move |s| (s * gain).clamp(i16::MIN as f32, i16::MAX as f32) as i16)
+}
+
+fn to_wav(samples: &[i16], sample_rate: u32) -> Result<Vec<u8>, String> {
+    let spec = WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    };
+    let mut bytes = Vec::new();
+    let mut writer = WavWriter::new(Cursor::new(&mut bytes), spec)
+        .map_err(|e| format!("failed to start wav: {e}"))?;
+    for sample in samples {
+        writer
+            .write_sample(*sample)
+            .map_err(|e| format!("failed to write wav: {e}"))?;
+    }
+    writer
+        .finalize()
+        .map_err(|e| format!("failed to finish wav: {e}"))?;
+    Ok(bytes)
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut voice_path: Option<PathBuf> = None;
+    let mut voice_config: Option<PathBuf> = None;
+    let mut espeak_bin = "espeak-ng".to_string();
+    let mut threads = 2_usize;
+    let mut sentence_silence_ms = DEFAULT_SENTENCE_SILENCE_MS;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--voice" => {
+                voice_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--voice-config" => {
+                voice_config = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--espeak-bin" => {
+                espeak_bin = value()?;
+                i += 2;
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=16).contains(n))
+                    .ok_or("--threads must be between 1 and 16")?;
+                i += 2;
+            }
+            "--sentence-silence-ms" => {
+                sentence_silence_ms = value()?
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|ms| *ms <= 2_000)
+                    .ok_or("--sentence-silence-ms must be between 0 and 2000")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-tts-worker --voice <voice.onnx> [--voice-config <voice.onnx.json>] [--espeak-bin espeak-ng] [--threads 2] [--sentence-silence-ms 200] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    if voice_path.is_none() && !healthcheck {
+        return Err("--voice is required unless --healthcheck is used".into());
+    }
+
+    Ok(Config {
+        voice_path,
+        voice_config,
+        espeak_bin,
+        threads,
+        sentence_silence_ms,
+        serve,
+        healthcheck,
+    })
+}
+
+fn run_server(mut tts: Tts) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        if !frame.audio.is_empty() {
+            return Err(format!(
+                "unexpected audio payload: {} bytes",
+                frame.audio.len()
+            ));
+        }
+
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match tts.handle(&req) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        match Phonemizer::new(cfg.espeak_bin, String::new()).check() {
+            Ok(()) => println!("ok"),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    let Some(voice_path) = cfg.voice_path else {
+        eprintln!("--voice is required");
+        std::process::exit(1);
+    };
+    let config_path = cfg.voice_config.unwrap_or_else(|| {
+        let mut path = voice_path.clone().into_os_string();
+        path.push(".json");
+        PathBuf::from(path)
+    });
+    let voice = match Voice::load(&voice_path, &config_path, cfg.threads) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let phonemizer = Phonemizer::new(cfg.espeak_bin, voice.espeak_voice().to_string());
+    if let Err(err) = phonemizer.check() {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+
+    let tts = Tts {
+        voice,
+        phonemizer,
+        sentence_silence_ms: cfg.sentence_silence_ms,
+    };
+
+    if let Err(err) = run_server(tts) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
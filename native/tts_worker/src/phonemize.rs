@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Piper voices are trained on espeak-ng IPA, so text goes through the
+// espeak-ng CLI one clause at a time and the clause's punctuation is put back
+// as its own phoneme, which is what gives the voice its pauses and intonation.
+const CLAUSE_BREAKS: &[char] = &[',', ';', ':', '.', '!', '?'];
+const SENTENCE_ENDS: &[char] = &['.', '!', '?'];
+const PAD: &str = "_";
+const BOS: &str = "^";
+const EOS: &str = "$";
+
+pub struct Phonemizer {
+    espeak_bin: String,
+    voice: String,
+}
+
+impl Phonemizer {
+    pub fn new(espeak_bin: String, voice: String) -> Self {
+        Self { espeak_bin, voice }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        Command::new(&self.espeak_bin)
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("failed to run {}: {e}", self.espeak_bin))
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("{} exited with {status}", self.espeak_bin))
+                }
+            })
+    }
+
+    // One phoneme string per sentence.
+    pub fn sentences(&self, text: &str) -> Result<Vec<String>, String> {
+        let mut sentences = Vec::new();
+        let mut current = String::new();
+        for (clause, mark) in clauses(text) {
+            let phonemes = if clause.trim().is_empty() {
+                String::new()
+            } else {
+                self.espeak(&clause)?
+            };
+            if !phonemes.is_empty() {
+                if !current.is_empty() {
+                    current.push(' ');
+                }
+                current.push_str(&phonemes);
+            }
+            if let Some(mark) = mark {
+                current.push(mark);
+            }
+            if mark.is_none_or(|mark| SENTENCE_ENDS.contains(&mark)) && !current.trim().is_empty() {
+                sentences.push(std::mem::take(&mut current));
+            }
+        }
+        if !current.trim().is_empty() {
+            sentences.push(current);
+        }
+        Ok(sentences)
+    }
+
+    fn espeak(&self, clause: &str) -> Result<String, String> {
+        let mut child = Command::new(&self.espeak_bin)
+            .args(["-q", "--ipa", "-v", &self.voice, "--stdin"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("failed to run {}: {e}", self.espeak_bin))?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(clause.as_bytes())
+                .map_err(|e| format!("failed to write to {}: {e}", self.espeak_bin))?;
+        }
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("failed to wait for {}: {e}", self.espeak_bin))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!(
+                "{} exited with {}: {}",
+                self.espeak_bin,
+                output.status,
+                stderr.trim()
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "))
+    }
+}
+
+// Splits on clause punctuation, keeping the mark (if any) with its clause.
+fn clauses(text: &str) -> Vec<(String, Option<char>)> {
+    let mut clauses = Vec::new();
+    let mut current = String::new();
+    for ch in text.chars() {
+        if CLAUSE_BREAKS.contains(&ch) {
+            clauses.push((std::mem::take(&mut current), Some(ch)));
+        } else if ch == '\n' {
+            clauses.push((std::mem::take(&mut current), None));
+        } else {
+            current.push(ch);
+        }
+    }
+    clauses.push((current, None));
+    clauses.retain(|(clause, mark)| !clause.trim().is_empty() || mark.is_some());
+    clauses
+}
+
+// Piper's id layout: BOS, then every phoneme followed by the pad id, then
+// EOS. Phonemes the voice doesn't know are dropped.
+pub fn phoneme_ids(phonemes: &str, id_map: &HashMap<String, Vec<i64>>) -> Vec<i64> {
+    let lookup = |symbol: &str| id_map.get(symbol).map(Vec::as_slice).unwrap_or(&[]);
+    let mut ids = Vec::with_capacity(phonemes.len() * 2 + 3);
+    ids.extend_from_slice(lookup(BOS));
+    ids.extend_from_slice(lookup(PAD));
+    let mut buf = [0_u8; 4];
+    for ch in phonemes.chars() {
+        let known = lookup(ch.encode_utf8(&mut buf));
+        if known.is_empty() {
+            continue;
+        }
+        ids.extend_from_slice(known);
+        ids.extend_from_slice(lookup(PAD));
+    }
+    ids.extend_from_slice(lookup(EOS));
+    ids
+}
@@ -0,0 +1,149 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::phonemize::phoneme_ids;
+
+// A Piper voice: `<voice>.onnx` plus the `<voice>.onnx.json` written next to
+// it at export time, which carries the phoneme table and default scales.
+#[derive(Deserialize)]
+struct VoiceConfig {
+    audio: AudioConfig,
+    #[serde(default)]
+    espeak: EspeakConfig,
+    #[serde(default)]
+    inference: InferenceConfig,
+    phoneme_id_map: HashMap<String, Vec<i64>>,
+    #[serde(default = "one")]
+    num_speakers: u32,
+}
+
+#[derive(Deserialize)]
+struct AudioConfig {
+    sample_rate: u32,
+}
+
+#[derive(Deserialize)]
+struct EspeakConfig {
+    voice: String,
+}
+
+impl Default for EspeakConfig {
+    fn default() -> Self {
+        Self {
+            voice: "en-us".into(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct InferenceConfig {
+    pub noise_scale: f32,
+    pub length_scale: f32,
+    pub noise_w: f32,
+}
+
+impl Default for InferenceConfig {
+    fn default() -> Self {
+        Self {
+            noise_scale: 0.667,
+            length_scale: 1.0,
+            noise_w: 0.8,
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+pub struct Voice {
+    session: Session,
+    config: VoiceConfig,
+}
+
+impl Voice {
+    pub fn load(model_path: &Path, config_path: &Path, threads: usize) -> Result<Self, String> {
+        if !model_path.is_file() {
+            return Err(format!("voice model not found: {}", model_path.display()));
+        }
+        let raw = fs::read_to_string(config_path)
+            .map_err(|e| format!("failed to read {}: {e}", config_path.display()))?;
+        let config: VoiceConfig = serde_json::from_str(&raw)
+            .map_err(|e| format!("invalid voice config {}: {e}", config_path.display()))?;
+        let session = Session::builder()
+            .and_then(|builder| builder.with_intra_threads(threads))
+            .and_then(|builder| builder.commit_from_file(model_path))
+            .map_err(|err| format!("failed to load voice model: {err}"))?;
+        Ok(Self { session, config })
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.config.audio.sample_rate
+    }
+
+    pub fn espeak_voice(&self) -> &str {
+        &self.config.espeak.voice
+    }
+
+    pub fn speakers(&self) -> u32 {
+        self.config.num_speakers
+    }
+
+    pub fn inference(&self) -> InferenceConfig {
+        self.config.inference
+    }
+
+    // Float samples for one sentence's phonemes.
+    pub fn synthesize(
+        &mut self,
+        phonemes: &str,
+        scales: InferenceConfig,
+        speaker: u32,
+    ) -> Result<Vec<f32>, String> {
+        let ids = phoneme_ids(phonemes, &self.config.phoneme_id_map);
+        if ids.len() <= 3 {
+            return Ok(Vec::new());
+        }
+        if speaker >= self.config.num_speakers {
+            return Err(format!(
+                "speaker {speaker} is out of range; the voice has {}",
+                self.config.num_speakers
+            ));
+        }
+
+        let to_err = |err: ort::Error| format!("speech synthesis failed: {err}");
+        let len = ids.len();
+        let input = Tensor::from_array(([1, len], ids)).map_err(to_err)?;
+        let lengths = Tensor::from_array(([1], vec![len as i64])).map_err(to_err)?;
+        let scales = Tensor::from_array((
+            [3],
+            vec![scales.noise_scale, scales.length_scale, scales.noise_w],
+        ))
+        .map_err(to_err)?;
+        let outputs = if self.config.num_speakers > 1 {
+            let sid = Tensor::from_array(([1], vec![speaker as i64])).map_err(to_err)?;
+            self.session.run(ort::inputs![
+                "input" => input,
+                "input_lengths" => lengths,
+                "scales" => scales,
+                "sid" => sid,
+            ])
+        } else {
+            self.session.run(ort::inputs![
+                "input" => input,
+                "input_lengths" => lengths,
+                "scales" => scales,
+            ])
+        }
+        .map_err(to_err)?;
+
+        let (_, audio) = outputs["output"]
+            .try_extract_tensor::<f32>()
+            .map_err(to_err)?;
+        Ok(audio.to_vec())
+    }
+}
@@ -0,0 +1,93 @@
+use serde_json::{json, Value};
+
+// Turns per-window speech probabilities into speech start/end events.
+// Speech opens once the probability stays at or above `threshold` for
+// `min_speech_ms`, and closes once it stays below `neg_threshold` for
+// `hangover_ms`; in between the previous state holds, so a breath or a soft
+// consonant never splits an utterance. Event times are stream milliseconds
+// since the last reset and point at where the run actually began.
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub threshold: f32,
+    pub neg_threshold: f32,
+    pub min_speech_ms: u32,
+    pub hangover_ms: u32,
+}
+
+pub struct Detector {
+    thresholds: Thresholds,
+    in_speech: bool,
+    speech_since: Option<f64>,
+    silence_since: Option<f64>,
+}
+
+impl Detector {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self {
+            thresholds,
+            in_speech: false,
+            speech_since: None,
+            silence_since: None,
+        }
+    }
+
+    pub fn thresholds(&self) -> Thresholds {
+        self.thresholds
+    }
+
+    pub fn in_speech(&self) -> bool {
+        self.in_speech
+    }
+
+    pub fn reset(&mut self, thresholds: Thresholds) {
+        *self = Self::new(thresholds);
+    }
+
+    // `start_ms`..`end_ms` is the span of stream time the window covered.
+    pub fn push(&mut self, probability: f32, start_ms: f64, end_ms: f64) -> Option<Value> {
+        let t = self.thresholds;
+        if !self.in_speech {
+            if probability >= t.threshold {
+                self.speech_since.get_or_insert(start_ms);
+            } else if probability < t.neg_threshold {
+                self.speech_since = None;
+            }
+            let since = self.speech_since?;
+            if end_ms - since < t.min_speech_ms as f64 {
+                return None;
+            }
+            self.in_speech = true;
+            self.speech_since = None;
+            self.silence_since = None;
+            return Some(event("speechStart", since));
+        }
+
+        if probability < t.neg_threshold {
+            self.silence_since.get_or_insert(start_ms);
+        } else if probability >= t.threshold {
+            self.silence_since = None;
+        }
+        let since = self.silence_since?;
+        if end_ms - since < t.hangover_ms as f64 {
+            return None;
+        }
+        self.in_speech = false;
+        self.silence_since = None;
+        Some(event("speechEnd", since))
+    }
+
+    // Closes an open utterance at the end of the stream.
+    pub fn flush(&mut self, now_ms: f64) -> Option<Value> {
+        self.speech_since = None;
+        if !self.in_speech {
+            return None;
+        }
+        let at_ms = self.silence_since.take().unwrap_or(now_ms);
+        self.in_speech = false;
+        Some(event("speechEnd", at_ms))
+    }
+}
+
+fn event(kind: &str, at_ms: f64) -> Value {
+    json!({ "type": kind, "atMs": at_ms.round() as u64 })
+}
@@ -0,0 +1,355 @@
+mod detector;
+mod silero;
+
+use detector::{Detector, Thresholds};
+use dingoflow_frame::{read_frame, write_response};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use silero::Silero;
+use std::io;
+use std::path::PathBuf;
+
+const DEFAULT_SAMPLE_RATE: u32 = 16_000;
+const DEFAULT_THRESHOLD: f32 = 0.5;
+const DEFAULT_MIN_SPEECH_MS: u32 = 250;
+const DEFAULT_HANGOVER_MS: u32 = 300;
+// Silero's reference iterator closes speech 0.15 below the opening threshold.
+const NEG_THRESHOLD_GAP: f32 = 0.15;
+
+// Classifies streamed PCM16 mono audio as speech or silence. Speaks the same
+// frames as the ASR workers, so the host drives one VAD for every backend
+// and for the audio loop.
+#[derive(Debug)]
+struct Config {
+    model_path: Option<PathBuf>,
+    threads: usize,
+    thresholds: Thresholds,
+    serve: bool,
+    healthcheck: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Request {
+    id: Option<String>,
+    action: Option<String>,
+    sample_rate: Option<u32>,
+    threshold: Option<f32>,
+    neg_threshold: Option<f32>,
+    min_speech_ms: Option<u32>,
+    hangover_ms: Option<u32>,
+}
+
+struct Vad {
+    silero: Silero,
+    detector: Detector,
+    defaults: Thresholds,
+    // Samples waiting for a full model window.
+    pending: Vec<f32>,
+    processed_samples: u64,
+}
+
+impl Vad {
+    fn stream_ms(&self, samples: u64) -> f64 {
+        samples as f64 * 1000.0 / self.silero.sample_rate() as f64
+    }
+
+    fn result(&self, probability: Option<f32>, events: Vec<Value>) -> Value {
+        let thresholds = self.detector.thresholds();
+        json!({
+            "speech": self.detector.in_speech(),
+            "probability": probability.map(|p| (p * 1000.0).round() / 1000.0),
+            "events": events,
+            "streamMs": self.stream_ms(self.processed_samples).round() as u64,
+            "sampleRate": self.silero.sample_rate(),
+            "threshold": thresholds.threshold,
+            "negThreshold": thresholds.neg_threshold,
+            "minSpeechMs": thresholds.min_speech_ms,
+            "hangoverMs": thresholds.hangover_ms,
+        })
+    }
+
+    fn reset(&mut self, req: &Request) -> Result<Value, String> {
+        let sample_rate = req.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
+        let threshold = req.threshold.unwrap_or(self.defaults.threshold);
+        let thresholds = validate_thresholds(Thresholds {
+            threshold,
+            neg_threshold: req.neg_threshold.unwrap_or_else(|| {
+                if req.threshold.is_some() {
+                    default_neg_threshold(threshold)
+                } else {
+                    self.defaults.neg_threshold
+                }
+            }),
+            min_speech_ms: req.min_speech_ms.unwrap_or(self.defaults.min_speech_ms),
+            hangover_ms: req.hangover_ms.unwrap_or(self.defaults.hangover_ms),
+        })?;
+        self.silero.reset(sample_rate)?;
+        self.detector.reset(thresholds);
+        self.pending.clear();
+        self.processed_samples = 0;
+        Ok(self.result(None, Vec::new()))
+    }
+
+    fn push(&mut self, req: &Request, audio: &[u8]) -> Result<Value, String> {
+        let sample_rate = req.sample_rate.unwrap_or(self.silero.sample_rate());
+        if sample_rate != self.silero.sample_rate() {
+            return Err(format!(
+                "stream is at {} Hz; send reset with sampleRate {sample_rate} first",
+                self.silero.sample_rate()
+            ));
+        }
+        self.pending.extend(pcm16_to_f32(audio));
+        self.drain(false)
+    }
+
+    // Scores every full window; on flush the tail is padded with silence and
+    // any open utterance is closed.
+    fn drain(&mut self, flush: bool) -> Result<Value, String> {
+        let window = Silero::window_samples(self.silero.sample_rate())?;
+        if flush && !self.pending.is_empty() {
+            let padded = self.pending.len().div_ceil(window) * window;
+            self.pending.resize(padded, 0.0);
+        }
+
+        let mut events = Vec::new();
+        let mut probability = None;
+        let mut consumed = 0;
+        while self.pending.len() - consumed >= window {
+            let p = self
+                .silero
+                .score(&self.pending[consumed..consumed + window])?;
+            let start_ms = self.stream_ms(self.processed_samples);
+            self.processed_samples += window as u64;
+            let end_ms = self.stream_ms(self.processed_samples);
+            events.extend(self.detector.push(p, start_ms, end_ms));
+            probability = Some(p);
+            consumed += window;
+        }
+        self.pending.drain(..consumed);
+
+        if flush {
+            let now_ms = self.stream_ms(self.processed_samples);
+            events.extend(self.detector.flush(now_ms));
+        }
+        Ok(self.result(probability, events))
+    }
+
+    fn warmup(&mut self) -> Result<Value, String> {
+        let sample_rate = self.silero.sample_rate();
+        let window = Silero::window_samples(sample_rate)?;
+        self.silero.score(&vec![0.0; window])?;
+        self.silero.reset(sample_rate)?;
+        Ok(json!({ "ready": true }))
+    }
+
+    fn handle(&mut self, req: &Request, audio: &[u8]) -> Result<Value, String> {
+        match req.action.as_deref().unwrap_or("push") {
+            "warmup" => self.warmup(),
+            "reset" => self.reset(req),
+            "push" => self.push(req, audio),
+            "flush" => self.drain(true),
+            other => Err(format!("Unsupported action: {other}")),
+        }
+    }
+}
+
+fn default_neg_threshold(threshold: f32) -> f32 {
+    (threshold - NEG_THRESHOLD_GAP).max(0.01)
+}
+
+fn validate_thresholds(thresholds: Thresholds) -> Result<Thresholds, String> {
+    if !(thresholds.threshold > 0.0 && thresholds.threshold < 1.0) {
+        return Err("threshold must be between 0 and 1".into());
+    }
+    if !(thresholds.neg_threshold > 0.0 && thresholds.neg_threshold <= thresholds.threshold) {
+        return Err("negThreshold must be above 0 and at most threshold".into());
+    }
+    if thresholds.min_speech_ms > 5_000 {
+        return Err("minSpeechMs must be between 0 and 5000".into());
+    }
+    if thresholds.hangover_ms > 10_000 {
+        return Err("hangoverMs must be between 0 and 10000".into());
+    }
+    Ok(thresholds)
+}
+
+fn parse_args() -> Result<Config, String> {
+    let args: Vec<String> = std::env::args().collect();
+
+    let mut model_path: Option<PathBuf> = None;
+    let mut threads = 1_usize;
+    let mut threshold = DEFAULT_THRESHOLD;
+    let mut neg_threshold: Option<f32> = None;
+    let mut min_speech_ms = DEFAULT_MIN_SPEECH_MS;
+    let mut hangover_ms = DEFAULT_HANGOVER_MS;
+    let mut serve = false;
+    let mut healthcheck = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        let flag = args[i].as_str();
+        let value = || {
+            args.get(i + 1)
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {flag}"))
+        };
+        match flag {
+            "--model" => {
+                model_path = Some(PathBuf::from(value()?));
+                i += 2;
+            }
+            "--threads" => {
+                threads = value()?
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=16).contains(n))
+                    .ok_or("--threads must be between 1 and 16")?;
+                i += 2;
+            }
+            "--threshold" => {
+                threshold = value()?
+                    .parse::<f32>()
+                    .map_err(|_| "--threshold must be a number")?;
+                i += 2;
+            }
+            "--neg-threshold" => {
+                neg_threshold = Some(
+                    value()?
+                        .parse::<f32>()
+                        .map_err(|_| "--neg-threshold must be a number")?,
+                );
+                i += 2;
+            }
+            "--min-speech-ms" => {
+                min_speech_ms = value()?
+                    .parse::<u32>()
+                    .map_err(|_| "--min-speech-ms must be a whole number")?;
+                i += 2;
+            }
+            "--hangover-ms" => {
+                hangover_ms = value()?
+                    .parse::<u32>()
+                    .map_err(|_| "--hangover-ms must be a whole number")?;
+                i += 2;
+            }
+            "--serve" => {
+                serve = true;
+                i += 1;
+            }
+            "--healthcheck" => {
+                healthcheck = true;
+                i += 1;
+            }
+            "--help" | "-h" => {
+                return Err(
+                    "usage: dingoflow-vad-worker --model <silero_vad.onnx> [--threads 1] [--threshold 0.5] [--neg-threshold 0.35] [--min-speech-ms 250] [--hangover-ms 300] [--healthcheck] --serve"
+                        .into(),
+                );
+            }
+            other => {
+                return Err(format!("Unsupported argument: {other}"));
+            }
+        }
+    }
+
+    if model_path.is_none() && !healthcheck {
+        return Err("--model is required unless --healthcheck is used".into());
+    }
+
+    let thresholds = validate_thresholds(Thresholds {
+        threshold,
+        neg_threshold: neg_threshold.unwrap_or_else(|| default_neg_threshold(threshold)),
+        min_speech_ms,
+        hangover_ms,
+    })
+    .map_err(|err| format!("invalid VAD settings: {err}"))?;
+
+    Ok(Config {
+        model_path,
+        threads,
+        thresholds,
+        serve,
+        healthcheck,
+    })
+}
+
+fn pcm16_to_f32(audio: &[u8]) -> impl Iterator<Item = f32> + '_ {
+    audio
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / i16::MAX as f32)
+}
+
+fn run_server(mut vad: Vad) -> Result<(), String> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut reader = stdin.lock();
+    let mut writer = stdout.lock();
+
+    while let Some(frame) = read_frame(&mut reader)? {
+        let response = match serde_json::from_slice::<Request>(&frame.json) {
+            Ok(req) => {
+                let request_id = req.id.clone().unwrap_or_else(|| "unknown".to_string());
+                match vad.handle(&req, &frame.audio) {
+                    Ok(result) => json!({ "id": request_id, "ok": true, "result": result }),
+                    Err(error) => json!({ "id": request_id, "ok": false, "error": error }),
+                }
+            }
+            Err(err) => json!({
+                "id": "unknown",
+                "ok": false,
+                "error": format!("invalid JSON request: {err}")
+            }),
+        };
+
+        write_response(&mut writer, &response)
+            .map_err(|err| format!("failed to write response: {err}"))?;
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cfg = match parse_args() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    if cfg.healthcheck {
+        println!("ok");
+        return;
+    }
+
+    if !cfg.serve {
+        eprintln!("--serve is required");
+        std::process::exit(1);
+    }
+
+    let Some(model_path) = cfg.model_path else {
+        eprintln!("--model is required");
+        std::process::exit(1);
+    };
+    let silero = match Silero::new(&model_path, cfg.threads) {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
+
+    let vad = Vad {
+        silero,
+        detector: Detector::new(cfg.thresholds),
+        defaults: cfg.thresholds,
+        pending: Vec::new(),
+        processed_samples: 0,
+    };
+
+    if let Err(err) = run_server(vad) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,90 @@
+use ort::session::Session;
+use ort::value::Tensor;
+use std::path::Path;
+
+// Silero VAD v5 (silero_vad.onnx). The model scores fixed windows of 512
+// samples at 16 kHz or 256 at 8 kHz, each prefixed with the tail of the
+// previous window, and carries a recurrent state between calls.
+const STATE_LEN: usize = 2 * 128;
+
+pub struct Silero {
+    session: Session,
+    sample_rate: u32,
+    state: Vec<f32>,
+    context: Vec<f32>,
+}
+
+impl Silero {
+    pub fn new(model_path: &Path, threads: usize) -> Result<Self, String> {
+        if !model_path.is_file() {
+            return Err(format!("VAD model not found: {}", model_path.display()));
+        }
+        let session = Session::builder()
+            .and_then(|builder| builder.with_intra_threads(threads))
+            .and_then(|builder| builder.commit_from_file(model_path))
+            .map_err(|err| format!("failed to load VAD model: {err}"))?;
+        let mut silero = Self {
+            session,
+            sample_rate: 16_000,
+            state: Vec::new(),
+            context: Vec::new(),
+        };
+        silero.reset(16_000)?;
+        Ok(silero)
+    }
+
+    pub fn window_samples(sample_rate: u32) -> Result<usize, String> {
+        match sample_rate {
+            16_000 => Ok(512),
+            8_000 => Ok(256),
+            other => Err(format!(
+                "unsupported VAD sample rate {other}; expected 8000 or 16000"
+            )),
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn reset(&mut self, sample_rate: u32) -> Result<(), String> {
+        let window = Self::window_samples(sample_rate)?;
+        self.sample_rate = sample_rate;
+        self.state = vec![0.0; STATE_LEN];
+        self.context = vec![0.0; window / 8];
+        Ok(())
+    }
+
+    // Speech probability for exactly one window of samples.
+    pub fn score(&mut self, window: &[f32]) -> Result<f32, String> {
+        let mut input = Vec::with_capacity(self.context.len() + window.len());
+        input.extend_from_slice(&self.context);
+        input.extend_from_slice(window);
+        let context_len = self.context.len();
+        self.context
+            .copy_from_slice(&input[input.len() - context_len..]);
+
+        let to_err = |err: ort::Error| format!("VAD inference failed: {err}");
+        let outputs = self
+            .session
+            .run(ort::inputs![
+                "input" => Tensor::from_array(([1, input.len()], input)).map_err(to_err)?,
+                "state" => Tensor::from_array(([2, 1, 128], self.state.clone())).map_err(to_err)?,
+                "sr" => Tensor::from_array(((), vec![self.sample_rate as i64])).map_err(to_err)?,
+            ])
+            .map_err(to_err)?;
+
+        let (_, probability) = outputs["output"]
+            .try_extract_tensor::<f32>()
+            .map_err(to_err)?;
+        let probability = probability.first().copied().unwrap_or(0.0);
+        let (_, state) = outputs["stateN"]
+            .try_extract_tensor::<f32>()
+            .map_err(to_err)?;
+        if state.len() != STATE_LEN {
+            return Err(format!("unexpected VAD state size {}", state.len()));
+        }
+        self.state.copy_from_slice(state);
+        Ok(probability)
+    }
+}
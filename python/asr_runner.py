@@ -11,6 +11,10 @@ from typing import Any, Dict
 import numpy as np
 from faster_whisper import WhisperModel
 
+FRAME_MAGIC = b"DFLW"
+FRAME_VERSION = 1
+FRAME_HEADER_BYTES = 13
+
 
 def parse_args() -> argparse.Namespace:
     parser = argparse.ArgumentParser(description="Run offline ASR with faster-whisper")
@@ -22,7 +26,7 @@ def parse_args() -> argparse.Namespace:
     parser.add_argument(
         "--framed-io",
         action="store_true",
-        help="Use framed stdin/stdout protocol (uint32 json_len, uint32 audio_len, json, audio)",
+        help="Use framed stdin/stdout protocol (b'DFLW', uint8 version, uint32 json_len, uint32 audio_len, json, audio)",
     )
     return parser.parse_args()
 
@@ -66,11 +70,19 @@ def read_exact(reader: Any, size: int) -> bytes:
 
 
 def read_framed_request(reader: Any) -> tuple[dict[str, Any] | None, bytes | None]:
-    header = read_exact(reader, 8)
+    header = read_exact(reader, FRAME_HEADER_BYTES)
     if not header:
         return None, None
 
-    json_len, audio_len = struct.unpack("<II", header)
+    # SystemExit gets past the per-request error handling: once the stream is
+    # out of step, nothing after it can be read either.
+    magic, version, json_len, audio_len = struct.unpack("<4sBII", header)
+    if magic != FRAME_MAGIC:
+        raise SystemExit(
+            f"bad frame magic {magic.hex()}, expected DFLW: the stream is out of sync or the peer is not a dingoflow client"
+        )
+    if version != FRAME_VERSION:
+        raise SystemExit(f"unsupported frame version {version}, expected {FRAME_VERSION}")
     if json_len == 0:
         raise ValueError("Invalid framed request: json_len must be > 0")
 
@@ -90,7 +102,7 @@ def read_framed_request(reader: Any) -> tuple[dict[str, Any] | None, bytes | Non
 
 def write_framed_response(writer: Any, response: dict[str, Any]) -> None:
     json_bytes = json.dumps(response, ensure_ascii=False).encode("utf-8")
-    writer.write(struct.pack("<I", len(json_bytes)))
+    writer.write(struct.pack("<4sBI", FRAME_MAGIC, FRAME_VERSION, len(json_bytes)))
     writer.write(json_bytes)
     writer.flush()
 
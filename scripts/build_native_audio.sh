@@ -4,7 +4,9 @@ set -euo pipefail
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
 
-cargo build --release --manifest-path "${ROOT_DIR}/native/audio_loop/Cargo.toml"
+# e.g. DINGOFLOW_AUDIO_FEATURES=opus to enable `--encode opus` (needs libopus).
+cargo build --release --manifest-path "${ROOT_DIR}/native/audio_loop/Cargo.toml" \
+  ${DINGOFLOW_AUDIO_FEATURES:+--features "${DINGOFLOW_AUDIO_FEATURES}"}
 
 echo "Native audio binary built at:"
 echo "  ${ROOT_DIR}/native/audio_loop/target/release/dingoflow-audio-loop"
@@ -0,0 +1,11 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+
+cargo build --release --features dictate --bin dingoflow-dictate --manifest-path "${ROOT_DIR}/native/parakeet_worker/Cargo.toml"
+
+echo "Native dictation CLI built at:"
+echo "  ${ROOT_DIR}/native/parakeet_worker/target/release/dingoflow-dictate"
+echo "Run it with --model <parakeet model dir> (or DINGOFLOW_ASR_MODEL_PATH); add --inject <text injector> to type into the focused app."
@@ -0,0 +1,18 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+
+# tonic-build compiles native/proto with protoc (brew install protobuf).
+if ! command -v protoc >/dev/null 2>&1 && [[ -z "${PROTOC:-}" ]]; then
+  echo "protoc not found; install protobuf or set PROTOC" >&2
+  exit 1
+fi
+
+cargo build --release --manifest-path "${ROOT_DIR}/native/grpc_gateway/Cargo.toml"
+
+echo "Native gRPC gateway built at:"
+echo "  ${ROOT_DIR}/native/grpc_gateway/target/release/dingoflow-grpc-gateway"
+echo "Serve a worker with e.g.:"
+echo "  dingoflow-grpc-gateway --worker-bin native/parakeet_worker/target/release/dingoflow-parakeet-worker --worker-arg --model --worker-arg models/parakeet-tdt-0.6b-v3-onnx"
@@ -0,0 +1,11 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+
+cargo build --release --manifest-path "${ROOT_DIR}/native/llm_worker/Cargo.toml"
+
+echo "Native LLM post-processing worker built at:"
+echo "  ${ROOT_DIR}/native/llm_worker/target/release/dingoflow-llm-worker"
+echo "It drives llama.cpp's llama-server (brew install llama.cpp) with a GGUF model."
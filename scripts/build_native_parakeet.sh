@@ -4,7 +4,9 @@ set -euo pipefail
 SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
 ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
 
-cargo build --release --manifest-path "${ROOT_DIR}/native/parakeet_worker/Cargo.toml"
+# e.g. DINGOFLOW_PARAKEET_FEATURES=mic to enable `--mic` (live capture in the worker).
+cargo build --release --manifest-path "${ROOT_DIR}/native/parakeet_worker/Cargo.toml" \
+  ${DINGOFLOW_PARAKEET_FEATURES:+--features "${DINGOFLOW_PARAKEET_FEATURES}"}
 
 echo "Native Parakeet binary built at:"
 echo "  ${ROOT_DIR}/native/parakeet_worker/target/release/dingoflow-parakeet-worker"
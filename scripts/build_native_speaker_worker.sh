@@ -0,0 +1,10 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+
+cargo build --release --manifest-path "${ROOT_DIR}/native/speaker_worker/Cargo.toml"
+
+echo "Native speaker ID worker built at:"
+echo "  ${ROOT_DIR}/native/speaker_worker/target/release/dingoflow-speaker-worker"
@@ -0,0 +1,10 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+
+cargo build --release --manifest-path "${ROOT_DIR}/native/asr/Cargo.toml"
+
+echo "Native unified ASR binary (--backend whisper|parakeet) built at:"
+echo "  ${ROOT_DIR}/native/asr/target/release/dingoflow-asr"
@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+AUDIO_BIN="${DINGOFLOW_NATIVE_AUDIO_BIN:-${ROOT_DIR}/native/audio_loop/target/release/dingoflow-audio-loop}"
+
+if [[ ! -x "${AUDIO_BIN}" ]]; then
+  echo "Native audio binary not found at ${AUDIO_BIN}; run ./scripts/build_native_audio.sh first."
+  exit 1
+fi
+
+# Captures two seconds and prints a JSON verdict. On macOS the first run
+# triggers the microphone permission prompt for the terminal app.
+if "${AUDIO_BIN}" --self-test "$@"; then
+  echo "Microphone OK."
+else
+  echo "Microphone check failed. On macOS, allow microphone access for your terminal in"
+  echo "System Settings > Privacy & Security > Microphone, then run this again."
+  exit 1
+fi
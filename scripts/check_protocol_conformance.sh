@@ -0,0 +1,25 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+MANIFEST="${ROOT_DIR}/native/protocol_conformance/Cargo.toml"
+
+# Without arguments, run the harness's own tests against the reference worker (plus any
+# workers listed in DINGOFLOW_CONFORMANCE_WORKERS, one command per line).
+if [[ $# -eq 0 ]]; then
+  cargo test --manifest-path "${MANIFEST}"
+  exit 0
+fi
+
+# Otherwise run the suite against one worker:
+#   ./scripts/check_protocol_conformance.sh native/vad_worker/target/release/dingoflow-vad-worker --serve --model models/silero-vad/silero_vad.onnx
+# Harness flags (--probe-action, --timeout-ms, --case, --json) go in DINGOFLOW_CONFORMANCE_FLAGS.
+cargo build --release --manifest-path "${MANIFEST}"
+
+WORKER_BIN="$1"
+shift
+
+# shellcheck disable=SC2086
+"${ROOT_DIR}/native/protocol_conformance/target/release/dingoflow-protocol-conformance" \
+  --worker-bin "${WORKER_BIN}" ${DINGOFLOW_CONFORMANCE_FLAGS:-} -- "$@"
@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+MODEL_DIR="${ROOT_DIR}/models/piper"
+# Path inside rhasspy/piper-voices, e.g. en/en_GB/alba/medium/en_GB-alba-medium
+VOICE="${DINGOFLOW_PIPER_VOICE:-en/en_US/lessac/medium/en_US-lessac-medium}"
+BASE_URL="https://huggingface.co/rhasspy/piper-voices/resolve/main"
+NAME="$(basename "${VOICE}")"
+
+mkdir -p "${MODEL_DIR}"
+
+for ext in onnx onnx.json; do
+  curl -fL --retry 3 -o "${MODEL_DIR}/${NAME}.${ext}.part" "${BASE_URL}/${VOICE}.${ext}"
+  mv "${MODEL_DIR}/${NAME}.${ext}.part" "${MODEL_DIR}/${NAME}.${ext}"
+done
+
+echo "Downloaded Piper voice to:"
+echo "  ${MODEL_DIR}/${NAME}.onnx"
+echo "The TTS worker also needs espeak-ng on PATH (brew install espeak-ng)."
@@ -0,0 +1,15 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+MODEL_DIR="${ROOT_DIR}/models/silero-vad"
+MODEL_URL="${DINGOFLOW_SILERO_VAD_URL:-https://github.com/snakers4/silero-vad/raw/master/src/silero_vad/data/silero_vad.onnx}"
+
+mkdir -p "${MODEL_DIR}"
+
+curl -fL --retry 3 -o "${MODEL_DIR}/silero_vad.onnx.part" "${MODEL_URL}"
+mv "${MODEL_DIR}/silero_vad.onnx.part" "${MODEL_DIR}/silero_vad.onnx"
+
+echo "Downloaded Silero VAD ONNX model to:"
+echo "  ${MODEL_DIR}/silero_vad.onnx"
@@ -0,0 +1,16 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+MODEL_DIR="${ROOT_DIR}/models/speaker"
+# WeSpeaker ResNet34 trained on VoxCeleb, as packaged by sherpa-onnx.
+MODEL_URL="${DINGOFLOW_SPEAKER_MODEL_URL:-https://github.com/k2-fsa/sherpa-onnx/releases/download/speaker-recongition-models/wespeaker_en_voxceleb_resnet34.onnx}"
+
+mkdir -p "${MODEL_DIR}"
+
+curl -fL --retry 3 -o "${MODEL_DIR}/speaker_embedding.onnx.part" "${MODEL_URL}"
+mv "${MODEL_DIR}/speaker_embedding.onnx.part" "${MODEL_DIR}/speaker_embedding.onnx"
+
+echo "Downloaded speaker embedding model to:"
+echo "  ${MODEL_DIR}/speaker_embedding.onnx"
@@ -0,0 +1,24 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+MODEL_DIR="${ROOT_DIR}/models/punctuation"
+MODEL_ID="${DINGOFLOW_PUNCTUATION_MODEL:-felflare/bert-restore-punctuation}"
+PY_BIN="${DINGOFLOW_PYTHON_BIN:-python3}"
+
+if ! "${PY_BIN}" - <<'PY' >/dev/null 2>&1
+import optimum.exporters.onnx  # noqa: F401
+PY
+then
+  echo "Installing Python export deps (optimum[exporters])..."
+  "${PY_BIN}" -m pip --version >/dev/null 2>&1 || "${PY_BIN}" -m ensurepip --upgrade >/dev/null 2>&1 || true
+  "${PY_BIN}" -m pip install --quiet --upgrade "optimum[exporters]"
+fi
+
+# Writes model.onnx, tokenizer.json and config.json, which is everything the
+# punctuation worker reads.
+"${PY_BIN}" -m optimum.exporters.onnx --model "${MODEL_ID}" --task token-classification "${MODEL_DIR}"
+
+echo "Exported punctuation model to:"
+echo "  ${MODEL_DIR}"
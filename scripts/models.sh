@@ -0,0 +1,14 @@
+#!/usr/bin/env bash
+set -euo pipefail
+
+# Runs dingoflow-models against ./models, building it on first use.
+# Examples: ./scripts/models.sh list, ./scripts/models.sh download silero-vad
+SCRIPT_DIR="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
+ROOT_DIR="$(cd "${SCRIPT_DIR}/.." && pwd)"
+BIN="${ROOT_DIR}/native/models/target/release/dingoflow-models"
+
+if [[ ! -x "${BIN}" ]]; then
+  cargo build --quiet --release --manifest-path "${ROOT_DIR}/native/models/Cargo.toml"
+fi
+
+exec "${BIN}" --models-dir "${ROOT_DIR}/models" "$@"
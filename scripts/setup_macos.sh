@@ -22,9 +22,10 @@ cat <<MSG
 Setup complete.
 
 Next steps:
-1) Download local ASR and formatter models (see README.md). For native Parakeet default, run ./scripts/download_parakeet_tdt_onnx.sh
+1) Download local ASR and formatter models (see README.md). For native Parakeet default, run ./scripts/download_parakeet_tdt_onnx.sh; for the native VAD worker, ./scripts/download_silero_vad.sh; for spoken feedback, ./scripts/download_piper_voice.sh; for punctuation restore, ./scripts/export_punctuation_onnx.sh; for speaker ID, ./scripts/download_speaker_model.sh. ./scripts/models.sh list shows what is installed
 2) export DINGOFLOW_PYTHON_BIN="$VENV_DIR/bin/python"
-3) Optional native builds: ./scripts/build_native_audio.sh ./scripts/build_native_asr.sh ./scripts/build_native_parakeet.sh ./scripts/build_native_injector.sh
-4) npm install && npm run dev
+3) Optional native builds: ./scripts/build_native_audio.sh ./scripts/build_native_asr.sh ./scripts/build_native_parakeet.sh ./scripts/build_native_injector.sh ./scripts/build_native_supervisor.sh ./scripts/build_native_dictate.sh ./scripts/build_native_injector_worker.sh ./scripts/build_native_hotkey_worker.sh ./scripts/build_native_vad_worker.sh ./scripts/build_native_tts_worker.sh ./scripts/build_native_punctuate_worker.sh ./scripts/build_native_llm_worker.sh ./scripts/build_native_speaker_worker.sh ./scripts/build_native_models.sh ./scripts/build_native_grpc_gateway.sh ./scripts/build_native_unified_asr.sh
+4) With the native audio build, confirm microphone access: ./scripts/check_microphone.sh
+5) npm install && npm run dev
 
 MSG
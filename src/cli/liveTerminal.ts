@@ -1,7 +1,7 @@
 import readline from 'node:readline';
 import { resolveConfig, validateConfig } from '../config';
 import { DingoFlowApp } from '../core/DingoFlowApp';
-import { RustNativeRecorder } from '../services/capture/RustNativeRecorder';
+import { RustNativeRecorder, calibrateNativeMicrophone } from '../services/capture/RustNativeRecorder';
 import { FasterWhisperClient } from '../services/asr/FasterWhisperClient';
 import { CloudAsrClient } from '../services/asr/CloudAsrClient';
 import { FormatMode } from '../types';
@@ -36,6 +36,7 @@ const printHelp = (): void => {
   process.stdout.write('  /mode clean         Set formatter mode\n');
   process.stdout.write('  /mode rewrite       Set formatter mode\n');
   process.stdout.write('  /status             Print current state\n');
+  process.stdout.write('  /calibrate          Measure mic levels and suggest a gain\n');
   process.stdout.write('  /quit               Exit\n');
   process.stdout.write('\n');
 };
@@ -117,6 +118,10 @@ const main = async (): Promise<void> => {
     }
   });
 
+  app.on('recorderWarning', (message) => {
+    process.stderr.write(`\n[warning] ${message}\n`);
+  });
+
   app.on('dictationCompleted', (result) => {
     process.stdout.write('\n\n--- dictation completed ---\n');
     process.stdout.write(`raw: ${result.rawTranscript}\n`);
@@ -178,6 +183,28 @@ const main = async (): Promise<void> => {
       return;
     }
 
+    if (input === '/calibrate') {
+      if (recording) {
+        process.stdout.write('Stop dictation before calibrating.\n');
+        return;
+      }
+      queue(async () => {
+        process.stdout.write('[calibrate] Stay quiet for a moment, then speak normally for a few seconds...\n');
+        const report = await calibrateNativeMicrophone(config.nativeAudioBin);
+        process.stdout.write(
+          `[calibrate] noise floor ${report.noiseFloorDbfs} dBFS, speech ${report.speechDbfs} dBFS, peak ${report.peakDbfs} dBFS\n`
+        );
+        if (report.suggestedGain === null) {
+          process.stdout.write('[calibrate] No speech detected; try again a little closer to the mic.\n');
+          return;
+        }
+        process.stdout.write(
+          `[calibrate] Suggested gain ${report.suggestedGain}; set DINGOFLOW_NATIVE_AUDIO_GAIN=${report.suggestedGain} to keep it.\n`
+        );
+      });
+      return;
+    }
+
     if (input.startsWith('/mode ')) {
       const mode = input.slice('/mode '.length).trim();
       if (!isMode(mode)) {
@@ -190,7 +217,7 @@ const main = async (): Promise<void> => {
     }
 
     if (input.length > 0 && input !== '/help') {
-      process.stdout.write('Unknown command. Use /help, /status, /mode, or /quit.\n');
+      process.stdout.write('Unknown command. Use /help, /status, /mode, /calibrate, or /quit.\n');
       return;
     }
 
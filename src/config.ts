@@ -10,6 +10,15 @@ const parseIntOrDefault = (value: string | undefined, fallback: number): number
   return Number.isNaN(parsed) ? fallback : parsed;
 };
 
+const parseFloatOrDefault = (value: string | undefined, fallback: number): number => {
+  if (!value) {
+    return fallback;
+  }
+
+  const parsed = Number.parseFloat(value);
+  return Number.isFinite(parsed) ? parsed : fallback;
+};
+
 const parseBoolOrDefault = (value: string | undefined, fallback: boolean): boolean => {
   if (value === undefined) {
     return fallback;
@@ -303,6 +312,8 @@ export const resolveConfig = (): AppConfig => {
     nativeVadEnabled: parseBoolOrDefault(process.env.DINGOFLOW_NATIVE_VAD_ENABLED, true),
     nativeVadMode: resolveNativeVadMode(process.env.DINGOFLOW_NATIVE_VAD_MODE),
     nativeVadFrameMs: parseIntOrDefault(process.env.DINGOFLOW_NATIVE_VAD_FRAME_MS, 20),
+    nativeAudioGain: parseFloatOrDefault(process.env.DINGOFLOW_NATIVE_AUDIO_GAIN, 1),
+    nativeAudioDevice: process.env.DINGOFLOW_NATIVE_AUDIO_DEVICE ?? '',
     parakeetStreamContextLeft: parseIntOrDefault(process.env.DINGOFLOW_PARAKEET_CTX_LEFT, 64),
     parakeetStreamContextRight: parseIntOrDefault(process.env.DINGOFLOW_PARAKEET_CTX_RIGHT, 8),
     parakeetStreamDepth: parseIntOrDefault(process.env.DINGOFLOW_PARAKEET_STREAM_DEPTH, 1),
@@ -439,6 +450,10 @@ export const validateConfig = (config: AppConfig): string[] => {
     errors.push('DINGOFLOW_NATIVE_VAD_FRAME_MS must be one of: 10, 20, 30.');
   }
 
+  if (!Number.isFinite(config.nativeAudioGain) || config.nativeAudioGain < 0 || config.nativeAudioGain > 8) {
+    errors.push('DINGOFLOW_NATIVE_AUDIO_GAIN must be between 0 and 8.');
+  }
+
   if (
     !(
       config.minAsrWindowMs <= config.normalAsrWindowMs &&
@@ -99,6 +99,7 @@ export declare interface DingoFlowApp {
   on(event: 'modeChanged', listener: (mode: FormatMode) => void): this;
   on(event: 'dictationCompleted', listener: (result: DictationResult) => void): this;
   on(event: 'livePreviewChanged', listener: (preview: LivePreviewState) => void): this;
+  on(event: 'recorderWarning', listener: (message: string) => void): this;
 }
 
 const clamp = (value: number, min: number, max: number): number => Math.max(min, Math.min(max, value));
@@ -301,6 +302,9 @@ export class DingoFlowApp extends EventEmitter {
           for (const gatedChunk of gateResult.buffers) {
             this.enqueueAudioChunk(gatedChunk);
           }
+        },
+        onWarning: (message) => {
+          this.emit('recorderWarning', message);
         }
       });
 
@@ -1,6 +1,7 @@
 export interface RealtimeStreamOptions {
   chunkDurationMs: number;
   onChunk: (chunk: Buffer) => void;
+  onWarning?: (message: string) => void;
 }
 
 export interface AudioRecorder {
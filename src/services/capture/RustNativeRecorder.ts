@@ -1,6 +1,7 @@
 import fs from 'node:fs';
 import { ChildProcess, spawn } from 'node:child_process';
 import { StructuredLogger } from '../../logging/StructuredLogger';
+import { runCommand } from '../process/runCommand';
 import { AudioRecorder, RealtimeStreamOptions } from './AudioRecorder';
 import { AppConfig } from '../../types';
 
@@ -8,6 +9,28 @@ const START_TIMEOUT_MS = 3000;
 const AUDIO_SAMPLE_RATE = 16000;
 const BYTES_PER_SAMPLE = 2;
 
+// One JSON object per stderr line under --json-events.
+interface NativeStatusEvent {
+  event: string;
+  source?: string;
+  kind?: string;
+  message?: string;
+}
+
+const parseStatusEvent = (line: string): NativeStatusEvent | undefined => {
+  const trimmed = line.trim();
+  if (!trimmed.startsWith('{')) {
+    return undefined;
+  }
+
+  try {
+    const parsed = JSON.parse(trimmed) as NativeStatusEvent;
+    return typeof parsed.event === 'string' ? parsed : undefined;
+  } catch {
+    return undefined;
+  }
+};
+
 const normalizeNativeError = (raw: string): string => {
   const detail = raw.trim();
 
@@ -26,6 +49,44 @@ const normalizeNativeError = (raw: string): string => {
   return 'Native recorder failed.';
 };
 
+export interface MicrophoneCalibration {
+  durationMs: number;
+  noiseFloorDbfs: number;
+  speechDbfs: number;
+  peakDbfs: number;
+  snrDb: number;
+  speechDetected: boolean;
+  suggestedGain: number | null;
+}
+
+// Runs `--calibrate` against the default microphone. The user should stay
+// quiet briefly and then speak; `savePath` keeps the report on disk too.
+export const calibrateNativeMicrophone = async (
+  binaryPath: string,
+  durationMs = 5000,
+  savePath?: string
+): Promise<MicrophoneCalibration> => {
+  const args = ['--calibrate', '--calibrate-ms', String(durationMs)];
+  if (savePath) {
+    args.push('--calibrate-save', savePath);
+  }
+
+  let stdout: string;
+  try {
+    ({ stdout } = await runCommand(binaryPath, args, { timeoutMs: durationMs + START_TIMEOUT_MS }));
+  } catch (error) {
+    const detail = error instanceof Error ? error.message : String(error);
+    throw new Error(normalizeNativeError(detail));
+  }
+
+  const line = stdout.trim().split('\n').pop() ?? '';
+  const report = JSON.parse(line) as MicrophoneCalibration & { event?: string };
+  if (report.event !== 'calibration') {
+    throw new Error(`Unexpected calibration output: ${line}`);
+  }
+  return report;
+};
+
 export class RustNativeRecorder implements AudioRecorder {
   private process: ChildProcess | undefined;
   private pendingChunks: Buffer[] = [];
@@ -38,7 +99,14 @@ export class RustNativeRecorder implements AudioRecorder {
     private readonly binaryPath: string,
     private readonly config?: Pick<
       AppConfig,
-      'nativeVadEnabled' | 'nativeVadMode' | 'nativeVadFrameMs' | 'speechOnsetMs' | 'speechHangoverMs' | 'speechPrerollMs'
+      | 'nativeVadEnabled'
+      | 'nativeVadMode'
+      | 'nativeVadFrameMs'
+      | 'nativeAudioGain'
+      | 'nativeAudioDevice'
+      | 'speechOnsetMs'
+      | 'speechHangoverMs'
+      | 'speechPrerollMs'
     >,
     private readonly logger?: StructuredLogger
   ) {}
@@ -71,9 +139,16 @@ export class RustNativeRecorder implements AudioRecorder {
       Math.floor((AUDIO_SAMPLE_RATE * BYTES_PER_SAMPLE * options.chunkDurationMs) / 1000)
     );
 
-    const args = ['--sample-rate', String(AUDIO_SAMPLE_RATE)];
+    const args = ['--json-events', '--sample-rate', String(AUDIO_SAMPLE_RATE)];
+    if (this.config?.nativeAudioDevice) {
+      args.push('--device', this.config.nativeAudioDevice);
+    }
+    if (this.config?.nativeAudioGain !== undefined && this.config.nativeAudioGain !== 1) {
+      args.push('--gain', String(this.config.nativeAudioGain));
+    }
     if (this.config?.nativeVadEnabled !== false) {
       args.push(
+        '--vad',
         '--vad-mode',
         this.config?.nativeVadMode ?? 'very-aggressive',
         '--vad-frame-ms',
@@ -92,6 +167,8 @@ export class RustNativeRecorder implements AudioRecorder {
     this.process = child;
 
     let stderrLog = '';
+    let errorLog = '';
+    let stderrLine = '';
     let ready = false;
 
     child.on('close', () => {
@@ -107,8 +184,19 @@ export class RustNativeRecorder implements AudioRecorder {
     child.stderr.on('data', (chunk) => {
       const text = chunk.toString();
       stderrLog += text;
-      if (text.includes('READY')) {
-        ready = true;
+
+      stderrLine += text;
+      const lines = stderrLine.split('\n');
+      stderrLine = lines.pop() ?? '';
+      for (const line of lines) {
+        const event = parseStatusEvent(line);
+        if (event?.event === 'ready') {
+          ready = true;
+        } else if (event?.event === 'error' && event.message) {
+          errorLog += `${event.message}\n`;
+        } else if (event) {
+          this.handleStatusEvent(event, options.onWarning);
+        }
       }
     });
 
@@ -121,7 +209,7 @@ export class RustNativeRecorder implements AudioRecorder {
 
         settled = true;
         child.kill('SIGKILL');
-        reject(new Error(normalizeNativeError(stderrLog)));
+        reject(new Error(normalizeNativeError(errorLog || stderrLog)));
       }, START_TIMEOUT_MS);
 
       const finish = (callback: () => void): void => {
@@ -141,7 +229,7 @@ export class RustNativeRecorder implements AudioRecorder {
       child.once('spawn', () => {
         const pollReady = (): void => {
           if (child.exitCode !== null) {
-            finish(() => reject(new Error(normalizeNativeError(stderrLog))));
+            finish(() => reject(new Error(normalizeNativeError(errorLog || stderrLog))));
             return;
           }
 
@@ -158,7 +246,7 @@ export class RustNativeRecorder implements AudioRecorder {
 
       child.once('close', (code) => {
         if (!settled) {
-          finish(() => reject(new Error(normalizeNativeError(`${stderrLog}\nexit code=${code}`))));
+          finish(() => reject(new Error(normalizeNativeError(`${errorLog || stderrLog}\nexit code=${code}`))));
         }
       });
     });
@@ -208,6 +296,26 @@ export class RustNativeRecorder implements AudioRecorder {
     return this.config?.nativeVadEnabled !== false;
   }
 
+  // Input diagnostics (clipping, digital silence) are passed up so the user
+  // sees why a transcript came back empty instead of just getting nothing.
+  private handleStatusEvent(
+    event: NativeStatusEvent,
+    onWarning: ((message: string) => void) | undefined
+  ): void {
+    if (event.event !== 'signalWarning' || !event.message) {
+      return;
+    }
+
+    const { kind, message } = event;
+    this.logger?.warn('Native recorder input warning', { kind, message });
+    try {
+      onWarning?.(message);
+    } catch (error) {
+      const detail = error instanceof Error ? error.message : String(error);
+      this.logger?.warn('Native recorder warning callback failed', { detail });
+    }
+  }
+
   private handleAudioData(chunk: Buffer): void {
     if (!this.onChunk || chunk.length === 0 || this.chunkByteSize <= 0) {
       return;
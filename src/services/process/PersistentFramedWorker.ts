@@ -22,8 +22,12 @@ export interface PersistentFramedWorkerOptions {
   logger?: StructuredLogger;
 }
 
-const FRAME_HEADER_BYTES = 8; // uint32 jsonLen + uint32 binaryLen
-const RESPONSE_HEADER_BYTES = 4; // uint32 jsonLen
+// Frames open with a magic and protocol version both ways, so a desynchronized
+// pipe or a worker from another build fails on its first bytes.
+const FRAME_MAGIC = Buffer.from('DFLW', 'ascii');
+const FRAME_VERSION = 1;
+const FRAME_HEADER_BYTES = 13; // magic + uint8 version + uint32 jsonLen + uint32 binaryLen
+const RESPONSE_HEADER_BYTES = 9; // magic + uint8 version + uint32 jsonLen
 const MAX_RESPONSE_JSON_BYTES = 8 * 1024 * 1024;
 
 export class PersistentFramedWorker {
@@ -74,8 +78,10 @@ export class PersistentFramedWorker {
     const jsonBytes = Buffer.from(jsonPayload, 'utf8');
     const audioBytes = binaryData && binaryData.length > 0 ? binaryData : Buffer.alloc(0);
     const header = Buffer.allocUnsafe(FRAME_HEADER_BYTES);
-    header.writeUInt32LE(jsonBytes.length, 0);
-    header.writeUInt32LE(audioBytes.length, 4);
+    FRAME_MAGIC.copy(header, 0);
+    header.writeUInt8(FRAME_VERSION, 4);
+    header.writeUInt32LE(jsonBytes.length, 5);
+    header.writeUInt32LE(audioBytes.length, 9);
 
     return new Promise<TResponse>((resolve, reject) => {
       const timeoutHandle = setTimeout(() => {
@@ -246,7 +252,21 @@ export class PersistentFramedWorker {
         : Buffer.concat([this.stdoutBuffer, safeChunk]);
 
     while (this.stdoutBuffer.length >= RESPONSE_HEADER_BYTES) {
-      const jsonLength = this.stdoutBuffer.readUInt32LE(0);
+      const magic = this.stdoutBuffer.subarray(0, FRAME_MAGIC.length);
+      const version = this.stdoutBuffer.readUInt8(FRAME_MAGIC.length);
+      if (!magic.equals(FRAME_MAGIC) || version !== FRAME_VERSION) {
+        const problem = magic.equals(FRAME_MAGIC)
+          ? `unsupported frame version ${version}, expected ${FRAME_VERSION}`
+          : `bad frame magic ${magic.toString('hex')}: output is out of sync or not a dingoflow worker`;
+        this.options.logger?.error(`${this.options.name} framed worker ${problem}`);
+        // Nothing after this point can be trusted; restart on the next request.
+        this.stdoutBuffer = Buffer.alloc(0);
+        this.rejectAllPending(new Error(`${this.options.name} worker ${problem}`));
+        this.child?.kill('SIGTERM');
+        return;
+      }
+
+      const jsonLength = this.stdoutBuffer.readUInt32LE(5);
       if (jsonLength <= 0 || jsonLength > MAX_RESPONSE_JSON_BYTES) {
         this.options.logger?.warn(`${this.options.name} framed worker produced invalid response length`, {
           jsonLength
@@ -82,6 +82,8 @@ export interface AppConfig {
   nativeVadEnabled: boolean;
   nativeVadMode: 'quality' | 'low-bitrate' | 'aggressive' | 'very-aggressive';
   nativeVadFrameMs: number;
+  nativeAudioGain: number;
+  nativeAudioDevice: string;
   parakeetStreamContextLeft: number;
   parakeetStreamContextRight: number;
   parakeetStreamDepth: number;
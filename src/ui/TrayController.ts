@@ -37,6 +37,10 @@ export class TrayController {
       this.notify('Dictation complete', result.formattedText.slice(0, 100));
     });
 
+    this.app.on('recorderWarning', (message) => {
+      this.notify('Microphone input', message);
+    });
+
     this.refreshMenu(this.app.getState(), this.app.getMode());
   }
 
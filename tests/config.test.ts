@@ -44,6 +44,8 @@ const clearEnv = (): void => {
   delete process.env.DINGOFLOW_NATIVE_VAD_ENABLED;
   delete process.env.DINGOFLOW_NATIVE_VAD_MODE;
   delete process.env.DINGOFLOW_NATIVE_VAD_FRAME_MS;
+  delete process.env.DINGOFLOW_NATIVE_AUDIO_GAIN;
+  delete process.env.DINGOFLOW_NATIVE_AUDIO_DEVICE;
   delete process.env.DINGOFLOW_PARAKEET_CTX_LEFT;
   delete process.env.DINGOFLOW_PARAKEET_CTX_RIGHT;
   delete process.env.DINGOFLOW_PARAKEET_STREAM_DEPTH;
@@ -85,6 +87,8 @@ describe('resolveConfig', () => {
     expect(config.nativeVadEnabled).toBe(true);
     expect(config.nativeVadMode).toBe('very-aggressive');
     expect(config.nativeVadFrameMs).toBe(20);
+    expect(config.nativeAudioGain).toBe(1);
+    expect(config.nativeAudioDevice).toBe('');
   });
 
   it('switches ASR defaults when parakeet backend is selected', () => {
@@ -159,6 +163,15 @@ describe('resolveConfig', () => {
     const config = resolveConfig();
     expect(config.parakeetFinalPass).toBe(false);
   });
+
+  it('falls back to the default audio gain when it is empty or not a number', () => {
+    process.env.DINGOFLOW_NATIVE_AUDIO_GAIN = '';
+    expect(resolveConfig().nativeAudioGain).toBe(1);
+    process.env.DINGOFLOW_NATIVE_AUDIO_GAIN = 'loud';
+    expect(resolveConfig().nativeAudioGain).toBe(1);
+    process.env.DINGOFLOW_NATIVE_AUDIO_GAIN = '2.5';
+    expect(resolveConfig().nativeAudioGain).toBe(2.5);
+  });
 });
 
 describe('validateConfig', () => {
@@ -202,6 +215,7 @@ describe('validateConfig', () => {
     process.env.DINGOFLOW_SPEECH_PREROLL_MS = '2001';
     process.env.DINGOFLOW_SPEECH_NOISE_MARGIN_DB = '2';
     process.env.DINGOFLOW_NATIVE_VAD_FRAME_MS = '25';
+    process.env.DINGOFLOW_NATIVE_AUDIO_GAIN = '9';
 
     const config = resolveConfig();
     const errors = validateConfig({
@@ -236,6 +250,7 @@ describe('validateConfig', () => {
       'DINGOFLOW_NATIVE_VAD_MODE must be one of: quality, low-bitrate, aggressive, very-aggressive.'
     );
     expect(errors).toContain('DINGOFLOW_NATIVE_VAD_FRAME_MS must be one of: 10, 20, 30.');
+    expect(errors).toContain('DINGOFLOW_NATIVE_AUDIO_GAIN must be between 0 and 8.');
   });
 
   it('flags unsupported ASR backend values', () => {